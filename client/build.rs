@@ -0,0 +1,641 @@
+//! Generate gRPC API types and clients from the protobuf sources in `proto`.
+//!
+//! Oasis Core gRPC services use the CBOR codec, so the protobuf definitions
+//! only serve as the interface description. Each `proto/<name>.proto` file
+//! which defines messages or services is turned into `<name>.rs` in the build
+//! output directory, containing:
+//!
+//! * a serde-serializable struct for each message,
+//! * a gRPC method descriptor for each RPC and
+//! * a client for each service.
+//!
+//! Only the subset of the protobuf language used by the sources is supported.
+//! The services described by the sources are checked against the Go services
+//! by `TestClientProtos` in `go/common/grpc`.
+use std::{
+    collections::HashSet,
+    env,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Directory containing the protobuf sources.
+const PROTO_DIR: &str = "proto";
+/// Package prefix of all gRPC service names.
+const SERVICE_PREFIX: &str = "oasis-core";
+
+/// Types which are not generated, together with the Rust types they map to.
+const EXTERNAL_TYPES: &[(&str, &str)] = &[
+    ("google.protobuf.Empty", "()"),
+    ("google.protobuf.BoolValue", "bool"),
+    ("google.protobuf.UInt64Value", "u64"),
+    ("google.protobuf.StringValue", "String"),
+    ("google.protobuf.BytesValue", "::serde_bytes::ByteBuf"),
+    (
+        "oasis_core.common.RuntimeId",
+        "::oasis_core_runtime::common::runtime::RuntimeId",
+    ),
+    (
+        "oasis_core.common.Hash",
+        "::oasis_core_runtime::common::crypto::hash::Hash",
+    ),
+    (
+        "oasis_core.common.Block",
+        "::oasis_core_runtime::common::roothash::Block",
+    ),
+    (
+        "oasis_core.common.AnnotatedBlock",
+        "::oasis_core_runtime::common::roothash::AnnotatedBlock",
+    ),
+    (
+        "oasis_core.common.TxnBatch",
+        "::oasis_core_runtime::transaction::types::TxnBatch",
+    ),
+];
+
+/// Message option marking messages which are encoded as their only field.
+const TRANSPARENT_OPTION: &str = "(oasis_core.common.transparent)";
+
+/// Rust keywords which can not be used as plain field names.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static",
+    "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+    "where", "while", "yield",
+];
+
+fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR must be set"));
+
+    println!("cargo:rerun-if-changed={}", PROTO_DIR);
+    let mut paths: Vec<PathBuf> = fs::read_dir(PROTO_DIR)
+        .expect("failed to read protobuf sources")
+        .map(|entry| entry.expect("failed to read protobuf sources").path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "proto"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        println!("cargo:rerun-if-changed={}", path.display());
+
+        let source = fs::read_to_string(&path).expect("failed to read protobuf source");
+        let mut file = match Parser::new(&source).parse_file() {
+            Ok(file) => file,
+            Err(err) => panic!("{}: {}", path.display(), err),
+        };
+        // Messages mapped to existing types only declare them.
+        let package = file.package.clone();
+        file.messages.retain(|message| {
+            let name = format!("{}.{}", package, message.name);
+            !EXTERNAL_TYPES.iter().any(|(external, _)| *external == name)
+        });
+        if file.messages.is_empty() && file.services.is_empty() {
+            continue;
+        }
+
+        let output = match generate(&file) {
+            Ok(output) => output,
+            Err(err) => panic!("{}: {}", path.display(), err),
+        };
+        let name = path.file_stem().unwrap().to_str().unwrap();
+        fs::write(out_dir.join(Path::new(name).with_extension("rs")), output)
+            .expect("failed to write generated code");
+    }
+}
+
+/// A parsed protobuf file.
+#[derive(Default)]
+struct File {
+    package: String,
+    messages: Vec<Message>,
+    services: Vec<Service>,
+}
+
+struct Message {
+    doc: Vec<String>,
+    name: String,
+    transparent: bool,
+    fields: Vec<Field>,
+}
+
+struct Field {
+    doc: Vec<String>,
+    name: String,
+    ty: String,
+    repeated: bool,
+}
+
+struct Service {
+    doc: Vec<String>,
+    name: String,
+    methods: Vec<Method>,
+}
+
+struct Method {
+    doc: Vec<String>,
+    name: String,
+    request: String,
+    response: String,
+    server_streaming: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(String),
+    Str(String),
+    Symbol(char),
+}
+
+/// A parser for the supported subset of proto3.
+struct Parser {
+    /// Tokens together with the comment lines directly preceding them.
+    tokens: Vec<(Token, Vec<String>)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(source: &str) -> Self {
+        let mut tokens = Vec::new();
+        let mut doc = Vec::new();
+
+        for line in source.lines() {
+            let line = line.trim();
+            if line.starts_with("//") {
+                let comment = line.trim_start_matches('/');
+                doc.push(comment.trim_start_matches(' ').to_owned());
+                continue;
+            }
+            // Only comments directly preceding a token document it.
+            if line.is_empty() {
+                doc.clear();
+                continue;
+            }
+
+            let line = line.splitn(2, "//").next().unwrap();
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                let token = if c.is_whitespace() {
+                    continue;
+                } else if c == '"' {
+                    Token::Str(chars.by_ref().take_while(|c| *c != '"').collect())
+                } else if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+                    let mut value = c.to_string();
+                    while let Some(&c) = chars.peek() {
+                        if !(c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+                            break;
+                        }
+                        value.push(c);
+                        chars.next();
+                    }
+                    if c.is_ascii_digit() {
+                        Token::Int(value)
+                    } else {
+                        Token::Ident(value)
+                    }
+                } else {
+                    Token::Symbol(c)
+                };
+                tokens.push((token, doc.split_off(0)));
+            }
+        }
+
+        Self { tokens, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn doc(&self) -> Vec<String> {
+        self.tokens
+            .get(self.pos)
+            .map_or_else(Vec::new, |(_, doc)| doc.clone())
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .map(|(token, _)| token.clone())
+            .ok_or_else(|| "unexpected end of file".to_owned())?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), String> {
+        match self.next()? {
+            Token::Symbol(c) if c == symbol => Ok(()),
+            token => Err(format!("expected '{}', found {:?}", symbol, token)),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.next()? {
+            Token::Ident(value) => Ok(value),
+            token => Err(format!("expected identifier, found {:?}", token)),
+        }
+    }
+
+    /// Parse an option name, which may be a parenthesized extension name.
+    fn option_name(&mut self) -> Result<String, String> {
+        if self.peek() == Some(&Token::Symbol('(')) {
+            self.next()?;
+            let name = self.ident()?;
+            self.expect(')')?;
+            return Ok(format!("({})", name));
+        }
+        self.ident()
+    }
+
+    /// Parse the remainder of an option statement, returning its name and value.
+    fn option(&mut self) -> Result<(String, Token), String> {
+        let name = self.option_name()?;
+        self.expect('=')?;
+        let value = self.next()?;
+        self.expect(';')?;
+        Ok((name, value))
+    }
+
+    /// Skip a block, including any nested blocks.
+    fn skip_block(&mut self) -> Result<(), String> {
+        self.expect('{')?;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next()? {
+                Token::Symbol('{') => depth += 1,
+                Token::Symbol('}') => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_file(&mut self) -> Result<File, String> {
+        let mut file = File::default();
+        while self.peek().is_some() {
+            let doc = self.doc();
+            match self.ident()?.as_str() {
+                "syntax" => {
+                    self.expect('=')?;
+                    match self.next()? {
+                        Token::Str(ref syntax) if syntax == "proto3" => {}
+                        token => return Err(format!("unsupported syntax {:?}", token)),
+                    }
+                    self.expect(';')?;
+                }
+                "package" => {
+                    file.package = self.ident()?;
+                    self.expect(';')?;
+                }
+                "import" => {
+                    self.next()?;
+                    self.expect(';')?;
+                }
+                "option" => {
+                    self.option()?;
+                }
+                "extend" => {
+                    self.ident()?;
+                    self.skip_block()?;
+                }
+                "message" => file.messages.push(self.parse_message(doc)?),
+                "service" => file.services.push(self.parse_service(doc)?),
+                keyword => return Err(format!("unsupported statement '{}'", keyword)),
+            }
+        }
+        Ok(file)
+    }
+
+    fn parse_message(&mut self, doc: Vec<String>) -> Result<Message, String> {
+        let mut message = Message {
+            doc,
+            name: self.ident()?,
+            transparent: false,
+            fields: Vec::new(),
+        };
+        self.expect('{')?;
+        loop {
+            if self.peek() == Some(&Token::Symbol('}')) {
+                self.next()?;
+                break;
+            }
+
+            let doc = self.doc();
+            let mut ty = self.ident()?;
+            match ty.as_str() {
+                "option" => {
+                    let (name, value) = self.option()?;
+                    if name == TRANSPARENT_OPTION {
+                        message.transparent = value == Token::Ident("true".to_owned());
+                    }
+                    continue;
+                }
+                "reserved" => {
+                    while self.next()? != Token::Symbol(';') {}
+                    continue;
+                }
+                "message" | "enum" | "oneof" | "map" => {
+                    return Err(format!("unsupported '{}' in message {}", ty, message.name))
+                }
+                _ => {}
+            }
+
+            let repeated = ty == "repeated";
+            if repeated {
+                ty = self.ident()?;
+            }
+            let name = self.ident()?;
+            self.expect('=')?;
+            self.next()?;
+            if self.peek() == Some(&Token::Symbol('[')) {
+                while self.next()? != Token::Symbol(']') {}
+            }
+            self.expect(';')?;
+
+            message.fields.push(Field {
+                doc,
+                name,
+                ty,
+                repeated,
+            });
+        }
+        Ok(message)
+    }
+
+    fn parse_service(&mut self, doc: Vec<String>) -> Result<Service, String> {
+        let mut service = Service {
+            doc,
+            name: self.ident()?,
+            methods: Vec::new(),
+        };
+        self.expect('{')?;
+        loop {
+            let doc = self.doc();
+            match self.next()? {
+                Token::Symbol('}') => break,
+                Token::Ident(ref keyword) if keyword == "option" => {
+                    self.option()?;
+                    continue;
+                }
+                Token::Ident(ref keyword) if keyword == "rpc" => {}
+                token => return Err(format!("expected rpc, found {:?}", token)),
+            }
+
+            let name = self.ident()?;
+            let (client_streaming, request) = self.parse_method_type()?;
+            if client_streaming {
+                return Err(format!("unsupported client streaming rpc {}", name));
+            }
+            match self.ident()?.as_str() {
+                "returns" => {}
+                token => return Err(format!("expected returns, found {:?}", token)),
+            }
+            let (server_streaming, response) = self.parse_method_type()?;
+            if self.peek() == Some(&Token::Symbol('{')) {
+                self.skip_block()?;
+            } else {
+                self.expect(';')?;
+            }
+
+            service.methods.push(Method {
+                doc,
+                name,
+                request,
+                response,
+                server_streaming,
+            });
+        }
+        Ok(service)
+    }
+
+    /// Parse a parenthesized method type, returning whether it is streamed.
+    fn parse_method_type(&mut self) -> Result<(bool, String), String> {
+        self.expect('(')?;
+        let mut ty = self.ident()?;
+        let streaming = ty == "stream";
+        if streaming {
+            ty = self.ident()?;
+        }
+        self.expect(')')?;
+        Ok((streaming, ty))
+    }
+}
+
+/// Convert a CamelCase name to snake_case.
+fn snake_case(name: &str) -> String {
+    let mut result = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                result.push('_');
+            }
+            result.push(c.to_ascii_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Format the doc comment lines at the given indentation.
+fn doc_comment(doc: &[String], indent: &str) -> String {
+    doc.iter()
+        .map(|line| {
+            if line.is_empty() {
+                format!("{}///\n", indent)
+            } else {
+                format!("{}/// {}\n", indent, line)
+            }
+        })
+        .collect()
+}
+
+/// Resolves protobuf type names to Rust types.
+struct TypeResolver<'a> {
+    package: &'a str,
+    messages: HashSet<&'a str>,
+    transparent: Vec<(&'a str, String)>,
+}
+
+impl<'a> TypeResolver<'a> {
+    fn new(file: &'a File) -> Result<Self, String> {
+        let mut resolver = Self {
+            package: &file.package,
+            messages: file.messages.iter().map(|m| m.name.as_str()).collect(),
+            transparent: Vec::new(),
+        };
+        for message in file.messages.iter().filter(|m| m.transparent) {
+            let field = match message.fields.as_slice() {
+                [field] => field,
+                _ => {
+                    return Err(format!(
+                        "transparent message {} must have a single field",
+                        message.name
+                    ))
+                }
+            };
+            let ty = resolver.field_type(field)?;
+            resolver.transparent.push((&message.name, ty));
+        }
+        Ok(resolver)
+    }
+
+    /// Resolve a message or external type name.
+    fn resolve(&self, name: &str) -> Result<String, String> {
+        let name = name.trim_start_matches('.');
+        if let Some((_, ty)) = EXTERNAL_TYPES.iter().find(|(n, _)| *n == name) {
+            return Ok((*ty).to_owned());
+        }
+
+        let prefix = format!("{}.", self.package);
+        let local = if name.starts_with(&prefix) {
+            &name[prefix.len()..]
+        } else {
+            name
+        };
+        if self.messages.contains(local) {
+            return Ok(local.to_owned());
+        }
+        Err(format!("unknown type {}", name))
+    }
+
+    /// Resolve the Rust type of a field, without any `Vec` for repeated fields.
+    fn element_type(&self, field: &Field) -> Result<String, String> {
+        let ty = match field.ty.as_str() {
+            "double" => "f64",
+            "float" => "f32",
+            "int32" | "sint32" | "sfixed32" => "i32",
+            "int64" | "sint64" | "sfixed64" => "i64",
+            "uint32" | "fixed32" => "u32",
+            "uint64" | "fixed64" => "u64",
+            "bool" => "bool",
+            "string" => "String",
+            "bytes" if field.repeated => "::serde_bytes::ByteBuf",
+            "bytes" => "Vec<u8>",
+            name => return self.resolve(name),
+        };
+        Ok(ty.to_owned())
+    }
+
+    fn field_type(&self, field: &Field) -> Result<String, String> {
+        let ty = self.element_type(field)?;
+        if field.repeated {
+            return Ok(format!("Vec<{}>", ty));
+        }
+        Ok(ty)
+    }
+}
+
+/// Generate the Rust code for a protobuf file.
+fn generate(file: &File) -> Result<String, String> {
+    let resolver = TypeResolver::new(file)?;
+    let mut out = String::from("// Generated by build.rs, do not edit.\n");
+
+    for message in &file.messages {
+        out.push('\n');
+        out.push_str(&doc_comment(&message.doc, ""));
+        if let Some((_, ty)) = resolver
+            .transparent
+            .iter()
+            .find(|(n, _)| *n == message.name)
+        {
+            writeln!(out, "pub type {} = {};", message.name, ty).unwrap();
+            continue;
+        }
+
+        out.push_str("#[derive(Clone, Debug, Default, ::serde_derive::Serialize, ::serde_derive::Deserialize)]\n");
+        writeln!(out, "pub struct {} {{", message.name).unwrap();
+        for field in &message.fields {
+            out.push_str(&doc_comment(&field.doc, "    "));
+            if field.ty == "bytes" && !field.repeated {
+                out.push_str("    #[serde(with = \"serde_bytes\")]\n");
+            }
+            let name = if KEYWORDS.contains(&field.name.as_str()) {
+                format!("r#{}", field.name)
+            } else {
+                field.name.clone()
+            };
+            writeln!(out, "    pub {}: {},", name, resolver.field_type(field)?).unwrap();
+        }
+        out.push_str("}\n");
+    }
+
+    for service in &file.services {
+        let constant =
+            |method: &Method| format!("METHOD_{}", snake_case(&method.name).to_uppercase());
+
+        out.push('\n');
+        for method in &service.methods {
+            writeln!(
+                out,
+                "{}!(\n    {},\n    \"/{}.{}/{}\",\n    {},\n    {}\n);",
+                if method.server_streaming {
+                    "grpc_stream"
+                } else {
+                    "grpc_method"
+                },
+                constant(method),
+                SERVICE_PREFIX,
+                service.name,
+                method.name,
+                resolver.resolve(&method.request)?,
+                resolver.resolve(&method.response)?,
+            )
+            .unwrap();
+        }
+
+        // Avoid names like RuntimeClientClient.
+        let client = if service.name.ends_with("Client") {
+            service.name.clone()
+        } else {
+            format!("{}Client", service.name)
+        };
+
+        out.push('\n');
+        out.push_str(&doc_comment(&service.doc, ""));
+        writeln!(
+            out,
+            "#[derive(Clone)]\npub struct {client} {{\n    client: ::grpcio::Client,\n}}\n\nimpl {client} {{\n    /// Create a new {name} client.\n    pub fn new(channel: ::grpcio::Channel) -> Self {{\n        {client} {{\n            client: ::grpcio::Client::new(channel),\n        }}\n    }}",
+            client = client,
+            name = service.name
+        )
+        .unwrap();
+
+        for method in &service.methods {
+            let request = resolver.resolve(&method.request)?;
+            let response = resolver.resolve(&method.response)?;
+            let (receiver, call) = if method.server_streaming {
+                ("ClientSStreamReceiver", "server_streaming")
+            } else {
+                ("ClientUnaryReceiver", "unary_call_async")
+            };
+            // Empty requests are omitted from the client method arguments.
+            let (argument, request) = if request == "()" {
+                (String::new(), "&()")
+            } else {
+                (format!("request: &{}, ", request), "request")
+            };
+
+            out.push('\n');
+            out.push_str(&doc_comment(&method.doc, "    "));
+            writeln!(
+                out,
+                "    pub fn {}(&self, {}opt: ::grpcio::CallOption) -> ::grpcio::Result<::grpcio::{}<{}>> {{\n        self.client.{}(&{}, {}, opt)\n    }}",
+                snake_case(&method.name),
+                argument,
+                receiver,
+                response,
+                call,
+                constant(method),
+                request,
+            )
+            .unwrap();
+        }
+        out.push_str("}\n");
+    }
+
+    Ok(out)
+}
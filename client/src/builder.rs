@@ -0,0 +1,345 @@
+//! Unified client configuration.
+//!
+//! A `ClientBuilder` holds the configuration shared by all clients (node
+//! endpoints, TLS, timeouts and retries) so that transaction, storage and
+//! enclave RPC clients for a runtime are configured consistently.
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use grpcio::{Channel, Environment};
+use oasis_core_runtime::{
+    common::{runtime::RuntimeId, sgx::avr::EnclaveIdentity},
+    rpc::session,
+};
+
+use crate::{
+    circuit_breaker::CircuitBreaker,
+    config::{ClientConfig, ConfigHandle},
+    executor::Spawner,
+    metrics::Metrics,
+    node::{self, TlsConfig},
+    rpc::{client::DEFAULT_MAX_RETRIES, CaptureTransport, GrpcTransport, RpcClient, Transport},
+    transaction::{
+        client::DEFAULT_MAX_RESUBMITS,
+        storage::{DEFAULT_BACKOFF_INITIAL, DEFAULT_BACKOFF_MAX, DEFAULT_RETRIES},
+        StorageNodes, TxnClient,
+    },
+};
+
+/// Default number of key manager keys cached by the key manager client.
+pub const DEFAULT_KEYS_CACHE_SIZE: usize = 100;
+
+/// Retry policy shared by all clients.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Number of retries of a storage request against a single node.
+    pub storage_retries: usize,
+    /// Initial delay between storage request retries.
+    pub backoff_initial: Duration,
+    /// Maximum delay between storage request retries.
+    pub backoff_max: Duration,
+    /// Maximum number of times a transaction is resubmitted.
+    pub max_resubmits: usize,
+    /// Maximum number of times a failed enclave RPC call is retried.
+    pub rpc_retries: usize,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            storage_retries: DEFAULT_RETRIES,
+            backoff_initial: DEFAULT_BACKOFF_INITIAL,
+            backoff_max: DEFAULT_BACKOFF_MAX,
+            max_resubmits: DEFAULT_MAX_RESUBMITS,
+            rpc_retries: DEFAULT_MAX_RETRIES,
+        }
+    }
+}
+
+/// Builder for consistently configured clients of a runtime.
+#[derive(Clone)]
+pub struct ClientBuilder {
+    environment: Arc<Environment>,
+    address: String,
+    runtime_id: RuntimeId,
+    node_options: node::Options,
+    read_nodes: Vec<String>,
+    read_quorum: usize,
+    storage_nodes: Vec<String>,
+    timeout: Option<Duration>,
+    retry_policy: RetryPolicy,
+    circuit_breaker: Option<CircuitBreaker>,
+    key_manager_enclaves: Option<HashSet<EnclaveIdentity>>,
+    keys_cache_size: usize,
+    capture_traffic: bool,
+    metrics: Option<Metrics>,
+    spawner: Option<Arc<dyn Spawner>>,
+    config: Option<ConfigHandle>,
+}
+
+impl ClientBuilder {
+    /// Create a new builder for clients of the given runtime, talking to the
+    /// node at the given address.
+    pub fn new(environment: Arc<Environment>, address: &str, runtime_id: RuntimeId) -> Self {
+        Self {
+            environment,
+            address: address.to_owned(),
+            runtime_id,
+            node_options: node::Options::default(),
+            read_nodes: vec![],
+            read_quorum: 1,
+            storage_nodes: vec![],
+            timeout: None,
+            retry_policy: RetryPolicy::default(),
+            circuit_breaker: None,
+            key_manager_enclaves: None,
+            keys_cache_size: DEFAULT_KEYS_CACHE_SIZE,
+            capture_traffic: false,
+            metrics: None,
+            spawner: None,
+            config: None,
+        }
+    }
+
+    /// Set the options used for all node connections.
+    ///
+    /// This replaces any TLS configuration set before.
+    pub fn with_node_options(mut self, options: node::Options) -> Self {
+        self.node_options = options;
+        self
+    }
+
+    /// Connect to all nodes using TLS.
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.node_options = self.node_options.with_tls(tls);
+        self
+    }
+
+    /// Read blocks from the given nodes, requiring `read_quorum` of them to
+    /// agree, instead of from the node the client is connected to.
+    ///
+    /// The read quorum is validated by `build_txn_client`.
+    pub fn with_read_nodes(mut self, addresses: Vec<String>, read_quorum: usize) -> Self {
+        self.read_nodes = addresses;
+        self.read_quorum = read_quorum;
+        self
+    }
+
+    /// Access the runtime state through the given storage nodes instead of
+    /// the storage service of the node the client is connected to.
+    pub fn with_storage_nodes(mut self, addresses: Vec<String>) -> Self {
+        self.storage_nodes = addresses;
+        self
+    }
+
+    /// Set the timeout of individual node calls.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the retry policy.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Make calls to the node through the given circuit breaker.
+    ///
+    /// The circuit breaker is shared by all transaction clients built.
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    /// Set the key manager enclave identities which are trusted by the key
+    /// manager client and the number of keys it caches.
+    ///
+    /// If no enclave identities are given, the key manager is not
+    /// authenticated.
+    pub fn with_key_manager(
+        mut self,
+        enclaves: Option<HashSet<EnclaveIdentity>>,
+        keys_cache_size: usize,
+    ) -> Self {
+        self.key_manager_enclaves = enclaves;
+        self.keys_cache_size = keys_cache_size;
+        self
+    }
+
+    /// Record metrics of all clients built in the given metrics.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Spawn the background tasks of all clients built with the given
+    /// spawner instead of the default one (see `executor::default_spawner`).
+    pub fn with_spawner(mut self, spawner: Arc<dyn Spawner>) -> Self {
+        self.spawner = Some(spawner);
+        self
+    }
+
+    /// Make the enclave RPC and key manager clients built follow the given
+    /// configuration, applying any changes made to it at runtime.
+    ///
+    /// The configuration takes precedence over the node address, timeout,
+    /// retry policy and key manager settings of the builder for these
+    /// clients. An initial configuration matching the builder can be
+    /// obtained via `config`.
+    pub fn with_config(mut self, config: ConfigHandle) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Log all enclave RPC frames sent by the enclave RPC clients built.
+    ///
+    /// This is meant for debugging only, see `rpc::capture`.
+    pub fn with_traffic_capture(mut self) -> Self {
+        self.capture_traffic = true;
+        self
+    }
+
+    /// Runtime identifier.
+    pub fn runtime_id(&self) -> RuntimeId {
+        self.runtime_id
+    }
+
+    /// Trusted key manager enclave identities.
+    pub fn key_manager_enclaves(&self) -> Option<HashSet<EnclaveIdentity>> {
+        self.key_manager_enclaves.clone()
+    }
+
+    /// Number of keys cached by the key manager client.
+    pub fn keys_cache_size(&self) -> usize {
+        self.keys_cache_size
+    }
+
+    /// Metrics the clients built record their metrics in, if any.
+    pub fn metrics(&self) -> Option<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// Client configuration matching the builder's settings.
+    pub fn config(&self) -> ClientConfig {
+        ClientConfig {
+            addresses: vec![self.address.clone()],
+            timeout: self.timeout,
+            retry_policy: self.retry_policy.clone(),
+            key_manager_enclaves: self.key_manager_enclaves.clone(),
+            keys_cache_size: self.keys_cache_size,
+        }
+    }
+
+    /// Configuration the clients built follow, if any.
+    pub fn config_handle(&self) -> Option<ConfigHandle> {
+        self.config.clone()
+    }
+
+    /// Options used for node connections.
+    fn node_options(&self) -> node::Options {
+        match self.spawner {
+            Some(ref spawner) => self.node_options.clone().with_spawner(spawner.clone()),
+            None => self.node_options.clone(),
+        }
+    }
+
+    /// Open a channel to the given node.
+    fn connect(&self, address: &str) -> Channel {
+        self.node_options()
+            .new(self.environment.clone(), address)
+            .channel()
+    }
+
+    /// Open a channel to the node the clients talk to.
+    pub fn channel(&self) -> Channel {
+        self.connect(&self.address)
+    }
+
+    /// Build a storage client.
+    pub fn build_storage_nodes(&self) -> Result<StorageNodes> {
+        let channels = if self.storage_nodes.is_empty() {
+            vec![self.channel()]
+        } else {
+            self.storage_nodes
+                .iter()
+                .map(|address| self.connect(address))
+                .collect()
+        };
+
+        let mut storage_nodes = StorageNodes::new(channels)
+            .with_retries(self.retry_policy.storage_retries)
+            .with_backoff(
+                self.retry_policy.backoff_initial,
+                self.retry_policy.backoff_max,
+            );
+        if let Some(timeout) = self.timeout {
+            storage_nodes = storage_nodes.with_timeout(timeout);
+        }
+        if let Some(ref metrics) = self.metrics {
+            storage_nodes = storage_nodes.with_metrics(metrics.clone());
+        }
+        storage_nodes.build()
+    }
+
+    /// Build a transaction client.
+    pub fn build_txn_client(&self) -> Result<TxnClient> {
+        let mut client = TxnClient::new(self.channel(), self.runtime_id, self.timeout)
+            .with_storage_nodes(self.build_storage_nodes()?)
+            .with_max_resubmits(self.retry_policy.max_resubmits);
+        if !self.read_nodes.is_empty() {
+            client = client.with_read_nodes(
+                self.read_nodes
+                    .iter()
+                    .map(|address| self.connect(address))
+                    .collect(),
+            );
+        }
+        client = client.with_read_quorum(self.read_quorum);
+        if let Some(ref circuit_breaker) = self.circuit_breaker {
+            client = client.with_circuit_breaker(circuit_breaker.clone());
+        }
+        if let Some(ref metrics) = self.metrics {
+            client = client.with_metrics(metrics.clone());
+        }
+        if let Some(ref spawner) = self.spawner {
+            client = client.with_spawner(spawner.clone());
+        }
+        client.build()
+    }
+
+    /// Build an enclave RPC client for the given endpoint of the runtime.
+    pub fn build_rpc_client(&self, builder: session::Builder, endpoint: &str) -> RpcClient {
+        let mut grpc_transport = GrpcTransport::new(self.channel(), self.runtime_id, endpoint);
+        if let Some(ref metrics) = self.metrics {
+            grpc_transport = grpc_transport.with_metrics(metrics.clone());
+        }
+        if let Some(ref config) = self.config {
+            let environment = self.environment.clone();
+            let node_options = self.node_options();
+            grpc_transport = grpc_transport.with_config(
+                config,
+                Arc::new(move |address: &str| {
+                    node_options
+                        .clone()
+                        .new(environment.clone(), address)
+                        .channel()
+                }),
+            );
+        }
+        let mut transport: Box<dyn Transport> = Box::new(grpc_transport);
+        if self.capture_traffic {
+            transport = Box::new(CaptureTransport::new(transport));
+        }
+
+        let mut client = RpcClient::new_with_transport(builder, transport)
+            .with_max_retries(self.retry_policy.rpc_retries);
+        if let Some(ref spawner) = self.spawner {
+            client = client.with_spawner(spawner.clone());
+        }
+        if let Some(ref config) = self.config {
+            client = client.with_config(config.clone());
+        }
+        client
+    }
+}
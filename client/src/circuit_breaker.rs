@@ -0,0 +1,419 @@
+//! Retry budget and circuit breaker for node calls.
+//!
+//! After a number of consecutive failed calls to a node the circuit opens and
+//! calls fail fast with `CircuitBreakerError::CircuitOpen` instead of adding
+//! to the load of a node that is already struggling. Once the cool-down period
+//! has passed, a single probe call is let through and the circuit closes again
+//! if it succeeds. A probe which is dropped before completing lets another
+//! probe through.
+//!
+//! Retries are additionally limited by a retry budget which grows with each
+//! successful call, so that retries cannot amplify an outage.
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Error, Result};
+use futures::{future, prelude::*};
+
+use crate::BoxFuture;
+
+/// Default number of consecutive failures after which the circuit opens.
+pub const DEFAULT_FAILURE_THRESHOLD: usize = 5;
+/// Default time the circuit stays open before a probe call is allowed.
+pub const DEFAULT_OPEN_DURATION: Duration = Duration::from_secs(5);
+/// Default number of retries earned by each successful call.
+pub const DEFAULT_RETRY_RATIO: f64 = 0.2;
+/// Default maximum number of retries that can be saved up.
+pub const DEFAULT_MAX_RETRY_BUDGET: f64 = 10.0;
+
+/// Circuit breaker error.
+#[derive(Debug, thiserror::Error)]
+pub enum CircuitBreakerError {
+    #[error("circuit open after repeated node call failures")]
+    CircuitOpen,
+    #[error("retry budget exhausted")]
+    RetryBudgetExhausted,
+    #[error("failure threshold must be positive")]
+    InvalidFailureThreshold,
+    #[error("retry budget must be non-negative (ratio: {0} max: {1})")]
+    InvalidRetryBudget(f64, f64),
+}
+
+#[derive(Debug, PartialEq)]
+enum State {
+    /// Calls are allowed.
+    Closed { failures: usize },
+    /// Calls fail fast until the given time.
+    Open { until: Instant },
+    /// A single probe call is in flight.
+    HalfOpen,
+}
+
+struct Inner {
+    state: State,
+    retry_budget: f64,
+}
+
+/// A circuit breaker and retry budget shared by the clients of a node.
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    inner: Arc<Mutex<Inner>>,
+    failure_threshold: usize,
+    open_duration: Duration,
+    retry_ratio: f64,
+    max_retry_budget: f64,
+}
+
+impl CircuitBreaker {
+    /// Create a new circuit breaker with the default configuration.
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                state: State::Closed { failures: 0 },
+                retry_budget: DEFAULT_MAX_RETRY_BUDGET,
+            })),
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            open_duration: DEFAULT_OPEN_DURATION,
+            retry_ratio: DEFAULT_RETRY_RATIO,
+            max_retry_budget: DEFAULT_MAX_RETRY_BUDGET,
+        }
+    }
+
+    /// Set the number of consecutive failures after which the circuit opens.
+    ///
+    /// The threshold is validated by `build`.
+    pub fn with_failure_threshold(mut self, failure_threshold: usize) -> Self {
+        self.failure_threshold = failure_threshold;
+        self
+    }
+
+    /// Set the time the circuit stays open before a probe call is allowed.
+    pub fn with_open_duration(mut self, open_duration: Duration) -> Self {
+        self.open_duration = open_duration;
+        self
+    }
+
+    /// Set the number of retries earned by each successful call and the
+    /// maximum number of retries that can be saved up.
+    ///
+    /// The budget is validated by `build`.
+    pub fn with_retry_budget(mut self, retry_ratio: f64, max_retry_budget: f64) -> Self {
+        self.retry_ratio = retry_ratio;
+        self.max_retry_budget = max_retry_budget;
+        self.inner.lock().unwrap().retry_budget = max_retry_budget;
+        self
+    }
+
+    /// Validate the configuration.
+    ///
+    /// Returns an error if the failure threshold is zero or the retry budget
+    /// is negative.
+    pub fn build(self) -> Result<Self> {
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Validate the configuration without consuming the circuit breaker.
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.failure_threshold == 0 {
+            return Err(CircuitBreakerError::InvalidFailureThreshold.into());
+        }
+        if self.retry_ratio.is_nan()
+            || self.max_retry_budget.is_nan()
+            || self.retry_ratio < 0.0
+            || self.max_retry_budget < 0.0
+        {
+            return Err(CircuitBreakerError::InvalidRetryBudget(
+                self.retry_ratio,
+                self.max_retry_budget,
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Check whether the circuit is currently open.
+    pub fn is_open(&self) -> bool {
+        match self.inner.lock().unwrap().state {
+            State::Closed { .. } => false,
+            State::Open { until } => Instant::now() < until,
+            State::HalfOpen => true,
+        }
+    }
+
+    /// Check whether a call may be made.
+    ///
+    /// If this succeeds, the outcome of the call should be reported through
+    /// the returned permit. Dropping the permit without reporting an outcome
+    /// abandons the call.
+    pub fn acquire(&self) -> Result<Permit> {
+        let mut inner = self.inner.lock().unwrap();
+        let probe = match inner.state {
+            State::Closed { .. } => false,
+            State::Open { until } if Instant::now() >= until => {
+                // Let a single call through to probe for recovery.
+                inner.state = State::HalfOpen;
+                true
+            }
+            _ => return Err(CircuitBreakerError::CircuitOpen.into()),
+        };
+
+        Ok(Permit {
+            breaker: self.clone(),
+            probe,
+            reported: false,
+        })
+    }
+
+    fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.state = State::Closed { failures: 0 };
+        inner.retry_budget = (inner.retry_budget + self.retry_ratio).min(self.max_retry_budget);
+    }
+
+    fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        let failures = match inner.state {
+            State::Closed { failures } => failures + 1,
+            // A failed probe opens the circuit again.
+            _ => self.failure_threshold,
+        };
+
+        inner.state = if failures >= self.failure_threshold {
+            State::Open {
+                until: Instant::now() + self.open_duration,
+            }
+        } else {
+            State::Closed { failures }
+        };
+    }
+
+    fn release_probe(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.state == State::HalfOpen {
+            // Let the next call probe instead.
+            inner.state = State::Open {
+                until: Instant::now(),
+            };
+        }
+    }
+
+    /// Withdraw a retry from the retry budget.
+    ///
+    /// Returns an error if the budget is exhausted, in which case the call
+    /// should not be retried.
+    pub fn try_retry(&self) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.retry_budget < 1.0 {
+            return Err(CircuitBreakerError::RetryBudgetExhausted.into());
+        }
+        inner.retry_budget -= 1.0;
+        Ok(())
+    }
+
+    /// Make a call through the circuit breaker, counting any error as a
+    /// failure.
+    pub fn call<T, F>(&self, f: F) -> BoxFuture<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> BoxFuture<T>,
+    {
+        self.call_with(f, |_| true)
+    }
+
+    /// Make a call through the circuit breaker, counting only errors for
+    /// which `is_failure` returns true as failures.
+    ///
+    /// This allows errors returned by a healthy node, e.g., because of an
+    /// invalid request, to be distinguished from node failures.
+    pub fn call_with<T, F>(&self, f: F, is_failure: fn(&Error) -> bool) -> BoxFuture<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> BoxFuture<T>,
+    {
+        let permit = match self.acquire() {
+            Ok(permit) => permit,
+            Err(error) => return Box::new(future::err(error)),
+        };
+
+        Box::new(f().then(move |result| {
+            match result {
+                Err(ref error) if is_failure(error) => permit.record_failure(),
+                _ => permit.record_success(),
+            }
+            result
+        }))
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Permission to make a single call through a circuit breaker.
+///
+/// If the call is the probe of a half-open circuit and the permit is dropped
+/// without reporting an outcome, the probe slot is released so that another
+/// call can probe for recovery.
+pub struct Permit {
+    breaker: CircuitBreaker,
+    probe: bool,
+    reported: bool,
+}
+
+impl Permit {
+    /// Report a successful call.
+    pub fn record_success(mut self) {
+        self.reported = true;
+        self.breaker.record_success();
+    }
+
+    /// Report a failed call.
+    pub fn record_failure(mut self) {
+        self.reported = true;
+        self.breaker.record_failure();
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if self.probe && !self.reported {
+            self.breaker.release_probe();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use anyhow::anyhow;
+
+    use super::*;
+
+    fn is_circuit_open(error: &Error) -> bool {
+        match error.downcast_ref::<CircuitBreakerError>() {
+            Some(CircuitBreakerError::CircuitOpen) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new()
+            .with_failure_threshold(2)
+            .with_open_duration(Duration::from_millis(50));
+        let fail = || -> BoxFuture<()> { Box::new(future::err(anyhow!("unavailable"))) };
+        let succeed = || -> BoxFuture<()> { Box::new(future::ok(())) };
+
+        // Failures below the threshold keep the circuit closed.
+        assert!(breaker.call(fail).wait().is_err());
+        assert!(!breaker.is_open());
+        assert!(breaker.call(succeed).wait().is_ok());
+        assert!(breaker.call(fail).wait().is_err());
+        assert!(!breaker.is_open());
+
+        // Consecutive failures open the circuit and calls fail fast.
+        assert!(breaker.call(fail).wait().is_err());
+        assert!(breaker.is_open());
+        let error = breaker
+            .call(|| -> BoxFuture<()> { panic!("call must not be made") })
+            .wait()
+            .unwrap_err();
+        assert!(is_circuit_open(&error));
+
+        // A failed probe opens the circuit again.
+        thread::sleep(Duration::from_millis(60));
+        assert!(!breaker.is_open());
+        assert!(breaker.call(fail).wait().is_err());
+        assert!(breaker.is_open());
+
+        // A successful probe closes the circuit.
+        thread::sleep(Duration::from_millis(60));
+        assert!(breaker.call(succeed).wait().is_ok());
+        assert!(!breaker.is_open());
+
+        // Errors which are not failures do not open the circuit.
+        for _ in 0..3 {
+            assert!(breaker.call_with(fail, |_| false).wait().is_err());
+        }
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn test_dropped_probe() {
+        let breaker = CircuitBreaker::new()
+            .with_failure_threshold(1)
+            .with_open_duration(Duration::from_millis(50));
+        let fail = || -> BoxFuture<()> { Box::new(future::err(anyhow!("unavailable"))) };
+        let succeed = || -> BoxFuture<()> { Box::new(future::ok(())) };
+
+        assert!(breaker.call(fail).wait().is_err());
+        assert!(breaker.is_open());
+
+        // While a probe is in flight, other calls fail fast.
+        thread::sleep(Duration::from_millis(60));
+        let probe = breaker.call(|| -> BoxFuture<()> { Box::new(future::empty()) });
+        let error = breaker.call(succeed).wait().unwrap_err();
+        assert!(is_circuit_open(&error));
+
+        // Dropping the probe before it completes lets another call probe.
+        drop(probe);
+        assert!(breaker.call(succeed).wait().is_ok());
+        assert!(!breaker.is_open());
+
+        // Dropping a permit of a closed circuit has no effect.
+        drop(breaker.acquire().unwrap());
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn test_build() {
+        CircuitBreaker::new()
+            .with_failure_threshold(1)
+            .with_retry_budget(0.0, 0.0)
+            .build()
+            .expect("valid configuration should build");
+
+        let error = CircuitBreaker::new()
+            .with_failure_threshold(0)
+            .build()
+            .err()
+            .expect("invalid failure threshold should be rejected");
+        match error.downcast_ref::<CircuitBreakerError>() {
+            Some(CircuitBreakerError::InvalidFailureThreshold) => {}
+            _ => panic!("expected invalid failure threshold, got: {}", error),
+        }
+
+        for (retry_ratio, max_retry_budget) in &[(-1.0, 1.0), (1.0, -1.0), (std::f64::NAN, 1.0)] {
+            let error = CircuitBreaker::new()
+                .with_retry_budget(*retry_ratio, *max_retry_budget)
+                .build()
+                .err()
+                .expect("invalid retry budget should be rejected");
+            match error.downcast_ref::<CircuitBreakerError>() {
+                Some(CircuitBreakerError::InvalidRetryBudget(..)) => {}
+                _ => panic!("expected invalid retry budget, got: {}", error),
+            }
+        }
+    }
+
+    #[test]
+    fn test_retry_budget() {
+        let breaker = CircuitBreaker::new().with_retry_budget(0.5, 2.0);
+        assert!(breaker.try_retry().is_ok());
+        assert!(breaker.try_retry().is_ok());
+        assert!(breaker.try_retry().is_err());
+
+        // Successful calls earn retries.
+        breaker.record_success();
+        assert!(breaker.try_retry().is_err());
+        breaker.record_success();
+        assert!(breaker.try_retry().is_ok());
+        assert!(breaker.try_retry().is_err());
+    }
+}
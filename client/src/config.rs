@@ -0,0 +1,190 @@
+//! Hot-reloadable client configuration.
+//!
+//! A `ConfigHandle` holds the client settings which may change while the
+//! clients are running (node addresses, timeouts, the retry policy and the
+//! key manager settings). Clients following a handle, e.g., those built by a
+//! `ClientBuilder` given `with_config`, subscribe to it and apply any change
+//! before their next call, so long-running processes like gateways do not
+//! need to be restarted for routine configuration changes.
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::Duration,
+};
+
+use oasis_core_runtime::common::sgx::avr::EnclaveIdentity;
+
+use crate::builder::RetryPolicy;
+
+/// Client settings which can be changed at runtime.
+#[derive(Clone, Debug)]
+pub struct ClientConfig {
+    /// Addresses of the nodes enclave RPC calls are made through. Calls are
+    /// distributed among all of them.
+    pub addresses: Vec<String>,
+    /// Timeout of individual node calls.
+    pub timeout: Option<Duration>,
+    /// Retry policy.
+    pub retry_policy: RetryPolicy,
+    /// Key manager enclave identities trusted by the key manager client,
+    /// the key manager is not authenticated if not set.
+    pub key_manager_enclaves: Option<HashSet<EnclaveIdentity>>,
+    /// Number of keys cached by the key manager client.
+    pub keys_cache_size: usize,
+}
+
+struct Inner {
+    config: RwLock<Arc<ClientConfig>>,
+    /// Number of updates made so far.
+    generation: AtomicU64,
+}
+
+/// A shared handle to a client configuration.
+#[derive(Clone)]
+pub struct ConfigHandle {
+    inner: Arc<Inner>,
+}
+
+impl ConfigHandle {
+    /// Create a new handle to the given configuration.
+    pub fn new(config: ClientConfig) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                config: RwLock::new(Arc::new(config)),
+                generation: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Current configuration.
+    pub fn get(&self) -> Arc<ClientConfig> {
+        self.inner.config.read().unwrap().clone()
+    }
+
+    /// Replace the configuration.
+    pub fn set(&self, config: ClientConfig) {
+        self.update(|current| *current = config);
+    }
+
+    /// Update the configuration with the given function.
+    ///
+    /// Updates are atomic, subscribers either see all changes made by the
+    /// function or none.
+    pub fn update<F>(&self, f: F)
+    where
+        F: FnOnce(&mut ClientConfig),
+    {
+        let mut current = self.inner.config.write().unwrap();
+        let mut config = (**current).clone();
+        f(&mut config);
+        *current = Arc::new(config);
+        self.inner.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Subscribe to changes of the configuration made from now on.
+    pub fn subscribe(&self) -> Subscription {
+        let (generation, config) = self.snapshot();
+        Subscription {
+            handle: self.clone(),
+            seen: Mutex::new((generation, config)),
+        }
+    }
+
+    fn snapshot(&self) -> (u64, Arc<ClientConfig>) {
+        // Updates are made while holding the write lock.
+        let config = self.inner.config.read().unwrap();
+        (self.inner.generation.load(Ordering::SeqCst), config.clone())
+    }
+}
+
+/// A change of the configuration.
+#[derive(Clone, Debug)]
+pub struct ConfigChange {
+    /// Configuration before the change.
+    pub old: Arc<ClientConfig>,
+    /// Configuration after the change.
+    pub new: Arc<ClientConfig>,
+}
+
+/// A subscription to changes of a configuration.
+pub struct Subscription {
+    handle: ConfigHandle,
+    /// Generation and configuration as of the last change seen.
+    seen: Mutex<(u64, Arc<ClientConfig>)>,
+}
+
+impl Subscription {
+    /// Handle the subscription is for.
+    pub fn handle(&self) -> &ConfigHandle {
+        &self.handle
+    }
+
+    /// Configuration as of the last change seen.
+    pub fn config(&self) -> Arc<ClientConfig> {
+        self.seen.lock().unwrap().1.clone()
+    }
+
+    /// Return the change of the configuration since the last change seen,
+    /// if any.
+    ///
+    /// Multiple updates made in the meantime are reported as a single
+    /// change.
+    pub fn changed(&self) -> Option<ConfigChange> {
+        let mut seen = self.seen.lock().unwrap();
+        if self.handle.inner.generation.load(Ordering::SeqCst) == seen.0 {
+            return None;
+        }
+
+        let (generation, config) = self.handle.snapshot();
+        let old = std::mem::replace(&mut *seen, (generation, config.clone())).1;
+        Some(ConfigChange { old, new: config })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config() -> ClientConfig {
+        ClientConfig {
+            addresses: vec!["unix:node.sock".to_owned()],
+            timeout: None,
+            retry_policy: RetryPolicy::default(),
+            key_manager_enclaves: None,
+            keys_cache_size: 100,
+        }
+    }
+
+    #[test]
+    fn test_config_handle() {
+        let handle = ConfigHandle::new(config());
+        let subscription = handle.subscribe();
+        assert!(subscription.changed().is_none());
+
+        handle.update(|config| config.timeout = Some(Duration::from_secs(1)));
+        assert_eq!(handle.get().timeout, Some(Duration::from_secs(1)));
+        // Subscriptions made after a change are not notified of it.
+        let late_subscription = handle.subscribe();
+        assert!(late_subscription.changed().is_none());
+
+        // Changes are collapsed until they are seen.
+        handle.update(|config| config.keys_cache_size = 10);
+        let change = subscription.changed().unwrap();
+        assert_eq!(change.old.timeout, None);
+        assert_eq!(change.old.keys_cache_size, 100);
+        assert_eq!(change.new.timeout, Some(Duration::from_secs(1)));
+        assert_eq!(change.new.keys_cache_size, 10);
+        assert_eq!(subscription.config().keys_cache_size, 10);
+        assert!(subscription.changed().is_none());
+
+        let change = late_subscription.changed().unwrap();
+        assert_eq!(change.old.keys_cache_size, 100);
+        assert_eq!(change.new.keys_cache_size, 10);
+
+        handle.set(config());
+        assert_eq!(subscription.changed().unwrap().new.timeout, None);
+    }
+}
@@ -0,0 +1,149 @@
+//! Client for service defined in go/consensus/api.
+use std::time::Instant;
+
+use futures::{future, prelude::*};
+use grpcio::{CallOption, Channel, Client};
+use io_context::Context;
+use serde_derive::{Deserialize, Serialize};
+
+use oasis_core_runtime::common::{
+    cbor,
+    consensus::{Fee, SignedTransaction, Transaction},
+    crypto::signature::PublicKey,
+    quantity::Quantity,
+};
+
+use crate::{
+    context,
+    grpc::{call_option, unary_call},
+    BoxFuture,
+};
+
+/// Special height always referring to the latest consensus block.
+pub const HEIGHT_LATEST: i64 = 0;
+
+grpc_method!(
+    METHOD_SUBMIT_TX,
+    "/oasis-core.Consensus/SubmitTx",
+    SignedTransaction,
+    ()
+);
+grpc_method!(
+    METHOD_GET_SIGNER_NONCE,
+    "/oasis-core.Consensus/GetSignerNonce",
+    GetSignerNonceRequest,
+    u64
+);
+grpc_method!(METHOD_GET_EPOCH, "/oasis-core.Consensus/GetEpoch", i64, u64);
+grpc_method!(
+    METHOD_ESTIMATE_GAS,
+    "/oasis-core.Consensus/EstimateGas",
+    EstimateGasRequest,
+    u64
+);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GetSignerNonceRequest {
+    pub id: PublicKey,
+    pub height: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EstimateGasRequest {
+    pub caller: PublicKey,
+    pub transaction: Transaction,
+}
+
+/// A consensus gRPC service client.
+#[derive(Clone)]
+pub struct ConsensusClient {
+    client: Client,
+    deadline: Option<Instant>,
+}
+
+impl ConsensusClient {
+    /// Create a new consensus client.
+    pub fn new(channel: Channel) -> Self {
+        ConsensusClient {
+            client: Client::new(channel),
+            deadline: None,
+        }
+    }
+
+    /// Apply the deadline of the given context to all calls made through the
+    /// returned client.
+    pub fn with_context(&self, ctx: &Context) -> Self {
+        let mut client = self.clone();
+        client.deadline = context::get_deadline(ctx);
+        client
+    }
+
+    /// Submit a signed transaction and wait for it to be included in a block.
+    pub fn submit_tx(&self, tx: &SignedTransaction) -> BoxFuture<()> {
+        unary_call(
+            self.client
+                .unary_call_async(&METHOD_SUBMIT_TX, tx, self.options()),
+        )
+    }
+
+    /// Submit a CBOR-encoded signed transaction, e.g., one produced offline
+    /// by a `TransactionBuilder`.
+    pub fn submit_tx_encoded(&self, tx: &[u8]) -> BoxFuture<()> {
+        match cbor::from_slice(tx) {
+            Ok(tx) => self.submit_tx(&tx),
+            Err(error) => Box::new(future::err(error.into())),
+        }
+    }
+
+    /// Retrieve the nonce that the next transaction signed by the given
+    /// account must use.
+    pub fn get_signer_nonce(&self, id: PublicKey, height: i64) -> BoxFuture<u64> {
+        let request = GetSignerNonceRequest { id, height };
+        unary_call(
+            self.client
+                .unary_call_async(&METHOD_GET_SIGNER_NONCE, &request, self.options()),
+        )
+    }
+
+    /// Retrieve the epoch at the given height.
+    pub fn get_epoch(&self, height: i64) -> BoxFuture<u64> {
+        unary_call(
+            self.client
+                .unary_call_async(&METHOD_GET_EPOCH, &height, self.options()),
+        )
+    }
+
+    /// Estimate the gas that the given transaction would use if signed by the
+    /// given account, by simulating it against the latest state.
+    pub fn estimate_gas(&self, signer: PublicKey, tx: &Transaction) -> BoxFuture<u64> {
+        let request = EstimateGasRequest {
+            caller: signer,
+            transaction: tx.clone(),
+        };
+        unary_call(
+            self.client
+                .unary_call_async(&METHOD_ESTIMATE_GAS, &request, self.options()),
+        )
+    }
+
+    /// Estimate the fee needed for the given transaction if signed by the
+    /// given account, at the given price per unit of gas.
+    ///
+    /// The returned fee can be used to populate the transaction's fee before
+    /// signing it.
+    pub fn estimate_fee(
+        &self,
+        signer: PublicKey,
+        tx: &Transaction,
+        gas_price: Quantity,
+    ) -> BoxFuture<Fee> {
+        Box::new(
+            self.estimate_gas(signer, tx)
+                .map(move |gas| Fee::for_gas(gas, &gas_price)),
+        )
+    }
+
+    fn options(&self) -> CallOption {
+        call_option(self.deadline, None)
+    }
+}
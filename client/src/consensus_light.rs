@@ -0,0 +1,857 @@
+//! Light client verification of consensus block headers.
+//!
+//! Consensus headers are Tendermint signed headers, served by a node's
+//! consensus light client service in their amino encoding. A header is only
+//! trusted once it has been linked to the trusted header via the next
+//! validator set the latter commits to, and carries valid precommits from
+//! validators holding more than two thirds of that set's voting power. The
+//! application state root of a verified header can then be used to verify
+//! consensus state lookups, see `consensus_state`.
+use anyhow::Result;
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use oasis_core_runtime::common::crypto::hash::Hash;
+
+use crate::consensus_state::TrustedStateRoot;
+
+/// Amino prefix of Ed25519 public keys.
+const ED25519_PUBKEY_PREFIX: [u8; 4] = [0x16, 0x24, 0xde, 0x64];
+/// Size of Ed25519 public keys.
+const ED25519_PUBKEY_SIZE: usize = 32;
+/// Size of validator addresses.
+const ADDRESS_SIZE: usize = 20;
+/// Precommit vote type.
+const PRECOMMIT_TYPE: u64 = 0x02;
+/// Block ID flag of commit signatures for the committed block.
+const BLOCK_ID_FLAG_COMMIT: u64 = 2;
+
+/// Consensus light client verification error.
+#[derive(Debug, Error)]
+pub enum ConsensusLightClientError {
+    #[error("malformed {0}")]
+    Malformed(&'static str),
+    #[error("header has unexpected chain id")]
+    ChainIDMismatch,
+    #[error("header is not the successor of height {0}")]
+    NotSuccessor(i64),
+    #[error("validator set does not match header at height {0}")]
+    ValidatorSetMismatch(i64),
+    #[error("commit is not for header at height {0}")]
+    CommitMismatch(i64),
+    #[error("invalid signature of validator {0}")]
+    InvalidSignature(usize),
+    #[error("total voting power overflows at height {0}")]
+    VotingPowerOverflow(i64),
+    #[error("not enough voting power for height {height} (got: {got} total: {total})")]
+    InsufficientVotingPower { height: i64, got: u64, total: u64 },
+    #[error("header at height {0} has no state root")]
+    NoStateRoot(i64),
+}
+
+/// A signed consensus block header.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SignedHeader {
+    /// Block height this header is for.
+    pub height: i64,
+    /// Consensus backend specific signed header.
+    #[serde(with = "serde_bytes")]
+    pub meta: Vec<u8>,
+}
+
+/// A consensus validator set.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ValidatorSet {
+    /// Block height this validator set is for.
+    pub height: i64,
+    /// Consensus backend specific validator set.
+    #[serde(with = "serde_bytes")]
+    pub meta: Vec<u8>,
+}
+
+/// A light client following the chain of consensus block headers.
+pub struct ConsensusLightClient {
+    chain_id: String,
+    trusted: Header,
+}
+
+impl ConsensusLightClient {
+    /// Create a new light client, starting at the given trusted header.
+    ///
+    /// The trusted header must be obtained out of band, e.g., from the
+    /// genesis document or a node run by the same entity. It is still checked
+    /// to be signed by the given validator set.
+    pub fn new(chain_id: &str, trusted: &SignedHeader, validators: &ValidatorSet) -> Result<Self> {
+        let trusted = verify(chain_id, trusted, validators)?;
+        Ok(Self {
+            chain_id: chain_id.to_owned(),
+            trusted,
+        })
+    }
+
+    /// Height of the latest verified header.
+    pub fn height(&self) -> i64 {
+        self.trusted.height
+    }
+
+    /// Verify the header immediately following the trusted header and, if it
+    /// is valid, make it the new trusted header.
+    pub fn advance(&mut self, header: &SignedHeader, validators: &ValidatorSet) -> Result<()> {
+        let header = verify(&self.chain_id, header, validators)?;
+        if header.height != self.trusted.height + 1
+            || header.validators_hash != self.trusted.next_validators_hash
+        {
+            return Err(ConsensusLightClientError::NotSuccessor(self.trusted.height).into());
+        }
+
+        self.trusted = header;
+        Ok(())
+    }
+
+    /// The consensus state root committed to by the trusted header.
+    ///
+    /// As headers commit to the state resulting from the previous block, the
+    /// root is the one of the state at the previous height.
+    pub fn state_root(&self) -> Result<TrustedStateRoot> {
+        if self.trusted.app_hash.len() != Hash::len() {
+            return Err(ConsensusLightClientError::NoStateRoot(self.trusted.height).into());
+        }
+
+        Ok(TrustedStateRoot::new(
+            (self.trusted.height - 1) as u64,
+            Hash::from(self.trusted.app_hash.as_slice()),
+        ))
+    }
+}
+
+/// Verify that the header is signed by the given validator set.
+fn verify(chain_id: &str, header: &SignedHeader, validators: &ValidatorSet) -> Result<Header> {
+    let (header, commit) = decode_signed_header(&header.meta)?;
+    let validators = decode_validator_set(&validators.meta)?;
+
+    if header.chain_id != chain_id {
+        return Err(ConsensusLightClientError::ChainIDMismatch.into());
+    }
+    if header.validators_hash != validator_set_hash(&validators) {
+        return Err(ConsensusLightClientError::ValidatorSetMismatch(header.height).into());
+    }
+    if commit.height != header.height
+        || commit.block_id.hash != header.hash
+        || commit.signatures.len() != validators.len()
+    {
+        return Err(ConsensusLightClientError::CommitMismatch(header.height).into());
+    }
+
+    // Voting powers are supplied by the node, so they must not wrap around.
+    let total = validators
+        .iter()
+        .try_fold(0u64, |total, v| total.checked_add(v.voting_power))
+        .ok_or(ConsensusLightClientError::VotingPowerOverflow(
+            header.height,
+        ))?;
+    let mut signed: u64 = 0;
+    for (index, (sig, validator)) in commit.signatures.iter().zip(&validators).enumerate() {
+        // Only precommits for the block count towards the quorum.
+        if sig.block_id_flag != BLOCK_ID_FLAG_COMMIT {
+            continue;
+        }
+        if sig.validator_address != validator.address {
+            return Err(ConsensusLightClientError::CommitMismatch(header.height).into());
+        }
+
+        let message = vote_sign_bytes(chain_id, &commit, sig);
+        let valid = ed25519_dalek::PublicKey::from_bytes(&validator.public_key)
+            .and_then(|pk| {
+                ed25519_dalek::Signature::from_bytes(&sig.signature)
+                    .and_then(|signature| pk.verify(&message, &signature))
+            })
+            .is_ok();
+        if !valid {
+            return Err(ConsensusLightClientError::InvalidSignature(index).into());
+        }
+        signed = signed.checked_add(validator.voting_power).ok_or(
+            ConsensusLightClientError::VotingPowerOverflow(header.height),
+        )?;
+    }
+    if u128::from(signed) * 3 <= u128::from(total) * 2 {
+        return Err(ConsensusLightClientError::InsufficientVotingPower {
+            height: header.height,
+            got: signed,
+            total,
+        }
+        .into());
+    }
+
+    Ok(header)
+}
+
+/// A decoded consensus block header.
+#[derive(Clone, Debug)]
+struct Header {
+    chain_id: String,
+    height: i64,
+    validators_hash: Vec<u8>,
+    next_validators_hash: Vec<u8>,
+    app_hash: Vec<u8>,
+    /// Hash of the header, computed while decoding.
+    hash: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct BlockID {
+    hash: Vec<u8>,
+    parts_total: u64,
+    parts_hash: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+struct CommitSig {
+    block_id_flag: u64,
+    validator_address: Vec<u8>,
+    /// Encoded timestamp, which is signed as is.
+    timestamp: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+struct Commit {
+    height: i64,
+    round: u64,
+    block_id: BlockID,
+    signatures: Vec<CommitSig>,
+}
+
+#[derive(Clone, Debug)]
+struct Validator {
+    address: Vec<u8>,
+    public_key: Vec<u8>,
+    voting_power: u64,
+}
+
+fn decode_signed_header(data: &[u8]) -> Result<(Header, Commit)> {
+    let mut header = None;
+    let mut commit = None;
+    let mut reader = Reader::new(data, "signed header");
+    while !reader.is_empty() {
+        match reader.field()? {
+            (1, WIRE_LENGTH_DELIMITED) => header = Some(decode_header(reader.length_delimited()?)?),
+            (2, WIRE_LENGTH_DELIMITED) => commit = Some(decode_commit(reader.length_delimited()?)?),
+            (_, wire_type) => reader.skip(wire_type)?,
+        }
+    }
+
+    match (header, commit) {
+        (Some(header), Some(commit)) => Ok((header, commit)),
+        _ => Err(ConsensusLightClientError::Malformed("signed header").into()),
+    }
+}
+
+fn decode_header(data: &[u8]) -> Result<Header> {
+    let mut height = 0;
+    let mut fields: Vec<Vec<u8>> = vec![vec![]; 14];
+    let mut reader = Reader::new(data, "header");
+    while !reader.is_empty() {
+        match reader.field()? {
+            (3, WIRE_VARINT) => height = reader.uvarint()? as i64,
+            (number @ 1..=14, WIRE_LENGTH_DELIMITED) => {
+                fields[number as usize - 1] = reader.length_delimited()?.to_vec()
+            }
+            (_, wire_type) => reader.skip(wire_type)?,
+        }
+    }
+
+    // The header hash is the Merkle root of its amino encoded fields, with
+    // absent fields encoded as their zero value.
+    let leaves: Vec<Vec<u8>> = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match index + 1 {
+            // Version, time and last block ID are structs, encoded bare.
+            1 | 4 | 5 => field.clone(),
+            3 => encode_uvarint(height as u64),
+            _ => encode_bytes(field),
+        })
+        .collect();
+
+    Ok(Header {
+        chain_id: String::from_utf8(fields[1].clone())
+            .map_err(|_| ConsensusLightClientError::Malformed("chain id"))?,
+        height,
+        validators_hash: fields[7].clone(),
+        next_validators_hash: fields[8].clone(),
+        app_hash: fields[10].clone(),
+        hash: simple_hash(&leaves),
+    })
+}
+
+fn decode_block_id(data: &[u8]) -> Result<BlockID> {
+    let mut block_id = BlockID::default();
+    let mut reader = Reader::new(data, "block id");
+    while !reader.is_empty() {
+        match reader.field()? {
+            (1, WIRE_LENGTH_DELIMITED) => block_id.hash = reader.length_delimited()?.to_vec(),
+            (2, WIRE_LENGTH_DELIMITED) => {
+                let mut parts = Reader::new(reader.length_delimited()?, "part set header");
+                while !parts.is_empty() {
+                    match parts.field()? {
+                        (1, WIRE_VARINT) => block_id.parts_total = parts.uvarint()?,
+                        (2, WIRE_LENGTH_DELIMITED) => {
+                            block_id.parts_hash = parts.length_delimited()?.to_vec()
+                        }
+                        (_, wire_type) => parts.skip(wire_type)?,
+                    }
+                }
+            }
+            (_, wire_type) => reader.skip(wire_type)?,
+        }
+    }
+    Ok(block_id)
+}
+
+fn decode_commit(data: &[u8]) -> Result<Commit> {
+    let mut commit = Commit {
+        height: 0,
+        round: 0,
+        block_id: BlockID::default(),
+        signatures: vec![],
+    };
+    let mut reader = Reader::new(data, "commit");
+    while !reader.is_empty() {
+        match reader.field()? {
+            (1, WIRE_VARINT) => commit.height = reader.uvarint()? as i64,
+            (2, WIRE_VARINT) => commit.round = reader.uvarint()?,
+            (3, WIRE_LENGTH_DELIMITED) => {
+                commit.block_id = decode_block_id(reader.length_delimited()?)?
+            }
+            (4, WIRE_LENGTH_DELIMITED) => commit
+                .signatures
+                .push(decode_commit_sig(reader.length_delimited()?)?),
+            (_, wire_type) => reader.skip(wire_type)?,
+        }
+    }
+    Ok(commit)
+}
+
+fn decode_commit_sig(data: &[u8]) -> Result<CommitSig> {
+    let mut sig = CommitSig {
+        block_id_flag: 0,
+        validator_address: vec![],
+        timestamp: vec![],
+        signature: vec![],
+    };
+    let mut reader = Reader::new(data, "commit signature");
+    while !reader.is_empty() {
+        match reader.field()? {
+            (1, WIRE_VARINT) => sig.block_id_flag = reader.uvarint()?,
+            (2, WIRE_LENGTH_DELIMITED) => {
+                sig.validator_address = reader.length_delimited()?.to_vec()
+            }
+            (3, WIRE_LENGTH_DELIMITED) => sig.timestamp = reader.length_delimited()?.to_vec(),
+            (4, WIRE_LENGTH_DELIMITED) => sig.signature = reader.length_delimited()?.to_vec(),
+            (_, wire_type) => reader.skip(wire_type)?,
+        }
+    }
+    Ok(sig)
+}
+
+fn decode_validator_set(data: &[u8]) -> Result<Vec<Validator>> {
+    let mut validators = vec![];
+    let mut reader = Reader::new(data, "validator set");
+    while !reader.is_empty() {
+        match reader.field()? {
+            (1, WIRE_LENGTH_DELIMITED) => {
+                validators.push(decode_validator(reader.length_delimited()?)?)
+            }
+            (_, wire_type) => reader.skip(wire_type)?,
+        }
+    }
+    Ok(validators)
+}
+
+fn decode_validator(data: &[u8]) -> Result<Validator> {
+    let mut validator = Validator {
+        address: vec![],
+        public_key: vec![],
+        voting_power: 0,
+    };
+    let mut reader = Reader::new(data, "validator");
+    while !reader.is_empty() {
+        match reader.field()? {
+            (1, WIRE_LENGTH_DELIMITED) => validator.address = reader.length_delimited()?.to_vec(),
+            (2, WIRE_LENGTH_DELIMITED) => {
+                let mut public_key = Reader::new(reader.length_delimited()?, "public key");
+                if public_key.bytes(ED25519_PUBKEY_PREFIX.len())? != &ED25519_PUBKEY_PREFIX[..] {
+                    return Err(ConsensusLightClientError::Malformed("public key").into());
+                }
+                validator.public_key = public_key.length_delimited()?.to_vec();
+            }
+            (3, WIRE_VARINT) => validator.voting_power = reader.uvarint()?,
+            (_, wire_type) => reader.skip(wire_type)?,
+        }
+    }
+
+    // Addresses are derived from public keys, so they must match.
+    if validator.public_key.len() != ED25519_PUBKEY_SIZE
+        || validator.address[..] != Sha256::digest(&validator.public_key)[..ADDRESS_SIZE]
+    {
+        return Err(ConsensusLightClientError::Malformed("validator").into());
+    }
+    Ok(validator)
+}
+
+/// Hash of a validator set, as committed to by headers.
+fn validator_set_hash(validators: &[Validator]) -> Vec<u8> {
+    let leaves: Vec<Vec<u8>> = validators
+        .iter()
+        .map(|validator| {
+            let mut public_key = ED25519_PUBKEY_PREFIX.to_vec();
+            public_key.extend(encode_bytes(&validator.public_key));
+
+            let mut leaf = vec![];
+            leaf.extend(encode_field(1, &public_key));
+            leaf.push(key(2, WIRE_VARINT));
+            leaf.extend(encode_uvarint(validator.voting_power));
+            leaf
+        })
+        .collect();
+    simple_hash(&leaves)
+}
+
+/// Bytes signed by the given commit signature, i.e. the length prefixed
+/// amino encoding of the canonical precommit vote.
+fn vote_sign_bytes(chain_id: &str, commit: &Commit, sig: &CommitSig) -> Vec<u8> {
+    let mut parts = vec![];
+    if !commit.block_id.parts_hash.is_empty() {
+        parts.extend(encode_field(1, &commit.block_id.parts_hash));
+    }
+    if commit.block_id.parts_total != 0 {
+        parts.push(key(2, WIRE_VARINT));
+        parts.extend(encode_uvarint(commit.block_id.parts_total));
+    }
+    let mut block_id = vec![];
+    if !commit.block_id.hash.is_empty() {
+        block_id.extend(encode_field(1, &commit.block_id.hash));
+    }
+    if !parts.is_empty() {
+        block_id.extend(encode_field(2, &parts));
+    }
+
+    let mut vote = vec![key(1, WIRE_VARINT)];
+    vote.extend(encode_uvarint(PRECOMMIT_TYPE));
+    if commit.height != 0 {
+        vote.push(key(2, WIRE_FIXED64));
+        vote.extend_from_slice(&commit.height.to_le_bytes());
+    }
+    if commit.round != 0 {
+        vote.push(key(3, WIRE_FIXED64));
+        vote.extend_from_slice(&(commit.round as i64).to_le_bytes());
+    }
+    if !block_id.is_empty() {
+        vote.extend(encode_field(4, &block_id));
+    }
+    if !sig.timestamp.is_empty() {
+        vote.extend(encode_field(5, &sig.timestamp));
+    }
+    if !chain_id.is_empty() {
+        vote.extend(encode_field(6, chain_id.as_bytes()));
+    }
+
+    encode_bytes(&vote)
+}
+
+/// Merkle root of the given items, as computed by Tendermint.
+fn simple_hash(items: &[Vec<u8>]) -> Vec<u8> {
+    match items.len() {
+        0 => vec![],
+        1 => leaf_hash(&items[0]),
+        n => {
+            // Split at the largest power of two smaller than the number of
+            // items.
+            let split = n.next_power_of_two() / 2;
+            inner_hash(&simple_hash(&items[..split]), &simple_hash(&items[split..]))
+        }
+    }
+}
+
+/// RFC 6962 leaf hash.
+fn leaf_hash(item: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.input(&[0x00]);
+    hasher.input(item);
+    hasher.result().to_vec()
+}
+
+/// RFC 6962 inner node hash.
+fn inner_hash(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.input(&[0x01]);
+    hasher.input(left);
+    hasher.input(right);
+    hasher.result().to_vec()
+}
+
+const WIRE_VARINT: u8 = 0;
+const WIRE_FIXED64: u8 = 1;
+const WIRE_LENGTH_DELIMITED: u8 = 2;
+const WIRE_FIXED32: u8 = 5;
+
+fn key(number: u8, wire_type: u8) -> u8 {
+    number << 3 | wire_type
+}
+
+fn encode_uvarint(mut value: u64) -> Vec<u8> {
+    let mut encoded = vec![];
+    while value >= 0x80 {
+        encoded.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    encoded.push(value as u8);
+    encoded
+}
+
+fn encode_bytes(value: &[u8]) -> Vec<u8> {
+    let mut encoded = encode_uvarint(value.len() as u64);
+    encoded.extend_from_slice(value);
+    encoded
+}
+
+fn encode_field(number: u8, value: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![key(number, WIRE_LENGTH_DELIMITED)];
+    encoded.extend(encode_bytes(value));
+    encoded
+}
+
+/// A reader of amino encoded structs.
+struct Reader<'a> {
+    data: &'a [u8],
+    what: &'static str,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], what: &'static str) -> Self {
+        Self { data, what }
+    }
+
+    fn malformed(&self) -> ConsensusLightClientError {
+        ConsensusLightClientError::Malformed(self.what)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(self.malformed().into());
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn uvarint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.bytes(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(self.malformed().into())
+    }
+
+    fn field(&mut self) -> Result<(u64, u8)> {
+        let key = self.uvarint()?;
+        Ok((key >> 3, (key & 0x7) as u8))
+    }
+
+    fn length_delimited(&mut self) -> Result<&'a [u8]> {
+        let len = self.uvarint()?;
+        if len > self.data.len() as u64 {
+            return Err(self.malformed().into());
+        }
+        self.bytes(len as usize)
+    }
+
+    fn skip(&mut self, wire_type: u8) -> Result<()> {
+        match wire_type {
+            WIRE_VARINT => self.uvarint().map(|_| ()),
+            WIRE_FIXED64 => self.bytes(8).map(|_| ()),
+            WIRE_LENGTH_DELIMITED => self.length_delimited().map(|_| ()),
+            WIRE_FIXED32 => self.bytes(4).map(|_| ()),
+            _ => Err(self.malformed().into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ed25519_dalek::{ExpandedSecretKey, PublicKey, SecretKey};
+
+    use super::*;
+
+    const CHAIN_ID: &str = "test-chain";
+
+    struct TestValidator {
+        secret: SecretKey,
+        public: PublicKey,
+        voting_power: u64,
+    }
+
+    impl TestValidator {
+        fn new(seed: u8) -> Self {
+            let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+            let public = PublicKey::from(&secret);
+            Self {
+                secret,
+                public,
+                voting_power: 10,
+            }
+        }
+
+        fn address(&self) -> Vec<u8> {
+            Sha256::digest(self.public.as_bytes())[..ADDRESS_SIZE].to_vec()
+        }
+    }
+
+    fn encode_validator_set(validators: &[TestValidator]) -> ValidatorSet {
+        let mut meta = vec![];
+        for validator in validators {
+            let mut public_key = ED25519_PUBKEY_PREFIX.to_vec();
+            public_key.extend(encode_bytes(validator.public.as_bytes()));
+
+            let mut encoded = encode_field(1, &validator.address());
+            encoded.extend(encode_field(2, &public_key));
+            encoded.push(key(3, WIRE_VARINT));
+            encoded.extend(encode_uvarint(validator.voting_power));
+            meta.extend(encode_field(1, &encoded));
+        }
+        ValidatorSet { height: 1, meta }
+    }
+
+    fn make_header(
+        height: i64,
+        app_hash: &[u8],
+        validators: &[TestValidator],
+        next_validators: &[TestValidator],
+        signers: usize,
+    ) -> SignedHeader {
+        let validators_hash = validator_set_hash(
+            &decode_validator_set(&encode_validator_set(validators).meta).unwrap(),
+        );
+        let next_validators_hash = validator_set_hash(
+            &decode_validator_set(&encode_validator_set(next_validators).meta).unwrap(),
+        );
+
+        let mut header = encode_field(2, CHAIN_ID.as_bytes());
+        header.push(key(3, WIRE_VARINT));
+        header.extend(encode_uvarint(height as u64));
+        header.extend(encode_field(8, &validators_hash));
+        header.extend(encode_field(9, &next_validators_hash));
+        header.extend(encode_field(11, app_hash));
+        let header_hash = decode_header(&header).unwrap().hash;
+
+        let mut commit = Commit {
+            height,
+            round: 0,
+            block_id: BlockID {
+                hash: header_hash.clone(),
+                ..Default::default()
+            },
+            signatures: vec![],
+        };
+        let timestamp = vec![key(1, WIRE_VARINT), 42];
+        for (index, validator) in validators.iter().enumerate() {
+            let mut sig = CommitSig {
+                block_id_flag: if index < signers {
+                    BLOCK_ID_FLAG_COMMIT
+                } else {
+                    1
+                },
+                validator_address: validator.address(),
+                timestamp: timestamp.clone(),
+                signature: vec![],
+            };
+            if index < signers {
+                let message = vote_sign_bytes(CHAIN_ID, &commit, &sig);
+                sig.signature = ExpandedSecretKey::from(&validator.secret)
+                    .sign(&message, &validator.public)
+                    .to_bytes()
+                    .to_vec();
+            }
+            commit.signatures.push(sig);
+        }
+
+        let mut encoded_commit = vec![key(1, WIRE_VARINT)];
+        encoded_commit.extend(encode_uvarint(height as u64));
+        encoded_commit.extend(encode_field(3, &encode_field(1, &header_hash)));
+        for sig in &commit.signatures {
+            let mut encoded = vec![key(1, WIRE_VARINT)];
+            encoded.extend(encode_uvarint(sig.block_id_flag));
+            encoded.extend(encode_field(2, &sig.validator_address));
+            encoded.extend(encode_field(3, &sig.timestamp));
+            if !sig.signature.is_empty() {
+                encoded.extend(encode_field(4, &sig.signature));
+            }
+            encoded_commit.extend(encode_field(4, &encoded));
+        }
+
+        let mut meta = encode_field(1, &header);
+        meta.extend(encode_field(2, &encoded_commit));
+        SignedHeader { height, meta }
+    }
+
+    #[test]
+    fn test_simple_hash() {
+        // RFC 6962 test vectors, as used by Tendermint's merkle package.
+        assert_eq!(
+            Hash::from(leaf_hash(b"").as_slice()),
+            Hash::from("6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d")
+        );
+        assert_eq!(
+            Hash::from(leaf_hash(b"L123456").as_slice()),
+            Hash::from("395aa064aa4c29f7010acfe3f25db9485bbd4b91897b6ad7ad547639252b4d56")
+        );
+        assert_eq!(
+            Hash::from(inner_hash(b"N123", b"N456").as_slice()),
+            Hash::from("aa217fe888e47007fa15edab33c2b492a722cb106c64667fc2b044444de66bbb")
+        );
+
+        // Trees are split at the largest power of two smaller than the
+        // number of items.
+        assert_eq!(simple_hash(&[]), Vec::<u8>::new());
+        assert_eq!(simple_hash(&[b"L123456".to_vec()]), leaf_hash(b"L123456"));
+        let leaves = vec![vec![1], vec![2], vec![3]];
+        assert_eq!(
+            simple_hash(&leaves),
+            inner_hash(
+                &inner_hash(&leaf_hash(&[1]), &leaf_hash(&[2])),
+                &leaf_hash(&[3])
+            )
+        );
+    }
+
+    #[test]
+    fn test_vote_sign_bytes() {
+        // Test vector from Tendermint's types package, for a precommit at
+        // height 1 and round 1 with a zero timestamp and an empty chain id.
+        let commit = Commit {
+            height: 1,
+            round: 1,
+            block_id: BlockID::default(),
+            signatures: vec![],
+        };
+        let sig = CommitSig {
+            block_id_flag: BLOCK_ID_FLAG_COMMIT,
+            validator_address: vec![],
+            timestamp: vec![
+                0x8, 0x80, 0x92, 0xb8, 0xc3, 0x98, 0xfe, 0xff, 0xff, 0xff, 0x1,
+            ],
+            signature: vec![],
+        };
+        assert_eq!(
+            vote_sign_bytes("", &commit, &sig),
+            vec![
+                0x21, // length
+                0x8, 0x2, // type
+                0x11, 0x1, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, // height
+                0x19, 0x1, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, // round
+                0x2a, 0xb, 0x8, 0x80, 0x92, 0xb8, 0xc3, 0x98, 0xfe, 0xff, 0xff, 0xff,
+                0x1, // timestamp
+            ]
+        );
+    }
+
+    #[test]
+    fn test_voting_power_overflow() {
+        let validators: Vec<_> = (1..=4)
+            .map(|seed| TestValidator {
+                voting_power: u64::max_value() / 2 + 1,
+                ..TestValidator::new(seed)
+            })
+            .collect();
+        let header = make_header(1, &[], &validators, &validators, 4);
+        let err = ConsensusLightClient::new(CHAIN_ID, &header, &encode_validator_set(&validators))
+            .err()
+            .expect("overflowing voting power should fail");
+        match err.downcast_ref::<ConsensusLightClientError>() {
+            Some(ConsensusLightClientError::VotingPowerOverflow(1)) => {}
+            other => panic!("expected voting power overflow error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_consensus_light_client() {
+        let validators: Vec<_> = (1..=4).map(TestValidator::new).collect();
+        let next_validators: Vec<_> = (5..=7).map(TestValidator::new).collect();
+        let state_root = Hash::digest_bytes(b"state");
+
+        let genesis = make_header(1, &[], &validators, &validators, 4);
+        let mut client =
+            ConsensusLightClient::new(CHAIN_ID, &genesis, &encode_validator_set(&validators))
+                .unwrap();
+        assert_eq!(client.height(), 1);
+        assert!(client.state_root().is_err(), "genesis has no state root");
+
+        // Headers must be signed by more than two thirds of the validators.
+        let header = make_header(2, state_root.as_ref(), &validators, &next_validators, 2);
+        assert!(client
+            .advance(&header, &encode_validator_set(&validators))
+            .is_err());
+
+        // Headers must be signed by the validators committed to.
+        let header = make_header(
+            2,
+            state_root.as_ref(),
+            &next_validators,
+            &next_validators,
+            3,
+        );
+        assert!(client
+            .advance(&header, &encode_validator_set(&next_validators))
+            .is_err());
+
+        let header = make_header(2, state_root.as_ref(), &validators, &next_validators, 3);
+        client
+            .advance(&header, &encode_validator_set(&validators))
+            .unwrap();
+        assert_eq!(client.height(), 2);
+        assert_eq!(
+            client.state_root().unwrap(),
+            TrustedStateRoot::new(1, state_root)
+        );
+
+        // Headers must follow the trusted one.
+        let header = make_header(
+            4,
+            state_root.as_ref(),
+            &next_validators,
+            &next_validators,
+            3,
+        );
+        assert!(client
+            .advance(&header, &encode_validator_set(&next_validators))
+            .is_err());
+
+        // Forged state roots invalidate the signatures.
+        let mut header = make_header(
+            3,
+            state_root.as_ref(),
+            &next_validators,
+            &next_validators,
+            3,
+        );
+        let position = header
+            .meta
+            .windows(Hash::len())
+            .position(|window| window == state_root.as_ref())
+            .unwrap();
+        header.meta[position] ^= 0xff;
+        assert!(client
+            .advance(&header, &encode_validator_set(&next_validators))
+            .is_err());
+        assert_eq!(client.height(), 2);
+    }
+}
@@ -0,0 +1,336 @@
+//! Verified access to consensus state.
+//!
+//! Consensus state is stored in an MKVS tree whose root is committed to by
+//! each consensus block header. Lookups are served by a node's consensus
+//! light client service together with Merkle proofs, which are verified
+//! against a trusted state root before any value is returned. Trusted roots
+//! are only taken from headers verified by a `ConsensusLightClient`.
+use std::{any::Any, time::Instant};
+
+use anyhow::{Context as _, Error, Result};
+use grpcio::{CallOption, Channel, Client};
+use io_context::Context;
+
+use oasis_core_runtime::{
+    common::{
+        cbor,
+        crypto::{
+            hash::Hash,
+            signature::{MultiSigned, PublicKey},
+        },
+        node::Node,
+        roothash::Namespace,
+        staking::Account,
+    },
+    storage::mkvs::{sync::*, Root, RootType, Tree},
+};
+
+use crate::{
+    consensus_light::{ConsensusLightClient, SignedHeader, ValidatorSet},
+    context,
+    grpc::{self, call_option, GrpcError},
+};
+
+grpc_method!(
+    METHOD_GET_SIGNED_HEADER,
+    "/oasis-core.ConsensusLight/GetSignedHeader",
+    i64,
+    SignedHeader
+);
+grpc_method!(
+    METHOD_GET_VALIDATOR_SET,
+    "/oasis-core.ConsensusLight/GetValidatorSet",
+    i64,
+    ValidatorSet
+);
+grpc_method!(
+    METHOD_STATE_SYNC_GET,
+    "/oasis-core.ConsensusLight/StateSyncGet",
+    GetRequest,
+    ProofResponse
+);
+grpc_method!(
+    METHOD_STATE_SYNC_GET_PREFIXES,
+    "/oasis-core.ConsensusLight/StateSyncGetPrefixes",
+    GetPrefixesRequest,
+    ProofResponse
+);
+grpc_method!(
+    METHOD_STATE_SYNC_ITERATE,
+    "/oasis-core.ConsensusLight/StateSyncIterate",
+    IterateRequest,
+    ProofResponse
+);
+
+// NOTE: These should be kept in sync with the key formats used by the
+// consensus applications in go/consensus/tendermint/apps.
+
+/// Key prefix of staking accounts, followed by the owner's public key.
+const STAKING_ACCOUNT_PREFIX: u8 = 0x50;
+/// Key prefix of signed nodes, followed by the hash of the node's public key.
+const REGISTRY_SIGNED_NODE_PREFIX: u8 = 0x11;
+
+/// A consensus state root obtained from a verified consensus header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrustedStateRoot {
+    height: u64,
+    hash: Hash,
+}
+
+impl TrustedStateRoot {
+    pub(crate) fn new(height: u64, hash: Hash) -> Self {
+        Self { height, hash }
+    }
+
+    /// Consensus height at which the state was committed.
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    /// Merkle root hash of the consensus state.
+    pub fn hash(&self) -> Hash {
+        self.hash
+    }
+
+    fn root(&self) -> Root {
+        Root {
+            namespace: Namespace::default(),
+            version: self.height,
+            root_type: RootType::State,
+            hash: self.hash,
+        }
+    }
+}
+
+/// A read syncer fetching consensus state proofs from a node.
+#[derive(Clone)]
+pub struct ConsensusStateSyncer {
+    client: Client,
+    deadline: Option<Instant>,
+}
+
+impl ConsensusStateSyncer {
+    /// Create a new consensus state syncer.
+    pub fn new(channel: Channel) -> Self {
+        Self {
+            client: Client::new(channel),
+            deadline: None,
+        }
+    }
+
+    /// Apply the deadline of the given context to all calls made through the
+    /// returned syncer.
+    pub fn with_context(&self, ctx: &Context) -> Self {
+        let mut syncer = self.clone();
+        syncer.deadline = context::get_deadline(ctx);
+        syncer
+    }
+
+    /// Advance the light client to the given height, verifying every header
+    /// on the way, and open a verified view of the consensus state committed
+    /// to by its trusted header.
+    ///
+    /// If the light client is already past the given height, the state
+    /// committed to by its trusted header is returned.
+    pub fn verified_state(
+        &self,
+        ctx: Context,
+        light_client: &mut ConsensusLightClient,
+        height: i64,
+    ) -> Result<ConsensusState> {
+        while light_client.height() < height {
+            let next = light_client.height() + 1;
+            let header: SignedHeader = self
+                .client
+                .unary_call(&METHOD_GET_SIGNED_HEADER, &next, self.options(&ctx))
+                .map_err(call_failed)?;
+            let validators: ValidatorSet = self
+                .client
+                .unary_call(&METHOD_GET_VALIDATOR_SET, &next, self.options(&ctx))
+                .map_err(call_failed)?;
+            light_client.advance(&header, &validators)?;
+        }
+
+        Ok(self.state(light_client.state_root()?))
+    }
+
+    /// Open a verified view of the consensus state under the given root.
+    pub fn state(&self, root: TrustedStateRoot) -> ConsensusState {
+        ConsensusState {
+            root,
+            tree: Tree::make()
+                .with_root(root.root())
+                .new(Box::new(self.clone())),
+        }
+    }
+
+    fn options(&self, ctx: &Context) -> CallOption {
+        let deadline = match (self.deadline, context::get_deadline(ctx)) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        call_option(deadline, None)
+    }
+}
+
+impl ReadSync for ConsensusStateSyncer {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn sync_get(&mut self, ctx: Context, request: GetRequest) -> Result<ProofResponse> {
+        self.client
+            .unary_call(&METHOD_STATE_SYNC_GET, &request, self.options(&ctx))
+            .map_err(call_failed)
+    }
+
+    fn sync_get_prefixes(
+        &mut self,
+        ctx: Context,
+        request: GetPrefixesRequest,
+    ) -> Result<ProofResponse> {
+        self.client
+            .unary_call(
+                &METHOD_STATE_SYNC_GET_PREFIXES,
+                &request,
+                self.options(&ctx),
+            )
+            .map_err(call_failed)
+    }
+
+    fn sync_iterate(&mut self, ctx: Context, request: IterateRequest) -> Result<ProofResponse> {
+        self.client
+            .unary_call(&METHOD_STATE_SYNC_ITERATE, &request, self.options(&ctx))
+            .map_err(call_failed)
+    }
+}
+
+/// A verified view of the consensus state.
+///
+/// All values are verified against the trusted state root, so a node can at
+/// worst fail a lookup but not forge its result.
+pub struct ConsensusState {
+    root: TrustedStateRoot,
+    tree: Tree,
+}
+
+impl ConsensusState {
+    /// The state root this view is verified against.
+    pub fn root(&self) -> TrustedStateRoot {
+        self.root
+    }
+
+    /// Fetch the raw value of the given key.
+    pub fn get(&self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.tree.get(ctx, key)
+    }
+
+    /// Fetch the staking account of the given owner.
+    ///
+    /// Owners without an account have an empty one.
+    pub fn account(&self, ctx: Context, owner: PublicKey) -> Result<Account> {
+        match self.get(ctx, &account_key(&owner))? {
+            Some(raw) => Ok(cbor::from_slice(&raw).context("account is malformed")?),
+            None => Ok(Account::default()),
+        }
+    }
+
+    /// Fetch the descriptor of the given node, if it is registered.
+    pub fn node(&self, ctx: Context, id: PublicKey) -> Result<Option<Node>> {
+        let raw = match self.get(ctx, &node_key(&id))? {
+            Some(raw) => raw,
+            None => return Ok(None),
+        };
+
+        // Signatures are not checked, as the blob is authenticated by the
+        // state proof.
+        let signed: MultiSigned = cbor::from_slice(&raw).context("signed node is malformed")?;
+        Ok(Some(
+            cbor::from_slice(&signed.blob).context("node is malformed")?,
+        ))
+    }
+}
+
+fn account_key(owner: &PublicKey) -> Vec<u8> {
+    let mut key = vec![STAKING_ACCOUNT_PREFIX];
+    key.extend_from_slice(owner.as_ref());
+    key
+}
+
+fn node_key(id: &PublicKey) -> Vec<u8> {
+    let mut key = vec![REGISTRY_SIGNED_NODE_PREFIX];
+    key.extend_from_slice(Hash::digest_bytes(id.as_ref()).as_ref());
+    key
+}
+
+fn call_failed(error: grpcio::Error) -> Error {
+    grpc::convert_error(error, |error| GrpcError::CallFailed(error).into())
+}
+
+#[cfg(test)]
+mod test {
+    use oasis_core_runtime::common::staking::GeneralAccount;
+
+    use super::*;
+
+    #[test]
+    fn test_verified_lookups() {
+        let owner = PublicKey::from(vec![1u8; 32].as_slice());
+        let node_id = PublicKey::from(vec![2u8; 32].as_slice());
+        let account = Account {
+            general: GeneralAccount {
+                nonce: 7,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let node = Node {
+            id: node_id,
+            ..Default::default()
+        };
+
+        // Build the state locally and serve lookups from the in-memory tree.
+        let mut tree = Tree::make()
+            .with_root(Root {
+                hash: Hash::empty_hash(),
+                ..Default::default()
+            })
+            .new(Box::new(NoopReadSyncer {}));
+        tree.insert(
+            Context::background(),
+            &account_key(&owner),
+            &cbor::to_vec(&account),
+        )
+        .unwrap();
+        tree.insert(
+            Context::background(),
+            &node_key(&node_id),
+            &cbor::to_vec(&MultiSigned::new(&node)),
+        )
+        .unwrap();
+        let (_, hash) = tree
+            .commit(Context::background(), Namespace::default(), 1)
+            .unwrap();
+
+        let state = ConsensusState {
+            root: TrustedStateRoot { height: 1, hash },
+            tree,
+        };
+        assert_eq!(
+            state.account(Context::background(), owner).unwrap(),
+            account
+        );
+        assert_eq!(
+            state.account(Context::background(), node_id).unwrap(),
+            Account::default()
+        );
+        assert_eq!(
+            state
+                .node(Context::background(), node_id)
+                .unwrap()
+                .map(|node| node.id),
+            Some(node_id)
+        );
+        assert!(state.node(Context::background(), owner).unwrap().is_none());
+    }
+}
@@ -0,0 +1,53 @@
+//! Deadline propagation through `Context`.
+//!
+//! A deadline added to a context applies to all calls made with the context
+//! or any of its children. Calls which are still in flight when the deadline
+//! expires fail with `GrpcError::Timeout`.
+use std::time::{Duration, Instant};
+
+use io_context::Context;
+
+const DEADLINE_CONTEXT_KEY: &'static str = "OASIS_CLIENT_DEADLINE";
+
+/// Add a deadline to the provided `Context`.
+///
+/// If the context already has an earlier deadline, it is kept.
+pub fn add_deadline(ctx: &mut Context, deadline: Instant) {
+    let deadline = match get_deadline(ctx) {
+        Some(current) => current.min(deadline),
+        None => deadline,
+    };
+    ctx.add_value(DEADLINE_CONTEXT_KEY, deadline);
+}
+
+/// Add a deadline the given duration from now to the provided `Context`.
+pub fn add_timeout(ctx: &mut Context, timeout: Duration) {
+    add_deadline(ctx, Instant::now() + timeout);
+}
+
+/// Retrieve the deadline from the provided `Context`.
+pub fn get_deadline(ctx: &Context) -> Option<Instant> {
+    ctx.get_value(DEADLINE_CONTEXT_KEY).cloned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_deadline() {
+        let mut ctx = Context::background();
+        assert_eq!(get_deadline(&ctx), None);
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        add_deadline(&mut ctx, deadline);
+        assert_eq!(get_deadline(&ctx), Some(deadline));
+
+        // Children inherit the deadline and cannot extend it.
+        let ctx = ctx.freeze();
+        let mut child = Context::create_child(&ctx);
+        assert_eq!(get_deadline(&child), Some(deadline));
+        add_timeout(&mut child, Duration::from_secs(60));
+        assert_eq!(get_deadline(&child), Some(deadline));
+    }
+}
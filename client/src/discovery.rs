@@ -0,0 +1,403 @@
+//! Node discovery from the registry.
+//!
+//! Nodes serving a runtime are discovered from the registry and ranked by
+//! their roles and measured latency. As committees only change at epoch
+//! transitions, the endpoint set is refreshed whenever the epoch changes.
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use anyhow::Error;
+use futures::{future, prelude::*};
+use grpcio::{CallOption, Channel, Client, Environment, RpcStatusCode};
+use serde_cbor::value::Value;
+use serde_derive::{Deserialize, Serialize};
+use tokio::timer::Interval;
+
+use oasis_core_runtime::{
+    common::{
+        crypto::{hash::Hash, signature::PublicKey},
+        node::{Node as NodeDescriptor, ROLE_COMPUTE_WORKER, ROLE_STORAGE_WORKER},
+        roothash::Namespace,
+        runtime::RuntimeId,
+    },
+    storage::mkvs::{
+        sync::{GetRequest, TreeID},
+        Root,
+    },
+};
+
+use crate::{
+    consensus::{ConsensusClient, HEIGHT_LATEST},
+    grpc::UnaryResponse,
+    node::{self, Node, TlsConfig},
+    registry::RegistryClient,
+    BoxFuture,
+};
+
+// Services served on committee addresses, used to probe nodes. Responses are
+// never decoded, so any response is accepted.
+grpc_method!(
+    METHOD_STORAGE_SYNC_GET,
+    "/oasis-core.Storage/SyncGet",
+    GetRequest,
+    Value
+);
+grpc_method!(
+    METHOD_IS_TRANSACTION_QUEUED,
+    "/oasis-core.TransactionScheduler/IsTransactionQueued",
+    IsTransactionQueuedRequest,
+    Value
+);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct IsTransactionQueuedRequest {
+    runtime_id: RuntimeId,
+    tx_hash: Hash,
+}
+
+/// Server name in the TLS certificates of committee nodes.
+pub const COMMITTEE_TLS_SERVER_NAME: &str = "oasis-node";
+/// Default timeout for latency probes.
+pub const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A discovered node endpoint.
+#[derive(Clone, Debug)]
+pub struct Endpoint {
+    /// Public key identifying the node.
+    pub node_id: PublicKey,
+    /// Bitmask of the node's roles.
+    pub roles: u32,
+    /// Address of the node.
+    pub address: SocketAddr,
+    /// DER-encoded TLS certificate of the node.
+    pub certificate: Vec<u8>,
+    /// Round-trip latency of the last probe, if the node responded.
+    pub latency: Option<Duration>,
+}
+
+impl Endpoint {
+    /// TLS configuration which authenticates the node by its certificate.
+    pub fn tls_config(&self) -> TlsConfig {
+        let mut pem = b"-----BEGIN CERTIFICATE-----\n".to_vec();
+        for line in base64::encode(&self.certificate).as_bytes().chunks(64) {
+            pem.extend_from_slice(line);
+            pem.push(b'\n');
+        }
+        pem.extend_from_slice(b"-----END CERTIFICATE-----\n");
+
+        TlsConfig {
+            root_certs: Some(pem),
+            server_name: Some(COMMITTEE_TLS_SERVER_NAME.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    /// Connect to the node using the given connection options.
+    pub fn connect(&self, environment: Arc<Environment>, options: node::Options) -> Node {
+        options
+            .with_tls(self.tls_config())
+            .new(environment, &self.address.to_string())
+    }
+}
+
+/// Discovery of the nodes serving a runtime.
+#[derive(Clone)]
+pub struct Discovery {
+    environment: Arc<Environment>,
+    registry: RegistryClient,
+    consensus: ConsensusClient,
+    runtime_id: RuntimeId,
+    roles: Vec<u32>,
+    options: node::Options,
+    probe_timeout: Duration,
+    endpoints: Arc<RwLock<Vec<Endpoint>>>,
+    epoch: Arc<Mutex<Option<u64>>>,
+}
+
+impl Discovery {
+    /// Create a new discovery of the nodes serving the given runtime, using
+    /// the consensus services of the node behind the given channel.
+    pub fn new(environment: Arc<Environment>, channel: Channel, runtime_id: RuntimeId) -> Self {
+        Self {
+            environment,
+            registry: RegistryClient::new(channel.clone()),
+            consensus: ConsensusClient::new(channel),
+            runtime_id,
+            roles: vec![ROLE_COMPUTE_WORKER, ROLE_STORAGE_WORKER],
+            options: node::Options::default(),
+            probe_timeout: DEFAULT_PROBE_TIMEOUT,
+            endpoints: Arc::new(RwLock::new(vec![])),
+            epoch: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Set the roles of the nodes to discover, in order of preference.
+    ///
+    /// Nodes with an earlier role rank ahead of nodes with a later one, and
+    /// nodes with none of the roles are ignored.
+    pub fn with_roles(mut self, roles: Vec<u32>) -> Self {
+        assert!(!roles.is_empty(), "at least one role is required");
+        self.roles = roles;
+        self
+    }
+
+    /// Set the options used to connect to discovered nodes.
+    pub fn with_node_options(mut self, options: node::Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set the timeout for latency probes.
+    pub fn with_probe_timeout(mut self, probe_timeout: Duration) -> Self {
+        self.probe_timeout = probe_timeout;
+        self
+    }
+
+    /// Current endpoints, best first.
+    pub fn endpoints(&self) -> Vec<Endpoint> {
+        self.endpoints.read().unwrap().clone()
+    }
+
+    /// Best current endpoint, if any.
+    pub fn best(&self) -> Option<Endpoint> {
+        self.endpoints.read().unwrap().first().cloned()
+    }
+
+    /// Connect to the best current endpoint, if any.
+    pub fn connect(&self) -> Option<Node> {
+        self.best()
+            .map(|endpoint| endpoint.connect(self.environment.clone(), self.options.clone()))
+    }
+
+    /// Discover the nodes serving the runtime, probe them and update the
+    /// endpoint set.
+    pub fn discover(&self) -> BoxFuture<Vec<Endpoint>> {
+        let discovery = self.clone();
+
+        Box::new(
+            self.registry
+                .get_nodes(HEIGHT_LATEST)
+                .and_then(move |nodes| {
+                    let probes: Vec<_> = nodes
+                        .iter()
+                        .filter_map(|node| discovery.endpoint(node))
+                        .map(|endpoint| discovery.probe(endpoint))
+                        .collect();
+
+                    future::join_all(probes).map(move |mut endpoints| {
+                        endpoints.sort_by_key(|endpoint| {
+                            (
+                                endpoint.latency.is_none(),
+                                discovery.role_rank(endpoint.roles),
+                                endpoint.latency,
+                            )
+                        });
+                        *discovery.endpoints.write().unwrap() = endpoints.clone();
+                        endpoints
+                    })
+                }),
+        )
+    }
+
+    /// Discover the nodes again if the epoch changed since the last
+    /// discovery.
+    ///
+    /// Returns true if the endpoint set was updated.
+    pub fn refresh(&self) -> BoxFuture<bool> {
+        let discovery = self.clone();
+
+        Box::new(self.consensus.get_epoch(HEIGHT_LATEST).and_then(
+            move |epoch| -> BoxFuture<bool> {
+                if *discovery.epoch.lock().unwrap() == Some(epoch) {
+                    return Box::new(future::ok(false));
+                }
+
+                let discovery2 = discovery.clone();
+                Box::new(discovery.discover().map(move |_| {
+                    *discovery2.epoch.lock().unwrap() = Some(epoch);
+                    true
+                }))
+            },
+        ))
+    }
+
+    /// Keep the endpoint set up to date by checking for epoch transitions at
+    /// the given interval.
+    ///
+    /// Failed refreshes are retried at the next interval.
+    pub fn maintain(self, interval: Duration) -> impl Future<Item = (), Error = Error> {
+        Interval::new(Instant::now(), interval)
+            .map_err(Error::from)
+            .for_each(move |_| self.refresh().then(|_| Ok::<_, Error>(())))
+    }
+
+    fn endpoint(&self, node: &NodeDescriptor) -> Option<Endpoint> {
+        if node.get_runtime(&self.runtime_id).is_none()
+            || !self.roles.iter().any(|role| node.has_roles(*role))
+        {
+            return None;
+        }
+
+        node.committee.addresses.iter().flatten().find_map(|addr| {
+            addr.address.to_socket_addr().map(|address| Endpoint {
+                node_id: node.id,
+                roles: node.roles,
+                address,
+                certificate: addr.certificate.to_vec(),
+                latency: None,
+            })
+        })
+    }
+
+    fn role_rank(&self, roles: u32) -> usize {
+        self.roles
+            .iter()
+            .position(|role| roles & role == *role)
+            .unwrap_or(self.roles.len())
+    }
+
+    /// Measure the round-trip latency to the node.
+    ///
+    /// Storage nodes are probed through their storage service and other nodes
+    /// through their transaction scheduler, as these are served on committee
+    /// addresses. Any response counts, including errors, e.g., as the probe
+    /// asks for a root the node does not have.
+    fn probe(&self, mut endpoint: Endpoint) -> BoxFuture<Endpoint> {
+        let node = endpoint.connect(self.environment.clone(), self.options.clone());
+        let client = Client::new(node.channel());
+        let options = CallOption::default().timeout(self.probe_timeout);
+        let start = Instant::now();
+
+        let rsp = if endpoint.roles & ROLE_STORAGE_WORKER != 0 {
+            let request = GetRequest {
+                tree: TreeID {
+                    root: Root {
+                        namespace: Namespace::from(self.runtime_id.as_ref()),
+                        hash: Hash::empty_hash(),
+                        ..Default::default()
+                    },
+                    position: Hash::empty_hash(),
+                },
+                key: vec![],
+                include_siblings: false,
+                compressed: false,
+            };
+            client.unary_call_async(&METHOD_STORAGE_SYNC_GET, &request, options)
+        } else {
+            let request = IsTransactionQueuedRequest {
+                runtime_id: self.runtime_id,
+                tx_hash: Hash::empty_hash(),
+            };
+            client.unary_call_async(&METHOD_IS_TRANSACTION_QUEUED, &request, options)
+        };
+
+        match rsp {
+            Ok(rsp) => Box::new(UnaryResponse::new(rsp).then(move |result| {
+                endpoint.latency = if responded(&result) {
+                    Some(start.elapsed())
+                } else {
+                    None
+                };
+                Ok(endpoint)
+            })),
+            Err(_) => Box::new(future::ok(endpoint)),
+        }
+    }
+}
+
+/// Whether the result of a call shows that the node responded.
+fn responded<T>(result: &grpcio::Result<T>) -> bool {
+    match result {
+        Ok(_) => true,
+        Err(grpcio::Error::RpcFailure(status)) => match status.status {
+            RpcStatusCode::Unavailable
+            | RpcStatusCode::DeadlineExceeded
+            | RpcStatusCode::Cancelled => false,
+            _ => true,
+        },
+        // The node responded with something other than CBOR.
+        Err(grpcio::Error::Codec(_)) => true,
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use oasis_core_runtime::common::node::{
+        Address, CommitteeAddress, CommitteeInfo, Runtime, ROLE_KEY_MANAGER,
+    };
+    use serde_bytes::ByteBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_endpoint_selection() {
+        let environment = Arc::new(Environment::new(1));
+        let channel = grpcio::ChannelBuilder::new(environment.clone()).connect("localhost:0");
+        let runtime_id = RuntimeId::from(vec![1u8; 32].as_slice());
+        let discovery = Discovery::new(environment, channel, runtime_id)
+            .with_roles(vec![ROLE_STORAGE_WORKER, ROLE_COMPUTE_WORKER]);
+
+        let mut node = NodeDescriptor {
+            roles: ROLE_COMPUTE_WORKER,
+            runtimes: Some(vec![Runtime {
+                id: runtime_id,
+                ..Default::default()
+            }]),
+            committee: CommitteeInfo {
+                addresses: Some(vec![CommitteeAddress {
+                    certificate: ByteBuf::from(vec![1, 2, 3]),
+                    address: Address {
+                        ip: ByteBuf::from(vec![127, 0, 0, 1]),
+                        port: 9100,
+                        zone: String::new(),
+                    },
+                }]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let endpoint = discovery.endpoint(&node).expect("node should be selected");
+        assert_eq!(endpoint.address, "127.0.0.1:9100".parse().unwrap());
+        assert_eq!(endpoint.certificate, vec![1, 2, 3]);
+        assert_eq!(discovery.role_rank(endpoint.roles), 1);
+        assert_eq!(
+            discovery.role_rank(ROLE_STORAGE_WORKER | ROLE_COMPUTE_WORKER),
+            0
+        );
+
+        let tls = endpoint.tls_config();
+        assert_eq!(
+            tls.root_certs.unwrap(),
+            b"-----BEGIN CERTIFICATE-----\nAQID\n-----END CERTIFICATE-----\n".to_vec()
+        );
+
+        // Nodes without a matching role are ignored.
+        node.roles = ROLE_KEY_MANAGER;
+        assert!(discovery.endpoint(&node).is_none());
+
+        // Nodes not serving the runtime are ignored.
+        node.roles = ROLE_COMPUTE_WORKER;
+        node.runtimes = None;
+        assert!(discovery.endpoint(&node).is_none());
+    }
+
+    #[test]
+    fn test_probe_responses() {
+        let failure = |status| {
+            Err::<(), _>(grpcio::Error::RpcFailure(grpcio::RpcStatus::new(
+                status, None,
+            )))
+        };
+
+        assert!(responded(&Ok(())));
+        // Errors returned by the node still measure the round trip.
+        assert!(responded(&failure(RpcStatusCode::NotFound)));
+        assert!(responded(&failure(RpcStatusCode::Unimplemented)));
+        assert!(!responded(&failure(RpcStatusCode::Unavailable)));
+        assert!(!responded(&failure(RpcStatusCode::DeadlineExceeded)));
+    }
+}
@@ -0,0 +1,147 @@
+//! Executors the clients spawn their background tasks on.
+//!
+//! Clients run some work in background tasks (e.g., the enclave RPC client's
+//! session controller and the transaction client's block watcher). These are
+//! spawned through a `Spawner`, which can be configured when the clients are
+//! built, so they can be embedded in applications running their own
+//! executors.
+use std::sync::Arc;
+
+use futures::Future;
+use tokio_current_thread::TaskExecutor;
+use tokio_executor::{DefaultExecutor, Executor};
+
+/// A background task.
+pub type Task = Box<dyn Future<Item = (), Error = ()> + Send>;
+
+/// A spawner of background tasks.
+pub trait Spawner: Send + Sync {
+    /// Spawn the given task.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the task cannot be spawned, e.g., because the executor has
+    /// been shut down.
+    fn spawn(&self, task: Task);
+}
+
+/// A spawner of tasks on a tokio executor.
+///
+/// By default, tasks are spawned on the default executor of the thread
+/// which spawns them, i.e., the executor the client's futures are polled on.
+/// To spawn tasks on a particular runtime instead, use the runtime's
+/// executor, e.g., `TokioSpawner::new(runtime.executor())`.
+#[derive(Clone, Debug)]
+pub struct TokioSpawner<E = DefaultExecutor> {
+    executor: E,
+}
+
+impl<E> TokioSpawner<E>
+where
+    E: Executor + Clone + Send + Sync,
+{
+    /// Create a new spawner of tasks on the given executor.
+    pub fn new(executor: E) -> Self {
+        Self { executor }
+    }
+}
+
+impl Default for TokioSpawner {
+    fn default() -> Self {
+        Self::new(DefaultExecutor::current())
+    }
+}
+
+impl<E> Spawner for TokioSpawner<E>
+where
+    E: Executor + Clone + Send + Sync,
+{
+    fn spawn(&self, task: Task) {
+        self.executor
+            .clone()
+            .spawn(task)
+            .expect("failed to spawn task");
+    }
+}
+
+/// A spawner of tasks on the current-thread executor running on the thread
+/// which spawns them.
+///
+/// This is meant for single-threaded environments, e.g., SGX enclaves.
+#[derive(Clone, Debug, Default)]
+pub struct CurrentThreadSpawner;
+
+impl Spawner for CurrentThreadSpawner {
+    fn spawn(&self, task: Task) {
+        TaskExecutor::current()
+            .spawn_local(task)
+            .expect("failed to spawn task");
+    }
+}
+
+/// The spawner used by clients unless configured otherwise.
+///
+/// In SGX enclaves, where the runtime runs a single-threaded executor,
+/// this is a `CurrentThreadSpawner` and a default `TokioSpawner` elsewhere.
+pub fn default_spawner() -> Arc<dyn Spawner> {
+    #[cfg(target_env = "sgx")]
+    {
+        Arc::new(CurrentThreadSpawner)
+    }
+
+    #[cfg(not(target_env = "sgx"))]
+    {
+        Arc::new(TokioSpawner::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::{future, sync::oneshot};
+    use tokio::runtime::Runtime;
+    use tokio_current_thread::CurrentThread;
+
+    use super::*;
+
+    fn task(tx: oneshot::Sender<u64>) -> Task {
+        Box::new(future::lazy(move || {
+            drop(tx.send(42));
+            Ok(())
+        }))
+    }
+
+    #[test]
+    fn test_tokio_spawner() {
+        let runtime = Runtime::new().unwrap();
+        let spawner = TokioSpawner::new(runtime.executor());
+
+        // Tasks are spawned on the given runtime, even if the spawning thread
+        // has no executor.
+        let (tx, rx) = oneshot::channel();
+        spawner.spawn(task(tx));
+        assert_eq!(rx.wait().unwrap(), 42);
+
+        // Or on the executor of the spawning thread by default.
+        let (tx, rx) = oneshot::channel();
+        let value = runtime
+            .block_on_all(future::lazy(move || {
+                TokioSpawner::default().spawn(task(tx));
+                rx
+            }))
+            .unwrap();
+        assert_eq!(value, 42);
+    }
+
+    #[test]
+    fn test_current_thread_spawner() {
+        let mut executor = CurrentThread::new();
+        let (tx, rx) = oneshot::channel();
+        let value = executor
+            .block_on(future::lazy(move || {
+                CurrentThreadSpawner.spawn(task(tx));
+                rx
+            }))
+            .unwrap();
+        assert_eq!(value, 42);
+    }
+}
@@ -1,8 +1,30 @@
 //! Helpers for calling Oasis gRPC services.
-use grpcio::{Error, Result};
+use std::time::{Duration, Instant};
+
+use futures::{future, prelude::*};
+use grpcio::{
+    CallOption, ClientUnaryReceiver, Error, Error::RpcFailure, MetadataBuilder, Result, RpcStatus,
+    RpcStatusCode,
+};
 use serde::{de::DeserializeOwned, Serialize};
 
-use oasis_core_runtime::common::cbor;
+use oasis_core_runtime::{
+    common::cbor,
+    tracing::{SpanContext, TRACE_HEADER},
+};
+
+use crate::BoxFuture;
+
+/// gRPC call error.
+#[derive(Debug, thiserror::Error)]
+pub enum GrpcError {
+    #[error("node call failed: {0}")]
+    CallFailed(String),
+    #[error("call deadline exceeded")]
+    Timeout,
+    #[error("call cancelled")]
+    Cancelled,
+}
 
 /// CBOR-encoded NULL value.
 static CBOR_NULL: &'static [u8] = &[0xF6];
@@ -32,6 +54,132 @@ where
     cbor::from_slice(buf).map_err(|e| Error::Codec(Box::new(e)))
 }
 
+/// Convert a gRPC error.
+///
+/// Expired deadlines and cancelled calls are reported as `GrpcError::Timeout`
+/// and `GrpcError::Cancelled`, other errors are converted using the given
+/// function.
+pub(crate) fn convert_error<F>(error: Error, call_failed: F) -> anyhow::Error
+where
+    F: FnOnce(String) -> anyhow::Error,
+{
+    match error {
+        RpcFailure(RpcStatus {
+            status: RpcStatusCode::DeadlineExceeded,
+            ..
+        }) => GrpcError::Timeout.into(),
+        RpcFailure(RpcStatus {
+            status: RpcStatusCode::Cancelled,
+            ..
+        }) => GrpcError::Cancelled.into(),
+        error => call_failed(format!("{}", error)),
+    }
+}
+
+/// Prepare call options honoring the given deadline and per-call timeout.
+///
+/// If the deadline has already expired, the call fails immediately with a
+/// deadline exceeded status.
+pub(crate) fn call_option(deadline: Option<Instant>, timeout: Option<Duration>) -> CallOption {
+    let remaining = deadline.map(|deadline| {
+        deadline
+            .checked_duration_since(Instant::now())
+            .unwrap_or(Duration::from_secs(0))
+    });
+    let timeout = match (remaining, timeout) {
+        (Some(remaining), Some(timeout)) => Some(remaining.min(timeout)),
+        (remaining, timeout) => remaining.or(timeout),
+    };
+
+    match timeout {
+        Some(timeout) => CallOption::default().timeout(timeout),
+        None => CallOption::default(),
+    }
+}
+
+/// Propagate the given span context to the node in the call metadata.
+pub(crate) fn with_span_context(options: CallOption, span_context: &SpanContext) -> CallOption {
+    let mut metadata = MetadataBuilder::new();
+    metadata
+        .add_str(TRACE_HEADER, &span_context.to_header_value())
+        .expect("span context header must be valid");
+    options.headers(metadata.build())
+}
+
+/// A unary call response which cancels the call when dropped before it
+/// completes.
+pub(crate) struct UnaryResponse<T> {
+    rsp: ClientUnaryReceiver<T>,
+    done: bool,
+}
+
+impl<T> UnaryResponse<T> {
+    pub(crate) fn new(rsp: ClientUnaryReceiver<T>) -> Self {
+        Self { rsp, done: false }
+    }
+}
+
+impl<T> Future for UnaryResponse<T> {
+    type Item = T;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<T, Error> {
+        let result = self.rsp.poll();
+        match result {
+            Ok(Async::NotReady) => {}
+            _ => self.done = true,
+        }
+        result
+    }
+}
+
+impl<T> Drop for UnaryResponse<T> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.rsp.cancel();
+        }
+    }
+}
+
+/// Wrap the response of a unary call into a future.
+pub(crate) fn unary_call<T>(rsp: Result<ClientUnaryReceiver<T>>) -> BoxFuture<T>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    match rsp {
+        Ok(rsp) => Box::new(
+            UnaryResponse::new(rsp)
+                .map_err(|error| convert_error(error, |error| GrpcError::CallFailed(error).into())),
+        ),
+        Err(error) => Box::new(future::err(convert_error(error, |error| {
+            GrpcError::CallFailed(error).into()
+        }))),
+    }
+}
+
+/// Wrap the response of a unary call into a future, mapping a not found
+/// status to `None`.
+pub(crate) fn unary_call_optional<T>(rsp: Result<ClientUnaryReceiver<T>>) -> BoxFuture<Option<T>>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    match rsp {
+        Ok(rsp) => Box::new(UnaryResponse::new(rsp).then(|result| match result {
+            Err(RpcFailure(RpcStatus {
+                status: RpcStatusCode::NotFound,
+                ..
+            })) => Ok(None),
+            Err(error) => Err(convert_error(error, |error| {
+                GrpcError::CallFailed(error).into()
+            })),
+            Ok(rsp) => Ok(Some(rsp)),
+        })),
+        Err(error) => Box::new(future::err(convert_error(error, |error| {
+            GrpcError::CallFailed(error).into()
+        }))),
+    }
+}
+
 /// A helper macro for defining gRPC methods using the CBOR codec.
 macro_rules! grpc_method {
     ($id:ident, $name:expr, $rq:ty, $rsp:ty) => {
@@ -76,4 +224,31 @@ mod test {
     fn test_empty_cbor_decode() {
         let _: () = cbor_decode(&[]).unwrap();
     }
+
+    #[test]
+    fn test_convert_error() {
+        let status = |status| {
+            RpcFailure(RpcStatus {
+                status,
+                details: None,
+            })
+        };
+        let call_failed = |error| GrpcError::CallFailed(error).into();
+
+        let error = convert_error(status(RpcStatusCode::DeadlineExceeded), call_failed);
+        match error.downcast_ref::<GrpcError>() {
+            Some(GrpcError::Timeout) => {}
+            _ => panic!("expected timeout, got: {}", error),
+        }
+        let error = convert_error(status(RpcStatusCode::Cancelled), call_failed);
+        match error.downcast_ref::<GrpcError>() {
+            Some(GrpcError::Cancelled) => {}
+            _ => panic!("expected cancellation, got: {}", error),
+        }
+        let error = convert_error(status(RpcStatusCode::Unavailable), call_failed);
+        match error.downcast_ref::<GrpcError>() {
+            Some(GrpcError::CallFailed(_)) => {}
+            _ => panic!("expected call failure, got: {}", error),
+        }
+    }
 }
@@ -0,0 +1,151 @@
+//! Client for service defined in go/keymanager/api.
+use std::time::Instant;
+
+use anyhow::Error;
+use futures::{prelude::*, stream};
+use grpcio::{CallOption, Channel, Client};
+use io_context::Context;
+
+use oasis_core_keymanager_api_common::Status;
+use oasis_core_runtime::common::runtime::RuntimeId;
+
+use crate::{
+    context,
+    grpc::{self, call_option, unary_call_optional, GrpcError},
+    registry::NamespaceQuery,
+    BoxFuture,
+};
+
+pub use crate::consensus::HEIGHT_LATEST;
+
+grpc_method!(
+    METHOD_GET_STATUS,
+    "/oasis-core.KeyManager/GetStatus",
+    NamespaceQuery,
+    Status
+);
+grpc_stream!(
+    METHOD_WATCH_STATUSES,
+    "/oasis-core.KeyManager/WatchStatuses",
+    (),
+    Status
+);
+
+/// A stream of key manager status changes.
+pub type StatusStream = Box<dyn Stream<Item = Status, Error = Error> + Send>;
+
+/// A key manager gRPC service client.
+#[derive(Clone)]
+pub struct KeyManagerStatusClient {
+    client: Client,
+    deadline: Option<Instant>,
+}
+
+impl KeyManagerStatusClient {
+    /// Create a new key manager status client.
+    pub fn new(channel: Channel) -> Self {
+        KeyManagerStatusClient {
+            client: Client::new(channel),
+            deadline: None,
+        }
+    }
+
+    /// Apply the deadline of the given context to all calls made through the
+    /// returned client.
+    pub fn with_context(&self, ctx: &Context) -> Self {
+        let mut client = self.clone();
+        client.deadline = context::get_deadline(ctx);
+        client
+    }
+
+    /// Retrieve the status of the given key manager runtime, if it exists.
+    pub fn get_status(&self, id: RuntimeId, height: i64) -> BoxFuture<Option<Status>> {
+        let request = NamespaceQuery { height, id };
+        unary_call_optional(self.client.unary_call_async(
+            &METHOD_GET_STATUS,
+            &request,
+            self.options(),
+        ))
+    }
+
+    /// Subscribe to status updates of all key manager runtimes.
+    pub fn watch_statuses(&self) -> StatusStream {
+        match self
+            .client
+            .server_streaming(&METHOD_WATCH_STATUSES, &(), Default::default())
+        {
+            Ok(statuses) => Box::new(statuses.map_err(call_failed)),
+            Err(error) => Box::new(stream::once(Err(call_failed(error)))),
+        }
+    }
+
+    /// Subscribe to status changes of the given key manager runtime.
+    ///
+    /// The stream starts with the current status and then yields a new status
+    /// whenever the key manager is initialized, its policy is updated or its
+    /// master secret checksum changes. This can be used to stop serving
+    /// confidential requests while the key manager is not ready.
+    pub fn watch_status(&self, id: RuntimeId) -> StatusStream {
+        let updates = self.watch_statuses().filter(move |status| status.id == id);
+        let mut last: Option<Status> = None;
+
+        Box::new(
+            self.get_status(id, HEIGHT_LATEST)
+                .into_stream()
+                .filter_map(|status| status)
+                .chain(updates)
+                .filter(move |status| {
+                    let changed = last.as_ref().map_or(true, |last| has_changed(last, status));
+                    last = Some(status.clone());
+                    changed
+                }),
+        )
+    }
+
+    fn options(&self) -> CallOption {
+        call_option(self.deadline, None)
+    }
+}
+
+/// Check whether any of the status fields relevant for serving confidential
+/// requests changed.
+fn has_changed(old: &Status, new: &Status) -> bool {
+    old.is_initialized != new.is_initialized
+        || old.is_secure != new.is_secure
+        || old.checksum != new.checksum
+        || old.policy_serial() != new.policy_serial()
+}
+
+fn call_failed(error: grpcio::Error) -> Error {
+    grpc::convert_error(error, |error| GrpcError::CallFailed(error).into())
+}
+
+#[cfg(test)]
+mod test {
+    use serde_bytes::ByteBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_status_changes() {
+        let status = Status {
+            id: RuntimeId::default(),
+            is_initialized: false,
+            is_secure: true,
+            checksum: None,
+            nodes: None,
+            policy: None,
+        };
+        assert!(!has_changed(&status, &status.clone()));
+
+        let mut initialized = status.clone();
+        initialized.is_initialized = true;
+        initialized.checksum = Some(ByteBuf::from(vec![1; 32]));
+        assert!(has_changed(&status, &initialized));
+
+        // Node set changes do not affect serving.
+        let mut nodes = initialized.clone();
+        nodes.nodes = Some(vec![Default::default()]);
+        assert!(!has_changed(&initialized, &nodes));
+    }
+}
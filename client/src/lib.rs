@@ -1,19 +1,56 @@
 //! Oasis Core client library.
 
-#[cfg(not(target_env = "sgx"))]
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
 #[macro_use]
 pub mod grpc;
-#[cfg(not(target_env = "sgx"))]
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub mod builder;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub mod circuit_breaker;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub mod config;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub mod consensus;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub mod consensus_light;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub mod consensus_state;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub mod context;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub mod discovery;
+pub mod executor;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub mod keymanager;
+pub mod light_client;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub mod metrics;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
 pub mod node;
+pub mod pagination;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub mod registry;
 // TODO: Rename "rpc" module to "enclave_rpc" or similar.
 pub mod rpc;
-#[cfg(not(target_env = "sgx"))]
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub mod scheduler;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub mod staking;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
 pub mod transaction;
 
+/// Dependencies used by the exported macros.
+#[doc(hidden)]
+pub mod __private {
+    pub use anyhow;
+    pub use io_context;
+    pub use oasis_core_runtime;
+}
+
 /// Boxed future type.
-pub type BoxFuture<T> = Box<dyn futures::Future<Item = T, Error = failure::Error> + Send>;
+pub type BoxFuture<T> = Box<dyn futures::Future<Item = T, Error = anyhow::Error> + Send>;
 
 // Re-exports.
-pub use self::rpc::RpcClient;
-#[cfg(not(target_env = "sgx"))]
-pub use self::{node::Node, transaction::TxnClient};
+pub use self::rpc::{RpcClient, Transport};
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub use self::{builder::ClientBuilder, node::Node, transaction::TxnClient};
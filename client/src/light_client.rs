@@ -0,0 +1,256 @@
+//! Light client verification of runtime block headers.
+//!
+//! Headers returned by a node are only trusted once they have been linked to
+//! a trusted header via their previous hash and, for rounds which produce new
+//! roots, carry valid storage receipts from enough members of the storage
+//! committee.
+//!
+//! As verification is local to the client, receipt signatures are checked
+//! with batch verification (see `PublicKey::verify_batch`).
+use std::collections::HashSet;
+
+use anyhow::Result;
+use oasis_core_runtime::common::{
+    crypto::signature::PublicKey,
+    roothash::{storage_receipt_context, Header, HeaderError, HeaderType},
+};
+use thiserror::Error;
+
+/// Light client verification error.
+#[derive(Debug, Error)]
+pub enum LightClientError {
+    #[error("header has unexpected namespace")]
+    NamespaceMismatch,
+    #[error("header is not the successor of round {0}")]
+    NotSuccessor(u64),
+    #[error("header for round {0} changes state without receipts")]
+    UnexpectedStateChange(u64),
+    #[error("not enough storage receipts for round {round} (got: {got} required: {required})")]
+    InsufficientReceipts {
+        round: u64,
+        got: usize,
+        required: usize,
+    },
+}
+
+/// A light client following a runtime's chain of block headers.
+pub struct LightClient {
+    chain_context: String,
+    storage_committee: HashSet<PublicKey>,
+    threshold: usize,
+    trusted: Header,
+}
+
+impl LightClient {
+    /// Create a new light client, starting at the given trusted header.
+    ///
+    /// The trusted header must be obtained out of band, e.g., from the
+    /// consensus layer. Headers with new roots must carry storage receipts
+    /// from at least `threshold` members of the given storage committee.
+    pub fn new(
+        trusted: Header,
+        chain_context: &str,
+        storage_committee: Vec<PublicKey>,
+        threshold: usize,
+    ) -> Self {
+        assert!(threshold > 0, "threshold must be positive");
+
+        Self {
+            chain_context: chain_context.to_owned(),
+            storage_committee: storage_committee.into_iter().collect(),
+            threshold,
+            trusted,
+        }
+    }
+
+    /// The latest verified header.
+    pub fn trusted_header(&self) -> &Header {
+        &self.trusted
+    }
+
+    /// Replace the storage committee, e.g., after an epoch transition.
+    pub fn set_storage_committee(&mut self, storage_committee: Vec<PublicKey>, threshold: usize) {
+        assert!(threshold > 0, "threshold must be positive");
+
+        self.storage_committee = storage_committee.into_iter().collect();
+        self.threshold = threshold;
+    }
+
+    /// Verify that the header's roots have been certified by the storage
+    /// committee.
+    ///
+    /// This does not check that the header is part of the trusted chain.
+    pub fn verify_receipts(&self, header: &Header) -> Result<()> {
+        self.check_receipts(header)?;
+        self.verify_receipt_signatures(&[header])
+    }
+
+    /// Verify the headers immediately following the trusted header and, if
+    /// all of them are valid, make the last one the new trusted header.
+    ///
+    /// The receipt signatures of all headers are verified in a single batch.
+    pub fn advance(&mut self, headers: &[Header]) -> Result<()> {
+        let mut trusted = &self.trusted;
+        let mut with_receipts = vec![];
+        for header in headers {
+            if header.round != trusted.round + 1 || header.previous_hash != trusted.encoded_hash() {
+                return Err(LightClientError::NotSuccessor(trusted.round).into());
+            }
+            if header.namespace != trusted.namespace {
+                return Err(LightClientError::NamespaceMismatch.into());
+            }
+
+            match header.header_type {
+                HeaderType::Normal => {
+                    self.check_receipts(header)?;
+                    with_receipts.push(header);
+                }
+                // Other blocks do not have new roots and thus no receipts.
+                _ => {
+                    if header.state_root != trusted.state_root {
+                        return Err(LightClientError::UnexpectedStateChange(header.round).into());
+                    }
+                }
+            }
+            trusted = header;
+        }
+        self.verify_receipt_signatures(&with_receipts)?;
+
+        if let Some(header) = headers.last() {
+            self.trusted = header.clone();
+        }
+        Ok(())
+    }
+
+    /// Check that the header carries receipts from enough members of the
+    /// storage committee, without verifying the receipt signatures.
+    fn check_receipts(&self, header: &Header) -> Result<()> {
+        if header.namespace != self.trusted.namespace {
+            return Err(LightClientError::NamespaceMismatch.into());
+        }
+
+        let signatures = match header.storage_signatures {
+            Some(ref signatures) if !signatures.is_empty() => signatures,
+            _ => return Err(HeaderError::MissingStorageSignatures.into()),
+        };
+        let mut signers = HashSet::new();
+        for bundle in signatures {
+            let public_key = bundle.public_key.ok_or(HeaderError::MissingPublicKey)?;
+            if self.storage_committee.contains(&public_key) {
+                signers.insert(public_key);
+            }
+        }
+        if signers.len() < self.threshold {
+            return Err(LightClientError::InsufficientReceipts {
+                round: header.round,
+                got: signers.len(),
+                required: self.threshold,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Verify all receipt signatures of the given headers, which must have
+    /// passed `check_receipts`.
+    fn verify_receipt_signatures(&self, headers: &[&Header]) -> Result<()> {
+        let bodies: Vec<_> = headers
+            .iter()
+            .map(|header| header.storage_receipt_body())
+            .collect();
+
+        let mut messages = vec![];
+        let mut public_keys = vec![];
+        let mut signatures = vec![];
+        for (header, body) in headers.iter().zip(&bodies) {
+            for bundle in header.storage_signatures.iter().flatten() {
+                messages.push(body.as_slice());
+                public_keys.push(bundle.public_key.ok_or(HeaderError::MissingPublicKey)?);
+                signatures.push(bundle.signature);
+            }
+        }
+
+        let context = storage_receipt_context(&self.chain_context);
+        PublicKey::verify_batch(&context, &messages, &public_keys, &signatures)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use oasis_core_runtime::common::crypto::{hash::Hash, signature::PrivateKey};
+
+    use super::*;
+
+    const CHAIN_CONTEXT: &str = "test chain";
+
+    fn make_header(previous: &Header, signers: &[&PrivateKey]) -> Header {
+        let mut header = Header {
+            namespace: previous.namespace,
+            round: previous.round + 1,
+            header_type: HeaderType::Normal,
+            previous_hash: previous.encoded_hash(),
+            io_root: Hash::empty_hash(),
+            state_root: Hash::digest_bytes(&previous.round.to_le_bytes()),
+            ..Default::default()
+        };
+        header.storage_signatures = Some(
+            signers
+                .iter()
+                .map(|signer| header.sign_storage_receipt(signer, CHAIN_CONTEXT).unwrap())
+                .collect(),
+        );
+        header
+    }
+
+    #[test]
+    fn test_light_client() {
+        let signers: Vec<_> = (0..3).map(|_| PrivateKey::generate()).collect();
+        let committee = signers.iter().map(|signer| signer.public_key()).collect();
+        let outsider = PrivateKey::generate();
+
+        let genesis = Header::default();
+        let mut client = LightClient::new(genesis.clone(), CHAIN_CONTEXT, committee, 2);
+
+        let header1 = make_header(&genesis, &[&signers[0], &signers[1]]);
+        let mut header2 = make_header(&header1, &[&signers[1], &signers[2]]);
+
+        // Invalid receipt signatures are rejected in a batch of headers.
+        let mut tampered = header2.clone();
+        tampered.storage_signatures.as_mut().unwrap()[1].signature =
+            header1.storage_signatures.as_ref().unwrap()[1].signature;
+        assert!(client.advance(&[header1.clone(), tampered]).is_err());
+        assert_eq!(client.trusted_header(), &genesis);
+
+        client
+            .advance(&[header1.clone(), header2.clone()])
+            .expect("headers should verify");
+        assert_eq!(client.trusted_header(), &header2);
+
+        // Not enough committee members.
+        let header3 = make_header(&header2, &[&signers[0], &outsider]);
+        assert!(client.advance(&[header3]).is_err());
+
+        // Not linked to the trusted header.
+        let header3 = make_header(&header1, &[&signers[0], &signers[1]]);
+        assert!(client.advance(&[header3]).is_err());
+
+        // Epoch transition blocks keep the state root.
+        let mut header3 = make_header(&header2, &[]);
+        header3.header_type = HeaderType::EpochTransition;
+        header3.state_root = header2.state_root;
+        header3.storage_signatures = None;
+        client
+            .advance(&[header3.clone()])
+            .expect("header should verify");
+
+        let mut header4 = make_header(&header3, &[]);
+        header4.header_type = HeaderType::RoundFailed;
+        assert!(client.advance(&[header4]).is_err());
+
+        // Tampered roots.
+        header2.state_root = Hash::empty_hash();
+        assert!(client.verify_receipts(&header2).is_err());
+    }
+}
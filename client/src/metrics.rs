@@ -0,0 +1,436 @@
+//! Client metrics.
+//!
+//! All non-SGX components (the transaction and storage clients, the key
+//! manager client and the enclave RPC gRPC transport) record their metrics
+//! in a single `Metrics` handle, which registers them with one Prometheus
+//! registry. Metric names follow the `oasis_<subsystem>_<name>` scheme.
+//!
+//! Metrics are only collected when a `Metrics` handle is passed to the
+//! clients, e.g., via `ClientBuilder::with_metrics`. The registry can be
+//! scraped over HTTP by starting an `Exporter`.
+use std::{
+    io::{Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use futures::prelude::*;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+
+use crate::BoxFuture;
+
+/// Namespace of all metrics.
+const NAMESPACE: &str = "oasis";
+/// Subsystem of the transaction and storage client metrics.
+const SUBSYSTEM_CLIENT: &str = "client";
+/// Subsystem of the gRPC transport metrics.
+const SUBSYSTEM_GRPC: &str = "grpc";
+/// Subsystem of the key manager client metrics.
+const SUBSYSTEM_KEYMANAGER: &str = "keymanager";
+
+/// Label value of successful operations.
+const RESULT_SUCCESS: &str = "success";
+/// Label value of failed operations.
+const RESULT_FAILURE: &str = "failure";
+/// Label value of cache hits.
+const RESULT_HIT: &str = "hit";
+/// Label value of cache misses.
+const RESULT_MISS: &str = "miss";
+
+fn opts(subsystem: &str, name: &str, help: &str) -> Opts {
+    Opts::new(name, help)
+        .namespace(NAMESPACE)
+        .subsystem(subsystem)
+}
+
+fn histogram_opts(subsystem: &str, name: &str, help: &str) -> HistogramOpts {
+    HistogramOpts::new(name, help)
+        .namespace(NAMESPACE)
+        .subsystem(subsystem)
+}
+
+/// A handle to the client metrics.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    txn_submissions: IntCounterVec,
+    round_lag: IntGauge,
+    query_latency: HistogramVec,
+    storage_syncs: IntCounterVec,
+    enclave_rpc_calls: IntCounterVec,
+    enclave_rpc_latency: HistogramVec,
+    key_cache_lookups: IntCounterVec,
+}
+
+impl Metrics {
+    /// Create the client metrics and register them with the given registry.
+    pub fn new(registry: &Registry) -> Result<Self> {
+        let metrics = Self {
+            registry: registry.clone(),
+            txn_submissions: IntCounterVec::new(
+                opts(
+                    SUBSYSTEM_CLIENT,
+                    "txn_submissions",
+                    "Number of transaction submissions.",
+                ),
+                &["result"],
+            )?,
+            round_lag: IntGauge::with_opts(opts(
+                SUBSYSTEM_CLIENT,
+                "round_lag",
+                "Number of rounds block streams are behind the latest block.",
+            ))?,
+            query_latency: HistogramVec::new(
+                histogram_opts(
+                    SUBSYSTEM_CLIENT,
+                    "query_latency",
+                    "Latency of node queries in seconds.",
+                ),
+                &["method"],
+            )?,
+            storage_syncs: IntCounterVec::new(
+                opts(
+                    SUBSYSTEM_CLIENT,
+                    "storage_syncs",
+                    "Number of storage sync requests.",
+                ),
+                &["method", "result"],
+            )?,
+            enclave_rpc_calls: IntCounterVec::new(
+                opts(
+                    SUBSYSTEM_GRPC,
+                    "enclave_rpc_calls",
+                    "Number of enclave RPC calls made over gRPC.",
+                ),
+                &["endpoint", "result"],
+            )?,
+            enclave_rpc_latency: HistogramVec::new(
+                histogram_opts(
+                    SUBSYSTEM_GRPC,
+                    "enclave_rpc_latency",
+                    "Latency of enclave RPC calls made over gRPC in seconds.",
+                ),
+                &["endpoint"],
+            )?,
+            key_cache_lookups: IntCounterVec::new(
+                opts(
+                    SUBSYSTEM_KEYMANAGER,
+                    "cache_lookups",
+                    "Number of key manager client cache lookups.",
+                ),
+                &["method", "result"],
+            )?,
+        };
+
+        registry.register(Box::new(metrics.txn_submissions.clone()))?;
+        registry.register(Box::new(metrics.round_lag.clone()))?;
+        registry.register(Box::new(metrics.query_latency.clone()))?;
+        registry.register(Box::new(metrics.storage_syncs.clone()))?;
+        registry.register(Box::new(metrics.enclave_rpc_calls.clone()))?;
+        registry.register(Box::new(metrics.enclave_rpc_latency.clone()))?;
+        registry.register(Box::new(metrics.key_cache_lookups.clone()))?;
+
+        Ok(metrics)
+    }
+
+    /// Registry the metrics are registered with.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Count the outcome of the given transaction submission.
+    pub(crate) fn observe_submission<T>(&self, submission: BoxFuture<T>) -> BoxFuture<T>
+    where
+        T: Send + 'static,
+    {
+        let txn_submissions = self.txn_submissions.clone();
+        Box::new(submission.then(move |result| {
+            txn_submissions
+                .with_label_values(&[result_label(&result)])
+                .inc();
+            result
+        }))
+    }
+
+    /// Record the latency of the given query.
+    pub(crate) fn observe_query<T>(&self, method: &str, query: BoxFuture<T>) -> BoxFuture<T>
+    where
+        T: Send + 'static,
+    {
+        let histogram = self.query_latency.with_label_values(&[method]);
+        let start = Instant::now();
+        Box::new(query.then(move |result| {
+            histogram.observe(start.elapsed().as_secs_f64());
+            result
+        }))
+    }
+
+    /// Record the number of rounds a block stream is behind.
+    pub(crate) fn observe_round_lag(&self, lag: u64) {
+        self.round_lag.set(lag as i64);
+    }
+
+    /// Count the outcome of a storage sync request.
+    pub(crate) fn observe_storage_sync<T>(&self, method: &str, result: &Result<T>) {
+        self.storage_syncs
+            .with_label_values(&[method, result_label(result)])
+            .inc();
+    }
+
+    /// Count the outcome and record the latency of the given enclave RPC
+    /// call to the given endpoint.
+    pub(crate) fn observe_enclave_rpc<T>(&self, endpoint: &str, call: BoxFuture<T>) -> BoxFuture<T>
+    where
+        T: Send + 'static,
+    {
+        let calls = self.enclave_rpc_calls.clone();
+        let histogram = self.enclave_rpc_latency.with_label_values(&[endpoint]);
+        let endpoint = endpoint.to_owned();
+        let start = Instant::now();
+        Box::new(call.then(move |result| {
+            histogram.observe(start.elapsed().as_secs_f64());
+            calls
+                .with_label_values(&[&endpoint, result_label(&result)])
+                .inc();
+            result
+        }))
+    }
+
+    /// Count a key manager client cache lookup.
+    pub fn observe_key_cache_lookup(&self, method: &str, hit: bool) {
+        let result = if hit { RESULT_HIT } else { RESULT_MISS };
+        self.key_cache_lookups
+            .with_label_values(&[method, result])
+            .inc();
+    }
+}
+
+fn result_label<T, E>(result: &Result<T, E>) -> &'static str {
+    match result {
+        Ok(_) => RESULT_SUCCESS,
+        Err(_) => RESULT_FAILURE,
+    }
+}
+
+/// Maximum time to wait for a scrape request or for its response to be sent.
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Maximum number of scrapes served concurrently.
+const MAX_CONCURRENT_SCRAPES: usize = 16;
+/// Maximum size of a scrape request head.
+const MAX_REQUEST_SIZE: usize = 16 * 1024;
+
+/// HTTP exporter serving the metrics of a registry in the Prometheus text
+/// format.
+///
+/// Every request is answered with the current metrics, regardless of the
+/// requested path. Scrapes are served concurrently and connections which
+/// exceed the concurrency limit are closed. The exporter keeps serving until
+/// it is shut down.
+pub struct Exporter {
+    local_addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl Exporter {
+    /// Start serving the metrics of the given registry on the given address.
+    pub fn start<A: ToSocketAddrs>(address: A, registry: Registry) -> Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let local_addr = listener.local_addr()?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let handle = {
+            let shutdown = shutdown.clone();
+            let active = Arc::new(AtomicUsize::new(0));
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if shutdown.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(_) => continue,
+                    };
+
+                    // Shed load instead of queueing scrapes behind slow
+                    // clients.
+                    if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONCURRENT_SCRAPES {
+                        active.fetch_sub(1, Ordering::SeqCst);
+                        continue;
+                    }
+
+                    let registry = registry.clone();
+                    let active = active.clone();
+                    thread::spawn(move || {
+                        // Failing to serve a single scrape is not fatal.
+                        let _ = serve(stream, &registry);
+                        active.fetch_sub(1, Ordering::SeqCst);
+                    });
+                }
+            })
+        };
+
+        Ok(Self {
+            local_addr,
+            shutdown,
+            handle,
+        })
+    }
+
+    /// Address the exporter is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop accepting connections and wait for the listener to be closed.
+    ///
+    /// Scrapes which are already being served are completed.
+    pub fn shutdown(self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+
+        // Wake up the listener, which is blocked waiting for a connection.
+        let mut addr = self.local_addr;
+        if addr.ip().is_unspecified() {
+            addr.set_ip(match addr.ip() {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            });
+        }
+        let _ = TcpStream::connect_timeout(&addr, SCRAPE_TIMEOUT);
+        let _ = self.handle.join();
+    }
+}
+
+fn serve(mut stream: TcpStream, registry: &Registry) -> Result<()> {
+    stream.set_read_timeout(Some(SCRAPE_TIMEOUT))?;
+    stream.set_write_timeout(Some(SCRAPE_TIMEOUT))?;
+
+    // Consume the request head, its contents are irrelevant.
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.ends_with(b"\r\n\r\n") && request.len() < MAX_REQUEST_SIZE {
+        let n = stream.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..n]);
+    }
+
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    encoder.encode(&registry.gather(), &mut body)?;
+
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        encoder.format_type(),
+        body.len()
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::anyhow;
+    use futures::future;
+
+    use super::*;
+
+    #[test]
+    fn test_metrics() {
+        let registry = Registry::new();
+        let metrics = Metrics::new(&registry).unwrap();
+
+        let ok: BoxFuture<()> = Box::new(future::ok(()));
+        let err: BoxFuture<()> = Box::new(future::err(anyhow!("rejected")));
+        metrics.observe_submission(ok).wait().unwrap();
+        metrics.observe_submission(err).wait().unwrap_err();
+        let query: BoxFuture<()> = Box::new(future::ok(()));
+        metrics.observe_query("get_block", query).wait().unwrap();
+        metrics.observe_round_lag(3);
+        metrics.observe_storage_sync::<()>("sync_get", &Ok(()));
+        let call: BoxFuture<()> = Box::new(future::err(anyhow!("unavailable")));
+        metrics
+            .observe_enclave_rpc("key-manager", call)
+            .wait()
+            .unwrap_err();
+        metrics.observe_key_cache_lookup("get_public_key", false);
+        metrics.observe_key_cache_lookup("get_public_key", true);
+
+        assert_eq!(
+            metrics
+                .txn_submissions
+                .with_label_values(&[RESULT_FAILURE])
+                .get(),
+            1
+        );
+        assert_eq!(metrics.round_lag.get(), 3);
+        assert_eq!(
+            metrics
+                .query_latency
+                .with_label_values(&["get_block"])
+                .get_sample_count(),
+            1
+        );
+        assert_eq!(
+            metrics
+                .enclave_rpc_calls
+                .with_label_values(&["key-manager", RESULT_FAILURE])
+                .get(),
+            1
+        );
+        assert_eq!(
+            metrics
+                .key_cache_lookups
+                .with_label_values(&["get_public_key", RESULT_HIT])
+                .get(),
+            1
+        );
+        assert_eq!(registry.gather().len(), 7);
+
+        // Metrics can only be registered once.
+        assert!(Metrics::new(&registry).is_err());
+    }
+
+    #[test]
+    fn test_exporter() {
+        let registry = Registry::new();
+        let metrics = Metrics::new(&registry).unwrap();
+        metrics.observe_round_lag(5);
+
+        let exporter = Exporter::start("127.0.0.1:0", metrics.registry().clone()).unwrap();
+        let scrape = || {
+            let mut stream = TcpStream::connect(exporter.local_addr()).unwrap();
+            stream
+                .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let response = scrape();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("oasis_client_round_lag 5"));
+
+        // A client which never sends its request does not block others.
+        let _idle = TcpStream::connect(exporter.local_addr()).unwrap();
+        assert!(scrape().contains("oasis_client_round_lag 5"));
+
+        // Connections are refused after shutting down.
+        let addr = exporter.local_addr();
+        exporter.shutdown();
+        assert!(TcpStream::connect(addr).is_err());
+    }
+}
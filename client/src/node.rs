@@ -1,27 +1,417 @@
 //! Connection to an Oasis node.
-use std::sync::Arc;
+use std::{
+    ffi::CString,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
-use grpcio::{Channel, ChannelBuilder, Environment};
+use futures::{future, prelude::*};
+use grpcio::{CallOption, Channel, ChannelBuilder, ChannelCredentialsBuilder, Environment};
+use thiserror::Error;
+use tokio::timer::Interval;
+
+use crate::{
+    executor::{default_spawner, Spawner, Task},
+    grpc::UnaryResponse,
+    transaction::api::control::NodeControllerClient,
+    BoxFuture,
+};
+
+/// Default initial delay before reconnecting to a node.
+pub const DEFAULT_RECONNECT_BACKOFF_INITIAL: Duration = Duration::from_millis(100);
+/// Default maximum delay before reconnecting to a node.
+pub const DEFAULT_RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(10);
+/// Default timeout for health checks.
+pub const DEFAULT_HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Channel argument holding a unique channel identifier.
+const CHANNEL_ID_ARG: &str = "oasis.channel_id";
+
+/// Identifier of the next channel.
+static NEXT_CHANNEL_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Node connection error.
+#[derive(Debug, Error)]
+pub enum NodeError {
+    #[error("node is unreachable: {0}")]
+    Unreachable(String),
+}
+
+/// TLS configuration for a node connection.
+#[derive(Clone, Debug, Default)]
+pub struct TlsConfig {
+    /// PEM-encoded root certificates used to authenticate the node. The
+    /// default roots are used if not set.
+    pub root_certs: Option<Vec<u8>>,
+    /// PEM-encoded client certificate chain and private key used to
+    /// authenticate the client to the node.
+    pub client_identity: Option<(Vec<u8>, Vec<u8>)>,
+    /// Server name expected in the node's certificate, if it differs from
+    /// the host name in the node's address.
+    pub server_name: Option<String>,
+}
+
+/// Node connection options.
+#[derive(Clone)]
+pub struct Options {
+    tls: Option<TlsConfig>,
+    pool_size: usize,
+    reconnect_backoff_initial: Duration,
+    reconnect_backoff_max: Duration,
+    keepalive: Option<(Duration, Duration)>,
+    health_check_timeout: Duration,
+    health_check_interval: Option<Duration>,
+    spawner: Option<Arc<dyn Spawner>>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            tls: None,
+            pool_size: 1,
+            reconnect_backoff_initial: DEFAULT_RECONNECT_BACKOFF_INITIAL,
+            reconnect_backoff_max: DEFAULT_RECONNECT_BACKOFF_MAX,
+            keepalive: None,
+            health_check_timeout: DEFAULT_HEALTH_CHECK_TIMEOUT,
+            health_check_interval: None,
+            spawner: None,
+        }
+    }
+}
+
+impl Options {
+    /// Connect to the node using TLS.
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Set the number of channels to open to the node.
+    ///
+    /// Channels are handed out in a round-robin fashion, so that concurrent
+    /// calls are spread over multiple connections.
+    pub fn with_pool_size(mut self, pool_size: usize) -> Self {
+        self.pool_size = pool_size.max(1);
+        self
+    }
+
+    /// Set the initial and maximum delay between reconnection attempts.
+    pub fn with_reconnect_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.reconnect_backoff_initial = initial;
+        self.reconnect_backoff_max = max;
+        self
+    }
+
+    /// Send keepalive pings at the given interval and consider the
+    /// connection broken if a ping is not acknowledged within the timeout.
+    pub fn with_keepalive(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.keepalive = Some((interval, timeout));
+        self
+    }
+
+    /// Set the timeout for health checks.
+    pub fn with_health_check_timeout(mut self, timeout: Duration) -> Self {
+        self.health_check_timeout = timeout;
+        self
+    }
+
+    /// Check the health of the connection at the given interval in a
+    /// background task, replacing channels which fail the check.
+    ///
+    /// The task is spawned when connecting and stops once the node
+    /// connection is dropped. Only channels obtained from the node after a
+    /// channel has been replaced use the new connection.
+    pub fn with_health_check_interval(mut self, interval: Duration) -> Self {
+        self.health_check_interval = Some(interval);
+        self
+    }
+
+    /// Spawn the health check task with the given spawner instead of the
+    /// default one (see `executor::default_spawner`).
+    pub fn with_spawner(mut self, spawner: Arc<dyn Spawner>) -> Self {
+        self.spawner = Some(spawner);
+        self
+    }
+
+    /// Connect to the node at the given address.
+    pub fn new(self, environment: Arc<Environment>, address: &str) -> Node {
+        let channels = (0..self.pool_size)
+            .map(|_| self.connect(environment.clone(), address))
+            .collect();
+        let health_check_interval = self.health_check_interval;
+        let spawner = self.spawner.clone();
+
+        let node = Node {
+            inner: Arc::new(Inner {
+                options: self,
+                environment,
+                address: address.to_owned(),
+                channels: Mutex::new(channels),
+                next: AtomicUsize::new(0),
+            }),
+        };
+        if let Some(interval) = health_check_interval {
+            spawner
+                .unwrap_or_else(default_spawner)
+                .spawn(node.maintain(interval));
+        }
+        node
+    }
+
+    fn connect(&self, environment: Arc<Environment>, address: &str) -> Channel {
+        // gRPC shares a single connection between channels with identical arguments,
+        // so each channel needs a distinct argument to get its own connection.
+        let id = NEXT_CHANNEL_ID.fetch_add(1, Ordering::Relaxed);
+        let mut builder = ChannelBuilder::new(environment)
+            .raw_cfg_int(CString::new(CHANNEL_ID_ARG).unwrap(), id as i32)
+            .max_receive_message_len(i32::max_value())
+            .max_send_message_len(i32::max_value())
+            .initial_reconnect_backoff(self.reconnect_backoff_initial)
+            .max_reconnect_backoff(self.reconnect_backoff_max);
+        if let Some((interval, timeout)) = self.keepalive {
+            builder = builder
+                .keepalive_time(interval)
+                .keepalive_timeout(timeout)
+                .keepalive_permit_without_calls(true);
+        }
+
+        match self.tls {
+            Some(ref tls) => {
+                let mut credentials = ChannelCredentialsBuilder::new();
+                if let Some(ref root_certs) = tls.root_certs {
+                    credentials = credentials.root_cert(root_certs.clone());
+                }
+                if let Some((ref cert, ref key)) = tls.client_identity {
+                    credentials = credentials.cert(cert.clone(), key.clone());
+                }
+
+                if let Some(ref server_name) = tls.server_name {
+                    builder = builder.override_ssl_target(server_name.clone());
+                }
+
+                builder.secure_connect(address, credentials.build())
+            }
+            None => builder.connect(address),
+        }
+    }
+}
 
 /// An Oasis node connection.
 pub struct Node {
-    channel: Channel,
+    inner: Arc<Inner>,
 }
 
 impl Node {
     /// Create a new Oasis node connection.
     pub fn new(environment: Arc<Environment>, address: &str) -> Self {
-        // Create a gRPC channel with the Oasis node.
-        let channel = ChannelBuilder::new(environment)
-            .max_receive_message_len(i32::max_value())
-            .max_send_message_len(i32::max_value())
-            .connect(address);
+        Self::make().new(environment, address)
+    }
 
-        Self { channel }
+    /// Create a new node connection options builder.
+    pub fn make() -> Options {
+        Options::default()
     }
 
     /// gRPC channel to Oasis node.
     pub fn channel(&self) -> Channel {
-        self.channel.clone()
+        let channels = self.inner.channels.lock().unwrap();
+        let index = self.inner.next.fetch_add(1, Ordering::Relaxed) % channels.len();
+        channels[index].clone()
+    }
+
+    /// Check that the node is reachable over all pooled channels.
+    ///
+    /// Channels which fail the check are replaced with new connections. An
+    /// error is returned if the node is not reachable over any channel.
+    pub fn check_health(&self) -> BoxFuture<()> {
+        Inner::check_health(self.inner.clone())
+    }
+
+    /// Check the health of the connection at the given interval until the
+    /// node connection is dropped.
+    fn maintain(&self, interval: Duration) -> Task {
+        let inner = Arc::downgrade(&self.inner);
+
+        Box::new(
+            Interval::new(Instant::now() + interval, interval)
+                .map_err(|_| ())
+                .for_each(move |_| -> Task {
+                    match inner.upgrade() {
+                        // Failed checks are retried at the next interval.
+                        Some(inner) => Box::new(Inner::check_health(inner).then(|_| Ok(()))),
+                        None => Box::new(future::err(())),
+                    }
+                }),
+        )
+    }
+}
+
+struct Inner {
+    options: Options,
+    environment: Arc<Environment>,
+    address: String,
+    channels: Mutex<Vec<Channel>>,
+    next: AtomicUsize,
+}
+
+impl Inner {
+    fn check_health(inner: Arc<Inner>) -> BoxFuture<()> {
+        let channels = inner.channels.lock().unwrap().clone();
+        let pings: Vec<_> = channels
+            .into_iter()
+            .map(|channel| inner.ping(channel))
+            .collect();
+
+        Box::new(future::join_all(pings).and_then(move |results| {
+            let mut healthy = 0;
+            let mut last_error = String::new();
+            for (index, result) in results.into_iter().enumerate() {
+                match result {
+                    Ok(()) => healthy += 1,
+                    Err(error) => {
+                        last_error = error;
+                        inner.reconnect(index);
+                    }
+                }
+            }
+
+            if healthy == 0 {
+                return Err(NodeError::Unreachable(last_error).into());
+            }
+            Ok(())
+        }))
+    }
+
+    fn reconnect(&self, index: usize) {
+        let channel = self
+            .options
+            .connect(self.environment.clone(), &self.address);
+        self.channels.lock().unwrap()[index] = channel;
+    }
+
+    fn ping(&self, channel: Channel) -> BoxFuture<Result<(), String>> {
+        let options = CallOption::default().timeout(self.options.health_check_timeout);
+
+        match NodeControllerClient::new(channel).is_synced(options) {
+            Ok(rsp) => Box::new(
+                UnaryResponse::new(rsp)
+                    .then(|result| Ok(result.map(|_| ()).map_err(|error| format!("{}", error)))),
+            ),
+            Err(error) => Box::new(future::ok(Err(format!("{}", error)))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use grpcio::{EnvBuilder, RpcContext, Server, ServerBuilder, ServiceBuilder, UnarySink};
+    use tokio::runtime::Runtime;
+
+    use super::*;
+    use crate::executor::TokioSpawner;
+
+    grpc_method!(
+        METHOD_IS_SYNCED,
+        "/oasis-core.NodeController/IsSynced",
+        (),
+        bool
+    );
+
+    /// Start a server recording the peers of health check calls.
+    fn start_server(
+        environment: Arc<Environment>,
+        peers: Arc<Mutex<Vec<String>>>,
+    ) -> (Server, String) {
+        let service = ServiceBuilder::new()
+            .add_unary_handler(
+                &METHOD_IS_SYNCED,
+                move |ctx: RpcContext, _: (), sink: UnarySink<bool>| {
+                    peers.lock().unwrap().push(ctx.peer());
+                    ctx.spawn(sink.success(true).map_err(|_| ()));
+                },
+            )
+            .build();
+        let mut server = ServerBuilder::new(environment)
+            .register_service(service)
+            .bind("127.0.0.1", 0)
+            .build()
+            .expect("server should build");
+        server.start();
+        let address = {
+            let (ref host, port) = server.bind_addrs()[0];
+            format!("{}:{}", host, port)
+        };
+
+        (server, address)
+    }
+
+    #[test]
+    fn test_pooled_channels() {
+        let environment = Arc::new(EnvBuilder::new().build());
+        let peers = Arc::new(Mutex::new(Vec::new()));
+        let (_server, address) = start_server(environment.clone(), peers.clone());
+
+        let node = Node::make()
+            .with_pool_size(3)
+            .new(environment.clone(), &address);
+        node.check_health().wait().expect("node should be healthy");
+        peers.lock().unwrap().clear();
+
+        for _ in 0..6 {
+            NodeControllerClient::new(node.channel())
+                .is_synced(CallOption::default())
+                .expect("call should start")
+                .wait()
+                .expect("call should succeed");
+        }
+
+        // Calls should be distributed over separate connections in a round-robin fashion.
+        let peers = peers.lock().unwrap();
+        assert_eq!(peers.len(), 6);
+        assert_eq!(peers.iter().collect::<HashSet<_>>().len(), 3);
+        assert_eq!(peers[..3], peers[3..]);
+    }
+
+    #[test]
+    fn test_health_check_task() {
+        let runtime = Runtime::new().unwrap();
+        let environment = Arc::new(EnvBuilder::new().build());
+        let peers = Arc::new(Mutex::new(Vec::new()));
+        let (_server, address) = start_server(environment.clone(), peers.clone());
+
+        // Health checks are driven in the background.
+        let node = Node::make()
+            .with_health_check_interval(Duration::from_millis(10))
+            .with_spawner(Arc::new(TokioSpawner::new(runtime.executor())))
+            .new(environment, &address);
+        std::thread::sleep(Duration::from_millis(500));
+        assert!(peers.lock().unwrap().len() >= 2);
+
+        // And stop once the node connection is dropped.
+        drop(node);
+        std::thread::sleep(Duration::from_millis(100));
+        let checks = peers.lock().unwrap().len();
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(peers.lock().unwrap().len(), checks);
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let environment = Arc::new(EnvBuilder::new().build());
+        let node = Node::make()
+            .with_pool_size(2)
+            .with_health_check_timeout(Duration::from_millis(100))
+            .new(environment, "127.0.0.1:1");
+
+        let error = node.check_health().wait().unwrap_err();
+        match error.downcast_ref::<NodeError>() {
+            Some(NodeError::Unreachable(_)) => {}
+            _ => panic!("expected unreachable node, got: {}", error),
+        }
     }
 }
@@ -0,0 +1,126 @@
+//! Paginated query results.
+//!
+//! Queries which may return many results return them in pages. Each page
+//! carries a continuation token which is passed back to the query to fetch
+//! the next page, and `PageStream` turns such a query into a stream of
+//! individual results.
+use std::collections::VecDeque;
+
+use anyhow::Error;
+use futures::{prelude::*, try_ready};
+
+use crate::BoxFuture;
+
+/// A page of query results.
+#[derive(Clone, Debug)]
+pub struct Page<T, C> {
+    /// Results in this page.
+    pub items: Vec<T>,
+    /// Continuation token to pass when requesting the next page, if more
+    /// results may be available.
+    pub next: Option<C>,
+}
+
+impl<T, C> Page<T, C> {
+    /// Create a new page.
+    pub fn new(items: Vec<T>, next: Option<C>) -> Self {
+        Self { items, next }
+    }
+}
+
+/// A stream of query results which fetches pages on demand.
+///
+/// At most one page is buffered at any time.
+pub struct PageStream<T, C> {
+    fetch: Box<dyn FnMut(Option<C>) -> BoxFuture<Page<T, C>> + Send>,
+    next: Option<C>,
+    done: bool,
+    buffer: VecDeque<T>,
+    pending: Option<BoxFuture<Page<T, C>>>,
+}
+
+impl<T, C> PageStream<T, C>
+where
+    C: Clone,
+{
+    /// Create a new stream starting after the given continuation token, or
+    /// at the beginning if none is given.
+    ///
+    /// The fetch function is called with the continuation token of the
+    /// previous page to fetch the next one.
+    pub fn new<F>(start: Option<C>, fetch: F) -> Self
+    where
+        F: FnMut(Option<C>) -> BoxFuture<Page<T, C>> + Send + 'static,
+    {
+        Self {
+            fetch: Box::new(fetch),
+            next: start,
+            done: false,
+            buffer: VecDeque::new(),
+            pending: None,
+        }
+    }
+}
+
+impl<T, C> Stream for PageStream<T, C>
+where
+    C: Clone,
+{
+    type Item = T;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<T>, Error> {
+        loop {
+            if let Some(item) = self.buffer.pop_front() {
+                return Ok(Async::Ready(Some(item)));
+            }
+            if self.done {
+                return Ok(Async::Ready(None));
+            }
+
+            let next = self.next.clone();
+            let fetch = &mut self.fetch;
+            let page = try_ready!(self.pending.get_or_insert_with(|| fetch(next)).poll());
+            self.pending = None;
+
+            self.buffer.extend(page.items);
+            match page.next {
+                Some(next) => self.next = Some(next),
+                None => self.done = true,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::future;
+
+    use super::*;
+
+    #[test]
+    fn test_page_stream() {
+        let items: Vec<u32> = (0..10).collect();
+        let stream = PageStream::new(None, move |after: Option<u32>| -> BoxFuture<_> {
+            let start = after.map_or(0, |after| after as usize + 1);
+            let page: Vec<_> = items.iter().cloned().skip(start).take(3).collect();
+            let next = if start + page.len() < items.len() {
+                page.last().cloned()
+            } else {
+                None
+            };
+            Box::new(future::ok(Page::new(page, next)))
+        });
+        assert_eq!(
+            stream.collect().wait().unwrap(),
+            (0..10).collect::<Vec<_>>()
+        );
+
+        // Resume after a continuation token.
+        let stream = PageStream::new(Some(5u32), |after: Option<u32>| -> BoxFuture<_> {
+            assert_eq!(after, Some(5));
+            Box::new(future::ok(Page::new(vec![6, 7], None)))
+        });
+        assert_eq!(stream.collect().wait().unwrap(), vec![6, 7]);
+    }
+}
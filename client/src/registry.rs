@@ -0,0 +1,330 @@
+//! Client for service defined in go/registry/api.
+use std::time::Instant;
+
+use anyhow::Result;
+use futures::prelude::*;
+use grpcio::{CallOption, Channel, Client};
+use io_context::Context;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use oasis_core_runtime::common::{
+    crypto::{hash::Hash, signature::PublicKey},
+    node::{Node, ROLE_KEY_MANAGER},
+    registry::{Runtime, RuntimeKind},
+    runtime::RuntimeId,
+    version::Version,
+};
+
+use crate::{
+    context,
+    grpc::{call_option, unary_call, unary_call_optional},
+    BoxFuture,
+};
+
+pub use crate::consensus::HEIGHT_LATEST;
+
+grpc_method!(
+    METHOD_GET_RUNTIMES,
+    "/oasis-core.Registry/GetRuntimes",
+    i64,
+    Vec<Runtime>
+);
+grpc_method!(
+    METHOD_GET_RUNTIME,
+    "/oasis-core.Registry/GetRuntime",
+    NamespaceQuery,
+    Runtime
+);
+grpc_method!(
+    METHOD_GET_NODES,
+    "/oasis-core.Registry/GetNodes",
+    i64,
+    Vec<Node>
+);
+grpc_method!(
+    METHOD_GET_NODE,
+    "/oasis-core.Registry/GetNode",
+    IdQuery,
+    Node
+);
+
+/// Runtime descriptor validation error.
+#[derive(Debug, Error)]
+pub enum RuntimeMismatchError {
+    #[error("runtime {0} is not registered")]
+    NotRegistered(RuntimeId),
+    #[error("unexpected runtime kind (expected: {0:?} got: {1:?})")]
+    Kind(RuntimeKind, RuntimeKind),
+    #[error("unexpected genesis state root (expected: {0} got: {1})")]
+    GenesisStateRoot(Hash, Hash),
+    #[error("unexpected key manager (expected: {0:?} got: {1:?})")]
+    KeyManager(Option<RuntimeId>, Option<RuntimeId>),
+    #[error("incompatible runtime version (expected: {0:?} got: {1:?})")]
+    Version(Version, Version),
+    #[error("nodes of entity {0} are not admitted")]
+    EntityNotAdmitted(PublicKey),
+}
+
+/// Local expectations about a runtime, checked against its registered
+/// descriptor.
+///
+/// Only the properties that have been set are checked.
+#[derive(Clone, Debug, Default)]
+pub struct ExpectedRuntime {
+    kind: Option<RuntimeKind>,
+    genesis_state_root: Option<Hash>,
+    key_manager: Option<Option<RuntimeId>>,
+    version: Option<Version>,
+    admitted_entity: Option<PublicKey>,
+}
+
+impl ExpectedRuntime {
+    /// Create empty expectations, matching any runtime.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expect the given runtime kind.
+    pub fn with_kind(mut self, kind: RuntimeKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Expect the given genesis state root.
+    pub fn with_genesis_state_root(mut self, state_root: Hash) -> Self {
+        self.genesis_state_root = Some(state_root);
+        self
+    }
+
+    /// Expect the runtime to use the given key manager, or none at all.
+    pub fn with_key_manager(mut self, key_manager: Option<RuntimeId>) -> Self {
+        self.key_manager = Some(key_manager);
+        self
+    }
+
+    /// Expect a runtime version compatible with the given one, i.e., with the
+    /// same major and minor version.
+    pub fn with_version(mut self, version: Version) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Expect nodes of the given entity to be admitted by the runtime's
+    /// admission policy.
+    pub fn with_admitted_entity(mut self, entity_id: PublicKey) -> Self {
+        self.admitted_entity = Some(entity_id);
+        self
+    }
+
+    /// Check the given runtime descriptor against the expectations.
+    pub fn check(&self, runtime: &Runtime) -> Result<()> {
+        if let Some(kind) = self.kind {
+            if kind != runtime.kind {
+                return Err(RuntimeMismatchError::Kind(kind, runtime.kind).into());
+            }
+        }
+        if let Some(state_root) = self.genesis_state_root {
+            if state_root != runtime.genesis.state_root {
+                return Err(RuntimeMismatchError::GenesisStateRoot(
+                    state_root,
+                    runtime.genesis.state_root,
+                )
+                .into());
+            }
+        }
+        if let Some(key_manager) = self.key_manager {
+            if key_manager != runtime.key_manager {
+                return Err(
+                    RuntimeMismatchError::KeyManager(key_manager, runtime.key_manager).into(),
+                );
+            }
+        }
+        if let Some(version) = self.version {
+            let actual = runtime.version.version;
+            if version.major_minor() != actual.major_minor() {
+                return Err(RuntimeMismatchError::Version(version, actual).into());
+            }
+        }
+        if let Some(entity_id) = self.admitted_entity {
+            if !runtime.admission_policy.allows(&entity_id) {
+                return Err(RuntimeMismatchError::EntityNotAdmitted(entity_id).into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NamespaceQuery {
+    pub height: i64,
+    pub id: RuntimeId,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IdQuery {
+    pub height: i64,
+    pub id: PublicKey,
+}
+
+/// A registry gRPC service client.
+///
+/// All queries take the consensus height at which the registry state is
+/// queried, use `HEIGHT_LATEST` to query the latest state.
+#[derive(Clone)]
+pub struct RegistryClient {
+    client: Client,
+    deadline: Option<Instant>,
+}
+
+impl RegistryClient {
+    /// Create a new registry client.
+    pub fn new(channel: Channel) -> Self {
+        RegistryClient {
+            client: Client::new(channel),
+            deadline: None,
+        }
+    }
+
+    /// Apply the deadline of the given context to all calls made through the
+    /// returned client.
+    pub fn with_context(&self, ctx: &Context) -> Self {
+        let mut client = self.clone();
+        client.deadline = context::get_deadline(ctx);
+        client
+    }
+
+    /// Retrieve all registered runtimes.
+    pub fn get_runtimes(&self, height: i64) -> BoxFuture<Vec<Runtime>> {
+        unary_call(
+            self.client
+                .unary_call_async(&METHOD_GET_RUNTIMES, &height, self.options()),
+        )
+    }
+
+    /// Retrieve a runtime descriptor, if the runtime is registered.
+    pub fn get_runtime(&self, id: RuntimeId, height: i64) -> BoxFuture<Option<Runtime>> {
+        let request = NamespaceQuery { height, id };
+        unary_call_optional(self.client.unary_call_async(
+            &METHOD_GET_RUNTIME,
+            &request,
+            self.options(),
+        ))
+    }
+
+    /// Retrieve a runtime descriptor and check it against the given
+    /// expectations.
+    ///
+    /// This is meant to be called at startup, so that misconfigured clients
+    /// fail fast instead of misbehaving later on.
+    pub fn check_runtime(
+        &self,
+        id: RuntimeId,
+        expected: &ExpectedRuntime,
+        height: i64,
+    ) -> BoxFuture<Runtime> {
+        let expected = expected.clone();
+
+        Box::new(self.get_runtime(id, height).and_then(move |runtime| {
+            let runtime = runtime.ok_or(RuntimeMismatchError::NotRegistered(id))?;
+            expected.check(&runtime)?;
+
+            Ok(runtime)
+        }))
+    }
+
+    /// Retrieve all registered nodes, including their supported runtimes,
+    /// capabilities and attestations.
+    pub fn get_nodes(&self, height: i64) -> BoxFuture<Vec<Node>> {
+        unary_call(
+            self.client
+                .unary_call_async(&METHOD_GET_NODES, &height, self.options()),
+        )
+    }
+
+    /// Retrieve a node descriptor, if the node is registered.
+    pub fn get_node(&self, id: PublicKey, height: i64) -> BoxFuture<Option<Node>> {
+        let request = IdQuery { height, id };
+        unary_call_optional(self.client.unary_call_async(
+            &METHOD_GET_NODE,
+            &request,
+            self.options(),
+        ))
+    }
+
+    /// Retrieve the key manager nodes serving the given runtime.
+    ///
+    /// Returns an empty list if the runtime does not use a key manager.
+    pub fn get_key_manager_nodes(&self, id: RuntimeId, height: i64) -> BoxFuture<Vec<Node>> {
+        let nodes = self.get_nodes(height);
+
+        Box::new(
+            self.get_runtime(id, height)
+                .join(nodes)
+                .map(|(runtime, nodes)| {
+                    let km_id = match runtime.and_then(|rt| rt.key_manager) {
+                        Some(km_id) => km_id,
+                        None => return vec![],
+                    };
+
+                    nodes
+                        .into_iter()
+                        .filter(|node| {
+                            node.has_roles(ROLE_KEY_MANAGER) && node.get_runtime(&km_id).is_some()
+                        })
+                        .collect()
+                }),
+        )
+    }
+
+    fn options(&self) -> CallOption {
+        call_option(self.deadline, None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use oasis_core_runtime::common::registry::EntityWhitelistRuntimeAdmissionPolicy;
+
+    use super::*;
+
+    #[test]
+    fn test_expected_runtime() {
+        let entity_id = PublicKey::from(vec![1u8; 32].as_slice());
+        let key_manager = RuntimeId::from(vec![2u8; 32].as_slice());
+        let mut runtime = Runtime::default();
+        runtime.kind = RuntimeKind::Compute;
+        runtime.genesis.state_root = Hash::empty_hash();
+        runtime.key_manager = Some(key_manager);
+        runtime.version.version = Version::new(1, 2, 3);
+
+        assert!(ExpectedRuntime::new().check(&runtime).is_ok());
+        let expected = ExpectedRuntime::new()
+            .with_kind(RuntimeKind::Compute)
+            .with_genesis_state_root(Hash::empty_hash())
+            .with_key_manager(Some(key_manager))
+            .with_version(Version::new(1, 2, 0));
+        assert!(expected.check(&runtime).is_ok());
+
+        let mismatches = vec![
+            ExpectedRuntime::new().with_kind(RuntimeKind::KeyManager),
+            ExpectedRuntime::new().with_genesis_state_root(Hash::digest_bytes(b"state")),
+            ExpectedRuntime::new().with_key_manager(None),
+            ExpectedRuntime::new().with_version(Version::new(1, 3, 3)),
+            ExpectedRuntime::new().with_admitted_entity(entity_id),
+        ];
+        for expected in mismatches {
+            assert!(expected.check(&runtime).is_err());
+        }
+
+        let mut entities = BTreeMap::new();
+        entities.insert(entity_id, true);
+        runtime.admission_policy.entity_whitelist =
+            Some(EntityWhitelistRuntimeAdmissionPolicy { entities });
+        let expected = ExpectedRuntime::new().with_admitted_entity(entity_id);
+        assert!(expected.check(&runtime).is_ok());
+    }
+}
@@ -0,0 +1,124 @@
+//! Enclave RPC traffic capture for debugging.
+//!
+//! A capturing transport logs every frame it sends together with the outcome
+//! of the call, using the runtime's structured (JSON) logger. Payloads are
+//! never logged, only truncated hashes of them, so captures can be shared
+//! when investigating misbehaving enclaves without exposing session data.
+use std::time::Instant;
+
+use futures::prelude::*;
+use io_context::Context;
+use slog::{info, o, warn, Logger};
+
+use oasis_core_runtime::{
+    common::{crypto::hash::Hash, logger::get_logger},
+    rpc::types,
+};
+
+use super::transport::Transport;
+use crate::BoxFuture;
+
+/// Number of hex digits of payload hashes included in captures.
+const PAYLOAD_HASH_DIGITS: usize = 16;
+
+/// A transport which logs all traffic going through the wrapped transport.
+pub struct CaptureTransport {
+    inner: Box<dyn Transport>,
+    logger: Logger,
+}
+
+impl CaptureTransport {
+    /// Create a new transport capturing the traffic of the given transport.
+    pub fn new(inner: Box<dyn Transport>) -> Self {
+        Self {
+            inner,
+            logger: get_logger("client/rpc/capture"),
+        }
+    }
+
+    fn capture(logger: Logger, response: BoxFuture<Vec<u8>>) -> BoxFuture<Vec<u8>> {
+        let start = Instant::now();
+
+        Box::new(response.then(move |result| {
+            let latency_us = start.elapsed().as_micros() as u64;
+            match result {
+                Ok(ref response) => info!(logger, "frame exchanged";
+                    "response_size" => response.len(),
+                    "response_hash" => payload_hash(response),
+                    "latency_us" => latency_us,
+                ),
+                Err(ref error) => warn!(logger, "frame exchange failed";
+                    "err" => %error,
+                    "latency_us" => latency_us,
+                ),
+            }
+            result
+        }))
+    }
+}
+
+impl Transport for CaptureTransport {
+    fn write_message(
+        &self,
+        ctx: Context,
+        session_id: types::SessionID,
+        data: Vec<u8>,
+        untrusted_plaintext: String,
+    ) -> BoxFuture<Vec<u8>> {
+        let logger = self.logger.new(o!(
+            "session_id" => format!("{:x}", session_id),
+            "method" => untrusted_plaintext.clone(),
+            "request_size" => data.len(),
+            "request_hash" => payload_hash(&data),
+        ));
+        let response = self
+            .inner
+            .write_message(ctx, session_id, data, untrusted_plaintext);
+
+        Self::capture(logger, response)
+    }
+
+    fn write_message_impl(&self, ctx: Context, data: Vec<u8>) -> BoxFuture<Vec<u8>> {
+        let logger = self.logger.new(o!(
+            "request_size" => data.len(),
+            "request_hash" => payload_hash(&data),
+        ));
+        let response = self.inner.write_message_impl(ctx, data);
+
+        Self::capture(logger, response)
+    }
+}
+
+/// Truncated hex-encoded hash of a payload.
+fn payload_hash(payload: &[u8]) -> String {
+    let mut hash = format!("{:x}", Hash::digest_bytes(payload));
+    hash.truncate(PAYLOAD_HASH_DIGITS);
+    hash
+}
+
+#[cfg(test)]
+mod test {
+    use futures::future;
+
+    use super::*;
+
+    struct EchoTransport;
+
+    impl Transport for EchoTransport {
+        fn write_message_impl(&self, _ctx: Context, data: Vec<u8>) -> BoxFuture<Vec<u8>> {
+            Box::new(future::ok(data))
+        }
+    }
+
+    #[test]
+    fn test_capture_transport() {
+        let transport = CaptureTransport::new(Box::new(EchoTransport));
+        let response = transport
+            .write_message_impl(Context::background(), b"frame".to_vec())
+            .wait()
+            .unwrap();
+        assert_eq!(response, b"frame".to_vec());
+
+        assert_eq!(payload_hash(b"frame").len(), PAYLOAD_HASH_DIGITS);
+    }
+}
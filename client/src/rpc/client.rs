@@ -1,61 +1,96 @@
 //! Enclave RPC client.
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc, Mutex,
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
 };
 
-use failure::{Fail, Fallible};
+use anyhow::Result;
 use futures::{
     future,
     prelude::*,
     sync::{mpsc, oneshot},
 };
-#[cfg(not(target_env = "sgx"))]
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
 use grpcio::Channel;
 use io_context::Context;
 use serde::{de::DeserializeOwned, Serialize};
-use tokio_executor::spawn;
+use thiserror::Error;
+use tracing::info_span;
 
-#[cfg(not(target_env = "sgx"))]
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
 use oasis_core_runtime::common::runtime::RuntimeId;
 use oasis_core_runtime::{
-    common::cbor,
+    common::{cbor, sgx::avr::EnclaveIdentity},
     protocol::Protocol,
     rpc::{
         session::{Builder, Session},
         types,
     },
+    tracing::enter_span,
+    types::{register_error_code, ErrorCode},
 };
 
-#[cfg(not(target_env = "sgx"))]
-use super::api::EnclaveRPCClient;
-#[cfg(not(target_env = "sgx"))]
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
 use super::transport::GrpcTransport;
 use super::transport::{RuntimeTransport, Transport};
-use crate::BoxFuture;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+use crate::config::{ConfigHandle, Subscription};
+use crate::{
+    executor::{default_spawner, Spawner},
+    BoxFuture,
+};
 
 /// Internal send queue backlog.
 const SENDQ_BACKLOG: usize = 10;
 
+/// Default maximum number of call retries.
+pub const DEFAULT_MAX_RETRIES: usize = 3;
+
+/// Name of the module for RPC client errors.
+const MODULE_NAME: &'static str = "client/rpc";
+
 /// RPC client error.
-#[derive(Debug, Fail)]
+#[derive(Debug, Error)]
 pub enum RpcClientError {
-    #[fail(display = "call failed: {}", 0)]
-    CallFailed(String),
-    #[fail(display = "expected response message, received: {:?}", 0)]
+    #[error("call failed: {0}")]
+    CallFailed(types::Error),
+    #[error("expected response message, received: {0:?}")]
     ExpectedResponseMessage(types::Message),
-    #[fail(display = "expected close message, received: {:?}", 0)]
+    #[error("expected close message, received: {0:?}")]
     ExpectedCloseMessage(types::Message),
-    #[fail(display = "transport error")]
+    #[error("transport error")]
     Transport,
-    #[fail(display = "client dropped")]
+    #[error("client dropped")]
     Dropped,
 }
 
+impl ErrorCode for RpcClientError {
+    fn module_name(&self) -> &str {
+        match self {
+            // Errors returned by the remote end keep their identity.
+            RpcClientError::CallFailed(error) => &error.module,
+            _ => MODULE_NAME,
+        }
+    }
+
+    fn code(&self) -> u32 {
+        match self {
+            RpcClientError::CallFailed(error) => error.code,
+            RpcClientError::ExpectedResponseMessage(_) => 1,
+            RpcClientError::ExpectedCloseMessage(_) => 2,
+            RpcClientError::Transport => 3,
+            RpcClientError::Dropped => 4,
+        }
+    }
+}
+
 type SendqRequest = (
     Arc<Context>,
     types::Request,
-    oneshot::Sender<Fallible<types::Response>>,
+    oneshot::Sender<Result<types::Response>>,
     usize,
 );
 
@@ -97,6 +132,41 @@ struct Inner {
     has_controller: AtomicBool,
     /// Maximum number of call retries.
     max_retries: usize,
+    /// Spawner of the controller.
+    spawner: Arc<dyn Spawner>,
+    /// Configuration the client follows, if any.
+    #[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+    config: Option<Subscription>,
+}
+
+impl Inner {
+    /// Current maximum number of call retries.
+    fn max_retries(&self) -> usize {
+        #[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+        {
+            if let Some(ref config) = self.config {
+                return config.handle().get().retry_policy.rpc_retries;
+            }
+        }
+
+        self.max_retries
+    }
+
+    /// Reset the session if the node addresses changed since the last call,
+    /// so that a new session is established with one of the new nodes.
+    fn apply_config(&self, session: &mut MultiplexedSession) {
+        #[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+        {
+            if let Some(change) = self.config.as_ref().and_then(|config| config.changed()) {
+                if change.new.addresses != change.old.addresses {
+                    session.reset();
+                }
+            }
+        }
+
+        #[cfg(any(target_env = "sgx", target_arch = "wasm32"))]
+        let _ = session;
+    }
 }
 
 /// RPC client.
@@ -106,6 +176,7 @@ pub struct RpcClient {
 
 impl RpcClient {
     fn new(transport: Box<dyn Transport>, builder: Builder) -> Self {
+        register_error_code::<RpcClientError>();
         let (tx, rx) = mpsc::channel(SENDQ_BACKLOG);
 
         Self {
@@ -115,7 +186,10 @@ impl RpcClient {
                 recvq: Mutex::new(Some(rx)),
                 sendq: tx,
                 has_controller: AtomicBool::new(false),
-                max_retries: 3,
+                max_retries: DEFAULT_MAX_RETRIES,
+                spawner: default_spawner(),
+                #[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+                config: None,
             }),
         }
     }
@@ -131,8 +205,19 @@ impl RpcClient {
         )
     }
 
+    /// Construct an unconnected RPC client with a custom transport.
+    ///
+    /// This is used on targets where gRPC is not available, e.g., on
+    /// wasm32 where frames can be relayed through a gateway using the
+    /// browser's networking APIs. Unless configured otherwise via
+    /// `with_spawner`, the client's controller is spawned on the default
+    /// executor, so one must be set for the calling thread.
+    pub fn new_with_transport(builder: Builder, transport: Box<dyn Transport>) -> Self {
+        Self::new(transport, builder)
+    }
+
     /// Construct an unconnected RPC client with gRPC transport.
-    #[cfg(not(target_env = "sgx"))]
+    #[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
     pub fn new_grpc(
         builder: Builder,
         channel: Channel,
@@ -140,17 +225,63 @@ impl RpcClient {
         endpoint: &str,
     ) -> Self {
         Self::new(
-            Box::new(GrpcTransport {
-                grpc_client: EnclaveRPCClient::new(channel),
-                runtime_id,
-                endpoint: endpoint.to_owned(),
-            }),
+            Box::new(GrpcTransport::new(channel, runtime_id, endpoint)),
             builder,
         )
     }
 
+    /// Set the maximum number of times a failed call is retried.
+    ///
+    /// # Panics
+    ///
+    /// This must be called before the client is first used.
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        Arc::get_mut(&mut self.inner)
+            .expect("client has not been used yet")
+            .max_retries = max_retries;
+        self
+    }
+
+    /// Set the spawner the client's controller is spawned with.
+    ///
+    /// # Panics
+    ///
+    /// This must be called before the client is first used.
+    pub fn with_spawner(mut self, spawner: Arc<dyn Spawner>) -> Self {
+        Arc::get_mut(&mut self.inner)
+            .expect("client has not been used yet")
+            .spawner = spawner;
+        self
+    }
+
+    /// Follow the retry policy of the given configuration, instead of the
+    /// maximum number of retries set via `with_max_retries`, and start a new
+    /// session whenever the node addresses change.
+    ///
+    /// # Panics
+    ///
+    /// This must be called before the client is first used.
+    #[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+    pub fn with_config(mut self, config: ConfigHandle) -> Self {
+        Arc::get_mut(&mut self.inner)
+            .expect("client has not been used yet")
+            .config = Some(config.subscribe());
+        self
+    }
+
+    /// Change the remote enclave identities the client's sessions are
+    /// authenticated against.
+    ///
+    /// The current session is reset, so the next call establishes a new
+    /// session with the remote enclave.
+    pub fn set_remote_enclaves(&self, enclaves: Option<HashSet<EnclaveIdentity>>) {
+        let mut session = self.inner.session.lock().unwrap();
+        session.builder = session.builder.clone().remote_enclaves(enclaves);
+        session.reset();
+    }
+
     /// Call a remote method.
-    pub fn call<C, O>(&self, ctx: Context, method: &'static str, args: C) -> BoxFuture<O>
+    pub fn call<C, O>(&self, mut ctx: Context, method: &'static str, args: C) -> BoxFuture<O>
     where
         C: Serialize,
         O: DeserializeOwned + Send + 'static,
@@ -160,8 +291,16 @@ impl RpcClient {
             args: cbor::to_value(args),
         };
 
+        // Propagate the span of the call in all frames sent for it.
+        let span = info_span!("rpc_call", method = method);
+        enter_span(&mut ctx, &span);
+
         Box::new(
             self.execute_call(ctx, request)
+                .then(move |result| {
+                    drop(span);
+                    result
+                })
                 .and_then(|response| match response.body {
                     types::Body::Success(value) => Ok(cbor::from_value(value)?),
                     types::Body::Error(error) => Err(RpcClientError::CallFailed(error).into()),
@@ -184,9 +323,10 @@ impl RpcClient {
                     .take()
                     .expect("has_controller was false");
 
+                let spawner = inner.spawner.clone();
                 let inner = inner.clone();
                 let inner2 = inner.clone();
-                spawn(
+                spawner.spawn(Box::new(
                     rx.for_each(move |(ctx, request, rsp_tx, retries)| {
                         let inner = inner.clone();
                         let inner2 = inner.clone();
@@ -200,7 +340,7 @@ impl RpcClient {
                             .then(
                                 move |result| -> Box<dyn Future<Item = (), Error = ()> + Send> {
                                     match result {
-                                        ref r if r.is_ok() || retries >= inner2.max_retries => {
+                                        ref r if r.is_ok() || retries >= inner2.max_retries() => {
                                             drop(rsp_tx.send(result));
                                             Box::new(future::ok(()))
                                         }
@@ -231,7 +371,7 @@ impl RpcClient {
                         // Close stream after the client is dropped.
                         Self::close(inner2).map_err(|_err| ())
                     }),
-                );
+                ));
             }
 
             // Send request to controller.
@@ -248,6 +388,7 @@ impl RpcClient {
     fn connect(inner: Arc<Inner>, ctx: Context) -> BoxFuture<()> {
         Box::new(future::lazy(move || -> BoxFuture<()> {
             let mut session = inner.session.lock().unwrap();
+            inner.apply_config(&mut session);
             if session.inner.is_connected() {
                 return Box::new(future::ok(()));
             }
@@ -376,15 +517,19 @@ impl RpcClient {
 
 #[cfg(test)]
 mod test {
-    use std::sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
     };
 
-    use failure::format_err;
+    use anyhow::anyhow;
     use futures::future;
     use io_context::Context;
     use tokio::runtime::Runtime;
+    use tokio_current_thread::CurrentThread;
 
     use oasis_core_runtime::{
         rak::RAK,
@@ -392,13 +537,20 @@ mod test {
     };
 
     use super::{super::transport::Transport, RpcClient};
-    use crate::BoxFuture;
+    use crate::{
+        builder::RetryPolicy,
+        config::{ClientConfig, ConfigHandle},
+        executor::CurrentThreadSpawner,
+        BoxFuture,
+    };
 
     #[derive(Clone)]
     struct MockTransport {
         rak: Arc<RAK>,
         demux: Arc<Mutex<Demux>>,
         next_error: Arc<AtomicBool>,
+        /// Sessions requests were received on, in order.
+        sessions: Arc<Mutex<Vec<types::SessionID>>>,
     }
 
     impl MockTransport {
@@ -409,6 +561,7 @@ mod test {
                 rak: rak.clone(),
                 demux: Arc::new(Mutex::new(Demux::new(rak))),
                 next_error: Arc::new(AtomicBool::new(false)),
+                sessions: Arc::new(Mutex::new(vec![])),
             }
         }
 
@@ -420,6 +573,10 @@ mod test {
         fn induce_transport_error(&self) {
             self.next_error.store(true, Ordering::SeqCst);
         }
+
+        fn session_count(&self) -> usize {
+            self.sessions.lock().unwrap().len()
+        }
     }
 
     impl Transport for MockTransport {
@@ -428,7 +585,7 @@ mod test {
                 .next_error
                 .compare_and_swap(true, false, Ordering::SeqCst)
             {
-                return Box::new(future::err(format_err!("transport error")));
+                return Box::new(future::err(anyhow!("transport error")));
             }
 
             let mut demux = self.demux.lock().unwrap();
@@ -437,9 +594,24 @@ mod test {
             let mut buffer = Vec::new();
             match demux.process_frame(data, &mut buffer) {
                 Err(err) => Box::new(future::err(err)),
-                Ok(Some((session_id, _session_info, message, _untrusted_plaintext))) => {
+                Ok(Some((
+                    session_id,
+                    _session_info,
+                    message,
+                    _untrusted_plaintext,
+                    _span_context,
+                ))) => {
+                    let mut sessions = self.sessions.lock().unwrap();
+                    if sessions.last() != Some(&session_id) {
+                        sessions.push(session_id);
+                    }
+                    drop(sessions);
+
                     // Message, process and write reply.
                     let body = match message {
+                        types::Message::Request(ref rq) if rq.method == "fail" => {
+                            types::Body::Error(types::Error::new("test", 5, "failed"))
+                        }
                         types::Message::Request(rq) => {
                             // Just echo back what was given.
                             types::Body::Success(rq.args)
@@ -491,5 +663,91 @@ mod test {
             .block_on(client.call(Context::background(), "test", 44))
             .unwrap();
         assert_eq!(result, 44, "call should work");
+
+        // Failed calls keep the module name and code of the remote error.
+        let error = rt
+            .block_on(client.call::<_, u64>(Context::background(), "fail", 45))
+            .unwrap_err();
+        match error.downcast_ref::<RpcClientError>() {
+            Some(RpcClientError::CallFailed(error)) => {
+                assert_eq!(error, &types::Error::new("test", 5, "failed"))
+            }
+            _ => panic!("unexpected error: {}", error),
+        }
+        let error: types::Error = error.context("key manager call").into();
+        assert_eq!(error.module, "test");
+        assert_eq!(error.code, 5);
+    }
+
+    #[test]
+    fn test_rpc_client_current_thread() {
+        let mut executor = CurrentThread::new();
+        let transport = MockTransport::new();
+        let builder = session::Builder::new();
+        let client = RpcClient::new(Box::new(transport), builder)
+            .with_spawner(Arc::new(CurrentThreadSpawner));
+
+        let result: u64 = executor
+            .block_on(client.call(Context::background(), "test", 42))
+            .unwrap();
+        assert_eq!(result, 42, "call should work");
+    }
+
+    #[test]
+    fn test_rpc_client_config() {
+        let mut rt = Runtime::new().unwrap();
+        let transport = MockTransport::new();
+        let builder = session::Builder::new();
+        let config = ConfigHandle::new(ClientConfig {
+            addresses: vec![],
+            timeout: None,
+            retry_policy: RetryPolicy {
+                rpc_retries: 0,
+                ..Default::default()
+            },
+            key_manager_enclaves: None,
+            keys_cache_size: 0,
+        });
+        let client =
+            RpcClient::new(Box::new(transport.clone()), builder).with_config(config.clone());
+
+        // Calls are not retried as configured.
+        transport.induce_transport_error();
+        assert!(rt
+            .block_on(client.call::<_, u64>(Context::background(), "test", 42))
+            .is_err());
+
+        // Changes of the retry policy are applied to subsequent calls.
+        config.update(|config| config.retry_policy.rpc_retries = 1);
+        transport.induce_transport_error();
+        let result: u64 = rt
+            .block_on(client.call(Context::background(), "test", 43))
+            .unwrap();
+        assert_eq!(result, 43, "call should work");
+
+        // Other changes keep the session.
+        let sessions = transport.session_count();
+        config.update(|config| config.timeout = Some(Duration::from_secs(1)));
+        let result: u64 = rt
+            .block_on(client.call(Context::background(), "test", 44))
+            .unwrap();
+        assert_eq!(result, 44, "call should work");
+        assert_eq!(transport.session_count(), sessions);
+
+        // Changing the node addresses resets the session.
+        config.update(|config| config.addresses = vec!["unix:other.sock".to_owned()]);
+        let result: u64 = rt
+            .block_on(client.call(Context::background(), "test", 45))
+            .unwrap();
+        assert_eq!(result, 45, "call should work");
+        assert_eq!(transport.session_count(), sessions + 1);
+
+        // Changing the remote enclaves resets the session.
+        client.set_remote_enclaves(None);
+        let result: u64 = rt
+            .block_on(client.call(Context::background(), "test", 46))
+            .unwrap();
+        assert_eq!(result, 46, "call should work");
+        assert_eq!(transport.session_count(), sessions + 2);
     }
 }
@@ -39,7 +39,7 @@ macro_rules! create_rpc_api_client {
             $(
                 pub fn $method_name(
                     &self,
-                    ctx: ::io_context::Context,
+                    ctx: $crate::__private::io_context::Context,
                     arguments: $request_type
                 ) -> $crate::BoxFuture<$response_type> {
                     self.rpc_client.call(ctx, stringify!($method_name), arguments)
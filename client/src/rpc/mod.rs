@@ -1,10 +1,17 @@
 //! Enclave RPC client.
 
-#[cfg(not(target_env = "sgx"))]
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
 mod api;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub mod capture;
 pub mod client;
 pub mod macros;
 mod transport;
 
 // Re-exports.
-pub use self::client::RpcClient;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub use self::{
+    capture::CaptureTransport,
+    transport::{Connector, GrpcTransport},
+};
+pub use self::{client::RpcClient, transport::Transport};
@@ -1,21 +1,42 @@
 use std::sync::Arc;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
 
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+use anyhow::anyhow;
 use futures::future;
-#[cfg(not(target_env = "sgx"))]
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
 use futures::Future;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+use grpcio::Channel;
 use io_context::Context;
 
-#[cfg(not(target_env = "sgx"))]
-use oasis_core_runtime::common::runtime::RuntimeId;
-use oasis_core_runtime::{common::cbor, protocol::Protocol, rpc::types, types::Body};
+use oasis_core_runtime::{common::cbor, protocol::Protocol, rpc::types, tracing, types::Body};
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+use oasis_core_runtime::{common::runtime::RuntimeId, tracing::SpanContext};
 
-#[cfg(not(target_env = "sgx"))]
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
 use super::api::{CallEnclaveRequest, EnclaveRPCClient};
 use super::client::RpcClientError;
 use crate::BoxFuture;
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+use crate::{
+    config::{ConfigHandle, Subscription},
+    context,
+    grpc::{call_option, convert_error, with_span_context, UnaryResponse},
+    metrics::Metrics,
+};
 
 /// An EnclaveRPC transport.
 pub trait Transport: Send + Sync {
+    /// Frame and send a message to the remote endpoint, returning the
+    /// response frame.
     fn write_message(
         &self,
         ctx: Context,
@@ -23,19 +44,30 @@ pub trait Transport: Send + Sync {
         data: Vec<u8>,
         untrusted_plaintext: String,
     ) -> BoxFuture<Vec<u8>> {
-        // Frame message.
-        let frame = types::Frame {
-            session: session_id,
-            untrusted_plaintext: untrusted_plaintext,
-            payload: data,
-        };
-
-        self.write_message_impl(ctx, cbor::to_vec(&frame))
+        let frame = frame_message(&ctx, session_id, data, untrusted_plaintext);
+        self.write_message_impl(ctx, frame)
     }
 
+    /// Send a raw frame to the remote endpoint, returning the response
+    /// frame.
     fn write_message_impl(&self, ctx: Context, data: Vec<u8>) -> BoxFuture<Vec<u8>>;
 }
 
+/// Frame a message of the given session.
+fn frame_message(
+    ctx: &Context,
+    session_id: types::SessionID,
+    data: Vec<u8>,
+    untrusted_plaintext: String,
+) -> Vec<u8> {
+    cbor::to_vec(&types::Frame {
+        session: session_id,
+        untrusted_plaintext: untrusted_plaintext,
+        payload: data,
+        span_context: tracing::get_span_context(ctx).cloned().unwrap_or_default(),
+    })
+}
+
 /// A transport implementation which can be used from inside the runtime and uses the Runtime Host
 /// Protocol to transport EnclaveRPC frames.
 pub struct RuntimeTransport {
@@ -67,26 +99,286 @@ impl Transport for RuntimeTransport {
     }
 }
 
+/// A function opening a channel to the node at the given address.
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+pub type Connector = Arc<dyn Fn(&str) -> Channel + Send + Sync>;
+
+/// Clients of the nodes of a client configuration.
+///
+/// Enclave RPC sessions are stateful, so all frames of a session are sent
+/// to the same node. A new session is started on the next node if a call
+/// made for the previous session failed. Changes of the node addresses are
+/// applied when a new session is started, changes of the timeout right
+/// away.
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+struct ConfiguredClients<C> {
+    subscription: Subscription,
+    connect: Box<dyn Fn(&str) -> C + Send + Sync>,
+    nodes: Mutex<Nodes<C>>,
+}
+
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+struct Nodes<C> {
+    /// Clients of the configured nodes.
+    clients: Vec<C>,
+    /// Index of the node of the current session.
+    current: usize,
+    /// Current session, if any.
+    session: Option<types::SessionID>,
+    /// Whether a call made for the current session failed.
+    failed: Arc<AtomicBool>,
+}
+
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+impl<C> ConfiguredClients<C> {
+    fn new<F>(config: &ConfigHandle, connect: F) -> Self
+    where
+        F: Fn(&str) -> C + Send + Sync + 'static,
+    {
+        let subscription = config.subscribe();
+        let clients = Self::connect_all(&subscription.config().addresses, &connect);
+
+        Self {
+            subscription,
+            connect: Box::new(connect),
+            nodes: Mutex::new(Nodes {
+                clients,
+                current: 0,
+                session: None,
+                failed: Arc::new(AtomicBool::new(false)),
+            }),
+        }
+    }
+
+    fn connect_all(addresses: &[String], connect: &dyn Fn(&str) -> C) -> Vec<C> {
+        addresses.iter().map(|address| connect(address)).collect()
+    }
+
+    /// Make a call for the given session through its node, given the
+    /// node's client and the configured timeout.
+    ///
+    /// Raw frames which do not belong to a session are sent to the node of
+    /// the current session.
+    fn call<F>(&self, session: Option<types::SessionID>, f: F) -> BoxFuture<Vec<u8>>
+    where
+        F: FnOnce(&C, Option<Duration>) -> BoxFuture<Vec<u8>>,
+    {
+        let mut nodes = self.nodes.lock().unwrap();
+        if session.is_some() && session != nodes.session {
+            // A new session is started, switch nodes if needed.
+            let mut reconnected = false;
+            if let Some(change) = self.subscription.changed() {
+                if change.new.addresses != change.old.addresses {
+                    nodes.clients = Self::connect_all(&change.new.addresses, &*self.connect);
+                    nodes.current = 0;
+                    reconnected = true;
+                }
+            }
+            if !reconnected && nodes.failed.load(Ordering::SeqCst) && !nodes.clients.is_empty() {
+                nodes.current = (nodes.current + 1) % nodes.clients.len();
+            }
+
+            nodes.session = session;
+            nodes.failed = Arc::new(AtomicBool::new(false));
+        }
+
+        let client = match nodes.clients.get(nodes.current) {
+            Some(client) => client,
+            None => return Box::new(future::err(anyhow!("no node addresses configured"))),
+        };
+        let timeout = self.subscription.handle().get().timeout;
+        let failed = nodes.failed.clone();
+        Box::new(f(client, timeout).map_err(move |error| {
+            failed.store(true, Ordering::SeqCst);
+            error
+        }))
+    }
+}
+
 /// A transport implementation which uses gRPC to transport EnclaveRPC frames.
-#[cfg(not(target_env = "sgx"))]
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
 pub struct GrpcTransport {
     pub grpc_client: EnclaveRPCClient,
     pub runtime_id: RuntimeId,
     pub endpoint: String,
+    pub metrics: Option<Metrics>,
+    /// Clients of the configured nodes used instead of `grpc_client`, if
+    /// following a configuration.
+    configured: Option<ConfiguredClients<EnclaveRPCClient>>,
 }
 
-#[cfg(not(target_env = "sgx"))]
-impl Transport for GrpcTransport {
-    fn write_message_impl(&self, _ctx: Context, data: Vec<u8>) -> BoxFuture<Vec<u8>> {
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+impl GrpcTransport {
+    /// Create a new gRPC transport to the given endpoint of the runtime.
+    pub fn new(channel: Channel, runtime_id: RuntimeId, endpoint: &str) -> Self {
+        Self {
+            grpc_client: EnclaveRPCClient::new(channel),
+            runtime_id,
+            endpoint: endpoint.to_owned(),
+            metrics: None,
+            configured: None,
+        }
+    }
+
+    /// Record calls made over the transport in the given metrics.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Follow the given configuration, making calls through its nodes
+    /// (connected to using the given connector) with its timeout.
+    ///
+    /// Each session is pinned to one node, sessions move to the next node
+    /// after a failed call. Changes of the node addresses are applied to
+    /// new sessions, so clients must reset their session when the addresses
+    /// change (as `RpcClient::with_config` does).
+    pub fn with_config(mut self, config: &ConfigHandle, connector: Connector) -> Self {
+        self.configured = Some(ConfiguredClients::new(config, move |address: &str| {
+            EnclaveRPCClient::new(connector(address))
+        }));
+        self
+    }
+
+    fn send(
+        &self,
+        ctx: Context,
+        session_id: Option<types::SessionID>,
+        data: Vec<u8>,
+    ) -> BoxFuture<Vec<u8>> {
         let req = CallEnclaveRequest {
             runtime_id: self.runtime_id,
             endpoint: self.endpoint.clone(),
             payload: data,
         };
+        let call = |grpc_client: &EnclaveRPCClient, timeout| -> BoxFuture<Vec<u8>> {
+            let mut options = call_option(context::get_deadline(&ctx), timeout);
+            if let Some(Ok(span_context)) =
+                tracing::get_span_context(&ctx).map(|data| SpanContext::from_bytes(data))
+            {
+                options = with_span_context(options, &span_context);
+            }
+
+            match grpc_client.call_enclave(&req, options) {
+                Ok(rsp) => Box::new(
+                    UnaryResponse::new(rsp)
+                        .map(|r| r.into())
+                        .map_err(|error| convert_error(error, |error| anyhow!("{}", error))),
+                ),
+                Err(error) => Box::new(future::err(convert_error(error, |error| {
+                    anyhow!("{}", error)
+                }))),
+            }
+        };
+        let result = match self.configured {
+            Some(ref configured) => configured.call(session_id, call),
+            None => call(&self.grpc_client, None),
+        };
 
-        match self.grpc_client.call_enclave(&req, Default::default()) {
-            Ok(rsp) => Box::new(rsp.map(|r| r.into()).map_err(|error| error.into())),
-            Err(error) => Box::new(future::err(error.into())),
+        match self.metrics {
+            Some(ref metrics) => metrics.observe_enclave_rpc(&self.endpoint, result),
+            None => result,
         }
     }
 }
+
+#[cfg(not(any(target_env = "sgx", target_arch = "wasm32")))]
+impl Transport for GrpcTransport {
+    fn write_message(
+        &self,
+        ctx: Context,
+        session_id: types::SessionID,
+        data: Vec<u8>,
+        untrusted_plaintext: String,
+    ) -> BoxFuture<Vec<u8>> {
+        let frame = frame_message(&ctx, session_id, data, untrusted_plaintext);
+        self.send(ctx, Some(session_id), frame)
+    }
+
+    fn write_message_impl(&self, ctx: Context, data: Vec<u8>) -> BoxFuture<Vec<u8>> {
+        self.send(ctx, None, data)
+    }
+}
+
+#[cfg(all(test, not(any(target_env = "sgx", target_arch = "wasm32"))))]
+mod test {
+    use super::*;
+    use crate::{builder::RetryPolicy, config::ClientConfig};
+
+    fn config(addresses: &[&str]) -> ConfigHandle {
+        ConfigHandle::new(ClientConfig {
+            addresses: addresses
+                .iter()
+                .map(|address| address.to_string())
+                .collect(),
+            timeout: None,
+            retry_policy: RetryPolicy::default(),
+            key_manager_enclaves: None,
+            keys_cache_size: 0,
+        })
+    }
+
+    /// Make a call, returning the address and timeout it was made with.
+    fn call(
+        clients: &ConfiguredClients<String>,
+        session: Option<types::SessionID>,
+        fail: bool,
+    ) -> (String, Option<Duration>) {
+        let used = Mutex::new(None);
+        let result = clients
+            .call(session, |address, timeout| {
+                *used.lock().unwrap() = Some((address.clone(), timeout));
+                if fail {
+                    Box::new(future::err(anyhow!("unavailable")))
+                } else {
+                    Box::new(future::ok(vec![]))
+                }
+            })
+            .wait();
+        assert_eq!(result.is_err(), fail);
+        used.into_inner().unwrap().expect("call must be made")
+    }
+
+    #[test]
+    fn test_configured_clients() {
+        let config = config(&["a", "b", "c"]);
+        let clients = ConfiguredClients::new(&config, |address: &str| address.to_owned());
+        let session1 = types::SessionID::random();
+        let session2 = types::SessionID::random();
+        let session3 = types::SessionID::random();
+
+        // All frames of a session go to the same node, as do raw frames.
+        assert_eq!(call(&clients, Some(session1), false).0, "a");
+        assert_eq!(call(&clients, Some(session1), false).0, "a");
+        assert_eq!(call(&clients, None, false).0, "a");
+
+        // New sessions stay on the node unless a call failed.
+        assert_eq!(call(&clients, Some(session2), false).0, "a");
+        assert_eq!(call(&clients, Some(session2), true).0, "a");
+        assert_eq!(call(&clients, Some(session2), false).0, "a");
+        assert_eq!(call(&clients, Some(session3), false).0, "b");
+
+        // Timeout changes apply right away.
+        config.update(|config| config.timeout = Some(Duration::from_secs(1)));
+        assert_eq!(
+            call(&clients, Some(session3), false),
+            ("b".to_owned(), Some(Duration::from_secs(1)))
+        );
+
+        // Address changes apply to new sessions only.
+        config.update(|config| config.addresses = vec!["d".to_owned(), "e".to_owned()]);
+        assert_eq!(call(&clients, Some(session3), false).0, "b");
+        assert_eq!(call(&clients, Some(session1), false).0, "d");
+        assert_eq!(call(&clients, Some(session1), true).0, "d");
+        assert_eq!(call(&clients, Some(session2), false).0, "e");
+
+        // Calls fail if no nodes are configured.
+        config.update(|config| config.addresses = vec![]);
+        let result = clients
+            .call(Some(session3), |_, _| -> BoxFuture<Vec<u8>> {
+                panic!("no call must be made")
+            })
+            .wait();
+        assert!(result.is_err());
+    }
+}
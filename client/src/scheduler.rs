@@ -0,0 +1,131 @@
+//! Client for service defined in go/scheduler/api.
+use std::time::Instant;
+
+use futures::prelude::*;
+use grpcio::{CallOption, Channel, Client};
+use io_context::Context;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use oasis_core_runtime::common::{
+    runtime::RuntimeId,
+    scheduler::{Committee, CommitteeKind, Validator},
+};
+
+use crate::{
+    consensus::{ConsensusClient, HEIGHT_LATEST},
+    context,
+    grpc::{call_option, unary_call},
+    BoxFuture,
+};
+
+grpc_method!(
+    METHOD_GET_VALIDATORS,
+    "/oasis-core.Scheduler/GetValidators",
+    i64,
+    Vec<Validator>
+);
+grpc_method!(
+    METHOD_GET_COMMITTEES,
+    "/oasis-core.Scheduler/GetCommittees",
+    GetCommitteesRequest,
+    Vec<Committee>
+);
+
+/// Scheduler client error.
+#[derive(Debug, Error)]
+pub enum SchedulerClientError {
+    #[error("committees for epoch {0} are not available (current epoch: {1})")]
+    EpochUnavailable(u64, u64),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GetCommitteesRequest {
+    pub height: i64,
+    pub runtime_id: RuntimeId,
+}
+
+/// A scheduler gRPC service client.
+#[derive(Clone)]
+pub struct SchedulerClient {
+    client: Client,
+    consensus: ConsensusClient,
+    deadline: Option<Instant>,
+}
+
+impl SchedulerClient {
+    /// Create a new scheduler client.
+    pub fn new(channel: Channel) -> Self {
+        SchedulerClient {
+            client: Client::new(channel.clone()),
+            consensus: ConsensusClient::new(channel),
+            deadline: None,
+        }
+    }
+
+    /// Apply the deadline of the given context to all calls made through the
+    /// returned client.
+    pub fn with_context(&self, ctx: &Context) -> Self {
+        let mut client = self.clone();
+        client.deadline = context::get_deadline(ctx);
+        client.consensus = self.consensus.with_context(ctx);
+        client
+    }
+
+    /// Retrieve the consensus validators at the given height.
+    pub fn get_validators(&self, height: i64) -> BoxFuture<Vec<Validator>> {
+        unary_call(
+            self.client
+                .unary_call_async(&METHOD_GET_VALIDATORS, &height, self.options()),
+        )
+    }
+
+    /// Retrieve all committees of a runtime at the given height.
+    pub fn get_committees(&self, runtime_id: RuntimeId, height: i64) -> BoxFuture<Vec<Committee>> {
+        let request = GetCommitteesRequest { height, runtime_id };
+        unary_call(
+            self.client
+                .unary_call_async(&METHOD_GET_COMMITTEES, &request, self.options()),
+        )
+    }
+
+    /// Retrieve the committee of the given kind of a runtime at the given
+    /// height, if one is elected.
+    pub fn get_committee(
+        &self,
+        runtime_id: RuntimeId,
+        kind: CommitteeKind,
+        height: i64,
+    ) -> BoxFuture<Option<Committee>> {
+        Box::new(
+            self.get_committees(runtime_id, height)
+                .map(move |committees| committees.into_iter().find(|c| c.kind == kind)),
+        )
+    }
+
+    /// Retrieve all committees of a runtime for the given epoch.
+    ///
+    /// Only committees of the current epoch are kept in the consensus state,
+    /// so this fails if the given epoch is not the current one.
+    pub fn get_committees_for_epoch(
+        &self,
+        runtime_id: RuntimeId,
+        epoch: u64,
+    ) -> BoxFuture<Vec<Committee>> {
+        Box::new(
+            self.consensus
+                .get_epoch(HEIGHT_LATEST)
+                .join(self.get_committees(runtime_id, HEIGHT_LATEST))
+                .and_then(move |(current, committees)| {
+                    if current != epoch || committees.iter().any(|c| c.valid_for != epoch) {
+                        return Err(SchedulerClientError::EpochUnavailable(epoch, current).into());
+                    }
+                    Ok(committees)
+                }),
+        )
+    }
+
+    fn options(&self) -> CallOption {
+        call_option(self.deadline, None)
+    }
+}
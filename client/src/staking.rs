@@ -0,0 +1,272 @@
+//! Client for service defined in go/staking/api.
+use std::{collections::BTreeMap, sync::Arc, time::Instant};
+
+use futures::{future, prelude::*};
+use grpcio::{CallOption, Channel, Client};
+use io_context::Context;
+use serde::Serialize;
+use serde_derive::{Deserialize, Serialize};
+
+use oasis_core_runtime::common::{
+    consensus::{Fee, Transaction, TransactionBuilder},
+    crypto::{
+        hash::Hash,
+        signature::{PublicKey, Signer},
+    },
+    quantity::Quantity,
+    staking::{
+        Account, DebondingDelegation, Delegation, Escrow, ReclaimEscrow, ThresholdKind, Transfer,
+        METHOD_ADD_ESCROW, METHOD_RECLAIM_ESCROW, METHOD_TRANSFER,
+    },
+};
+
+use crate::{
+    consensus::{ConsensusClient, HEIGHT_LATEST},
+    context,
+    grpc::{call_option, unary_call},
+    BoxFuture,
+};
+
+grpc_method!(
+    METHOD_THRESHOLD,
+    "/oasis-core.Staking/Threshold",
+    ThresholdQuery,
+    Quantity
+);
+grpc_method!(
+    METHOD_ACCOUNTS,
+    "/oasis-core.Staking/Accounts",
+    i64,
+    Vec<PublicKey>
+);
+grpc_method!(
+    METHOD_ACCOUNT_INFO,
+    "/oasis-core.Staking/AccountInfo",
+    OwnerQuery,
+    Account
+);
+grpc_method!(
+    METHOD_DELEGATIONS,
+    "/oasis-core.Staking/Delegations",
+    OwnerQuery,
+    BTreeMap<PublicKey, Delegation>
+);
+grpc_method!(
+    METHOD_DEBONDING_DELEGATIONS,
+    "/oasis-core.Staking/DebondingDelegations",
+    OwnerQuery,
+    BTreeMap<PublicKey, Vec<DebondingDelegation>>
+);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ThresholdQuery {
+    pub height: i64,
+    pub kind: ThresholdKind,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OwnerQuery {
+    pub height: i64,
+    pub owner: PublicKey,
+}
+
+/// A staking gRPC service client.
+///
+/// All queries take the consensus height at which the staking state is
+/// queried, use `HEIGHT_LATEST` to query the latest state. Transactions are
+/// signed for the given chain and submitted through the consensus service.
+#[derive(Clone)]
+pub struct StakingClient {
+    client: Client,
+    consensus: ConsensusClient,
+    chain_context: String,
+    deadline: Option<Instant>,
+}
+
+impl StakingClient {
+    /// Create a new staking client.
+    pub fn new(channel: Channel, chain_context: &str) -> Self {
+        StakingClient {
+            client: Client::new(channel.clone()),
+            consensus: ConsensusClient::new(channel),
+            chain_context: chain_context.to_owned(),
+            deadline: None,
+        }
+    }
+
+    /// Apply the deadline of the given context to all calls made through the
+    /// returned client.
+    pub fn with_context(&self, ctx: &Context) -> Self {
+        let mut client = self.clone();
+        client.deadline = context::get_deadline(ctx);
+        client.consensus = self.consensus.with_context(ctx);
+        client
+    }
+
+    /// Retrieve the given staking threshold.
+    pub fn threshold(&self, kind: ThresholdKind, height: i64) -> BoxFuture<Quantity> {
+        let request = ThresholdQuery { height, kind };
+        unary_call(
+            self.client
+                .unary_call_async(&METHOD_THRESHOLD, &request, self.options()),
+        )
+    }
+
+    /// Retrieve the public keys of all accounts with a non-zero balance.
+    pub fn accounts(&self, height: i64) -> BoxFuture<Vec<PublicKey>> {
+        unary_call(
+            self.client
+                .unary_call_async(&METHOD_ACCOUNTS, &height, self.options()),
+        )
+    }
+
+    /// Retrieve the account of the given owner.
+    pub fn account_info(&self, owner: PublicKey, height: i64) -> BoxFuture<Account> {
+        let request = OwnerQuery { height, owner };
+        unary_call(
+            self.client
+                .unary_call_async(&METHOD_ACCOUNT_INFO, &request, self.options()),
+        )
+    }
+
+    /// Retrieve the delegations of the given owner, keyed by the escrow
+    /// account.
+    pub fn delegations(
+        &self,
+        owner: PublicKey,
+        height: i64,
+    ) -> BoxFuture<BTreeMap<PublicKey, Delegation>> {
+        let request = OwnerQuery { height, owner };
+        unary_call(
+            self.client
+                .unary_call_async(&METHOD_DELEGATIONS, &request, self.options()),
+        )
+    }
+
+    /// Retrieve the debonding delegations of the given owner, keyed by the
+    /// escrow account.
+    pub fn debonding_delegations(
+        &self,
+        owner: PublicKey,
+        height: i64,
+    ) -> BoxFuture<BTreeMap<PublicKey, Vec<DebondingDelegation>>> {
+        let request = OwnerQuery { height, owner };
+        unary_call(self.client.unary_call_async(
+            &METHOD_DEBONDING_DELEGATIONS,
+            &request,
+            self.options(),
+        ))
+    }
+
+    /// Transfer tokens to another account.
+    pub fn transfer(
+        &self,
+        signer: Arc<dyn Signer>,
+        public_key: PublicKey,
+        fee: Option<Fee>,
+        to: PublicKey,
+        tokens: Quantity,
+    ) -> BoxFuture<Hash> {
+        self.submit(
+            signer,
+            public_key,
+            fee,
+            METHOD_TRANSFER,
+            Transfer { to, tokens },
+        )
+    }
+
+    /// Escrow tokens into an escrow account.
+    pub fn add_escrow(
+        &self,
+        signer: Arc<dyn Signer>,
+        public_key: PublicKey,
+        fee: Option<Fee>,
+        account: PublicKey,
+        tokens: Quantity,
+    ) -> BoxFuture<Hash> {
+        self.submit(
+            signer,
+            public_key,
+            fee,
+            METHOD_ADD_ESCROW,
+            Escrow { account, tokens },
+        )
+    }
+
+    /// Start debonding shares from an escrow account.
+    pub fn reclaim_escrow(
+        &self,
+        signer: Arc<dyn Signer>,
+        public_key: PublicKey,
+        fee: Option<Fee>,
+        account: PublicKey,
+        shares: Quantity,
+    ) -> BoxFuture<Hash> {
+        self.submit(
+            signer,
+            public_key,
+            fee,
+            METHOD_RECLAIM_ESCROW,
+            ReclaimEscrow { account, shares },
+        )
+    }
+
+    /// Estimate the fee needed for a staking transaction signed by the given
+    /// account, at the given price per unit of gas.
+    pub fn estimate_fee<B>(
+        &self,
+        public_key: PublicKey,
+        method: &str,
+        body: B,
+        gas_price: Quantity,
+    ) -> BoxFuture<Fee>
+    where
+        B: Serialize,
+    {
+        let tx = Transaction::new(0, None, method, body);
+        self.consensus.estimate_fee(public_key, &tx, gas_price)
+    }
+
+    /// Sign and submit a staking transaction, using the signer's next nonce.
+    ///
+    /// Returns the hash of the submitted transaction once it is included in
+    /// a block.
+    pub fn submit<B>(
+        &self,
+        signer: Arc<dyn Signer>,
+        public_key: PublicKey,
+        fee: Option<Fee>,
+        method: &str,
+        body: B,
+    ) -> BoxFuture<Hash>
+    where
+        B: Serialize + Send + 'static,
+    {
+        let consensus = self.consensus.clone();
+        let chain_context = self.chain_context.clone();
+        let method = method.to_owned();
+
+        Box::new(
+            self.consensus
+                .get_signer_nonce(public_key, HEIGHT_LATEST)
+                .and_then(move |nonce| {
+                    let mut builder = TransactionBuilder::new(&chain_context, nonce);
+                    if let Some(fee) = fee {
+                        builder = builder.with_fee(fee);
+                    }
+                    let signed = match builder.sign(signer.as_ref(), public_key, &method, body) {
+                        Ok(signed) => signed,
+                        Err(error) => return future::Either::A(future::err(error)),
+                    };
+
+                    let tx_hash = signed.hash();
+                    future::Either::B(consensus.submit_tx(&signed).map(move |_| tx_hash))
+                }),
+        )
+    }
+
+    fn options(&self) -> CallOption {
+        call_option(self.deadline, None)
+    }
+}
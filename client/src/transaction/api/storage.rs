@@ -1,11 +1,11 @@
 //! Client for service defined in go/storage/api.
-use grpcio::{CallOption, Channel, Client, Result};
+use grpcio::{CallOption, Channel, Client, ClientSStreamReceiver, Result};
 use serde_cbor::value::Value;
 use serde_derive::{Deserialize, Serialize};
 
 use oasis_core_runtime::{
     common::{crypto::hash::Hash, roothash::Namespace},
-    storage::mkvs::{sync, WriteLog},
+    storage::mkvs::{sync, Root, WriteLog},
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -18,6 +18,27 @@ pub struct ApplyRequest {
     pub writelog: WriteLog,
 }
 
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SyncOptions {
+    #[serde(with = "serde_bytes")]
+    pub offset_key: Vec<u8>,
+    pub limit: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SyncChunk {
+    #[serde(rename = "final")]
+    pub is_final: bool,
+    pub writelog: WriteLog,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GetDiffRequest {
+    pub start_root: Root,
+    pub end_root: Root,
+    pub options: SyncOptions,
+}
+
 // XXX: This should be an actual receipt once somebody needs it from Rust.
 /// A storage receipt.
 type Receipt = Value;
@@ -46,6 +67,12 @@ grpc_method!(
     sync::IterateRequest,
     sync::ProofResponse
 );
+grpc_stream!(
+    METHOD_GET_DIFF,
+    "/oasis-core.Storage/GetDiff",
+    GetDiffRequest,
+    SyncChunk
+);
 
 /// A (simplified) storage gRPC service client.
 #[derive(Clone)]
@@ -94,4 +121,15 @@ impl StorageClient {
     ) -> Result<sync::ProofResponse> {
         self.client.unary_call(&METHOD_SYNC_ITERATE, &request, opt)
     }
+
+    /// Fetch the write log entries which transform the start root into the
+    /// end root.
+    pub fn get_diff(
+        &self,
+        request: &GetDiffRequest,
+        opt: CallOption,
+    ) -> Result<ClientSStreamReceiver<SyncChunk>> {
+        self.client
+            .server_streaming(&METHOD_GET_DIFF, &request, opt)
+    }
 }
@@ -0,0 +1,338 @@
+//! Resumable runtime block subscription.
+use std::{
+    mem,
+    time::{Duration, Instant},
+};
+
+use anyhow::Error;
+use futures::prelude::*;
+use grpcio::{
+    CallOption, ClientSStreamReceiver, ClientUnaryReceiver, Error::RpcFailure, RpcStatus,
+    RpcStatusCode,
+};
+use tokio::timer::Delay;
+
+use oasis_core_runtime::common::{
+    roothash::{AnnotatedBlock, Block},
+    runtime::RuntimeId,
+};
+
+use super::{
+    api::client::{GetBlockRequest, RuntimeClient},
+    snapshot::BlockSnapshot,
+    storage::StorageNodes,
+};
+use crate::metrics::Metrics;
+
+/// Default maximum number of consecutive reconnection attempts.
+const DEFAULT_MAX_RECONNECTS: usize = 10;
+/// Default initial delay between consecutive reconnection attempts.
+const DEFAULT_RECONNECT_BACKOFF_INITIAL: Duration = Duration::from_millis(100);
+/// Default maximum delay between consecutive reconnection attempts.
+const DEFAULT_RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(10);
+
+/// Block stream error.
+#[derive(Debug, thiserror::Error)]
+pub enum BlockStreamError {
+    #[error("block subscription failed after {0} reconnection attempts: {1}")]
+    ReconnectLimitReached(usize, String),
+    #[error("block for round {0} is no longer available")]
+    RoundUnavailable(u64),
+}
+
+enum State {
+    /// The subscription needs to be (re)established.
+    Disconnected,
+    /// Waiting before reconnecting after a failed attempt.
+    Backoff(Delay),
+    /// Receiving blocks from the subscription.
+    Streaming(ClientSStreamReceiver<AnnotatedBlock>),
+    /// Fetching a round that was missed while disconnected.
+    Backfilling(
+        ClientUnaryReceiver<Block>,
+        ClientSStreamReceiver<AnnotatedBlock>,
+    ),
+}
+
+/// A stream of runtime blocks.
+///
+/// The stream transparently reconnects when the underlying subscription
+/// fails. Rounds that were finalized while the stream was disconnected are
+/// fetched individually, so every round is emitted exactly once and in
+/// order.
+pub struct BlockStream {
+    client: RuntimeClient,
+    storage_client: StorageNodes,
+    runtime_id: RuntimeId,
+    /// Next round to emit, if known.
+    next_round: Option<u64>,
+    /// Block received from the subscription while backfilling.
+    pending: Option<Block>,
+    reconnects: usize,
+    max_reconnects: usize,
+    backoff_initial: Duration,
+    backoff_max: Duration,
+    last_error: String,
+    state: State,
+    metrics: Option<Metrics>,
+}
+
+impl BlockStream {
+    pub(super) fn new(
+        client: RuntimeClient,
+        storage_client: StorageNodes,
+        runtime_id: RuntimeId,
+        start_round: Option<u64>,
+    ) -> Self {
+        Self {
+            client,
+            storage_client,
+            runtime_id,
+            next_round: start_round,
+            pending: None,
+            reconnects: 0,
+            max_reconnects: DEFAULT_MAX_RECONNECTS,
+            backoff_initial: DEFAULT_RECONNECT_BACKOFF_INITIAL,
+            backoff_max: DEFAULT_RECONNECT_BACKOFF_MAX,
+            last_error: String::new(),
+            state: State::Disconnected,
+            metrics: None,
+        }
+    }
+
+    /// Set the maximum number of consecutive reconnection attempts before
+    /// the stream fails.
+    pub fn with_max_reconnects(mut self, max_reconnects: usize) -> Self {
+        self.max_reconnects = max_reconnects;
+        self
+    }
+
+    /// Set the initial and maximum delay between consecutive reconnection
+    /// attempts. The delay doubles with every failed attempt.
+    pub fn with_reconnect_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.backoff_initial = initial;
+        self.backoff_max = max;
+        self
+    }
+
+    /// Record how many rounds the stream is behind in the given metrics.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Round of the next block that will be emitted, if known.
+    pub fn next_round(&self) -> Option<u64> {
+        self.next_round
+    }
+
+    fn emit(&mut self, block: Block) -> BlockSnapshot {
+        self.reconnects = 0;
+        self.next_round = Some(block.header.round + 1);
+        if let Some(ref metrics) = self.metrics {
+            let latest_round = self
+                .pending
+                .as_ref()
+                .map_or(block.header.round, |pending| pending.header.round);
+            metrics.observe_round_lag(latest_round - block.header.round);
+        }
+        BlockSnapshot::new(self.storage_client.clone(), block)
+    }
+
+    /// Delay before the given reconnection attempt (starting at 1).
+    fn backoff(&self, attempt: usize) -> Duration {
+        let factor = 1u32
+            .checked_shl((attempt - 1) as u32)
+            .unwrap_or(u32::max_value());
+        self.backoff_initial
+            .checked_mul(factor)
+            .map(|delay| delay.min(self.backoff_max))
+            .unwrap_or(self.backoff_max)
+    }
+
+    fn connect(&mut self) {
+        match self.client.watch_blocks(self.runtime_id) {
+            Ok(blocks) => self.state = State::Streaming(blocks),
+            Err(error) => self.last_error = format!("{}", error),
+        }
+    }
+
+    fn fetch_block(&self, round: u64) -> grpcio::Result<ClientUnaryReceiver<Block>> {
+        let request = GetBlockRequest {
+            runtime_id: self.runtime_id,
+            round,
+        };
+        self.client
+            .get_block(&request, CallOption::default().wait_for_ready(true))
+    }
+}
+
+impl Stream for BlockStream {
+    type Item = BlockSnapshot;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            match mem::replace(&mut self.state, State::Disconnected) {
+                State::Disconnected => {
+                    if self.reconnects > self.max_reconnects {
+                        return Err(BlockStreamError::ReconnectLimitReached(
+                            self.max_reconnects,
+                            self.last_error.clone(),
+                        )
+                        .into());
+                    }
+                    self.reconnects += 1;
+
+                    // Connect right away the first time, but back off when
+                    // the previous attempt has failed without emitting a
+                    // block.
+                    if self.reconnects > 1 {
+                        let delay = self.backoff(self.reconnects - 1);
+                        self.state = State::Backoff(Delay::new(Instant::now() + delay));
+                        continue;
+                    }
+                    self.connect();
+                }
+                State::Backoff(mut delay) => match delay.poll()? {
+                    Async::NotReady => {
+                        self.state = State::Backoff(delay);
+                        return Ok(Async::NotReady);
+                    }
+                    Async::Ready(()) => self.connect(),
+                },
+                State::Streaming(mut blocks) => {
+                    // Emit the block that triggered a completed backfill.
+                    if let Some(block) = self.pending.take() {
+                        self.state = State::Streaming(blocks);
+                        return Ok(Async::Ready(Some(self.emit(block))));
+                    }
+
+                    let block = match blocks.poll() {
+                        Ok(Async::NotReady) => {
+                            self.state = State::Streaming(blocks);
+                            return Ok(Async::NotReady);
+                        }
+                        Ok(Async::Ready(Some(annotated))) => annotated.block,
+                        Ok(Async::Ready(None)) => {
+                            self.last_error = "subscription closed".to_owned();
+                            continue;
+                        }
+                        Err(error) => {
+                            self.last_error = format!("{}", error);
+                            continue;
+                        }
+                    };
+
+                    let round = block.header.round;
+                    match self.next_round {
+                        // Already emitted, e.g., the current block is sent
+                        // again after reconnecting.
+                        Some(next_round) if round < next_round => {
+                            self.state = State::Streaming(blocks);
+                        }
+                        // Some rounds were missed, fetch them first.
+                        Some(next_round) if round > next_round => {
+                            match self.fetch_block(next_round) {
+                                Ok(rsp) => {
+                                    self.pending = Some(block);
+                                    self.state = State::Backfilling(rsp, blocks);
+                                }
+                                Err(error) => self.last_error = format!("{}", error),
+                            }
+                        }
+                        _ => {
+                            self.state = State::Streaming(blocks);
+                            return Ok(Async::Ready(Some(self.emit(block))));
+                        }
+                    }
+                }
+                State::Backfilling(mut rsp, blocks) => {
+                    let block = match rsp.poll() {
+                        Ok(Async::NotReady) => {
+                            self.state = State::Backfilling(rsp, blocks);
+                            return Ok(Async::NotReady);
+                        }
+                        Ok(Async::Ready(block)) => block,
+                        Err(RpcFailure(RpcStatus {
+                            status: RpcStatusCode::NotFound,
+                            ..
+                        })) => {
+                            let round = self.next_round.expect("backfilling from a known round");
+                            return Err(BlockStreamError::RoundUnavailable(round).into());
+                        }
+                        Err(error) => {
+                            // The current block will be received again after
+                            // reconnecting.
+                            self.pending = None;
+                            self.last_error = format!("{}", error);
+                            continue;
+                        }
+                    };
+
+                    let snapshot = self.emit(block);
+                    let next_round = snapshot.block.header.round + 1;
+                    let pending_round = self
+                        .pending
+                        .as_ref()
+                        .expect("backfilling with a pending block")
+                        .header
+                        .round;
+                    if next_round < pending_round {
+                        match self.fetch_block(next_round) {
+                            Ok(rsp) => self.state = State::Backfilling(rsp, blocks),
+                            Err(error) => {
+                                self.pending = None;
+                                self.last_error = format!("{}", error);
+                            }
+                        }
+                    } else {
+                        self.state = State::Streaming(blocks);
+                    }
+
+                    return Ok(Async::Ready(Some(snapshot)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use grpcio::{ChannelBuilder, EnvBuilder};
+    use tokio::runtime::current_thread::Runtime;
+
+    use super::*;
+
+    #[test]
+    fn test_reconnect_backoff() {
+        // Nothing listens on the port, so every subscription attempt fails.
+        let env = Arc::new(EnvBuilder::new().cq_count(1).build());
+        let channel = ChannelBuilder::new(env).connect("localhost:1");
+        let stream = BlockStream::new(
+            RuntimeClient::new(channel.clone()),
+            StorageNodes::new(vec![channel]),
+            RuntimeId::default(),
+            None,
+        )
+        .with_max_reconnects(3)
+        .with_reconnect_backoff(Duration::from_millis(20), Duration::from_millis(40));
+        assert_eq!(stream.backoff(1), Duration::from_millis(20));
+        assert_eq!(stream.backoff(2), Duration::from_millis(40));
+        assert_eq!(stream.backoff(100), Duration::from_millis(40));
+
+        let mut rt = Runtime::new().unwrap();
+        let start = Instant::now();
+        match rt.block_on(stream.into_future()) {
+            Err((error, _)) => match error.downcast_ref::<BlockStreamError>() {
+                Some(BlockStreamError::ReconnectLimitReached(3, _)) => {}
+                _ => panic!("unexpected error: {}", error),
+            },
+            Ok(_) => panic!("subscription should fail"),
+        }
+        // The three reconnection attempts wait for 20ms, 40ms and 40ms.
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+}
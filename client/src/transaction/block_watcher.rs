@@ -4,16 +4,16 @@ use std::sync::{
     Arc, Mutex,
 };
 
-use failure::Fail;
 use futures::{prelude::*, stream::Fuse, try_ready};
-use tokio::{spawn, sync::watch};
+use tokio::sync::watch;
 
 use super::snapshot::BlockSnapshot;
+use crate::executor::Spawner;
 
 /// Block watcher error.
-#[derive(Debug, Fail)]
+#[derive(Debug, thiserror::Error)]
 pub enum WatchError {
-    #[fail(display = "block watcher closed")]
+    #[error("block watcher closed")]
     WatcherClosed,
 }
 
@@ -61,10 +61,10 @@ impl BlockWatcher {
         );
     }
 
-    /// Spawn a block watcher task.
+    /// Spawn a block watcher task with the given spawner.
     ///
     /// Must only be called after first calling `start_spawn`.
-    pub fn spawn<T>(&self, blocks: T)
+    pub fn spawn<T>(&self, spawner: &dyn Spawner, blocks: T)
     where
         T: Stream<Item = BlockSnapshot> + Send + 'static,
     {
@@ -77,7 +77,7 @@ impl BlockWatcher {
             .expect("must only be called in start_spawn");
 
         let inner = self.inner.clone();
-        spawn(
+        spawner.spawn(Box::new(
             Watch::new(blocks.map(|blk| Some(blk)), tx)
                 .map_err(|_err| ())
                 .and_then(move |tx| {
@@ -90,7 +90,7 @@ impl BlockWatcher {
                     );
                     Ok(())
                 }),
-        );
+        ));
     }
 
     /// Get the latest block.
@@ -108,6 +108,30 @@ impl BlockWatcher {
                     .expect("None blocks were skipped"))
             })
     }
+
+    /// Wait for a block with a round greater than the given round.
+    pub fn wait_block_after(
+        &self,
+        round: u64,
+    ) -> impl Future<Item = BlockSnapshot, Error = WatchError> {
+        self.inner
+            .current_block
+            .clone()
+            .skip_while(move |block| {
+                Ok(match block {
+                    Some(block) => block.block.header.round <= round,
+                    None => true,
+                })
+            })
+            .take(1)
+            .into_future()
+            .map_err(|_err| WatchError::WatcherClosed)
+            .and_then(|(maybe_block, _)| {
+                Ok(maybe_block
+                    .ok_or(WatchError::WatcherClosed)?
+                    .expect("None blocks were skipped"))
+            })
+    }
 }
 
 struct Watch<T: Stream, U> {
@@ -1,63 +1,273 @@
 //! Transaction client.
-use std::time::Duration;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use failure::{Error, Fail, Fallible};
-use futures::{future, prelude::*};
-use grpcio::{Channel, Error::RpcFailure, RpcStatus, RpcStatusCode};
-use rustracing::{sampler::AllSampler, tag};
-use rustracing_jaeger::{span::Span, Tracer};
+use anyhow::{Error, Result};
+use futures::{
+    future::{self, Loop},
+    prelude::*,
+    stream,
+    sync::mpsc,
+};
+use grpcio::{Channel, ClientUnaryReceiver, Error::RpcFailure, RpcStatus, RpcStatusCode};
+use io_context::Context;
 use serde::{de::DeserializeOwned, Serialize};
+use tracing::{info_span, Span};
 
 use oasis_core_runtime::{
-    common::{cbor, crypto::hash::Hash, runtime::RuntimeId},
-    transaction::types::{TxnBatch, TxnCall, TxnOutput},
+    common::{cbor, crypto::hash::Hash, roothash::Block, runtime::RuntimeId},
+    storage::mkvs::Root,
+    tracing::enter_span,
+    transaction::{
+        dispatcher::MethodDispatcher,
+        tree::{Tree as IoTree, TxnReceipt},
+        types::{TxnBatch, TxnCall, TxnOutput},
+    },
 };
 
 use super::{
     api,
+    block_stream::BlockStream,
     block_watcher::BlockWatcher,
+    events::{EventCursor, EventStream},
+    query::{self, QueryCursor, QueryPage},
     snapshot::{BlockSnapshot, TransactionSnapshot},
+    status::{StatusSender, TxnStatus, TxnStatusStream},
+    storage::{StateDiff, StorageNodes},
+};
+use crate::{
+    circuit_breaker::CircuitBreaker,
+    context,
+    executor::{default_spawner, Spawner},
+    grpc::{self, call_option, with_span_context, GrpcError, UnaryResponse},
+    metrics::Metrics,
+    pagination::PageStream,
+    BoxFuture,
 };
-use crate::BoxFuture;
 
 /// Transaction client error.
-#[derive(Debug, Fail)]
+#[derive(Debug, thiserror::Error)]
 pub enum TxnClientError {
-    #[fail(display = "node call failed: {}", 0)]
+    #[error("node call failed: {0}")]
     CallFailed(String),
-    #[fail(display = "block watcher closed")]
+    #[error("node unavailable: {0}")]
+    NodeUnavailable(String),
+    #[error("block watcher closed")]
     WatcherClosed,
-    #[fail(display = "transaction failed: {}", 0)]
+    #[error("transaction failed: {0}")]
     TxnFailed(String),
+    #[error("transaction not included after {0} resubmissions: {1}")]
+    ResubmitLimitReached(usize, String),
+    #[error("block for round {0} not found")]
+    BlockNotFound(u64),
+    #[error("no query methods registered")]
+    NoQueryMethods,
+    #[error("transaction {0} not found in blocks after round {1}")]
+    TxnNotFound(Hash, u64),
+    #[error("read quorum must be between 1 and the number of read nodes (got: {read_quorum} nodes: {nodes})")]
+    InvalidReadQuorum { read_quorum: usize, nodes: usize },
+    #[error("max in flight must be positive")]
+    InvalidMaxInFlight,
+    #[error("read quorum not reached (got: {got} required: {required} last error: {last_error})")]
+    ReadQuorumNotReached {
+        got: usize,
+        required: usize,
+        last_error: String,
+    },
 }
 
+/// Default maximum number of times a transaction is resubmitted.
+pub const DEFAULT_MAX_RESUBMITS: usize = 5;
+
+/// Default maximum number of transactions in flight in `submit_txs`.
+const DEFAULT_MAX_IN_FLIGHT: usize = 32;
+
+/// Function creating a dispatcher with the runtime's methods registered.
+type QueryMethods = Arc<dyn Fn() -> MethodDispatcher + Send + Sync>;
+
 /// Interface for the node's client interface.
+#[derive(Clone)]
 pub struct TxnClient {
     /// The underlying client gRPC interface.
     client: api::client::RuntimeClient,
+    /// The client gRPC interfaces used for reading blocks.
+    read_clients: Vec<api::client::RuntimeClient>,
+    /// Number of read nodes that must agree on a block.
+    read_quorum: usize,
     /// The underlying node controller gRPC interface.
     node_controller: api::control::NodeControllerClient,
-    /// The underlying storage gRPC interface.
-    storage_client: api::storage::StorageClient,
+    /// The storage nodes used to access the runtime state.
+    storage_client: StorageNodes,
     /// Runtime identifier.
     runtime_id: RuntimeId,
     /// RPC timeout.
     timeout: Option<Duration>,
+    /// Deadline for all calls.
+    deadline: Option<Instant>,
     /// Block watcher for `get_latest_block` call.
     block_watcher: BlockWatcher,
+    /// Maximum number of resubmissions in `submit_and_wait`.
+    max_resubmits: usize,
+    /// Maximum number of transactions in flight in `submit_txs`.
+    max_in_flight: usize,
+    /// Circuit breaker for calls to the node.
+    circuit_breaker: Option<CircuitBreaker>,
+    /// Metrics to record calls in, if any.
+    metrics: Option<Metrics>,
+    /// Spawner of the block watcher.
+    spawner: Arc<dyn Spawner>,
+    /// Runtime methods used by `query_at`, if any.
+    query_methods: Option<QueryMethods>,
 }
 
 impl TxnClient {
     /// Create a new transaction client.
     pub fn new(channel: Channel, runtime_id: RuntimeId, timeout: Option<Duration>) -> Self {
+        let client = api::client::RuntimeClient::new(channel.clone());
+
         Self {
-            client: api::client::RuntimeClient::new(channel.clone()),
+            read_clients: vec![client.clone()],
+            read_quorum: 1,
+            client,
             node_controller: api::control::NodeControllerClient::new(channel.clone()),
-            storage_client: api::storage::StorageClient::new(channel),
+            storage_client: StorageNodes::new(vec![channel]),
             runtime_id: runtime_id.clone(),
             timeout: timeout,
+            deadline: None,
             block_watcher: BlockWatcher::new(),
+            max_resubmits: DEFAULT_MAX_RESUBMITS,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            circuit_breaker: None,
+            metrics: None,
+            spawner: default_spawner(),
+            query_methods: None,
+        }
+    }
+
+    /// Access the runtime state through the given storage nodes instead of
+    /// the storage service of the node the client is connected to.
+    ///
+    /// This allows reads to fail over between members of the storage
+    /// committee.
+    pub fn with_storage_nodes(mut self, storage_nodes: StorageNodes) -> Self {
+        self.storage_client = storage_nodes;
+        self
+    }
+
+    /// Read blocks from the given nodes instead of the node the client is
+    /// connected to.
+    ///
+    /// Together with `with_read_quorum` this protects against a single stale
+    /// or malicious node.
+    pub fn with_read_nodes(mut self, channels: Vec<Channel>) -> Self {
+        self.read_clients = channels
+            .into_iter()
+            .map(api::client::RuntimeClient::new)
+            .collect();
+        self
+    }
+
+    /// Set the number of read nodes which must return the same block for a
+    /// block read to succeed.
+    ///
+    /// With the default of one, the first successful response is used. The
+    /// quorum is validated by `build`.
+    pub fn with_read_quorum(mut self, read_quorum: usize) -> Self {
+        self.read_quorum = read_quorum;
+        self
+    }
+
+    /// Set the maximum number of times `submit_and_wait` resubmits a
+    /// transaction before giving up.
+    pub fn with_max_resubmits(mut self, max_resubmits: usize) -> Self {
+        self.max_resubmits = max_resubmits;
+        self
+    }
+
+    /// Set the maximum number of transactions that `submit_txs` has in
+    /// flight at the same time.
+    ///
+    /// The limit is validated by `build`.
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight;
+        self
+    }
+
+    /// Make transaction submissions and block reads through the given
+    /// circuit breaker.
+    ///
+    /// While the circuit is open, calls fail fast with
+    /// `CircuitBreakerError::CircuitOpen` and resubmissions are limited by the
+    /// breaker's retry budget.
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    /// Record transaction submissions, query latencies and the round lag of
+    /// block streams in the given metrics.
+    ///
+    /// Storage reads are only counted if the storage client has been given
+    /// the metrics as well.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Set the spawner the block watcher of `get_latest_block` is spawned
+    /// with.
+    pub fn with_spawner(mut self, spawner: Arc<dyn Spawner>) -> Self {
+        self.spawner = spawner;
+        self
+    }
+
+    /// Register the runtime methods `query_at` evaluates queries with.
+    ///
+    /// The given function must return a dispatcher with the runtime's methods
+    /// registered. It is called for each query, as dispatchers cannot be
+    /// shared between threads.
+    pub fn with_query_methods<F>(mut self, query_methods: F) -> Self
+    where
+        F: Fn() -> MethodDispatcher + Send + Sync + 'static,
+    {
+        self.query_methods = Some(Arc::new(query_methods));
+        self
+    }
+
+    /// Validate the configuration.
+    ///
+    /// Returns an error if the read quorum is zero or larger than the number
+    /// of read nodes, if the maximum number of transactions in flight is
+    /// zero or if the circuit breaker is misconfigured.
+    pub fn build(self) -> Result<Self> {
+        if self.read_quorum == 0 || self.read_quorum > self.read_clients.len() {
+            return Err(TxnClientError::InvalidReadQuorum {
+                read_quorum: self.read_quorum,
+                nodes: self.read_clients.len(),
+            }
+            .into());
+        }
+        if self.max_in_flight == 0 {
+            return Err(TxnClientError::InvalidMaxInFlight.into());
+        }
+        if let Some(ref circuit_breaker) = self.circuit_breaker {
+            circuit_breaker.validate()?;
         }
+        Ok(self)
+    }
+
+    /// Apply the deadline of the given context to all calls made through the
+    /// returned client, including storage reads.
+    ///
+    /// Calls that are still in flight when the deadline expires fail with
+    /// `GrpcError::Timeout`.
+    pub fn with_context(&self, ctx: &Context) -> Self {
+        let mut client = self.clone();
+        client.deadline = context::get_deadline(ctx);
+        client.storage_client = self.storage_client.with_context(ctx);
+        client
     }
 
     /// Call a remote method.
@@ -71,10 +281,7 @@ impl TxnClient {
             args: cbor::to_value(args),
         };
 
-        Box::new(
-            self.submit_tx_raw(&call)
-                .and_then(|out| parse_call_output(out)),
-        )
+        self.submit_and_wait(call)
     }
 
     /// Dispatch a raw call to the node.
@@ -88,31 +295,212 @@ impl TxnClient {
             data: cbor::to_vec(&call),
         };
 
-        match self.client.submit_tx(&request, options) {
-            Ok(resp) => Box::new(
-                resp.map(|r| {
-                    drop(span);
-                    r.into()
-                })
-                .map_err(|error| TxnClientError::CallFailed(format!("{}", error)).into()),
-            ),
-            Err(error) => Box::new(future::err(
-                TxnClientError::CallFailed(format!("{}", error)).into(),
-            )),
+        let result = self.guarded(
+            || -> BoxFuture<Vec<u8>> {
+                match self.client.submit_tx(&request, options) {
+                    Ok(resp) => Box::new(
+                        UnaryResponse::new(resp)
+                            .map(|r| {
+                                drop(span);
+                                r.into()
+                            })
+                            .map_err(call_failed),
+                    ),
+                    Err(error) => Box::new(future::err(call_failed(error))),
+                }
+            },
+            |_| true,
+        );
+        match self.metrics {
+            Some(ref metrics) => metrics.observe_submission(result),
+            None => result,
         }
     }
 
+    /// Submit a transaction and wait for it to be included in a block.
+    ///
+    /// If the submission fails because the node was unavailable, the
+    /// transaction is resubmitted once the next block has been observed.
+    /// Gives up after the configured number of resubmissions.
+    pub fn submit_and_wait<O>(&self, call: TxnCall) -> BoxFuture<O>
+    where
+        O: DeserializeOwned + Send + 'static,
+    {
+        Box::new(
+            self.submit_with_resubmits(call, StatusSender::default())
+                .and_then(|(_, out)| parse_call_output(out)),
+        )
+    }
+
+    /// Submit a transaction and follow its progress.
+    ///
+    /// The returned stream yields status updates as the transaction is
+    /// submitted, resubmitted and executed, and ends once the block
+    /// including the transaction has been found (`TxnStatus::Finalized`) or
+    /// the transaction failed (`TxnStatus::Failed`).
+    pub fn submit_with_status(&self, call: TxnCall) -> TxnStatusStream {
+        let (sender, receiver) = mpsc::unbounded();
+        let status = StatusSender::new(sender);
+        let final_status = status.clone();
+        let tx_hash = Hash::digest_bytes(&cbor::to_vec(&call));
+        let client = self.clone();
+
+        let submission = self
+            .submit_with_resubmits(call, status.clone())
+            .and_then(move |(round, out)| -> BoxFuture<u64> {
+                match parse_call_output(out) {
+                    Ok(output) => {
+                        status.send(TxnStatus::Executed { output });
+                        client.find_tx_round(tx_hash, round)
+                    }
+                    Err(error) => Box::new(future::err(error)),
+                }
+            })
+            .then(move |result| {
+                final_status.send(match result {
+                    Ok(round) => TxnStatus::Finalized { round },
+                    Err(error) => TxnStatus::Failed {
+                        reason: format!("{}", error),
+                    },
+                });
+                Ok::<_, Error>(())
+            });
+
+        Box::new(
+            receiver
+                .map_err(|_| unreachable!("unbounded receivers do not fail"))
+                .select(submission.into_stream().filter_map(|_| None)),
+        )
+    }
+
+    /// Submit a transaction, resubmitting it if the node was unavailable.
+    ///
+    /// Returns the output of the transaction together with the latest round
+    /// before its last submission.
+    fn submit_with_resubmits(
+        &self,
+        call: TxnCall,
+        status: StatusSender,
+    ) -> BoxFuture<(u64, Vec<u8>)> {
+        let client = self.clone();
+
+        Box::new(future::loop_fn(0, move |resubmits| {
+            let client = client.clone();
+            let call = call.clone();
+            let status = status.clone();
+
+            client.get_latest_block().and_then(move |block| {
+                let round = block.block.header.round;
+
+                status.send(TxnStatus::Queued { attempt: resubmits });
+                client.submit_tx_raw(&call).then(
+                    move |result| -> BoxFuture<Loop<(u64, Vec<u8>), usize>> {
+                        match result {
+                            Ok(out) => Box::new(future::ok(Loop::Break((round, out)))),
+                            Err(error) if is_retryable(&error) => {
+                                if client.deadline_expired() {
+                                    return Box::new(future::err(GrpcError::Timeout.into()));
+                                }
+                                if resubmits >= client.max_resubmits {
+                                    return Box::new(future::err(
+                                        TxnClientError::ResubmitLimitReached(
+                                            resubmits,
+                                            format!("{}", error),
+                                        )
+                                        .into(),
+                                    ));
+                                }
+                                if let Some(ref circuit_breaker) = client.circuit_breaker {
+                                    if let Err(error) = circuit_breaker.try_retry() {
+                                        return Box::new(future::err(error));
+                                    }
+                                }
+
+                                status.send(TxnStatus::Resubmitting {
+                                    attempt: resubmits,
+                                    reason: format!("{}", error),
+                                });
+                                Box::new(
+                                    client
+                                        .block_watcher
+                                        .wait_block_after(round)
+                                        .map_err(|err| err.into())
+                                        .map(move |_| Loop::Continue(resubmits + 1)),
+                                )
+                            }
+                            Err(error) => Box::new(future::err(error)),
+                        }
+                    },
+                )
+            })
+        }))
+    }
+
+    /// Find the round of the block including the given transaction, searching
+    /// backwards from the latest block to the block after `after_round`.
+    fn find_tx_round(&self, tx_hash: Hash, after_round: u64) -> BoxFuture<u64> {
+        let client = self.clone();
+
+        Box::new(self.get_latest_block().and_then(move |latest| {
+            future::loop_fn(
+                latest.block.header.round,
+                move |round| -> BoxFuture<Loop<u64, u64>> {
+                    if round <= after_round {
+                        return Box::new(future::err(
+                            TxnClientError::TxnNotFound(tx_hash, after_round).into(),
+                        ));
+                    }
+
+                    let storage_client = client.storage_client.clone();
+                    Box::new(client.get_block(round).and_then(move |block| {
+                        let block = block.ok_or(TxnClientError::BlockNotFound(round))?;
+                        let tree = IoTree::new(
+                            Box::new(storage_client),
+                            block.block.header.io_tree_root(),
+                        )?;
+                        match tree.get_output(Context::background(), tx_hash)? {
+                            Some(_) => Ok(Loop::Break(round)),
+                            None => Ok(Loop::Continue(round - 1)),
+                        }
+                    }))
+                },
+            )
+        }))
+    }
+
+    /// Submit a group of transactions and wait for all of them to be
+    /// included in a block.
+    ///
+    /// The transactions are submitted concurrently over the same connection,
+    /// with at most the configured number in flight. The result contains the
+    /// outcome of each transaction, in the order the transactions were given,
+    /// so a failed transaction does not affect the others.
+    pub fn submit_txs<O>(&self, ctx: Context, calls: Vec<TxnCall>) -> BoxFuture<Vec<Result<O>>>
+    where
+        O: DeserializeOwned + Send + 'static,
+    {
+        let client = self.with_context(&ctx);
+        let max_in_flight = self.max_in_flight;
+
+        Box::new(
+            stream::iter_ok::<_, Error>(calls)
+                .map(move |call| {
+                    client
+                        .submit_and_wait(call)
+                        .then(|result| Ok::<_, Error>(result))
+                })
+                .buffered(max_in_flight)
+                .collect(),
+        )
+    }
+
     /// Wait for the node to finish syncing.
     pub fn wait_sync(&self) -> BoxFuture<()> {
         let (span, options) = self.prepare_options("TxnClient::wait_sync");
 
         let result: BoxFuture<()> = match self.node_controller.wait_sync(options) {
-            Ok(resp) => Box::new(
-                resp.map_err(|error| TxnClientError::CallFailed(format!("{}", error)).into()),
-            ),
-            Err(error) => Box::new(future::err(
-                TxnClientError::CallFailed(format!("{}", error)).into(),
-            )),
+            Ok(resp) => Box::new(UnaryResponse::new(resp).map_err(call_failed)),
+            Err(error) => Box::new(future::err(call_failed(error))),
         };
         drop(span);
         result
@@ -123,12 +511,8 @@ impl TxnClient {
         let (span, options) = self.prepare_options("TxnClient::is_synced");
 
         let result: BoxFuture<bool> = match self.node_controller.is_synced(options) {
-            Ok(resp) => Box::new(
-                resp.map_err(|error| TxnClientError::CallFailed(format!("{}", error)).into()),
-            ),
-            Err(error) => Box::new(future::err(
-                TxnClientError::CallFailed(format!("{}", error)).into(),
-            )),
+            Ok(resp) => Box::new(UnaryResponse::new(resp).map_err(call_failed)),
+            Err(error) => Box::new(future::err(call_failed(error))),
         };
         drop(span);
         result
@@ -140,14 +524,16 @@ impl TxnClient {
         let runtime_id = self.runtime_id.clone();
         let client = self.client.clone();
         let storage_client = self.storage_client.clone();
+        let spawner = self.spawner.clone();
 
         Box::new(future::lazy(move || -> BoxFuture<BlockSnapshot> {
             // Spawn block watcher if not running yet.
             if block_watcher.start_spawn() {
                 let block_watcher = block_watcher.clone();
-                match client.watch_blocks(runtime_id) {
+                match client.watch_blocks(&runtime_id, Default::default()) {
                     Ok(blocks) => {
                         block_watcher.spawn(
+                            &*spawner,
                             blocks
                                 .map_err(|err| -> Error { err.into() })
                                 .and_then(move |rsp| {
@@ -158,9 +544,7 @@ impl TxnClient {
                     Err(error) => {
                         // Failed to start watching blocks, retry on next attempt.
                         block_watcher.cancel_spawn();
-                        return Box::new(future::err(
-                            TxnClientError::CallFailed(format!("{}", error)).into(),
-                        ));
+                        return Box::new(future::err(call_failed(error)));
                     }
                 }
             }
@@ -169,33 +553,102 @@ impl TxnClient {
         }))
     }
 
+    /// Subscribe to runtime blocks, starting with the latest block.
+    ///
+    /// The returned stream reconnects automatically and does not skip any
+    /// rounds, so it can be used instead of polling `get_block`.
+    pub fn watch_blocks(&self) -> BlockStream {
+        self.block_stream(None)
+    }
+
+    /// Subscribe to runtime blocks, starting at the given round.
+    ///
+    /// Rounds before the latest block are fetched from the node first. This
+    /// can be used to resume a subscription from the last seen round.
+    pub fn watch_blocks_from(&self, round: u64) -> BlockStream {
+        self.block_stream(Some(round))
+    }
+
+    /// Subscribe to runtime events, starting with the events in the latest
+    /// block.
+    pub fn watch_events(&self) -> EventStream {
+        EventStream::new(
+            self.clone(),
+            self.storage_client.clone(),
+            self.watch_blocks(),
+            None,
+        )
+    }
+
+    /// Subscribe to runtime events, starting with the events in the given
+    /// round.
+    ///
+    /// Rounds before the latest block are backfilled first, after which the
+    /// stream continues with live events. Each event is emitted exactly once.
+    pub fn watch_events_from(&self, round: u64) -> EventStream {
+        EventStream::new(
+            self.clone(),
+            self.storage_client.clone(),
+            self.watch_blocks_from(round),
+            None,
+        )
+    }
+
+    /// Subscribe to runtime events, resuming after the given event.
+    ///
+    /// This can be used to continue processing events after a restart
+    /// without missing or repeating any events.
+    pub fn resume_events(&self, after: EventCursor) -> EventStream {
+        EventStream::new(
+            self.clone(),
+            self.storage_client.clone(),
+            self.watch_blocks_from(after.round),
+            Some(after),
+        )
+    }
+
     /// Retrieve block snapshot at specified round.
     pub fn get_block(&self, round: u64) -> BoxFuture<Option<BlockSnapshot>> {
-        let (span, options) = self.prepare_options("TxnClient::get_block");
         let request = api::client::GetBlockRequest {
             runtime_id: self.runtime_id,
             round: round,
         };
 
-        let result: BoxFuture<Option<BlockSnapshot>> =
-            match self.client.get_block(&request, options) {
-                Ok(resp) => {
-                    let storage_client = self.storage_client.clone();
-                    Box::new(resp.then(move |result| match result {
-                        Err(RpcFailure(RpcStatus {
-                            status: RpcStatusCode::NotFound,
-                            ..
-                        })) => Ok(None),
-                        Err(error) => Err(TxnClientError::CallFailed(format!("{}", error)).into()),
-                        Ok(rsp) => Ok(Some(BlockSnapshot::new(storage_client, rsp))),
-                    }))
-                }
-                Err(error) => Box::new(future::err(
-                    TxnClientError::CallFailed(format!("{}", error)).into(),
-                )),
-            };
-        drop(span);
-        result
+        self.read_block("TxnClient::get_block", move |client, options| {
+            client.get_block(&request, options)
+        })
+    }
+
+    /// Evaluate a runtime method against the state at the given round.
+    ///
+    /// The state root is taken from the block at the given round and the
+    /// method is executed locally using the methods registered with
+    /// `with_query_methods`, with all state verified against that root. See
+    /// `BlockSnapshot::query` for details.
+    pub fn query_at<C, O>(&self, ctx: Context, round: u64, method: &str, args: C) -> BoxFuture<O>
+    where
+        C: Serialize,
+        O: DeserializeOwned + Send + 'static,
+    {
+        let query_methods = match self.query_methods {
+            Some(ref query_methods) => query_methods.clone(),
+            None => return Box::new(future::err(TxnClientError::NoQueryMethods.into())),
+        };
+        let ctx = ctx.freeze();
+        let method = method.to_owned();
+        let args = cbor::to_value(args);
+
+        Box::new(self.get_block(round).and_then(move |snapshot| {
+            let dispatcher = query_methods();
+            query_snapshot(
+                snapshot,
+                round,
+                Context::create_child(&ctx),
+                &dispatcher,
+                &method,
+                args,
+            )
+        }))
     }
 
     /// Retrieve transaction at specified block round and index.
@@ -211,12 +664,12 @@ impl TxnClient {
             match self.client.get_tx(&request, options) {
                 Ok(resp) => {
                     let storage_client = self.storage_client.clone();
-                    Box::new(resp.then(move |result| match result {
+                    Box::new(UnaryResponse::new(resp).then(move |result| match result {
                         Err(RpcFailure(RpcStatus {
                             status: RpcStatusCode::NotFound,
                             ..
                         })) => Ok(None),
-                        Err(error) => Err(TxnClientError::CallFailed(format!("{}", error)).into()),
+                        Err(error) => Err(call_failed(error)),
                         Ok(rsp) => Ok(Some(TransactionSnapshot::new(
                             storage_client,
                             rsp.block,
@@ -226,12 +679,10 @@ impl TxnClient {
                         )?)),
                     }))
                 }
-                Err(error) => Box::new(future::err(
-                    TxnClientError::CallFailed(format!("{}", error)).into(),
-                )),
+                Err(error) => Box::new(future::err(call_failed(error))),
             };
         drop(span);
-        result
+        self.observe_query("TxnClient::get_tx", result)
     }
 
     /// Retrieve transaction at specified block hash and index.
@@ -251,12 +702,12 @@ impl TxnClient {
             match self.client.get_tx_by_block_hash(&request, options) {
                 Ok(resp) => {
                     let storage_client = self.storage_client.clone();
-                    Box::new(resp.then(move |result| match result {
+                    Box::new(UnaryResponse::new(resp).then(move |result| match result {
                         Err(RpcFailure(RpcStatus {
                             status: RpcStatusCode::NotFound,
                             ..
                         })) => Ok(None),
-                        Err(error) => Err(TxnClientError::CallFailed(format!("{}", error)).into()),
+                        Err(error) => Err(call_failed(error)),
                         Ok(rsp) => Ok(Some(TransactionSnapshot::new(
                             storage_client,
                             rsp.block,
@@ -266,14 +717,54 @@ impl TxnClient {
                         )?)),
                     }))
                 }
-                Err(error) => Box::new(future::err(
-                    TxnClientError::CallFailed(format!("{}", error)).into(),
-                )),
+                Err(error) => Box::new(future::err(call_failed(error))),
             };
         drop(span);
         result
     }
 
+    /// Retrieve a receipt for the transaction at the specified block round
+    /// and index.
+    ///
+    /// The receipt includes proofs which have been verified against the I/O
+    /// root of the block and can be passed on to third parties.
+    pub fn get_receipt(&self, round: u64, index: u32) -> BoxFuture<Option<TxnReceipt>> {
+        let client = self.clone();
+
+        Box::new(
+            self.get_block(round)
+                .and_then(move |block| -> BoxFuture<Option<TxnReceipt>> {
+                    let header = match block {
+                        Some(block) => block.block.header,
+                        None => return Box::new(future::ok(None)),
+                    };
+                    let io_root = header.io_tree_root();
+                    let storage_client = client.storage_client.clone();
+
+                    Box::new(
+                        client
+                            .get_txs(round, header.io_root)
+                            .and_then(move |batch| {
+                                let tx_hash = match batch.get(index as usize) {
+                                    Some(input) => Hash::digest_bytes(input),
+                                    None => return Ok(None),
+                                };
+
+                                let ctx = Context::background().freeze();
+                                let tree = IoTree::new(Box::new(storage_client), io_root)?;
+                                let receipt =
+                                    tree.get_receipt(Context::create_child(&ctx), tx_hash)?;
+                                if let Some(ref receipt) = receipt {
+                                    receipt.verify(Context::create_child(&ctx), io_root)?;
+                                }
+
+                                Ok(receipt)
+                            }),
+                    )
+                }),
+        )
+    }
+
     /// Retrieve transactions at specific I/O root.
     pub fn get_txs(&self, round: u64, io_root: Hash) -> BoxFuture<TxnBatch> {
         let (span, options) = self.prepare_options("TxnClient::get_txs");
@@ -284,12 +775,8 @@ impl TxnClient {
         };
 
         let result: BoxFuture<TxnBatch> = match self.client.get_txs(&request, options) {
-            Ok(resp) => Box::new(
-                resp.map_err(|error| TxnClientError::CallFailed(format!("{}", error)).into()),
-            ),
-            Err(error) => Box::new(future::err(
-                TxnClientError::CallFailed(format!("{}", error)).into(),
-            )),
+            Ok(resp) => Box::new(UnaryResponse::new(resp).map_err(call_failed)),
+            Err(error) => Box::new(future::err(call_failed(error))),
         };
         drop(span);
         result
@@ -297,31 +784,59 @@ impl TxnClient {
 
     /// Retrieve a block by its hash.
     pub fn get_block_by_hash(&self, block_hash: Hash) -> BoxFuture<Option<BlockSnapshot>> {
-        let (span, options) = self.prepare_options("TxnClient::get_block_by_hash");
         let request = api::client::GetBlockByHashRequest {
             runtime_id: self.runtime_id,
             block_hash,
         };
 
-        let result: BoxFuture<Option<BlockSnapshot>> =
-            match self.client.get_block_by_hash(&request, options) {
-                Ok(resp) => {
-                    let storage_client = self.storage_client.clone();
-                    Box::new(resp.then(move |result| match result {
-                        Err(RpcFailure(RpcStatus {
-                            status: RpcStatusCode::NotFound,
-                            ..
-                        })) => Ok(None),
-                        Err(error) => Err(TxnClientError::CallFailed(format!("{}", error)).into()),
-                        Ok(rsp) => Ok(Some(BlockSnapshot::new(storage_client, rsp))),
-                    }))
-                }
-                Err(error) => Box::new(future::err(
-                    TxnClientError::CallFailed(format!("{}", error)).into(),
-                )),
-            };
-        drop(span);
-        result
+        self.read_block("TxnClient::get_block_by_hash", move |client, options| {
+            client.get_block_by_hash(&request, options)
+        })
+    }
+
+    /// Retrieve the state changes made in the given round.
+    ///
+    /// The returned diff can be applied to a local copy of the state at the
+    /// end of the previous round using `StateDiff::apply`, which allows the
+    /// runtime state to be mirrored without running a compute node.
+    pub fn get_state_diff(&self, round: u64) -> BoxFuture<Option<StateDiff>> {
+        let storage_client = self.storage_client.clone();
+        let previous: BoxFuture<Option<BlockSnapshot>> = if round > 0 {
+            self.get_block(round - 1)
+        } else {
+            Box::new(future::ok(None))
+        };
+
+        Box::new(self.get_block(round).join(previous).and_then(
+            move |(block, previous)| -> BoxFuture<Option<StateDiff>> {
+                let header = match block {
+                    Some(block) => block.block.header,
+                    None => return Box::new(future::ok(None)),
+                };
+                let end_root = header.state_tree_root();
+                let start_root = match previous {
+                    Some(previous) => previous.block.header.state_tree_root(),
+                    // The first round starts with an empty state.
+                    None if round == 0 => Root {
+                        hash: Hash::empty_hash(),
+                        ..end_root
+                    },
+                    None => return Box::new(future::ok(None)),
+                };
+
+                Box::new(
+                    storage_client
+                        .get_diff(start_root, end_root)
+                        .map(move |write_log| {
+                            Some(StateDiff {
+                                start_root,
+                                end_root,
+                                write_log,
+                            })
+                        }),
+                )
+            },
+        ))
     }
 
     /// Query the transaction index.
@@ -341,12 +856,12 @@ impl TxnClient {
             match self.client.query_tx(&request, options) {
                 Ok(resp) => {
                     let storage_client = self.storage_client.clone();
-                    Box::new(resp.then(move |result| match result {
+                    Box::new(UnaryResponse::new(resp).then(move |result| match result {
                         Err(RpcFailure(RpcStatus {
                             status: RpcStatusCode::NotFound,
                             ..
                         })) => Ok(None),
-                        Err(error) => Err(TxnClientError::CallFailed(format!("{}", error)).into()),
+                        Err(error) => Err(call_failed(error)),
                         Ok(rsp) => Ok(Some(TransactionSnapshot::new(
                             storage_client,
                             rsp.block,
@@ -356,9 +871,7 @@ impl TxnClient {
                         )?)),
                     }))
                 }
-                Err(error) => Box::new(future::err(
-                    TxnClientError::CallFailed(format!("{}", error)).into(),
-                )),
+                Err(error) => Box::new(future::err(call_failed(error))),
             };
         drop(span);
         result
@@ -372,35 +885,145 @@ impl TxnClient {
             query,
         };
 
-        let result: BoxFuture<Vec<TransactionSnapshot>> = match self
-            .client
-            .query_txs(&request, options)
-        {
-            Ok(resp) => {
-                let storage_client = self.storage_client.clone();
-                Box::new(
-                    resp.map_err(|error| TxnClientError::CallFailed(format!("{}", error)).into())
-                        .and_then(move |rsp| {
-                            rsp.into_iter()
-                                .map(|tx| {
-                                    TransactionSnapshot::new(
-                                        storage_client.clone(),
-                                        tx.block,
-                                        tx.index,
-                                        tx.input,
-                                        tx.output,
-                                    )
-                                })
-                                .collect::<Result<_, _>>()
-                        }),
-                )
-            }
-            Err(error) => Box::new(future::err(
-                TxnClientError::CallFailed(format!("{}", error)).into(),
-            )),
-        };
+        let result: BoxFuture<Vec<TransactionSnapshot>> =
+            match self.client.query_txs(&request, options) {
+                Ok(resp) => {
+                    let storage_client = self.storage_client.clone();
+                    Box::new(
+                        UnaryResponse::new(resp)
+                            .map_err(call_failed)
+                            .and_then(move |rsp| {
+                                rsp.into_iter()
+                                    .map(|tx| {
+                                        TransactionSnapshot::new(
+                                            storage_client.clone(),
+                                            tx.block,
+                                            tx.index,
+                                            tx.input,
+                                            tx.output,
+                                        )
+                                    })
+                                    .collect::<Result<_, _>>()
+                            }),
+                    )
+                }
+                Err(error) => Box::new(future::err(call_failed(error))),
+            };
         drop(span);
-        result
+        self.observe_query("TxnClient::query_txs", result)
+    }
+
+    /// Query transactions by tags and return a single page of results.
+    ///
+    /// Results are ordered by round and index. To fetch the next page, call
+    /// this method again with the cursor returned in the previous page. If
+    /// the node does not index tags, the blocks in the query's round range
+    /// are scanned on the client instead.
+    pub fn query_txs_page(
+        &self,
+        query: api::client::Query,
+        after: Option<QueryCursor>,
+        page_size: usize,
+    ) -> BoxFuture<QueryPage> {
+        let mut indexed_query = query.clone();
+        if let Some(after) = after {
+            indexed_query.round_min = indexed_query.round_min.max(after.round);
+        }
+        indexed_query.limit = query::INDEXER_QUERY_LIMIT;
+
+        let client = self.clone();
+        Box::new(
+            self.query_txs(indexed_query)
+                .then(move |result| -> BoxFuture<QueryPage> {
+                    match result {
+                        Ok(txs) => {
+                            let exhausted = (txs.len() as u64) < query::INDEXER_QUERY_LIMIT;
+                            Box::new(future::ok(query::paginate(
+                                txs, after, page_size, exhausted,
+                            )))
+                        }
+                        Err(error) => {
+                            if !format!("{}", error).contains(query::INDEXER_DISABLED_ERROR) {
+                                return Box::new(future::err(error));
+                            }
+                            client.scan_txs_page(query, after, page_size)
+                        }
+                    }
+                }),
+        )
+    }
+
+    /// Query transactions by tags and return a stream of all results.
+    ///
+    /// Results are fetched a page at a time as the stream is consumed, so
+    /// that queries with many results do not need to be buffered.
+    pub fn query_txs_stream(
+        &self,
+        query: api::client::Query,
+        page_size: usize,
+    ) -> PageStream<TransactionSnapshot, QueryCursor> {
+        let client = self.clone();
+        PageStream::new(None, move |after| {
+            client.query_txs_page(query.clone(), after, page_size)
+        })
+    }
+
+    /// Query transactions by scanning blocks instead of using the node's
+    /// tag index.
+    fn scan_txs_page(
+        &self,
+        query: api::client::Query,
+        after: Option<QueryCursor>,
+        page_size: usize,
+    ) -> BoxFuture<QueryPage> {
+        let start_round = match after {
+            Some(after) => query.round_min.max(after.round),
+            None => query.round_min,
+        };
+        let client = self.clone();
+
+        Box::new(
+            future::loop_fn(
+                (start_round, Vec::new()),
+                move |(round, mut txs): (u64, Vec<TransactionSnapshot>)| {
+                    let client = client.clone();
+                    let query = query.clone();
+
+                    client.get_block(round).and_then(
+                        move |block| -> BoxFuture<Loop<(Vec<_>, bool), (u64, Vec<_>)>> {
+                            let block = match block {
+                                Some(block) if query.matches_round(round) => block.block,
+                                // Reached the end of the range or the latest block.
+                                _ => return Box::new(future::ok(Loop::Break((txs, true)))),
+                            };
+                            if block.header.io_root == Hash::empty_hash() {
+                                return Box::new(future::ok(Loop::Continue((round + 1, txs))));
+                            }
+
+                            let storage_client = client.storage_client.clone();
+                            Box::new(client.get_txs(round, block.header.io_root).and_then(
+                                move |batch| {
+                                    let found =
+                                        query::filter_block(&storage_client, block, batch, &query)?;
+                                    txs.extend(found.into_iter().filter(|tx| {
+                                        after.map_or(true, |after| {
+                                            (round, tx.index) > (after.round, after.index)
+                                        })
+                                    }));
+
+                                    if txs.len() > page_size {
+                                        Ok(Loop::Break((txs, false)))
+                                    } else {
+                                        Ok(Loop::Continue((round + 1, txs)))
+                                    }
+                                },
+                            ))
+                        },
+                    )
+                },
+            )
+            .map(move |(txs, exhausted)| query::paginate(txs, after, page_size, exhausted)),
+        )
     }
 
     /// Wait for a block to be indexed by the indexer.
@@ -412,40 +1035,156 @@ impl TxnClient {
         };
 
         let result: BoxFuture<()> = match self.client.wait_block_indexed(&request, options) {
-            Ok(resp) => Box::new(
-                resp.map_err(|error| TxnClientError::CallFailed(format!("{}", error)).into()),
-            ),
-            Err(error) => Box::new(future::err(
-                TxnClientError::CallFailed(format!("{}", error)).into(),
-            )),
+            Ok(resp) => Box::new(UnaryResponse::new(resp).map_err(call_failed)),
+            Err(error) => Box::new(future::err(call_failed(error))),
         };
         drop(span);
         result
     }
 
-    fn prepare_options(&self, span_name: &'static str) -> (Span, grpcio::CallOption) {
-        // TODO: Use oasis_core_tracing to get the tracer.
-        let (tracer, _) = Tracer::new(AllSampler);
+    /// Read a block from all read nodes and check that enough of them agree.
+    fn read_block<F>(&self, span_name: &'static str, f: F) -> BoxFuture<Option<BlockSnapshot>>
+    where
+        F: Fn(
+            &api::client::RuntimeClient,
+            grpcio::CallOption,
+        ) -> grpcio::Result<ClientUnaryReceiver<Block>>,
+    {
+        let result = self.guarded(|| self.read_block_unguarded(span_name, f), |_| true);
+        self.observe_query(span_name, result)
+    }
 
-        let span = tracer
-            .span(span_name)
-            .tag(tag::StdTag::span_kind("client"))
-            .start();
+    fn read_block_unguarded<F>(
+        &self,
+        span_name: &'static str,
+        f: F,
+    ) -> BoxFuture<Option<BlockSnapshot>>
+    where
+        F: Fn(
+            &api::client::RuntimeClient,
+            grpcio::CallOption,
+        ) -> grpcio::Result<ClientUnaryReceiver<Block>>,
+    {
+        let (span, options) = self.prepare_options(span_name);
+        let responses: Vec<BoxFuture<Result<Option<Block>, String>>> = self
+            .read_clients
+            .iter()
+            .map(|client| -> BoxFuture<Result<Option<Block>, String>> {
+                match f(client, options.clone()) {
+                    Ok(resp) => Box::new(UnaryResponse::new(resp).then(|result| {
+                        Ok(match result {
+                            Err(RpcFailure(RpcStatus {
+                                status: RpcStatusCode::NotFound,
+                                ..
+                            })) => Ok(None),
+                            Err(error) => Err(format!("{}", error)),
+                            Ok(rsp) => Ok(Some(rsp)),
+                        })
+                    })),
+                    Err(error) => Box::new(future::ok(Err(format!("{}", error)))),
+                }
+            })
+            .collect();
+        drop(span);
+
+        let client = self.clone();
+        Box::new(future::join_all(responses).and_then(move |responses| {
+            let block = tally_blocks(responses, client.read_quorum).map_err(|error| {
+                if client.deadline_expired() {
+                    GrpcError::Timeout.into()
+                } else {
+                    error
+                }
+            })?;
+            Ok(block.map(|block| BlockSnapshot::new(client.storage_client, block)))
+        }))
+    }
 
-        let mut options = grpcio::CallOption::default().wait_for_ready(true);
-        if let Some(timeout) = self.timeout {
-            options = options.timeout(timeout);
+    fn block_stream(&self, start_round: Option<u64>) -> BlockStream {
+        let stream = BlockStream::new(
+            self.client.clone(),
+            self.storage_client.clone(),
+            self.runtime_id,
+            start_round,
+        );
+        match self.metrics {
+            Some(ref metrics) => stream.with_metrics(metrics.clone()),
+            None => stream,
         }
+    }
 
-        // TODO: Inject to options.
-        // options = inject_to_options(options, span.context());
+    fn observe_query<T>(&self, method: &str, query: BoxFuture<T>) -> BoxFuture<T>
+    where
+        T: Send + 'static,
+    {
+        match self.metrics {
+            Some(ref metrics) => metrics.observe_query(method, query),
+            None => query,
+        }
+    }
+
+    /// Make a call through the circuit breaker, if one is configured.
+    fn guarded<T, F>(&self, f: F, is_failure: fn(&Error) -> bool) -> BoxFuture<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> BoxFuture<T>,
+    {
+        match self.circuit_breaker {
+            Some(ref circuit_breaker) => circuit_breaker.call_with(f, is_failure),
+            None => f(),
+        }
+    }
+
+    fn deadline_expired(&self) -> bool {
+        self.deadline
+            .map_or(false, |deadline| Instant::now() >= deadline)
+    }
+
+    fn prepare_options(&self, span_name: &'static str) -> (Span, grpcio::CallOption) {
+        // Propagate the span to the node so that the node's and the
+        // runtime's spans of the call end up in the same trace.
+        let span = info_span!("client_call", method = span_name);
+        let mut options = call_option(self.deadline, self.timeout).wait_for_ready(true);
+        if let Some(span_context) = enter_span(&mut Context::background(), &span) {
+            options = with_span_context(options, &span_context);
+        }
 
         (span, options)
     }
 }
 
+/// Evaluate a runtime method against the given snapshot of the block at the
+/// given round, if it exists.
+fn query_snapshot<C, O>(
+    snapshot: Option<BlockSnapshot>,
+    round: u64,
+    ctx: Context,
+    dispatcher: &MethodDispatcher,
+    method: &str,
+    args: C,
+) -> Result<O>
+where
+    C: Serialize,
+    O: DeserializeOwned,
+{
+    snapshot
+        .ok_or(TxnClientError::BlockNotFound(round))?
+        .query(ctx, dispatcher, method, args)
+}
+
+/// Convert a gRPC error into a transaction client error.
+fn call_failed(error: grpcio::Error) -> Error {
+    match error {
+        RpcFailure(RpcStatus {
+            status: RpcStatusCode::Unavailable,
+            ..
+        }) => TxnClientError::NodeUnavailable(format!("{}", error)).into(),
+        error => grpc::convert_error(error, |error| TxnClientError::CallFailed(error).into()),
+    }
+}
+
 /// Parse runtime call output.
-pub fn parse_call_output<O>(output: Vec<u8>) -> Fallible<O>
+pub fn parse_call_output<O>(output: Vec<u8>) -> Result<O>
 where
     O: DeserializeOwned,
 {
@@ -455,3 +1194,234 @@ where
         TxnOutput::Error(error) => Err(TxnClientError::TxnFailed(error).into()),
     }
 }
+
+/// Select the block returned by at least `read_quorum` nodes.
+///
+/// Blocks are compared by their header hash and a block not being found
+/// counts as a response. Failed calls do not count towards the quorum.
+fn tally_blocks(
+    responses: Vec<Result<Option<Block>, String>>,
+    read_quorum: usize,
+) -> Result<Option<Block>> {
+    let mut tally: Vec<(Option<Block>, Option<Hash>, usize)> = Vec::new();
+    let mut last_error = String::new();
+    for response in responses {
+        let block = match response {
+            Ok(block) => block,
+            Err(error) => {
+                last_error = error;
+                continue;
+            }
+        };
+        let hash = block.as_ref().map(|block| block.header.encoded_hash());
+
+        let index = match tally.iter().position(|(_, h, _)| h == &hash) {
+            Some(index) => {
+                tally[index].2 += 1;
+                index
+            }
+            None => {
+                tally.push((block, hash, 1));
+                tally.len() - 1
+            }
+        };
+
+        if tally[index].2 >= read_quorum {
+            return Ok(tally.swap_remove(index).0);
+        }
+    }
+
+    Err(TxnClientError::ReadQuorumNotReached {
+        got: tally.iter().map(|(_, _, c)| *c).max().unwrap_or(0),
+        required: read_quorum,
+        last_error,
+    }
+    .into())
+}
+
+/// Check whether a failed submission may succeed if resubmitted.
+///
+/// Submissions rejected by the transaction scheduler because the node is not
+/// the leader or the epoch changed are already retried by the node's runtime
+/// client (see go/runtime/client), which keeps the call pending until the
+/// transaction can be scheduled, so such rejections never reach this client.
+/// Only submissions which did not reach the node are resubmitted, based on
+/// the gRPC status code rather than on the error message.
+fn is_retryable(error: &Error) -> bool {
+    match error.downcast_ref::<TxnClientError>() {
+        Some(TxnClientError::NodeUnavailable(_)) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use grpcio::{ChannelBuilder, EnvBuilder};
+
+    use oasis_core_runtime::transaction::{
+        dispatcher::{Method, MethodDescriptor},
+        Context as TxnContext,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_tally_blocks() {
+        let block = |round| {
+            let mut block = Block::default();
+            block.header.round = round;
+            block
+        };
+
+        // First success.
+        let responses = vec![Err("unavailable".to_owned()), Ok(Some(block(1)))];
+        assert_eq!(tally_blocks(responses, 1).unwrap(), Some(block(1)));
+
+        // Quorum.
+        let responses = vec![Ok(Some(block(1))), Ok(Some(block(2))), Ok(Some(block(2)))];
+        assert_eq!(tally_blocks(responses, 2).unwrap(), Some(block(2)));
+        let responses = vec![Ok(None), Err("unavailable".to_owned()), Ok(None)];
+        assert_eq!(tally_blocks(responses, 2).unwrap(), None);
+
+        // Disagreement.
+        let responses = vec![Ok(Some(block(1))), Ok(Some(block(2))), Ok(None)];
+        assert!(tally_blocks(responses, 2).is_err());
+        let responses = vec![Ok(Some(block(1))), Err("unavailable".to_owned())];
+        assert!(tally_blocks(responses, 2).is_err());
+    }
+
+    #[test]
+    fn test_query_snapshot() {
+        let mut dispatcher = MethodDispatcher::new();
+        dispatcher.add_method(Method::new(
+            MethodDescriptor {
+                name: "double".to_owned(),
+            },
+            |call: &u64, _ctx: &mut TxnContext| -> Result<u64> { Ok(call * 2) },
+        ));
+
+        // Missing blocks are reported as such.
+        match query_snapshot::<_, u64>(
+            None,
+            42,
+            Context::background(),
+            &dispatcher,
+            "double",
+            21u64,
+        ) {
+            Err(error) => match error.downcast_ref::<TxnClientError>() {
+                Some(TxnClientError::BlockNotFound(42)) => {}
+                _ => panic!("expected block not found, got: {}", error),
+            },
+            Ok(_) => panic!("query of a missing block should fail"),
+        }
+
+        // Methods are evaluated against the block's state. The state is empty,
+        // so the storage node is never contacted.
+        let env = Arc::new(EnvBuilder::new().cq_count(1).build());
+        let channel = ChannelBuilder::new(env).connect("localhost:1");
+        let mut block = Block::default();
+        block.header.round = 42;
+        block.header.state_root = Hash::empty_hash();
+        let snapshot = BlockSnapshot::new(StorageNodes::new(vec![channel]), block);
+        let result: u64 = query_snapshot(
+            Some(snapshot),
+            42,
+            Context::background(),
+            &dispatcher,
+            "double",
+            21u64,
+        )
+        .unwrap();
+        assert_eq!(result, 42);
+    }
+
+    #[test]
+    fn test_build() {
+        let env = Arc::new(EnvBuilder::new().cq_count(1).build());
+        let channel = || ChannelBuilder::new(env.clone()).connect("localhost:1");
+        let client = || TxnClient::new(channel(), RuntimeId::default(), None);
+        let expect_invalid = |result: Result<TxnClient>| {
+            let error = result
+                .err()
+                .expect("invalid configuration should be rejected");
+            match error.downcast_ref::<TxnClientError>() {
+                Some(TxnClientError::InvalidReadQuorum { .. }) => {}
+                _ => panic!("expected invalid read quorum, got: {}", error),
+            }
+        };
+
+        client()
+            .build()
+            .expect("default configuration should build");
+        client()
+            .with_read_nodes(vec![channel(), channel()])
+            .with_read_quorum(2)
+            .build()
+            .expect("valid configuration should build");
+
+        expect_invalid(client().with_read_nodes(vec![]).build());
+        expect_invalid(client().with_read_quorum(0).build());
+        expect_invalid(
+            client()
+                .with_read_nodes(vec![channel(), channel()])
+                .with_read_quorum(3)
+                .build(),
+        );
+
+        let error = client()
+            .with_max_in_flight(0)
+            .build()
+            .err()
+            .expect("invalid configuration should be rejected");
+        match error.downcast_ref::<TxnClientError>() {
+            Some(TxnClientError::InvalidMaxInFlight) => {}
+            _ => panic!("expected invalid max in flight, got: {}", error),
+        }
+    }
+
+    #[test]
+    fn test_query_at_without_methods() {
+        let env = Arc::new(EnvBuilder::new().cq_count(1).build());
+        let channel = ChannelBuilder::new(env).connect("localhost:1");
+        let client = TxnClient::new(channel, RuntimeId::default(), None);
+
+        // Queries fail without contacting the node if no methods are registered.
+        match client
+            .query_at::<_, u64>(Context::background(), 42, "double", 21u64)
+            .wait()
+        {
+            Err(error) => match error.downcast_ref::<TxnClientError>() {
+                Some(TxnClientError::NoQueryMethods) => {}
+                _ => panic!("expected no query methods, got: {}", error),
+            },
+            Ok(_) => panic!("query without registered methods should fail"),
+        }
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let status = |status, details: &str| {
+            RpcFailure(RpcStatus {
+                status,
+                details: Some(details.to_owned()),
+            })
+        };
+
+        let error = call_failed(status(RpcStatusCode::Unavailable, "connection reset"));
+        assert!(is_retryable(&error));
+
+        // Retries do not depend on the error message.
+        let error = call_failed(status(
+            RpcStatusCode::Unknown,
+            "txnscheduler: epoch number mismatch",
+        ));
+        assert!(!is_retryable(&error));
+        let error = call_failed(status(RpcStatusCode::Unknown, "node unavailable"));
+        assert!(!is_retryable(&error));
+        let error = call_failed(status(RpcStatusCode::DeadlineExceeded, ""));
+        assert!(!is_retryable(&error));
+        let error: Error = TxnClientError::WatcherClosed.into();
+        assert!(!is_retryable(&error));
+    }
+}
@@ -0,0 +1,217 @@
+//! Runtime event subscription.
+//!
+//! Events are the tags emitted by transactions. An event stream follows the
+//! runtime's blocks, backfilling any rounds before the latest one first, and
+//! emits the events of each round ordered by transaction index and tag key.
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::Error;
+use futures::{future, prelude::*, try_ready};
+use io_context::Context;
+
+use oasis_core_runtime::{
+    common::crypto::hash::Hash,
+    transaction::{tags::Tag, tree::Tree as IoTree, types::TxnBatch},
+};
+
+use super::{
+    block_stream::BlockStream, client::TxnClient, snapshot::BlockSnapshot, storage::StorageNodes,
+};
+use crate::BoxFuture;
+
+/// A runtime event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    /// Round of the block containing the transaction that emitted the event.
+    pub round: u64,
+    /// Index of the transaction that emitted the event within its block.
+    pub index: u32,
+    /// The emitted tag.
+    pub tag: Tag,
+}
+
+impl Event {
+    /// Position of the event in the stream of all events.
+    pub fn cursor(&self) -> EventCursor {
+        EventCursor {
+            round: self.round,
+            index: self.index,
+            key: self.tag.key.clone(),
+        }
+    }
+}
+
+/// Position of an event in the stream of all events.
+///
+/// Indexers can persist the cursor of the last processed event and resume
+/// the stream after it without missing or repeating any events.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EventCursor {
+    /// Round of the event.
+    pub round: u64,
+    /// Index of the transaction that emitted the event.
+    pub index: u32,
+    /// Key of the emitted tag.
+    pub key: Vec<u8>,
+}
+
+/// A stream of runtime events.
+pub struct EventStream {
+    client: TxnClient,
+    storage_client: StorageNodes,
+    blocks: BlockStream,
+    /// Events up to and including this cursor are skipped.
+    after: Option<EventCursor>,
+    /// Events of the current round that have not been emitted yet.
+    pending: VecDeque<Event>,
+    fetching: Option<BoxFuture<Vec<Event>>>,
+}
+
+impl EventStream {
+    pub(super) fn new(
+        client: TxnClient,
+        storage_client: StorageNodes,
+        blocks: BlockStream,
+        after: Option<EventCursor>,
+    ) -> Self {
+        Self {
+            client,
+            storage_client,
+            blocks,
+            after,
+            pending: VecDeque::new(),
+            fetching: None,
+        }
+    }
+
+    fn fetch_events(&self, snapshot: BlockSnapshot) -> BoxFuture<Vec<Event>> {
+        let block = snapshot.block;
+        let round = block.header.round;
+        if block.header.io_root == Hash::empty_hash() {
+            return Box::new(future::ok(vec![]));
+        }
+
+        let storage_client = self.storage_client.clone();
+        let after = self.after.clone();
+        Box::new(
+            self.client
+                .get_txs(round, block.header.io_root)
+                .and_then(move |batch| {
+                    let tree = IoTree::new(Box::new(storage_client), block.header.io_tree_root())?;
+                    let tags = tree.get_tags(Context::background())?;
+
+                    Ok(order_events(round, &batch, tags, after.as_ref()))
+                }),
+        )
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Event;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Async::Ready(Some(event)));
+            }
+
+            if let Some(mut fetching) = self.fetching.take() {
+                match fetching.poll()? {
+                    Async::NotReady => {
+                        self.fetching = Some(fetching);
+                        return Ok(Async::NotReady);
+                    }
+                    Async::Ready(events) => {
+                        self.pending.extend(events);
+                        continue;
+                    }
+                }
+            }
+
+            match try_ready!(self.blocks.poll()) {
+                Some(snapshot) => self.fetching = Some(self.fetch_events(snapshot)),
+                None => return Ok(Async::Ready(None)),
+            }
+        }
+    }
+}
+
+/// Order the tags emitted in a round by transaction index and key, dropping
+/// any events up to and including the given cursor.
+fn order_events(
+    round: u64,
+    batch: &TxnBatch,
+    tags: Vec<Tag>,
+    after: Option<&EventCursor>,
+) -> Vec<Event> {
+    let indices: HashMap<Hash, u32> = batch
+        .0
+        .iter()
+        .enumerate()
+        .map(|(index, input)| (Hash::digest_bytes(input), index as u32))
+        .collect();
+
+    let mut events: Vec<_> = tags
+        .into_iter()
+        .filter_map(|tag| {
+            indices.get(&tag.tx_hash).map(|index| Event {
+                round,
+                index: *index,
+                tag,
+            })
+        })
+        .filter(|event| after.map_or(true, |after| event.cursor() > *after))
+        .collect();
+    events.sort_by_key(Event::cursor);
+
+    events
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_order_events() {
+        let inputs = vec![b"tx0".to_vec(), b"tx1".to_vec()];
+        let tag = |key: &[u8], index: usize| Tag {
+            key: key.to_vec(),
+            value: vec![],
+            tx_hash: Hash::digest_bytes(&inputs[index]),
+        };
+        let batch = TxnBatch(inputs.clone());
+        let tags = || vec![tag(b"a", 1), tag(b"b", 0), tag(b"a", 0)];
+        let keys = |events: Vec<Event>| -> Vec<_> {
+            events
+                .into_iter()
+                .map(|event| (event.index, event.tag.key))
+                .collect()
+        };
+
+        let events = order_events(5, &batch, tags(), None);
+        assert!(events.iter().all(|event| event.round == 5));
+        assert_eq!(
+            keys(events),
+            vec![(0, b"a".to_vec()), (0, b"b".to_vec()), (1, b"a".to_vec())]
+        );
+
+        // Events up to the cursor are skipped when resuming.
+        let after = EventCursor {
+            round: 5,
+            index: 0,
+            key: b"b".to_vec(),
+        };
+        let events = order_events(5, &batch, tags(), Some(&after));
+        assert_eq!(keys(events), vec![(1, b"a".to_vec())]);
+
+        // Earlier rounds do not affect later ones.
+        let events = order_events(6, &batch, tags(), Some(&after));
+        assert_eq!(keys(events).len(), 3);
+
+        // Tags of unknown transactions are ignored.
+        let mut tags = tags();
+        tags.push(Tag::new(b"c".to_vec(), vec![]));
+        assert_eq!(order_events(5, &batch, tags, None).len(), 3);
+    }
+}
@@ -14,6 +14,17 @@
 ///
 /// In this example, the generated client type will be called `MyClient`. The API
 /// definitions will passed as the last argument as defined by the `api` token.
+///
+/// Optionally, a second type name can be given to also generate a query client
+/// which evaluates the same methods locally against the state at a given block,
+/// using the runtime's method dispatcher:
+/// ```rust,ignore
+/// with_api! {
+///     create_txn_api_client!(MyClient, MyQueryClient, api);
+/// }
+/// ```
+///
+/// Failed calls result in `TxnClientError::TxnFailed` with both clients.
 #[macro_export]
 macro_rules! create_txn_api_client {
     (
@@ -51,4 +62,107 @@ macro_rules! create_txn_api_client {
             )*
         }
     };
+
+    (
+        $name:ident,
+        $query_name:ident,
+
+        $(
+            pub fn $method_name: ident ( $request_type: ty ) -> $response_type: ty ;
+        )*
+    ) => {
+        $crate::create_txn_api_client!(
+            $name,
+            $(
+                pub fn $method_name ( $request_type ) -> $response_type ;
+            )*
+        );
+
+        pub struct $query_name {
+            dispatcher: $crate::__private::oasis_core_runtime::transaction::dispatcher::MethodDispatcher,
+        }
+
+        impl $query_name {
+            /// Create new query client instance.
+            ///
+            /// The dispatcher must have the runtime's methods registered.
+            pub fn new(
+                dispatcher: $crate::__private::oasis_core_runtime::transaction::dispatcher::MethodDispatcher,
+            ) -> Self {
+                Self {
+                    dispatcher,
+                }
+            }
+
+            // Generate methods.
+            $(
+                pub fn $method_name(
+                    &self,
+                    ctx: $crate::__private::io_context::Context,
+                    snapshot: &$crate::transaction::snapshot::BlockSnapshot,
+                    arguments: $request_type
+                ) -> $crate::__private::anyhow::Result<$response_type> {
+                    snapshot
+                        .query(ctx, &self.dispatcher, stringify!($method_name), arguments)
+                        .map_err(|error| {
+                            $crate::transaction::client::TxnClientError::TxnFailed(
+                                format!("{}", error),
+                            )
+                            .into()
+                        })
+                }
+            )*
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use grpcio::{ChannelBuilder, EnvBuilder};
+    use io_context::Context;
+
+    use oasis_core_runtime::{
+        common::{crypto::hash::Hash, roothash::Block},
+        register_runtime_txn_methods,
+        transaction::{dispatcher::MethodDispatcher, Context as TxnContext},
+    };
+
+    use self::api::TestQueryClient;
+    use crate::transaction::{snapshot::BlockSnapshot, storage::StorageNodes};
+
+    fn double(args: &u64, _ctx: &mut TxnContext) -> anyhow::Result<u64> {
+        Ok(args * 2)
+    }
+
+    // Only the query client is exercised, the transaction client needs a node.
+    #[allow(dead_code)]
+    mod api {
+        create_txn_api_client!(
+            TestClient,
+            TestQueryClient,
+            pub fn double(u64) -> u64;
+        );
+    }
+
+    #[test]
+    fn test_create_txn_api_client() {
+        let mut dispatcher = MethodDispatcher::new();
+        register_runtime_txn_methods!(
+            dispatcher,
+            pub fn double(u64) -> u64;
+        );
+        let client = TestQueryClient::new(dispatcher);
+
+        // The state is empty, so the storage node is never contacted.
+        let env = Arc::new(EnvBuilder::new().cq_count(1).build());
+        let channel = ChannelBuilder::new(env).connect("localhost:1");
+        let mut block = Block::default();
+        block.header.state_root = Hash::empty_hash();
+        let snapshot = BlockSnapshot::new(StorageNodes::new(vec![channel]), block);
+
+        let result = client.double(Context::background(), &snapshot, 21).unwrap();
+        assert_eq!(result, 42);
+    }
 }
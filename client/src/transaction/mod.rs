@@ -1,13 +1,23 @@
 //! Transaction client.
 
 pub mod api;
+pub mod block_stream;
 mod block_watcher;
 pub mod client;
+pub mod events;
 pub mod macros;
+pub mod query;
 pub mod snapshot;
+pub mod status;
+pub mod storage;
 
 // Re-exports.
 pub use self::{
     api::client::{Query, QueryCondition, ROUND_LATEST},
+    block_stream::BlockStream,
     client::TxnClient,
+    events::{Event, EventCursor, EventStream},
+    query::{QueryCursor, QueryPage},
+    status::{TxnStatus, TxnStatusStream},
+    storage::{StateDiff, StorageNodes},
 };
@@ -0,0 +1,214 @@
+//! Paginated transaction queries.
+use anyhow::{anyhow, Result};
+use io_context::Context;
+use oasis_core_runtime::{
+    common::{crypto::hash::Hash, roothash::Block},
+    transaction::{tree::Tree as IoTree, types::TxnBatch},
+};
+
+use crate::pagination::Page;
+
+use super::{
+    api::client::{Query, QueryCondition},
+    snapshot::TransactionSnapshot,
+    storage::StorageNodes,
+};
+
+/// Maximum number of results requested from the node's tag indexer.
+pub(super) const INDEXER_QUERY_LIMIT: u64 = 1000;
+
+/// Error message returned by nodes that have the tag indexer disabled.
+pub(super) const INDEXER_DISABLED_ERROR: &str = "tag indexer is disabled";
+
+/// Position of a transaction in the results of a query.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct QueryCursor {
+    /// Round of the last returned transaction.
+    pub round: u64,
+    /// Index of the last returned transaction within its round.
+    pub index: u32,
+}
+
+impl QueryCursor {
+    fn of(tx: &TransactionSnapshot) -> Self {
+        Self {
+            round: tx.block_snapshot.block.header.round,
+            index: tx.index,
+        }
+    }
+}
+
+/// A page of transaction query results, ordered by round and index.
+pub type QueryPage = Page<TransactionSnapshot, QueryCursor>;
+
+impl Query {
+    /// Add a condition requiring the given tag key to have the given value.
+    pub fn with_tag<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.conditions.push(QueryCondition {
+            key: key.as_ref().to_vec(),
+            values: vec![value.as_ref().to_vec().into()],
+        });
+        self
+    }
+
+    /// Restrict the query to the given (inclusive) range of rounds.
+    ///
+    /// A zero bound means that the range is unbounded on that side.
+    pub fn with_rounds(mut self, round_min: u64, round_max: u64) -> Self {
+        self.round_min = round_min;
+        self.round_max = round_max;
+        self
+    }
+
+    /// Check whether the given round is within the query's round range.
+    pub(super) fn matches_round(&self, round: u64) -> bool {
+        round >= self.round_min && (self.round_max == 0 || round <= self.round_max)
+    }
+}
+
+/// Order query results and select a page following the given cursor.
+///
+/// If `exhausted` is false, there may be further results beyond the ones
+/// given and a cursor is returned even when the page is not full.
+pub(super) fn paginate(
+    mut txs: Vec<TransactionSnapshot>,
+    after: Option<QueryCursor>,
+    page_size: usize,
+    exhausted: bool,
+) -> QueryPage {
+    txs.sort_by_key(QueryCursor::of);
+    if let Some(after) = after {
+        txs.retain(|tx| QueryCursor::of(tx) > after);
+    }
+
+    let more = txs.len() > page_size || !exhausted;
+    txs.truncate(page_size);
+    let next = if more {
+        txs.last().map(QueryCursor::of).or(after)
+    } else {
+        None
+    };
+
+    Page::new(txs, next)
+}
+
+/// Find the transactions in the given block that match all query
+/// conditions, by reading tags directly from the block's I/O tree.
+///
+/// This is used when the node does not index tags.
+pub(super) fn filter_block(
+    storage_client: &StorageNodes,
+    block: Block,
+    batch: TxnBatch,
+    query: &Query,
+) -> Result<Vec<TransactionSnapshot>> {
+    let ctx = Context::background().freeze();
+    let tree = IoTree::new(
+        Box::new(storage_client.clone()),
+        block.header.io_tree_root(),
+    )?;
+
+    let mut txs = vec![];
+    for (index, input) in batch.0.into_iter().enumerate() {
+        let tx_hash = Hash::digest_bytes(&input);
+
+        let mut matches = true;
+        for cond in &query.conditions {
+            if cond.values.is_empty() {
+                continue;
+            }
+
+            let value = tree.get_tag(Context::create_child(&ctx), &cond.key, tx_hash)?;
+            matches = match value {
+                Some(value) => cond.values.iter().any(|v| v[..] == value[..]),
+                None => false,
+            };
+            if !matches {
+                break;
+            }
+        }
+        if !matches {
+            continue;
+        }
+
+        let output = tree
+            .get_output(Context::create_child(&ctx), tx_hash)?
+            .ok_or_else(|| anyhow!("output is missing"))?;
+        txs.push(TransactionSnapshot::new(
+            storage_client.clone(),
+            block.clone(),
+            index as u32,
+            input,
+            output,
+        )?);
+    }
+
+    Ok(txs)
+}
+
+#[cfg(test)]
+mod test {
+    use oasis_core_runtime::{
+        common::cbor,
+        transaction::types::{TxnCall, TxnOutput},
+    };
+
+    use super::*;
+
+    fn make_tx(round: u64, index: u32) -> TransactionSnapshot {
+        let mut block = Block::default();
+        block.header.round = round;
+        let input = cbor::to_vec(&TxnCall {
+            method: "test".to_owned(),
+            args: cbor::Value::Null,
+        });
+        let output = cbor::to_vec(&TxnOutput::Success(cbor::Value::Null));
+
+        TransactionSnapshot::new(StorageNodes::new(vec![]), block, index, input, output).unwrap()
+    }
+
+    fn cursors(page: &QueryPage) -> Vec<(u64, u32)> {
+        page.items
+            .iter()
+            .map(|tx| {
+                let cursor = QueryCursor::of(tx);
+                (cursor.round, cursor.index)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_paginate() {
+        let txs = || vec![make_tx(2, 0), make_tx(1, 1), make_tx(3, 0), make_tx(1, 0)];
+
+        let page = paginate(txs(), None, 3, true);
+        assert_eq!(cursors(&page), vec![(1, 0), (1, 1), (2, 0)]);
+        let next = page.next.expect("more results should be available");
+        assert_eq!(next, QueryCursor { round: 2, index: 0 });
+
+        let page = paginate(txs(), Some(next), 3, true);
+        assert_eq!(cursors(&page), vec![(3, 0)]);
+        assert_eq!(page.next, None);
+
+        // Results that may be incomplete always produce a cursor.
+        let page = paginate(txs(), Some(next), 3, false);
+        assert_eq!(cursors(&page), vec![(3, 0)]);
+        assert_eq!(page.next, Some(QueryCursor { round: 3, index: 0 }));
+    }
+
+    #[test]
+    fn test_query_rounds() {
+        let query = Query::default().with_rounds(5, 10);
+        assert!(!query.matches_round(4));
+        assert!(query.matches_round(5));
+        assert!(query.matches_round(10));
+        assert!(!query.matches_round(11));
+
+        let query = Query::default().with_rounds(5, 0);
+        assert!(query.matches_round(u64::max_value()));
+    }
+}
@@ -1,8 +1,11 @@
 //! A block snapshot.
-use std::any::Any;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
-use failure::{Fallible, ResultExt};
-use grpcio::CallOption;
+use anyhow::{anyhow, Context as _, Result};
+use futures::future;
 use io_context::Context;
 use oasis_core_runtime::{
     common::{
@@ -11,13 +14,22 @@ use oasis_core_runtime::{
         roothash::{Block, Namespace},
     },
     storage::{
-        mkvs::{sync::*, Prefix, Root, Tree, WriteLog},
-        MKVS,
+        mkvs::{sync::*, OverlayTree, Prefix, Tree, WriteLog},
+        KeyValue, StorageContext, MKVS,
+    },
+    transaction::{
+        dispatcher::MethodDispatcher,
+        types::{TxnCall, TxnOutput},
+        Context as TxnContext,
     },
-    transaction::types::{TxnCall, TxnOutput},
 };
+use serde::{de::DeserializeOwned, Serialize};
 
-use super::{api, client::TxnClientError};
+use super::storage::StorageNodes;
+use crate::{
+    pagination::{Page, PageStream},
+    BoxFuture,
+};
 
 /// A transaction snapshot.
 #[derive(Clone)]
@@ -34,12 +46,12 @@ pub struct TransactionSnapshot {
 
 impl TransactionSnapshot {
     pub(super) fn new(
-        storage_client: api::storage::StorageClient,
+        storage_client: StorageNodes,
         block: Block,
         index: u32,
         input: Vec<u8>,
         output: Vec<u8>,
-    ) -> Fallible<Self> {
+    ) -> Result<Self> {
         Ok(Self {
             block_snapshot: BlockSnapshot::new(storage_client, block),
             index,
@@ -56,7 +68,7 @@ pub struct BlockSnapshot {
     /// Block header hash.
     pub block_hash: Hash,
 
-    read_syncer: RemoteReadSync,
+    read_syncer: StorageNodes,
     mkvs: Tree,
 }
 
@@ -66,11 +78,7 @@ impl Clone for BlockSnapshot {
         let block_hash = self.block_hash;
         let read_syncer = self.read_syncer.clone();
         let mkvs = Tree::make()
-            .with_root(Root {
-                namespace: self.block.header.namespace,
-                version: self.block.header.round,
-                hash: self.block.header.state_root,
-            })
+            .with_root(self.block.header.state_tree_root())
             .new(Box::new(read_syncer.clone()));
 
         Self {
@@ -83,14 +91,9 @@ impl Clone for BlockSnapshot {
 }
 
 impl BlockSnapshot {
-    pub(super) fn new(storage_client: api::storage::StorageClient, block: Block) -> Self {
-        let read_syncer = RemoteReadSync(storage_client);
+    pub(super) fn new(read_syncer: StorageNodes, block: Block) -> Self {
         let mkvs = Tree::make()
-            .with_root(Root {
-                namespace: block.header.namespace,
-                version: block.header.round,
-                hash: block.header.state_root,
-            })
+            .with_root(block.header.state_tree_root())
             .new(Box::new(read_syncer.clone()));
 
         Self {
@@ -100,22 +103,213 @@ impl BlockSnapshot {
             mkvs,
         }
     }
+
+    /// Fetch the value of the given key together with a proof of its
+    /// inclusion (or non-inclusion) under the block's state root.
+    ///
+    /// The proof is verified before being returned and can be passed on to
+    /// third parties which can verify it using `ProofVerifier::verify_proof_for_key`.
+    pub fn get_with_proof(&self, ctx: Context, key: &[u8]) -> Result<(Option<Vec<u8>>, Proof)> {
+        let ctx = ctx.freeze();
+        let root = self.block.header.state_tree_root();
+
+        let mut read_syncer = self.read_syncer.clone();
+        let rsp = read_syncer.sync_get(
+            Context::create_child(&ctx),
+            GetRequest {
+                tree: TreeID {
+                    root,
+                    position: root.hash,
+                },
+                key: key.to_vec(),
+                include_siblings: false,
+                // Third parties may not support compressed proofs.
+                compressed: false,
+            },
+        )?;
+        let value = ProofVerifier.verify_proof_for_key(
+            Context::create_child(&ctx),
+            root.hash,
+            key,
+            &rsp.proof,
+        )?;
+
+        Ok((value, rsp.proof))
+    }
+
+    /// Fetch the first `1 + prefetch` items starting at the given key together
+    /// with a proof that these are exactly the items following the key under
+    /// the block's state root.
+    ///
+    /// The proof is verified before being returned and can be passed on to
+    /// third parties which can verify it using `ProofVerifier::verify_proof_for_iterate`.
+    pub fn iterate_with_proof(
+        &self,
+        ctx: Context,
+        key: &[u8],
+        prefetch: u16,
+    ) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, Proof)> {
+        let ctx = ctx.freeze();
+        let root = self.block.header.state_tree_root();
+
+        let mut read_syncer = self.read_syncer.clone();
+        let rsp = read_syncer.sync_iterate(
+            Context::create_child(&ctx),
+            IterateRequest {
+                tree: TreeID {
+                    root,
+                    position: root.hash,
+                },
+                key: key.to_vec(),
+                prefetch,
+                // Third parties may not support compressed proofs.
+                compressed: false,
+            },
+        )?;
+        let items = ProofVerifier.verify_proof_for_iterate(
+            Context::create_child(&ctx),
+            root.hash,
+            key,
+            prefetch,
+            &rsp.proof,
+        )?;
+
+        Ok((items, rsp.proof))
+    }
+
+    /// Fetch a page of at most `limit` items whose keys start with the
+    /// given prefix, ordered by key.
+    ///
+    /// To fetch the next page, call this method again with the continuation
+    /// key returned in the previous page.
+    pub fn get_prefix_page(
+        &self,
+        ctx: Context,
+        prefix: &[u8],
+        after: Option<Vec<u8>>,
+        limit: usize,
+    ) -> Result<Page<(Vec<u8>, Vec<u8>), Vec<u8>>> {
+        let mut it = self.mkvs.iter(ctx);
+        it.set_prefetch(limit);
+        match after {
+            // Appending a zero byte gives the smallest key following the
+            // last returned one.
+            Some(mut key) => {
+                key.push(0);
+                it.seek(&key);
+            }
+            None => it.seek(prefix),
+        }
+
+        let mut items: Vec<_> = it
+            .by_ref()
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .take(limit + 1)
+            .collect();
+        if let Some(error) = it.error() {
+            return Err(anyhow!("{}", error));
+        }
+
+        let next = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(|(key, _)| key.clone())
+        } else {
+            None
+        };
+
+        Ok(Page::new(items, next))
+    }
+
+    /// Return a stream of all items whose keys start with the given prefix,
+    /// ordered by key.
+    ///
+    /// Items are fetched `page_size` at a time as the stream is consumed.
+    pub fn prefix_stream(
+        &self,
+        prefix: Vec<u8>,
+        page_size: usize,
+    ) -> PageStream<(Vec<u8>, Vec<u8>), Vec<u8>> {
+        let snapshot = self.clone();
+        PageStream::new(None, move |after| -> BoxFuture<_> {
+            let snapshot = snapshot.clone();
+            let prefix = prefix.clone();
+            Box::new(future::lazy(move || {
+                snapshot.get_prefix_page(Context::background(), &prefix, after, page_size)
+            }))
+        })
+    }
+
+    /// Evaluate a runtime method against the state at this block.
+    ///
+    /// The method is executed locally using the given dispatcher, which
+    /// must have the runtime's methods registered. All state is fetched
+    /// through the read syncer and verified against the block's state root.
+    /// Any state modifications made by the method are discarded.
+    pub fn query<C, O>(
+        &self,
+        ctx: Context,
+        dispatcher: &MethodDispatcher,
+        method: &str,
+        args: C,
+    ) -> Result<O>
+    where
+        C: Serialize,
+        O: DeserializeOwned,
+    {
+        let call = TxnCall {
+            method: method.to_owned(),
+            args: cbor::to_value(args),
+        };
+        let header = self.block.header.clone();
+        let mut overlay = OverlayTree::new(self.clone());
+        let untrusted_local = Arc::new(QueryLocalStorage::default());
+
+        let result = StorageContext::enter(&mut overlay, untrusted_local, || {
+            let txn_ctx = TxnContext::new(ctx.freeze(), &header, false);
+            dispatcher.dispatch_call(call, txn_ctx)
+        })?;
+
+        Ok(cbor::from_value(result)?)
+    }
+}
+
+/// Untrusted local storage used while evaluating queries.
+///
+/// Anything stored is discarded together with the query.
+#[derive(Default)]
+struct QueryLocalStorage(Mutex<HashMap<Vec<u8>, Vec<u8>>>);
+
+impl KeyValue for QueryLocalStorage {
+    fn get(&self, key: Vec<u8>) -> Result<Vec<u8>> {
+        Ok(self
+            .0
+            .lock()
+            .unwrap()
+            .get(&key)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn insert(&self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
+        self.0.lock().unwrap().insert(key, value);
+        Ok(())
+    }
 }
 
 impl MKVS for BlockSnapshot {
-    fn get(&self, ctx: Context, key: &[u8]) -> Option<Vec<u8>> {
+    fn get(&self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
         MKVS::get(&self.mkvs, ctx, key)
     }
 
-    fn insert(&mut self, _ctx: Context, _key: &[u8], _value: &[u8]) -> Option<Vec<u8>> {
+    fn insert(&mut self, _ctx: Context, _key: &[u8], _value: &[u8]) -> Result<Option<Vec<u8>>> {
         unimplemented!("block snapshot is read-only");
     }
 
-    fn remove(&mut self, _ctx: Context, _key: &[u8]) -> Option<Vec<u8>> {
+    fn remove(&mut self, _ctx: Context, _key: &[u8]) -> Result<Option<Vec<u8>>> {
         unimplemented!("block snapshot is read-only");
     }
 
-    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16) {
+    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16) -> Result<()> {
         MKVS::prefetch_prefixes(&self.mkvs, ctx, prefixes, limit)
     }
 
@@ -124,7 +318,7 @@ impl MKVS for BlockSnapshot {
         _ctx: Context,
         _namespace: Namespace,
         _round: u64,
-    ) -> Fallible<(WriteLog, Hash)> {
+    ) -> Result<(WriteLog, Hash)> {
         unimplemented!("block snapshot is read-only");
     }
 
@@ -132,37 +326,3 @@ impl MKVS for BlockSnapshot {
         unimplemented!("block snapshot is read-only");
     }
 }
-
-#[derive(Clone)]
-struct RemoteReadSync(api::storage::StorageClient);
-
-impl ReadSync for RemoteReadSync {
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
-
-    fn sync_get(&mut self, _ctx: Context, request: GetRequest) -> Fallible<ProofResponse> {
-        Ok(self
-            .0
-            .sync_get(&request, CallOption::default().wait_for_ready(true))
-            .map_err(|error| TxnClientError::CallFailed(format!("{}", error)))?)
-    }
-
-    fn sync_get_prefixes(
-        &mut self,
-        _ctx: Context,
-        request: GetPrefixesRequest,
-    ) -> Fallible<ProofResponse> {
-        Ok(self
-            .0
-            .sync_get_prefixes(&request, CallOption::default().wait_for_ready(true))
-            .map_err(|error| TxnClientError::CallFailed(format!("{}", error)))?)
-    }
-
-    fn sync_iterate(&mut self, _ctx: Context, request: IterateRequest) -> Fallible<ProofResponse> {
-        Ok(self
-            .0
-            .sync_iterate(&request, CallOption::default().wait_for_ready(true))
-            .map_err(|error| TxnClientError::CallFailed(format!("{}", error)))?)
-    }
-}
@@ -0,0 +1,109 @@
+//! Transaction submission status.
+use anyhow::Error;
+use futures::{prelude::*, sync::mpsc};
+
+use oasis_core_runtime::common::cbor;
+
+/// Status of a submitted transaction.
+///
+/// A transaction goes through `Queued` (and possibly `Resubmitting`) until it
+/// has been executed, after which the block including it is located and the
+/// transaction becomes `Finalized`. Any failure along the way, including the
+/// transaction itself failing, ends with `Failed`.
+///
+/// The node does not report when a queued transaction is scheduled, so
+/// scheduling and execution are observed as a single step.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TxnStatus {
+    /// The transaction has been submitted to the node and is waiting to be
+    /// scheduled and executed.
+    Queued {
+        /// Submission attempt, starting at zero.
+        attempt: usize,
+    },
+    /// The submission did not reach the node and the transaction will be
+    /// resubmitted once the next block has been observed.
+    Resubmitting {
+        /// Submission attempt that was rejected.
+        attempt: usize,
+        /// Reason for the rejection.
+        reason: String,
+    },
+    /// The transaction has been executed successfully.
+    Executed {
+        /// Output of the transaction.
+        output: cbor::Value,
+    },
+    /// The transaction has been found in the block of the given round.
+    Finalized {
+        /// Round of the block including the transaction.
+        round: u64,
+    },
+    /// The transaction failed.
+    Failed {
+        /// Reason for the failure.
+        reason: String,
+    },
+}
+
+impl TxnStatus {
+    /// Check whether no further status updates will follow.
+    pub fn is_final(&self) -> bool {
+        match self {
+            TxnStatus::Finalized { .. } | TxnStatus::Failed { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// A stream of status updates of a submitted transaction.
+///
+/// The stream ends after a final status.
+pub type TxnStatusStream = Box<dyn Stream<Item = TxnStatus, Error = Error> + Send>;
+
+/// Sender of status updates.
+///
+/// Updates are dropped if nobody is listening.
+#[derive(Clone, Default)]
+pub(super) struct StatusSender(Option<mpsc::UnboundedSender<TxnStatus>>);
+
+impl StatusSender {
+    pub(super) fn new(sender: mpsc::UnboundedSender<TxnStatus>) -> Self {
+        StatusSender(Some(sender))
+    }
+
+    pub(super) fn send(&self, status: TxnStatus) {
+        if let Some(ref sender) = self.0 {
+            // The receiver may have been dropped.
+            let _ = sender.unbounded_send(status);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_status_sender() {
+        let (sender, receiver) = mpsc::unbounded();
+        let status = StatusSender::new(sender);
+        status.send(TxnStatus::Queued { attempt: 0 });
+        status.send(TxnStatus::Finalized { round: 1 });
+        drop(status);
+
+        let updates: Vec<_> = receiver.collect().wait().unwrap();
+        assert_eq!(
+            updates,
+            vec![
+                TxnStatus::Queued { attempt: 0 },
+                TxnStatus::Finalized { round: 1 }
+            ]
+        );
+        assert!(!updates[0].is_final());
+        assert!(updates[1].is_final());
+
+        // Updates without a listener are dropped.
+        StatusSender::default().send(TxnStatus::Queued { attempt: 0 });
+    }
+}
@@ -0,0 +1,571 @@
+//! Storage access with failover across multiple storage nodes.
+use std::{
+    any::Any,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Error, Result};
+use futures::{future, prelude::*};
+use grpcio::{CallOption, Channel};
+use io_context::Context;
+use oasis_core_runtime::{
+    common::crypto::hash::Hash,
+    storage::mkvs::{sync::*, Root, Tree, WriteLog},
+};
+
+use super::api::storage::{GetDiffRequest, StorageClient};
+use crate::{
+    context,
+    grpc::{call_option, GrpcError},
+    metrics::Metrics,
+    BoxFuture,
+};
+
+/// Default number of retries of a request against a single storage node.
+pub const DEFAULT_RETRIES: usize = 2;
+/// Default delay before the first retry.
+pub const DEFAULT_BACKOFF_INITIAL: Duration = Duration::from_millis(100);
+/// Default upper bound on the delay between retries.
+pub const DEFAULT_BACKOFF_MAX: Duration = Duration::from_secs(2);
+
+/// Storage client error.
+#[derive(Debug, thiserror::Error)]
+pub enum StorageClientError {
+    #[error("no storage nodes configured")]
+    NoNodes,
+    #[error(
+        "read quorum must be between 1 and the number of nodes (got: {read_quorum} nodes: {nodes})"
+    )]
+    InvalidReadQuorum { read_quorum: usize, nodes: usize },
+    #[error("read quorum not reached: {got} of {required} matching responses (last error: {last_error})")]
+    QuorumNotReached {
+        got: usize,
+        required: usize,
+        last_error: String,
+    },
+    #[error("write log stream ended prematurely")]
+    IncompleteDiff,
+    #[error("state root mismatch (expected: {expected} got: {got})")]
+    RootMismatch { expected: Hash, got: Hash },
+}
+
+/// Retry and quorum policy for storage requests.
+#[derive(Clone, Debug)]
+struct Policy {
+    retries: usize,
+    backoff_initial: Duration,
+    backoff_max: Duration,
+    read_quorum: usize,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            retries: DEFAULT_RETRIES,
+            backoff_initial: DEFAULT_BACKOFF_INITIAL,
+            backoff_max: DEFAULT_BACKOFF_MAX,
+            read_quorum: 1,
+        }
+    }
+}
+
+impl Policy {
+    /// Delay before the given retry (starting at 1).
+    fn backoff(&self, retry: usize) -> Duration {
+        let factor = 1u32
+            .checked_shl((retry - 1) as u32)
+            .unwrap_or(u32::max_value());
+        self.backoff_initial
+            .checked_mul(factor)
+            .map(|delay| delay.min(self.backoff_max))
+            .unwrap_or(self.backoff_max)
+    }
+
+    /// Perform a read against the given nodes, starting at node `start`.
+    ///
+    /// Each node is retried with exponential backoff before moving on to the
+    /// next one. The read succeeds once `read_quorum` nodes have returned the
+    /// same response.
+    ///
+    /// No further attempts are made once the deadline has expired.
+    fn read<N, T, F>(
+        &self,
+        nodes: &[N],
+        start: usize,
+        deadline: Option<Instant>,
+        mut f: F,
+    ) -> Result<T>
+    where
+        T: PartialEq,
+        F: FnMut(&N) -> grpcio::Result<T>,
+    {
+        if nodes.is_empty() {
+            return Err(StorageClientError::NoNodes.into());
+        }
+
+        let mut responses: Vec<(T, usize)> = Vec::new();
+        let mut last_error = String::new();
+        for i in 0..nodes.len() {
+            let node = &nodes[(start + i) % nodes.len()];
+
+            let mut retry = 0;
+            let response = loop {
+                if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                    return Err(GrpcError::Timeout.into());
+                }
+
+                match f(node) {
+                    Ok(response) => break Some(response),
+                    Err(error) => {
+                        last_error = format!("{}", error);
+                        if retry >= self.retries {
+                            break None;
+                        }
+                        retry += 1;
+                        thread::sleep(self.backoff(retry));
+                    }
+                }
+            };
+
+            if let Some(response) = response {
+                let index = match responses.iter().position(|(r, _)| r == &response) {
+                    Some(index) => {
+                        responses[index].1 += 1;
+                        index
+                    }
+                    None => {
+                        responses.push((response, 1));
+                        responses.len() - 1
+                    }
+                };
+
+                if responses[index].1 >= self.read_quorum {
+                    return Ok(responses.swap_remove(index).0);
+                }
+            }
+        }
+
+        Err(StorageClientError::QuorumNotReached {
+            got: responses.iter().map(|(_, c)| *c).max().unwrap_or(0),
+            required: self.read_quorum,
+            last_error,
+        }
+        .into())
+    }
+}
+
+/// A storage client which distributes requests over a set of storage nodes
+/// (e.g., the members of the runtime's storage committee).
+///
+/// Requests that fail are retried with exponential backoff and then fail
+/// over to the next node. Reads can optionally require that multiple nodes
+/// return the same response.
+#[derive(Clone)]
+pub struct StorageNodes {
+    clients: Arc<Vec<StorageClient>>,
+    policy: Policy,
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    next: Arc<AtomicUsize>,
+    metrics: Option<Metrics>,
+}
+
+impl StorageNodes {
+    /// Create a new storage client over the given storage node channels.
+    pub fn new(channels: Vec<Channel>) -> Self {
+        Self {
+            clients: Arc::new(channels.into_iter().map(StorageClient::new).collect()),
+            policy: Policy::default(),
+            timeout: None,
+            deadline: None,
+            next: Arc::new(AtomicUsize::new(0)),
+            metrics: None,
+        }
+    }
+
+    /// Set the number of retries against a single node before failing over.
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.policy.retries = retries;
+        self
+    }
+
+    /// Set the initial and maximum delay between retries.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.policy.backoff_initial = initial;
+        self.policy.backoff_max = max;
+        self
+    }
+
+    /// Set the number of nodes which must return the same response for a
+    /// read to succeed.
+    ///
+    /// The quorum is validated by `build`.
+    pub fn with_read_quorum(mut self, read_quorum: usize) -> Self {
+        self.policy.read_quorum = read_quorum;
+        self
+    }
+
+    /// Set the timeout of each individual request.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Count storage sync requests in the given metrics.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Validate the configuration.
+    ///
+    /// Returns an error if the read quorum is zero or larger than the number
+    /// of storage nodes.
+    pub fn build(self) -> Result<Self> {
+        if self.policy.read_quorum == 0 || self.policy.read_quorum > self.clients.len() {
+            return Err(StorageClientError::InvalidReadQuorum {
+                read_quorum: self.policy.read_quorum,
+                nodes: self.clients.len(),
+            }
+            .into());
+        }
+        Ok(self)
+    }
+
+    /// Apply the deadline of the given context to all requests made through
+    /// the returned client.
+    ///
+    /// Reads through `ReadSync` additionally honor the deadline of the context
+    /// they are made with.
+    pub fn with_context(&self, ctx: &Context) -> Self {
+        let mut nodes = self.clone();
+        nodes.deadline = context::get_deadline(ctx);
+        nodes
+    }
+
+    /// Number of configured storage nodes.
+    pub fn len(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Return true if there are no configured storage nodes.
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    /// Fetch the write log which transforms the state at `start_root` into
+    /// the state at `end_root`.
+    ///
+    /// In case the request fails, it is retried on the other nodes.
+    pub fn get_diff(&self, start_root: Root, end_root: Root) -> BoxFuture<WriteLog> {
+        let request = GetDiffRequest {
+            start_root,
+            end_root,
+            options: Default::default(),
+        };
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        self.get_diff_from(start, self.clients.len(), request)
+    }
+
+    fn get_diff_from(
+        &self,
+        node: usize,
+        remaining: usize,
+        request: GetDiffRequest,
+    ) -> BoxFuture<WriteLog> {
+        if remaining == 0 {
+            return Box::new(future::err(StorageClientError::NoNodes.into()));
+        }
+
+        let client = &self.clients[node % self.clients.len()];
+        let result: BoxFuture<WriteLog> =
+            match client.get_diff(&request, self.options(self.deadline)) {
+                Ok(stream) => Box::new(
+                    stream
+                        .map_err(|err| -> Error { err.into() })
+                        .fold((WriteLog::new(), false), |(mut log, _), chunk| {
+                            log.extend(chunk.writelog);
+                            Ok::<_, Error>((log, chunk.is_final))
+                        })
+                        .and_then(|(log, is_final)| {
+                            if !is_final {
+                                return Err(StorageClientError::IncompleteDiff.into());
+                            }
+                            Ok(log)
+                        }),
+                ),
+                Err(error) => Box::new(future::err(error.into())),
+            };
+        if remaining == 1 {
+            return result;
+        }
+
+        let nodes = self.clone();
+        Box::new(result.or_else(move |_| nodes.get_diff_from(node + 1, remaining - 1, request)))
+    }
+
+    fn options(&self, deadline: Option<Instant>) -> CallOption {
+        // With a single node there is nothing to fail over to, so wait for the
+        // connection to become ready instead of failing fast.
+        call_option(deadline, self.timeout).wait_for_ready(self.clients.len() == 1)
+    }
+
+    fn observe_sync<T>(&self, method: &str, result: Result<T>) -> Result<T> {
+        if let Some(ref metrics) = self.metrics {
+            metrics.observe_storage_sync(method, &result);
+        }
+        result
+    }
+
+    fn read<T, F>(&self, ctx: &Context, mut f: F) -> Result<T>
+    where
+        T: PartialEq,
+        F: FnMut(&StorageClient, CallOption) -> grpcio::Result<T>,
+    {
+        let deadline = match (self.deadline, context::get_deadline(ctx)) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        // Rotate the starting node to spread the load.
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        self.policy.read(&self.clients, start, deadline, |client| {
+            f(client, self.options(deadline))
+        })
+    }
+}
+
+impl ReadSync for StorageNodes {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn sync_get(&mut self, ctx: Context, request: GetRequest) -> Result<ProofResponse> {
+        let result = self.read(&ctx, |client, options| client.sync_get(&request, options));
+        self.observe_sync("sync_get", result)
+    }
+
+    fn sync_get_prefixes(
+        &mut self,
+        ctx: Context,
+        request: GetPrefixesRequest,
+    ) -> Result<ProofResponse> {
+        let result = self.read(&ctx, |client, options| {
+            client.sync_get_prefixes(&request, options)
+        });
+        self.observe_sync("sync_get_prefixes", result)
+    }
+
+    fn sync_iterate(&mut self, ctx: Context, request: IterateRequest) -> Result<ProofResponse> {
+        let result = self.read(&ctx, |client, options| {
+            client.sync_iterate(&request, options)
+        });
+        self.observe_sync("sync_iterate", result)
+    }
+}
+
+/// The state changes made in a single round.
+#[derive(Clone, Debug)]
+pub struct StateDiff {
+    /// State root at the end of the previous round.
+    pub start_root: Root,
+    /// State root at the end of the round.
+    pub end_root: Root,
+    /// Write log which transforms the start root into the end root.
+    pub write_log: WriteLog,
+}
+
+impl StateDiff {
+    /// Apply the write log to a local tree holding the state at the start
+    /// root, commit it and verify that the resulting root matches the end
+    /// root.
+    ///
+    /// In case verification fails, the tree is left at the unverified root
+    /// and should be discarded.
+    pub fn apply(&self, ctx: Context, tree: &mut Tree) -> Result<()> {
+        let ctx = ctx.freeze();
+        tree.apply_write_log(Context::create_child(&ctx), self.write_log.clone())?;
+        let (_, hash) = tree.commit(
+            Context::create_child(&ctx),
+            self.end_root.namespace,
+            self.end_root.version,
+        )?;
+        if hash != self.end_root.hash {
+            return Err(StorageClientError::RootMismatch {
+                expected: self.end_root.hash,
+                got: hash,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+
+    use grpcio::{ChannelBuilder, EnvBuilder, Error::RemoteStopped};
+
+    use super::*;
+
+    fn policy(retries: usize, read_quorum: usize) -> Policy {
+        Policy {
+            retries,
+            backoff_initial: Duration::from_millis(1),
+            backoff_max: Duration::from_millis(4),
+            read_quorum,
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = policy(0, 1);
+        assert_eq!(policy.backoff(1), Duration::from_millis(1));
+        assert_eq!(policy.backoff(2), Duration::from_millis(2));
+        assert_eq!(policy.backoff(3), Duration::from_millis(4));
+        assert_eq!(policy.backoff(4), Duration::from_millis(4));
+        assert_eq!(policy.backoff(100), Duration::from_millis(4));
+    }
+
+    #[test]
+    fn test_failover() {
+        // Node 0 always fails, node 1 fails once, node 2 succeeds.
+        let calls = RefCell::new(vec![0; 3]);
+        let nodes = [0usize, 1, 2];
+        let read = |node: &usize| {
+            let mut calls = calls.borrow_mut();
+            calls[*node] += 1;
+            match (*node, calls[*node]) {
+                (0, _) | (1, 1) => Err(RemoteStopped),
+                (node, _) => Ok(node),
+            }
+        };
+
+        // Without retries, the read fails over to node 2.
+        assert_eq!(policy(0, 1).read(&nodes, 0, None, read).unwrap(), 2);
+        assert_eq!(*calls.borrow(), vec![1, 1, 1]);
+
+        // With retries, node 1 succeeds after a retry.
+        *calls.borrow_mut() = vec![0; 3];
+        assert_eq!(policy(1, 1).read(&nodes, 0, None, read).unwrap(), 1);
+        assert_eq!(*calls.borrow(), vec![2, 2, 0]);
+
+        // No nodes.
+        assert!(policy(0, 1).read(&[] as &[usize], 0, None, read).is_err());
+
+        // No attempts are made after the deadline.
+        *calls.borrow_mut() = vec![0; 3];
+        let error = policy(1, 1)
+            .read(&nodes, 0, Some(Instant::now()), read)
+            .unwrap_err();
+        match error.downcast_ref::<GrpcError>() {
+            Some(GrpcError::Timeout) => {}
+            _ => panic!("expected timeout, got: {}", error),
+        }
+        assert_eq!(*calls.borrow(), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn test_read_quorum() {
+        let nodes = [1, 2, 1, 1];
+        let read = |node: &i32| -> grpcio::Result<i32> { Ok(*node) };
+
+        assert_eq!(policy(0, 2).read(&nodes, 0, None, read).unwrap(), 1);
+        assert_eq!(policy(0, 3).read(&nodes, 1, None, read).unwrap(), 1);
+        assert!(policy(0, 4).read(&nodes, 0, None, read).is_err());
+
+        // Failing nodes do not count towards the quorum.
+        let read = |node: &i32| match node {
+            2 => Err(RemoteStopped),
+            node => Ok(*node),
+        };
+        assert_eq!(policy(0, 3).read(&nodes, 0, None, read).unwrap(), 1);
+        assert!(policy(0, 2).read(&[1, 2], 0, None, read).is_err());
+    }
+
+    #[test]
+    fn test_build() {
+        let env = Arc::new(EnvBuilder::new().cq_count(1).build());
+        let channels: Vec<_> = (0..2)
+            .map(|_| ChannelBuilder::new(env.clone()).connect("localhost:1"))
+            .collect();
+
+        StorageNodes::new(channels.clone())
+            .with_read_quorum(2)
+            .build()
+            .expect("valid configuration should build");
+        for read_quorum in &[0, 3] {
+            let error = StorageNodes::new(channels.clone())
+                .with_read_quorum(*read_quorum)
+                .build()
+                .err()
+                .expect("invalid read quorum should be rejected");
+            match error.downcast_ref::<StorageClientError>() {
+                Some(StorageClientError::InvalidReadQuorum { .. }) => {}
+                _ => panic!("expected invalid read quorum, got: {}", error),
+            }
+        }
+    }
+
+    #[test]
+    fn test_state_diff_apply() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+        tree.insert(Context::background(), b"foo", b"bar")
+            .expect("insert");
+        let (_, start_hash) = tree
+            .commit(Context::background(), Default::default(), 1)
+            .expect("commit");
+        let mut mirror = Tree::make().new(Box::new(NoopReadSyncer {}));
+        mirror
+            .insert(Context::background(), b"foo", b"bar")
+            .expect("insert");
+        mirror
+            .commit(Context::background(), Default::default(), 1)
+            .expect("commit");
+
+        tree.insert(Context::background(), b"moo", b"boo")
+            .expect("insert");
+        tree.remove(Context::background(), b"foo").expect("remove");
+        let (write_log, end_hash) = tree
+            .commit(Context::background(), Default::default(), 2)
+            .expect("commit");
+
+        let diff = StateDiff {
+            start_root: Root {
+                version: 1,
+                hash: start_hash,
+                ..Default::default()
+            },
+            end_root: Root {
+                version: 2,
+                hash: end_hash,
+                ..Default::default()
+            },
+            write_log,
+        };
+        diff.apply(Context::background(), &mut mirror)
+            .expect("apply");
+        assert_eq!(
+            mirror.get(Context::background(), b"moo").expect("get"),
+            Some(b"boo".to_vec())
+        );
+        assert_eq!(
+            mirror.get(Context::background(), b"foo").expect("get"),
+            None
+        );
+
+        // Applying the diff to a tree at a different root should fail.
+        let mut other = Tree::make().new(Box::new(NoopReadSyncer {}));
+        other
+            .insert(Context::background(), b"zoo", b"bar")
+            .expect("insert");
+        assert!(diff.apply(Context::background(), &mut other).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+//! Base64 encoding using the standard alphabet with padding.
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const PADDING: u8 = b'=';
+
+/// Base64 decoding error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// An invalid byte was found at the given position.
+    InvalidByte(usize, u8),
+    /// The input has an invalid length.
+    InvalidLength(usize),
+    /// The last symbol at the given position has non-zero trailing bits.
+    InvalidLastSymbol(usize, u8),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::InvalidByte(pos, v) => {
+                write!(f, "invalid byte '{}' at position {}", v, pos)
+            }
+            DecodeError::InvalidLength(length) => write!(f, "invalid length {}", length),
+            DecodeError::InvalidLastSymbol(pos, v) => {
+                write!(f, "invalid last symbol '{}' at position {}", v, pos)
+            }
+        }
+    }
+}
+
+/// Encode the data as base64.
+pub fn encode<T: AsRef<[u8]>>(data: T) -> String {
+    let data = data.as_ref();
+    let mut encoded = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let block = chunk.iter().enumerate().fold(0u32, |block, (i, byte)| {
+            block | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(block >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push(PADDING as char);
+            }
+        }
+    }
+    encoded
+}
+
+/// Decode base64 data, with or without padding.
+pub fn decode<T: AsRef<[u8]>>(data: T) -> Result<Vec<u8>, DecodeError> {
+    let data = data.as_ref();
+    let unpadded = data
+        .iter()
+        .rposition(|byte| *byte != PADDING)
+        .map_or(0, |pos| pos + 1);
+    let padding = data.len() - unpadded;
+    if unpadded % 4 == 1 || padding > 2 || (padding > 0 && data.len() % 4 != 0) {
+        return Err(DecodeError::InvalidLength(data.len()));
+    }
+
+    let mut decoded = Vec::with_capacity(unpadded * 3 / 4);
+    for (index, chunk) in data[..unpadded].chunks(4).enumerate() {
+        let mut block = 0u32;
+        for (i, byte) in chunk.iter().enumerate() {
+            let value = ALPHABET
+                .iter()
+                .position(|symbol| symbol == byte)
+                .ok_or(DecodeError::InvalidByte(index * 4 + i, *byte))?;
+            block |= (value as u32) << (18 - 6 * i);
+        }
+
+        let length = chunk.len() * 3 / 4;
+        // Bits of the last symbol which do not make up a full byte must be zero.
+        if block << (8 * length) & 0x00ff_ffff != 0 {
+            let pos = index * 4 + chunk.len() - 1;
+            return Err(DecodeError::InvalidLastSymbol(pos, data[pos]));
+        }
+        decoded.extend_from_slice(&block.to_be_bytes()[1..1 + length]);
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        // RFC 4648 test vectors.
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (data, encoded) in vectors.iter() {
+            assert_eq!(encode(data), *encoded);
+            assert_eq!(decode(encoded).unwrap(), data.as_bytes());
+            assert_eq!(
+                decode(encoded.trim_end_matches('=')).unwrap(),
+                data.as_bytes()
+            );
+        }
+
+        let data: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode(encode(&data)).unwrap(), data);
+
+        assert_eq!(decode("Zg=a"), Err(DecodeError::InvalidByte(2, b'=')));
+        assert_eq!(decode("Zm9v!A"), Err(DecodeError::InvalidByte(4, b'!')));
+        assert_eq!(decode("Zm9vY"), Err(DecodeError::InvalidLength(5)));
+        assert_eq!(decode("Zg="), Err(DecodeError::InvalidLength(3)));
+        assert_eq!(decode("Zm9==="), Err(DecodeError::InvalidLength(6)));
+        assert_eq!(decode("Zh=="), Err(DecodeError::InvalidLastSymbol(1, b'h')));
+        assert_eq!(decode("Zm9="), Err(DecodeError::InvalidLastSymbol(2, b'9')));
+    }
+}
@@ -14,6 +14,48 @@ macro_rules! impl_bytes {
         #[derive(Clone)]
         pub struct $name(pub [u8; $size]);
 
+        impl Copy for $name {}
+
+        impl Into<[u8; $size]> for $name {
+            fn into(self) -> [u8; $size] {
+                self.0
+            }
+        }
+
+        // Formatting.
+
+        impl ::core::fmt::LowerHex for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                for i in &self.0[..] {
+                    write!(f, "{:02x}", i)?;
+                }
+                Ok(())
+            }
+        }
+
+        impl ::core::fmt::Debug for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                ::core::fmt::LowerHex::fmt(self, f)
+            }
+        }
+
+        impl ::core::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                for i in &self.0[0..2] {
+                    write!(f, "{:02x}", i)?;
+                }
+                write!(f, "…")?;
+                for i in &self.0[$size - 2..$size] {
+                    write!(f, "{:02x}", i)?;
+                }
+                Ok(())
+            }
+        }
+
+        $crate::impl_bytes!(@common $name, $size);
+    };
+
+    (@common $name:ident, $size:expr) => {
         impl $name {
             /// Size of this object in bytes.
             pub const fn len() -> usize {
@@ -63,14 +105,6 @@ macro_rules! impl_bytes {
             }
         }
 
-        impl Copy for $name {}
-
-        impl Into<[u8; $size]> for $name {
-            fn into(self) -> [u8; $size] {
-                self.0
-            }
-        }
-
         impl From<&[u8]> for $name {
             fn from(b: &[u8]) -> $name {
                 let mut data = [0; $size];
@@ -84,28 +118,28 @@ macro_rules! impl_bytes {
                 let s = if s.starts_with("0x") { &s[2..] } else { s };
 
                 if s.len() % 2 == 1 {
-                    ("0".to_owned() + s).parse().unwrap()
+                    $crate::__private::alloc::format!("0{}", s).parse().unwrap()
                 } else {
                     s.parse().unwrap()
                 }
             }
         }
 
-        impl From<Vec<u8>> for $name {
-            fn from(v: Vec<u8>) -> $name {
+        impl From<$crate::__private::alloc::vec::Vec<u8>> for $name {
+            fn from(v: $crate::__private::alloc::vec::Vec<u8>) -> $name {
                 Self::from(&v[..])
             }
         }
 
-        impl ::std::str::FromStr for $name {
-            type Err = ::rustc_hex::FromHexError;
+        impl ::core::str::FromStr for $name {
+            type Err = $crate::__private::rustc_hex::FromHexError;
 
-            fn from_str(s: &str) -> Result<$name, ::rustc_hex::FromHexError> {
-                use ::rustc_hex::FromHex;
+            fn from_str(s: &str) -> Result<$name, $crate::__private::rustc_hex::FromHexError> {
+                use $crate::__private::rustc_hex::FromHex;
 
-                let a: Vec<u8> = s.from_hex()?;
+                let a: $crate::__private::alloc::vec::Vec<u8> = s.from_hex()?;
                 if a.len() != $size {
-                    return Err(::rustc_hex::FromHexError::InvalidHexLength);
+                    return Err($crate::__private::rustc_hex::FromHexError::InvalidHexLength);
                 }
 
                 let mut ret = [0; $size];
@@ -114,45 +148,15 @@ macro_rules! impl_bytes {
             }
         }
 
-        // Formatting.
-
-        impl ::core::fmt::LowerHex for $name {
-            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
-                for i in &self.0[..] {
-                    write!(f, "{:02x}", i)?;
-                }
-                Ok(())
-            }
-        }
-
-        impl ::core::fmt::Debug for $name {
-            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
-                ::core::fmt::LowerHex::fmt(self, f)
-            }
-        }
-
-        impl ::core::fmt::Display for $name {
-            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
-                for i in &self.0[0..2] {
-                    write!(f, "{:02x}", i)?;
-                }
-                write!(f, "…")?;
-                for i in &self.0[$size - 2..$size] {
-                    write!(f, "{:02x}", i)?;
-                }
-                Ok(())
-            }
-        }
-
         // Serialization.
 
-        impl ::serde::Serialize for $name {
+        impl $crate::__private::serde::Serialize for $name {
             fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
             where
-                S: ::serde::Serializer,
+                S: $crate::__private::serde::Serializer,
             {
                 if serializer.is_human_readable() {
-                    serializer.serialize_str(&base64::encode(&self))
+                    serializer.serialize_str(&$crate::base64::encode(&self))
                 } else {
                     serializer.serialize_bytes(self.as_ref())
                 }
@@ -161,38 +165,34 @@ macro_rules! impl_bytes {
 
         // Deserialization.
 
-        impl<'de> ::serde::Deserialize<'de> for $name {
+        impl<'de> $crate::__private::serde::Deserialize<'de> for $name {
             fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
             where
-                D: ::serde::Deserializer<'de>,
+                D: $crate::__private::serde::Deserializer<'de>,
             {
                 struct BytesVisitor;
 
-                impl<'de> ::serde::de::Visitor<'de> for BytesVisitor {
+                impl<'de> $crate::__private::serde::de::Visitor<'de> for BytesVisitor {
                     type Value = $name;
 
                     fn expecting(
                         &self,
-                        formatter: &mut ::std::fmt::Formatter,
-                    ) -> ::std::fmt::Result {
+                        formatter: &mut ::core::fmt::Formatter,
+                    ) -> ::core::fmt::Result {
                         formatter.write_str("bytes or string expected")
                     }
 
                     fn visit_str<E>(self, data: &str) -> Result<$name, E>
                     where
-                        E: ::serde::de::Error,
+                        E: $crate::__private::serde::de::Error,
                     {
                         let mut array = [0; $size];
-                        let bytes = match base64::decode(data) {
+                        let bytes = match $crate::base64::decode(data) {
                             Ok(b) => b,
-                            Err(err) => return match err {
-                                base64::DecodeError::InvalidByte(pos, v) => Err(::serde::de::Error::custom(format!("invalid base64-encoded string: invalid byte '{}' at position {}", v, pos))),
-                                base64::DecodeError::InvalidLength => Err(::serde::de::Error::custom(format!("invalid base64-encoded string: invalid length {}", data.len()))),
-                                base64::DecodeError::InvalidLastSymbol(pos, v) => Err(::serde::de::Error::custom(format!("invalid base64-encoded string: invalid last symbol '{}' at position {}", v, pos))),
-                            },
+                            Err(err) => return Err(E::custom(format_args!("invalid base64-encoded string: {}", err))),
                         };
                         if bytes.len() != $size {
-                            return Err(::serde::de::Error::invalid_length(bytes.len(), &self));
+                            return Err(E::invalid_length(bytes.len(), &self));
                         }
                         array[..].copy_from_slice(&bytes);
 
@@ -201,10 +201,10 @@ macro_rules! impl_bytes {
 
                    fn visit_bytes<E>(self, data: &[u8]) -> Result<$name, E>
                     where
-                        E: ::serde::de::Error,
+                        E: $crate::__private::serde::de::Error,
                     {
                         if data.len() != $size {
-                            return Err(::serde::de::Error::invalid_length(data.len(), &self));
+                            return Err(E::invalid_length(data.len(), &self));
                         }
                         let mut array = [0; $size];
                         array[..].copy_from_slice(data);
@@ -224,6 +224,39 @@ macro_rules! impl_bytes {
     };
 }
 
+/// Define a byte array-like type holding secret key material.
+///
+/// In contrast to types defined by `impl_bytes!`, the type is not `Copy`, is
+/// zeroized when dropped and does not reveal its contents when formatted.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// impl_secret_bytes!(MySecret, 32, "A 32-byte secret.");
+/// ```
+#[macro_export]
+macro_rules! impl_secret_bytes {
+    ($name:ident, $size:expr, $doc:expr) => {
+        #[doc=$doc]
+        #[derive(Clone)]
+        pub struct $name(pub [u8; $size]);
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                $crate::__private::zeroize::Zeroize::zeroize(&mut self.0[..]);
+            }
+        }
+
+        impl ::core::fmt::Debug for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                write!(f, concat!(stringify!($name), "(<redacted>)"))
+            }
+        }
+
+        $crate::impl_bytes!(@common $name, $size);
+    };
+}
+
 #[cfg(test)]
 mod tests {
     // Use hash of an empty string as a test key.
@@ -234,6 +267,7 @@ mod tests {
     ];
 
     impl_bytes!(TestKey, 32, "test key");
+    impl_secret_bytes!(TestSecret, 32, "test secret");
 
     #[test]
     fn test_length() {
@@ -270,4 +304,14 @@ mod tests {
         let new_test_key: TestKey = serde_cbor::from_slice(&test_key_vec).unwrap();
         assert_eq!(new_test_key, test_key);
     }
+
+    #[test]
+    fn test_secret() {
+        let secret = TestSecret(TEST_KEY_BYTES);
+        assert_eq!(format!("{:?}", secret), "TestSecret(<redacted>)");
+
+        let secret_vec = serde_cbor::to_vec(&secret).unwrap();
+        let new_secret: TestSecret = serde_cbor::from_slice(&secret_vec).unwrap();
+        assert_eq!(new_secret, secret);
+    }
 }
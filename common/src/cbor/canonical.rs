@@ -0,0 +1,334 @@
+//! Canonical CBOR encoding without `Value`.
+//!
+//! The encoding is the same as the one produced by serializing through
+//! serde_cbor's `Value`: integers and lengths use their shortest form, all
+//! lengths are definite and map entries are sorted by their encoded keys.
+//! With shortest form headers, the bytewise order of encoded keys is the
+//! same as `Value`'s order of shorter keys first, then lexicographic.
+use alloc::vec::Vec;
+use core::str;
+
+use super::CanonicalError;
+
+/// Break marker terminating indefinite length items.
+const BREAK: u8 = 0xff;
+
+/// Check that the slice holds a single canonically encoded item without
+/// floating point values.
+pub(super) fn check(slice: &[u8]) -> Result<(), CanonicalError> {
+    let mut reader = Reader::new(slice);
+    reader.check_item()?;
+    if reader.offset != slice.len() {
+        return Err(CanonicalError::NonCanonical);
+    }
+    Ok(())
+}
+
+/// Re-encode the single item held by the slice canonically.
+pub(super) fn canonicalize(slice: &[u8]) -> Result<Vec<u8>, CanonicalError> {
+    let mut reader = Reader::new(slice);
+    let mut output = Vec::with_capacity(slice.len());
+    reader.encode_item(&mut output)?;
+    if reader.offset != slice.len() {
+        return Err(CanonicalError::Malformed);
+    }
+    Ok(output)
+}
+
+/// Additional information of the shortest form header for an argument.
+fn shortest_info(argument: u64) -> u8 {
+    match argument {
+        0..=23 => argument as u8,
+        24..=0xff => 24,
+        0x100..=0xffff => 25,
+        0x1_0000..=0xffff_ffff => 26,
+        _ => 27,
+    }
+}
+
+/// Write a shortest form header.
+fn write_head(output: &mut Vec<u8>, major: u8, argument: u64) {
+    let info = shortest_info(argument);
+    let size = match info {
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => 0,
+    };
+    output.push(major << 5 | info);
+    output.extend_from_slice(&argument.to_be_bytes()[8 - size..]);
+}
+
+struct Reader<'a> {
+    input: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        Self { input, offset: 0 }
+    }
+
+    fn peek(&self) -> Result<u8, CanonicalError> {
+        self.input
+            .get(self.offset)
+            .cloned()
+            .ok_or(CanonicalError::Malformed)
+    }
+
+    fn take(&mut self, length: u64) -> Result<&'a [u8], CanonicalError> {
+        if length > (self.input.len() - self.offset) as u64 {
+            return Err(CanonicalError::Malformed);
+        }
+        let input = self.input;
+        let start = self.offset;
+        self.offset += length as usize;
+        Ok(&input[start..self.offset])
+    }
+
+    /// Read the header of an item, returning the major type, additional
+    /// information and argument (`None` for indefinite lengths).
+    fn read_head(&mut self) -> Result<(u8, u8, Option<u64>), CanonicalError> {
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        let size = match info {
+            0..=23 => return Ok((major, info, Some(info as u64))),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            31 => return Ok((major, info, None)),
+            _ => return Err(CanonicalError::Malformed),
+        };
+        let argument = self
+            .take(size)?
+            .iter()
+            .fold(0, |value, byte| (value << 8) | *byte as u64);
+
+        Ok((major, info, Some(argument)))
+    }
+
+    /// Whether another item of a collection of the given length follows,
+    /// consuming the break marker of indefinite length collections.
+    fn has_next(&mut self, length: Option<u64>, count: u64) -> Result<bool, CanonicalError> {
+        match length {
+            Some(length) => Ok(count < length),
+            None if self.peek()? == BREAK => {
+                self.offset += 1;
+                Ok(false)
+            }
+            None => Ok(true),
+        }
+    }
+
+    fn check_item(&mut self) -> Result<(), CanonicalError> {
+        let (major, info, argument) = self.read_head()?;
+        if major == 7 {
+            return match info {
+                // False, true and null.
+                20..=22 => Ok(()),
+                25..=27 => Err(CanonicalError::Float),
+                _ => Err(CanonicalError::NonCanonical),
+            };
+        }
+        let length = match argument {
+            Some(argument) if info == shortest_info(argument) => argument,
+            _ => return Err(CanonicalError::NonCanonical),
+        };
+
+        match major {
+            // Integers.
+            0 | 1 => Ok(()),
+            // Byte strings.
+            2 => self.take(length).map(|_| ()),
+            // Text strings.
+            3 => match str::from_utf8(self.take(length)?) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(CanonicalError::Malformed),
+            },
+            // Arrays.
+            4 => {
+                for _ in 0..length {
+                    self.check_item()?;
+                }
+                Ok(())
+            }
+            // Maps, with strictly increasing keys.
+            5 => {
+                let input = self.input;
+                let mut previous: Option<&[u8]> = None;
+                for _ in 0..length {
+                    let start = self.offset;
+                    self.check_item()?;
+                    let key = &input[start..self.offset];
+                    if previous.map_or(false, |previous| previous >= key) {
+                        return Err(CanonicalError::NonCanonical);
+                    }
+                    previous = Some(key);
+                    self.check_item()?;
+                }
+                Ok(())
+            }
+            // Tags.
+            _ => Err(CanonicalError::NonCanonical),
+        }
+    }
+
+    fn encode_item(&mut self, output: &mut Vec<u8>) -> Result<(), CanonicalError> {
+        let start = self.offset;
+        let (major, _, argument) = self.read_head()?;
+
+        match (major, argument) {
+            // Integers.
+            (0, Some(argument)) | (1, Some(argument)) => write_head(output, major, argument),
+            // Byte and text strings.
+            (2, Some(length)) | (3, Some(length)) => {
+                write_head(output, major, length);
+                output.extend_from_slice(self.take(length)?);
+            }
+            // Indefinite length strings, consisting of definite length chunks.
+            (2, None) | (3, None) => {
+                let mut data = Vec::new();
+                while self.has_next(None, 0)? {
+                    match self.read_head()? {
+                        (chunk_major, _, Some(length)) if chunk_major == major => {
+                            data.extend_from_slice(self.take(length)?)
+                        }
+                        _ => return Err(CanonicalError::Malformed),
+                    }
+                }
+                write_head(output, major, data.len() as u64);
+                output.extend_from_slice(&data);
+            }
+            // Arrays.
+            (4, length) => {
+                let mut items = Vec::new();
+                let mut count = 0;
+                while self.has_next(length, count)? {
+                    self.encode_item(&mut items)?;
+                    count += 1;
+                }
+                write_head(output, major, count);
+                output.extend_from_slice(&items);
+            }
+            // Maps.
+            (5, length) => {
+                let mut entries = Vec::new();
+                let mut count = 0;
+                while self.has_next(length, count)? {
+                    let (mut key, mut value) = (Vec::new(), Vec::new());
+                    self.encode_item(&mut key)?;
+                    self.encode_item(&mut value)?;
+                    entries.push((key, value));
+                    count += 1;
+                }
+
+                // Sort entries by key, keeping the last of duplicate keys.
+                entries.reverse();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+                entries.dedup_by(|a, b| a.0 == b.0);
+
+                write_head(output, major, entries.len() as u64);
+                for (key, value) in entries {
+                    output.extend_from_slice(&key);
+                    output.extend_from_slice(&value);
+                }
+            }
+            // Tags.
+            (6, Some(tag)) => {
+                write_head(output, major, tag);
+                self.encode_item(output)?;
+            }
+            // Simple values and floating point numbers, but not a stray break.
+            (7, Some(_)) => output.extend_from_slice(&self.input[start..self.offset]),
+            _ => return Err(CanonicalError::Malformed),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        for raw in &[
+            vec![0x01],
+            vec![0x18, 0x18],
+            vec![0x39, 0x01, 0x00],
+            vec![0x43, 0x01, 0x02, 0x03],
+            vec![0x82, 0x01, 0xf6],
+            // {1: 0, "a": 0, "bb": 0}
+            vec![0xa3, 0x01, 0x00, 0x61, 0x61, 0x00, 0x62, 0x62, 0x62, 0x00],
+        ] {
+            assert!(check(raw).is_ok(), "{:x?}", raw);
+            assert_eq!(&canonicalize(raw).unwrap(), raw);
+        }
+
+        for raw in &[
+            // Non-shortest forms.
+            vec![0x18, 0x01],
+            vec![0x59, 0x00, 0x01, 0x00],
+            // Indefinite lengths.
+            vec![0x9f, 0x01, 0xff],
+            // Unsorted and duplicate keys.
+            vec![0xa2, 0x62, 0x62, 0x62, 0x00, 0x61, 0x61, 0x00],
+            vec![0xa2, 0x61, 0x61, 0x00, 0x61, 0x61, 0x01],
+            // Tags and undefined.
+            vec![0xc1, 0x01],
+            vec![0xf7],
+            // Trailing data.
+            vec![0x01, 0x01],
+        ] {
+            assert_eq!(
+                check(raw).unwrap_err().to_string(),
+                CanonicalError::NonCanonical.to_string(),
+                "{:x?}",
+                raw
+            );
+        }
+
+        assert!(check(&[0xf9, 0x3c, 0x00]).is_err());
+        assert!(check(&[0x82, 0xfb, 0, 0, 0, 0, 0, 0, 0, 0, 0x01]).is_err());
+        assert!(check(&[0x61, 0xff]).is_err());
+        assert!(check(&[0x82, 0x01]).is_err());
+        assert!(check(&[]).is_err());
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        for (raw, canonical) in &[
+            // Indefinite lengths.
+            (vec![0x9f, 0x01, 0x02, 0xff], vec![0x82, 0x01, 0x02]),
+            (
+                vec![0x5f, 0x41, 0x01, 0x42, 0x02, 0x03, 0xff],
+                vec![0x43, 0x01, 0x02, 0x03],
+            ),
+            // Non-shortest forms.
+            (vec![0x19, 0x00, 0x01], vec![0x01]),
+            (vec![0x58, 0x01, 0x00], vec![0x41, 0x00]),
+            // Unsorted and duplicate keys, also in nested maps.
+            (
+                vec![
+                    0xbf, 0x62, 0x62, 0x62, 0x00, 0x61, 0x61, 0x00, 0x01, 0x00, 0xff,
+                ],
+                vec![0xa3, 0x01, 0x00, 0x61, 0x61, 0x00, 0x62, 0x62, 0x62, 0x00],
+            ),
+            (
+                vec![0x81, 0xa2, 0x61, 0x61, 0x00, 0x61, 0x61, 0x01],
+                vec![0x81, 0xa1, 0x61, 0x61, 0x01],
+            ),
+            // Floating point values are kept as they are.
+            (vec![0xf9, 0x3c, 0x00], vec![0xf9, 0x3c, 0x00]),
+        ] {
+            assert_eq!(&canonicalize(raw).unwrap(), canonical, "{:x?}", raw);
+        }
+
+        assert!(canonicalize(&[0xff]).is_err());
+        assert!(canonicalize(&[0x9f, 0x01]).is_err());
+        assert!(canonicalize(&[0x5f, 0x61, 0x00, 0xff]).is_err());
+        assert!(canonicalize(&[0x01, 0x01]).is_err());
+    }
+}
@@ -0,0 +1,599 @@
+//! Canonical CBOR serialization/deserialization functions.
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::{io::Write, mem};
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+pub use serde_cbor::value::{from_value, Value};
+use serde_cbor::{self, Result};
+
+mod canonical;
+#[cfg(feature = "std")]
+mod stream;
+
+#[cfg(feature = "std")]
+pub use self::stream::{ArrayDecoder, MapDecoder, StreamError};
+
+/// Canonical CBOR error.
+#[derive(Debug)]
+pub enum CanonicalError {
+    NonCanonical,
+    Float,
+    Malformed,
+}
+
+impl fmt::Display for CanonicalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CanonicalError::NonCanonical => write!(f, "cbor: non-canonical encoding"),
+            CanonicalError::Float => write!(f, "cbor: floating point values are not allowed"),
+            CanonicalError::Malformed => write!(f, "cbor: malformed input"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CanonicalError {}
+
+/// Default maximum size of decoded inputs in bytes.
+pub const DEFAULT_MAX_INPUT_SIZE: usize = 16 * 1024 * 1024;
+/// Default maximum nesting depth of decoded inputs.
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+/// Default maximum number of items in a decoded array or map.
+pub const DEFAULT_MAX_COLLECTION_LENGTH: u64 = 1 << 20;
+/// Default maximum estimated number of bytes allocated while decoding.
+pub const DEFAULT_MAX_ALLOCATION: usize = 64 * 1024 * 1024;
+
+/// Estimated number of bytes allocated for each item of a collection.
+#[cfg(feature = "std")]
+const ITEM_ALLOCATION: usize = mem::size_of::<Value>();
+/// Estimated number of bytes allocated for each item of a collection, the
+/// size of `Value` on 64-bit platforms.
+#[cfg(not(feature = "std"))]
+const ITEM_ALLOCATION: usize = 32;
+
+/// CBOR decoding limit error.
+#[derive(Debug)]
+pub enum LimitError {
+    InputTooLarge(usize),
+    TooDeep(usize),
+    CollectionTooLong(u64),
+    AllocationLimit(usize),
+    Malformed,
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitError::InputTooLarge(size) => write!(f, "cbor: input exceeds {} bytes", size),
+            LimitError::TooDeep(depth) => write!(f, "cbor: input exceeds nesting depth {}", depth),
+            LimitError::CollectionTooLong(length) => {
+                write!(f, "cbor: collection exceeds {} items", length)
+            }
+            LimitError::AllocationLimit(size) => {
+                write!(f, "cbor: decoding would allocate more than {} bytes", size)
+            }
+            LimitError::Malformed => write!(f, "cbor: malformed input"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LimitError {}
+
+/// CBOR error, used instead of `anyhow::Error` without the `std` feature.
+#[cfg(not(feature = "std"))]
+#[derive(Debug)]
+pub enum Error {
+    Limit(LimitError),
+    Canonical(CanonicalError),
+    Decode(serde_cbor::Error),
+}
+
+#[cfg(not(feature = "std"))]
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Limit(error) => write!(f, "{}", error),
+            Error::Canonical(error) => write!(f, "{}", error),
+            Error::Decode(error) => write!(f, "cbor: {}", error),
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl From<LimitError> for Error {
+    fn from(error: LimitError) -> Self {
+        Error::Limit(error)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl From<CanonicalError> for Error {
+    fn from(error: CanonicalError) -> Self {
+        Error::Canonical(error)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl From<serde_cbor::Error> for Error {
+    fn from(error: serde_cbor::Error) -> Self {
+        Error::Decode(error)
+    }
+}
+
+#[cfg(feature = "std")]
+type Fallible<T> = anyhow::Result<T>;
+#[cfg(not(feature = "std"))]
+type Fallible<T> = core::result::Result<T, Error>;
+
+/// Resource limits for decoding untrusted inputs.
+///
+/// The limits are checked on the raw input before it is decoded, so that
+/// malicious inputs cannot make the decoder allocate large amounts of memory
+/// or recurse deeply.
+#[derive(Clone, Debug)]
+pub struct DecodeLimits {
+    /// Maximum size of the input in bytes.
+    pub max_input_size: usize,
+    /// Maximum nesting depth of arrays, maps and tags.
+    pub max_depth: usize,
+    /// Maximum number of items in an array or entries in a map.
+    pub max_collection_length: u64,
+    /// Maximum estimated number of bytes allocated while decoding, counting
+    /// all string bytes and a fixed size for every collection item.
+    pub max_allocation: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_input_size: DEFAULT_MAX_INPUT_SIZE,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_collection_length: DEFAULT_MAX_COLLECTION_LENGTH,
+            max_allocation: DEFAULT_MAX_ALLOCATION,
+        }
+    }
+}
+
+impl DecodeLimits {
+    /// Check that decoding the given input stays within the limits.
+    pub fn check(&self, slice: &[u8]) -> Fallible<()> {
+        if slice.len() > self.max_input_size {
+            return Err(LimitError::InputTooLarge(self.max_input_size).into());
+        }
+
+        let mut checker = LimitChecker {
+            limits: self,
+            input: slice,
+            offset: 0,
+            allocation: 0,
+        };
+        checker.check_item(0)?;
+        Ok(())
+    }
+}
+
+/// Walks the raw encoding of a single item, checking limits.
+struct LimitChecker<'a> {
+    limits: &'a DecodeLimits,
+    input: &'a [u8],
+    offset: usize,
+    allocation: usize,
+}
+
+impl<'a> LimitChecker<'a> {
+    fn remaining(&self) -> usize {
+        self.input.len() - self.offset
+    }
+
+    fn peek(&self) -> core::result::Result<u8, LimitError> {
+        self.input
+            .get(self.offset)
+            .cloned()
+            .ok_or(LimitError::Malformed)
+    }
+
+    fn skip(&mut self, length: u64) -> core::result::Result<(), LimitError> {
+        if length > self.remaining() as u64 {
+            return Err(LimitError::Malformed);
+        }
+        self.offset += length as usize;
+        Ok(())
+    }
+
+    fn allocate(&mut self, length: u64, size: usize) -> core::result::Result<(), LimitError> {
+        let limit = self.limits.max_allocation;
+        let allocation = length
+            .checked_mul(size as u64)
+            .and_then(|bytes| bytes.checked_add(self.allocation as u64))
+            .filter(|allocation| *allocation <= limit as u64)
+            .ok_or(LimitError::AllocationLimit(limit))?;
+        self.allocation = allocation as usize;
+        Ok(())
+    }
+
+    /// Read the argument of an item, `None` for indefinite lengths.
+    fn read_argument(&mut self, info: u8) -> core::result::Result<Option<u64>, LimitError> {
+        let size = match info {
+            0..=23 => return Ok(Some(info as u64)),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            31 => return Ok(None),
+            _ => return Err(LimitError::Malformed),
+        };
+        let start = self.offset;
+        self.skip(size)?;
+
+        Ok(Some(
+            self.input[start..self.offset]
+                .iter()
+                .fold(0, |value, byte| (value << 8) | *byte as u64),
+        ))
+    }
+
+    /// Check a collection of the given length, `None` for indefinite
+    /// lengths, with `width` items per entry.
+    fn check_collection(
+        &mut self,
+        length: Option<u64>,
+        width: u64,
+        depth: usize,
+    ) -> core::result::Result<(), LimitError> {
+        let max_length = self.limits.max_collection_length;
+        if let Some(length) = length {
+            if length > max_length {
+                return Err(LimitError::CollectionTooLong(max_length));
+            }
+            // Every item takes at least one byte.
+            if length.saturating_mul(width) > self.remaining() as u64 {
+                return Err(LimitError::Malformed);
+            }
+        }
+
+        let mut count = 0;
+        while length.map_or(true, |length| count < length) {
+            if length.is_none() && self.peek()? == 0xff {
+                self.offset += 1;
+                break;
+            }
+            if count >= max_length {
+                return Err(LimitError::CollectionTooLong(max_length));
+            }
+            self.allocate(width, ITEM_ALLOCATION)?;
+            for _ in 0..width {
+                self.check_item(depth + 1)?;
+            }
+            count += 1;
+        }
+        Ok(())
+    }
+
+    fn check_item(&mut self, depth: usize) -> core::result::Result<(), LimitError> {
+        if depth > self.limits.max_depth {
+            return Err(LimitError::TooDeep(self.limits.max_depth));
+        }
+
+        let initial = self.peek()?;
+        self.offset += 1;
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        let argument = self.read_argument(info)?;
+
+        match (major, argument) {
+            // Integers and simple values (but not a stray break).
+            (0, Some(_)) | (1, Some(_)) | (7, Some(_)) => Ok(()),
+            // Byte and text strings.
+            (2, Some(length)) | (3, Some(length)) => {
+                self.allocate(length, 1)?;
+                self.skip(length)
+            }
+            // Indefinite length strings, consisting of definite length chunks.
+            (2, None) | (3, None) => loop {
+                if self.peek()? == 0xff {
+                    self.offset += 1;
+                    return Ok(());
+                }
+                if self.peek()? >> 5 != major || self.peek()? & 0x1f == 31 {
+                    return Err(LimitError::Malformed);
+                }
+                self.check_item(depth + 1)?;
+            },
+            // Arrays.
+            (4, length) => self.check_collection(length, 1, depth),
+            // Maps.
+            (5, length) => self.check_collection(length, 2, depth),
+            // Tags.
+            (6, Some(_)) => self.check_item(depth + 1),
+            _ => Err(LimitError::Malformed),
+        }
+    }
+}
+
+/// Convert a value to a `Value`.
+#[cfg(feature = "std")]
+pub fn to_value<T>(value: T) -> Value
+where
+    T: Serialize,
+{
+    serde_cbor::value::to_value(value).unwrap()
+}
+
+/// Serializes a value to a vector.
+///
+/// Map keys are sorted and integers use their shortest form, so the result
+/// is canonical as long as the value does not contain floating point numbers.
+#[cfg(feature = "std")]
+pub fn to_vec<T>(value: &T) -> Vec<u8>
+where
+    T: Serialize,
+{
+    // Use to_value first to force serialization into canonical format.
+    serde_cbor::to_vec(&to_value(&value)).unwrap()
+}
+
+/// Serializes a value to a vector.
+///
+/// Map keys are sorted and integers use their shortest form, so the result
+/// is canonical as long as the value does not contain floating point numbers.
+#[cfg(not(feature = "std"))]
+pub fn to_vec<T>(value: &T) -> Vec<u8>
+where
+    T: Serialize,
+{
+    // Value is not available, so canonicalize the encoding afterwards.
+    canonical::canonicalize(&serde_cbor::to_vec(value).unwrap()).unwrap()
+}
+
+/// Serializes a value to a writer.
+#[cfg(feature = "std")]
+pub fn to_writer<W, T>(writer: W, value: &T)
+where
+    W: Write,
+    T: Serialize,
+{
+    // Use to_value first to force serialization into canonical format.
+    serde_cbor::to_writer(writer, &to_value(&value)).unwrap()
+}
+
+/// Deserializes a slice to a value.
+pub fn from_slice<'a, T>(slice: &'a [u8]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    serde_cbor::from_slice(slice)
+}
+
+/// Deserializes a slice to a value, enforcing the given resource limits.
+///
+/// This should be used for untrusted inputs.
+pub fn from_slice_with_limits<'a, T>(slice: &'a [u8], limits: &DecodeLimits) -> Fallible<T>
+where
+    T: Deserialize<'a>,
+{
+    limits.check(slice)?;
+    Ok(serde_cbor::from_slice(slice)?)
+}
+
+/// Deserializes a slice to a value, rejecting non-canonical encodings.
+///
+/// This should be used for data which is hashed or signed by other parties
+/// as multiple encodings of the same value would otherwise be accepted.
+/// Floating point values are rejected as well, as they have multiple
+/// encodings of the same number. The default decoding limits are enforced.
+pub fn from_slice_canonical<'a, T>(slice: &'a [u8]) -> Fallible<T>
+where
+    T: Deserialize<'a>,
+{
+    DecodeLimits::default().check(slice)?;
+    check_canonical(slice)?;
+    Ok(serde_cbor::from_slice(slice)?)
+}
+
+/// Check whether the given slice is a canonical CBOR encoding.
+pub fn is_canonical(slice: &[u8]) -> bool {
+    DecodeLimits::default().check(slice).is_ok() && check_canonical(slice).is_ok()
+}
+
+#[cfg(feature = "std")]
+fn check_canonical(slice: &[u8]) -> Fallible<()> {
+    let value: Value = serde_cbor::from_slice(slice)?;
+    if contains_float(&value) {
+        return Err(CanonicalError::Float.into());
+    }
+    if serde_cbor::to_vec(&value)? != slice {
+        return Err(CanonicalError::NonCanonical.into());
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "std"))]
+fn check_canonical(slice: &[u8]) -> Fallible<()> {
+    Ok(canonical::check(slice)?)
+}
+
+#[cfg(feature = "std")]
+fn contains_float(value: &Value) -> bool {
+    match value {
+        Value::Float(_) => true,
+        Value::Array(values) => values.iter().any(contains_float),
+        Value::Map(map) => map
+            .iter()
+            .any(|(key, value)| contains_float(key) || contains_float(value)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, HashMap};
+
+    use serde_derive::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Test {
+        zzz: u64,
+        a: u64,
+        map: HashMap<String, u64>,
+    }
+
+    #[test]
+    fn test_canonical_map_ordering() {
+        // Insertion order must not affect the encoding.
+        let mut encodings = Vec::new();
+        for order in &[["a", "bb", "c"], ["c", "a", "bb"], ["bb", "c", "a"]] {
+            let mut map = HashMap::new();
+            for key in order.iter() {
+                map.insert(key.to_string(), key.len() as u64);
+            }
+            encodings.push(to_vec(&Test { zzz: 1, a: 2, map }));
+        }
+        assert_eq!(encodings[0], encodings[1]);
+        assert_eq!(encodings[0], encodings[2]);
+        assert!(is_canonical(&encodings[0]));
+
+        // Shorter keys sort first, then lexicographically.
+        let mut map = BTreeMap::new();
+        map.insert("bb".to_string(), 0u64);
+        map.insert("c".to_string(), 0u64);
+        let value = to_value(&map);
+        let keys: Vec<Value> = match value {
+            Value::Map(m) => m.into_iter().map(|(k, _)| k).collect(),
+            _ => panic!("expected map"),
+        };
+        assert_eq!(
+            keys,
+            vec![Value::Text("c".to_string()), Value::Text("bb".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_canonical_round_trip() {
+        let mut map = HashMap::new();
+        map.insert("foo".to_string(), 42);
+        let t = Test { zzz: 1, a: 2, map };
+
+        let encoded = to_vec(&t);
+        let decoded: Test = from_slice_canonical(&encoded).expect("decode");
+        assert_eq!(decoded, t);
+        assert_eq!(to_vec(&decoded), encoded);
+    }
+
+    #[test]
+    fn test_non_canonical_rejected() {
+        // {"b": 1, "a": 2} with keys in non-canonical order.
+        let non_canonical = vec![0xa2, 0x61, 0x62, 0x01, 0x61, 0x61, 0x02];
+        assert!(!is_canonical(&non_canonical));
+        let result: Fallible<BTreeMap<String, u64>> = from_slice_canonical(&non_canonical);
+        assert!(result.is_err());
+
+        // Non-minimal integer encoding (1 encoded as uint8).
+        let non_minimal = vec![0x18, 0x01];
+        assert!(!is_canonical(&non_minimal));
+
+        // Floating point values, even when nested.
+        let float = to_vec(&vec![1.5f64]);
+        assert!(!is_canonical(&float));
+        let result: Fallible<Vec<f64>> = from_slice_canonical(&float);
+        assert!(result.is_err());
+
+        // Canonical encoding is accepted.
+        let canonical = vec![0xa2, 0x61, 0x61, 0x02, 0x61, 0x62, 0x01];
+        let result: BTreeMap<String, u64> =
+            from_slice_canonical(&canonical).expect("canonical decode");
+        assert_eq!(result.get("a"), Some(&2));
+    }
+
+    #[test]
+    fn test_raw_canonical() {
+        // The encoding used without the standard library must match.
+        let mut map = HashMap::new();
+        map.insert("foo".to_string(), 42);
+        map.insert("ba".to_string(), 300);
+        map.insert("c".to_string(), u64::max_value());
+        let t = Test { zzz: 1, a: 2, map };
+        let raw = serde_cbor::to_vec(&t).unwrap();
+        assert_eq!(canonical::canonicalize(&raw).unwrap(), to_vec(&t));
+
+        for raw in &[
+            to_vec(&t),
+            to_vec(&vec![1.5f64]),
+            vec![0xa2, 0x61, 0x62, 0x01, 0x61, 0x61, 0x02],
+            vec![0x18, 0x01],
+        ] {
+            assert_eq!(canonical::check(raw).is_ok(), is_canonical(raw));
+        }
+    }
+
+    #[test]
+    fn test_decode_limits() {
+        let limits = DecodeLimits {
+            max_input_size: 64,
+            max_depth: 2,
+            max_collection_length: 4,
+            max_allocation: 1024,
+        };
+        let check = |value: &Value| limits.check(&to_vec(value));
+        let error = |value: &Value| match check(value).unwrap_err().downcast::<LimitError>() {
+            Ok(error) => error,
+            Err(error) => panic!("unexpected error: {}", error),
+        };
+        let nested =
+            |depth: usize| (0..depth).fold(Value::Null, |value, _| Value::Array(vec![value]));
+
+        assert!(check(&to_value(&vec![1u64, 2, 3])).is_ok());
+        assert!(check(&nested(2)).is_ok());
+        match error(&nested(3)) {
+            LimitError::TooDeep(2) => {}
+            error => panic!("unexpected error: {}", error),
+        }
+        match error(&to_value(&vec![0u8; 5])) {
+            LimitError::CollectionTooLong(4) => {}
+            error => panic!("unexpected error: {}", error),
+        }
+        let small = DecodeLimits {
+            max_allocation: 16,
+            ..limits.clone()
+        };
+        assert!(small.check(&to_vec(&"a".repeat(16))).is_ok());
+        match small
+            .check(&to_vec(&"a".repeat(17)))
+            .unwrap_err()
+            .downcast()
+        {
+            Ok(LimitError::AllocationLimit(16)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        match error(&Value::Bytes(vec![0; 64])) {
+            LimitError::InputTooLarge(64) => {}
+            error => panic!("unexpected error: {}", error),
+        }
+
+        // Declared lengths larger than the input.
+        for raw in &[
+            vec![0x5a, 0xff, 0xff, 0xff, 0xff],
+            vec![0x84, 0x01],
+            vec![0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+        ] {
+            assert!(limits.check(raw).is_err());
+        }
+
+        // Indefinite lengths are limited as well.
+        assert!(limits.check(&[0x9f, 0x01, 0x02, 0xff]).is_ok());
+        assert!(limits
+            .check(&[0x9f, 0x01, 0x02, 0x03, 0x04, 0x05, 0xff])
+            .is_err());
+        assert!(limits.check(&[0x9f, 0x01]).is_err());
+        assert!(limits.check(&[0x5f, 0x41, 0x00, 0xff]).is_ok());
+        assert!(limits.check(&[0x5f, 0x61, 0x00, 0xff]).is_err());
+        assert!(limits.check(&[0xff]).is_err());
+
+        let decoded: Vec<u64> = from_slice_with_limits(&to_vec(&vec![1u64, 2]), &limits).unwrap();
+        assert_eq!(decoded, vec![1, 2]);
+    }
+}
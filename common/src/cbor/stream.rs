@@ -0,0 +1,224 @@
+//! Streaming CBOR decoders.
+use std::{fmt, io::Read, marker::PhantomData};
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde_cbor;
+
+/// CBOR streaming decoder error.
+#[derive(Debug)]
+pub enum StreamError {
+    UnexpectedType(&'static str),
+    IndefiniteLength,
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StreamError::UnexpectedType(expected) => write!(f, "cbor: expected {}", expected),
+            StreamError::IndefiniteLength => {
+                write!(f, "cbor: indefinite lengths are not supported")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
+
+/// An incremental decoder of the items of an array.
+///
+/// Items are decoded one at a time as the iterator is advanced, so arrays
+/// larger than the available memory can be processed as long as individual
+/// items are small.
+pub struct ArrayDecoder<R, T> {
+    reader: R,
+    remaining: u64,
+    item: PhantomData<T>,
+}
+
+impl<R, T> ArrayDecoder<R, T>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    /// Create a new decoder, reading the array header from the reader.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let remaining = read_length(&mut reader, 4, "array")?;
+
+        Ok(Self {
+            reader,
+            remaining,
+            item: PhantomData,
+        })
+    }
+
+    /// Number of items which have not been decoded yet.
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Return the underlying reader.
+    ///
+    /// If all items have been decoded, the reader is positioned right after
+    /// the array.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R, T> Iterator for ArrayDecoder<R, T>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let item = read_item(&mut self.reader);
+        if item.is_err() {
+            // The position of the next item is unknown.
+            self.remaining = 0;
+        }
+        Some(item)
+    }
+}
+
+/// An incremental decoder of the entries of a map.
+///
+/// Entries are decoded one at a time as the iterator is advanced, see
+/// `ArrayDecoder`.
+pub struct MapDecoder<R, K, V> {
+    reader: R,
+    remaining: u64,
+    entry: PhantomData<(K, V)>,
+}
+
+impl<R, K, V> MapDecoder<R, K, V>
+where
+    R: Read,
+    K: DeserializeOwned,
+    V: DeserializeOwned,
+{
+    /// Create a new decoder, reading the map header from the reader.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let remaining = read_length(&mut reader, 5, "map")?;
+
+        Ok(Self {
+            reader,
+            remaining,
+            entry: PhantomData,
+        })
+    }
+
+    /// Number of entries which have not been decoded yet.
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Return the underlying reader.
+    ///
+    /// If all entries have been decoded, the reader is positioned right
+    /// after the map.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R, K, V> Iterator for MapDecoder<R, K, V>
+where
+    R: Read,
+    K: DeserializeOwned,
+    V: DeserializeOwned,
+{
+    type Item = Result<(K, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let entry =
+            read_item(&mut self.reader).and_then(|key| Ok((key, read_item(&mut self.reader)?)));
+        if entry.is_err() {
+            // The position of the next entry is unknown.
+            self.remaining = 0;
+        }
+        Some(entry)
+    }
+}
+
+/// Read the header of a definite length item of the given major type.
+fn read_length<R: Read>(reader: &mut R, major: u8, expected: &'static str) -> Result<u64> {
+    let mut initial = [0u8; 1];
+    reader.read_exact(&mut initial)?;
+    if initial[0] >> 5 != major {
+        return Err(StreamError::UnexpectedType(expected).into());
+    }
+
+    let size = match initial[0] & 0x1f {
+        info @ 0..=23 => return Ok(info as u64),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        31 => return Err(StreamError::IndefiniteLength.into()),
+        _ => return Err(StreamError::UnexpectedType(expected).into()),
+    };
+    let mut argument = [0u8; 8];
+    reader.read_exact(&mut argument[8 - size..])?;
+
+    Ok(u64::from_be_bytes(argument))
+}
+
+/// Decode a single item from the reader without reading past it.
+fn read_item<R: Read, T: DeserializeOwned>(reader: &mut R) -> Result<T> {
+    let mut deserializer = serde_cbor::Deserializer::from_reader(reader);
+    Ok(T::deserialize(&mut deserializer)?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::{super::to_vec, *};
+
+    #[test]
+    fn test_streaming_decoders() {
+        let items: Vec<(u64, String)> = (0..300).map(|i| (i, format!("item {}", i))).collect();
+        let mut map = BTreeMap::new();
+        map.insert("a".to_string(), 1u64);
+        map.insert("bb".to_string(), 2u64);
+        let mut encoded = to_vec(&items);
+        encoded.extend(to_vec(&map));
+
+        let mut reader = &encoded[..];
+        let mut decoder = ArrayDecoder::<_, (u64, String)>::new(&mut reader).unwrap();
+        assert_eq!(decoder.remaining(), 300);
+        assert_eq!(decoder.next().unwrap().unwrap(), items[0]);
+        assert_eq!(decoder.remaining(), 299);
+        let rest: Vec<_> = decoder.by_ref().map(|item| item.unwrap()).collect();
+        assert_eq!(rest, &items[1..]);
+        decoder.into_inner();
+
+        // The reader is positioned after the array.
+        let decoder = MapDecoder::<_, String, u64>::new(&mut reader).unwrap();
+        let entries: BTreeMap<_, _> = decoder.map(|entry| entry.unwrap()).collect();
+        assert_eq!(entries, map);
+        assert!(reader.is_empty());
+
+        // Other types and indefinite lengths are rejected.
+        assert!(ArrayDecoder::<_, u64>::new(&to_vec(&map)[..]).is_err());
+        assert!(ArrayDecoder::<_, u64>::new(&[0x9f, 0x01, 0xff][..]).is_err());
+
+        // Decoding stops after a malformed item.
+        let mut decoder = ArrayDecoder::<_, u64>::new(&[0x82, 0x61, 0x61, 0x01][..]).unwrap();
+        assert!(decoder.next().unwrap().is_err());
+        assert!(decoder.next().is_none());
+    }
+}
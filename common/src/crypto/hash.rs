@@ -0,0 +1,104 @@
+//! Hash type.
+use sha2::{Digest, Sha512Trunc256};
+
+use super::blake3;
+
+impl_bytes!(
+    Hash,
+    32,
+    "A 32-byte hash, SHA-512/256 unless computed using BLAKE3."
+);
+
+impl Hash {
+    /// Compute a digest of the passed slice of bytes.
+    pub fn digest_bytes(data: &[u8]) -> Hash {
+        let mut result = [0u8; 32];
+        result[..].copy_from_slice(Sha512Trunc256::digest(&data).as_ref());
+
+        Hash(result)
+    }
+
+    /// Compute a digest of the passed slices of bytes.
+    pub fn digest_bytes_list(data: &[&[u8]]) -> Hash {
+        let mut ctx = Sha512Trunc256::new();
+        for datum in data {
+            ctx.input(datum);
+        }
+
+        let mut result = [0u8; 32];
+        result[..].copy_from_slice(ctx.result().as_ref());
+
+        Hash(result)
+    }
+
+    /// Compute a BLAKE3 digest of the passed slice of bytes, domain separated
+    /// by the given context.
+    ///
+    /// The context should be a hardcoded, globally unique string such as
+    /// `"oasis-core/storage: checkpoint chunk"`. Digests are never equal to
+    /// SHA-512/256 digests of the same data, so the two must not be mixed.
+    pub fn digest_blake3(context: &str, data: &[u8]) -> Hash {
+        Self::digest_blake3_list(context, &[data])
+    }
+
+    /// Compute a BLAKE3 digest of the passed slices of bytes, domain separated
+    /// by the given context.
+    pub fn digest_blake3_list(context: &str, data: &[&[u8]]) -> Hash {
+        let mut hasher = blake3::Hasher::new_derive_key(context);
+        for datum in data {
+            hasher.update(datum);
+        }
+
+        Hash(hasher.finalize().into())
+    }
+
+    /// Compute a keyed BLAKE3 digest (a MAC) of the passed slice of bytes.
+    pub fn digest_blake3_keyed(key: &[u8; blake3::KEY_LEN], data: &[u8]) -> Hash {
+        Hash(blake3::keyed_hash(key, data).into())
+    }
+
+    /// Returns true if the hash is of an empty string.
+    pub fn is_empty(&self) -> bool {
+        return self == &Hash::empty_hash();
+    }
+
+    /// Hash of an empty string.
+    pub fn empty_hash() -> Hash {
+        // This is SHA-512/256 of an empty string.
+        Hash([
+            0xc6, 0x72, 0xb8, 0xd1, 0xef, 0x56, 0xed, 0x28, 0xab, 0x87, 0xc3, 0x62, 0x2c, 0x51,
+            0x14, 0x06, 0x9b, 0xdd, 0x3a, 0xd7, 0xb8, 0xf9, 0x73, 0x74, 0x98, 0xd0, 0xc0, 0x1e,
+            0xce, 0xf0, 0x96, 0x7a,
+        ])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_digest_blake3() {
+        let context = "oasis-core/test: blake3";
+        let data: Vec<u8> = (0..1025).map(|i| (i % 251) as u8).collect();
+
+        let digest = Hash::digest_blake3(context, &data);
+        assert_eq!(
+            format!("{:x}", digest),
+            "22863d4442df99b9324963d4cf05f3424995e41d1ec3a42c4f217af566279aae"
+        );
+        assert_eq!(
+            Hash::digest_blake3_list(context, &[&data[..100], &data[100..]]),
+            digest
+        );
+        assert_ne!(Hash::digest_blake3("oasis-core/test: other", &data), digest);
+        assert_ne!(Hash::digest_bytes(&data), digest);
+
+        let key = [7u8; blake3::KEY_LEN];
+        assert_ne!(Hash::digest_blake3_keyed(&key, &data), digest);
+        assert_ne!(
+            Hash::digest_blake3_keyed(&key, &data),
+            Hash::digest_blake3_keyed(&[8u8; blake3::KEY_LEN], &data)
+        );
+    }
+}
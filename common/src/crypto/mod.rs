@@ -0,0 +1,7 @@
+//! Cryptographic primitives.
+
+pub mod hash;
+pub mod signature;
+
+// Re-exports.
+pub use blake3;
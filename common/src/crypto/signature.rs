@@ -0,0 +1,439 @@
+//! Signature types.
+use alloc::vec::Vec;
+use core::fmt;
+
+#[cfg(feature = "std")]
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use ed25519_dalek;
+use serde_derive::{Deserialize, Serialize};
+
+use super::hash::Hash;
+
+impl_bytes!(
+    PublicKey,
+    ed25519_dalek::PUBLIC_KEY_LENGTH,
+    "An Ed25519 public key."
+);
+
+/// Signature error.
+#[derive(Debug)]
+pub enum SignatureError {
+    MalleabilityError,
+    MissingPublicKey,
+    InsufficientSignatures(usize, usize),
+    BlobMismatch,
+    BatchLengthMismatch,
+    MalformedPublicKey,
+    MalformedSignature,
+    VerificationFailed,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignatureError::MalleabilityError => write!(f, "signature malleability check failed"),
+            SignatureError::MissingPublicKey => write!(f, "signature is missing the public key"),
+            SignatureError::InsufficientSignatures(got, required) => write!(
+                f,
+                "insufficient signatures (got: {} required: {})",
+                got, required
+            ),
+            SignatureError::BlobMismatch => write!(f, "signatures are over different blobs"),
+            SignatureError::BatchLengthMismatch => {
+                write!(f, "batch verification input lengths mismatch")
+            }
+            SignatureError::MalformedPublicKey => write!(f, "malformed public key"),
+            SignatureError::MalformedSignature => write!(f, "malformed signature"),
+            SignatureError::VerificationFailed => write!(f, "signature verification failed"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SignatureError {}
+
+/// Minimum number of signatures for which batch verification pays off.
+#[cfg(feature = "std")]
+const BATCH_VERIFY_THRESHOLD: usize = 4;
+
+static CURVE_ORDER: &'static [u64] = &[
+    0x1000000000000000,
+    0,
+    0x14def9dea2f79cd6,
+    0x5812631a5cf5d3ed,
+];
+
+impl PublicKey {
+    /// Verify a batch of signatures, each over the message at the same index
+    /// by the public key at the same index, all under the given context.
+    ///
+    /// Batch verification is considerably faster than verifying signatures
+    /// one by one. Batches below a small threshold, batches containing
+    /// small-order public keys or R points and batches which fail to verify
+    /// are verified serially instead, so that the returned error is the one
+    /// of the first invalid signature. Without the `std` feature all batches
+    /// are verified serially.
+    ///
+    /// Batch verification is randomized and may accept signatures whose
+    /// points have a torsion component which `Signature::verify` rejects, so
+    /// it must not be used where all verifiers have to agree on the result,
+    /// e.g., when verifying consensus data.
+    pub fn verify_batch(
+        context: &[u8],
+        messages: &[&[u8]],
+        public_keys: &[PublicKey],
+        signatures: &[Signature],
+    ) -> Result<(), SignatureError> {
+        if messages.len() != public_keys.len() || messages.len() != signatures.len() {
+            return Err(SignatureError::BatchLengthMismatch);
+        }
+
+        #[cfg(feature = "std")]
+        {
+            if messages.len() >= BATCH_VERIFY_THRESHOLD
+                && batch_verify(context, messages, public_keys, signatures)
+            {
+                return Ok(());
+            }
+        }
+
+        for ((message, pk), sig) in messages.iter().zip(public_keys).zip(signatures) {
+            sig.verify(pk, context, message)?;
+        }
+        Ok(())
+    }
+}
+
+impl_bytes!(Signature, 64, "An Ed25519 signature.");
+
+impl Signature {
+    /// Verify signature.
+    pub fn verify(
+        &self,
+        pk: &PublicKey,
+        context: &[u8],
+        message: &[u8],
+    ) -> Result<(), SignatureError> {
+        // TODO/#2103: Replace this with Ed25519ctx.
+        let pk = ed25519_dalek::PublicKey::from_bytes(pk.as_ref())
+            .map_err(|_| SignatureError::MalformedPublicKey)?;
+        let digest = Hash::digest_bytes_list(&[context, message]);
+        let sig_slice = self.as_ref();
+        let sig = ed25519_dalek::Signature::from_bytes(sig_slice)
+            .map_err(|_| SignatureError::MalformedSignature)?;
+
+        // ed25519-dalek does not enforce the RFC 8032 mandated constraint
+        // that s is in range [0, order), so signatures are malleable.
+        if !sc_minimal(&sig_slice[32..]) {
+            return Err(SignatureError::MalleabilityError);
+        }
+
+        pk.verify(digest.as_ref(), &sig)
+            .map_err(|_| SignatureError::VerificationFailed)
+    }
+}
+
+/// A signature bundled with a public key.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SignatureBundle {
+    /// Public key that produced the signature.
+    pub public_key: Option<PublicKey>,
+    /// Actual signature.
+    pub signature: Signature,
+}
+
+/// Verify the signatures using ed25519 batch verification, returning false
+/// if any of them is malformed or invalid.
+#[cfg(feature = "std")]
+fn batch_verify(
+    context: &[u8],
+    messages: &[&[u8]],
+    public_keys: &[PublicKey],
+    signatures: &[Signature],
+) -> bool {
+    // See Signature::verify.
+    if !signatures.iter().all(|sig| sc_minimal(&sig.as_ref()[32..])) {
+        return false;
+    }
+
+    // The random linear combination of the verification equations cancels
+    // out small-order components with non-negligible probability, so such
+    // signatures could be accepted even if they are invalid.
+    let is_small_order = |bytes: &[u8]| match CompressedEdwardsY::from_slice(bytes).decompress() {
+        Some(point) => point.is_small_order(),
+        None => true,
+    };
+    if public_keys.iter().any(|pk| is_small_order(pk.as_ref()))
+        || signatures
+            .iter()
+            .any(|sig| is_small_order(&sig.as_ref()[..32]))
+    {
+        return false;
+    }
+
+    // TODO/#2103: Replace this with Ed25519ctx.
+    let digests: Vec<Hash> = messages
+        .iter()
+        .map(|message| Hash::digest_bytes_list(&[context, *message]))
+        .collect();
+    let digests: Vec<&[u8]> = digests.iter().map(|digest| digest.as_ref()).collect();
+    let pks: Result<Vec<_>, _> = public_keys
+        .iter()
+        .map(|pk| ed25519_dalek::PublicKey::from_bytes(pk.as_ref()))
+        .collect();
+    let sigs: Result<Vec<_>, _> = signatures
+        .iter()
+        .map(|sig| ed25519_dalek::Signature::from_bytes(sig.as_ref()))
+        .collect();
+
+    match (pks, sigs) {
+        (Ok(pks), Ok(sigs)) => ed25519_dalek::verify_batch(&digests, &sigs, &pks).is_ok(),
+        _ => false,
+    }
+}
+
+// Check if s < L, per RFC 8032, inspired by the Go runtime library's version
+// of this check.
+fn sc_minimal(raw_s: &[u8]) -> bool {
+    let mut s = [0u64; 4];
+
+    // Read the raw scalar into limbs, most significant first, because the
+    // raw representation is little-endian.
+    for (limb, chunk) in s.iter_mut().rev().zip(raw_s.chunks(8)) {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(chunk);
+        *limb = u64::from_le_bytes(bytes);
+    }
+
+    // Compare each limb, from most significant to least.
+    for i in 0..4 {
+        if s[i] > CURVE_ORDER[i] {
+            return false;
+        } else if s[i] < CURVE_ORDER[i] {
+            return true;
+        }
+    }
+
+    // The scalar is equal to the order of the curve.
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use self::test::{black_box, Bencher};
+    use super::*;
+
+    /// Sign the message the same way as the runtime's private keys do.
+    fn sign(seed: u8, context: &[u8], message: &[u8]) -> (PublicKey, Signature) {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[seed; 32]).unwrap();
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        let digest = Hash::digest_bytes_list(&[context, message]);
+        let signature =
+            ed25519_dalek::ExpandedSecretKey::from(&secret).sign(digest.as_ref(), &public);
+
+        (
+            PublicKey(public.to_bytes()),
+            Signature(signature.to_bytes()),
+        )
+    }
+
+    #[test]
+    fn test_sc_minimal() {
+        // L - 2^0
+        assert!(sc_minimal(&[
+            0xec, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
+            0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x10
+        ]));
+
+        // L - 2^64
+        assert!(sc_minimal(&[
+            0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd5, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
+            0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x10
+        ]));
+
+        // L - 2^192
+        assert!(sc_minimal(&[
+            0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd5, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
+            0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff,
+            0xff, 0xff, 0xff, 0x0f,
+        ]));
+
+        // L
+        assert!(!sc_minimal(&[
+            0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
+            0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x10
+        ]));
+
+        // L + 2^0
+        assert!(!sc_minimal(&[
+            0xef, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
+            0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x10
+        ]));
+
+        // L + 2^64
+        assert!(!sc_minimal(&[
+            0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd7, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
+            0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x10
+        ]));
+
+        // L + 2^128
+        assert!(!sc_minimal(&[
+            0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
+            0xde, 0x14, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x10
+        ]));
+
+        // L + 2^192
+        assert!(!sc_minimal(&[
+            0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
+            0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x10
+        ]));
+
+        // Scalar from the go runtime's test case.
+        assert!(!sc_minimal(&[
+            0x67, 0x65, 0x4b, 0xce, 0x38, 0x32, 0xc2, 0xd7, 0x6f, 0x8f, 0x6f, 0x5d, 0xaf, 0xc0,
+            0x8d, 0x93, 0x39, 0xd4, 0xee, 0xf6, 0x76, 0x57, 0x33, 0x36, 0xa5, 0xc5, 0x1e, 0xb6,
+            0xf9, 0x46, 0xb3, 0x1d,
+        ]))
+    }
+
+    #[test]
+    fn test_verify() {
+        let context = b"oasis-core/test: verify";
+        let (public_key, signature) = sign(1, context, b"message");
+        signature.verify(&public_key, context, b"message").unwrap();
+
+        assert!(signature.verify(&public_key, context, b"other").is_err());
+        assert!(signature
+            .verify(&public_key, b"oasis-core/test: other", b"message")
+            .is_err());
+        assert!(signature
+            .verify(&sign(2, context, b"message").0, context, b"message")
+            .is_err());
+    }
+
+    #[test]
+    fn test_verify_batch() {
+        let context = b"oasis-core/test: verify batch";
+        let messages: Vec<Vec<u8>> = (0..8u8).map(|i| vec![i; 32]).collect();
+        let messages: Vec<&[u8]> = messages.iter().map(|m| m.as_slice()).collect();
+        let (public_keys, mut signatures): (Vec<_>, Vec<_>) = messages
+            .iter()
+            .enumerate()
+            .map(|(i, message)| sign(i as u8, context, message))
+            .unzip();
+
+        // Both the batch and the serial path accept valid signatures.
+        PublicKey::verify_batch(context, &messages, &public_keys, &signatures).unwrap();
+        PublicKey::verify_batch(context, &messages[..2], &public_keys[..2], &signatures[..2])
+            .unwrap();
+        PublicKey::verify_batch(context, &[], &[], &[]).unwrap();
+
+        // Signatures must be valid for the context.
+        assert!(PublicKey::verify_batch(
+            b"oasis-core/test: other",
+            &messages,
+            &public_keys,
+            &signatures
+        )
+        .is_err());
+
+        // Inputs must be of the same length.
+        assert!(
+            PublicKey::verify_batch(context, &messages[1..], &public_keys, &signatures).is_err()
+        );
+
+        // A single invalid signature fails the whole batch.
+        signatures.swap(3, 4);
+        assert!(PublicKey::verify_batch(context, &messages, &public_keys, &signatures).is_err());
+    }
+
+    #[test]
+    fn test_verify_batch_small_order() {
+        let context = b"oasis-core/test: verify batch";
+        let messages: Vec<Vec<u8>> = (0..8u8).map(|i| vec![i; 32]).collect();
+        let messages: Vec<&[u8]> = messages.iter().map(|m| m.as_slice()).collect();
+        let (mut public_keys, mut signatures): (Vec<_>, Vec<_>) = messages
+            .iter()
+            .enumerate()
+            .map(|(i, message)| sign(i as u8, context, message))
+            .unzip();
+
+        // The identity as public key and R with s = 0 satisfies the
+        // verification equation, so the signature is valid when verified
+        // serially and must also be accepted in a batch.
+        let mut identity = [0u8; 32];
+        identity[0] = 1;
+        public_keys[0] = PublicKey(identity);
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&identity);
+        signatures[0] = Signature(signature);
+        assert!(signatures[0]
+            .verify(&public_keys[0], context, messages[0])
+            .is_ok());
+        PublicKey::verify_batch(context, &messages, &public_keys, &signatures).unwrap();
+
+        // An R point of order two is rejected when verified serially, but
+        // its contribution to a batch vanishes for even random coefficients.
+        // It must be rejected every time.
+        let mut order_two = [0xffu8; 32];
+        order_two[0] = 0xec;
+        order_two[31] = 0x7f;
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&order_two);
+        signatures[0] = Signature(signature);
+        assert!(signatures[0]
+            .verify(&public_keys[0], context, messages[0])
+            .is_err());
+        for _ in 0..32 {
+            assert!(
+                PublicKey::verify_batch(context, &messages, &public_keys, &signatures).is_err()
+            );
+        }
+    }
+
+    fn bench_verify(b: &mut Bencher, count: u8, batch: bool) {
+        let context = b"oasis-core/test: verify batch";
+        let messages: Vec<Vec<u8>> = (0..count).map(|i| vec![i; 32]).collect();
+        let messages: Vec<&[u8]> = messages.iter().map(|m| m.as_slice()).collect();
+        let (public_keys, signatures): (Vec<_>, Vec<_>) = messages
+            .iter()
+            .enumerate()
+            .map(|(i, message)| sign(i as u8, context, message))
+            .unzip();
+
+        b.iter(|| {
+            if batch {
+                black_box(PublicKey::verify_batch(
+                    context,
+                    &messages,
+                    &public_keys,
+                    &signatures,
+                ))
+                .unwrap();
+            } else {
+                for ((message, pk), sig) in messages.iter().zip(&public_keys).zip(&signatures) {
+                    black_box(sig.verify(pk, context, message)).unwrap();
+                }
+            }
+        });
+    }
+
+    #[bench]
+    fn bench_verify_serial_64(b: &mut Bencher) {
+        bench_verify(b, 64, false)
+    }
+
+    #[bench]
+    fn bench_verify_batch_64(b: &mut Bencher) {
+        bench_verify(b, 64, true)
+    }
+}
@@ -0,0 +1,32 @@
+//! Oasis Core common types.
+//!
+//! Core types shared by the runtime, clients and verifiers: hashes,
+//! signatures, namespaces, quantities and canonical CBOR.
+//!
+//! With the default `std` feature disabled the crate only requires `alloc`,
+//! so the types can be reused in constrained environments such as other
+//! TEEs, wasm light clients and embedded verifiers. Functionality which
+//! depends on the standard library (e.g., streaming CBOR decoding) is only
+//! available with the `std` feature.
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(test, feature(test))]
+
+extern crate alloc;
+
+#[macro_use]
+pub mod bytes;
+pub mod base64;
+pub mod cbor;
+pub mod crypto;
+pub mod namespace;
+pub mod quantity;
+
+/// Dependencies used by the exported macros.
+#[doc(hidden)]
+pub mod __private {
+    pub extern crate alloc;
+
+    pub use rustc_hex;
+    pub use serde;
+    pub use zeroize;
+}
@@ -0,0 +1,3 @@
+//! Chain namespace.
+
+impl_bytes!(Namespace, 32, "Chain namespace.");
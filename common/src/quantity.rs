@@ -0,0 +1,452 @@
+//! An unsigned quantity of tokens.
+//!
+//! # Note
+//!
+//! This **MUST** be kept in sync with go/common/quantity.
+//!
+use alloc::{format, string::ToString, vec, vec::Vec};
+use core::{cmp::Ordering, convert::TryFrom, fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_bytes::{ByteBuf, Bytes};
+
+/// Quantity error.
+#[derive(Debug)]
+pub enum QuantityError {
+    Overflow,
+    Malformed,
+}
+
+impl fmt::Display for QuantityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QuantityError::Overflow => write!(f, "quantity: overflow"),
+            QuantityError::Malformed => write!(f, "quantity: malformed quantity"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for QuantityError {}
+
+/// An arbitrary-precision unsigned quantity of tokens.
+///
+/// Quantities are encoded as big-endian byte strings without leading zeroes.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct Quantity {
+    // Little-endian 64-bit limbs without trailing zero limbs, so that every
+    // value has a unique representation.
+    limbs: Vec<u64>,
+}
+
+impl Quantity {
+    fn from_limbs(mut limbs: Vec<u64>) -> Self {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        Quantity { limbs }
+    }
+
+    /// A zero quantity.
+    pub fn zero() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if the quantity is zero.
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    /// Encode the quantity as big-endian bytes without leading zeroes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.limbs.len() * 8);
+        for limb in self.limbs.iter().rev() {
+            bytes.extend_from_slice(&limb.to_be_bytes());
+        }
+        let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+        bytes.split_off(start)
+    }
+
+    /// Decode a quantity from big-endian bytes.
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let limbs = data
+            .rchunks(8)
+            .map(|chunk| {
+                let mut limb = [0u8; 8];
+                limb[8 - chunk.len()..].copy_from_slice(chunk);
+                u64::from_be_bytes(limb)
+            })
+            .collect();
+        Self::from_limbs(limbs)
+    }
+
+    /// Checked addition.
+    ///
+    /// Quantities are unbounded, so this never fails. It is provided for
+    /// parity with the other checked operations.
+    pub fn checked_add(&self, other: &Quantity) -> Option<Quantity> {
+        let (long, short) = if self.limbs.len() >= other.limbs.len() {
+            (&self.limbs, &other.limbs)
+        } else {
+            (&other.limbs, &self.limbs)
+        };
+
+        let mut limbs = Vec::with_capacity(long.len() + 1);
+        let mut carry = 0u128;
+        for (i, limb) in long.iter().enumerate() {
+            let sum = u128::from(*limb) + u128::from(*short.get(i).unwrap_or(&0)) + carry;
+            limbs.push(sum as u64);
+            carry = sum >> 64;
+        }
+        limbs.push(carry as u64);
+
+        Some(Self::from_limbs(limbs))
+    }
+
+    /// Checked subtraction.
+    ///
+    /// Returns `None` if the result would be negative.
+    pub fn checked_sub(&self, other: &Quantity) -> Option<Quantity> {
+        if *self < *other {
+            return None;
+        }
+
+        let mut limbs = Vec::with_capacity(self.limbs.len());
+        let mut borrow = false;
+        for (i, limb) in self.limbs.iter().enumerate() {
+            let (diff, borrow_a) = limb.overflowing_sub(*other.limbs.get(i).unwrap_or(&0));
+            let (diff, borrow_b) = diff.overflowing_sub(borrow as u64);
+            limbs.push(diff);
+            borrow = borrow_a || borrow_b;
+        }
+
+        Some(Self::from_limbs(limbs))
+    }
+
+    /// Subtraction that stops at zero instead of becoming negative.
+    pub fn saturating_sub(&self, other: &Quantity) -> Quantity {
+        self.checked_sub(other).unwrap_or_default()
+    }
+
+    /// Checked multiplication.
+    ///
+    /// Quantities are unbounded, so this never fails. It is provided for
+    /// parity with the other checked operations.
+    pub fn checked_mul(&self, other: &Quantity) -> Option<Quantity> {
+        let mut limbs = vec![0u64; self.limbs.len() + other.limbs.len()];
+        for (i, a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u128;
+            for (j, b) in other.limbs.iter().enumerate() {
+                let product = u128::from(*a) * u128::from(*b) + u128::from(limbs[i + j]) + carry;
+                limbs[i + j] = product as u64;
+                carry = product >> 64;
+            }
+            limbs[i + other.limbs.len()] = carry as u64;
+        }
+
+        Some(Self::from_limbs(limbs))
+    }
+
+    /// Checked division, rounding towards zero.
+    ///
+    /// Returns `None` if the divisor is zero.
+    pub fn checked_div(&self, other: &Quantity) -> Option<Quantity> {
+        if other.is_zero() {
+            return None;
+        }
+        if *self < *other {
+            return Some(Quantity::zero());
+        }
+
+        // Binary long division.
+        let mut quotient = vec![0u64; self.limbs.len()];
+        let mut remainder = Quantity::zero();
+        for bit in (0..self.limbs.len() * 64).rev() {
+            remainder = remainder.shl1(self.bit(bit));
+            if remainder >= *other {
+                remainder = remainder.checked_sub(other).unwrap();
+                quotient[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+
+        Some(Self::from_limbs(quotient))
+    }
+
+    fn bit(&self, bit: usize) -> bool {
+        (self.limbs[bit / 64] >> (bit % 64)) & 1 == 1
+    }
+
+    /// Shift left by one bit, shifting in the given bit.
+    fn shl1(&self, low: bool) -> Quantity {
+        let mut limbs = Vec::with_capacity(self.limbs.len() + 1);
+        let mut carry = low as u64;
+        for limb in &self.limbs {
+            limbs.push((limb << 1) | carry);
+            carry = limb >> 63;
+        }
+        limbs.push(carry);
+        Self::from_limbs(limbs)
+    }
+
+    /// Multiply by and add small values in place.
+    fn mul_add_small(&mut self, mul: u64, add: u64) {
+        let mut carry = u128::from(add);
+        for limb in self.limbs.iter_mut() {
+            let product = u128::from(*limb) * u128::from(mul) + carry;
+            *limb = product as u64;
+            carry = product >> 64;
+        }
+        if carry > 0 {
+            self.limbs.push(carry as u64);
+        }
+    }
+
+    /// Divide by a small non-zero value in place, returning the remainder.
+    fn div_rem_small(&mut self, div: u64) -> u64 {
+        let mut remainder = 0u128;
+        for limb in self.limbs.iter_mut().rev() {
+            let dividend = (remainder << 64) | u128::from(*limb);
+            *limb = (dividend / u128::from(div)) as u64;
+            remainder = dividend % u128::from(div);
+        }
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        remainder as u64
+    }
+
+    /// Convert to a `u128`, saturating at the maximum value.
+    pub fn saturating_to_u128(&self) -> u128 {
+        u128::try_from(self).unwrap_or(u128::max_value())
+    }
+
+    /// Convert to a `u64`, saturating at the maximum value.
+    pub fn saturating_to_u64(&self) -> u64 {
+        u64::try_from(self).unwrap_or(u64::max_value())
+    }
+}
+
+impl Ord for Quantity {
+    fn cmp(&self, other: &Quantity) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for Quantity {
+    fn partial_cmp(&self, other: &Quantity) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<u128> for Quantity {
+    fn from(value: u128) -> Self {
+        Self::from_limbs(vec![value as u64, (value >> 64) as u64])
+    }
+}
+
+impl From<u64> for Quantity {
+    fn from(value: u64) -> Self {
+        Self::from_limbs(vec![value])
+    }
+}
+
+impl TryFrom<&Quantity> for u128 {
+    type Error = QuantityError;
+
+    fn try_from(value: &Quantity) -> Result<Self, Self::Error> {
+        match value.limbs.len() {
+            0 => Ok(0),
+            1 => Ok(value.limbs[0].into()),
+            2 => Ok(u128::from(value.limbs[1]) << 64 | u128::from(value.limbs[0])),
+            _ => Err(QuantityError::Overflow),
+        }
+    }
+}
+
+impl TryFrom<&Quantity> for u64 {
+    type Error = QuantityError;
+
+    fn try_from(value: &Quantity) -> Result<Self, Self::Error> {
+        match value.limbs.len() {
+            0 => Ok(0),
+            1 => Ok(value.limbs[0]),
+            _ => Err(QuantityError::Overflow),
+        }
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Largest power of ten fitting into a limb.
+        const CHUNK: u64 = 10_000_000_000_000_000_000;
+        const CHUNK_DIGITS: usize = 19;
+
+        let mut value = self.clone();
+        let mut chunks = vec![];
+        loop {
+            chunks.push(value.div_rem_small(CHUNK));
+            if value.is_zero() {
+                break;
+            }
+        }
+
+        let mut digits = chunks.pop().unwrap().to_string();
+        for chunk in chunks.iter().rev() {
+            digits.push_str(&format!("{:0width$}", chunk, width = CHUNK_DIGITS));
+        }
+        f.pad_integral(true, "", &digits)
+    }
+}
+
+impl fmt::Debug for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Quantity({})", self)
+    }
+}
+
+impl FromStr for Quantity {
+    type Err = QuantityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(QuantityError::Malformed);
+        }
+
+        let mut value = Quantity::zero();
+        for c in s.chars() {
+            let digit = c.to_digit(10).ok_or(QuantityError::Malformed)?;
+            value.mul_add_small(10, digit.into());
+        }
+        Ok(value)
+    }
+}
+
+impl Serialize for Quantity {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Bytes::new(&self.to_bytes()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Quantity {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let data = ByteBuf::deserialize(deserializer)?;
+        if data.first() == Some(&0) {
+            return Err(de::Error::custom("quantity: non-canonical encoding"));
+        }
+        Ok(Quantity::from_bytes(&data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbor;
+
+    fn q(s: &str) -> Quantity {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_quantity_serialization() {
+        for value in &[
+            Quantity::zero(),
+            Quantity::from(1u64),
+            Quantity::from(255u64),
+            Quantity::from(256u64),
+            Quantity::from(1_000_000_000u64),
+            Quantity::from(u128::max_value()),
+            q("340282366920938463463374607431768211456"),
+        ] {
+            let enc = cbor::to_vec(value);
+            let dec: Quantity = cbor::from_slice(&enc).unwrap();
+            assert_eq!(&dec, value);
+        }
+
+        assert_eq!(Quantity::zero().to_bytes(), Vec::<u8>::new());
+        assert_eq!(Quantity::from(256u64).to_bytes(), vec![1, 0]);
+        assert_eq!(Quantity::from_bytes(&[0, 0, 1, 0]), Quantity::from(256u64));
+        assert_eq!(
+            Quantity::from_bytes(&[1; 17]).to_bytes(),
+            vec![1; 17],
+            "quantities are unbounded"
+        );
+
+        // Encodings must be canonical.
+        let enc = cbor::to_vec(&serde_bytes::Bytes::new(&[0, 1]));
+        assert!(cbor::from_slice::<Quantity>(&enc).is_err());
+    }
+
+    #[test]
+    fn test_quantity_display() {
+        for s in &[
+            "0",
+            "1",
+            "9999999999999999999",
+            "10000000000000000000",
+            "18446744073709551616",
+            "340282366920938463463374607431768211455",
+            "1000000000000000000000000000000000000000000000000000000000001",
+        ] {
+            assert_eq!(q(s).to_string(), *s);
+        }
+        assert_eq!(format!("{:>5}", Quantity::from(42u64)), "   42");
+        assert_eq!(format!("{:?}", Quantity::from(42u64)), "Quantity(42)");
+        assert!("".parse::<Quantity>().is_err());
+        assert!("-1".parse::<Quantity>().is_err());
+        assert!("1.5".parse::<Quantity>().is_err());
+    }
+
+    #[test]
+    fn test_quantity_arithmetic() {
+        let max = Quantity::from(u128::max_value());
+        let one = Quantity::from(1u64);
+
+        let sum = max.checked_add(&one).unwrap();
+        assert_eq!(sum, q("340282366920938463463374607431768211456"));
+        assert_eq!(sum.checked_sub(&one), Some(max.clone()));
+        assert_eq!(one.checked_sub(&sum), None);
+        assert_eq!(one.saturating_sub(&sum), Quantity::zero());
+        assert_eq!(max.checked_sub(&max), Some(Quantity::zero()));
+
+        let product = max.checked_mul(&max).unwrap();
+        assert_eq!(
+            product,
+            q("115792089237316195423570985008687907852589419931798687112530834793049593217025")
+        );
+        assert_eq!(product.checked_div(&max), Some(max.clone()));
+        assert_eq!(
+            product.checked_div(&sum),
+            Some(q("340282366920938463463374607431768211454"))
+        );
+        assert_eq!(one.checked_div(&max), Some(Quantity::zero()));
+        assert_eq!(max.checked_div(&Quantity::zero()), None);
+        assert_eq!(max.checked_mul(&Quantity::zero()), Some(Quantity::zero()));
+
+        assert!(Quantity::zero() < one);
+        assert!(max < sum);
+        assert!(q("18446744073709551616") > q("18446744073709551615"));
+    }
+
+    #[test]
+    fn test_quantity_conversions() {
+        let max = Quantity::from(u128::max_value());
+        let sum = max.checked_add(&Quantity::from(1u64)).unwrap();
+
+        assert_eq!(u128::try_from(&max).unwrap(), u128::max_value());
+        assert!(u128::try_from(&sum).is_err());
+        assert_eq!(sum.saturating_to_u128(), u128::max_value());
+        assert_eq!(u64::try_from(&Quantity::from(7u64)).unwrap(), 7);
+        assert!(u64::try_from(&max).is_err());
+        assert_eq!(max.saturating_to_u64(), u64::max_value());
+        assert_eq!(Quantity::zero().saturating_to_u64(), 0);
+    }
+}
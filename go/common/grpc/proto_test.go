@@ -0,0 +1,75 @@
+package grpc_test
+
+import (
+	"io/ioutil"
+	"path/filepath"
+	"regexp"
+	"sort"
+	"strings"
+	"testing"
+
+	"github.com/stretchr/testify/require"
+
+	"github.com/oasislabs/oasis-core/go/common/grpc"
+	// Register the services used by the Rust client.
+	_ "github.com/oasislabs/oasis-core/go/control/api"
+	_ "github.com/oasislabs/oasis-core/go/runtime/client/api"
+	_ "github.com/oasislabs/oasis-core/go/runtime/enclaverpc/api"
+)
+
+// clientProtoDir is the directory containing the protobuf sources the Rust
+// client is generated from.
+const clientProtoDir = "../../../client/proto"
+
+// clientUnexposedMethods are the methods intentionally not exposed by the
+// Rust client.
+var clientUnexposedMethods = map[string]bool{
+	"oasis-core.NodeController/UpgradeBinary": true,
+	"oasis-core.NodeController/CancelUpgrade": true,
+	"oasis-core.NodeController/GetStatus":     true,
+}
+
+var (
+	protoServiceRe = regexp.MustCompile(`(?m)^service (\w+) \{`)
+	protoRPCRe     = regexp.MustCompile(`(?m)^\s*rpc (\w+)\(`)
+)
+
+// TestClientProtos makes sure that the services described by the Rust
+// client protobuf sources do not drift from the Go services.
+func TestClientProtos(t *testing.T) {
+	require := require.New(t)
+
+	paths, err := filepath.Glob(filepath.Join(clientProtoDir, "*.proto"))
+	require.NoError(err, "Glob")
+	require.NotEmpty(paths, "client protobuf sources should exist")
+
+	var services int
+	for _, path := range paths {
+		data, err := ioutil.ReadFile(path)
+		require.NoError(err, "ReadFile(%s)", path)
+		src := string(data)
+
+		for _, loc := range protoServiceRe.FindAllStringSubmatchIndex(src, -1) {
+			sn := grpc.NewServiceName(src[loc[2]:loc[3]])
+			end := strings.Index(src[loc[1]:], "\n}")
+			require.True(end >= 0, "service %s in %s should be terminated", sn, path)
+
+			var protoMethods []string
+			for _, m := range protoRPCRe.FindAllStringSubmatch(src[loc[1]:loc[1]+end], -1) {
+				protoMethods = append(protoMethods, m[1])
+			}
+			sort.Strings(protoMethods)
+
+			var goMethods []string
+			for _, m := range grpc.GetRegisteredMethods(sn) {
+				if !clientUnexposedMethods[string(sn)+"/"+m] {
+					goMethods = append(goMethods, m)
+				}
+			}
+			require.NotEmpty(goMethods, "service %s in %s should be defined in Go", sn, path)
+			require.Equal(goMethods, protoMethods, "methods of service %s in %s should match the Go service", sn, path)
+			services++
+		}
+	}
+	require.NotZero(services, "client protobuf sources should define services")
+}
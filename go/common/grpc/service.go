@@ -4,6 +4,7 @@ import (
 	"context"
 	"fmt"
 	"reflect"
+	"sort"
 	"strings"
 	"sync"
 
@@ -53,6 +54,24 @@ func GetRegisteredMethod(name string) (*MethodDesc, error) {
 	return m, nil
 }
 
+// GetRegisteredMethods returns the short names of all registered methods of
+// the given service, sorted.
+func GetRegisteredMethods(sn ServiceName) []string {
+	var methods []string
+	registeredMethods.Range(func(_, md interface{}) bool {
+		m, ok := md.(*MethodDesc)
+		if !ok {
+			panic(fmt.Errorf("unexpected method description type: %T", md))
+		}
+		if ServiceNameFromMethod(m.FullName()) == sn {
+			methods = append(methods, m.ShortName())
+		}
+		return true
+	})
+	sort.Strings(methods)
+	return methods
+}
+
 // NewMethod creates a new method name for the given service.
 func (sn ServiceName) NewMethod(name string, requestType interface{}) *MethodDesc {
 	if strings.Contains(name, "/") {
@@ -31,6 +31,7 @@ import (
 	"github.com/oasislabs/oasis-core/go/consensus/tendermint/api"
 	epochtime "github.com/oasislabs/oasis-core/go/epochtime/api"
 	genesis "github.com/oasislabs/oasis-core/go/genesis/api"
+	"github.com/oasislabs/oasis-core/go/storage/mkvs/syncer"
 	upgrade "github.com/oasislabs/oasis-core/go/upgrade/api"
 )
 
@@ -268,6 +269,11 @@ func (a *ApplicationServer) BlockHeight() int64 {
 	return a.mux.state.BlockHeight()
 }
 
+// State returns a read syncer serving proofs of the application state.
+func (a *ApplicationServer) State() syncer.ReadSyncer {
+	return a.mux.state.Storage()
+}
+
 // NewApplicationServer returns a new ApplicationServer, using the provided
 // directory to persist state.
 func NewApplicationServer(ctx context.Context, upgrader upgrade.Backend, cfg *ApplicationConfig) (*ApplicationServer, error) {
@@ -9,6 +9,7 @@ import (
 	tmstate "github.com/tendermint/tendermint/state"
 
 	consensusAPI "github.com/oasislabs/oasis-core/go/consensus/api"
+	"github.com/oasislabs/oasis-core/go/storage/mkvs/syncer"
 )
 
 // We must use Tendermint's amino codec as some Tendermint's types are not easily unmarshallable.
@@ -73,3 +74,8 @@ func (t *tendermintService) GetParameters(ctx context.Context, height int64) (*c
 		Meta:   aminoCodec.MustMarshalBinaryBare(params.ConsensusParams),
 	}, nil
 }
+
+// Implements LightClientBackend.
+func (t *tendermintService) State() syncer.ReadSyncer {
+	return t.mux.State()
+}
@@ -25,6 +25,9 @@ var (
 	// methodGetStatuses is the GetStatuses method.
 	methodGetStatuses = serviceName.NewMethod("GetStatuses", int64(0))
 
+	// methodWatchStatuses is the WatchStatuses method.
+	methodWatchStatuses = serviceName.NewMethod("WatchStatuses", nil)
+
 	// serviceDesc is the gRPC service descriptor.
 	serviceDesc = grpc.ServiceDesc{
 		ServiceName: string(serviceName),
@@ -39,6 +42,13 @@ var (
 				Handler:    handlerGetStatuses,
 			},
 		},
+		Streams: []grpc.StreamDesc{
+			{
+				StreamName:    methodWatchStatuses.ShortName(),
+				Handler:       handlerWatchStatuses,
+				ServerStreams: true,
+			},
+		},
 	}
 )
 
@@ -115,6 +125,31 @@ func handlerGetStatuses( //nolint: golint
 	return interceptor(ctx, height, info, handler)
 }
 
+func handlerWatchStatuses(srv interface{}, stream grpc.ServerStream) error {
+	if err := stream.RecvMsg(nil); err != nil {
+		return err
+	}
+
+	ctx := stream.Context()
+	ch, sub := srv.(Backend).WatchStatuses()
+	defer sub.Close()
+
+	for {
+		select {
+		case status, ok := <-ch:
+			if !ok {
+				return nil
+			}
+
+			if err := stream.SendMsg(status); err != nil {
+				return err
+			}
+		case <-ctx.Done():
+			return ctx.Err()
+		}
+	}
+}
+
 // RegisterService registers a new keymanager backend service with the given gRPC server.
 func RegisterService(server *grpc.Server, service Backend) {
 	server.RegisterService(&serviceDesc, service)
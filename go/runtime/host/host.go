@@ -24,6 +24,10 @@ type Config struct {
 	// Extra is an optional provisioner-specific configuration.
 	Extra interface{}
 
+	// MemoryLimits are the soft limits on the live bytes of runtime memory subsystems, see
+	// protocol.MemorySubsystems for the subsystem names.
+	MemoryLimits map[string]uint64
+
 	// MessageHandler is the message handler for the Runtime Host Protocol messages.
 	MessageHandler protocol.Handler
 }
@@ -5,6 +5,7 @@ import (
 	"context"
 	"fmt"
 	"net"
+	"sort"
 	"sync"
 	"time"
 
@@ -80,7 +81,7 @@ type Connection interface {
 	// Only one of InitHost/InitGuest can be called otherwise the method may panic.
 	//
 	// Returns the self-reported runtime version.
-	InitHost(ctx context.Context, conn net.Conn) (*version.Version, error)
+	InitHost(ctx context.Context, conn net.Conn, hi *HostInfo) (*version.Version, error)
 
 	// InitGuest performs initialization in guest mode and transitions the connection to Ready
 	// state.
@@ -89,6 +90,13 @@ type Connection interface {
 	InitGuest(ctx context.Context, conn net.Conn) error
 }
 
+// HostInfo is the configuration the host passes to the runtime during initialization.
+type HostInfo struct {
+	// MemoryLimits are the soft limits on the live bytes of runtime memory subsystems, see
+	// MemorySubsystems for the subsystem names. Subsystems without a limit are not limited.
+	MemoryLimits map[string]uint64
+}
+
 // state is the connection state.
 type state uint8
 
@@ -337,6 +345,19 @@ func (c *connection) handleMessage(ctx context.Context, message *Message) {
 	switch message.MessageType {
 	case MessageRequest:
 		// Incoming request.
+		if message.Body.HostLogRequest != nil {
+			// Log records are accepted in any state.
+			c.logRecords(message.Body.HostLogRequest.Records)
+			_ = c.sendMessage(ctx, newResponseMessage(message, &Body{HostLogResponse: &Empty{}}))
+			return
+		}
+		if message.Body.HostPanicRequest != nil {
+			// Panics are reported in any state, the runtime aborts right after.
+			c.logPanic(&message.Body.HostPanicRequest.Report)
+			_ = c.sendMessage(ctx, newResponseMessage(message, &Body{HostPanicResponse: &Empty{}}))
+			return
+		}
+
 		var allowed bool
 		state := c.getState()
 		switch {
@@ -412,6 +433,42 @@ func (c *connection) handleMessage(ctx context.Context, message *Message) {
 	}
 }
 
+func (c *connection) logRecords(records []LogRecord) {
+	for _, record := range records {
+		keys := make([]string, 0, len(record.Fields))
+		for key := range record.Fields {
+			keys = append(keys, key)
+		}
+		sort.Strings(keys)
+
+		keyvals := []interface{}{"source", "runtime", "target", record.Target}
+		for _, key := range keys {
+			keyvals = append(keyvals, key, record.Fields[key])
+		}
+
+		switch record.Level {
+		case "error":
+			c.logger.Error(record.Message, keyvals...)
+		case "warn":
+			c.logger.Warn(record.Message, keyvals...)
+		case "info":
+			c.logger.Info(record.Message, keyvals...)
+		default:
+			c.logger.Debug(record.Message, keyvals...)
+		}
+	}
+}
+
+func (c *connection) logPanic(report *PanicReport) {
+	c.logger.Error("runtime panicked",
+		"source", "runtime",
+		"panic", report.Message,
+		"thread", report.Thread,
+		"location", fmt.Sprintf("%s:%d:%d", report.File, report.Line, report.Column),
+		"backtrace", report.Backtrace,
+	)
+}
+
 func (c *connection) workerIncoming() {
 	ctx, cancel := context.WithCancel(context.Background())
 	defer func() {
@@ -481,12 +538,19 @@ func (c *connection) InitGuest(ctx context.Context, conn net.Conn) error {
 }
 
 // Implements Connection.
-func (c *connection) InitHost(ctx context.Context, conn net.Conn) (*version.Version, error) {
+func (c *connection) InitHost(ctx context.Context, conn net.Conn, hi *HostInfo) (*version.Version, error) {
 	c.initConn(conn)
 
+	if hi == nil {
+		hi = &HostInfo{}
+	}
+
 	// Check Runtime Host Protocol version.
 	rsp, err := c.call(ctx, &Body{RuntimeInfoRequest: &RuntimeInfoRequest{
-		RuntimeID: c.runtimeID,
+		RuntimeID:           c.runtimeID,
+		ProtocolVersion:     version.RuntimeProtocol.ToU64(),
+		MemoryLimits:        hi.MemoryLimits,
+		CompressedWriteLogs: true,
 	}})
 	switch {
 	default:
@@ -60,12 +60,12 @@ func TestEchoRequestResponse(t *testing.T) {
 
 	err = protoA.InitGuest(context.Background(), connA)
 	require.NoError(err, "A.InitGuest()")
-	_, err = protoB.InitHost(context.Background(), connB)
+	_, err = protoB.InitHost(context.Background(), connB, nil)
 	require.NoError(err, "B.InitHost()")
 
-	require.Panics(func() { _, _ = protoA.InitHost(context.Background(), connA) }, "connection reinit should panic")
+	require.Panics(func() { _, _ = protoA.InitHost(context.Background(), connA, nil) }, "connection reinit should panic")
 	require.Panics(func() { _ = protoA.InitGuest(context.Background(), connA) }, "connection reinit should panic")
-	require.Panics(func() { _, _ = protoB.InitHost(context.Background(), connB) }, "connection reinit should panic")
+	require.Panics(func() { _, _ = protoB.InitHost(context.Background(), connB, nil) }, "connection reinit should panic")
 	require.Panics(func() { _ = protoB.InitGuest(context.Background(), connB) }, "connection reinit should panic")
 
 	reqA := Body{Empty: &Empty{}}
@@ -90,9 +90,9 @@ func TestEchoRequestResponse(t *testing.T) {
 	_, err = protoB.Call(context.Background(), &reqB)
 	require.Error(err, "B.Call() must error when connection is closed")
 
-	require.Panics(func() { _, _ = protoA.InitHost(context.Background(), connA) }, "connection reinit should panic")
+	require.Panics(func() { _, _ = protoA.InitHost(context.Background(), connA, nil) }, "connection reinit should panic")
 	require.Panics(func() { _ = protoA.InitGuest(context.Background(), connA) }, "connection reinit should panic")
-	require.Panics(func() { _, _ = protoB.InitHost(context.Background(), connB) }, "connection reinit should panic")
+	require.Panics(func() { _, _ = protoB.InitHost(context.Background(), connB, nil) }, "connection reinit should panic")
 	require.Panics(func() { _ = protoB.InitGuest(context.Background(), connB) }, "connection reinit should panic")
 }
 
@@ -111,7 +111,7 @@ func TestBigMessage(t *testing.T) {
 
 	err = protoA.InitGuest(context.Background(), connA)
 	require.NoError(err, "A.InitGuest()")
-	_, err = protoB.InitHost(context.Background(), connB)
+	_, err = protoB.InitHost(context.Background(), connB, nil)
 	require.NoError(err, "B.InitHost()")
 
 	rq := make([]byte, 2000000)
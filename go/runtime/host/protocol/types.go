@@ -77,18 +77,30 @@ type Body struct {
 	RuntimeExecuteTxBatchResponse         *RuntimeExecuteTxBatchResponse         `json:",omitempty"`
 	RuntimeAbortRequest                   *Empty                                 `json:",omitempty"`
 	RuntimeAbortResponse                  *Empty                                 `json:",omitempty"`
+	RuntimeHealthRequest                  *Empty                                 `json:",omitempty"`
+	RuntimeHealthResponse                 *RuntimeHealthResponse                 `json:",omitempty"`
 
 	// Host interface.
-	HostKeyManagerPolicyRequest  *HostKeyManagerPolicyRequest  `json:",omitempty"`
-	HostKeyManagerPolicyResponse *HostKeyManagerPolicyResponse `json:",omitempty"`
-	HostRPCCallRequest           *HostRPCCallRequest           `json:",omitempty"`
-	HostRPCCallResponse          *HostRPCCallResponse          `json:",omitempty"`
-	HostStorageSyncRequest       *HostStorageSyncRequest       `json:",omitempty"`
-	HostStorageSyncResponse      *HostStorageSyncResponse      `json:",omitempty"`
-	HostLocalStorageGetRequest   *HostLocalStorageGetRequest   `json:",omitempty"`
-	HostLocalStorageGetResponse  *HostLocalStorageGetResponse  `json:",omitempty"`
-	HostLocalStorageSetRequest   *HostLocalStorageSetRequest   `json:",omitempty"`
-	HostLocalStorageSetResponse  *Empty                        `json:",omitempty"`
+	HostKeyManagerPolicyRequest    *HostKeyManagerPolicyRequest   `json:",omitempty"`
+	HostKeyManagerPolicyResponse   *HostKeyManagerPolicyResponse  `json:",omitempty"`
+	HostRPCCallRequest             *HostRPCCallRequest            `json:",omitempty"`
+	HostRPCCallResponse            *HostRPCCallResponse           `json:",omitempty"`
+	HostStorageSyncRequest         *HostStorageSyncRequest        `json:",omitempty"`
+	HostStorageSyncResponse        *HostStorageSyncResponse       `json:",omitempty"`
+	HostLocalStorageGetRequest     *HostLocalStorageGetRequest    `json:",omitempty"`
+	HostLocalStorageGetResponse    *HostLocalStorageGetResponse   `json:",omitempty"`
+	HostLocalStorageSetRequest     *HostLocalStorageSetRequest    `json:",omitempty"`
+	HostLocalStorageSetResponse    *Empty                         `json:",omitempty"`
+	HostSpillStorageGetRequest     *HostSpillStorageGetRequest    `json:",omitempty"`
+	HostSpillStorageGetResponse    *HostSpillStorageGetResponse   `json:",omitempty"`
+	HostSpillStorageInsertRequest  *HostSpillStorageInsertRequest `json:",omitempty"`
+	HostSpillStorageInsertResponse *Empty                         `json:",omitempty"`
+	HostSpillStorageClearRequest   *Empty                         `json:",omitempty"`
+	HostSpillStorageClearResponse  *Empty                         `json:",omitempty"`
+	HostLogRequest                 *HostLogRequest                `json:",omitempty"`
+	HostLogResponse                *Empty                         `json:",omitempty"`
+	HostPanicRequest               *HostPanicRequest              `json:",omitempty"`
+	HostPanicResponse              *Empty                         `json:",omitempty"`
 }
 
 // Type returns the message type by determining the name of the first non-nil member.
@@ -117,6 +129,16 @@ type Error struct {
 type RuntimeInfoRequest struct {
 	// RuntimeID is the assigned runtime ID of the loaded runtime.
 	RuntimeID common.Namespace `json:"runtime_id"`
+
+	// ProtocolVersion is the runtime protocol version supported by the host.
+	ProtocolVersion uint64 `json:"protocol_version"`
+
+	// MemoryLimits are the soft limits on the live bytes of runtime memory subsystems.
+	MemoryLimits map[string]uint64 `json:"memory_limits,omitempty"`
+
+	// CompressedWriteLogs signals that the host accepts compressed write logs in
+	// computed batches.
+	CompressedWriteLogs bool `json:"compressed_write_logs,omitempty"`
 }
 
 // RuntimeInfoResponse is a worker info response message body.
@@ -200,6 +222,14 @@ type ComputedBatch struct {
 	IOWriteLog storage.WriteLog `json:"io_write_log"`
 	// Batch of storage write operations.
 	StateWriteLog storage.WriteLog `json:"state_write_log"`
+	// Compressed log that generates the I/O tree, used instead of IOWriteLog
+	// if set. Use WriteLogs to obtain the write logs.
+	CompressedIOWriteLog *CompressedWriteLog `json:"compressed_io_write_log,omitempty"`
+	// Compressed batch of storage write operations, used instead of
+	// StateWriteLog if set. Use WriteLogs to obtain the write logs.
+	CompressedStateWriteLog *CompressedWriteLog `json:"compressed_state_write_log,omitempty"`
+	// Pruning hints for ephemeral state.
+	PruneHints []storage.PruneHint `json:"prune_hints,omitempty"`
 	// If this runtime uses a TEE, then this is the signature of Header with
 	// node's RAK for this runtime.
 	RakSig signature.RawSignature `json:"rak_sig"`
@@ -219,11 +249,86 @@ type RuntimeExecuteTxBatchRequest struct {
 	Inputs transaction.RawBatch `json:"inputs"`
 	// Block on which the batch computation should be based.
 	Block roothash.Block `json:"block"`
+	// Beacon is the random beacon at the consensus height of the block.
+	Beacon []byte `json:"beacon,omitempty"`
 }
 
 // RuntimeExecuteTxBatchResponse is a worker execute tx batch response message body.
 type RuntimeExecuteTxBatchResponse struct {
 	Batch ComputedBatch `json:"batch"`
+
+	// StateMetrics are the state tree counters accumulated since the previous response.
+	StateMetrics StateMetrics `json:"state_metrics"`
+}
+
+// StateMetrics are the runtime's state tree cache and sync counters.
+type StateMetrics struct {
+	// Hits is the number of node dereferences served from the cache.
+	Hits uint64 `json:"hits"`
+	// SpillHits is the number of node dereferences served from the spill storage.
+	SpillHits uint64 `json:"spill_hits"`
+	// Misses is the number of node dereferences which required a remote sync.
+	Misses uint64 `json:"misses"`
+	// SyncRoundTrips is the number of round trips made to the read syncer.
+	SyncRoundTrips uint64 `json:"sync_round_trips"`
+	// NodesFetched is the number of nodes fetched from the read syncer.
+	NodesFetched uint64 `json:"nodes_fetched"`
+	// BytesSynced is the total size of proofs received from the read syncer.
+	BytesSynced uint64 `json:"bytes_synced"`
+	// NodesSpilled is the number of nodes written to the spill storage.
+	NodesSpilled uint64 `json:"nodes_spilled"`
+	// Commits is the number of commits.
+	Commits uint64 `json:"commits"`
+	// WriteLogEntries is the total number of write log entries produced by commits.
+	WriteLogEntries uint64 `json:"write_log_entries"`
+	// WriteLogBytes is the total size of keys and values in write logs produced by commits.
+	WriteLogBytes uint64 `json:"write_log_bytes"`
+}
+
+// RuntimeHealthResponse is a runtime health response message body.
+type RuntimeHealthResponse struct {
+	Memory MemoryStats `json:"memory"`
+}
+
+// MemoryStats are the runtime's memory statistics.
+type MemoryStats struct {
+	// Accounting is true iff the runtime accounts its allocations.
+	Accounting bool `json:"accounting"`
+
+	// Subsystems are the statistics by subsystem.
+	Subsystems map[string]SubsystemMemoryStats `json:"subsystems"`
+}
+
+// Runtime memory subsystems.
+const (
+	// MemorySubsystemOther are allocations which are not attributed to any particular subsystem.
+	MemorySubsystemOther = "other"
+	// MemorySubsystemMkvsCache is the MKVS node cache.
+	MemorySubsystemMkvsCache = "mkvs_cache"
+	// MemorySubsystemRPCBuffers are the EnclaveRPC session state and frame buffers.
+	MemorySubsystemRPCBuffers = "rpc_buffers"
+	// MemorySubsystemKeyCache is the key manager client key cache.
+	MemorySubsystemKeyCache = "key_cache"
+)
+
+// MemorySubsystems are all runtime memory subsystems.
+var MemorySubsystems = []string{
+	MemorySubsystemOther,
+	MemorySubsystemMkvsCache,
+	MemorySubsystemRPCBuffers,
+	MemorySubsystemKeyCache,
+}
+
+// SubsystemMemoryStats are the memory statistics of a runtime subsystem.
+type SubsystemMemoryStats struct {
+	// LiveBytes is the number of live bytes.
+	LiveBytes uint64 `json:"live_bytes"`
+
+	// LiveAllocations is the number of live allocations.
+	LiveAllocations uint64 `json:"live_allocations"`
+
+	// Limit is the soft limit on the number of live bytes, if any.
+	Limit *uint64 `json:"limit,omitempty"`
 }
 
 // HostKeyManagerPolicyRequest is a host key manager policy request message body.
@@ -273,3 +378,53 @@ type HostLocalStorageSetRequest struct {
 	Key   []byte `json:"key"`
 	Value []byte `json:"value"`
 }
+
+// HostSpillStorageGetRequest is a host spill storage get request message body.
+type HostSpillStorageGetRequest struct {
+	Key []byte `json:"key"`
+}
+
+// HostSpillStorageGetResponse is a host spill storage get response message body.
+type HostSpillStorageGetResponse struct {
+	Value []byte `json:"value"`
+}
+
+// HostSpillStorageInsertRequest is a host spill storage insert request message body.
+type HostSpillStorageInsertRequest struct {
+	Entries []SpillStorageEntry `json:"entries"`
+}
+
+// SpillStorageEntry is an entry of the host spill storage.
+type SpillStorageEntry struct {
+	Key   []byte `json:"key"`
+	Value []byte `json:"value"`
+}
+
+// HostLogRequest is a host log request message body.
+type HostLogRequest struct {
+	Records []LogRecord `json:"records"`
+}
+
+// LogRecord is a log record emitted by the runtime.
+type LogRecord struct {
+	Level   string            `json:"level"`
+	Target  string            `json:"target"`
+	Message string            `json:"message"`
+	Fields  map[string]string `json:"fields,omitempty"`
+}
+
+// HostPanicRequest is a host panic request message body, sent by the
+// runtime right before it aborts due to a panic.
+type HostPanicRequest struct {
+	Report PanicReport `json:"report"`
+}
+
+// PanicReport is a report of a runtime panic.
+type PanicReport struct {
+	Message   string `json:"message"`
+	Thread    string `json:"thread"`
+	File      string `json:"file"`
+	Line      uint32 `json:"line"`
+	Column    uint32 `json:"column"`
+	Backtrace string `json:"backtrace"`
+}
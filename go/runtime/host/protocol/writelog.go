@@ -0,0 +1,78 @@
+package protocol
+
+import (
+	"errors"
+
+	storage "github.com/oasislabs/oasis-core/go/storage/api"
+)
+
+// ErrMalformedWriteLog is the error returned when a compressed write log is malformed.
+var ErrMalformedWriteLog = errors.New("rhp: malformed compressed write log")
+
+// CompressedLogEntry is an entry in the compressed write log.
+type CompressedLogEntry struct {
+	// Shared is the length of the prefix shared with the previous entry's key.
+	Shared uint32 `json:"shared"`
+	// Suffix is the remainder of the key after the shared prefix.
+	Suffix []byte `json:"suffix"`
+	// Value is the index into the value table (nil if the key was deleted).
+	Value *uint32 `json:"value"`
+}
+
+// CompressedWriteLog is a compact representation of a write log.
+//
+// Entries are sorted by key and deduplicated, keys are prefix-compressed
+// against the previous key and identical values are only stored once.
+type CompressedWriteLog struct {
+	// Values are the unique values referenced by the entries.
+	Values [][]byte `json:"values"`
+	// Entries are the compressed write log entries.
+	Entries []CompressedLogEntry `json:"entries"`
+}
+
+// Decompress decompresses the write log.
+func (c *CompressedWriteLog) Decompress() (storage.WriteLog, error) {
+	writeLog := make(storage.WriteLog, 0, len(c.Entries))
+	var previousKey []byte
+	for _, entry := range c.Entries {
+		if int(entry.Shared) > len(previousKey) {
+			return nil, ErrMalformedWriteLog
+		}
+
+		key := append(append([]byte{}, previousKey[:entry.Shared]...), entry.Suffix...)
+
+		var value []byte
+		if entry.Value != nil {
+			if int(*entry.Value) >= len(c.Values) {
+				return nil, ErrMalformedWriteLog
+			}
+			// Inserted values must be non-nil to be distinguished from deletions.
+			value = append([]byte{}, c.Values[*entry.Value]...)
+		}
+
+		previousKey = key
+		writeLog = append(writeLog, storage.LogEntry{Key: key, Value: value})
+	}
+
+	return writeLog, nil
+}
+
+// WriteLogs returns the I/O and state write logs of the computed batch,
+// decompressing them if needed.
+func (b *ComputedBatch) WriteLogs() (storage.WriteLog, storage.WriteLog, error) {
+	ioWriteLog, stateWriteLog := b.IOWriteLog, b.StateWriteLog
+
+	var err error
+	if b.CompressedIOWriteLog != nil {
+		if ioWriteLog, err = b.CompressedIOWriteLog.Decompress(); err != nil {
+			return nil, nil, err
+		}
+	}
+	if b.CompressedStateWriteLog != nil {
+		if stateWriteLog, err = b.CompressedStateWriteLog.Decompress(); err != nil {
+			return nil, nil, err
+		}
+	}
+
+	return ioWriteLog, stateWriteLog, nil
+}
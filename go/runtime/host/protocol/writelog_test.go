@@ -0,0 +1,100 @@
+package protocol
+
+import (
+	"testing"
+
+	"github.com/stretchr/testify/require"
+
+	"github.com/oasislabs/oasis-core/go/common/cbor"
+	storage "github.com/oasislabs/oasis-core/go/storage/api"
+)
+
+func valueIndex(i uint32) *uint32 {
+	return &i
+}
+
+func TestCompressedWriteLog(t *testing.T) {
+	require := require.New(t)
+
+	compressed := CompressedWriteLog{
+		Values: [][]byte{[]byte("same value"), []byte("other value"), {}},
+		Entries: []CompressedLogEntry{
+			{Shared: 0, Suffix: []byte("another key"), Value: nil},
+			{Shared: 0, Suffix: []byte("key 1"), Value: valueIndex(0)},
+			{Shared: 4, Suffix: []byte("2"), Value: valueIndex(0)},
+			{Shared: 4, Suffix: []byte("3"), Value: valueIndex(1)},
+			{Shared: 3, Suffix: []byte(" 4"), Value: valueIndex(2)},
+		},
+	}
+
+	var decoded CompressedWriteLog
+	err := cbor.Unmarshal(cbor.Marshal(compressed), &decoded)
+	require.NoError(err, "Unmarshal")
+
+	writeLog, err := decoded.Decompress()
+	require.NoError(err, "Decompress")
+	require.EqualValues(storage.WriteLog{
+		{Key: []byte("another key"), Value: nil},
+		{Key: []byte("key 1"), Value: []byte("same value")},
+		{Key: []byte("key 2"), Value: []byte("same value")},
+		{Key: []byte("key 3"), Value: []byte("other value")},
+		{Key: []byte("key 4"), Value: []byte{}},
+	}, writeLog, "Decompress")
+	require.Nil(writeLog[0].Value, "deletions should have a nil value")
+	require.NotNil(writeLog[4].Value, "empty values should not be deletions")
+
+	// Shared prefix longer than the previous key.
+	malformed := CompressedWriteLog{
+		Entries: []CompressedLogEntry{
+			{Shared: 1, Suffix: []byte("key"), Value: nil},
+		},
+	}
+	_, err = malformed.Decompress()
+	require.Equal(ErrMalformedWriteLog, err, "Decompress should reject out of range prefix")
+
+	// Value index out of range.
+	malformed = CompressedWriteLog{
+		Entries: []CompressedLogEntry{
+			{Shared: 0, Suffix: []byte("key"), Value: valueIndex(0)},
+		},
+	}
+	_, err = malformed.Decompress()
+	require.Equal(ErrMalformedWriteLog, err, "Decompress should reject out of range value index")
+}
+
+func TestComputedBatchWriteLogs(t *testing.T) {
+	require := require.New(t)
+
+	plain := storage.WriteLog{{Key: []byte("plain"), Value: []byte("value")}}
+	batch := ComputedBatch{
+		IOWriteLog:    plain,
+		StateWriteLog: plain,
+	}
+	ioWriteLog, stateWriteLog, err := batch.WriteLogs()
+	require.NoError(err, "WriteLogs")
+	require.EqualValues(plain, ioWriteLog, "uncompressed I/O write log")
+	require.EqualValues(plain, stateWriteLog, "uncompressed state write log")
+
+	// Compressed write logs take precedence.
+	batch.CompressedStateWriteLog = &CompressedWriteLog{
+		Values: [][]byte{[]byte("value")},
+		Entries: []CompressedLogEntry{
+			{Shared: 0, Suffix: []byte("compressed"), Value: valueIndex(0)},
+		},
+	}
+	ioWriteLog, stateWriteLog, err = batch.WriteLogs()
+	require.NoError(err, "WriteLogs")
+	require.EqualValues(plain, ioWriteLog, "uncompressed I/O write log")
+	require.EqualValues(storage.WriteLog{
+		{Key: []byte("compressed"), Value: []byte("value")},
+	}, stateWriteLog, "compressed state write log")
+
+	// Malformed compressed write logs should be rejected.
+	batch.CompressedIOWriteLog = &CompressedWriteLog{
+		Entries: []CompressedLogEntry{
+			{Shared: 0, Suffix: []byte("key"), Value: valueIndex(1)},
+		},
+	}
+	_, _, err = batch.WriteLogs()
+	require.Equal(ErrMalformedWriteLog, err, "WriteLogs should reject malformed write logs")
+}
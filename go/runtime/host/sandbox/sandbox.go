@@ -301,7 +301,10 @@ func (r *sandboxedRuntime) startProcess() (err error) {
 	var rtVersion *version.Version
 	initCtx, cancel := context.WithTimeout(ctx, runtimeInitTimeout)
 	defer cancel()
-	if rtVersion, err = pc.InitHost(initCtx, conn); err != nil {
+	hi := &protocol.HostInfo{
+		MemoryLimits: r.rtCfg.MemoryLimits,
+	}
+	if rtVersion, err = pc.InitHost(initCtx, conn, hi); err != nil {
 		return fmt.Errorf("failed to initialize connection: %w", err)
 	}
 
@@ -125,6 +125,12 @@ func testBasic(t *testing.T, cfg host.Config, p host.Provisioner) {
 	require.NoError(err, "Call")
 	require.NotNil(rsp.Empty, "runtime response to RuntimePingRequest should return an Empty body")
 
+	// Test the health request.
+	rsp, err = r.Call(ctx, &protocol.Body{RuntimeHealthRequest: &protocol.Empty{}})
+	require.NoError(err, "Call")
+	require.NotNil(rsp.RuntimeHealthResponse, "runtime response to RuntimeHealthRequest should return a RuntimeHealthResponse body")
+	require.Len(rsp.RuntimeHealthResponse.Memory.Subsystems, 4, "runtime should report memory statistics of all subsystems")
+
 	// Request the runtime to stop.
 	r.Stop()
 
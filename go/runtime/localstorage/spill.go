@@ -0,0 +1,89 @@
+package localstorage
+
+import (
+	"sync"
+
+	"github.com/oasislabs/oasis-core/go/common/cache/lru"
+	"github.com/oasislabs/oasis-core/go/common/cbor"
+)
+
+// DefaultSpillStorageCapacity is the default capacity of the spill storage
+// in bytes.
+const DefaultSpillStorageCapacity = 64 * 1024 * 1024
+
+var _ SpillStorage = (*spillStorage)(nil)
+
+// SpillStorage is the untrusted storage that runtimes spill nodes evicted
+// from their in-memory state cache to.
+//
+// Spill storage is separate from the runtime's local storage. It is held in
+// memory and bounded in size. As all entries can be refetched by the runtime,
+// the least recently used entries are silently dropped once full.
+type SpillStorage interface {
+	// Get retrieves a previously stored value under the given key, or an
+	// empty value if there is no such value.
+	Get(key []byte) []byte
+
+	// Set sets a key to a specific value.
+	Set(key, value []byte)
+
+	// Clear removes all entries.
+	Clear()
+}
+
+type spillValue []byte
+
+func (v spillValue) Size() uint64 {
+	return uint64(len(v))
+}
+
+type spillStorage struct {
+	sync.Mutex
+
+	capacity uint64
+	cache    *lru.Cache
+}
+
+func (s *spillStorage) Get(key []byte) []byte {
+	s.Lock()
+	defer s.Unlock()
+
+	value, ok := s.cache.Get(string(key))
+	if !ok {
+		return cbor.FixSliceForSerde(nil)
+	}
+	return cbor.FixSliceForSerde(append([]byte{}, value.(spillValue)...))
+}
+
+func (s *spillStorage) Set(key, value []byte) {
+	s.Lock()
+	defer s.Unlock()
+
+	// Values which do not fit are simply not stored.
+	_ = s.cache.Put(string(key), spillValue(append([]byte{}, value...)))
+}
+
+func (s *spillStorage) Clear() {
+	s.Lock()
+	defer s.Unlock()
+
+	s.cache = newSpillCache(s.capacity)
+}
+
+func newSpillCache(capacity uint64) *lru.Cache {
+	cache, err := lru.New(lru.Capacity(capacity, true))
+	if err != nil {
+		// Capacity is the only option used, which cannot fail.
+		panic(err)
+	}
+	return cache
+}
+
+// NewSpillStorage creates new spill storage holding at most the given number
+// of bytes.
+func NewSpillStorage(capacity uint64) SpillStorage {
+	return &spillStorage{
+		capacity: capacity,
+		cache:    newSpillCache(capacity),
+	}
+}
@@ -0,0 +1,32 @@
+package localstorage
+
+import (
+	"fmt"
+	"testing"
+
+	"github.com/stretchr/testify/require"
+)
+
+func TestSpillStorage(t *testing.T) {
+	require := require.New(t)
+
+	s := NewSpillStorage(100)
+	require.Empty(s.Get([]byte("missing")), "Get should return an empty value for missing keys")
+
+	s.Set([]byte("key"), []byte("value"))
+	require.EqualValues([]byte("value"), s.Get([]byte("key")), "Get")
+
+	// Filling the storage should drop the least recently used entries.
+	for i := 0; i < 20; i++ {
+		s.Set([]byte(fmt.Sprintf("key %d", i)), []byte("0123456789"))
+	}
+	require.Empty(s.Get([]byte("key")), "least recently used entry should be dropped")
+	require.EqualValues([]byte("0123456789"), s.Get([]byte("key 19")), "Get")
+
+	// Values larger than the capacity should not be stored.
+	s.Set([]byte("large"), make([]byte, 101))
+	require.Empty(s.Get([]byte("large")), "too large value should not be stored")
+
+	s.Clear()
+	require.Empty(s.Get([]byte("key 19")), "Clear should remove all entries")
+}
@@ -76,6 +76,9 @@ type Runtime interface {
 
 	// LocalStorage returns the per-runtime local storage.
 	LocalStorage() localstorage.LocalStorage
+
+	// SpillStorage returns the per-runtime spill storage.
+	SpillStorage() localstorage.SpillStorage
 }
 
 type runtime struct {
@@ -87,6 +90,7 @@ type runtime struct {
 	consensus    consensus.Backend
 	storage      storageAPI.Backend
 	localStorage localstorage.LocalStorage
+	spillStorage localstorage.SpillStorage
 
 	history    history.History
 	tagIndexer *tagindexer.Service
@@ -140,6 +144,10 @@ func (r *runtime) LocalStorage() localstorage.LocalStorage {
 	return r.localStorage
 }
 
+func (r *runtime) SpillStorage() localstorage.SpillStorage {
+	return r.spillStorage
+}
+
 func (r *runtime) stop() {
 	// Stop watching runtime updates.
 	r.cancelCtx()
@@ -295,6 +303,7 @@ func (r *runtimeRegistry) addSupportedRuntime(ctx context.Context, id common.Nam
 
 	rt.storage = storageBackend
 	rt.localStorage = localStorage
+	rt.spillStorage = localstorage.NewSpillStorage(localstorage.DefaultSpillStorageCapacity)
 	rt.history = history
 	rt.tagIndexer = tagIndexer
 	r.runtimes[id] = rt
@@ -238,6 +238,22 @@ type ApplyOp struct {
 	DstRoot hash.Hash `json:"dst_root"`
 	// WriteLog is a write log of operations to apply.
 	WriteLog WriteLog `json:"writelog"`
+	// PruneHints are the pruning hints emitted by the runtime for the
+	// resulting root.
+	PruneHints []PruneHint `json:"prune_hints,omitempty"`
+}
+
+// PruneHint is a pruning hint for keys under a given prefix.
+//
+// Runtimes emit pruning hints for ephemeral data, so that storage nodes know
+// for how many rounds historical versions of the affected keys must be
+// retained before they may be pruned.
+type PruneHint struct {
+	// Prefix is the prefix of the prunable keys.
+	Prefix []byte `json:"prefix"`
+	// RetainRounds is the number of rounds for which historical versions
+	// must be retained.
+	RetainRounds uint64 `json:"retain_rounds"`
 }
 
 // MergeOps is a merge operation within a batch of merge operations.
@@ -30,7 +30,7 @@ func (t *tree) SyncIterate(ctx context.Context, request *syncer.IterateRequest)
 	// root as an iterator may encompass many subtrees. Make sure to propagate
 	// prefetching to any upstream remote syncers.
 	it := t.NewIterator(ctx,
-		WithProof(request.Tree.Root.Hash),
+		withRequestProof(request.Tree.Root.Hash, request.Compressed),
 		IteratorPrefetch(request.Prefetch),
 	)
 	defer it.Close()
@@ -154,6 +154,21 @@ func WithProof(root hash.Hash) IteratorOption {
 	}
 }
 
+// WithCompressedProof configures the iterator for generating proofs of all
+// visited nodes which use prefix compression.
+func WithCompressedProof(root hash.Hash) IteratorOption {
+	return func(it Iterator) {
+		it.(*treeIterator).proofBuilder = syncer.NewCompressedProofBuilder(root)
+	}
+}
+
+func withRequestProof(root hash.Hash, compressed bool) IteratorOption {
+	if compressed {
+		return WithCompressedProof(root)
+	}
+	return WithProof(root)
+}
+
 func newTreeIterator(ctx context.Context, tree *tree, options ...IteratorOption) Iterator {
 	it := &treeIterator{
 		ctx:  ctx,
@@ -253,7 +268,7 @@ func (it *treeIterator) doNext(ptr *node.Pointer, bitDepth node.Depth, path node
 	if pb := it.proofBuilder; pb != nil && ptr != nil {
 		proofRoot := pb.GetRoot()
 		if pb.HasRoot() || proofRoot.Equal(&ptr.Hash) {
-			pb.Include(nd)
+			pb.Include(nd, bitDepth)
 		}
 	}
 
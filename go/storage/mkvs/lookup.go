@@ -49,6 +49,9 @@ func (t *tree) SyncGet(ctx context.Context, request *syncer.GetRequest) (*syncer
 	t.cache.markPosition()
 
 	pb := syncer.NewProofBuilder(request.Tree.Position)
+	if request.Compressed {
+		pb = syncer.NewCompressedProofBuilder(request.Tree.Position)
+	}
 	opts := doGetOptions{
 		proofBuilder:    pb,
 		includeSiblings: request.IncludeSiblings,
@@ -110,7 +113,7 @@ func (t *tree) doGet(
 	if pb := opts.proofBuilder; pb != nil && ptr != nil {
 		proofRoot := pb.GetRoot()
 		if pb.HasRoot() || proofRoot.Equal(&ptr.Hash) {
-			pb.Include(nd)
+			pb.Include(nd, bitDepth)
 		}
 	}
 
@@ -369,12 +369,31 @@ func (n *InternalNode) ExtractUnchecked() Node {
 // CompactMarshalBinary encodes an internal node into binary form without
 // any hash pointers (e.g., for proofs).
 func (n *InternalNode) CompactMarshalBinary() (data []byte, err error) {
+	return n.compactMarshalBinary(false)
+}
+
+// CompressedMarshalBinary encodes an internal node into binary form without
+// any hash pointers using prefix compression (e.g., for compressed proofs).
+//
+// The key of the contained leaf node is omitted as it is equal to the path
+// of the internal node.
+func (n *InternalNode) CompressedMarshalBinary() (data []byte, err error) {
+	return n.compactMarshalBinary(true)
+}
+
+func (n *InternalNode) compactMarshalBinary(compressed bool) (data []byte, err error) {
 	// Internal node's LeafNode is always marshaled along the internal node.
 	var leafNodeBinary []byte
-	if n.LeafNode == nil {
+	switch {
+	case n.LeafNode == nil:
 		leafNodeBinary = make([]byte, 1)
 		leafNodeBinary[0] = PrefixNilNode
-	} else {
+	case compressed:
+		leafNode := n.LeafNode.Node.(*LeafNode)
+		if leafNodeBinary, err = leafNode.marshalBinary(len(leafNode.Key)); err != nil {
+			return nil, fmt.Errorf("mkvs: failed to marshal leaf node: %w", err)
+		}
+	default:
 		if leafNodeBinary, err = n.LeafNode.Node.MarshalBinary(); err != nil {
 			return nil, fmt.Errorf("mkvs: failed to marshal leaf node: %w", err)
 		}
@@ -580,7 +599,24 @@ func (n *LeafNode) ExtractUnchecked() Node {
 
 // CompactMarshalBinary encodes a leaf node into binary form.
 func (n *LeafNode) CompactMarshalBinary() (data []byte, err error) {
-	keyData, err := n.Key.MarshalBinary()
+	return n.marshalBinary(0)
+}
+
+// CompressedMarshalBinary encodes a leaf node at the given bit depth into
+// binary form using prefix compression (e.g., for compressed proofs).
+//
+// The bytes of the key which are shared with the path leading to the node
+// are omitted.
+func (n *LeafNode) CompressedMarshalBinary(bitDepth Depth) (data []byte, err error) {
+	skip := int(bitDepth) / 8
+	if skip > len(n.Key) {
+		skip = len(n.Key)
+	}
+	return n.marshalBinary(skip)
+}
+
+func (n *LeafNode) marshalBinary(skip int) (data []byte, err error) {
+	keyData, err := n.Key[skip:].MarshalBinary()
 	if err != nil {
 		return nil, err
 	}
@@ -72,7 +72,7 @@ func (t *tree) SyncGetPrefixes(ctx context.Context, request *syncer.GetPrefixesR
 		}
 	}
 
-	it := t.NewIterator(ctx, WithProof(request.Tree.Root.Hash))
+	it := t.NewIterator(ctx, withRequestProof(request.Tree.Root.Hash, request.Compressed))
 	defer it.Close()
 
 	var total int
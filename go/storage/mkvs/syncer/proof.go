@@ -14,6 +14,9 @@ const (
 	proofEntryFull byte = 0x01
 	// proofEntryHash is the proof entry type for subtree hashes.
 	proofEntryHash byte = 0x02
+	// proofEntryCompressed is the proof entry type for full nodes using
+	// prefix compression.
+	proofEntryCompressed byte = 0x03
 )
 
 // Proof is a Merkle proof for a subtree.
@@ -33,9 +36,10 @@ type proofNode struct {
 
 // ProofBuilder is a Merkle proof builder.
 type ProofBuilder struct {
-	root     hash.Hash
-	included map[hash.Hash]*proofNode
-	size     uint64
+	root       hash.Hash
+	included   map[hash.Hash]*proofNode
+	size       uint64
+	compressed bool
 }
 
 // NewProofBuilder creates a new Merkle proof builder for the given root.
@@ -46,10 +50,22 @@ func NewProofBuilder(root hash.Hash) *ProofBuilder {
 	}
 }
 
-// Include adds a node to the set of included nodes.
+// NewCompressedProofBuilder creates a new Merkle proof builder for the given
+// root which encodes nodes using prefix compression.
+//
+// Compressed proofs are smaller when keys share long prefixes, but can only
+// be verified by verifiers that support compressed entries.
+func NewCompressedProofBuilder(root hash.Hash) *ProofBuilder {
+	b := NewProofBuilder(root)
+	b.compressed = true
+	return b
+}
+
+// Include adds a node located at the given bit depth to the set of included
+// nodes.
 //
 // The node must be clean.
-func (b *ProofBuilder) Include(n node.Node) {
+func (b *ProofBuilder) Include(n node.Node, bitDepth node.Depth) {
 	if n == nil {
 		return
 	}
@@ -66,7 +82,20 @@ func (b *ProofBuilder) Include(n node.Node) {
 	// Node is available, serialize it.
 	var err error
 	var pn proofNode
-	pn.serialized, err = n.CompactMarshalBinary()
+	switch nd := n.(type) {
+	case *node.InternalNode:
+		if b.compressed {
+			pn.serialized, err = nd.CompressedMarshalBinary()
+		} else {
+			pn.serialized, err = nd.CompactMarshalBinary()
+		}
+	case *node.LeafNode:
+		if b.compressed {
+			pn.serialized, err = nd.CompressedMarshalBinary(bitDepth)
+		} else {
+			pn.serialized, err = nd.CompactMarshalBinary()
+		}
+	}
 	if err != nil {
 		panic(err)
 	}
@@ -142,7 +171,11 @@ func (b *ProofBuilder) build(ctx context.Context, proof *Proof, h hash.Hash) err
 	}
 
 	// Pre-order traversal, add visited node.
-	proof.Entries = append(proof.Entries, append([]byte{proofEntryFull}, n.serialized...))
+	entryType := proofEntryFull
+	if b.compressed {
+		entryType = proofEntryCompressed
+	}
+	proof.Entries = append(proof.Entries, append([]byte{entryType}, n.serialized...))
 
 	// And then add any children.
 	for _, childHash := range n.children {
@@ -34,6 +34,8 @@ type GetRequest struct {
 	Tree            TreeID `json:"tree"`
 	Key             []byte `json:"key"`
 	IncludeSiblings bool   `json:"include_siblings,omitempty"`
+	// Compressed requests a proof which uses prefix compression.
+	Compressed bool `json:"compressed,omitempty"`
 }
 
 // GetPrefixesRequest is a request for the SyncGetPrefixes operation.
@@ -41,6 +43,8 @@ type GetPrefixesRequest struct {
 	Tree     TreeID   `json:"tree"`
 	Prefixes [][]byte `json:"prefixes"`
 	Limit    uint16   `json:"limit"`
+	// Compressed requests a proof which uses prefix compression.
+	Compressed bool `json:"compressed,omitempty"`
 }
 
 // IterateRequest is a request for the SyncIterate operation.
@@ -48,6 +52,8 @@ type IterateRequest struct {
 	Tree     TreeID `json:"tree"`
 	Key      []byte `json:"key"`
 	Prefetch uint16 `json:"prefetch"`
+	// Compressed requests a proof which uses prefix compression.
+	Compressed bool `json:"compressed,omitempty"`
 }
 
 // ProofResponse is a response for requests that produce proofs.
@@ -39,11 +39,11 @@ func TestProof(t *testing.T) {
 	require.NoError(err, "Build should not fail without a root present")
 
 	// Including a nil node should not panic.
-	builder.Include(nil)
+	builder.Include(nil, 0)
 
 	// Include root node.
 	rootNode := tree.cache.pendingRoot.Node
-	builder.Include(rootNode)
+	builder.Include(rootNode, 0)
 	require.True(builder.HasRoot(), "HasRoot should return true after root included")
 
 	proof, err := builder.Build(ctx)
@@ -54,7 +54,7 @@ func TestProof(t *testing.T) {
 	// Include root.left node.
 	rootIntNode := rootNode.(*node.InternalNode)
 	leftNode1 := rootIntNode.Left.Node
-	builder.Include(leftNode1)
+	builder.Include(leftNode1, rootIntNode.LabelBitLength)
 
 	proof, err = builder.Build(ctx)
 	require.NoError(err, "Build should not fail")
@@ -157,6 +157,60 @@ func TestProof(t *testing.T) {
 	require.Error(err, "VerifyProof should fail with invalid proof")
 }
 
+func TestCompressedProof(t *testing.T) {
+	require := require.New(t)
+
+	ctx := context.Background()
+	keys, values := generateKeyValuePairsEx("runtime/state/some/deep/namespace/", 100)
+	var ns common.Namespace
+
+	tree := New(nil, nil).(*tree)
+	for i, key := range keys {
+		err := tree.Insert(ctx, key, values[i])
+		require.NoError(err, "Insert")
+	}
+	_, rootHash, err := tree.Commit(ctx, ns, 0)
+	require.NoError(err, "Commit")
+
+	proofSize := func(proof *syncer.Proof) (size int) {
+		for _, entry := range proof.Entries {
+			size += len(entry)
+		}
+		return
+	}
+
+	root := node.Root{Namespace: ns, Version: 0, Hash: rootHash}
+	for _, key := range keys {
+		request := &syncer.GetRequest{
+			Tree: syncer.TreeID{
+				Root:     root,
+				Position: rootHash,
+			},
+			Key: key,
+		}
+		var full, compressed *syncer.ProofResponse
+		full, err = tree.SyncGet(ctx, request)
+		require.NoError(err, "SyncGet")
+
+		request.Compressed = true
+		compressed, err = tree.SyncGet(ctx, request)
+		require.NoError(err, "SyncGet")
+
+		require.Len(compressed.Proof.Entries, len(full.Proof.Entries), "compressed proof should have the same entries")
+		for i, entry := range compressed.Proof.Entries {
+			if entry == nil {
+				continue
+			}
+			if full.Proof.Entries[i][0] == 0x01 {
+				require.EqualValues(0x03, entry[0], "full nodes should use compressed entries")
+			} else {
+				require.EqualValues(full.Proof.Entries[i], entry, "hash entries should not change")
+			}
+		}
+		require.True(proofSize(&compressed.Proof) < proofSize(&full.Proof), "compressed proof should be smaller")
+	}
+}
+
 func copyProof(p *syncer.Proof) *syncer.Proof {
 	if p == nil {
 		return nil
@@ -107,8 +107,9 @@ type Node struct {
 
 	// Mutable and shared between nodes' workers.
 	// Guarded by .CrossNode.
-	CrossNode    sync.Mutex
-	CurrentBlock *block.Block
+	CrossNode          sync.Mutex
+	CurrentBlock       *block.Block
+	CurrentBlockHeight int64
 
 	logger *logging.Logger
 }
@@ -217,6 +218,7 @@ func (n *Node) handleNewBlockLocked(blk *block.Block, height int64) {
 
 	// Update the current block.
 	n.CurrentBlock = blk
+	n.CurrentBlockHeight = height
 
 	for _, hooks := range n.hooks {
 		hooks.HandleNewBlockEarlyLocked(blk)
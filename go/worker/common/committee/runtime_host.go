@@ -31,6 +31,7 @@ type computeRuntimeHostHandler struct {
 	keyManager       keymanagerApi.Backend
 	keyManagerClient *keymanagerClient.Client
 	localStorage     localstorage.LocalStorage
+	spillStorage     localstorage.SpillStorage
 }
 
 func (h *computeRuntimeHostHandler) Handle(ctx context.Context, body *protocol.Body) (*protocol.Body, error) {
@@ -116,6 +117,21 @@ func (h *computeRuntimeHostHandler) Handle(ctx context.Context, body *protocol.B
 		}
 		return &protocol.Body{HostLocalStorageSetResponse: &protocol.Empty{}}, nil
 	}
+	// Spill storage.
+	if body.HostSpillStorageGetRequest != nil {
+		value := h.spillStorage.Get(body.HostSpillStorageGetRequest.Key)
+		return &protocol.Body{HostSpillStorageGetResponse: &protocol.HostSpillStorageGetResponse{Value: value}}, nil
+	}
+	if body.HostSpillStorageInsertRequest != nil {
+		for _, entry := range body.HostSpillStorageInsertRequest.Entries {
+			h.spillStorage.Set(entry.Key, entry.Value)
+		}
+		return &protocol.Body{HostSpillStorageInsertResponse: &protocol.Empty{}}, nil
+	}
+	if body.HostSpillStorageClearRequest != nil {
+		h.spillStorage.Clear()
+		return &protocol.Body{HostSpillStorageClearResponse: &protocol.Empty{}}, nil
+	}
 
 	return nil, errMethodNotSupported
 }
@@ -133,5 +149,6 @@ func (n *Node) NewRuntimeHostHandler() protocol.Handler {
 		n.KeyManager,
 		n.KeyManagerClient,
 		n.Runtime.LocalStorage(),
+		n.Runtime.SpillStorage(),
 	}
 }
@@ -2,6 +2,7 @@ package common
 
 import (
 	"fmt"
+	"strconv"
 	"time"
 
 	flag "github.com/spf13/pflag"
@@ -14,6 +15,7 @@ import (
 	cmdFlags "github.com/oasislabs/oasis-core/go/oasis-node/cmd/common/flags"
 	runtimeHost "github.com/oasislabs/oasis-core/go/runtime/host"
 	hostMock "github.com/oasislabs/oasis-core/go/runtime/host/mock"
+	"github.com/oasislabs/oasis-core/go/runtime/host/protocol"
 	hostSandbox "github.com/oasislabs/oasis-core/go/runtime/host/sandbox"
 	hostSgx "github.com/oasislabs/oasis-core/go/runtime/host/sgx"
 	"github.com/oasislabs/oasis-core/go/worker/common/configparser"
@@ -42,6 +44,10 @@ var (
 	// The value should be a map of runtime IDs to corresponding resource
 	// paths.
 	CfgRuntimeSGXSignatures = "worker.runtime.sgx.signatures"
+	// CfgRuntimeMemoryLimits configures the soft limits on the memory used by runtime subsystems.
+	// The value should be a map of subsystem names to limits in bytes. The limits apply to all
+	// runtimes.
+	CfgRuntimeMemoryLimits = "worker.runtime.memory_limits"
 
 	cfgStorageCommitTimeout = "worker.storage_commit_timeout"
 
@@ -185,6 +191,11 @@ func NewConfig(ias ias.Endpoint) (*Config, error) {
 			return nil, fmt.Errorf("unsupported runtime provisioner: %s", p)
 		}
 
+		var memoryLimits map[string]uint64
+		if memoryLimits, err = parseMemoryLimits(viper.GetStringMapString(CfgRuntimeMemoryLimits)); err != nil {
+			return nil, err
+		}
+
 		// Configure runtimes.
 		runtimeSGXSignatures := viper.GetStringMapString(CfgRuntimeSGXSignatures)
 		rh.Runtimes = make(map[common.Namespace]runtimeHost.Config)
@@ -195,8 +206,9 @@ func NewConfig(ias ias.Endpoint) (*Config, error) {
 			}
 
 			runtimeHostCfg := runtimeHost.Config{
-				RuntimeID: id,
-				Path:      path,
+				RuntimeID:    id,
+				Path:         path,
+				MemoryLimits: memoryLimits,
 			}
 
 			// This config is SGX specific, but that's all that's supported
@@ -225,6 +237,33 @@ func NewConfig(ias ias.Endpoint) (*Config, error) {
 	return &cfg, nil
 }
 
+func parseMemoryLimits(raw map[string]string) (map[string]uint64, error) {
+	if len(raw) == 0 {
+		return nil, nil
+	}
+
+	limits := make(map[string]uint64)
+	for subsystem, rawLimit := range raw {
+		var known bool
+		for _, s := range protocol.MemorySubsystems {
+			if subsystem == s {
+				known = true
+				break
+			}
+		}
+		if !known {
+			return nil, fmt.Errorf("unknown runtime memory subsystem '%s'", subsystem)
+		}
+
+		limit, err := strconv.ParseUint(rawLimit, 10, 64)
+		if err != nil {
+			return nil, fmt.Errorf("bad memory limit for runtime memory subsystem '%s': %w", subsystem, err)
+		}
+		limits[subsystem] = limit
+	}
+	return limits, nil
+}
+
 func init() {
 	Flags.Uint16(CfgClientPort, 9100, "Port to use for incoming gRPC client connections")
 	Flags.StringSlice(cfgClientAddresses, []string{}, "Address/port(s) to use for client connections when registering this node (if not set, all non-loopback local interfaces will be used)")
@@ -234,6 +273,7 @@ func init() {
 	Flags.String(CfgRuntimeSGXLoader, "", "(for SGX runtimes) Path to SGXS runtime loader binary")
 	Flags.StringToString(CfgRuntimePaths, nil, "Paths to runtime resources (format: <rt1-ID>=<path>,<rt2-ID>=<path>)")
 	Flags.StringToString(CfgRuntimeSGXSignatures, nil, "(for SGX runtimes) Paths to signatures (format: <rt1-ID>=<path>,<rt2-ID>=<path>")
+	Flags.StringToString(CfgRuntimeMemoryLimits, nil, "Soft limits on the memory used by runtime subsystems in bytes (format: <subsystem>=<limit>,...)")
 
 	Flags.Duration(cfgStorageCommitTimeout, 5*time.Second, "Storage commit timeout")
 
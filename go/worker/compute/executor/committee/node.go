@@ -96,6 +96,55 @@ var (
 		},
 		[]string{"runtime"},
 	)
+	stateCacheLookups = prometheus.NewCounterVec(
+		prometheus.CounterOpts{
+			Name: "oasis_worker_state_cache_lookups",
+			Help: "Number of runtime state tree node lookups by where they were served from (cache, spill or sync).",
+		},
+		[]string{"runtime", "source"},
+	)
+	stateSyncRoundTrips = prometheus.NewCounterVec(
+		prometheus.CounterOpts{
+			Name: "oasis_worker_state_sync_round_trips",
+			Help: "Number of runtime state tree round trips to storage.",
+		},
+		[]string{"runtime"},
+	)
+	stateNodesFetched = prometheus.NewCounterVec(
+		prometheus.CounterOpts{
+			Name: "oasis_worker_state_nodes_fetched",
+			Help: "Number of runtime state tree nodes fetched from storage.",
+		},
+		[]string{"runtime"},
+	)
+	stateBytesSynced = prometheus.NewCounterVec(
+		prometheus.CounterOpts{
+			Name: "oasis_worker_state_bytes_synced",
+			Help: "Size of runtime state tree proofs fetched from storage (bytes).",
+		},
+		[]string{"runtime"},
+	)
+	stateNodesSpilled = prometheus.NewCounterVec(
+		prometheus.CounterOpts{
+			Name: "oasis_worker_state_nodes_spilled",
+			Help: "Number of runtime state tree nodes spilled to untrusted storage.",
+		},
+		[]string{"runtime"},
+	)
+	stateWriteLogEntries = prometheus.NewCounterVec(
+		prometheus.CounterOpts{
+			Name: "oasis_worker_state_write_log_entries",
+			Help: "Number of runtime state write log entries.",
+		},
+		[]string{"runtime"},
+	)
+	stateWriteLogBytes = prometheus.NewCounterVec(
+		prometheus.CounterOpts{
+			Name: "oasis_worker_state_write_log_bytes",
+			Help: "Size of keys and values in runtime state write logs (bytes).",
+		},
+		[]string{"runtime"},
+	)
 	nodeCollectors = []prometheus.Collector{
 		discrepancyDetectedCount,
 		abortedBatchCount,
@@ -104,6 +153,13 @@ var (
 		batchProcessingTime,
 		batchRuntimeProcessingTime,
 		batchSize,
+		stateCacheLookups,
+		stateSyncRoundTrips,
+		stateNodesFetched,
+		stateBytesSynced,
+		stateNodesSpilled,
+		stateWriteLogEntries,
+		stateWriteLogBytes,
 	}
 
 	metricsOnce sync.Once
@@ -473,13 +529,8 @@ func (n *Node) startProcessingBatchLocked(
 	ctx, cancel := context.WithCancel(n.ctx)
 	done := make(chan *protocol.ComputedBatch, 1)
 
-	rq := &protocol.Body{
-		RuntimeExecuteTxBatchRequest: &protocol.RuntimeExecuteTxBatchRequest{
-			IORoot: ioRoot,
-			Inputs: batch,
-			Block:  *n.commonNode.CurrentBlock,
-		},
-	}
+	blk := *n.commonNode.CurrentBlock
+	height := n.commonNode.CurrentBlockHeight
 
 	batchStartTime := time.Now()
 	batchSize.With(n.getMetricLabels()).Observe(float64(len(batch)))
@@ -497,8 +548,30 @@ func (n *Node) startProcessingBatchLocked(
 	// Request the worker host to process a batch. This is done in a separate
 	// goroutine so that the committee node can continue processing blocks.
 	go func() {
+		// Closing the channel without a result aborts the batch.
 		defer close(done)
 
+		// Use the random beacon at the height of the current block so that all
+		// executors derive the same randomness. This is a consensus call, so
+		// it must not be made while holding the cross node lock.
+		beacon, err := n.commonNode.Consensus.Beacon().GetBeacon(ctx, height)
+		if err != nil {
+			n.logger.Error("failed to get random beacon",
+				"err", err,
+				"height", height,
+			)
+			return
+		}
+
+		rq := &protocol.Body{
+			RuntimeExecuteTxBatchRequest: &protocol.RuntimeExecuteTxBatchRequest{
+				IORoot: ioRoot,
+				Inputs: batch,
+				Block:  blk,
+				Beacon: beacon,
+			},
+		}
+
 		span := opentracing.StartSpan("CallBatch(rq)",
 			opentracing.Tag{Key: "rq", Value: rq},
 			opentracing.ChildOf(batchSpanCtx),
@@ -540,11 +613,34 @@ func (n *Node) startProcessingBatchLocked(
 			return
 		}
 
+		n.observeStateMetrics(&rsp.RuntimeExecuteTxBatchResponse.StateMetrics)
+
 		// Submit response to the executor worker.
 		done <- &rsp.RuntimeExecuteTxBatchResponse.Batch
 	}()
 }
 
+func (n *Node) observeStateMetrics(m *protocol.StateMetrics) {
+	labels := n.getMetricLabels()
+	lookups := map[string]uint64{
+		"cache": m.Hits,
+		"spill": m.SpillHits,
+		"sync":  m.Misses,
+	}
+	for source, count := range lookups {
+		stateCacheLookups.With(prometheus.Labels{
+			"runtime": labels["runtime"],
+			"source":  source,
+		}).Add(float64(count))
+	}
+	stateSyncRoundTrips.With(labels).Add(float64(m.SyncRoundTrips))
+	stateNodesFetched.With(labels).Add(float64(m.NodesFetched))
+	stateBytesSynced.With(labels).Add(float64(m.BytesSynced))
+	stateNodesSpilled.With(labels).Add(float64(m.NodesSpilled))
+	stateWriteLogEntries.With(labels).Add(float64(m.WriteLogEntries))
+	stateWriteLogBytes.With(labels).Add(float64(m.WriteLogBytes))
+}
+
 // Guarded by n.commonNode.CrossNode.
 func (n *Node) abortBatchLocked(reason error) {
 	state, ok := n.state.(StateProcessingBatch)
@@ -609,6 +705,14 @@ func (n *Node) proposeBatchLocked(batch *protocol.ComputedBatch) {
 
 		lastHeader := n.commonNode.CurrentBlock.Header
 
+		ioWriteLog, stateWriteLog, err := batch.WriteLogs()
+		if err != nil {
+			n.logger.Error("failed to decode write logs",
+				"err", err,
+			)
+			return err
+		}
+
 		// NOTE: Order is important for verifying the receipt.
 		applyOps := []storage.ApplyOp{
 			// I/O root.
@@ -616,14 +720,15 @@ func (n *Node) proposeBatchLocked(batch *protocol.ComputedBatch) {
 				SrcRound: lastHeader.Round + 1,
 				SrcRoot:  state.ioRoot,
 				DstRoot:  batch.Header.IORoot,
-				WriteLog: batch.IOWriteLog,
+				WriteLog: ioWriteLog,
 			},
 			// State root.
 			storage.ApplyOp{
-				SrcRound: lastHeader.Round,
-				SrcRoot:  lastHeader.StateRoot,
-				DstRoot:  batch.Header.StateRoot,
-				WriteLog: batch.StateWriteLog,
+				SrcRound:   lastHeader.Round,
+				SrcRoot:    lastHeader.StateRoot,
+				DstRoot:    batch.Header.StateRoot,
+				WriteLog:   stateWriteLog,
+				PruneHints: batch.PruneHints,
 			},
 		}
 
@@ -14,6 +14,7 @@ import (
 	"github.com/oasislabs/oasis-core/go/common/cbor"
 	"github.com/oasislabs/oasis-core/go/common/crypto/hash"
 	"github.com/oasislabs/oasis-core/go/common/crypto/signature"
+	"github.com/oasislabs/oasis-core/go/common/errors"
 	"github.com/oasislabs/oasis-core/go/common/grpc/policy"
 	"github.com/oasislabs/oasis-core/go/common/logging"
 	"github.com/oasislabs/oasis-core/go/common/node"
@@ -336,8 +337,8 @@ func (w *Worker) updateStatus(status *api.Status, startedEvent *host.StartedEven
 func extractMessageResponsePayload(raw []byte) ([]byte, error) {
 	// See: runtime/src/rpc/types.rs
 	type MessageResponseBody struct {
-		Success interface{} `json:",omitempty"`
-		Error   *string     `json:",omitempty"`
+		Success interface{}     `json:",omitempty"`
+		Error   *protocol.Error `json:",omitempty"`
 	}
 	type MessageResponse struct {
 		Response *struct {
@@ -357,7 +358,11 @@ func extractMessageResponsePayload(raw []byte) ([]byte, error) {
 	switch {
 	case msg.Response.Body.Success != nil:
 	case msg.Response.Body.Error != nil:
-		return nil, fmt.Errorf("rpc failure: '%s'", *msg.Response.Body.Error)
+		rpcErr := msg.Response.Body.Error
+		if err := errors.FromCode(rpcErr.Module, rpcErr.Code); err != nil {
+			return nil, fmt.Errorf("rpc failure: %w", err)
+		}
+		return nil, fmt.Errorf("rpc failure: '%s'", rpcErr.Message)
 	default:
 		return nil, fmt.Errorf("unknown rpc response status: '%s'", hex.EncodeToString(raw))
 	}
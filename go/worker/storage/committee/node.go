@@ -169,6 +169,9 @@ type Node struct {
 	syncedLock  sync.RWMutex
 	syncedState watcherState
 
+	retainLock   sync.RWMutex
+	retainRounds uint64
+
 	blockCh    *channels.InfiniteChannel
 	diffCh     chan *fetchedDiff
 	finalizeCh chan *blockSummary
@@ -386,6 +389,30 @@ func (n *Node) GetLastSynced() (uint64, mkvsNode.Root, mkvsNode.Root) {
 	return n.syncedState.LastBlock.Round, n.syncedState.LastBlock.IORoot, n.syncedState.LastBlock.StateRoot
 }
 
+// NotifyPruneHints notifies the node of the pruning hints emitted by the
+// runtime. Rounds are retained for the longest retention requested so far.
+func (n *Node) NotifyPruneHints(hints []storageApi.PruneHint) {
+	n.retainLock.Lock()
+	defer n.retainLock.Unlock()
+
+	for _, hint := range hints {
+		if hint.RetainRounds > n.retainRounds {
+			n.logger.Info("extending round retention due to prune hint",
+				"prefix", hint.Prefix,
+				"retain_rounds", hint.RetainRounds,
+			)
+			n.retainRounds = hint.RetainRounds
+		}
+	}
+}
+
+func (n *Node) getRetainRounds() uint64 {
+	n.retainLock.RLock()
+	defer n.retainLock.RUnlock()
+
+	return n.retainRounds
+}
+
 // ForceFinalize forces a storage finalization for the given round.
 func (n *Node) ForceFinalize(ctx context.Context, round uint64) error {
 	n.logger.Debug("forcing round finalization",
@@ -821,6 +848,8 @@ type pruneHandler struct {
 func (p *pruneHandler) Prune(ctx context.Context, rounds []uint64) error {
 	// Make sure we never prune past what was synced.
 	lastSycnedRound, _, _ := p.node.GetLastSynced()
+	// Make sure we never prune rounds the runtime asked to retain.
+	retainRounds := p.node.getRetainRounds()
 
 	for _, round := range rounds {
 		if round >= lastSycnedRound {
@@ -828,6 +857,11 @@ func (p *pruneHandler) Prune(ctx context.Context, rounds []uint64) error {
 				lastSycnedRound,
 			)
 		}
+		if round+retainRounds >= lastSycnedRound {
+			return fmt.Errorf("worker/storage: tried to prune round retained by prune hints (retain rounds: %d)",
+				retainRounds,
+			)
+		}
 
 		// TODO: Make sure we don't prune rounds that need to be checkpointed but haven't been yet.
 
@@ -119,7 +119,19 @@ func (s *storageService) ApplyBatch(ctx context.Context, request *api.ApplyBatch
 		}
 	}
 
-	return s.storage.ApplyBatch(ctx, request)
+	receipts, err := s.storage.ApplyBatch(ctx, request)
+	if err != nil {
+		return nil, err
+	}
+
+	// Make sure rounds are retained for as long as the runtime asked.
+	for _, op := range request.Ops {
+		if len(op.PruneHints) > 0 {
+			s.w.notifyPruneHints(request.Namespace, op.PruneHints)
+		}
+	}
+
+	return receipts, nil
 }
 
 func (s *storageService) Merge(ctx context.Context, request *api.MergeRequest) ([]*api.Receipt, error) {
@@ -237,6 +237,12 @@ func (s *Worker) Quit() <-chan struct{} {
 func (s *Worker) Cleanup() {
 }
 
+func (s *Worker) notifyPruneHints(ns common.Namespace, hints []api.PruneHint) {
+	if node, ok := s.runtimes[ns]; ok {
+		node.NotifyPruneHints(hints)
+	}
+}
+
 func init() {
 	Flags.Bool(CfgWorkerEnabled, false, "Enable storage worker")
 	Flags.Uint(cfgWorkerFetcherCount, 4, "Number of concurrent storage diff fetchers")
@@ -3,10 +3,10 @@ use std::{
     default::Default,
 };
 
-use base64;
-use failure::Fail;
 use rand::{rngs::OsRng, Rng};
+use serde_bytes::ByteBuf;
 use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
 use x25519_dalek;
 
 use oasis_core_runtime::{
@@ -15,14 +15,21 @@ use oasis_core_runtime::{
         runtime::RuntimeId,
         sgx::avr::EnclaveIdentity,
     },
-    impl_bytes, runtime_api,
+    impl_bytes, impl_secret_bytes, runtime_api,
+    types::ErrorCode,
+};
+
+// Re-exports.
+pub use oasis_core_runtime::common::crypto::context::{
+    KEYMANAGER_INIT_RESPONSE_CONTEXT as INIT_RESPONSE_CONTEXT,
+    KEYMANAGER_PUBLIC_KEY_CONTEXT as PUBLIC_KEY_CONTEXT,
 };
 
 impl_bytes!(ContractId, 32, "A 256-bit contract identifier.");
-impl_bytes!(PrivateKey, 32, "A private key.");
+impl_secret_bytes!(PrivateKey, 32, "A private key.");
 impl_bytes!(PublicKey, 32, "A public key.");
-impl_bytes!(StateKey, 32, "A state key.");
-impl_bytes!(MasterSecret, 32, "A 256 bit master secret.");
+impl_secret_bytes!(StateKey, 32, "A state key.");
+impl_secret_bytes!(MasterSecret, 32, "A 256 bit master secret.");
 
 /// Key manager initialization request.
 #[derive(Clone, Serialize, Deserialize)]
@@ -50,9 +57,6 @@ pub struct InitResponse {
     pub policy_checksum: Vec<u8>,
 }
 
-/// Context used for the init response signature.
-pub const INIT_RESPONSE_CONTEXT: &'static [u8] = b"oasis-core/keymanager: init response";
-
 /// Signed InitResponse.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct SignedInitResponse {
@@ -165,13 +169,10 @@ impl InputKeyPair {
     }
 
     pub fn get_sk(&self) -> PrivateKey {
-        self.sk
+        self.sk.clone()
     }
 }
 
-/// Context used for the public key signature.
-pub const PUBLIC_KEY_CONTEXT: [u8; 8] = *b"EkKmPubK";
-
 /// Signed public key.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct SignedPublicKey {
@@ -185,30 +186,54 @@ pub struct SignedPublicKey {
 }
 
 /// Key manager error.
-#[derive(Debug, Fail)]
+#[derive(Debug, Error)]
 pub enum KeyManagerError {
-    #[fail(display = "client session is not authenticated")]
+    #[error("client session is not authenticated")]
     NotAuthenticated,
-    #[fail(display = "client session authentication is invalid")]
+    #[error("client session authentication is invalid")]
     InvalidAuthentication,
-    #[fail(display = "key manager is not initialized")]
+    #[error("key manager is not initialized")]
     NotInitialized,
-    #[fail(display = "key manager state corrupted")]
+    #[error("key manager state corrupted")]
     StateCorrupted,
-    #[fail(display = "key manager replication required")]
+    #[error("key manager replication required")]
     ReplicationRequired,
-    #[fail(display = "policy rollback")]
+    #[error("policy rollback")]
     PolicyRollback,
-    #[fail(display = "policy alteration, without serial increment")]
+    #[error("policy alteration, without serial increment")]
     PolicyChanged,
-    #[fail(display = "policy is malformed or invalid")]
+    #[error("policy is malformed or invalid")]
     PolicyInvalid,
-    #[fail(display = "policy failed signature verification")]
+    #[error("policy failed signature verification")]
     PolicyInvalidSignature,
-    #[fail(display = "policy has insufficient signatures")]
+    #[error("policy has insufficient signatures")]
     PolicyInsufficientSignatures,
 }
 
+/// Name of the module for key manager errors.
+const MODULE_NAME: &'static str = "keymanager/api";
+
+impl ErrorCode for KeyManagerError {
+    fn module_name(&self) -> &str {
+        MODULE_NAME
+    }
+
+    fn code(&self) -> u32 {
+        match self {
+            KeyManagerError::NotAuthenticated => 1,
+            KeyManagerError::InvalidAuthentication => 2,
+            KeyManagerError::NotInitialized => 3,
+            KeyManagerError::StateCorrupted => 4,
+            KeyManagerError::ReplicationRequired => 5,
+            KeyManagerError::PolicyRollback => 6,
+            KeyManagerError::PolicyChanged => 7,
+            KeyManagerError::PolicyInvalid => 8,
+            KeyManagerError::PolicyInvalidSignature => 9,
+            KeyManagerError::PolicyInsufficientSignatures => 10,
+        }
+    }
+}
+
 /// Key manager access control policy.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PolicySGX {
@@ -231,6 +256,30 @@ pub struct SignedPolicySGX {
     pub signatures: Vec<SignatureBundle>,
 }
 
+/// Key manager status.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Status {
+    /// Key manager runtime identifier.
+    pub id: RuntimeId,
+    /// True iff the key manager is done initializing.
+    pub is_initialized: bool,
+    /// True iff the key manager is secure.
+    pub is_secure: bool,
+    /// Key manager master secret verification checksum.
+    pub checksum: Option<ByteBuf>,
+    /// Currently active key manager nodes.
+    pub nodes: Option<Vec<OasisPublicKey>>,
+    /// Key manager policy.
+    pub policy: Option<SignedPolicySGX>,
+}
+
+impl Status {
+    /// Serial number of the key manager policy, if any.
+    pub fn policy_serial(&self) -> Option<u32> {
+        self.policy.as_ref().map(|policy| policy.policy.serial)
+    }
+}
+
 /// Set of trusted key manager policy signing keys.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TrustedPolicySigners {
@@ -256,3 +305,36 @@ runtime_api! {
 
     pub fn replicate_master_secret(ReplicateRequest) -> ReplicateResponse;
 }
+
+#[cfg(all(test, debug_assertions))]
+mod test {
+    use oasis_core_runtime::common::crypto::memory::{scan_freed, ScanningAllocator};
+
+    use super::*;
+
+    #[global_allocator]
+    static ALLOCATOR: ScanningAllocator = ScanningAllocator;
+
+    #[test]
+    fn test_secrets_zeroized() {
+        assert_eq!(
+            scan_freed(|marker| {
+                drop(Box::new(ContractKey::new(
+                    PublicKey::default(),
+                    PrivateKey(*marker),
+                    StateKey(*marker),
+                    vec![],
+                )))
+            }),
+            0
+        );
+        assert_eq!(
+            scan_freed(|marker| {
+                drop(Box::new(ReplicateResponse {
+                    master_secret: MasterSecret(*marker),
+                }))
+            }),
+            0
+        );
+    }
+}
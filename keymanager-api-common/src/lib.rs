@@ -1,7 +1,10 @@
 //! Key manager API common types and functions.
-use failure::Fallible;
+use anyhow::Result;
 use lazy_static::lazy_static;
-use oasis_core_runtime::common::{cbor, crypto::signature::PublicKey as OasisPublicKey};
+use oasis_core_runtime::common::{
+    cbor,
+    crypto::{context::KEYMANAGER_POLICY_CONTEXT, signature::PublicKey as OasisPublicKey},
+};
 use std::{
     collections::HashSet,
     sync::{Mutex, Once},
@@ -31,11 +34,9 @@ pub fn set_trusted_policy_signers(signers: TrustedPolicySigners) -> bool {
     true
 }
 
-const POLICY_SIGN_CONTEXT: &'static [u8] = b"oasis-core/keymanager: policy";
-
 impl SignedPolicySGX {
     /// Verify the signatures and return the PolicySGX, if the signatures are correct.
-    pub fn verify(&self) -> Fallible<PolicySGX> {
+    pub fn verify(&self) -> Result<PolicySGX> {
         // Verify the signatures.
         let untrusted_policy_raw = cbor::to_vec(&self.policy);
         let mut signers: HashSet<OasisPublicKey> = HashSet::new();
@@ -47,7 +48,11 @@ impl SignedPolicySGX {
 
             if !sig
                 .signature
-                .verify(&public_key, &POLICY_SIGN_CONTEXT, &untrusted_policy_raw)
+                .verify(
+                    &public_key,
+                    &KEYMANAGER_POLICY_CONTEXT,
+                    &untrusted_policy_raw,
+                )
                 .is_ok()
             {
                 return Err(KeyManagerError::PolicyInvalidSignature.into());
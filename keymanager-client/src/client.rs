@@ -1,6 +1,7 @@
 //! Key manager client which talks to a remote key manager enclave.
 use std::{
     collections::HashSet,
+    hash::Hash,
     sync::{Arc, RwLock},
 };
 
@@ -15,16 +16,24 @@ use std::iter::FromIterator;
 #[cfg(target_env = "sgx")]
 use oasis_core_runtime::{common::cbor, protocol::ProtocolError, types::Body};
 
+#[cfg(not(target_env = "sgx"))]
+use oasis_core_client::{
+    config::{ConfigHandle, Subscription},
+    metrics::Metrics,
+    ClientBuilder,
+};
 use oasis_core_client::{create_rpc_api_client, BoxFuture, RpcClient};
 use oasis_core_keymanager_api_common::*;
 use oasis_core_runtime::{
     common::{runtime::RuntimeId, sgx::avr::EnclaveIdentity},
+    memory::{self, MemoryError, Subsystem},
     protocol::Protocol,
     rak::RAK,
     rpc::session,
+    types::register_error_code,
 };
 
-use super::KeyManagerClient;
+use super::{envelope::EnvelopeError, KeyManagerClient};
 
 with_api! {
     create_rpc_api_client!(Client, api);
@@ -44,20 +53,57 @@ struct Inner {
     get_public_key_cache: RwLock<LruCache<ContractId, SignedPublicKey>>,
 }
 
+/// Evict entries from the given key cache until the key cache is within its
+/// memory limit. Fails only if the limit is still exceeded once the cache is
+/// empty.
+fn evict_for_memory<K: Hash + Eq, V>(cache: &mut LruCache<K, V>) -> Result<(), MemoryError> {
+    loop {
+        match memory::check_limit(Subsystem::KeyCache, 0) {
+            Ok(()) => return Ok(()),
+            Err(err) => {
+                if cache.pop_lru().is_none() {
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
 /// A key manager client which talks to a remote key manager enclave.
 pub struct RemoteClient {
     inner: Arc<Inner>,
+    /// Metrics to record cache lookups in, if any.
+    #[cfg(not(target_env = "sgx"))]
+    metrics: Option<Metrics>,
+    /// Configuration the client follows, if any.
+    #[cfg(not(target_env = "sgx"))]
+    config: Option<Subscription>,
 }
 
 impl RemoteClient {
     fn new(runtime_id: RuntimeId, client: RpcClient, keys_cache_sizes: usize) -> Self {
+        register_error_code::<KeyManagerError>();
+        register_error_code::<EnvelopeError>();
+
+        let (get_or_create_secret_keys_cache, get_public_key_cache) =
+            memory::scope(Subsystem::KeyCache, || {
+                (
+                    LruCache::new(keys_cache_sizes),
+                    LruCache::new(keys_cache_sizes),
+                )
+            });
+
         Self {
             inner: Arc::new(Inner {
                 runtime_id,
                 rpc_client: Client::new(client),
-                get_or_create_secret_keys_cache: RwLock::new(LruCache::new(keys_cache_sizes)),
-                get_public_key_cache: RwLock::new(LruCache::new(keys_cache_sizes)),
+                get_or_create_secret_keys_cache: RwLock::new(get_or_create_secret_keys_cache),
+                get_public_key_cache: RwLock::new(get_public_key_cache),
             }),
+            #[cfg(not(target_env = "sgx"))]
+            metrics: None,
+            #[cfg(not(target_env = "sgx"))]
+            config: None,
         }
     }
 
@@ -149,6 +195,98 @@ impl RemoteClient {
             keys_cache_sizes,
         )
     }
+
+    /// Create a new key manager client with gRPC transport, configured by
+    /// the given client builder.
+    #[cfg(not(target_env = "sgx"))]
+    pub fn from_builder(builder: &ClientBuilder) -> Self {
+        let client = Self::new(
+            builder.runtime_id(),
+            builder.build_rpc_client(
+                session::Builder::new().remote_enclaves(builder.key_manager_enclaves()),
+                KEY_MANAGER_ENDPOINT,
+            ),
+            builder.keys_cache_size(),
+        );
+        let client = match builder.metrics() {
+            Some(metrics) => client.with_metrics(metrics),
+            None => client,
+        };
+        match builder.config_handle() {
+            Some(config) => client.with_config(&config),
+            None => client,
+        }
+    }
+
+    /// Record key cache lookups in the given metrics.
+    #[cfg(not(target_env = "sgx"))]
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Follow the key manager settings of the given configuration.
+    ///
+    /// Changes of the configuration are applied on the next call.
+    #[cfg(not(target_env = "sgx"))]
+    pub fn with_config(mut self, config: &ConfigHandle) -> Self {
+        let subscription = config.subscribe();
+        let current = subscription.config();
+        self.set_keys_cache_size(current.keys_cache_size);
+        self.set_enclaves(current.key_manager_enclaves.clone());
+        self.config = Some(subscription);
+        self
+    }
+
+    /// Apply the changes of the configuration since the last call, if any.
+    fn update_config(&self) {
+        #[cfg(not(target_env = "sgx"))]
+        {
+            let change = match self.config.as_ref().and_then(|config| config.changed()) {
+                Some(change) => change,
+                None => return,
+            };
+            if change.new.keys_cache_size != change.old.keys_cache_size {
+                self.set_keys_cache_size(change.new.keys_cache_size);
+            }
+            if change.new.key_manager_enclaves != change.old.key_manager_enclaves {
+                self.set_enclaves(change.new.key_manager_enclaves.clone());
+            }
+        }
+    }
+
+    /// Replace the key caches with empty ones of the given size.
+    #[cfg(not(target_env = "sgx"))]
+    fn set_keys_cache_size(&self, keys_cache_size: usize) {
+        memory::scope(Subsystem::KeyCache, || {
+            *self.inner.get_or_create_secret_keys_cache.write().unwrap() =
+                LruCache::new(keys_cache_size);
+            *self.inner.get_public_key_cache.write().unwrap() = LruCache::new(keys_cache_size);
+        });
+    }
+
+    /// Change the trusted key manager enclave identities.
+    #[cfg(not(target_env = "sgx"))]
+    fn set_enclaves(&self, enclaves: Option<HashSet<EnclaveIdentity>>) {
+        self.inner
+            .rpc_client
+            .rpc_client
+            .set_remote_enclaves(enclaves);
+        // Cached keys may have been obtained from enclaves no longer trusted.
+        self.clear_cache();
+    }
+
+    fn observe_cache_lookup(&self, method: &str, hit: bool) {
+        #[cfg(not(target_env = "sgx"))]
+        {
+            if let Some(ref metrics) = self.metrics {
+                metrics.observe_key_cache_lookup(method, hit);
+            }
+        }
+
+        #[cfg(target_env = "sgx")]
+        let _ = (method, hit);
+    }
 }
 
 impl KeyManagerClient for RemoteClient {
@@ -165,12 +303,22 @@ impl KeyManagerClient for RemoteClient {
     }
 
     fn get_or_create_keys(&self, ctx: Context, contract_id: ContractId) -> BoxFuture<ContractKey> {
+        self.update_config();
+
         let mut cache = self.inner.get_or_create_secret_keys_cache.write().unwrap();
-        if let Some(keys) = cache.get(&contract_id) {
-            return Box::new(future::ok(keys.clone()));
+        let keys = cache.get(&contract_id).cloned();
+        self.observe_cache_lookup("get_or_create_keys", keys.is_some());
+        if let Some(keys) = keys {
+            return Box::new(future::ok(keys));
         }
 
-        // No entry in cache, fetch from key manager.
+        // No entry in cache, make room for the fetched entry unless the cache
+        // cannot be shrunk any further.
+        if let Err(err) = evict_for_memory(&mut cache) {
+            return Box::new(future::err(err.into()));
+        }
+        drop(cache);
+
         let inner = self.inner.clone();
         Box::new(
             self.inner
@@ -178,7 +326,7 @@ impl KeyManagerClient for RemoteClient {
                 .get_or_create_keys(ctx, RequestIds::new(inner.runtime_id, contract_id))
                 .and_then(move |keys| {
                     let mut cache = inner.get_or_create_secret_keys_cache.write().unwrap();
-                    cache.put(contract_id, keys.clone());
+                    memory::scope(Subsystem::KeyCache, || cache.put(contract_id, keys.clone()));
 
                     Ok(keys)
                 }),
@@ -190,12 +338,22 @@ impl KeyManagerClient for RemoteClient {
         ctx: Context,
         contract_id: ContractId,
     ) -> BoxFuture<Option<SignedPublicKey>> {
+        self.update_config();
+
         let mut cache = self.inner.get_public_key_cache.write().unwrap();
-        if let Some(key) = cache.get(&contract_id) {
-            return Box::new(future::ok(Some(key.clone())));
+        let key = cache.get(&contract_id).cloned();
+        self.observe_cache_lookup("get_public_key", key.is_some());
+        if let Some(key) = key {
+            return Box::new(future::ok(Some(key)));
+        }
+
+        // No entry in cache, make room for the fetched entry unless the cache
+        // cannot be shrunk any further.
+        if let Err(err) = evict_for_memory(&mut cache) {
+            return Box::new(future::err(err.into()));
         }
+        drop(cache);
 
-        // No entry in cache, fetch from key manager.
         let inner = self.inner.clone();
         Box::new(
             self.inner
@@ -204,7 +362,7 @@ impl KeyManagerClient for RemoteClient {
                 .and_then(move |key| match key {
                     Some(key) => {
                         let mut cache = inner.get_public_key_cache.write().unwrap();
-                        cache.put(contract_id, key.clone());
+                        memory::scope(Subsystem::KeyCache, || cache.put(contract_id, key.clone()));
 
                         Ok(Some(key))
                     }
@@ -214,6 +372,8 @@ impl KeyManagerClient for RemoteClient {
     }
 
     fn replicate_master_secret(&self, ctx: Context) -> BoxFuture<Option<MasterSecret>> {
+        self.update_config();
+
         Box::new(
             self.inner
                 .rpc_client
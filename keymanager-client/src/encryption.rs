@@ -0,0 +1,295 @@
+//! Transparent state encryption for MKVS.
+use anyhow::Result;
+use futures::Future;
+use io_context::Context;
+use oasis_core_client::BoxFuture;
+use oasis_core_keymanager_api_common::*;
+use oasis_core_runtime::{
+    common::crypto::{
+        hash::Hash,
+        mrae::deoxysii::{DeoxysII, KEY_SIZE, NONCE_SIZE, TAG_SIZE},
+    },
+    storage::MKVS,
+};
+use thiserror::Error;
+
+use super::KeyManagerClient;
+
+/// Domain separation context for key hashing.
+const KEY_HASH_CONTEXT: &'static [u8] = b"oasis-core/keymanager: state key hash";
+/// Domain separation context for nonce derivation.
+const NONCE_CONTEXT: &'static [u8] = b"oasis-core/keymanager: state nonce";
+
+/// Encryption context error.
+#[derive(Debug, Error)]
+pub enum EncryptionError {
+    #[error("invalid encryption key size {0}")]
+    InvalidKeySize(usize),
+}
+
+/// How keys are transformed before they reach the MKVS.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyMode {
+    /// Keys are deterministically encrypted and can be recovered.
+    Encrypted,
+    /// Keys are replaced by a keyed hash and cannot be recovered.
+    Hashed,
+}
+
+/// A keyed storage encryption context, for use with a MKVS instance.
+///
+/// Values are encrypted using Deoxys-II with the contract's state key and
+/// are bound to the (transformed) key under which they are stored. Nonces are
+/// derived deterministically from the key and value so that all replicas end
+/// up with the same state.
+pub struct EncryptionContext {
+    state_key: StateKey,
+    key_mode: KeyMode,
+    d2: DeoxysII,
+}
+
+impl EncryptionContext {
+    /// Initialize a new encryption context with the given state key.
+    pub fn new(state_key: StateKey, key_mode: KeyMode) -> Self {
+        let d2 = DeoxysII::new(&state_key.0);
+
+        Self {
+            state_key,
+            key_mode,
+            d2,
+        }
+    }
+
+    /// Initialize a new encryption context with the given raw key.
+    ///
+    /// Fails if the key is not a valid Deoxys-II key.
+    pub fn from_raw_key(key: &[u8], key_mode: KeyMode) -> Result<Self> {
+        if key.len() != KEY_SIZE {
+            return Err(EncryptionError::InvalidKeySize(key.len()).into());
+        }
+        let mut state_key = StateKey::default();
+        state_key.0.copy_from_slice(key);
+
+        Ok(Self::new(state_key, key_mode))
+    }
+
+    /// Fetch the state key for the given contract from the key manager and
+    /// initialize a new encryption context.
+    pub fn for_contract(
+        km_client: &dyn KeyManagerClient,
+        ctx: Context,
+        contract_id: ContractId,
+        key_mode: KeyMode,
+    ) -> BoxFuture<Self> {
+        Box::new(
+            km_client
+                .get_or_create_keys(ctx, contract_id)
+                .map(move |keys| Self::new(keys.state_key, key_mode)),
+        )
+    }
+
+    /// Get encrypted MKVS entry.
+    pub fn get(&self, mkvs: &dyn MKVS, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let key = self.derive_key(key);
+        let ciphertext = mkvs.get(ctx, &key)?;
+
+        Ok(ciphertext.and_then(|ciphertext| self.open(&key, &ciphertext)))
+    }
+
+    /// Insert encrypted MKVS entry.
+    pub fn insert(
+        &self,
+        mkvs: &mut dyn MKVS,
+        ctx: Context,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        let key = self.derive_key(key);
+        let ciphertext = self.seal(&key, value);
+        let ciphertext = mkvs.insert(ctx, &key, &ciphertext)?;
+
+        Ok(ciphertext.and_then(|ciphertext| self.open(&key, &ciphertext)))
+    }
+
+    /// Remove encrypted MKVS entry.
+    pub fn remove(&self, mkvs: &mut dyn MKVS, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let key = self.derive_key(key);
+        let ciphertext = mkvs.remove(ctx, &key)?;
+
+        Ok(ciphertext.and_then(|ciphertext| self.open(&key, &ciphertext)))
+    }
+
+    /// Re-encrypt an MKVS entry under a new encryption context.
+    ///
+    /// This should be used when the contract's state key has been rotated.
+    /// Returns true if the entry existed and has been re-encrypted.
+    pub fn reencrypt(
+        &self,
+        new: &EncryptionContext,
+        mkvs: &mut dyn MKVS,
+        ctx: Context,
+        key: &[u8],
+    ) -> Result<bool> {
+        let ctx = ctx.freeze();
+        let value = match self.remove(mkvs, Context::create_child(&ctx), key)? {
+            Some(value) => value,
+            None => return Ok(false),
+        };
+        new.insert(mkvs, Context::create_child(&ctx), key, &value)?;
+
+        Ok(true)
+    }
+
+    fn seal(&self, key: &[u8], value: &[u8]) -> Vec<u8> {
+        let nonce = self.derive_nonce(key, value);
+        let mut ciphertext = self.d2.seal(&nonce, value.to_vec(), key.to_vec());
+        ciphertext.extend_from_slice(&nonce);
+        ciphertext
+    }
+
+    fn open(&self, key: &[u8], ciphertext: &[u8]) -> Option<Vec<u8>> {
+        // ciphertext || tag || nonce.
+        if ciphertext.len() < TAG_SIZE + NONCE_SIZE {
+            return None;
+        }
+
+        let nonce_offset = ciphertext.len() - NONCE_SIZE;
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce.copy_from_slice(&ciphertext[nonce_offset..]);
+        let ciphertext = &ciphertext[..nonce_offset];
+
+        self.d2.open(&nonce, ciphertext.to_vec(), key.to_vec()).ok()
+    }
+
+    fn derive_key(&self, key: &[u8]) -> Vec<u8> {
+        match self.key_mode {
+            KeyMode::Encrypted => {
+                // Deoxys-II is nonce-misuse resistant, so a fixed nonce only
+                // reveals whether two keys are equal.
+                let nonce = [0u8; NONCE_SIZE];
+                self.d2.seal(&nonce, key.to_vec(), vec![])
+            }
+            KeyMode::Hashed => {
+                Hash::digest_bytes_list(&[KEY_HASH_CONTEXT, self.state_key.as_ref(), key])
+                    .as_ref()
+                    .to_vec()
+            }
+        }
+    }
+
+    fn derive_nonce(&self, key: &[u8], value: &[u8]) -> [u8; NONCE_SIZE] {
+        let hash = Hash::digest_bytes_list(&[NONCE_CONTEXT, self.state_key.as_ref(), key, value]);
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce.copy_from_slice(&hash.as_ref()[..NONCE_SIZE]);
+        nonce
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use io_context::Context;
+    use oasis_core_runtime::storage::{
+        mkvs::{sync::NoopReadSyncer, Tree},
+        MKVS,
+    };
+
+    use super::*;
+
+    fn test_mode(key_mode: KeyMode) {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+        let enc_ctx = EncryptionContext::new(ContractKey::generate_mock().state_key, key_mode);
+
+        assert_eq!(
+            enc_ctx
+                .insert(&mut tree, Context::background(), b"foo", b"bar")
+                .expect("insert"),
+            None
+        );
+        assert_eq!(
+            enc_ctx
+                .get(&tree, Context::background(), b"foo")
+                .expect("get"),
+            Some(b"bar".to_vec())
+        );
+
+        // Plaintext must not be stored.
+        assert_eq!(
+            MKVS::get(&tree, Context::background(), b"foo").expect("get"),
+            None
+        );
+
+        // Other keys should not decrypt.
+        let other_ctx = EncryptionContext::new(ContractKey::generate_mock().state_key, key_mode);
+        assert_eq!(
+            other_ctx
+                .get(&tree, Context::background(), b"foo")
+                .expect("get"),
+            None
+        );
+
+        // Key rotation.
+        assert!(enc_ctx
+            .reencrypt(&other_ctx, &mut tree, Context::background(), b"foo")
+            .expect("reencrypt"));
+        assert_eq!(
+            enc_ctx
+                .get(&tree, Context::background(), b"foo")
+                .expect("get"),
+            None
+        );
+        assert_eq!(
+            other_ctx
+                .get(&tree, Context::background(), b"foo")
+                .expect("get"),
+            Some(b"bar".to_vec())
+        );
+
+        assert_eq!(
+            other_ctx
+                .remove(&mut tree, Context::background(), b"foo")
+                .expect("remove"),
+            Some(b"bar".to_vec())
+        );
+        assert_eq!(
+            other_ctx
+                .get(&tree, Context::background(), b"foo")
+                .expect("get"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_encryption_context() {
+        test_mode(KeyMode::Encrypted);
+        test_mode(KeyMode::Hashed);
+    }
+
+    #[test]
+    fn test_encryption_context_raw_key() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+        let state_key = ContractKey::generate_mock().state_key;
+        let enc_ctx = EncryptionContext::new(state_key.clone(), KeyMode::Encrypted);
+        enc_ctx
+            .insert(&mut tree, Context::background(), b"foo", b"bar")
+            .expect("insert");
+
+        let raw_ctx = EncryptionContext::from_raw_key(state_key.as_ref(), KeyMode::Encrypted)
+            .expect("from_raw_key");
+        assert_eq!(
+            raw_ctx
+                .get(&tree, Context::background(), b"foo")
+                .expect("get"),
+            Some(b"bar".to_vec())
+        );
+
+        for size in &[0, KEY_SIZE - 1, KEY_SIZE + 1] {
+            match EncryptionContext::from_raw_key(&vec![0u8; *size], KeyMode::Encrypted) {
+                Err(error) => match error.downcast_ref::<EncryptionError>() {
+                    Some(EncryptionError::InvalidKeySize(got)) => assert_eq!(got, size),
+                    _ => panic!("unexpected error: {}", error),
+                },
+                Ok(_) => panic!("invalid key size {} should be rejected", size),
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Confidential transaction envelopes.
+//!
+//! Clients encrypt transaction payloads to the public key of a contract
+//! using an ephemeral X25519 key pair and Deoxys-II. The runtime decrypts
+//! the request with the contract's private key and encrypts the response
+//! under the same shared key, so only the client can read it.
+use anyhow::Result;
+use futures::Future;
+use io_context::Context;
+use oasis_core_client::BoxFuture;
+use oasis_core_keymanager_api_common::*;
+use oasis_core_runtime::{
+    common::crypto::{mrae::deoxysii::NONCE_SIZE, x25519},
+    types::ErrorCode,
+};
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::KeyManagerClient;
+
+/// Additional data that binds envelopes to their purpose.
+const ENVELOPE_CONTEXT: &'static [u8] = b"oasis-core/keymanager: tx envelope";
+
+/// Nonce used for requests.
+///
+/// Every request uses a fresh ephemeral key pair, so fixed nonces never
+/// repeat under the same key.
+const REQUEST_NONCE: [u8; NONCE_SIZE] = [0u8; NONCE_SIZE];
+/// Nonce used for responses.
+const RESPONSE_NONCE: [u8; NONCE_SIZE] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+
+/// Name of the module for envelope errors.
+const MODULE_NAME: &'static str = "keymanager/client/envelope";
+
+/// Envelope error.
+#[derive(Debug, Error)]
+pub enum EnvelopeError {
+    #[error("contract public key not available")]
+    PublicKeyNotAvailable,
+    #[error("malformed envelope nonce")]
+    MalformedNonce,
+}
+
+impl ErrorCode for EnvelopeError {
+    fn module_name(&self) -> &str {
+        MODULE_NAME
+    }
+
+    fn code(&self) -> u32 {
+        match self {
+            EnvelopeError::PublicKeyNotAvailable => 1,
+            EnvelopeError::MalformedNonce => 2,
+        }
+    }
+}
+
+/// An encrypted transaction payload.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Envelope {
+    /// Public key of the sender.
+    pub public_key: PublicKey,
+    /// Nonce used for encryption.
+    #[serde(with = "serde_bytes")]
+    pub nonce: Vec<u8>,
+    /// Encrypted payload.
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+}
+
+impl Envelope {
+    fn seal(
+        nonce: [u8; NONCE_SIZE],
+        plaintext: Vec<u8>,
+        public_key: PublicKey,
+        peer_public_key: &PublicKey,
+        private_key: &x25519::PrivateKey,
+    ) -> Result<Self> {
+        let data = x25519::seal(
+            &nonce,
+            plaintext,
+            ENVELOPE_CONTEXT.to_vec(),
+            &x25519::PublicKey(peer_public_key.0),
+            private_key,
+        );
+
+        Ok(Self {
+            public_key,
+            nonce: nonce.to_vec(),
+            data,
+        })
+    }
+
+    fn open(
+        &self,
+        expected_nonce: [u8; NONCE_SIZE],
+        peer_public_key: &PublicKey,
+        private_key: &x25519::PrivateKey,
+    ) -> Result<Vec<u8>> {
+        if self.nonce[..] != expected_nonce[..] {
+            return Err(EnvelopeError::MalformedNonce.into());
+        }
+
+        x25519::open(
+            &expected_nonce,
+            self.data.clone(),
+            ENVELOPE_CONTEXT.to_vec(),
+            &x25519::PublicKey(peer_public_key.0),
+            private_key,
+        )
+    }
+}
+
+/// Client side of a confidential call.
+///
+/// A new context must be used for each request.
+pub struct CallContext {
+    contract_public_key: PublicKey,
+    public_key: PublicKey,
+    private_key: x25519::PrivateKey,
+}
+
+impl CallContext {
+    /// Create a new call context encrypting to the given contract public key.
+    pub fn new(contract_public_key: PublicKey) -> Self {
+        let private_key = x25519::PrivateKey::generate();
+
+        Self {
+            contract_public_key,
+            public_key: PublicKey(private_key.public_key().0),
+            private_key,
+        }
+    }
+
+    /// Fetch the public key of the given contract from the key manager and
+    /// create a new call context.
+    ///
+    /// The key manager client is responsible for verifying the public key.
+    pub fn for_contract(
+        km_client: &dyn KeyManagerClient,
+        ctx: Context,
+        contract_id: ContractId,
+    ) -> BoxFuture<Self> {
+        Box::new(
+            km_client
+                .get_public_key(ctx, contract_id)
+                .and_then(|signed_pk| match signed_pk {
+                    Some(signed_pk) => Ok(Self::new(signed_pk.key)),
+                    None => Err(EnvelopeError::PublicKeyNotAvailable.into()),
+                }),
+        )
+    }
+
+    /// Encrypt a request payload.
+    pub fn seal_request(&self, plaintext: Vec<u8>) -> Result<Envelope> {
+        Envelope::seal(
+            REQUEST_NONCE,
+            plaintext,
+            self.public_key,
+            &self.contract_public_key,
+            &self.private_key,
+        )
+    }
+
+    /// Decrypt a response payload.
+    pub fn open_response(&self, envelope: &Envelope) -> Result<Vec<u8>> {
+        envelope.open(RESPONSE_NONCE, &self.contract_public_key, &self.private_key)
+    }
+}
+
+/// Decrypt a request payload inside the runtime, using the contract's keys.
+pub fn open_request(keys: &InputKeyPair, envelope: &Envelope) -> Result<Vec<u8>> {
+    envelope.open(REQUEST_NONCE, &envelope.public_key, &private_key(keys))
+}
+
+/// Encrypt a response payload inside the runtime, so that only the sender
+/// of the given request can decrypt it.
+pub fn seal_response(
+    keys: &InputKeyPair,
+    request: &Envelope,
+    plaintext: Vec<u8>,
+) -> Result<Envelope> {
+    Envelope::seal(
+        RESPONSE_NONCE,
+        plaintext,
+        keys.get_pk(),
+        &request.public_key,
+        &private_key(keys),
+    )
+}
+
+fn private_key(keys: &InputKeyPair) -> x25519::PrivateKey {
+    x25519::PrivateKey::from_bytes(keys.get_sk().0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_envelope() {
+        let keys = ContractKey::generate_mock().input_keypair;
+        let call = CallContext::new(keys.get_pk());
+
+        let request = call.seal_request(b"request".to_vec()).unwrap();
+        assert_ne!(request.data, b"request".to_vec());
+        assert_eq!(open_request(&keys, &request).unwrap(), b"request".to_vec());
+
+        let response = seal_response(&keys, &request, b"response".to_vec()).unwrap();
+        assert_eq!(call.open_response(&response).unwrap(), b"response".to_vec());
+
+        // Responses cannot be opened as requests and vice versa.
+        assert!(open_request(&keys, &response).is_err());
+        assert!(call.open_response(&request).is_err());
+
+        // Other contracts cannot open the request.
+        let other = ContractKey::generate_mock().input_keypair;
+        assert!(open_request(&other, &request).is_err());
+    }
+}
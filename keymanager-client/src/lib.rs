@@ -1,6 +1,8 @@
 //! Key manager client.
 
 pub mod client;
+pub mod encryption;
+pub mod envelope;
 pub mod mock;
 
 use std::sync::Arc;
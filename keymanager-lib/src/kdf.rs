@@ -1,11 +1,12 @@
 ///! Key Derivation Function.
 use std::sync::{Arc, RwLock};
 
-use failure::Fallible;
+use anyhow::Result;
 use io_context::Context as IoContext;
 use lazy_static::lazy_static;
 use lru::LruCache;
 use rand::{rngs::OsRng, Rng};
+use serde_derive::{Deserialize, Serialize};
 use sgx_isa::Keypolicy;
 use sp800_185::{CShake, KMac};
 use x25519_dalek;
@@ -24,6 +25,7 @@ use oasis_core_runtime::{
             mrae::deoxysii::{DeoxysII, NONCE_SIZE, TAG_SIZE},
             signature,
         },
+        envelope::{self, Envelope, Versioned},
         runtime::RuntimeId,
         sgx::egetkey::egetkey,
     },
@@ -77,6 +79,19 @@ const MASTER_SECRET_STORAGE_KEY: &'static [u8] = b"keymanager_master_secret";
 const MASTER_SECRET_STORAGE_SIZE: usize = 32 + TAG_SIZE + NONCE_SIZE;
 const MASTER_SECRET_SEAL_CONTEXT: &'static [u8] = b"Ekiden Keymanager Seal master secret v0";
 
+/// Sealed master secret, as persisted in untrusted local storage.
+#[derive(Serialize, Deserialize)]
+struct SealedMasterSecret {
+    /// Encrypted master secret (ciphertext || tag || nonce).
+    #[serde(with = "serde_bytes")]
+    ciphertext: Vec<u8>,
+}
+
+impl Versioned for SealedMasterSecret {
+    const TYPE_TAG: &'static str = "keymanager/master-secret";
+    const VERSION: u16 = 1;
+}
+
 /// Kdf, which derives key manager keys from a master secret.
 pub struct Kdf {
     inner: RwLock<Inner>,
@@ -100,7 +115,7 @@ impl Inner {
         self.cache.clear();
     }
 
-    fn derive_contract_key(&self, req: &RequestIds) -> Fallible<ContractKey> {
+    fn derive_contract_key(&self, req: &RequestIds) -> Result<ContractKey> {
         let checksum = self.get_checksum()?;
         let mut contract_secret = self.derive_contract_secret(req)?;
 
@@ -113,7 +128,7 @@ impl Inner {
         // State (storage) key.
         let mut k = [0u8; 32];
         xof.squeeze(&mut k);
-        let state_key = StateKey::from(k.to_vec());
+        let state_key = StateKey(k);
 
         // Public/private keypair.
         xof.squeeze(&mut k);
@@ -129,7 +144,7 @@ impl Inner {
         ))
     }
 
-    fn derive_contract_secret(&self, req: &RequestIds) -> Fallible<Vec<u8>> {
+    fn derive_contract_secret(&self, req: &RequestIds) -> Result<Vec<u8>> {
         let master_secret = match self.master_secret.as_ref() {
             Some(master_secret) => master_secret,
             None => return Err(KeyManagerError::NotInitialized.into()),
@@ -142,11 +157,13 @@ impl Inner {
         f.update(req.runtime_id.as_ref());
         f.update(req.contract_id.as_ref());
         f.finalize(&mut k);
+        let contract_secret = k.to_vec();
+        k.zeroize();
 
-        Ok(k.to_vec())
+        Ok(contract_secret)
     }
 
-    fn get_checksum(&self) -> Fallible<Vec<u8>> {
+    fn get_checksum(&self) -> Result<Vec<u8>> {
         match self.checksum.as_ref() {
             Some(checksum) => Ok(checksum.clone()),
             None => Err(KeyManagerError::NotInitialized.into()),
@@ -179,7 +196,7 @@ impl Kdf {
         req: &InitRequest,
         ctx: &mut RpcContext,
         policy_checksum: Vec<u8>,
-    ) -> Fallible<SignedInitResponse> {
+    ) -> Result<SignedInitResponse> {
         let mut inner = self.inner.write().unwrap();
 
         let rctx = runtime_context!(ctx, KmContext);
@@ -326,7 +343,7 @@ impl Kdf {
     }
 
     // Get or create keys.
-    pub fn get_or_create_keys(&self, req: &RequestIds) -> Fallible<ContractKey> {
+    pub fn get_or_create_keys(&self, req: &RequestIds) -> Result<ContractKey> {
         let cache_key = req.to_cache_key();
 
         // Check to see if the cached value exists.
@@ -343,13 +360,13 @@ impl Kdf {
     }
 
     /// Get the public part of the key.
-    pub fn get_public_key(&self, req: &RequestIds) -> Fallible<Option<PublicKey>> {
+    pub fn get_public_key(&self, req: &RequestIds) -> Result<Option<PublicKey>> {
         let contract_keys = self.get_or_create_keys(req)?;
         Ok(Some(contract_keys.input_keypair.get_pk()))
     }
 
     /// Signs the public key using the key manager key.
-    pub fn sign_public_key(&self, key: PublicKey) -> Fallible<SignedPublicKey> {
+    pub fn sign_public_key(&self, key: PublicKey) -> Result<SignedPublicKey> {
         let mut body = key.as_ref().to_vec();
 
         let inner = self.inner.read().unwrap();
@@ -370,27 +387,39 @@ impl Kdf {
     }
 
     // Replicate master secret.
-    pub fn replicate_master_secret(&self) -> Fallible<ReplicateResponse> {
+    pub fn replicate_master_secret(&self) -> Result<ReplicateResponse> {
         let inner = self.inner.read().unwrap();
 
-        match inner.master_secret {
-            Some(master_secret) => Ok(ReplicateResponse { master_secret }),
+        match inner.master_secret.as_ref() {
+            Some(master_secret) => Ok(ReplicateResponse {
+                master_secret: master_secret.clone(),
+            }),
             None => Err(KeyManagerError::NotInitialized.into()),
         }
     }
 
     fn load_master_secret(runtime_id: &RuntimeId) -> Option<MasterSecret> {
-        let ciphertext = StorageContext::with_current(|_mkvs, untrusted_local| {
+        let data = StorageContext::with_current(|_mkvs, untrusted_local| {
             untrusted_local.get(MASTER_SECRET_STORAGE_KEY.to_vec())
         })
         .unwrap();
+        if data.is_empty() {
+            return None;
+        }
 
-        match ciphertext.len() {
-            0 => return None,
-            MASTER_SECRET_STORAGE_SIZE => (),
-            _ => {
-                panic!("persisted state is corrupted, invalid size");
+        // Master secrets persisted before envelopes were introduced are not
+        // wrapped in one.
+        let ciphertext = match Envelope::from_slice(&data) {
+            Ok(envelope) => {
+                let sealed: SealedMasterSecret = envelope
+                    .open()
+                    .unwrap_or_else(|err| panic!("persisted state is unusable: {}", err));
+                sealed.ciphertext
             }
+            Err(_) => data,
+        };
+        if ciphertext.len() != MASTER_SECRET_STORAGE_SIZE {
+            panic!("persisted state is corrupted, invalid size");
         }
 
         // Split the ciphertext || tag || nonce.
@@ -400,11 +429,13 @@ impl Kdf {
 
         // Decrypt the persisted master secret.
         let d2 = Self::new_d2();
-        let plaintext = d2
+        let mut plaintext = d2
             .open(&nonce, ciphertext.to_vec(), runtime_id.as_ref().to_vec())
             .expect("persisted state is corrupted");
+        let master_secret = MasterSecret::from(&plaintext[..]);
+        plaintext.zeroize();
 
-        Some(MasterSecret::from(plaintext))
+        Some(master_secret)
     }
 
     fn save_master_secret(master_secret: &MasterSecret, runtime_id: &RuntimeId) {
@@ -420,10 +451,11 @@ impl Kdf {
             runtime_id.as_ref().to_vec(),
         );
         ciphertext.extend_from_slice(&nonce);
+        let data = envelope::to_vec(&SealedMasterSecret { ciphertext });
 
         // Persist the encrypted master secret.
         StorageContext::with_current(|_mkvs, untrusted_local| {
-            untrusted_local.insert(MASTER_SECRET_STORAGE_KEY.to_vec(), ciphertext)
+            untrusted_local.insert(MASTER_SECRET_STORAGE_KEY.to_vec(), data)
         })
         .expect("failed to persist master secret");
     }
@@ -432,9 +464,8 @@ impl Kdf {
         let mut rng = OsRng {};
 
         // TODO: Support static keying for debugging.
-        let mut master_secret = [0u8; 32];
-        rng.fill(&mut master_secret);
-        let master_secret = MasterSecret::from(master_secret.to_vec());
+        let mut master_secret = MasterSecret::default();
+        rng.fill(&mut master_secret.0);
 
         Self::save_master_secret(&master_secret, runtime_id);
 
@@ -460,3 +491,42 @@ impl Kdf {
         d2
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use self::test::{black_box, Bencher};
+    use super::*;
+    use oasis_core_keymanager_api_common::ContractId;
+
+    fn new_kdf() -> Kdf {
+        let kdf = Kdf::new();
+        {
+            let mut inner = kdf.inner.write().unwrap();
+            inner.master_secret = Some(MasterSecret([1u8; 32]));
+            inner.checksum = Some(vec![2u8; 32]);
+        }
+        kdf
+    }
+
+    #[bench]
+    fn bench_get_or_create_keys_cached(b: &mut Bencher) {
+        let kdf = new_kdf();
+        let req = RequestIds::new(RuntimeId::default(), ContractId::default());
+        kdf.get_or_create_keys(&req).unwrap();
+
+        b.iter(|| black_box(kdf.get_or_create_keys(&req).unwrap()));
+    }
+
+    #[bench]
+    fn bench_get_or_create_keys_uncached(b: &mut Bencher) {
+        let kdf = new_kdf();
+        let req = RequestIds::new(RuntimeId::default(), ContractId::default());
+
+        b.iter(|| {
+            kdf.inner.write().unwrap().cache.clear();
+            black_box(kdf.get_or_create_keys(&req).unwrap())
+        });
+    }
+}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use failure::Fallible;
+use anyhow::Result;
 
 use oasis_core_keymanager_api_common::*;
 use oasis_core_runtime::{
@@ -11,13 +11,14 @@ use oasis_core_runtime::{
         dispatcher::{Method as RpcMethod, MethodDescriptor as RpcMethodDescriptor},
         Context as RpcContext,
     },
+    types::register_error_code,
     Protocol, RpcDemux, RpcDispatcher, TxnDispatcher,
 };
 
 use crate::{context, kdf::Kdf, policy::Policy};
 
 /// Initialize the Kdf.
-fn init_kdf(req: &InitRequest, ctx: &mut RpcContext) -> Fallible<SignedInitResponse> {
+fn init_kdf(req: &InitRequest, ctx: &mut RpcContext) -> Result<SignedInitResponse> {
     let policy_checksum = Policy::global().init(ctx, &req.policy)?;
     Kdf::global().init(&req, ctx, policy_checksum)
 }
@@ -33,6 +34,9 @@ pub fn new_keymanager(signers: TrustedPolicySigners) -> Box<dyn Initializer> {
         // Initialize the set of trusted policy signers.
         set_trusted_policy_signers(signers.clone());
 
+        // Report key manager errors to clients with their codes.
+        register_error_code::<KeyManagerError>();
+
         // Register RPC methods exposed via EnclaveRPC to remote clients.
         {
             use crate::methods::*;
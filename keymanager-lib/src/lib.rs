@@ -1,3 +1,5 @@
+#![feature(test)]
+
 pub mod context;
 pub mod kdf;
 pub mod keymanager;
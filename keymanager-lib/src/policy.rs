@@ -4,8 +4,9 @@ use std::{
     sync::RwLock,
 };
 
-use failure::Fallible;
+use anyhow::Result;
 use lazy_static::lazy_static;
+use serde_derive::{Deserialize, Serialize};
 use sgx_isa::Keypolicy;
 use tiny_keccak::sha3_256;
 
@@ -13,6 +14,7 @@ use oasis_core_keymanager_api_common::*;
 use oasis_core_runtime::{
     common::{
         cbor,
+        envelope::{self, Envelope, Versioned},
         runtime::RuntimeId,
         sgx::{
             avr::EnclaveIdentity,
@@ -32,6 +34,19 @@ lazy_static! {
 const POLICY_STORAGE_KEY: &'static [u8] = b"keymanager_policy";
 const POLICY_SEAL_CONTEXT: &'static [u8] = b"Ekiden Keymanager Seal policy v0";
 
+/// Sealed raw policy, as persisted in untrusted local storage.
+#[derive(Serialize, Deserialize)]
+struct SealedPolicy {
+    /// Sealed raw policy.
+    #[serde(with = "serde_bytes")]
+    ciphertext: Vec<u8>,
+}
+
+impl Versioned for SealedPolicy {
+    const TYPE_TAG: &'static str = "keymanager/policy";
+    const VERSION: u16 = 1;
+}
+
 /// Policy, which manages the key manager policy.
 pub struct Policy {
     inner: RwLock<Inner>,
@@ -58,7 +73,7 @@ impl Policy {
     }
 
     /// Initialize (or update) the policy state.
-    pub fn init(&self, ctx: &mut RpcContext, raw_policy: &Vec<u8>) -> Fallible<Vec<u8>> {
+    pub fn init(&self, ctx: &mut RpcContext, raw_policy: &Vec<u8>) -> Result<Vec<u8>> {
         // If this is an insecure build, don't bother trying to apply any policy.
         if Self::unsafe_skip() {
             return Ok(vec![]);
@@ -113,7 +128,7 @@ impl Policy {
         &self,
         remote_enclave: &EnclaveIdentity,
         req: &RequestIds,
-    ) -> Fallible<()> {
+    ) -> Result<()> {
         let inner = self.inner.read().unwrap();
         let policy = match inner.policy.as_ref() {
             Some(policy) => policy,
@@ -126,7 +141,7 @@ impl Policy {
     }
 
     /// Check if the MRENCLAVE/MRSIGNER may replicate.
-    pub fn may_replicate_master_secret(&self, remote_enclave: &EnclaveIdentity) -> Fallible<()> {
+    pub fn may_replicate_master_secret(&self, remote_enclave: &EnclaveIdentity) -> Result<()> {
         // Always allow replication to ourselves, if it is possible to do so in
         // an authenticated manner.
         #[cfg(target_env = "sgx")]
@@ -170,11 +185,23 @@ impl Policy {
     }
 
     fn load_policy() -> Option<CachedPolicy> {
-        let ciphertext = StorageContext::with_current(|_mkvs, untrusted_local| {
+        let data = StorageContext::with_current(|_mkvs, untrusted_local| {
             untrusted_local.get(POLICY_STORAGE_KEY.to_vec())
         })
         .unwrap();
 
+        // Policies persisted before envelopes were introduced are not wrapped
+        // in one.
+        let ciphertext = match Envelope::from_slice(&data) {
+            Ok(envelope) => {
+                let sealed: SealedPolicy = envelope
+                    .open()
+                    .unwrap_or_else(|err| panic!("persisted policy is unusable: {}", err));
+                sealed.ciphertext
+            }
+            Err(_) => data,
+        };
+
         unseal(Keypolicy::MRENCLAVE, &POLICY_SEAL_CONTEXT, &ciphertext).map(|plaintext| {
             // Deserialization failures are fatal, because it is state corruption.
             CachedPolicy::parse(&plaintext).expect("failed to deserialize persisted policy")
@@ -183,12 +210,13 @@ impl Policy {
 
     fn save_raw_policy(raw_policy: &Vec<u8>) {
         let ciphertext = seal(Keypolicy::MRENCLAVE, &POLICY_SEAL_CONTEXT, &raw_policy);
+        let data = envelope::to_vec(&SealedPolicy { ciphertext });
 
-        // Persist the encrypted master secret.
+        // Persist the encrypted policy.
         StorageContext::with_current(|_mkvs, untrusted_local| {
-            untrusted_local.insert(POLICY_STORAGE_KEY.to_vec(), ciphertext)
+            untrusted_local.insert(POLICY_STORAGE_KEY.to_vec(), data)
         })
-        .expect("failed to persist master secret");
+        .expect("failed to persist policy");
     }
 }
 
@@ -203,9 +231,9 @@ struct CachedPolicy {
 }
 
 impl CachedPolicy {
-    fn parse(raw: &Vec<u8>) -> Fallible<Self> {
+    fn parse(raw: &Vec<u8>) -> Result<Self> {
         // Parse out the signed policy.
-        let untrusted_policy: SignedPolicySGX = cbor::from_slice(&raw)?;
+        let untrusted_policy: SignedPolicySGX = cbor::from_slice_canonical(&raw)?;
         let policy = untrusted_policy.verify()?;
 
         let mut cached_policy = Self::default();
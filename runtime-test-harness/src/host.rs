@@ -0,0 +1,206 @@
+//! Simulated host side of the runtime host protocol.
+use std::{
+    collections::HashMap,
+    io::{BufReader, BufWriter, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use anyhow::Result;
+use crossbeam::channel;
+use io_context::Context;
+use slog::Logger;
+
+use oasis_core_runtime::{
+    common::{cbor, logger::get_logger},
+    protocol::{decode_message, Stream},
+    storage::mkvs::{sync::ReadSync, MemoryStore},
+    types::{
+        Body, Error as RuntimeError, Message, MessageType, StorageSyncRequest, StorageSyncResponse,
+    },
+};
+
+use super::HarnessError;
+
+/// Simulated runtime host.
+///
+/// The host serves storage sync requests from an in-memory store and keeps
+/// the untrusted local storage in memory. Requests which would need to be
+/// forwarded to other nodes (e.g., key manager RPCs) are rejected, runtimes
+/// under test should use the mock key manager client instead.
+pub struct Host {
+    logger: Logger,
+    /// Stream to the runtime.
+    stream: Stream,
+    /// Mutex for sending outgoing messages.
+    outgoing_mutex: Mutex<()>,
+    /// Outgoing request identifier generator.
+    last_request_id: AtomicUsize,
+    /// Pending outgoing requests.
+    pending_out_requests: Mutex<HashMap<u64, channel::Sender<Body>>>,
+    /// Storage backing the state and I/O trees.
+    storage: MemoryStore,
+    /// Untrusted local storage.
+    local_storage: Mutex<HashMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl Host {
+    /// Create a new simulated host and start handling messages received
+    /// from the runtime.
+    pub fn start(stream: Stream, storage: MemoryStore) -> Result<Arc<Self>> {
+        let reader = stream.try_clone()?;
+        let host = Arc::new(Self {
+            logger: get_logger("harness/host"),
+            stream,
+            outgoing_mutex: Mutex::new(()),
+            last_request_id: AtomicUsize::new(0),
+            pending_out_requests: Mutex::new(HashMap::new()),
+            storage,
+            local_storage: Mutex::new(HashMap::new()),
+        });
+
+        let h = host.clone();
+        thread::spawn(move || h.run(reader));
+
+        Ok(host)
+    }
+
+    /// Make a request to the runtime and wait for the response.
+    pub fn call(&self, body: Body) -> Result<Body> {
+        let id = self.last_request_id.fetch_add(1, Ordering::SeqCst) as u64;
+        let (tx, rx) = channel::bounded(1);
+        self.pending_out_requests.lock().unwrap().insert(id, tx);
+
+        self.send(Message {
+            id,
+            message_type: MessageType::Request,
+            body,
+            span_context: vec![],
+        })?;
+
+        match rx.recv() {
+            Ok(Body::Error(error)) => Err(HarnessError::Runtime(error).into()),
+            Ok(body) => Ok(body),
+            Err(_) => Err(HarnessError::ConnectionClosed.into()),
+        }
+    }
+
+    fn send(&self, message: Message) -> Result<()> {
+        let _guard = self.outgoing_mutex.lock().unwrap();
+        let mut writer = BufWriter::new(&self.stream);
+
+        let buffer = cbor::to_vec(&message);
+        writer.write_all(&(buffer.len() as u32).to_be_bytes())?;
+        writer.write_all(&buffer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    fn run(&self, stream: Stream) {
+        let mut reader = BufReader::new(stream);
+
+        loop {
+            let message = match decode_message(&mut reader) {
+                Ok(message) => message,
+                Err(error) => {
+                    debug!(self.logger, "Runtime connection closed"; "err" => %error);
+                    break;
+                }
+            };
+
+            match message.message_type {
+                MessageType::Request => {
+                    let body = match self.handle_request(message.body) {
+                        Ok(body) => body,
+                        Err(error) => {
+                            Body::Error(RuntimeError::new("harness", 1, &format!("{}", error)))
+                        }
+                    };
+
+                    let response = Message {
+                        id: message.id,
+                        message_type: MessageType::Response,
+                        body,
+                        span_context: vec![],
+                    };
+                    if let Err(error) = self.send(response) {
+                        error!(self.logger, "Failed to send response"; "err" => %error);
+                        break;
+                    }
+                }
+                MessageType::Response => {
+                    let sender = self
+                        .pending_out_requests
+                        .lock()
+                        .unwrap()
+                        .remove(&message.id);
+                    match sender {
+                        Some(sender) => {
+                            let _ = sender.send(message.body);
+                        }
+                        None => {
+                            warn!(self.logger, "Received response message for unknown request"; "msg_id" => message.id);
+                        }
+                    }
+                }
+                _ => warn!(self.logger, "Received a malformed message"),
+            }
+        }
+
+        // Wake up anyone still waiting for a response.
+        self.pending_out_requests.lock().unwrap().clear();
+    }
+
+    fn handle_request(&self, request: Body) -> Result<Body> {
+        match request {
+            Body::HostStorageSyncRequest { request } => {
+                let mut read_syncer = self.storage.read_syncer();
+                let ctx = Context::background();
+                let response = match request {
+                    StorageSyncRequest::SyncGet(request) => read_syncer.sync_get(ctx, request)?,
+                    StorageSyncRequest::SyncGetPrefixes(request) => {
+                        read_syncer.sync_get_prefixes(ctx, request)?
+                    }
+                    StorageSyncRequest::SyncIterate(request) => {
+                        read_syncer.sync_iterate(ctx, request)?
+                    }
+                };
+
+                Ok(Body::HostStorageSyncResponse {
+                    response: StorageSyncResponse::ProofResponse(response),
+                })
+            }
+            Body::HostLocalStorageGetRequest { key } => {
+                let value = self
+                    .local_storage
+                    .lock()
+                    .unwrap()
+                    .get(&key)
+                    .cloned()
+                    .unwrap_or_default();
+
+                Ok(Body::HostLocalStorageGetResponse { value })
+            }
+            Body::HostLocalStorageSetRequest { key, value } => {
+                self.local_storage.lock().unwrap().insert(key, value);
+
+                Ok(Body::HostLocalStorageSetResponse {})
+            }
+            Body::HostLogRequest { records } => {
+                for record in records {
+                    debug!(self.logger, "{}", record.message; "target" => record.target, "level" => record.level);
+                }
+
+                Ok(Body::HostLogResponse {})
+            }
+            req => {
+                warn!(self.logger, "Received unsupported request"; "req" => format!("{:?}", req));
+                Err(HarnessError::UnsupportedRequest.into())
+            }
+        }
+    }
+}
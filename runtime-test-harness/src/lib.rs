@@ -0,0 +1,546 @@
+//! Oasis Core runtime test harness.
+//!
+//! The harness runs a runtime's dispatcher in-process against a simulated
+//! host, which serves the state and I/O trees from an in-memory store. This
+//! makes it possible to write end-to-end tests of runtimes without a node or
+//! SGX.
+//!
+//! # Examples
+//!
+//! ```rust,ignore
+//! let builder = Harness::make();
+//! let km_client = builder.key_manager_client();
+//! let mut harness = builder.start(Box::new(move |protocol, rak, rpc_demux, rpc| {
+//!     // Initialize the runtime as usual, using `km_client` as the key
+//!     // manager client.
+//! }))?;
+//!
+//! // Execute a batch. The roots computed by the runtime are verified
+//! // before the harness advances to the next block.
+//! let outputs = harness.execute_calls(&[TxnCall { .. }])?;
+//!
+//! // Query the resulting state.
+//! let value = harness.state().get(Context::background(), b"key")?;
+//! ```
+#[macro_use]
+extern crate slog;
+
+mod host;
+
+use std::{collections::BTreeMap, sync::Arc, thread};
+
+use anyhow::Result;
+use io_context::Context;
+use thiserror::Error;
+
+use oasis_core_keymanager_client::mock::MockClient;
+use oasis_core_runtime::{
+    common::{
+        cbor,
+        crypto::hash::Hash,
+        roothash::{Block, Header, HeaderType, Namespace},
+        runtime::RuntimeId,
+        version::Version,
+    },
+    dispatcher::{Dispatcher, Initializer},
+    memory::Subsystem,
+    protocol::{Protocol, Stream},
+    rak::RAK,
+    rpc::types::{Message as RpcMessage, Request as RpcRequest, Response as RpcResponse},
+    storage::mkvs::{sync::NoopReadSyncer, MemoryStore, MemoryTree, Root, RootType},
+    transaction::{
+        tree::Tree as TxnTree,
+        types::{TxnBatch, TxnCall, TxnOutput},
+    },
+    types::{Body, ComputedBatch, Error as RuntimeError},
+    BUILD_INFO,
+};
+
+use self::host::Host;
+
+/// Harness error.
+#[derive(Debug, Error)]
+pub enum HarnessError {
+    #[error("harness: runtime error: {0}")]
+    Runtime(#[source] RuntimeError),
+    #[error("harness: invalid response from runtime")]
+    InvalidResponse,
+    #[error("harness: connection to runtime closed")]
+    ConnectionClosed,
+    #[error("harness: unsupported host request")]
+    UnsupportedRequest,
+    #[error("harness: {what} mismatch (expected: {expected:?} got: {got:?})")]
+    Mismatch {
+        what: &'static str,
+        expected: Hash,
+        got: Hash,
+    },
+}
+
+/// Harness builder.
+pub struct Builder {
+    runtime_id: RuntimeId,
+    runtime_version: Version,
+    memory_limits: BTreeMap<Subsystem, u64>,
+    key_manager: Arc<MockClient>,
+}
+
+impl Builder {
+    /// Set the identifier of the runtime under test.
+    pub fn with_runtime_id(mut self, runtime_id: RuntimeId) -> Self {
+        self.runtime_id = runtime_id;
+        self
+    }
+
+    /// Set the version of the runtime under test.
+    pub fn with_runtime_version(mut self, runtime_version: Version) -> Self {
+        self.runtime_version = runtime_version;
+        self
+    }
+
+    /// Set the soft limit on the live bytes of the given memory subsystem of
+    /// the runtime under test.
+    pub fn with_memory_limit(mut self, subsystem: Subsystem, limit: u64) -> Self {
+        self.memory_limits.insert(subsystem, limit);
+        self
+    }
+
+    /// Return the mock key manager client, which should be passed to the
+    /// runtime under test by its initializer.
+    pub fn key_manager_client(&self) -> Arc<MockClient> {
+        self.key_manager.clone()
+    }
+
+    /// Start the runtime and connect it to a simulated host.
+    pub fn start(self, initializer: Box<dyn Initializer>) -> Result<Harness> {
+        let (runtime_stream, host_stream) = Stream::pair()?;
+        let storage = MemoryStore::new();
+
+        let rak = Arc::new(RAK::new());
+        let dispatcher = Dispatcher::new(initializer, rak.clone());
+        let protocol = Arc::new(Protocol::new(
+            runtime_stream,
+            rak,
+            dispatcher,
+            self.runtime_version,
+        ));
+        thread::spawn(move || protocol.start());
+
+        let host = Host::start(host_stream, storage.clone())?;
+        match host.call(Body::RuntimeInfoRequest {
+            runtime_id: self.runtime_id,
+            protocol_version: BUILD_INFO.protocol_version.into(),
+            memory_limits: self.memory_limits,
+        })? {
+            Body::RuntimeInfoResponse { .. } => {}
+            _ => return Err(HarnessError::InvalidResponse.into()),
+        }
+
+        let block = Block {
+            header: Header {
+                namespace: Namespace::from(self.runtime_id.as_ref()),
+                header_type: HeaderType::Normal,
+                io_root: Hash::empty_hash(),
+                state_root: Hash::empty_hash(),
+                ..Default::default()
+            },
+        };
+
+        Ok(Harness {
+            host,
+            storage,
+            key_manager: self.key_manager,
+            runtime_id: self.runtime_id,
+            block,
+        })
+    }
+}
+
+/// A runtime connected to a simulated host.
+pub struct Harness {
+    host: Arc<Host>,
+    storage: MemoryStore,
+    key_manager: Arc<MockClient>,
+    runtime_id: RuntimeId,
+    block: Block,
+}
+
+impl Harness {
+    /// Create a new harness builder.
+    pub fn make() -> Builder {
+        Builder {
+            runtime_id: RuntimeId::default(),
+            runtime_version: Version::default(),
+            memory_limits: BTreeMap::new(),
+            key_manager: Arc::new(MockClient::new()),
+        }
+    }
+
+    /// Return the identifier of the runtime under test.
+    pub fn runtime_id(&self) -> RuntimeId {
+        self.runtime_id
+    }
+
+    /// Return the mock key manager client.
+    pub fn key_manager_client(&self) -> Arc<MockClient> {
+        self.key_manager.clone()
+    }
+
+    /// Return the latest block.
+    pub fn block(&self) -> &Block {
+        &self.block
+    }
+
+    /// Open the state tree at the latest block.
+    pub fn state(&self) -> MemoryTree {
+        self.storage.open(self.block.header.state_tree_root())
+    }
+
+    /// Execute a batch of transactions.
+    ///
+    /// The I/O and state roots computed by the runtime are verified against
+    /// the returned write logs, which are then applied to storage and a new
+    /// block is produced.
+    pub fn execute_batch(&mut self, inputs: Vec<Vec<u8>>) -> Result<ComputedBatch> {
+        let ctx = Context::background().freeze();
+        let header = &self.block.header;
+        let round = header.round + 1;
+        let io_root = Root {
+            namespace: header.namespace,
+            version: round,
+            root_type: RootType::IO,
+            hash: Hash::empty_hash(),
+        };
+
+        // Generate the I/O tree with the inputs, as done by the transaction
+        // scheduler.
+        let mut txn_tree = TxnTree::new(Box::new(NoopReadSyncer {}), io_root)?;
+        for (batch_order, input) in inputs.iter().enumerate() {
+            txn_tree.add_input(
+                Context::create_child(&ctx),
+                input.clone(),
+                batch_order as u32,
+            )?;
+        }
+        let (mut io_write_log, inputs_root) = txn_tree.commit(Context::create_child(&ctx))?;
+
+        let batch = match self.host.call(Body::RuntimeExecuteTxBatchRequest {
+            io_root: inputs_root,
+            inputs: TxnBatch::new(inputs),
+            block: self.block.clone(),
+            beacon: vec![],
+        })? {
+            Body::RuntimeExecuteTxBatchResponse { batch, .. } => batch,
+            _ => return Err(HarnessError::InvalidResponse.into()),
+        };
+
+        if batch.header.previous_hash != header.encoded_hash() {
+            return Err(HarnessError::Mismatch {
+                what: "previous block hash",
+                expected: header.encoded_hash(),
+                got: batch.header.previous_hash,
+            }
+            .into());
+        }
+
+        // Verify the roots by applying the write logs.
+        io_write_log.extend(batch.io_write_log.iter().cloned());
+        let mut io_tree = self.storage.open(io_root);
+        io_tree.apply_write_log(Context::create_child(&ctx), io_write_log)?;
+        let (_, new_io_root) =
+            io_tree.commit(Context::create_child(&ctx), header.namespace, round)?;
+        if new_io_root != batch.header.io_root {
+            return Err(HarnessError::Mismatch {
+                what: "I/O root",
+                expected: new_io_root,
+                got: batch.header.io_root,
+            }
+            .into());
+        }
+
+        let mut state_tree = self.storage.open(header.state_tree_root());
+        state_tree.apply_write_log(Context::create_child(&ctx), batch.state_write_log.clone())?;
+        let (_, new_state_root) =
+            state_tree.commit(Context::create_child(&ctx), header.namespace, round)?;
+        if new_state_root != batch.header.state_root {
+            return Err(HarnessError::Mismatch {
+                what: "state root",
+                expected: new_state_root,
+                got: batch.header.state_root,
+            }
+            .into());
+        }
+
+        self.block = Block {
+            header: Header {
+                version: header.version,
+                namespace: header.namespace,
+                round,
+                timestamp: header.timestamp + 1,
+                header_type: HeaderType::Normal,
+                previous_hash: batch.header.previous_hash,
+                io_root: batch.header.io_root,
+                state_root: batch.header.state_root,
+                ..Default::default()
+            },
+        };
+
+        Ok(batch)
+    }
+
+    /// Execute a batch of calls and return their outputs.
+    pub fn execute_calls(&mut self, calls: &[TxnCall]) -> Result<Vec<TxnOutput>> {
+        let inputs: Vec<Vec<u8>> = calls.iter().map(|call| cbor::to_vec(call)).collect();
+        self.execute_batch(inputs.clone())?;
+
+        let mut outputs = Vec::with_capacity(inputs.len());
+        for input in &inputs {
+            match self.get_output(input)? {
+                Some(output) => outputs.push(output),
+                None => return Err(HarnessError::InvalidResponse.into()),
+            }
+        }
+
+        Ok(outputs)
+    }
+
+    /// Check a batch of transactions against the latest block.
+    pub fn check_batch(&self, inputs: Vec<Vec<u8>>) -> Result<Vec<TxnOutput>> {
+        let results = match self.host.call(Body::RuntimeCheckTxBatchRequest {
+            inputs: TxnBatch::new(inputs),
+            block: self.block.clone(),
+        })? {
+            Body::RuntimeCheckTxBatchResponse { results } => results,
+            _ => return Err(HarnessError::InvalidResponse.into()),
+        };
+
+        let mut outputs = Vec::with_capacity(results.len());
+        for result in results.iter() {
+            outputs.push(cbor::from_slice(result)?);
+        }
+
+        Ok(outputs)
+    }
+
+    /// Return the output of a transaction executed in the latest block.
+    pub fn get_output(&self, input: &[u8]) -> Result<Option<TxnOutput>> {
+        let txn_tree = TxnTree::new(
+            Box::new(self.storage.read_syncer()),
+            self.block.header.io_tree_root(),
+        )?;
+
+        match txn_tree.get_output(Context::background(), Hash::digest_bytes(input))? {
+            Some(output) => Ok(Some(cbor::from_slice(&output)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Make a local RPC call against the state at the latest block.
+    pub fn local_rpc_call(&self, request: RpcRequest) -> Result<RpcResponse> {
+        let response = match self.host.call(Body::RuntimeLocalRPCCallRequest {
+            request: cbor::to_vec(&request),
+            state_root: self.block.header.state_root,
+        })? {
+            Body::RuntimeLocalRPCCallResponse { response } => response,
+            _ => return Err(HarnessError::InvalidResponse.into()),
+        };
+
+        match cbor::from_slice(&response)? {
+            RpcMessage::Response(response) => Ok(response),
+            _ => Err(HarnessError::InvalidResponse.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::Future;
+    use oasis_core_keymanager_client::{ContractId, KeyManagerClient};
+    use oasis_core_runtime::{
+        executor::Executor,
+        rpc::{
+            dispatcher::{Method as RpcMethod, MethodDescriptor as RpcMethodDescriptor},
+            types::Body as RpcBody,
+            Context as RpcContext,
+        },
+        runtime_context,
+        storage::{StorageContext, MKVS},
+        transaction::{
+            dispatcher::{CheckOnlySuccess, Method, MethodDescriptor},
+            Context as TxnContext,
+        },
+        RpcDemux, RpcDispatcher, TxnDispatcher, TxnMethDispatcher,
+    };
+
+    use super::*;
+
+    struct TestContext {
+        km_client: Arc<MockClient>,
+    }
+
+    fn insert(args: &(String, String), ctx: &mut TxnContext) -> Result<Option<String>> {
+        if ctx.check_only {
+            return Err(CheckOnlySuccess::default().into());
+        }
+
+        let existing = StorageContext::with_current(|mkvs, _untrusted_local| {
+            mkvs.insert(
+                Context::create_child(&ctx.io_ctx),
+                args.0.as_bytes(),
+                args.1.as_bytes(),
+            )
+        })?;
+        Ok(existing.map(String::from_utf8).transpose()?)
+    }
+
+    fn public_key(args: &String, ctx: &mut TxnContext) -> Result<Vec<u8>> {
+        let rctx = runtime_context!(ctx, TestContext);
+        let contract_id = ContractId::from(Hash::digest_bytes(args.as_bytes()).as_ref());
+        let result = rctx
+            .km_client
+            .get_public_key(Context::create_child(&ctx.io_ctx), contract_id);
+        let key = Executor::with_current(|executor| executor.block_on(result))?;
+
+        Ok(key.unwrap().key.as_ref().to_vec())
+    }
+
+    fn get(args: &String, _ctx: &mut RpcContext) -> Result<Option<String>> {
+        let value = StorageContext::with_current(|mkvs, _untrusted_local| {
+            mkvs.get(Context::background(), args.as_bytes())
+        })?;
+        Ok(value.map(String::from_utf8).transpose()?)
+    }
+
+    fn start() -> Harness {
+        let builder = Harness::make();
+        let km_client = builder.key_manager_client();
+        let init = move |_protocol: &Arc<Protocol>,
+                         _rak: &Arc<RAK>,
+                         _rpc_demux: &mut RpcDemux,
+                         rpc: &mut RpcDispatcher|
+              -> Option<Box<dyn TxnDispatcher>> {
+            let mut txn = TxnMethDispatcher::new();
+            txn.add_method(Method::new(
+                MethodDescriptor {
+                    name: "insert".to_owned(),
+                },
+                insert,
+            ));
+            txn.add_method(Method::new(
+                MethodDescriptor {
+                    name: "public_key".to_owned(),
+                },
+                public_key,
+            ));
+            let km_client = km_client.clone();
+            txn.set_context_initializer(move |ctx: &mut TxnContext| {
+                ctx.runtime = Box::new(TestContext {
+                    km_client: km_client.clone(),
+                })
+            });
+
+            rpc.add_method(
+                RpcMethod::new(
+                    RpcMethodDescriptor {
+                        name: "get".to_owned(),
+                    },
+                    get,
+                ),
+                true,
+            );
+
+            Some(Box::new(txn))
+        };
+
+        builder.start(Box::new(init)).expect("harness should start")
+    }
+
+    fn call<T: serde::Serialize>(method: &str, args: T) -> TxnCall {
+        TxnCall {
+            method: method.to_owned(),
+            args: cbor::to_value(args),
+        }
+    }
+
+    #[test]
+    fn test_harness() {
+        let mut harness = start();
+        assert_eq!(harness.block().header.round, 0);
+
+        // Execute a batch and query the resulting state.
+        let outputs = harness
+            .execute_calls(&[
+                call("insert", ("foo", "bar")),
+                call("insert", ("moo", "boo")),
+            ])
+            .expect("batch should execute");
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(harness.block().header.round, 1);
+        assert_eq!(
+            MKVS::get(&harness.state(), Context::background(), b"foo").expect("get"),
+            Some(b"bar".to_vec())
+        );
+
+        // Subsequent rounds build on the previous state.
+        let outputs = harness
+            .execute_calls(&[call("insert", ("foo", "baz")), call("missing", ())])
+            .expect("batch should execute");
+        match outputs[0] {
+            TxnOutput::Success(ref value) => assert_eq!(value, &cbor::to_value(Some("bar"))),
+            ref output => panic!("unexpected output: {:?}", output),
+        }
+        match outputs[1] {
+            TxnOutput::Error(_) => {}
+            ref output => panic!("unexpected output: {:?}", output),
+        }
+        let state = harness.state();
+        assert_eq!(
+            MKVS::get(&state, Context::background(), b"foo").expect("get"),
+            Some(b"baz".to_vec())
+        );
+        assert_eq!(
+            MKVS::get(&state, Context::background(), b"moo").expect("get"),
+            Some(b"boo".to_vec())
+        );
+
+        // Checks do not produce a new block.
+        let results = harness
+            .check_batch(vec![cbor::to_vec(&call("insert", ("a", "b")))])
+            .expect("batch should be checked");
+        match results[0] {
+            TxnOutput::Success(_) => {}
+            ref output => panic!("unexpected output: {:?}", output),
+        }
+        assert_eq!(harness.block().header.round, 2);
+
+        // Local RPC calls see the latest state.
+        let response = harness
+            .local_rpc_call(RpcRequest {
+                method: "get".to_owned(),
+                args: cbor::to_value("moo"),
+            })
+            .expect("local RPC call should succeed");
+        match response.body {
+            RpcBody::Success(value) => assert_eq!(value, cbor::to_value(Some("boo"))),
+            body => panic!("unexpected response: {:?}", body),
+        }
+
+        // The runtime uses the mock key manager.
+        let outputs = harness
+            .execute_calls(&[call("public_key", "contract")])
+            .expect("batch should execute");
+        let contract_id = ContractId::from(Hash::digest_bytes(b"contract").as_ref());
+        let expected = harness
+            .key_manager_client()
+            .get_public_key(Context::background(), contract_id)
+            .wait()
+            .unwrap()
+            .unwrap();
+        match outputs[0] {
+            TxnOutput::Success(ref value) => {
+                assert_eq!(value, &cbor::to_value(expected.key.as_ref().to_vec()))
+            }
+            ref output => panic!("unexpected output: {:?}", output),
+        }
+    }
+}
@@ -0,0 +1,15 @@
+//! Build with `OASIS_UNSAFE_SKIP_AVR_VERIFY=1` to get past the signature
+//! verification and also cover the report body and quote parsing.
+use honggfuzz::fuzz;
+
+use oasis_core_runtime::common::{cbor, sgx::avr};
+
+fn main() {
+    loop {
+        fuzz!(|data: &[u8]| {
+            if let Ok(avr) = cbor::from_slice::<avr::AVR>(data) {
+                let _ = avr::verify(&avr);
+            }
+        });
+    }
+}
@@ -0,0 +1,11 @@
+use honggfuzz::fuzz;
+
+use oasis_core_runtime::protocol::decode_message;
+
+fn main() {
+    loop {
+        fuzz!(|data: &[u8]| {
+            let _ = decode_message(data);
+        });
+    }
+}
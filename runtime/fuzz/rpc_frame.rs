@@ -0,0 +1,16 @@
+use honggfuzz::fuzz;
+
+use oasis_core_runtime::{
+    common::cbor,
+    rpc::types::{Frame, Message},
+};
+
+fn main() {
+    loop {
+        fuzz!(|data: &[u8]| {
+            if let Ok(frame) = cbor::from_slice::<Frame>(data) {
+                let _ = cbor::from_slice::<Message>(&frame.payload);
+            }
+        });
+    }
+}
@@ -0,0 +1,24 @@
+use honggfuzz::fuzz;
+
+use oasis_core_runtime::rpc::session::Builder;
+
+fn main() {
+    loop {
+        fuzz!(|data: &[u8]| {
+            // Input is a sequence of messages, each prefixed by its big-endian
+            // 16-bit length (the maximum Noise message size).
+            let mut session = Builder::new().build_responder();
+            let mut data = data;
+            while data.len() >= 2 {
+                let length = u16::from_be_bytes([data[0], data[1]]) as usize;
+                let message = &data[2..][..length.min(data.len() - 2)];
+                data = &data[2 + message.len()..];
+
+                let mut writer = vec![];
+                if session.process_data(message.to_vec(), &mut writer).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+}
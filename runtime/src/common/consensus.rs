@@ -0,0 +1,269 @@
+//! Consensus transaction structures.
+//!
+//! # Note
+//!
+//! This **MUST** be kept in sync with go/consensus/api/transaction.
+//!
+use anyhow::Result;
+use serde_derive::{Deserialize, Serialize};
+
+use super::{
+    cbor,
+    crypto::{
+        hash::Hash,
+        signature::{PublicKey, Signature, Signer},
+    },
+    quantity::Quantity,
+};
+
+/// Signature context used for signing consensus transactions.
+pub use super::crypto::context::CONSENSUS_TX_CONTEXT as SIGNATURE_CONTEXT;
+
+/// Returns the transaction signature context for the given chain context.
+pub fn signature_context(chain_context: &str) -> Vec<u8> {
+    SIGNATURE_CONTEXT.for_chain(chain_context)
+}
+
+/// Transaction fee.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Fee {
+    /// Fee amount to be paid.
+    pub amount: Quantity,
+    /// Maximum gas that a transaction can use.
+    pub gas: u64,
+}
+
+impl Fee {
+    /// Create a fee paying for the given amount of gas at the given price
+    /// per unit of gas.
+    pub fn for_gas(gas: u64, gas_price: &Quantity) -> Self {
+        let amount = gas_price
+            .checked_mul(&gas.into())
+            .expect("quantity multiplication never overflows");
+
+        Self { amount, gas }
+    }
+}
+
+/// An unsigned consensus transaction.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Transaction {
+    /// Nonce to prevent replay.
+    pub nonce: u64,
+    /// Optional fee that the sender commits to pay to execute the
+    /// transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee: Option<Fee>,
+    /// Method that should be called.
+    pub method: String,
+    /// Method call body.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<cbor::Value>,
+}
+
+impl Transaction {
+    /// Create a new transaction calling the given method.
+    pub fn new<B>(nonce: u64, fee: Option<Fee>, method: &str, body: B) -> Self
+    where
+        B: serde::Serialize,
+    {
+        Self {
+            nonce,
+            fee,
+            method: method.to_owned(),
+            body: Some(cbor::to_value(body)),
+        }
+    }
+}
+
+/// A transaction signature, bundled with the signer's public key.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TransactionSignature {
+    /// Public key that produced the signature.
+    pub public_key: PublicKey,
+    /// Actual signature.
+    pub signature: Signature,
+}
+
+/// A signed consensus transaction.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SignedTransaction {
+    /// CBOR-encoded transaction.
+    #[serde(rename = "untrusted_raw_value", with = "serde_bytes")]
+    pub blob: Vec<u8>,
+    /// Signature over the encoded transaction.
+    pub signature: TransactionSignature,
+}
+
+impl SignedTransaction {
+    /// Sign a transaction for the given chain.
+    pub fn sign(
+        signer: &dyn Signer,
+        public_key: PublicKey,
+        chain_context: &str,
+        tx: &Transaction,
+    ) -> Result<Self> {
+        let blob = cbor::to_vec(tx);
+        let signature = signer.sign(&signature_context(chain_context), &blob)?;
+
+        Ok(Self {
+            blob,
+            signature: TransactionSignature {
+                public_key,
+                signature,
+            },
+        })
+    }
+
+    /// Verify the signature and return the signed transaction.
+    pub fn open(&self, chain_context: &str) -> Result<Transaction> {
+        self.signature.signature.verify(
+            &self.signature.public_key,
+            &signature_context(chain_context),
+            &self.blob,
+        )?;
+
+        cbor::from_slice_canonical(&self.blob)
+    }
+
+    /// Cryptographic hash of the encoded signed transaction.
+    pub fn hash(&self) -> Hash {
+        Hash::digest_bytes(&cbor::to_vec(self))
+    }
+}
+
+/// A builder for signed consensus transactions.
+///
+/// The builder does not access the network, so everything that would otherwise
+/// be queried from a node (the chain context and the signer's nonce) must be
+/// given explicitly. This allows transactions to be prepared and signed on
+/// air-gapped machines or by HSM-backed `Signer` implementations, and to be
+/// submitted separately.
+#[derive(Clone, Debug)]
+pub struct TransactionBuilder {
+    chain_context: String,
+    nonce: u64,
+    fee: Option<Fee>,
+}
+
+impl TransactionBuilder {
+    /// Create a new builder for transactions on the given chain, using the
+    /// given signer nonce.
+    pub fn new(chain_context: &str, nonce: u64) -> Self {
+        Self {
+            chain_context: chain_context.to_owned(),
+            nonce,
+            fee: None,
+        }
+    }
+
+    /// Set the fee that the signer commits to pay.
+    pub fn with_fee(mut self, fee: Fee) -> Self {
+        self.fee = Some(fee);
+        self
+    }
+
+    /// Build an unsigned transaction calling the given method.
+    pub fn build<B>(&self, method: &str, body: B) -> Transaction
+    where
+        B: serde::Serialize,
+    {
+        Transaction::new(self.nonce, self.fee.clone(), method, body)
+    }
+
+    /// Build and sign a transaction calling the given method.
+    pub fn sign<B>(
+        &self,
+        signer: &dyn Signer,
+        public_key: PublicKey,
+        method: &str,
+        body: B,
+    ) -> Result<SignedTransaction>
+    where
+        B: serde::Serialize,
+    {
+        SignedTransaction::sign(
+            signer,
+            public_key,
+            &self.chain_context,
+            &self.build(method, body),
+        )
+    }
+
+    /// Build and sign a transaction calling the given method, and return it
+    /// CBOR-encoded, ready for submission.
+    pub fn sign_encoded<B>(
+        &self,
+        signer: &dyn Signer,
+        public_key: PublicKey,
+        method: &str,
+        body: B,
+    ) -> Result<Vec<u8>>
+    where
+        B: serde::Serialize,
+    {
+        Ok(cbor::to_vec(&self.sign(signer, public_key, method, body)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::crypto::signature::PrivateKey, *};
+
+    #[test]
+    fn test_signed_transaction() {
+        let sk = PrivateKey::generate();
+        let tx = Transaction::new(
+            42,
+            Some(Fee {
+                amount: Quantity::from(100u64),
+                gas: 1000,
+            }),
+            "test.Method",
+            "body",
+        );
+
+        let signed = SignedTransaction::sign(&sk, sk.public_key(), "test chain", &tx).unwrap();
+        assert_eq!(signed.open("test chain").unwrap(), tx);
+        assert!(signed.open("other chain").is_err());
+
+        let enc = cbor::to_vec(&signed);
+        let dec: SignedTransaction = cbor::from_slice(&enc).unwrap();
+        assert_eq!(dec, signed);
+        assert_eq!(dec.hash(), signed.hash());
+    }
+
+    #[test]
+    fn test_fee_for_gas() {
+        let fee = Fee::for_gas(1000, &Quantity::from(3u64));
+        assert_eq!(fee.amount, Quantity::from(3000u64));
+        assert_eq!(fee.gas, 1000);
+
+        // Fee amounts do not overflow.
+        let fee = Fee::for_gas(2, &Quantity::from(u128::max_value()));
+        assert_eq!(
+            fee.amount,
+            "680564733841876926926749214863536422910".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_transaction_builder() {
+        let sk = PrivateKey::generate();
+        let fee = Fee {
+            amount: Quantity::from(10u64),
+            gas: 100,
+        };
+        let builder = TransactionBuilder::new("test chain", 7).with_fee(fee.clone());
+
+        let tx = builder.build("test.Method", 1u64);
+        assert_eq!(tx.nonce, 7);
+        assert_eq!(tx.fee, Some(fee));
+
+        let enc = builder
+            .sign_encoded(&sk, sk.public_key(), "test.Method", 1u64)
+            .unwrap();
+        let signed: SignedTransaction = cbor::from_slice(&enc).unwrap();
+        assert_eq!(signed.open("test chain").unwrap(), tx);
+    }
+}
@@ -0,0 +1,326 @@
+//! Staking account addresses.
+//!
+//! An address consists of a version byte followed by a truncated hash of the
+//! versioned domain separation context and the public key. Addresses are
+//! rendered using bech32 (BIP-173).
+use anyhow::Result;
+use thiserror::Error;
+
+use super::{hash::Hash, signature::PublicKey};
+
+/// Size of an address in bytes.
+pub const ADDRESS_SIZE: usize = 21;
+/// Size of the address version prefix in bytes.
+const ADDRESS_VERSION_SIZE: usize = 1;
+
+/// Human readable part of bech32-encoded addresses.
+pub const ADDRESS_BECH32_HRP: &'static str = "oasis";
+/// Domain separation context of version 0 staking addresses.
+const ADDRESS_V0_CONTEXT: &'static [u8] = b"oasis-core/address: staking";
+/// Version of staking addresses.
+const ADDRESS_V0_VERSION: u8 = 0;
+
+/// Characters used by the bech32 encoding, indexed by their value.
+const BECH32_CHARSET: &'static [u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+/// Number of characters of the bech32 checksum.
+const BECH32_CHECKSUM_LENGTH: usize = 6;
+/// Maximum length of a bech32 string.
+const BECH32_MAX_LENGTH: usize = 90;
+
+impl_bytes!(Address, ADDRESS_SIZE, "A staking account address.");
+
+/// Address error.
+#[derive(Debug, Error)]
+pub enum AddressError {
+    #[error("malformed bech32 string: {0}")]
+    MalformedBech32(&'static str),
+    #[error("invalid bech32 checksum")]
+    InvalidChecksum,
+    #[error("unexpected human readable part: {0}")]
+    UnexpectedHrp(String),
+    #[error("invalid address length: {0}")]
+    InvalidLength(usize),
+    #[error("unsupported address version: {0}")]
+    UnsupportedVersion(u8),
+}
+
+impl Address {
+    /// Derive an address of the given version from the given data, using
+    /// the given domain separation context.
+    pub fn new(context: &[u8], version: u8, data: &[u8]) -> Self {
+        let hash = Hash::digest_bytes_list(&[context, &[version], data]);
+
+        let mut address = [0u8; ADDRESS_SIZE];
+        address[0] = version;
+        address[ADDRESS_VERSION_SIZE..]
+            .copy_from_slice(&hash.as_ref()[..ADDRESS_SIZE - ADDRESS_VERSION_SIZE]);
+
+        Address(address)
+    }
+
+    /// Derive the staking address of the given public key.
+    pub fn from_public_key(public_key: &PublicKey) -> Self {
+        Self::new(ADDRESS_V0_CONTEXT, ADDRESS_V0_VERSION, public_key.as_ref())
+    }
+
+    /// Version of the address.
+    pub fn version(&self) -> u8 {
+        self.0[0]
+    }
+
+    /// Render the address in bech32.
+    pub fn to_bech32(&self) -> String {
+        bech32_encode(
+            ADDRESS_BECH32_HRP,
+            &convert_bits(&self.0, 8, 5, true).unwrap(),
+        )
+    }
+
+    /// Parse and validate a bech32-encoded address.
+    pub fn from_bech32(data: &str) -> Result<Self> {
+        let (hrp, data) = bech32_decode(data)?;
+        if hrp != ADDRESS_BECH32_HRP {
+            return Err(AddressError::UnexpectedHrp(hrp).into());
+        }
+
+        let data = convert_bits(&data, 5, 8, false)?;
+        if data.len() != ADDRESS_SIZE {
+            return Err(AddressError::InvalidLength(data.len()).into());
+        }
+        let address = Address::from(data);
+        if address.version() != ADDRESS_V0_VERSION {
+            return Err(AddressError::UnsupportedVersion(address.version()).into());
+        }
+
+        Ok(address)
+    }
+}
+
+impl From<&PublicKey> for Address {
+    fn from(public_key: &PublicKey) -> Self {
+        Self::from_public_key(public_key)
+    }
+}
+
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+
+    let mut checksum = 1u32;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x01ff_ffff) << 5) ^ u32::from(*value);
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+fn bech32_expand_hrp(hrp: &str) -> Vec<u8> {
+    let mut expanded: Vec<u8> = hrp.bytes().map(|c| c >> 5).collect();
+    expanded.push(0);
+    expanded.extend(hrp.bytes().map(|c| c & 0x1f));
+    expanded
+}
+
+fn bech32_checksum(hrp: &str, data: &[u8]) -> Vec<u8> {
+    let mut values = bech32_expand_hrp(hrp);
+    values.extend_from_slice(data);
+    values.extend_from_slice(&[0u8; BECH32_CHECKSUM_LENGTH]);
+    let polymod = bech32_polymod(&values) ^ 1;
+
+    (0..BECH32_CHECKSUM_LENGTH)
+        .map(|i| ((polymod >> (5 * (BECH32_CHECKSUM_LENGTH - 1 - i))) & 0x1f) as u8)
+        .collect()
+}
+
+/// Encode 5-bit values under the given (lowercase) human readable part.
+fn bech32_encode(hrp: &str, data: &[u8]) -> String {
+    let checksum = bech32_checksum(hrp, data);
+
+    let mut encoded = String::with_capacity(hrp.len() + 1 + data.len() + checksum.len());
+    encoded.push_str(hrp);
+    encoded.push('1');
+    for value in data.iter().chain(checksum.iter()) {
+        encoded.push(BECH32_CHARSET[*value as usize] as char);
+    }
+    encoded
+}
+
+/// Decode a bech32 string into its (lowercase) human readable part and its
+/// 5-bit values, verifying the checksum.
+fn bech32_decode(encoded: &str) -> Result<(String, Vec<u8>)> {
+    if encoded.len() > BECH32_MAX_LENGTH {
+        return Err(AddressError::MalformedBech32("too long").into());
+    }
+    if !encoded.bytes().all(|c| c >= 33 && c <= 126) {
+        return Err(AddressError::MalformedBech32("invalid character").into());
+    }
+    let lowercase = encoded.to_ascii_lowercase();
+    if lowercase != encoded && encoded.to_ascii_uppercase() != encoded {
+        return Err(AddressError::MalformedBech32("mixed case").into());
+    }
+
+    let separator = match lowercase.rfind('1') {
+        Some(separator) => separator,
+        None => return Err(AddressError::MalformedBech32("missing separator").into()),
+    };
+    if separator == 0 || separator + 1 + BECH32_CHECKSUM_LENGTH > lowercase.len() {
+        return Err(AddressError::MalformedBech32("invalid separator position").into());
+    }
+    let hrp = &lowercase[..separator];
+
+    let mut data = Vec::with_capacity(lowercase.len() - separator - 1);
+    for c in lowercase[separator + 1..].bytes() {
+        match BECH32_CHARSET.iter().position(|d| *d == c) {
+            Some(value) => data.push(value as u8),
+            None => return Err(AddressError::MalformedBech32("invalid character").into()),
+        }
+    }
+
+    let mut values = bech32_expand_hrp(hrp);
+    values.extend_from_slice(&data);
+    if bech32_polymod(&values) != 1 {
+        return Err(AddressError::InvalidChecksum.into());
+    }
+    data.truncate(data.len() - BECH32_CHECKSUM_LENGTH);
+
+    Ok((hrp.to_owned(), data))
+}
+
+/// Regroup values of `from` bits into values of `to` bits.
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Result<Vec<u8>> {
+    let max = (1u32 << to) - 1;
+    let mut acc = 0u32;
+    let mut bits = 0u32;
+    let mut converted = Vec::with_capacity(data.len() * from as usize / to as usize + 1);
+
+    for value in data {
+        let value = u32::from(*value);
+        if value >> from != 0 {
+            return Err(AddressError::MalformedBech32("invalid data").into());
+        }
+        acc = (acc << from) | value;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            converted.push(((acc >> bits) & max) as u8);
+        }
+    }
+
+    if pad {
+        if bits > 0 {
+            converted.push(((acc << (to - bits)) & max) as u8);
+        }
+    } else if bits >= from || (acc << (to - bits)) & max != 0 {
+        return Err(AddressError::MalformedBech32("invalid padding").into());
+    }
+
+    Ok(converted)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_address() {
+        let vectors = vec![
+            (
+                PublicKey::from(vec![0u8; 32].as_slice()),
+                "00511b853162f9cc93e749e4244087c7c007680832",
+                "oasis1qpg3hpf3vtuueyl8f8jzgsy8clqqw6qgxgurwfy5",
+            ),
+            (
+                PublicKey::from((0u8..32).collect::<Vec<_>>().as_slice()),
+                "00ee65c18bab42d221fb03cd848841820e2fbfdc2a",
+                "oasis1qrhxtsvt4dpdyg0mq0xcfzzpsg8zl07u9gqk7gm4",
+            ),
+        ];
+
+        for (public_key, raw, bech32) in vectors {
+            let address = Address::from_public_key(&public_key);
+            assert_eq!(format!("{:x}", address), raw);
+            assert_eq!(address.version(), 0);
+            assert_eq!(address.to_bech32(), bech32);
+            assert_eq!(Address::from_bech32(bech32).unwrap(), address);
+            assert_eq!(
+                Address::from_bech32(&bech32.to_ascii_uppercase()).unwrap(),
+                address
+            );
+        }
+    }
+
+    #[test]
+    fn test_address_invalid() {
+        let invalid = vec![
+            // Mixed case.
+            "oasis1qpg3hpf3vtuueyl8f8jzgsy8clqqw6qgxgurwfY5",
+            // Invalid checksum.
+            "oasis1qpg3hpf3vtuueyl8f8jzgsy8clqqw6qgxgurwfy4",
+            // Invalid character.
+            "oasis1qpg3hpf3vtuueyl8f8jzgsy8clqqw6qgxgurwfyb",
+            // Missing separator.
+            "oasisqpg3hpf3vtuueyl8f8jzgsy8clqqw6qgxgurwfy5",
+            // Unexpected human readable part.
+            "a12uel5l",
+            // Empty.
+            "",
+        ];
+        for address in invalid {
+            assert!(Address::from_bech32(address).is_err(), "{}", address);
+        }
+
+        // Unsupported versions and lengths are rejected.
+        let mut address = Address::from_public_key(&PublicKey::default());
+        address.0[0] = 1;
+        assert!(Address::from_bech32(&address.to_bech32()).is_err());
+        let short = bech32_encode(
+            ADDRESS_BECH32_HRP,
+            &convert_bits(&[0u8; ADDRESS_SIZE - 1], 8, 5, true).unwrap(),
+        );
+        assert!(Address::from_bech32(&short).is_err());
+    }
+
+    #[test]
+    fn test_bech32() {
+        // Test vectors from BIP-173.
+        let valid = vec![
+            "A12UEL5L",
+            "a12uel5l",
+            "an83characterlonghumanreadablepartthatcontainsthenumber1andtheexcludedcharactersbio1tt5tgs",
+            "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw",
+            "split1checkupstagehandshakeupstreamerranterredcaperred2y9e3w",
+            "?1ezyfcl",
+        ];
+        for encoded in valid {
+            let (hrp, data) = bech32_decode(encoded).unwrap();
+            assert_eq!(bech32_encode(&hrp, &data), encoded.to_ascii_lowercase());
+        }
+
+        let invalid = vec![
+            "\x201nwldj5",
+            "\x7f1axkwrx",
+            "an84characterslonghumanreadablepartthatcontainsthenumber1andtheexcludedcharactersbio1569pvx",
+            "pzry9x0s0muk",
+            "1pzry9x0s0muk",
+            "x1b4n0q5v",
+            "li1dgmt3",
+            "de1lg7wt\u{ff}",
+            "A1G7SGD8",
+            "10a06t8",
+            "1qzzfhee",
+        ];
+        for encoded in invalid {
+            assert!(bech32_decode(encoded).is_err(), "{}", encoded);
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Pluggable AEAD backends.
+//!
+//! Deoxys-II is nonce misuse-resistant and constant time without hardware
+//! support, which makes it the default for anything running in an enclave.
+//! AES-GCM is considerably faster on CPUs with AES-NI, but catastrophically
+//! fails on nonce reuse, so it should only be selected where nonces are
+//! guaranteed to be unique and timing side channels are not a concern.
+use anyhow::Result;
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey};
+use thiserror::Error;
+use zeroize::Zeroize;
+
+use super::mrae::deoxysii::{self, DeoxysII};
+
+/// AEAD error.
+#[derive(Debug, Error)]
+pub enum AeadError {
+    #[error("invalid key size (expected: {0} got: {1})")]
+    InvalidKeySize(usize, usize),
+    #[error("invalid nonce size (expected: {0} got: {1})")]
+    InvalidNonceSize(usize, usize),
+    #[error("ciphertext is corrupted")]
+    Corrupted,
+}
+
+/// An authenticated encryption with associated data scheme.
+pub trait Aead: Send + Sync {
+    /// Size of nonces in bytes.
+    fn nonce_size(&self) -> usize;
+
+    /// Size of the authentication tag appended to ciphertexts in bytes.
+    fn tag_size(&self) -> usize;
+
+    /// Encrypt and authenticate the plaintext and authenticate the
+    /// additional data.
+    ///
+    /// The nonce must be unique for all time for a given key.
+    fn seal(&self, nonce: &[u8], plaintext: Vec<u8>, additional_data: Vec<u8>) -> Result<Vec<u8>>;
+
+    /// Authenticate and decrypt the ciphertext and authenticate the
+    /// additional data.
+    fn open(&self, nonce: &[u8], ciphertext: Vec<u8>, additional_data: Vec<u8>) -> Result<Vec<u8>>;
+}
+
+/// AEAD algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    /// Deoxys-II-256-128.
+    DeoxysII,
+    /// AES-256-GCM, hardware accelerated where supported.
+    Aes256Gcm,
+}
+
+impl Default for Algorithm {
+    fn default() -> Self {
+        Algorithm::DeoxysII
+    }
+}
+
+impl Algorithm {
+    /// Size of keys in bytes.
+    pub fn key_size(&self) -> usize {
+        match self {
+            Algorithm::DeoxysII => deoxysii::KEY_SIZE,
+            Algorithm::Aes256Gcm => aead::AES_256_GCM.key_len(),
+        }
+    }
+
+    /// Create a new instance of the algorithm with the given key.
+    pub fn new_aead(&self, key: &[u8]) -> Result<Box<dyn Aead>> {
+        if key.len() != self.key_size() {
+            return Err(AeadError::InvalidKeySize(self.key_size(), key.len()).into());
+        }
+
+        match self {
+            Algorithm::DeoxysII => {
+                let mut raw_key = [0u8; deoxysii::KEY_SIZE];
+                raw_key.copy_from_slice(key);
+                let d2 = DeoxysII::new(&raw_key);
+                raw_key.zeroize();
+
+                Ok(Box::new(d2))
+            }
+            Algorithm::Aes256Gcm => Ok(Box::new(Aes256Gcm::new(key)?)),
+        }
+    }
+}
+
+impl Aead for DeoxysII {
+    fn nonce_size(&self) -> usize {
+        deoxysii::NONCE_SIZE
+    }
+
+    fn tag_size(&self) -> usize {
+        deoxysii::TAG_SIZE
+    }
+
+    fn seal(&self, nonce: &[u8], plaintext: Vec<u8>, additional_data: Vec<u8>) -> Result<Vec<u8>> {
+        let nonce = deoxysii_nonce(nonce)?;
+        Ok(DeoxysII::seal(self, &nonce, plaintext, additional_data))
+    }
+
+    fn open(&self, nonce: &[u8], ciphertext: Vec<u8>, additional_data: Vec<u8>) -> Result<Vec<u8>> {
+        let nonce = deoxysii_nonce(nonce)?;
+        DeoxysII::open(self, &nonce, ciphertext, additional_data)
+            .map_err(|_| AeadError::Corrupted.into())
+    }
+}
+
+fn deoxysii_nonce(nonce: &[u8]) -> Result<[u8; deoxysii::NONCE_SIZE]> {
+    if nonce.len() != deoxysii::NONCE_SIZE {
+        return Err(AeadError::InvalidNonceSize(deoxysii::NONCE_SIZE, nonce.len()).into());
+    }
+
+    let mut raw_nonce = [0u8; deoxysii::NONCE_SIZE];
+    raw_nonce.copy_from_slice(nonce);
+    Ok(raw_nonce)
+}
+
+/// AES-256-GCM.
+pub struct Aes256Gcm {
+    key: LessSafeKey,
+}
+
+impl Aes256Gcm {
+    /// Create a new instance with the given key.
+    pub fn new(key: &[u8]) -> Result<Self> {
+        let key = UnboundKey::new(&aead::AES_256_GCM, key)
+            .map_err(|_| AeadError::InvalidKeySize(aead::AES_256_GCM.key_len(), key.len()))?;
+
+        Ok(Self {
+            key: LessSafeKey::new(key),
+        })
+    }
+
+    fn nonce(nonce: &[u8]) -> Result<Nonce> {
+        Nonce::try_assume_unique_for_key(nonce)
+            .map_err(|_| AeadError::InvalidNonceSize(aead::NONCE_LEN, nonce.len()).into())
+    }
+}
+
+impl Aead for Aes256Gcm {
+    fn nonce_size(&self) -> usize {
+        aead::NONCE_LEN
+    }
+
+    fn tag_size(&self) -> usize {
+        self.key.algorithm().tag_len()
+    }
+
+    fn seal(
+        &self,
+        nonce: &[u8],
+        mut plaintext: Vec<u8>,
+        additional_data: Vec<u8>,
+    ) -> Result<Vec<u8>> {
+        let nonce = Self::nonce(nonce)?;
+        self.key
+            .seal_in_place_append_tag(nonce, Aad::from(additional_data), &mut plaintext)
+            .map_err(|_| AeadError::Corrupted)?;
+
+        Ok(plaintext)
+    }
+
+    fn open(
+        &self,
+        nonce: &[u8],
+        mut ciphertext: Vec<u8>,
+        additional_data: Vec<u8>,
+    ) -> Result<Vec<u8>> {
+        let nonce = Self::nonce(nonce)?;
+        let length = self
+            .key
+            .open_in_place(nonce, Aad::from(additional_data), &mut ciphertext)
+            .map_err(|_| AeadError::Corrupted)?
+            .len();
+        ciphertext.truncate(length);
+
+        Ok(ciphertext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use self::test::{black_box, Bencher};
+    use super::*;
+
+    const ALGORITHMS: &[Algorithm] = &[Algorithm::DeoxysII, Algorithm::Aes256Gcm];
+
+    fn new_aead(algorithm: Algorithm) -> Box<dyn Aead> {
+        algorithm
+            .new_aead(&vec![42u8; algorithm.key_size()])
+            .unwrap()
+    }
+
+    #[test]
+    fn test_aead_round_trip() {
+        for algorithm in ALGORITHMS {
+            let aead = new_aead(*algorithm);
+            let nonce = vec![1u8; aead.nonce_size()];
+            let text = b"This is a test!".to_vec();
+            let aad = vec![42u8; 10];
+
+            let sealed = aead.seal(&nonce, text.clone(), aad.clone()).unwrap();
+            assert_eq!(sealed.len(), text.len() + aead.tag_size());
+            let opened = aead.open(&nonce, sealed.clone(), aad.clone()).unwrap();
+            assert_eq!(opened, text);
+
+            // Tampering is detected.
+            let mut tampered = sealed.clone();
+            tampered[0] ^= 1;
+            assert!(aead.open(&nonce, tampered, aad.clone()).is_err());
+            assert!(aead.open(&nonce, sealed, vec![]).is_err());
+
+            // Invalid sizes are rejected.
+            assert!(aead.seal(&nonce[1..], text, aad).is_err());
+            assert!(algorithm.new_aead(&[0u8; 16]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_deoxysii_compatibility() {
+        // The trait must not change the Deoxys-II encoding.
+        let key = [7u8; deoxysii::KEY_SIZE];
+        let nonce = [1u8; deoxysii::NONCE_SIZE];
+        let d2 = DeoxysII::new(&key);
+        let aead = Algorithm::DeoxysII.new_aead(&key).unwrap();
+
+        assert_eq!(
+            aead.seal(&nonce, b"text".to_vec(), b"aad".to_vec())
+                .unwrap(),
+            d2.seal(&nonce, b"text".to_vec(), b"aad".to_vec())
+        );
+    }
+
+    fn bench_seal_4096(b: &mut Bencher, algorithm: Algorithm) {
+        let aead = new_aead(algorithm);
+        let nonce = vec![0u8; aead.nonce_size()];
+        let text = [0u8; 4096];
+        let aad = [0u8; 64];
+
+        b.iter(|| {
+            let _sealed = black_box(aead.seal(&nonce, text.to_vec(), aad.to_vec()));
+        });
+    }
+
+    #[bench]
+    fn bench_deoxysii_seal_4096(b: &mut Bencher) {
+        bench_seal_4096(b, Algorithm::DeoxysII)
+    }
+
+    #[bench]
+    fn bench_aes256gcm_seal_4096(b: &mut Bencher) {
+        bench_seal_4096(b, Algorithm::Aes256Gcm)
+    }
+}
@@ -0,0 +1,250 @@
+//! Signature context registry.
+//!
+//! All contexts used for signing are declared in one place using the
+//! `signature_contexts!` macro, which also generates a test checking that
+//! the declared contexts are well formed and unique.
+//!
+//! Contexts declared as chain separated can only be used for signing after
+//! being combined with the chain context of the network, so signatures made
+//! on one network cannot be replayed on another one.
+//!
+//! # Note
+//!
+//! The rules **MUST** be kept in sync with go/common/crypto/signature.
+use std::{collections::HashSet, ops::Deref};
+
+use thiserror::Error;
+
+/// Maximum size of a signature context in bytes.
+pub const CONTEXT_MAX_SIZE: usize = 255;
+/// Maximum size of a chain context in bytes.
+pub const CHAIN_CONTEXT_MAX_SIZE: usize = 64;
+/// Separator between a chain separated context and the chain context.
+pub const CHAIN_CONTEXT_SEPARATOR: &'static str = " for chain ";
+
+/// Declare signature contexts.
+///
+/// Each declaration is either a `Context`, which is used for signing as is,
+/// or a `ChainContext`, which must be combined with a chain context first.
+/// The macro additionally declares `REGISTERED_CONTEXTS`, listing all of the
+/// declared contexts, and a test checking them.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// signature_contexts! {
+///     /// Context used for signing widgets.
+///     pub const WIDGET_CONTEXT: Context = "oasis-core/widget: widget";
+///     /// Context used for signing widget transfers.
+///     pub const WIDGET_TRANSFER_CONTEXT: ChainContext = "oasis-core/widget: transfer";
+/// }
+/// ```
+#[macro_export]
+macro_rules! signature_contexts {
+    (
+        $(
+            $(#[$attr:meta])*
+            $vis:vis const $name:ident: $kind:ident = $context:expr;
+        )*
+    ) => {
+        $(
+            $(#[$attr])*
+            $vis const $name: $crate::common::crypto::context::$kind =
+                $crate::common::crypto::context::$kind::new($context);
+        )*
+
+        /// All signature contexts declared in this module.
+        pub const REGISTERED_CONTEXTS: &[$crate::common::crypto::context::Registration] = &[
+            $(
+                $crate::common::crypto::context::Registration {
+                    name: stringify!($name),
+                    context: $context,
+                    chain_separated: $crate::common::crypto::context::$kind::CHAIN_SEPARATED,
+                },
+            )*
+        ];
+
+        #[cfg(test)]
+        #[test]
+        fn test_registered_contexts() {
+            $crate::common::crypto::context::check_contexts(REGISTERED_CONTEXTS).unwrap();
+        }
+    };
+}
+
+signature_contexts! {
+    /// RAK signature session binding context.
+    pub const RAK_SESSION_BINDING_CONTEXT: Context = "EkRakRpc";
+    /// Compute results header signature context.
+    pub const COMPUTE_RESULTS_HEADER_CONTEXT: Context =
+        "oasis-core/roothash: compute results header";
+    /// Storage receipt signature context.
+    pub const STORAGE_RECEIPT_CONTEXT: ChainContext = "oasis-core/storage: receipt";
+    /// Consensus transaction signature context.
+    pub const CONSENSUS_TX_CONTEXT: ChainContext = "oasis-core/consensus: tx";
+    /// Key manager policy signature context.
+    pub const KEYMANAGER_POLICY_CONTEXT: Context = "oasis-core/keymanager: policy";
+    /// Key manager init response signature context.
+    pub const KEYMANAGER_INIT_RESPONSE_CONTEXT: Context = "oasis-core/keymanager: init response";
+    /// Key manager public key signature context.
+    pub const KEYMANAGER_PUBLIC_KEY_CONTEXT: Context = "EkKmPubK";
+}
+
+/// Signature context error.
+#[derive(Debug, Error)]
+pub enum ContextError {
+    #[error("signature: malformed context: '{0}'")]
+    Malformed(String),
+    #[error("signature: context must not include the chain separator: '{0}'")]
+    IncludesChainSeparator(String),
+    #[error("signature: context already registered: '{0}'")]
+    AlreadyRegistered(String),
+}
+
+/// A signature context used for signing as is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Context(&'static str);
+
+impl Context {
+    /// Whether the context requires chain domain separation.
+    pub const CHAIN_SEPARATED: bool = false;
+
+    /// Create a new signature context.
+    ///
+    /// Contexts should be declared using `signature_contexts!` instead.
+    pub const fn new(context: &'static str) -> Self {
+        Context(context)
+    }
+
+    /// The raw context.
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
+}
+
+impl Deref for Context {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl AsRef<[u8]> for Context {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+/// A signature context requiring chain domain separation.
+///
+/// The context can only be used for signing through `for_chain`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChainContext(&'static str);
+
+impl ChainContext {
+    /// Whether the context requires chain domain separation.
+    pub const CHAIN_SEPARATED: bool = true;
+
+    /// Create a new chain separated signature context.
+    ///
+    /// Contexts should be declared using `signature_contexts!` instead.
+    pub const fn new(context: &'static str) -> Self {
+        ChainContext(context)
+    }
+
+    /// The raw context, without chain domain separation.
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
+
+    /// Signature context for the given chain context.
+    pub fn for_chain(&self, chain_context: &str) -> Vec<u8> {
+        format!("{}{}{}", self.0, CHAIN_CONTEXT_SEPARATOR, chain_context).into_bytes()
+    }
+}
+
+/// A declared signature context.
+#[derive(Clone, Copy, Debug)]
+pub struct Registration {
+    /// Name of the declared constant.
+    pub name: &'static str,
+    /// The raw context.
+    pub context: &'static str,
+    /// Whether the context requires chain domain separation.
+    pub chain_separated: bool,
+}
+
+/// Check that the given contexts are well formed and unique.
+///
+/// Contexts must be non-empty and short enough to fit the Ed25519 context
+/// size limit even after chain domain separation. To avoid conflicts with
+/// chain separated contexts, no context may include the chain separator.
+pub fn check_contexts(registrations: &[Registration]) -> Result<(), ContextError> {
+    let mut registered = HashSet::new();
+    for registration in registrations {
+        let context = registration.context;
+
+        let mut size = context.len();
+        if registration.chain_separated {
+            size += CHAIN_CONTEXT_SEPARATOR.len() + CHAIN_CONTEXT_MAX_SIZE;
+        }
+        if context.is_empty() || size > CONTEXT_MAX_SIZE {
+            return Err(ContextError::Malformed(context.to_owned()));
+        }
+        if context.contains(CHAIN_CONTEXT_SEPARATOR) {
+            return Err(ContextError::IncludesChainSeparator(context.to_owned()));
+        }
+        if !registered.insert(context) {
+            return Err(ContextError::AlreadyRegistered(context.to_owned()));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn registration(context: &'static str, chain_separated: bool) -> Registration {
+        Registration {
+            name: "TEST_CONTEXT",
+            context,
+            chain_separated,
+        }
+    }
+
+    #[test]
+    fn test_check_contexts() {
+        assert!(check_contexts(&[
+            registration("oasis-core/test: a", false),
+            registration("oasis-core/test: b", true),
+        ])
+        .is_ok());
+
+        // Duplicates are rejected, regardless of chain separation.
+        assert!(check_contexts(&[
+            registration("oasis-core/test: a", false),
+            registration("oasis-core/test: a", true),
+        ])
+        .is_err());
+        // Contexts may not be empty.
+        assert!(check_contexts(&[registration("", false)]).is_err());
+        // Contexts may not be too long.
+        let long: &'static str = Box::leak("a".repeat(CONTEXT_MAX_SIZE).into_boxed_str());
+        assert!(check_contexts(&[registration(long, false)]).is_ok());
+        assert!(check_contexts(&[registration(long, true)]).is_err());
+        // Contexts may not include the chain separator.
+        assert!(check_contexts(&[registration("oasis-core/test: a for chain b", false)]).is_err());
+    }
+
+    #[test]
+    fn test_chain_context() {
+        assert_eq!(
+            STORAGE_RECEIPT_CONTEXT.for_chain("test chain"),
+            b"oasis-core/storage: receipt for chain test chain".to_vec()
+        );
+        assert_eq!(&*RAK_SESSION_BINDING_CONTEXT, b"EkRakRpc");
+    }
+}
@@ -0,0 +1,131 @@
+//! Checks for secret key material left behind in freed memory.
+//!
+//! Only available in debug builds. A test binary installs the
+//! `ScanningAllocator` as its global allocator and runs the code under test
+//! via `scan_freed`, which counts the heap blocks freed in the meantime that
+//! still contain a random marker. Using the marker as key material thus shows
+//! whether the key was zeroized before its memory was released.
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    slice,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use lazy_static::lazy_static;
+use rand::{rngs::OsRng, Rng};
+
+/// Size of the marker passed to the code under test.
+pub const MARKER_SIZE: usize = 32;
+
+/// Word the marker is made of.
+static MARKER_WORD: AtomicU64 = AtomicU64::new(0);
+/// Whether freed blocks are currently being scanned.
+static SCANNING: AtomicBool = AtomicBool::new(false);
+/// Number of freed blocks containing the marker.
+static FOUND: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    // Scans share the global state above, so only one may run at a time.
+    static ref SCAN_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// A global allocator which scans blocks for the marker before freeing them.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// #[cfg(all(test, debug_assertions))]
+/// #[global_allocator]
+/// static ALLOCATOR: ScanningAllocator = ScanningAllocator;
+/// ```
+pub struct ScanningAllocator;
+
+unsafe impl GlobalAlloc for ScanningAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if SCANNING.load(Ordering::SeqCst) {
+            let block = slice::from_raw_parts(ptr, layout.size());
+            if contains_marker(block, MARKER_WORD.load(Ordering::SeqCst)) {
+                FOUND.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Run the given function with a fresh marker and return the number of heap
+/// blocks freed during the run that still contained (any 8-byte part of) the
+/// marker.
+///
+/// Blocks are only scanned if the `ScanningAllocator` is the global allocator.
+pub fn scan_freed<F>(f: F) -> usize
+where
+    F: FnOnce(&[u8; MARKER_SIZE]),
+{
+    let _guard = SCAN_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+
+    let mut rng = OsRng {};
+    let word: u64 = rng.gen();
+    let mut marker = [0u8; MARKER_SIZE];
+    for chunk in marker.chunks_mut(8) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+
+    MARKER_WORD.store(word, Ordering::SeqCst);
+    FOUND.store(0, Ordering::SeqCst);
+    SCANNING.store(true, Ordering::SeqCst);
+    f(&marker);
+    SCANNING.store(false, Ordering::SeqCst);
+
+    FOUND.load(Ordering::SeqCst)
+}
+
+fn contains_marker(block: &[u8], word: u64) -> bool {
+    let word = word.to_le_bytes();
+    block.windows(word.len()).any(|window| window == word)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        super::{signature, x25519},
+        *,
+    };
+
+    #[global_allocator]
+    static ALLOCATOR: ScanningAllocator = ScanningAllocator;
+
+    impl_secret_bytes!(TestSecret, MARKER_SIZE, "test secret");
+
+    #[test]
+    fn test_scan_freed() {
+        // Plain byte arrays are not zeroized.
+        assert_eq!(scan_freed(|marker| drop(Box::new(*marker))), 1);
+        assert_eq!(scan_freed(|marker| drop(marker.to_vec())), 1);
+        assert_eq!(scan_freed(|_| drop(vec![0u8; MARKER_SIZE])), 0);
+    }
+
+    #[test]
+    fn test_secret_bytes_zeroized() {
+        assert_eq!(scan_freed(|marker| drop(Box::new(TestSecret(*marker)))), 0);
+    }
+
+    #[test]
+    fn test_private_keys_zeroized() {
+        assert_eq!(
+            scan_freed(|marker| drop(Box::new(x25519::PrivateKey::from_bytes(*marker)))),
+            0
+        );
+        assert_eq!(
+            scan_freed(|marker| drop(Box::new(signature::PrivateKey::from_bytes(marker.to_vec())))),
+            0
+        );
+    }
+}
@@ -1,5 +1,13 @@
 //! Cryptographic primitives.
 
-pub mod hash;
+pub mod address;
+pub mod aead;
+pub mod context;
+#[cfg(debug_assertions)]
+pub mod memory;
 pub mod mrae;
 pub mod signature;
+pub mod x25519;
+
+// Re-exports.
+pub use oasis_core_common::crypto::{blake3, hash};
@@ -8,14 +8,15 @@ use super::{
     x25519_dalek,
 };
 
-use failure::Fallible;
+use anyhow::Result;
 use rand::rngs::OsRng;
+use zeroize::Zeroize;
 
 type Kdf = Hmac<Sha512Trunc256>;
 
 /// Derives a MRAE AEAD symmetric key suitable for use with the asymmetric
 /// box primitives from the provided X25519 public and private keys.
-fn derive_symmetric_key(public: &[u8; 32], private: &[u8; 32]) -> [u8; KEY_SIZE] {
+pub(crate) fn derive_symmetric_key(public: &[u8; 32], private: &[u8; 32]) -> [u8; KEY_SIZE] {
     let public = x25519_dalek::PublicKey::from(public.clone());
     let private = x25519_dalek::StaticSecret::from(private.clone());
 
@@ -54,10 +55,11 @@ pub fn box_seal(
     additional_data: Vec<u8>,
     peers_public_key: &[u8; 32],
     private_key: &[u8; 32],
-) -> Fallible<Vec<u8>> {
-    let key = derive_symmetric_key(peers_public_key, private_key);
+) -> Result<Vec<u8>> {
+    let mut key = derive_symmetric_key(peers_public_key, private_key);
 
     let d2 = DeoxysII::new(&key);
+    key.zeroize();
 
     Ok(d2.seal(nonce, plaintext, additional_data))
 }
@@ -73,10 +75,11 @@ pub fn box_open(
     additional_data: Vec<u8>,
     peers_public_key: &[u8; 32],
     private_key: &[u8; 32],
-) -> Fallible<Vec<u8>> {
-    let key = derive_symmetric_key(peers_public_key, private_key);
+) -> Result<Vec<u8>> {
+    let mut key = derive_symmetric_key(peers_public_key, private_key);
 
     let d2 = DeoxysII::new(&key);
+    key.zeroize();
 
     d2.open(nonce, ciphertext, additional_data)
         .map_err(|err| err.into())
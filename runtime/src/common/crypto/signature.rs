@@ -1,34 +1,53 @@
 //! Signature types.
-use std::io::Cursor;
+use std::{collections::HashSet, str::FromStr};
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use anyhow::Result;
 use ed25519_dalek;
-use failure::Fallible;
+use hmac::{Hmac, Mac};
 use rand::rngs::OsRng;
+use serde::de::DeserializeOwned;
 use serde_derive::{Deserialize, Serialize};
+use sha2::Sha512;
+use thiserror::Error;
 use zeroize::Zeroize;
 
-use super::hash::Hash;
+use super::{super::cbor, hash::Hash};
 
-impl_bytes!(
-    PublicKey,
-    ed25519_dalek::PUBLIC_KEY_LENGTH,
-    "An Ed25519 public key."
-);
+// Re-exports.
+pub use oasis_core_common::crypto::signature::{
+    PublicKey, Signature, SignatureBundle, SignatureError,
+};
 
-/// Signature error.
-#[derive(Debug, Fail)]
-enum SignatureError {
-    #[fail(display = "signature malleability check failed")]
-    MalleabilityError,
+/// Key derivation error.
+#[derive(Debug, Error)]
+pub enum KeyDerivationError {
+    #[error("invalid derivation path: {0}")]
+    InvalidPath(String),
+    #[error("seed must be between 16 and 64 bytes")]
+    InvalidSeedLength,
 }
 
-static CURVE_ORDER: &'static [u64] = &[
-    0x1000000000000000,
-    0,
-    0x14def9dea2f79cd6,
-    0x5812631a5cf5d3ed,
-];
+/// Signer set error.
+#[derive(Debug, Error)]
+pub enum SignerSetError {
+    #[error("threshold must be positive")]
+    ZeroThreshold,
+    #[error("threshold {0} exceeds the number of signers {1}")]
+    ThresholdTooLarge(usize, usize),
+}
+
+/// Key of the SLIP-10 master key derivation for ed25519.
+const SLIP10_CURVE: &'static [u8] = b"ed25519 seed";
+/// Offset of hardened child indices.
+const HARDENED: u32 = 0x8000_0000;
+/// BIP-44 purpose.
+const BIP44_PURPOSE: u32 = 44;
+/// SLIP-44 coin type used by ADR 0008.
+const ADR8_COIN_TYPE: u32 = 474;
+/// Number of PBKDF2 rounds used to derive a seed from a mnemonic.
+const MNEMONIC_ROUNDS: usize = 2048;
+
+type HmacSha512 = Hmac<Sha512>;
 
 /// An Ed25519 private key.
 pub struct PrivateKey(pub ed25519_dalek::Keypair);
@@ -71,14 +90,144 @@ impl PrivateKey {
         PrivateKey(ed25519_dalek::Keypair { secret, public: pk })
     }
 
+    /// Derive a private key from a seed along the given path, following
+    /// SLIP-10.
+    pub fn from_seed(seed: &[u8], path: &DerivationPath) -> Result<Self> {
+        if seed.len() < 16 || seed.len() > 64 {
+            return Err(KeyDerivationError::InvalidSeedLength.into());
+        }
+
+        let (mut key, mut chain_code) = slip10_derive(SLIP10_CURVE, &[seed]);
+        for index in path.indices() {
+            let (child_key, child_chain_code) = slip10_derive(
+                &chain_code,
+                &[&[0u8][..], &key[..], &index.to_be_bytes()[..]],
+            );
+            key.zeroize();
+            chain_code.zeroize();
+            key = child_key;
+            chain_code = child_chain_code;
+        }
+        chain_code.zeroize();
+
+        let private_key = Self::from_bytes(key.to_vec());
+        key.zeroize();
+        Ok(private_key)
+    }
+
+    /// Derive a private key from a BIP-39 mnemonic and passphrase along the
+    /// given path.
+    ///
+    /// The mnemonic and passphrase must be NFKD normalized, which is always
+    /// the case for the English wordlist. The mnemonic's checksum is not
+    /// verified.
+    pub fn from_mnemonic(mnemonic: &str, passphrase: &str, path: &DerivationPath) -> Self {
+        let mut seed = mnemonic_to_seed(mnemonic, passphrase);
+        let key = Self::from_seed(&seed, path).expect("mnemonic seeds are 64 bytes");
+        seed.zeroize();
+        key
+    }
+
     /// Returns the public key.
     pub fn public_key(&self) -> PublicKey {
         PublicKey(self.0.public.to_bytes())
     }
 }
 
+/// A SLIP-10 key derivation path.
+///
+/// Only hardened derivation is defined for ed25519, so all indices are
+/// hardened.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DerivationPath(Vec<u32>);
+
+impl DerivationPath {
+    /// The ADR 0008 path of the given account, `m/44'/474'/account'`.
+    pub fn adr8(account: u32) -> Self {
+        Self(vec![
+            BIP44_PURPOSE | HARDENED,
+            ADR8_COIN_TYPE | HARDENED,
+            account | HARDENED,
+        ])
+    }
+
+    /// Child indices along the path, including the hardened offset.
+    pub fn indices(&self) -> &[u32] {
+        &self.0
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = KeyDerivationError;
+
+    /// Parse a path of the form `m/44'/474'/0'`, where hardened indices are
+    /// marked with either `'` or `h`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || KeyDerivationError::InvalidPath(s.to_owned());
+        let mut components = s.split('/');
+        if components.next() != Some("m") {
+            return Err(invalid());
+        }
+
+        components
+            .map(|component| {
+                let index = if component.ends_with('\'') || component.ends_with('h') {
+                    &component[..component.len() - 1]
+                } else {
+                    return Err(invalid());
+                };
+                match index.parse::<u32>() {
+                    Ok(index) if index < HARDENED => Ok(index | HARDENED),
+                    _ => Err(invalid()),
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(DerivationPath)
+    }
+}
+
+/// Derive a seed from a BIP-39 mnemonic and passphrase.
+pub fn mnemonic_to_seed(mnemonic: &str, passphrase: &str) -> [u8; 64] {
+    // PBKDF2-HMAC-SHA512 with a single output block.
+    let salt = format!("mnemonic{}", passphrase);
+    let prf = HmacSha512::new_varkey(mnemonic.as_bytes()).expect("Hmac::new_varkey");
+
+    let mut mac = prf.clone();
+    mac.input(salt.as_bytes());
+    mac.input(&[0, 0, 0, 1]);
+    let mut block = mac.result().code();
+    let mut seed = [0u8; 64];
+    seed.copy_from_slice(&block);
+
+    for _ in 1..MNEMONIC_ROUNDS {
+        let mut mac = prf.clone();
+        mac.input(&block);
+        block = mac.result().code();
+        for (seed, block) in seed.iter_mut().zip(block.iter()) {
+            *seed ^= block;
+        }
+    }
+
+    seed
+}
+
+/// A single SLIP-10 derivation step, returning the key and chain code.
+fn slip10_derive(key: &[u8], data: &[&[u8]]) -> ([u8; 32], [u8; 32]) {
+    let mut mac = HmacSha512::new_varkey(key).expect("Hmac::new_varkey");
+    for data in data {
+        mac.input(data);
+    }
+    let digest = mac.result().code();
+
+    let mut key = [0u8; 32];
+    let mut chain_code = [0u8; 32];
+    key.copy_from_slice(&digest[..32]);
+    chain_code.copy_from_slice(&digest[32..]);
+    (key, chain_code)
+}
+
 impl Signer for PrivateKey {
-    fn sign(&self, context: &[u8], message: &[u8]) -> Fallible<Signature> {
+    fn sign(&self, context: &[u8], message: &[u8]) -> Result<Signature> {
         // TODO/#2103: Replace this with Ed25519ctx.
         let digest = Hash::digest_bytes_list(&[context, message]);
 
@@ -86,135 +235,153 @@ impl Signer for PrivateKey {
     }
 }
 
-impl_bytes!(Signature, 64, "An Ed25519 signature.");
+/// A abstract signer.
+pub trait Signer: Send + Sync {
+    /// Generates a signature over the context and message.
+    fn sign(&self, context: &[u8], message: &[u8]) -> Result<Signature>;
+}
+
+/// A set of public keys of which a threshold must sign.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignerSet {
+    signers: HashSet<PublicKey>,
+    threshold: usize,
+}
 
-impl Signature {
-    /// Verify signature.
-    pub fn verify(&self, pk: &PublicKey, context: &[u8], message: &[u8]) -> Fallible<()> {
-        // TODO/#2103: Replace this with Ed25519ctx.
-        let pk = ed25519_dalek::PublicKey::from_bytes(pk.as_ref()).unwrap();
-        let digest = Hash::digest_bytes_list(&[context, message]);
-        let sig_slice = self.as_ref();
-        let sig = ed25519_dalek::Signature::from_bytes(sig_slice).unwrap();
+impl SignerSet {
+    /// Create a new set, requiring signatures from at least `threshold` of
+    /// the given signers.
+    ///
+    /// Duplicate signers are only counted once.
+    pub fn new(signers: Vec<PublicKey>, threshold: usize) -> Result<Self> {
+        let signers: HashSet<_> = signers.into_iter().collect();
+        if threshold == 0 {
+            return Err(SignerSetError::ZeroThreshold.into());
+        }
+        if threshold > signers.len() {
+            return Err(SignerSetError::ThresholdTooLarge(threshold, signers.len()).into());
+        }
+
+        Ok(Self { signers, threshold })
+    }
+
+    /// Number of signatures required.
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
 
-        // ed25519-dalek does not enforce the RFC 8032 mandated constraint
-        // that s is in range [0, order), so signatures are malleable.
-        if !sc_minimal(&sig_slice[32..]) {
-            return Err(SignatureError::MalleabilityError.into());
+    /// Check whether the given public key is in the set.
+    pub fn contains(&self, public_key: &PublicKey) -> bool {
+        self.signers.contains(public_key)
+    }
+
+    /// Verify that enough signers of the set signed the message.
+    ///
+    /// All signatures must be valid, including those by public keys which
+    /// are not in the set. Returns the signers of the set which signed.
+    pub fn verify(
+        &self,
+        context: &[u8],
+        message: &[u8],
+        signatures: &[SignatureBundle],
+    ) -> Result<HashSet<PublicKey>> {
+        // Signatures are verified serially, as all nodes must agree on
+        // whether they are valid (see PublicKey::verify_batch).
+        let mut signed = HashSet::new();
+        for signature in signatures {
+            let public_key = signature
+                .public_key
+                .ok_or(SignatureError::MissingPublicKey)?;
+            signature.signature.verify(&public_key, context, message)?;
+
+            if self.signers.contains(&public_key) {
+                signed.insert(public_key);
+            }
         }
 
-        Ok(pk.verify(digest.as_ref(), &sig)?)
+        if signed.len() < self.threshold {
+            return Err(
+                SignatureError::InsufficientSignatures(signed.len(), self.threshold).into(),
+            );
+        }
+        Ok(signed)
     }
 }
 
-/// A signature bundled with a public key.
+/// A blob signed by multiple public keys.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct SignatureBundle {
-    /// Public key that produced the signature.
-    pub public_key: Option<PublicKey>,
-    /// Actual signature.
-    pub signature: Signature,
+pub struct MultiSigned {
+    /// CBOR-encoded signed value.
+    #[serde(rename = "untrusted_raw_value", with = "serde_bytes")]
+    pub blob: Vec<u8>,
+    /// Signatures over the blob.
+    pub signatures: Vec<SignatureBundle>,
 }
 
-/// A abstract signer.
-pub trait Signer: Send + Sync {
-    /// Generates a signature over the context and message.
-    fn sign(&self, context: &[u8], message: &[u8]) -> Fallible<Signature>;
-}
+impl MultiSigned {
+    /// Create a new blob without any signatures.
+    pub fn new<T: serde::Serialize>(value: &T) -> Self {
+        Self {
+            blob: cbor::to_vec(value),
+            signatures: vec![],
+        }
+    }
 
-// Check if s < L, per RFC 8032, inspired by the Go runtime library's version
-// of this check.
-fn sc_minimal(raw_s: &[u8]) -> bool {
-    let mut rd = Cursor::new(raw_s);
-    let mut s = [0u64; 4];
-
-    // Read the raw scalar into limbs, and reverse it, because the raw
-    // representation is little-endian.
-    rd.read_u64_into::<LittleEndian>(&mut s[..]).unwrap();
-    s.reverse();
-
-    // Compare each limb, from most significant to least.
-    for i in 0..4 {
-        if s[i] > CURVE_ORDER[i] {
-            return false;
-        } else if s[i] < CURVE_ORDER[i] {
-            return true;
+    /// Sign the blob, replacing any previous signature by the same key.
+    pub fn sign(
+        &mut self,
+        signer: &dyn Signer,
+        public_key: PublicKey,
+        context: &[u8],
+    ) -> Result<()> {
+        let signature = signer.sign(context, &self.blob)?;
+        self.add_signature(SignatureBundle {
+            public_key: Some(public_key),
+            signature,
+        });
+        Ok(())
+    }
+
+    /// Add the signatures of another copy of the same blob, e.g., one
+    /// signed independently by other parties.
+    pub fn merge(&mut self, other: MultiSigned) -> Result<()> {
+        if self.blob != other.blob {
+            return Err(SignatureError::BlobMismatch.into());
+        }
+        for signature in other.signatures {
+            self.add_signature(signature);
         }
+        Ok(())
     }
 
-    // The scalar is equal to the order of the curve.
-    false
+    /// Check whether the blob includes a signature by the given key.
+    ///
+    /// This does not verify the signature.
+    pub fn is_signed_by(&self, public_key: &PublicKey) -> bool {
+        self.signatures
+            .iter()
+            .any(|signature| signature.public_key.as_ref() == Some(public_key))
+    }
+
+    /// Verify the signatures against the given signer set and decode the
+    /// blob.
+    pub fn open<T: DeserializeOwned>(&self, context: &[u8], signers: &SignerSet) -> Result<T> {
+        signers.verify(context, &self.blob, &self.signatures)?;
+        cbor::from_slice_canonical(&self.blob)
+    }
+
+    fn add_signature(&mut self, signature: SignatureBundle) {
+        self.signatures
+            .retain(|existing| existing.public_key != signature.public_key);
+        self.signatures.push(signature);
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use rustc_hex::FromHex;
 
-    #[test]
-    fn test_sc_minimal() {
-        // L - 2^0
-        assert!(sc_minimal(&[
-            0xec, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
-            0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x10
-        ]));
-
-        // L - 2^64
-        assert!(sc_minimal(&[
-            0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd5, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
-            0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x10
-        ]));
-
-        // L - 2^192
-        assert!(sc_minimal(&[
-            0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd5, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
-            0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff,
-            0xff, 0xff, 0xff, 0x0f,
-        ]));
-
-        // L
-        assert!(!sc_minimal(&[
-            0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
-            0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x10
-        ]));
-
-        // L + 2^0
-        assert!(!sc_minimal(&[
-            0xef, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
-            0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x10
-        ]));
-
-        // L + 2^64
-        assert!(!sc_minimal(&[
-            0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd7, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
-            0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x10
-        ]));
-
-        // L + 2^128
-        assert!(!sc_minimal(&[
-            0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
-            0xde, 0x14, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x10
-        ]));
-
-        // L + 2^192
-        assert!(!sc_minimal(&[
-            0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
-            0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x10
-        ]));
-
-        // Scalar from the go runtime's test case.
-        assert!(!sc_minimal(&[
-            0x67, 0x65, 0x4b, 0xce, 0x38, 0x32, 0xc2, 0xd7, 0x6f, 0x8f, 0x6f, 0x5d, 0xaf, 0xc0,
-            0x8d, 0x93, 0x39, 0xd4, 0xee, 0xf6, 0x76, 0x57, 0x33, 0x36, 0xa5, 0xc5, 0x1e, 0xb6,
-            0xf9, 0x46, 0xb3, 0x1d,
-        ]))
-    }
+    use super::*;
 
     #[test]
     fn test_private_key_to_bytes() {
@@ -235,4 +402,121 @@ mod tests {
     fn test_private_key_to_bytes_malformed_b() {
         PrivateKey::from_bytes(vec![1, 2, 3]);
     }
+
+    #[test]
+    fn test_multi_signed() {
+        let context = b"oasis-core/test: multi signed";
+        let keys: Vec<_> = (0..3)
+            .map(|i| PrivateKey::from_test_seed(format!("multi signed {}", i)))
+            .collect();
+        let public_keys: Vec<_> = keys.iter().map(|key| key.public_key()).collect();
+        let signers = SignerSet::new(public_keys.clone(), 2).unwrap();
+        let outsider = PrivateKey::from_test_seed("outsider".to_owned());
+
+        let mut signed = MultiSigned::new(&42u64);
+        signed
+            .sign(&keys[0], keys[0].public_key(), context)
+            .unwrap();
+        signed
+            .sign(&keys[0], keys[0].public_key(), context)
+            .unwrap();
+        signed
+            .sign(&outsider, outsider.public_key(), context)
+            .unwrap();
+        assert_eq!(signed.signatures.len(), 2);
+        assert!(signed.is_signed_by(&keys[0].public_key()));
+        assert!(!signed.is_signed_by(&keys[1].public_key()));
+
+        // Duplicate and outside signatures do not count towards the threshold.
+        assert!(signed.open::<u64>(context, &signers).is_err());
+
+        // Signatures collected independently can be merged.
+        let mut other = MultiSigned::new(&42u64);
+        other.sign(&keys[2], keys[2].public_key(), context).unwrap();
+        signed.merge(other).unwrap();
+        assert_eq!(signed.open::<u64>(context, &signers).unwrap(), 42);
+        assert!(signed.merge(MultiSigned::new(&43u64)).is_err());
+
+        // Signatures must be valid for the context.
+        assert!(signed
+            .open::<u64>(b"oasis-core/test: other", &signers)
+            .is_err());
+
+        // Invalid signatures are rejected even if the threshold is met.
+        let mut forged = signed.clone();
+        forged.signatures.push(SignatureBundle {
+            public_key: Some(keys[1].public_key()),
+            signature: Signature::default(),
+        });
+        assert!(forged.open::<u64>(context, &signers).is_err());
+
+        // Encoding round-trips.
+        let decoded: MultiSigned = cbor::from_slice(&cbor::to_vec(&signed)).unwrap();
+        assert_eq!(decoded, signed);
+
+        // Thresholds must be satisfiable.
+        assert!(SignerSet::new(public_keys.clone(), 0).is_err());
+        assert!(SignerSet::new(public_keys.clone(), 3).is_ok());
+        assert!(SignerSet::new(public_keys.clone(), 4).is_err());
+        let mut duplicated = public_keys.clone();
+        duplicated.push(public_keys[0]);
+        assert!(SignerSet::new(duplicated, 4).is_err());
+    }
+
+    #[test]
+    fn test_slip10_derivation() {
+        // SLIP-10 ed25519 test vector 1.
+        let seed = [
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+            0x0e, 0x0f,
+        ];
+        let vectors = vec![
+            (
+                "m",
+                "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7",
+                "a4b2856bfec510abab89753fac1ac0e1112364e7d250545963f135f2a33188ed",
+            ),
+            (
+                "m/0'",
+                "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3",
+                "8c8a13df77a28f3445213a0f432fde644acaa215fc72dcdf300d5efaa85d350c",
+            ),
+            (
+                "m/0h/1h",
+                "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2",
+                "1932a5270f335bed617d5b935c80aedb1a35bd9fc1e31acafd5372c30f5c1187",
+            ),
+            (
+                "m/0'/1'/2'",
+                "92a5b23c0b8a99e37d07df3fb9966917f5d06e02ddbd909c7e184371463e9fc9",
+                "ae98736566d30ed0e9d2f4486a64bc95740d89c7db33f52121f8ea8f76ff0fc1",
+            ),
+        ];
+        for (path, private_key, public_key) in vectors {
+            let key = PrivateKey::from_seed(&seed, &path.parse().unwrap()).unwrap();
+            assert_eq!(key.to_bytes(), private_key.from_hex::<Vec<u8>>().unwrap());
+            assert_eq!(key.public_key(), PublicKey::from(public_key));
+        }
+
+        assert_eq!(
+            "m/44'/474'/3'".parse::<DerivationPath>().unwrap(),
+            DerivationPath::adr8(3)
+        );
+        for path in &["", "44'", "m/44", "m/x'", "m/2147483648'"] {
+            assert!(path.parse::<DerivationPath>().is_err());
+        }
+        assert!(PrivateKey::from_seed(&seed[..8], &DerivationPath::adr8(0)).is_err());
+    }
+
+    #[test]
+    fn test_mnemonic_to_seed() {
+        // BIP-39 English test vector.
+        let seed = mnemonic_to_seed(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+             abandon about",
+            "TREZOR",
+        );
+        let expected: Vec<u8> = "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04".from_hex().unwrap();
+        assert_eq!(&seed[..], &expected[..]);
+    }
 }
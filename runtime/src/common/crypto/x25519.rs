@@ -0,0 +1,242 @@
+//! X25519 key exchange and public key authenticated encryption.
+//!
+//! A box is encrypted with Deoxys-II under a key derived from an X25519
+//! exchange between the sender's and the recipient's key pairs, so only the
+//! two of them can open it. Senders which do not have a long-term key pair
+//! use an ephemeral one per exchange and send its public key along.
+use anyhow::Result;
+use rand::rngs::OsRng;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+use x25519_dalek;
+use zeroize::Zeroize;
+
+use super::mrae::deoxysii::{self, DeoxysII, NONCE_SIZE};
+
+impl_bytes!(PublicKey, 32, "An X25519 public key.");
+
+/// Box error.
+#[derive(Debug, Error)]
+pub enum BoxError {
+    #[error("malformed box nonce")]
+    MalformedNonce,
+    #[error("box is not from the expected sender")]
+    UnexpectedSender,
+}
+
+/// An X25519 private key.
+///
+/// The key is zeroized when dropped.
+#[derive(Clone)]
+pub struct PrivateKey([u8; 32]);
+
+impl PrivateKey {
+    /// Generate a new private key.
+    pub fn generate() -> Self {
+        let mut rng = OsRng {};
+        PrivateKey(x25519_dalek::StaticSecret::new(&mut rng).to_bytes())
+    }
+
+    /// Construct a private key from its raw bytes.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        PrivateKey(bytes)
+    }
+
+    /// Raw bytes of the private key.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Public key corresponding to the private key.
+    pub fn public_key(&self) -> PublicKey {
+        let secret = x25519_dalek::StaticSecret::from(self.0);
+        PublicKey(*x25519_dalek::PublicKey::from(&secret).as_bytes())
+    }
+
+    /// Derive the symmetric key shared with the owner of the given public
+    /// key.
+    pub fn derive_shared_key(&self, peer_public_key: &PublicKey) -> [u8; deoxysii::KEY_SIZE] {
+        deoxysii::derive_symmetric_key(&peer_public_key.0, &self.0)
+    }
+}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Encrypt the plaintext to the owner of the peer public key.
+///
+/// The nonce must be unique for all time for a given pair of keys, so fixed
+/// nonces may only be used with ephemeral keys.
+pub fn seal(
+    nonce: &[u8; NONCE_SIZE],
+    plaintext: Vec<u8>,
+    additional_data: Vec<u8>,
+    peer_public_key: &PublicKey,
+    private_key: &PrivateKey,
+) -> Vec<u8> {
+    new_d2(peer_public_key, private_key).seal(nonce, plaintext, additional_data)
+}
+
+/// Decrypt a ciphertext sealed by the owner of the peer public key.
+pub fn open(
+    nonce: &[u8; NONCE_SIZE],
+    ciphertext: Vec<u8>,
+    additional_data: Vec<u8>,
+    peer_public_key: &PublicKey,
+    private_key: &PrivateKey,
+) -> Result<Vec<u8>> {
+    new_d2(peer_public_key, private_key)
+        .open(nonce, ciphertext, additional_data)
+        .map_err(|err| err.into())
+}
+
+fn new_d2(peer_public_key: &PublicKey, private_key: &PrivateKey) -> DeoxysII {
+    let mut key = private_key.derive_shared_key(peer_public_key);
+    let d2 = DeoxysII::new(&key);
+    key.zeroize();
+
+    d2
+}
+
+/// A box, carrying the sender's public key and the nonce along with the
+/// ciphertext.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedBox {
+    /// Public key of the sender.
+    pub public_key: PublicKey,
+    /// Nonce used for encryption.
+    #[serde(with = "serde_bytes")]
+    pub nonce: Vec<u8>,
+    /// Encrypted payload.
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+}
+
+impl SealedBox {
+    /// Seal the plaintext from the given sender to the given recipient.
+    pub fn seal(
+        nonce: [u8; NONCE_SIZE],
+        plaintext: Vec<u8>,
+        additional_data: Vec<u8>,
+        recipient: &PublicKey,
+        sender: &PrivateKey,
+    ) -> Self {
+        Self {
+            public_key: sender.public_key(),
+            nonce: nonce.to_vec(),
+            data: seal(&nonce, plaintext, additional_data, recipient, sender),
+        }
+    }
+
+    /// Seal the plaintext to the given recipient using a new ephemeral key
+    /// pair.
+    ///
+    /// Returns the ephemeral private key, which is needed to open replies.
+    pub fn seal_ephemeral(
+        plaintext: Vec<u8>,
+        additional_data: Vec<u8>,
+        recipient: &PublicKey,
+    ) -> (Self, PrivateKey) {
+        let private_key = PrivateKey::generate();
+        // The key pair is never reused, so a fixed nonce is safe.
+        let sealed = Self::seal(
+            [0u8; NONCE_SIZE],
+            plaintext,
+            additional_data,
+            recipient,
+            &private_key,
+        );
+
+        (sealed, private_key)
+    }
+
+    /// Open a box sealed to the given private key by any sender.
+    ///
+    /// The sender is not authenticated, use `open_from` if the sender is
+    /// known in advance.
+    pub fn open(&self, additional_data: Vec<u8>, private_key: &PrivateKey) -> Result<Vec<u8>> {
+        if self.nonce.len() != NONCE_SIZE {
+            return Err(BoxError::MalformedNonce.into());
+        }
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce.copy_from_slice(&self.nonce);
+
+        open(
+            &nonce,
+            self.data.clone(),
+            additional_data,
+            &self.public_key,
+            private_key,
+        )
+    }
+
+    /// Open a box sealed to the given private key by the given sender.
+    pub fn open_from(
+        &self,
+        sender: &PublicKey,
+        additional_data: Vec<u8>,
+        private_key: &PrivateKey,
+    ) -> Result<Vec<u8>> {
+        if self.public_key != *sender {
+            return Err(BoxError::UnexpectedSender.into());
+        }
+        self.open(additional_data, private_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sealed_box() {
+        let recipient = PrivateKey::generate();
+        let aad = b"oasis-core/test: box".to_vec();
+
+        let (request, sender) =
+            SealedBox::seal_ephemeral(b"request".to_vec(), aad.clone(), &recipient.public_key());
+        assert_eq!(request.public_key, sender.public_key());
+        assert_ne!(request.data, b"request".to_vec());
+        assert_eq!(
+            request.open(aad.clone(), &recipient).unwrap(),
+            b"request".to_vec()
+        );
+        assert!(request.open(vec![], &recipient).is_err());
+        assert!(request.open(aad.clone(), &PrivateKey::generate()).is_err());
+
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce[0] = 1;
+        let response = SealedBox::seal(
+            nonce,
+            b"response".to_vec(),
+            aad.clone(),
+            &request.public_key,
+            &recipient,
+        );
+        assert_eq!(
+            response
+                .open_from(&recipient.public_key(), aad.clone(), &sender)
+                .unwrap(),
+            b"response".to_vec()
+        );
+        assert!(response
+            .open_from(&sender.public_key(), aad.clone(), &sender)
+            .is_err());
+
+        // Boxes are compatible with the raw box primitives.
+        assert_eq!(
+            deoxysii::box_open(
+                &nonce,
+                response.data.clone(),
+                aad,
+                &recipient.public_key().0,
+                sender.as_bytes(),
+            )
+            .unwrap(),
+            b"response".to_vec()
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Self-describing versioned envelopes for persisted blobs.
+//!
+//! Anything persisted or exchanged out-of-band (e.g., sealed caches,
+//! checkpoints or evidence bundles) should be wrapped in an envelope, which
+//! records the kind of the blob and the version of its encoding next to the
+//! CBOR-encoded body. This makes it possible to report version mismatches
+//! instead of failing to decode the body.
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::cbor;
+
+/// Envelope error.
+#[derive(Debug, Error)]
+pub enum EnvelopeError {
+    #[error("malformed envelope: {0}")]
+    Malformed(String),
+    #[error("unexpected blob type (expected: {expected} got: {got})")]
+    TypeMismatch { expected: String, got: String },
+    #[error("{type_tag} version {version} is newer than the latest supported version {latest}, upgrade required")]
+    VersionTooNew {
+        type_tag: String,
+        version: u16,
+        latest: u16,
+    },
+    #[error("{type_tag} version {version} is no longer supported (latest version: {latest})")]
+    VersionTooOld {
+        type_tag: String,
+        version: u16,
+        latest: u16,
+    },
+    #[error("malformed {type_tag} version {version} body: {reason}")]
+    MalformedBody {
+        type_tag: String,
+        version: u16,
+        reason: String,
+    },
+}
+
+/// A type which can be wrapped in an envelope.
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// Tag identifying the kind of the blob.
+    const TYPE_TAG: &'static str;
+    /// Version of the encoding produced by `Serialize`.
+    const VERSION: u16;
+
+    /// Decode a body of the given version.
+    ///
+    /// The default implementation only supports the latest version. Types
+    /// which need to read blobs written by older versions should override it
+    /// and migrate the older encodings.
+    fn decode_body(version: u16, body: &[u8]) -> Result<Self> {
+        if version < Self::VERSION {
+            return Err(EnvelopeError::VersionTooOld {
+                type_tag: Self::TYPE_TAG.to_owned(),
+                version,
+                latest: Self::VERSION,
+            }
+            .into());
+        }
+
+        cbor::from_slice(body).map_err(|err| {
+            EnvelopeError::MalformedBody {
+                type_tag: Self::TYPE_TAG.to_owned(),
+                version,
+                reason: err.to_string(),
+            }
+            .into()
+        })
+    }
+}
+
+/// A self-describing versioned envelope.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Envelope {
+    /// Tag identifying the kind of the blob.
+    #[serde(rename = "type")]
+    pub type_tag: String,
+    /// Version of the body encoding.
+    pub version: u16,
+    /// CBOR-encoded body.
+    #[serde(with = "serde_bytes")]
+    pub body: Vec<u8>,
+}
+
+impl Envelope {
+    /// Wrap a value in an envelope.
+    pub fn new<T: Versioned>(value: &T) -> Self {
+        Self {
+            type_tag: T::TYPE_TAG.to_owned(),
+            version: T::VERSION,
+            body: cbor::to_vec(value),
+        }
+    }
+
+    /// Decode an envelope, checking only that it is well-formed.
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        cbor::from_slice(data).map_err(|err| EnvelopeError::Malformed(err.to_string()).into())
+    }
+
+    /// Open the envelope, decoding its body.
+    pub fn open<T: Versioned>(&self) -> Result<T> {
+        if self.type_tag != T::TYPE_TAG {
+            return Err(EnvelopeError::TypeMismatch {
+                expected: T::TYPE_TAG.to_owned(),
+                got: self.type_tag.clone(),
+            }
+            .into());
+        }
+        if self.version > T::VERSION {
+            return Err(EnvelopeError::VersionTooNew {
+                type_tag: self.type_tag.clone(),
+                version: self.version,
+                latest: T::VERSION,
+            }
+            .into());
+        }
+
+        T::decode_body(self.version, &self.body)
+    }
+}
+
+/// Serialize a value wrapped in an envelope.
+pub fn to_vec<T: Versioned>(value: &T) -> Vec<u8> {
+    cbor::to_vec(&Envelope::new(value))
+}
+
+/// Deserialize a value wrapped in an envelope.
+pub fn from_slice<T: Versioned>(data: &[u8]) -> Result<T> {
+    Envelope::from_slice(data)?.open()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestV1 {
+        value: u64,
+    }
+
+    impl Versioned for TestV1 {
+        const TYPE_TAG: &'static str = "test";
+        const VERSION: u16 = 1;
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestV2 {
+        value: u64,
+        name: String,
+    }
+
+    impl Versioned for TestV2 {
+        const TYPE_TAG: &'static str = "test";
+        const VERSION: u16 = 2;
+
+        fn decode_body(version: u16, body: &[u8]) -> Result<Self> {
+            match version {
+                1 => {
+                    let old: TestV1 = cbor::from_slice(body)?;
+                    Ok(Self {
+                        value: old.value,
+                        name: String::new(),
+                    })
+                }
+                _ => Ok(cbor::from_slice(body)?),
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Other;
+
+    impl Versioned for Other {
+        const TYPE_TAG: &'static str = "other";
+        const VERSION: u16 = 1;
+    }
+
+    fn error<T: Versioned>(data: &[u8]) -> EnvelopeError {
+        match from_slice::<T>(data).unwrap_err().downcast() {
+            Ok(err) => err,
+            Err(err) => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn test_envelope() {
+        let v1 = TestV1 { value: 42 };
+        let data = to_vec(&v1);
+        assert_eq!(from_slice::<TestV1>(&data).unwrap(), v1);
+
+        let envelope = Envelope::from_slice(&data).unwrap();
+        assert_eq!(envelope.type_tag, "test");
+        assert_eq!(envelope.version, 1);
+
+        // Older versions can be migrated.
+        assert_eq!(
+            from_slice::<TestV2>(&data).unwrap(),
+            TestV2 {
+                value: 42,
+                name: String::new(),
+            }
+        );
+
+        // Newer versions are reported as such.
+        let v2 = TestV2 {
+            value: 42,
+            name: "foo".to_owned(),
+        };
+        match error::<TestV1>(&to_vec(&v2)) {
+            EnvelopeError::VersionTooNew {
+                version: 2,
+                latest: 1,
+                ..
+            } => {}
+            err => panic!("unexpected error: {}", err),
+        }
+
+        match error::<Other>(&data) {
+            EnvelopeError::TypeMismatch { .. } => {}
+            err => panic!("unexpected error: {}", err),
+        }
+        match error::<TestV1>(&cbor::to_vec(&v1)) {
+            EnvelopeError::Malformed(_) => {}
+            err => panic!("unexpected error: {}", err),
+        }
+
+        // Older versions are rejected unless migrated.
+        let old = Envelope {
+            type_tag: "other".to_owned(),
+            version: 0,
+            body: vec![],
+        };
+        match error::<Other>(&cbor::to_vec(&old)) {
+            EnvelopeError::VersionTooOld { .. } => {}
+            err => panic!("unexpected error: {}", err),
+        }
+
+        let corrupted = Envelope {
+            body: vec![0xff],
+            ..envelope
+        };
+        match error::<TestV1>(&cbor::to_vec(&corrupted)) {
+            EnvelopeError::MalformedBody { version: 1, .. } => {}
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+}
@@ -33,6 +33,8 @@ pub fn init_logger(level: LogLevel) {
             .lock()
             .unwrap()
             .get_or_insert(slog_scope::set_global_logger(global_logger));
-        let _log_guard = slog_stdlog::init_with_level(level).unwrap();
+        // The log crate logger may have already been set, e.g., to forward
+        // records to the worker host.
+        let _ = slog_stdlog::init_with_level(level);
     });
 }
@@ -0,0 +1,292 @@
+//! Binary Merkle trees with compact multiproofs.
+//!
+//! Leaves and inner nodes are hashed with distinct prefixes, so a leaf can
+//! never be passed off as an inner node. When a level has an odd number of
+//! nodes, the last one is promoted to the next level unchanged. The root
+//! commits to the number of leaves, so that proofs cannot claim a different
+//! shape of the tree and thereby move leaves to other indices.
+//!
+//! A multiproof for a set of leaves contains only the hashes which cannot be
+//! computed from the proven leaves themselves, level by level in order of
+//! increasing index.
+use anyhow::Result;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::crypto::hash::Hash;
+
+/// Prefix of leaf hashes.
+const LEAF_PREFIX: &'static [u8] = &[0x00];
+/// Prefix of inner node hashes.
+const NODE_PREFIX: &'static [u8] = &[0x01];
+/// Prefix of root hashes.
+const ROOT_PREFIX: &'static [u8] = &[0x02];
+
+/// Merkle tree error.
+#[derive(Debug, Error)]
+pub enum MerkleError {
+    #[error("leaf index {0} out of range")]
+    IndexOutOfRange(usize),
+    #[error("duplicate leaf index {0}")]
+    DuplicateIndex(usize),
+    #[error("no leaves to prove")]
+    NoLeaves,
+    #[error("invalid proof")]
+    InvalidProof,
+}
+
+/// Hash of a leaf.
+pub fn leaf_hash(data: &[u8]) -> Hash {
+    Hash::digest_bytes_list(&[LEAF_PREFIX, data])
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    Hash::digest_bytes_list(&[NODE_PREFIX, left.as_ref(), right.as_ref()])
+}
+
+fn root_hash(leaf_count: u64, top: &Hash) -> Hash {
+    Hash::digest_bytes_list(&[ROOT_PREFIX, &leaf_count.to_le_bytes(), top.as_ref()])
+}
+
+/// A Merkle tree over a list of leaves.
+#[derive(Clone, Debug)]
+pub struct MerkleTree {
+    /// Node hashes, starting with the leaf level.
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    /// Build a tree over the given leaves.
+    pub fn new<L: AsRef<[u8]>>(leaves: &[L]) -> Self {
+        let mut levels = vec![leaves
+            .iter()
+            .map(|leaf| leaf_hash(leaf.as_ref()))
+            .collect::<Vec<_>>()];
+        while levels.last().unwrap().len() > 1 {
+            let level = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [node] => *node,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(level);
+        }
+
+        Self { levels }
+    }
+
+    /// Number of leaves.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Check whether the tree has no leaves.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Root hash of the tree, the empty hash if there are no leaves.
+    pub fn root(&self) -> Hash {
+        match self.levels.last().unwrap().first() {
+            Some(top) => root_hash(self.len() as u64, top),
+            None => Hash::empty_hash(),
+        }
+    }
+
+    /// Generate a proof of the leaves at the given indices.
+    pub fn prove(&self, indices: &[usize]) -> Result<MultiProof> {
+        let mut known = sorted_indices(indices.iter().cloned(), self.len())?;
+
+        let mut hashes = vec![];
+        for level in &self.levels[..self.levels.len() - 1] {
+            for index in &known {
+                let sibling = index ^ 1;
+                if sibling < level.len() && known.binary_search(&sibling).is_err() {
+                    hashes.push(level[sibling]);
+                }
+            }
+            known = parent_indices(&known);
+        }
+
+        Ok(MultiProof {
+            leaf_count: self.len() as u64,
+            hashes,
+        })
+    }
+}
+
+/// A proof of a set of leaves of a Merkle tree.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultiProof {
+    /// Number of leaves in the tree, as committed to by the root.
+    pub leaf_count: u64,
+    /// Hashes needed to compute the root, besides the proven leaves.
+    pub hashes: Vec<Hash>,
+}
+
+impl MultiProof {
+    /// Verify that the given leaves, keyed by index, are included in the
+    /// tree with the given root.
+    pub fn verify<L: AsRef<[u8]>>(&self, root: &Hash, leaves: &[(usize, L)]) -> Result<()> {
+        let mut level_len = self.leaf_count as usize;
+        let indices = sorted_indices(leaves.iter().map(|(index, _)| *index), level_len)?;
+        let mut nodes: Vec<(usize, Hash)> = leaves
+            .iter()
+            .map(|(index, leaf)| (*index, leaf_hash(leaf.as_ref())))
+            .collect();
+        nodes.sort_by_key(|(index, _)| *index);
+        debug_assert_eq!(indices.len(), nodes.len());
+
+        let mut hashes = self.hashes.iter();
+        while level_len > 1 {
+            let mut parents = Vec::with_capacity(nodes.len());
+            let mut i = 0;
+            while i < nodes.len() {
+                let (index, hash) = nodes[i];
+                let sibling = index ^ 1;
+                let parent = if sibling >= level_len {
+                    // Promoted without hashing.
+                    hash
+                } else if i + 1 < nodes.len() && nodes[i + 1].0 == sibling {
+                    // Both children are known.
+                    i += 1;
+                    node_hash(&hash, &nodes[i].1)
+                } else {
+                    let sibling_hash = hashes.next().ok_or(MerkleError::InvalidProof)?;
+                    if index & 1 == 0 {
+                        node_hash(&hash, sibling_hash)
+                    } else {
+                        node_hash(sibling_hash, &hash)
+                    }
+                };
+                parents.push((index / 2, parent));
+                i += 1;
+            }
+
+            nodes = parents;
+            level_len = (level_len + 1) / 2;
+        }
+
+        if hashes.next().is_some() || root_hash(self.leaf_count, &nodes[0].1) != *root {
+            return Err(MerkleError::InvalidProof.into());
+        }
+        Ok(())
+    }
+}
+
+fn sorted_indices<I: Iterator<Item = usize>>(indices: I, len: usize) -> Result<Vec<usize>> {
+    let mut sorted: Vec<usize> = indices.collect();
+    if sorted.is_empty() {
+        return Err(MerkleError::NoLeaves.into());
+    }
+    sorted.sort();
+
+    for (i, index) in sorted.iter().enumerate() {
+        if *index >= len {
+            return Err(MerkleError::IndexOutOfRange(*index).into());
+        }
+        if i > 0 && sorted[i - 1] == *index {
+            return Err(MerkleError::DuplicateIndex(*index).into());
+        }
+    }
+    Ok(sorted)
+}
+
+fn parent_indices(indices: &[usize]) -> Vec<usize> {
+    let mut parents: Vec<usize> = indices.iter().map(|index| index / 2).collect();
+    parents.dedup();
+    parents
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn leaves(count: usize) -> Vec<Vec<u8>> {
+        (0..count)
+            .map(|i| format!("leaf {}", i).into_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_merkle_tree() {
+        assert_eq!(MerkleTree::new::<Vec<u8>>(&[]).root(), Hash::empty_hash());
+        assert_eq!(
+            MerkleTree::new(&leaves(1)).root(),
+            root_hash(1, &leaf_hash(b"leaf 0"))
+        );
+        assert_eq!(
+            MerkleTree::new(&leaves(3)).root(),
+            root_hash(
+                3,
+                &node_hash(
+                    &node_hash(&leaf_hash(b"leaf 0"), &leaf_hash(b"leaf 1")),
+                    &leaf_hash(b"leaf 2")
+                )
+            )
+        );
+
+        // Prove every subset of leaves of small trees.
+        for count in 1..=9 {
+            let leaves = leaves(count);
+            let tree = MerkleTree::new(&leaves);
+            let root = tree.root();
+
+            for subset in 1..(1u32 << count) {
+                let indices: Vec<usize> = (0..count).filter(|i| subset & (1 << i) != 0).collect();
+                let proven: Vec<_> = indices.iter().map(|i| (*i, &leaves[*i])).collect();
+                let proof = tree.prove(&indices).unwrap();
+                proof.verify(&root, &proven).unwrap();
+
+                // Proofs do not verify for other leaves or roots.
+                let mut wrong = proven.clone();
+                wrong[0].1 = &leaves[(wrong[0].0 + 1) % count];
+                if count > 1 {
+                    assert!(proof.verify(&root, &wrong).is_err());
+                }
+                assert!(proof.verify(&Hash::empty_hash(), &proven).is_err());
+
+                // Proofs do not verify for trees of other sizes.
+                let mut resized = proof.clone();
+                resized.leaf_count += 1;
+                assert!(resized.verify(&root, &proven).is_err());
+            }
+        }
+
+        // A leaf cannot be moved to another index by claiming a different
+        // number of leaves: with 3 leaves, the last one is promoted, so it
+        // would otherwise also verify as the second leaf of a 2-leaf tree.
+        let leaves3 = leaves(3);
+        let tree = MerkleTree::new(&leaves3);
+        let proof = MultiProof {
+            leaf_count: 2,
+            hashes: vec![node_hash(&leaf_hash(b"leaf 0"), &leaf_hash(b"leaf 1"))],
+        };
+        assert!(proof.verify(&tree.root(), &[(1, &leaves3[2])]).is_err());
+
+        // Compact proofs only include what cannot be computed.
+        let leaves = leaves(8);
+        let tree = MerkleTree::new(&leaves);
+        assert_eq!(tree.prove(&[0]).unwrap().hashes.len(), 3);
+        assert_eq!(tree.prove(&[0, 1, 2, 3]).unwrap().hashes.len(), 1);
+        assert!(tree
+            .prove(&(0..8).collect::<Vec<_>>())
+            .unwrap()
+            .hashes
+            .is_empty());
+
+        // Malformed proofs and requests.
+        assert!(tree.prove(&[]).is_err());
+        assert!(tree.prove(&[8]).is_err());
+        assert!(tree.prove(&[1, 1]).is_err());
+        let mut proof = tree.prove(&[0]).unwrap();
+        proof.hashes.push(Hash::empty_hash());
+        assert!(proof.verify(&tree.root(), &[(0, &leaves[0])]).is_err());
+        proof.hashes.truncate(2);
+        assert!(proof.verify(&tree.root(), &[(0, &leaves[0])]).is_err());
+    }
+}
@@ -1,14 +1,20 @@
 //! Common types.
 
-#[macro_use]
-pub mod bytes;
-pub mod cbor;
+pub mod consensus;
 pub mod crypto;
+pub mod envelope;
 pub mod key_format;
 pub mod logger;
+pub mod merkle;
+pub mod node;
 pub mod registry;
 pub mod roothash;
 pub mod runtime;
+pub mod scheduler;
 pub mod sgx;
+pub mod staking;
 pub mod time;
 pub mod version;
+
+// Re-exports.
+pub use oasis_core_common::{cbor, quantity};
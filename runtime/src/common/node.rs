@@ -0,0 +1,224 @@
+//! Node structures.
+//!
+//! # Note
+//!
+//! This **MUST** be kept in sync with go/common/node.
+//!
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use serde_bytes::ByteBuf;
+use serde_derive::{Deserialize, Serialize};
+use serde_repr::*;
+
+use super::{crypto::signature::PublicKey, runtime::RuntimeId, version::Version};
+
+/// Compute worker role.
+pub const ROLE_COMPUTE_WORKER: u32 = 1 << 0;
+/// Storage worker role.
+pub const ROLE_STORAGE_WORKER: u32 = 1 << 1;
+/// Key manager role.
+pub const ROLE_KEY_MANAGER: u32 = 1 << 2;
+/// Validator role.
+pub const ROLE_VALIDATOR: u32 = 1 << 3;
+/// Public consensus RPC services role.
+pub const ROLE_CONSENSUS_RPC: u32 = 1 << 4;
+
+/// TEE hardware implementation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum TEEHardware {
+    /// Non-TEE implementation.
+    Invalid = 0,
+    /// Intel SGX.
+    IntelSGX = 1,
+}
+
+impl Default for TEEHardware {
+    fn default() -> Self {
+        TEEHardware::Invalid
+    }
+}
+
+/// Node's TEE capability.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CapabilityTEE {
+    /// TEE hardware type.
+    pub hardware: TEEHardware,
+    /// Runtime attestation key.
+    pub rak: PublicKey,
+    /// Attestation.
+    pub attestation: Option<ByteBuf>,
+}
+
+/// Node's capabilities.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// TEE capability, if the node supports it.
+    #[serde(default)]
+    pub tee: Option<CapabilityTEE>,
+}
+
+/// Runtime supported by a node.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Runtime {
+    /// Runtime identifier.
+    pub id: RuntimeId,
+    /// Supported runtime version.
+    pub version: Version,
+    /// Node's capabilities for the runtime.
+    pub capabilities: Capabilities,
+    /// Extra per-runtime opaque information.
+    pub extra_info: Option<ByteBuf>,
+}
+
+/// Node's TCP address.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Address {
+    /// IP address (4 or 16 bytes).
+    #[serde(rename = "IP")]
+    pub ip: ByteBuf,
+    /// TCP port.
+    #[serde(rename = "Port")]
+    pub port: u16,
+    /// IPv6 scoped addressing zone.
+    #[serde(rename = "Zone")]
+    pub zone: String,
+}
+
+impl Address {
+    /// Convert the address to a socket address, if the IP address is valid.
+    pub fn to_socket_addr(&self) -> Option<SocketAddr> {
+        let ip = match self.ip.len() {
+            4 => {
+                let mut octets = [0u8; 4];
+                octets.copy_from_slice(&self.ip);
+                IpAddr::V4(Ipv4Addr::from(octets))
+            }
+            16 => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(&self.ip);
+                let ip = Ipv6Addr::from(octets);
+                // IPv4 addresses are usually encoded as IPv4-mapped IPv6
+                // addresses.
+                match ip.segments() {
+                    [0, 0, 0, 0, 0, 0xffff, _, _] => IpAddr::V4(Ipv4Addr::new(
+                        octets[12], octets[13], octets[14], octets[15],
+                    )),
+                    _ => IpAddr::V6(ip),
+                }
+            }
+            _ => return None,
+        };
+
+        Some(SocketAddr::new(ip, self.port))
+    }
+}
+
+/// Node's committee address together with the certificate used to
+/// authenticate it.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CommitteeAddress {
+    /// DER-encoded TLS certificate.
+    pub certificate: ByteBuf,
+    /// Address.
+    pub address: Address,
+}
+
+/// Node's committee information.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CommitteeInfo {
+    /// DER-encoded TLS certificate.
+    pub certificate: ByteBuf,
+    /// Addresses at which the node can be reached.
+    pub addresses: Option<Vec<CommitteeAddress>>,
+}
+
+/// Node descriptor.
+///
+/// Only the fields needed by clients are included, others are ignored when
+/// decoding.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Node {
+    /// Structure version.
+    #[serde(rename = "v", default)]
+    pub descriptor_version: u16,
+    /// Public key identifying the node.
+    pub id: PublicKey,
+    /// Public key identifying the entity controlling the node.
+    pub entity_id: PublicKey,
+    /// Epoch in which the node's commitment expires.
+    pub expiration: u64,
+    /// Committee information.
+    #[serde(default)]
+    pub committee: CommitteeInfo,
+    /// Runtimes supported by the node.
+    pub runtimes: Option<Vec<Runtime>>,
+    /// Bitmask of the node's roles.
+    pub roles: u32,
+}
+
+impl Node {
+    /// Check whether the node has all of the given roles.
+    pub fn has_roles(&self, roles: u32) -> bool {
+        self.roles & roles == roles
+    }
+
+    /// Return the node's descriptor for the given runtime, if it supports it.
+    pub fn get_runtime(&self, id: &RuntimeId) -> Option<&Runtime> {
+        self.runtimes.iter().flatten().find(|rt| &rt.id == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_roles() {
+        let node = Node {
+            roles: ROLE_COMPUTE_WORKER | ROLE_STORAGE_WORKER,
+            runtimes: Some(vec![Runtime {
+                id: RuntimeId::from(vec![1u8; 32].as_slice()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        assert!(node.has_roles(ROLE_COMPUTE_WORKER));
+        assert!(node.has_roles(ROLE_COMPUTE_WORKER | ROLE_STORAGE_WORKER));
+        assert!(!node.has_roles(ROLE_KEY_MANAGER));
+        assert!(node
+            .get_runtime(&RuntimeId::from(vec![1u8; 32].as_slice()))
+            .is_some());
+        assert!(node.get_runtime(&RuntimeId::default()).is_none());
+    }
+
+    #[test]
+    fn test_address() {
+        let mut addr = Address {
+            ip: ByteBuf::from(vec![127, 0, 0, 1]),
+            port: 42261,
+            zone: String::new(),
+        };
+        assert_eq!(
+            addr.to_socket_addr(),
+            Some("127.0.0.1:42261".parse().unwrap())
+        );
+
+        let mut mapped = vec![0u8; 10];
+        mapped.extend_from_slice(&[0xff, 0xff, 10, 0, 0, 1]);
+        addr.ip = ByteBuf::from(mapped);
+        assert_eq!(
+            addr.to_socket_addr(),
+            Some("10.0.0.1:42261".parse().unwrap())
+        );
+
+        let mut ipv6 = vec![0u8; 16];
+        ipv6[15] = 1;
+        addr.ip = ByteBuf::from(ipv6);
+        assert_eq!(addr.to_socket_addr(), Some("[::1]:42261".parse().unwrap()));
+
+        addr.ip = ByteBuf::from(vec![1, 2, 3]);
+        assert_eq!(addr.to_socket_addr(), None);
+    }
+}
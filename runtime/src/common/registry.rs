@@ -4,11 +4,21 @@
 //!
 //! This **MUST** be kept in sync with go/registry/api.
 //!
+use std::collections::BTreeMap;
+
 use super::{
     super::storage::mkvs::WriteLog,
-    crypto::{hash, signature::SignatureBundle},
+    crypto::{
+        hash,
+        signature::{PublicKey, SignatureBundle},
+    },
+    node::TEEHardware,
+    runtime::RuntimeId,
+    version::Version,
 };
+use serde_bytes::ByteBuf;
 use serde_derive::{Deserialize, Serialize};
+use serde_repr::*;
 
 /// Runtime genesis information that is used to initialize runtime state in the first block.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -29,3 +39,96 @@ pub struct RuntimeGenesis {
     /// Runtime round in the genesis.
     pub round: u64,
 }
+
+/// Runtime kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(u32)]
+pub enum RuntimeKind {
+    /// Invalid runtime that should never be explicitly set.
+    Invalid = 0,
+    /// Generic compute runtime.
+    Compute = 1,
+    /// Key manager runtime.
+    KeyManager = 2,
+}
+
+impl Default for RuntimeKind {
+    fn default() -> Self {
+        RuntimeKind::Invalid
+    }
+}
+
+/// Versioning information about a runtime.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VersionInfo {
+    /// Version of the runtime.
+    pub version: Version,
+    /// Enclave version information, in an enclave provider specific format.
+    #[serde(default)]
+    pub tee: Option<ByteBuf>,
+}
+
+/// Admission policy allowing any node to register.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AnyNodeRuntimeAdmissionPolicy {}
+
+/// Admission policy allowing only nodes of whitelisted entities to register.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EntityWhitelistRuntimeAdmissionPolicy {
+    /// Whitelisted entities.
+    pub entities: BTreeMap<PublicKey, bool>,
+}
+
+/// Specification of which nodes are allowed to register for a runtime.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RuntimeAdmissionPolicy {
+    /// Allow any node to register.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub any_node: Option<AnyNodeRuntimeAdmissionPolicy>,
+    /// Allow only nodes of whitelisted entities to register.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entity_whitelist: Option<EntityWhitelistRuntimeAdmissionPolicy>,
+}
+
+impl RuntimeAdmissionPolicy {
+    /// Check whether nodes of the given entity are allowed to register.
+    pub fn allows(&self, entity_id: &PublicKey) -> bool {
+        if self.any_node.is_some() {
+            return true;
+        }
+        match self.entity_whitelist {
+            Some(ref whitelist) => whitelist.entities.get(entity_id) == Some(&true),
+            None => false,
+        }
+    }
+}
+
+/// Runtime descriptor.
+///
+/// Only the fields needed by clients are included, others are ignored when
+/// decoding.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Runtime {
+    /// Structure version.
+    #[serde(rename = "v", default)]
+    pub descriptor_version: u16,
+    /// Runtime identifier.
+    pub id: RuntimeId,
+    /// Public key identifying the entity controlling the runtime.
+    pub entity_id: PublicKey,
+    /// Runtime genesis information.
+    pub genesis: RuntimeGenesis,
+    /// Type of runtime.
+    pub kind: RuntimeKind,
+    /// Runtime's TEE hardware requirements.
+    pub tee_hardware: TEEHardware,
+    /// Runtime version information.
+    #[serde(rename = "versions")]
+    pub version: VersionInfo,
+    /// Key manager runtime used by this runtime, if any.
+    #[serde(default)]
+    pub key_manager: Option<RuntimeId>,
+    /// Which nodes are allowed to register for this runtime.
+    #[serde(default)]
+    pub admission_policy: RuntimeAdmissionPolicy,
+}
@@ -4,13 +4,22 @@
 //!
 //! This **MUST** be kept in sync with go/roothash/api/block.
 //!
+use anyhow::Result;
 use serde_derive::{Deserialize, Serialize};
 use serde_repr::*;
+use thiserror::Error;
 
 use super::{
     cbor,
-    crypto::{hash::Hash, signature::SignatureBundle},
+    crypto::{
+        hash::Hash,
+        signature::{PrivateKey, SignatureBundle, Signer},
+    },
 };
+use crate::storage::mkvs::{Root, RootType};
+
+pub use super::crypto::context::{COMPUTE_RESULTS_HEADER_CONTEXT, STORAGE_RECEIPT_CONTEXT};
+pub use oasis_core_common::namespace::Namespace;
 
 /// Runtime block.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -28,8 +37,6 @@ pub struct AnnotatedBlock {
     pub block: Block,
 }
 
-impl_bytes!(Namespace, 32, "Chain namespace.");
-
 /// Header type.
 ///
 /// # Note
@@ -85,12 +92,111 @@ impl Header {
     pub fn encoded_hash(&self) -> Hash {
         Hash::digest_bytes(&cbor::to_vec(&self))
     }
+
+    /// Returns the (typed) root of the state tree after this round.
+    pub fn state_tree_root(&self) -> Root {
+        Root {
+            namespace: self.namespace,
+            version: self.round,
+            root_type: RootType::State,
+            hash: self.state_root,
+        }
+    }
+
+    /// Returns the (typed) root of the I/O tree of this round.
+    pub fn io_tree_root(&self) -> Root {
+        Root {
+            namespace: self.namespace,
+            version: self.round,
+            root_type: RootType::IO,
+            hash: self.io_root,
+        }
+    }
+}
+
+/// Returns the storage receipt signature context for the given chain
+/// context.
+pub fn storage_receipt_context(chain_context: &str) -> Vec<u8> {
+    STORAGE_RECEIPT_CONTEXT.for_chain(chain_context)
+}
+
+/// Header verification error.
+#[derive(Debug, Error)]
+pub enum HeaderError {
+    #[error("roothash: missing storage receipt signatures")]
+    MissingStorageSignatures,
+    #[error("roothash: storage receipt signature without public key")]
+    MissingPublicKey,
 }
 
-/// Compute results header signature context.
-#[cfg_attr(not(target_env = "sgx"), allow(unused))]
-pub const COMPUTE_RESULTS_HEADER_CONTEXT: &'static [u8] =
-    b"oasis-core/roothash: compute results header";
+/// Storage receipt body.
+///
+/// This should be kept in sync with go/storage/api/api.go.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct StorageReceiptBody {
+    version: u16,
+    #[serde(rename = "ns")]
+    namespace: Namespace,
+    round: u64,
+    roots: Vec<Hash>,
+}
+
+impl Header {
+    /// Returns the merkle roots that must be part of a storage receipt.
+    pub fn roots_for_storage_receipt(&self) -> Vec<Hash> {
+        vec![self.io_root, self.state_root]
+    }
+
+    /// Returns the storage receipt body signed by storage nodes.
+    pub fn storage_receipt_body(&self) -> Vec<u8> {
+        cbor::to_vec(&StorageReceiptBody {
+            version: 1,
+            namespace: self.namespace,
+            round: self.round,
+            roots: self.roots_for_storage_receipt(),
+        })
+    }
+
+    /// Verify that all storage receipt signatures are valid signatures over
+    /// the header's merkle roots.
+    ///
+    /// Ensuring that the signatures were produced by the expected storage
+    /// nodes is the responsibility of the caller.
+    pub fn verify_storage_receipt_signatures(&self, chain_context: &str) -> Result<()> {
+        let signatures = match self.storage_signatures {
+            Some(ref signatures) if !signatures.is_empty() => signatures,
+            _ => return Err(HeaderError::MissingStorageSignatures.into()),
+        };
+
+        let body = self.storage_receipt_body();
+        // Signatures are verified serially, as all nodes must agree on
+        // whether they are valid (see PublicKey::verify_batch).
+        let context = storage_receipt_context(chain_context);
+        for bundle in signatures {
+            let public_key = bundle
+                .public_key
+                .as_ref()
+                .ok_or(HeaderError::MissingPublicKey)?;
+            bundle.signature.verify(public_key, &context, &body)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sign a storage receipt for the header's merkle roots.
+    pub fn sign_storage_receipt(
+        &self,
+        signer: &PrivateKey,
+        chain_context: &str,
+    ) -> Result<SignatureBundle> {
+        let body = self.storage_receipt_body();
+
+        Ok(SignatureBundle {
+            public_key: Some(signer.public_key()),
+            signature: signer.sign(&storage_receipt_context(chain_context), &body)?,
+        })
+    }
+}
 
 /// The header of a computed batch output by a runtime. This header is a
 /// compressed representation (e.g., hashes instead of full content) of
@@ -137,4 +243,36 @@ mod tests {
             Hash::from("c39e8aefea5a1f794fb57f294a4ea8599381cd8739e67a8a9acb7763b54a630a")
         );
     }
+
+    #[test]
+    fn test_storage_receipt_signatures() {
+        let mut header = Header {
+            round: 10,
+            io_root: Hash::empty_hash(),
+            state_root: Hash::digest_bytes(b"state"),
+            ..Default::default()
+        };
+        assert!(header
+            .verify_storage_receipt_signatures("test chain")
+            .is_err());
+
+        let signer = PrivateKey::generate();
+        header.storage_signatures = Some(vec![header
+            .sign_storage_receipt(&signer, "test chain")
+            .unwrap()]);
+        header
+            .verify_storage_receipt_signatures("test chain")
+            .expect("signatures should verify");
+
+        // Different chain.
+        assert!(header
+            .verify_storage_receipt_signatures("other chain")
+            .is_err());
+
+        // Different roots.
+        header.state_root = Hash::empty_hash();
+        assert!(header
+            .verify_storage_receipt_signatures("test chain")
+            .is_err());
+    }
 }
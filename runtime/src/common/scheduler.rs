@@ -0,0 +1,140 @@
+//! Scheduler structures.
+//!
+//! # Note
+//!
+//! This **MUST** be kept in sync with go/scheduler/api.
+//!
+use serde_derive::{Deserialize, Serialize};
+use serde_repr::*;
+
+use super::{crypto::signature::PublicKey, runtime::RuntimeId};
+
+/// Role of a node in a committee.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum Role {
+    /// Invalid role (should never appear on the wire).
+    Invalid = 0,
+    /// Worker.
+    Worker = 1,
+    /// Backup worker.
+    BackupWorker = 2,
+    /// Group leader.
+    Leader = 3,
+}
+
+impl Default for Role {
+    fn default() -> Self {
+        Role::Invalid
+    }
+}
+
+/// Functionality a committee exists to provide.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum CommitteeKind {
+    /// Invalid committee.
+    Invalid = 0,
+    /// Executor committee.
+    ComputeExecutor = 1,
+    /// Transaction scheduler committee.
+    ComputeTxnScheduler = 2,
+    /// Merge committee.
+    ComputeMerge = 3,
+    /// Storage committee.
+    Storage = 4,
+}
+
+impl Default for CommitteeKind {
+    fn default() -> Self {
+        CommitteeKind::Invalid
+    }
+}
+
+/// A node participating in a committee.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CommitteeNode {
+    /// Node's role in the committee.
+    pub role: Role,
+    /// Node's public key.
+    pub public_key: PublicKey,
+}
+
+/// A per-runtime committee.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Committee {
+    /// Functionality the committee exists to provide.
+    pub kind: CommitteeKind,
+    /// Committee members.
+    pub members: Vec<CommitteeNode>,
+    /// Runtime that the committee is for.
+    pub runtime_id: RuntimeId,
+    /// Epoch for which the committee is valid.
+    pub valid_for: u64,
+}
+
+impl Committee {
+    /// Committee leader, if the committee has one.
+    pub fn leader(&self) -> Option<&PublicKey> {
+        self.members
+            .iter()
+            .find(|member| member.role == Role::Leader)
+            .map(|member| &member.public_key)
+    }
+
+    /// Public keys of the members with the given role.
+    pub fn members_with_role(&self, role: Role) -> Vec<PublicKey> {
+        self.members
+            .iter()
+            .filter(|member| member.role == role)
+            .map(|member| member.public_key)
+            .collect()
+    }
+
+    /// Check whether the given node is a member of the committee.
+    pub fn is_member(&self, public_key: &PublicKey) -> bool {
+        self.members
+            .iter()
+            .any(|member| &member.public_key == public_key)
+    }
+}
+
+/// A consensus validator.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Validator {
+    /// Validator node identifier.
+    pub id: PublicKey,
+    /// Validator's consensus voting power.
+    pub voting_power: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_committee_members() {
+        let leader = PublicKey::from(vec![1u8; 32].as_slice());
+        let worker = PublicKey::from(vec![2u8; 32].as_slice());
+        let committee = Committee {
+            kind: CommitteeKind::ComputeTxnScheduler,
+            members: vec![
+                CommitteeNode {
+                    role: Role::Worker,
+                    public_key: worker,
+                },
+                CommitteeNode {
+                    role: Role::Leader,
+                    public_key: leader,
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(committee.leader(), Some(&leader));
+        assert_eq!(committee.members_with_role(Role::Worker), vec![worker]);
+        assert!(committee.members_with_role(Role::BackupWorker).is_empty());
+        assert!(committee.is_member(&worker));
+        assert!(!committee.is_member(&PublicKey::default()));
+    }
+}
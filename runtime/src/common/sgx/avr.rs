@@ -1,10 +1,10 @@
 //! Attestation verification report handling.
 use std::io::{Cursor, Read, Seek, SeekFrom};
 
+use anyhow::Result;
 use base64;
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
 use chrono::prelude::*;
-use failure::Fallible;
 use pem_iterator::{
     body::Single,
     boundary::{BoundaryParser, BoundaryType, LabelMatcher},
@@ -12,37 +12,39 @@ use pem_iterator::{
 use percent_encoding;
 use serde_derive::{Deserialize, Serialize};
 use serde_json;
-use sgx_isa::{AttributesFlags, Report};
+#[cfg(target_env = "sgx")]
+use sgx_isa::Report;
+use thiserror::Error;
 use webpki;
 
 use crate::common::time::{insecure_posix_time, update_insecure_posix_time};
 
 /// AVR verification error.
-#[derive(Debug, Fail)]
+#[derive(Debug, Error)]
 enum AVRError {
-    #[fail(display = "failed to parse report body")]
+    #[error("failed to parse report body")]
     MalformedReportBody,
-    #[fail(display = "report body did not contain timestamp")]
+    #[error("report body did not contain timestamp")]
     MissingTimestamp,
-    #[fail(display = "failed to parse timestamp")]
+    #[error("failed to parse timestamp")]
     MalformedTimestamp,
-    #[fail(display = "timestamp differs by more than 1 day")]
+    #[error("timestamp differs by more than 1 day")]
     TimestampOutOfRange,
-    #[fail(display = "rejecting quote status ({})", status)]
+    #[error("rejecting quote status ({status})")]
     QuoteStatusInvalid { status: String },
-    #[fail(display = "debug enclaves not allowed")]
+    #[error("debug enclaves not allowed")]
     DebugEnclave,
-    #[fail(display = "production enclaves not allowed")]
+    #[error("production enclaves not allowed")]
     ProductionEnclave,
-    #[fail(display = "AVR did not contain quote status")]
+    #[error("AVR did not contain quote status")]
     MissingQuoteStatus,
-    #[fail(display = "AVR did not contain quote body")]
+    #[error("AVR did not contain quote body")]
     MissingQuoteBody,
-    #[fail(display = "AVR did not contain nonce")]
+    #[error("AVR did not contain nonce")]
     MissingNonce,
-    #[fail(display = "failed to parse quote")]
+    #[error("failed to parse quote")]
     MalformedQuote,
-    #[fail(display = "unable to find any certificates")]
+    #[error("unable to find any certificates")]
     NoCertificates,
 }
 
@@ -130,6 +132,53 @@ static IAS_SIG_ALGS: &'static [&'static webpki::SignatureAlgorithm] =
 const PEM_CERTIFICATE_LABEL: &str = "CERTIFICATE";
 const IAS_TS_FMT: &str = "%FT%T%.6f";
 
+// SGX report body layout constants.
+const REPORT_BODY_SIZE: usize = 384;
+const REPORT_ATTRIBUTES_OFFSET: usize = 48;
+const REPORT_MRENCLAVE_OFFSET: usize = 64;
+const REPORT_MRSIGNER_OFFSET: usize = 128;
+const REPORT_REPORTDATA_OFFSET: usize = 320;
+const ATTRIBUTES_FLAGS_DEBUG: u64 = 0x02;
+
+/// Decoded SGX report body.
+///
+/// Only the fields required for AVR verification are decoded, so that
+/// verification does not depend on SGX support.
+#[derive(Default, Debug)]
+struct ReportBody {
+    attributes_flags: u64,
+    mr_enclave: MrEnclave,
+    mr_signer: MrSigner,
+    report_data: Vec<u8>,
+}
+
+impl ReportBody {
+    /// Decode report body.
+    fn decode(report_body: &[u8]) -> Result<ReportBody> {
+        if report_body.len() != REPORT_BODY_SIZE {
+            return Err(AVRError::MalformedReportBody.into());
+        }
+
+        Ok(ReportBody {
+            attributes_flags: LittleEndian::read_u64(
+                &report_body[REPORT_ATTRIBUTES_OFFSET..REPORT_ATTRIBUTES_OFFSET + 8],
+            ),
+            mr_enclave: MrEnclave::from(
+                &report_body[REPORT_MRENCLAVE_OFFSET..REPORT_MRENCLAVE_OFFSET + 32],
+            ),
+            mr_signer: MrSigner::from(
+                &report_body[REPORT_MRSIGNER_OFFSET..REPORT_MRSIGNER_OFFSET + 32],
+            ),
+            report_data: report_body[REPORT_REPORTDATA_OFFSET..].to_vec(),
+        })
+    }
+
+    /// Check whether the report was generated by a debug enclave.
+    fn is_debug(&self) -> bool {
+        self.attributes_flags & ATTRIBUTES_FLAGS_DEBUG != 0
+    }
+}
+
 /// Decoded quote body.
 #[derive(Default, Debug)]
 struct QuoteBody {
@@ -139,12 +188,12 @@ struct QuoteBody {
     isv_svn_qe: u16,
     isv_svn_pce: u16,
     basename: [u8; 32],
-    report_body: Report,
+    report_body: ReportBody,
 }
 
 impl QuoteBody {
     /// Decode quote body.
-    fn decode(quote_body: &Vec<u8>) -> Fallible<QuoteBody> {
+    fn decode(quote_body: &Vec<u8>) -> Result<QuoteBody> {
         let mut reader = Cursor::new(quote_body);
         let mut quote_body: QuoteBody = QuoteBody::default();
 
@@ -160,12 +209,9 @@ impl QuoteBody {
         reader.read_exact(&mut quote_body.basename)?;
 
         // Report body.
-        let mut report_buf = vec![0; Report::UNPADDED_SIZE];
-        reader.read(&mut report_buf)?;
-        quote_body.report_body = match Report::try_copy_from(&report_buf) {
-            Some(r) => r,
-            None => return Err(AVRError::MalformedReportBody.into()),
-        };
+        let mut report_buf = vec![0; REPORT_BODY_SIZE];
+        reader.read_exact(&mut report_buf)?;
+        quote_body.report_body = ReportBody::decode(&report_buf)?;
 
         Ok(quote_body)
     }
@@ -199,7 +245,7 @@ pub(crate) struct ParsedAVR {
 }
 
 impl ParsedAVR {
-    pub(crate) fn new(avr: &AVR) -> Fallible<Self> {
+    pub(crate) fn new(avr: &AVR) -> Result<Self> {
         let body = match serde_json::from_slice(&avr.body) {
             Ok(avr_body) => avr_body,
             _ => return Err(AVRError::MalformedReportBody.into()),
@@ -207,21 +253,21 @@ impl ParsedAVR {
         Ok(Self { body })
     }
 
-    fn isv_enclave_quote_status(&self) -> Fallible<String> {
+    fn isv_enclave_quote_status(&self) -> Result<String> {
         match self.body["isvEnclaveQuoteStatus"].as_str() {
             Some(status) => Ok(status.to_string()),
             None => Err(AVRError::MissingQuoteStatus.into()),
         }
     }
 
-    fn isv_enclave_quote_body(&self) -> Fallible<String> {
+    fn isv_enclave_quote_body(&self) -> Result<String> {
         match self.body["isvEnclaveQuoteBody"].as_str() {
             Some(quote_body) => Ok(quote_body.to_string()),
             None => Err(AVRError::MissingQuoteBody.into()),
         }
     }
 
-    fn timestamp(&self) -> Fallible<i64> {
+    fn timestamp(&self) -> Result<i64> {
         let timestamp = match self.body["timestamp"].as_str() {
             Some(timestamp) => timestamp,
             None => {
@@ -231,7 +277,7 @@ impl ParsedAVR {
         parse_avr_timestamp(&timestamp)
     }
 
-    pub(crate) fn nonce(&self) -> Fallible<String> {
+    pub(crate) fn nonce(&self) -> Result<String> {
         match self.body["nonce"].as_str() {
             Some(nonce) => Ok(nonce.to_string()),
             None => Err(AVRError::MissingNonce.into()),
@@ -240,7 +286,7 @@ impl ParsedAVR {
 }
 
 /// Verify attestation report.
-pub fn verify(avr: &AVR) -> Fallible<AuthenticatedAVR> {
+pub fn verify(avr: &AVR) -> Result<AuthenticatedAVR> {
     let unsafe_skip_avr_verification = option_env!("OASIS_UNSAFE_SKIP_AVR_VERIFY").is_some();
     let strict_avr_verification = option_env!("OASIS_STRICT_AVR_VERIFY").is_some();
 
@@ -302,11 +348,7 @@ pub fn verify(avr: &AVR) -> Fallible<AuthenticatedAVR> {
 
     // Disallow debug enclaves, if we are in production environment and disallow production enclaves,
     // if we are in debug environment.
-    let is_debug = quote_body
-        .report_body
-        .attributes
-        .flags
-        .contains(AttributesFlags::DEBUG);
+    let is_debug = quote_body.report_body.is_debug();
     let allow_debug = option_env!("OASIS_UNSAFE_ALLOW_DEBUG_ENCLAVES").is_some();
     if is_debug && !allow_debug {
         return Err(AVRError::DebugEnclave.into());
@@ -318,17 +360,17 @@ pub fn verify(avr: &AVR) -> Fallible<AuthenticatedAVR> {
     update_insecure_posix_time(timestamp);
 
     Ok(AuthenticatedAVR {
-        report_data: quote_body.report_body.reportdata.to_vec(),
+        report_data: quote_body.report_body.report_data,
         identity: EnclaveIdentity {
-            mr_enclave: MrEnclave::from(quote_body.report_body.mrenclave.to_vec()),
-            mr_signer: MrSigner::from(quote_body.report_body.mrsigner.to_vec()),
+            mr_enclave: quote_body.report_body.mr_enclave,
+            mr_signer: quote_body.report_body.mr_signer,
         },
         timestamp,
         nonce: nonce.to_string(),
     })
 }
 
-fn parse_avr_timestamp(timestamp: &str) -> Fallible<i64> {
+fn parse_avr_timestamp(timestamp: &str) -> Result<i64> {
     let timestamp_unix = match Utc.datetime_from_str(&timestamp, IAS_TS_FMT) {
         Ok(timestamp) => timestamp.timestamp(),
         _ => return Err(AVRError::MalformedTimestamp.into()),
@@ -341,7 +383,7 @@ fn validate_avr_signature(
     message: &[u8],
     signature: &[u8],
     unix_time: u64,
-) -> Fallible<()> {
+) -> Result<()> {
     // Load the Intel SGX Attestation Report Signing CA certificate.
     let anchors = webpki::TLSServerTrustAnchors(&IAS_ANCHORS);
 
@@ -370,7 +412,7 @@ fn validate_decoded_avr_signature(
     message: &[u8],
     signature: Vec<u8>,
     time: webpki::Time,
-) -> Fallible<()> {
+) -> Result<()> {
     assert!(cert_ders.len() >= 1);
     let (cert_der, inter_ders) = cert_ders.split_at(1);
     let inter_ders: Vec<_> = inter_ders.iter().map(|der| &der[..]).collect();
@@ -530,4 +572,34 @@ mod tests {
         let timestamp = parse_avr_timestamp("2018-03-30T22:02:26.123456").unwrap();
         assert_eq!(timestamp, SIG_AT as i64);
     }
+
+    #[test]
+    fn test_quote_body_decode() {
+        const MSG: &[u8] = include_bytes!("../../../testdata/avr_body_group_out_of_date.json");
+
+        let avr = AVR {
+            body: MSG.to_vec(),
+            signature: vec![],
+            certificate_chain: vec![],
+        };
+        let avr_body = ParsedAVR::new(&avr).unwrap();
+        let quote_body = base64::decode(&avr_body.isv_enclave_quote_body().unwrap()).unwrap();
+        let quote_body = QuoteBody::decode(&quote_body).unwrap();
+
+        let report_body = quote_body.report_body;
+        assert!(report_body.is_debug());
+        assert_eq!(
+            report_body.mr_enclave,
+            MrEnclave::from("83d1607d933a8f1970fa30ac94cdb6921fd8ffb8414650af06fe63c008a4a9af")
+        );
+        assert_eq!(
+            report_body.mr_signer,
+            MrSigner::from("83d719e77deaca1470f6baf62a4d774303c899db69020f9c70ee1dfc08c7ce9e")
+        );
+        assert_eq!(report_body.report_data.len(), 64);
+        assert_eq!(&report_body.report_data[..8], b"EkQ-Iden");
+
+        // Truncated quote bodies are rejected.
+        assert!(QuoteBody::decode(&vec![0; 48 + REPORT_BODY_SIZE - 1]).is_err());
+    }
 }
@@ -2,6 +2,7 @@
 
 use sgx_isa::Keypolicy;
 use sp800_185::KMac;
+use zeroize::Zeroize;
 
 #[cfg(target_env = "sgx")]
 use sgx_isa::{Keyname, Keyrequest};
@@ -70,13 +71,14 @@ pub fn egetkey(key_policy: Keypolicy, context: &[u8]) -> [u8; 32] {
 
     // Obtain the per-CPU package SGX sealing key, with the requested
     // policy.
-    let master_secret = egetkey_impl(key_policy, context);
+    let mut master_secret = egetkey_impl(key_policy, context);
 
     // Expand the 128 bit EGETKEY result into a 256 bit key, suitable
     // for use with our MRAE primitives.
     let mut kdf = KMac::new_kmac256(&master_secret, SEAL_KDF_CUSTOM);
     kdf.update(&context);
     kdf.finalize(&mut k);
+    master_secret.zeroize();
 
     k
 }
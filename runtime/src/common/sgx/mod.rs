@@ -1,5 +1,7 @@
 //! SGX-specific functionality.
 
 pub mod avr;
+#[cfg(feature = "sgx")]
 pub mod egetkey;
+#[cfg(feature = "sgx")]
 pub mod seal;
@@ -0,0 +1,155 @@
+//! Staking structures.
+//!
+//! # Note
+//!
+//! This **MUST** be kept in sync with go/staking/api.
+//!
+use serde_derive::{Deserialize, Serialize};
+use serde_repr::*;
+
+use super::{crypto::signature::PublicKey, quantity::Quantity};
+
+/// Method name of the transfer transaction.
+pub const METHOD_TRANSFER: &'static str = "staking.Transfer";
+/// Method name of the burn transaction.
+pub const METHOD_BURN: &'static str = "staking.Burn";
+/// Method name of the add escrow transaction.
+pub const METHOD_ADD_ESCROW: &'static str = "staking.AddEscrow";
+/// Method name of the reclaim escrow transaction.
+pub const METHOD_RECLAIM_ESCROW: &'static str = "staking.ReclaimEscrow";
+
+/// Kind of staking threshold.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize_repr, Deserialize_repr,
+)]
+#[repr(u8)]
+pub enum ThresholdKind {
+    Entity = 0,
+    NodeValidator = 1,
+    NodeCompute = 2,
+    NodeStorage = 3,
+    NodeKeyManager = 4,
+    RuntimeCompute = 5,
+    RuntimeKeyManager = 6,
+}
+
+/// A general-purpose account.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GeneralAccount {
+    /// Account balance.
+    pub balance: Quantity,
+    /// Nonce of the next transaction signed by the account.
+    pub nonce: u64,
+}
+
+/// A combined balance of several entries, the relative sizes of which are
+/// tracked through shares.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SharePool {
+    /// Total balance of the pool.
+    pub balance: Quantity,
+    /// Total number of shares issued by the pool.
+    pub total_shares: Quantity,
+}
+
+impl SharePool {
+    /// Number of tokens that the given number of shares is worth.
+    pub fn tokens_for_shares(&self, shares: &Quantity) -> Quantity {
+        shares
+            .checked_mul(&self.balance)
+            .and_then(|tokens| tokens.checked_div(&self.total_shares))
+            .unwrap_or_default()
+    }
+}
+
+/// An escrow account.
+///
+/// Only the balances are included, other fields are ignored when decoding.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EscrowAccount {
+    /// Active escrow balance.
+    pub active: SharePool,
+    /// Escrow balance that is being debonded.
+    pub debonding: SharePool,
+}
+
+/// An entry in the staking ledger.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Account {
+    /// General account.
+    pub general: GeneralAccount,
+    /// Escrow account.
+    pub escrow: EscrowAccount,
+}
+
+/// A delegation descriptor.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Delegation {
+    /// Shares of the escrow account's active pool.
+    pub shares: Quantity,
+}
+
+/// A debonding delegation descriptor.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DebondingDelegation {
+    /// Shares of the escrow account's debonding pool.
+    pub shares: Quantity,
+    /// Epoch at which the debonding ends.
+    #[serde(rename = "debond_end")]
+    pub debond_end_time: u64,
+}
+
+/// Body of a transfer transaction.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Transfer {
+    #[serde(rename = "xfer_to")]
+    pub to: PublicKey,
+    #[serde(rename = "xfer_tokens")]
+    pub tokens: Quantity,
+}
+
+/// Body of a burn transaction.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Burn {
+    #[serde(rename = "burn_tokens")]
+    pub tokens: Quantity,
+}
+
+/// Body of an add escrow transaction.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Escrow {
+    #[serde(rename = "escrow_account")]
+    pub account: PublicKey,
+    #[serde(rename = "escrow_tokens")]
+    pub tokens: Quantity,
+}
+
+/// Body of a reclaim escrow transaction.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ReclaimEscrow {
+    #[serde(rename = "escrow_account")]
+    pub account: PublicKey,
+    #[serde(rename = "reclaim_shares")]
+    pub shares: Quantity,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_pool() {
+        let pool = SharePool {
+            balance: Quantity::from(1000u64),
+            total_shares: Quantity::from(100u64),
+        };
+        assert_eq!(
+            pool.tokens_for_shares(&Quantity::from(10u64)),
+            Quantity::from(100u64)
+        );
+        assert_eq!(
+            SharePool::default().tokens_for_shares(&Quantity::from(10u64)),
+            Quantity::zero()
+        );
+    }
+}
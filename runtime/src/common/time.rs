@@ -25,8 +25,7 @@ struct Inner {
 pub fn insecure_posix_time() -> i64 {
     let mut inner = TIME_SOURCE.inner.lock().unwrap();
 
-    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-    let now = now.as_secs() as i64;
+    let now = system_time();
 
     if now < inner.timestamp {
         panic!("time: clock appeared to have ran backwards")
@@ -36,6 +35,19 @@ pub fn insecure_posix_time() -> i64 {
     inner.timestamp
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+fn system_time() -> i64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    now.as_secs() as i64
+}
+
+#[cfg(target_arch = "wasm32")]
+fn system_time() -> i64 {
+    // The system clock is not available on wasm32-unknown-unknown, use the
+    // JavaScript one instead.
+    (js_sys::Date::now() / 1000.0) as i64
+}
+
 // Returns `insecure_posix_time` as SystemTime.
 pub fn insecure_posix_system_time() -> SystemTime {
     UNIX_EPOCH + Duration::from_secs(insecure_posix_time() as u64)
@@ -1,12 +1,29 @@
 /// Protocol and runtime versioning.
-
 // NOTE: This should be kept in sync with go/common/version/version.go.
+use std::fmt;
+
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Version error.
+#[derive(Debug, Error)]
+pub enum VersionError {
+    #[error("incompatible versions (local: {local} remote: {remote})")]
+    Incompatible { local: Version, remote: Version },
+}
 
 /// A protocol or runtime version.
-#[derive(Clone, Copy, Debug)]
+///
+/// Versions are ordered by their major, minor and patch segments.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
 pub struct Version {
+    #[serde(rename = "Major")]
     major: u16,
+    #[serde(rename = "Minor")]
     minor: u16,
+    #[serde(rename = "Patch")]
     patch: u16,
 }
 
@@ -41,6 +58,44 @@ impl Version {
             patch: 0,
         }
     }
+
+    /// Extract the major segment of the Version only.
+    pub fn mask_to_major(&self) -> Version {
+        Version {
+            major: self.major,
+            minor: 0,
+            patch: 0,
+        }
+    }
+
+    /// Check whether the two versions are compatible.
+    ///
+    /// Versions are compatible if their major segments match. Before the
+    /// first major release, minor segments must match as well.
+    pub fn compatible_with(&self, other: &Version) -> bool {
+        match self.major {
+            0 => self.major_minor() == other.major_minor(),
+            _ => self.mask_to_major() == other.mask_to_major(),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Negotiate the version used to communicate with a remote party.
+///
+/// If the versions are compatible, the lower of the two is used as it
+/// identifies the features supported by both parties.
+pub fn negotiate(local: Version, remote: Version) -> Result<Version, VersionError> {
+    if !local.compatible_with(&remote) {
+        return Err(VersionError::Incompatible { local, remote });
+    }
+
+    Ok(local.min(remote))
 }
 
 // Returns the version as a platform-dependent u64.
@@ -69,3 +124,42 @@ pub const PROTOCOL_VERSION: Version = Version {
     minor: 14,
     patch: 0,
 };
+
+// Version of the protocol used between enclave RPC clients and enclaves. It is
+// negotiated during the session handshake.
+pub const ENCLAVE_RPC_PROTOCOL_VERSION: Version = Version {
+    major: 0,
+    minor: 1,
+    patch: 0,
+};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compatible_with() {
+        let v = Version::new;
+
+        assert!(v(1, 2, 3).compatible_with(&v(1, 0, 0)));
+        assert!(v(1, 2, 3).compatible_with(&v(1, 5, 1)));
+        assert!(!v(1, 2, 3).compatible_with(&v(2, 2, 3)));
+        assert!(v(0, 14, 0).compatible_with(&v(0, 14, 2)));
+        assert!(!v(0, 14, 0).compatible_with(&v(0, 15, 0)));
+        assert_eq!(v(1, 2, 3).mask_to_major(), v(1, 0, 0));
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let v = Version::new;
+
+        assert_eq!(negotiate(v(1, 2, 3), v(1, 5, 0)).unwrap(), v(1, 2, 3));
+        assert_eq!(negotiate(v(0, 14, 2), v(0, 14, 0)).unwrap(), v(0, 14, 0));
+
+        let err = negotiate(v(0, 14, 0), v(0, 13, 1)).unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "incompatible versions (local: 0.14.0 remote: 0.13.1)"
+        );
+    }
+}
@@ -1,15 +1,17 @@
 //! Runtime call dispatcher.
 use std::{
     convert::TryInto,
-    process,
+    mem, process,
     sync::{Arc, Condvar, Mutex},
     thread,
 };
 
+use ::tracing::info_span;
+use anyhow::Result;
 use crossbeam::channel;
-use failure::Fallible;
 use io_context::Context;
 use slog::Logger;
+use thiserror::Error;
 
 use crate::{
     common::{
@@ -21,7 +23,8 @@ use crate::{
         logger::get_logger,
         roothash::{Block, ComputeResultsHeader, COMPUTE_RESULTS_HEADER_CONTEXT},
     },
-    protocol::{Protocol, ProtocolUntrustedLocalStorage},
+    memory::{self, Subsystem},
+    protocol::{Protocol, ProtocolSpillStorage, ProtocolUntrustedLocalStorage},
     rak::RAK,
     rpc::{
         demux::Demux as RpcDemux,
@@ -31,22 +34,48 @@ use crate::{
     },
     storage::{
         mkvs::{
+            dedup_write_log,
             sync::{HostReadSyncer, NoopReadSyncer},
-            Root, Tree,
+            CompressedWriteLog, OverlayTree, Root, RootType, Tree, WriteLog,
         },
         StorageContext,
     },
+    tracing,
     transaction::{
         dispatcher::{Dispatcher as TxnDispatcher, NoopDispatcher as TxnNoopDispatcher},
         tree::Tree as TxnTree,
         types::TxnBatch,
         Context as TxnContext,
     },
-    types::{Body, ComputedBatch},
+    types::{Body, ComputedBatch, ErrorCode},
 };
 
 /// Maximum amount of requests that can be in the dispatcher queue.
 const BACKLOG_SIZE: usize = 10;
+/// Module name of dispatcher errors.
+const MODULE_NAME: &'static str = "runtime/dispatcher";
+
+/// Dispatcher error.
+#[derive(Debug, Error)]
+pub(crate) enum DispatcherError {
+    #[error("Request's method doesn't match untrusted_plaintext copy.")]
+    MethodMismatch,
+    #[error("invalid RPC message type")]
+    InvalidRpcMessageType,
+}
+
+impl ErrorCode for DispatcherError {
+    fn module_name(&self) -> &str {
+        MODULE_NAME
+    }
+
+    fn code(&self) -> u32 {
+        match self {
+            DispatcherError::MethodMismatch => 1,
+            DispatcherError::InvalidRpcMessageType => 2,
+        }
+    }
+}
 
 /// Interface for dispatcher initializers.
 pub trait Initializer: Send + Sync {
@@ -137,7 +166,7 @@ impl Dispatcher {
     }
 
     /// Queue a new request to be dispatched.
-    pub fn queue_request(&self, ctx: Context, id: u64, body: Body) -> Fallible<()> {
+    pub fn queue_request(&self, ctx: Context, id: u64, body: Body) -> Result<()> {
         self.queue_tx.try_send((ctx, id, body))?;
         Ok(())
     }
@@ -165,7 +194,7 @@ impl Dispatcher {
             Box::new(TxnNoopDispatcher::new())
         };
 
-        // Create common MKVS to use as a cache as long as the root stays the same.
+        // Create common MKVS to use as a cache, retained across rounds.
         let mut cache = Cache::new(&protocol, Default::default());
 
         'dispatch: loop {
@@ -214,6 +243,7 @@ impl Dispatcher {
                         io_root,
                         inputs,
                         block,
+                        beacon,
                     },
                 )) => {
                     // Transaction execution.
@@ -226,6 +256,7 @@ impl Dispatcher {
                         io_root,
                         inputs,
                         block,
+                        beacon,
                         false,
                     );
                 }
@@ -240,6 +271,7 @@ impl Dispatcher {
                         Hash::default(),
                         inputs,
                         block,
+                        vec![],
                         true,
                     );
                 }
@@ -262,11 +294,12 @@ impl Dispatcher {
         cache: &mut Cache,
         txn_dispatcher: &mut Box<dyn TxnDispatcher>,
         protocol: &Arc<Protocol>,
-        ctx: Context,
+        mut ctx: Context,
         id: u64,
         io_root: Hash,
         mut inputs: TxnBatch,
         block: Block,
+        beacon: Vec<u8>,
         check_only: bool,
     ) {
         debug!(self.logger, "Received transaction batch request";
@@ -274,26 +307,55 @@ impl Dispatcher {
             "check_only" => check_only,
         );
 
+        let span = if check_only {
+            info_span!(
+                "check_batch",
+                round = block.header.round,
+                size = inputs.len()
+            )
+        } else {
+            info_span!(
+                "execute_batch",
+                round = block.header.round,
+                size = inputs.len()
+            )
+        };
+        tracing::enter_span(&mut ctx, &span);
+        let _guard = span.enter();
+
         // Create a new context and dispatch the batch.
         let ctx = ctx.freeze();
-        cache.maybe_replace(
-            protocol,
-            Root {
-                namespace: block.header.namespace,
-                version: block.header.round,
-                hash: block.header.state_root,
-            },
-        );
+        if let Err(error) = cache.maybe_replace(block.header.state_tree_root()) {
+            error!(self.logger, "Error while switching state root"; "err" => %error);
+
+            protocol
+                .send_response(id, Body::Error(error.into()))
+                .unwrap();
+            return;
+        }
 
         let untrusted_local = Arc::new(ProtocolUntrustedLocalStorage::new(
             Context::create_child(&ctx),
             protocol.clone(),
         ));
-        let txn_ctx = TxnContext::new(ctx.clone(), &block.header, check_only);
-        let (mut outputs, mut tags, messages) =
-            StorageContext::enter(&mut cache.mkvs, untrusted_local.clone(), || {
-                txn_dispatcher.dispatch_batch(&inputs, txn_ctx)
-            });
+        let mut txn_ctx = TxnContext::new(ctx.clone(), &block.header, check_only);
+        if !beacon.is_empty() {
+            txn_ctx = txn_ctx.with_beacon(beacon);
+        }
+        let (mut outputs, mut tags, messages, prune_hints) =
+            if check_only && txn_dispatcher.check_pending_state() {
+                // Make state changes which have not yet been finalized visible to the check.
+                let mut overlay = cache.pending_overlay();
+                let result = StorageContext::enter(&mut overlay, untrusted_local.clone(), || {
+                    txn_dispatcher.dispatch_batch(&inputs, txn_ctx)
+                });
+                cache.restore(overlay);
+                result
+            } else {
+                StorageContext::enter(&mut cache.mkvs, untrusted_local.clone(), || {
+                    txn_dispatcher.dispatch_batch(&inputs, txn_ctx)
+                })
+            };
 
         if check_only {
             debug!(self.logger, "Transaction batch check complete");
@@ -304,29 +366,44 @@ impl Dispatcher {
                 .unwrap();
         } else {
             // Finalize state.
-            let (state_write_log, new_state_root) = cache
-                .mkvs
-                .commit(
-                    Context::create_child(&ctx),
-                    block.header.namespace,
-                    block.header.round + 1,
-                )
+            let (state_write_log, new_state_root) = info_span!("commit_state")
+                .in_scope(|| {
+                    cache.mkvs.commit(
+                        Context::create_child(&ctx),
+                        block.header.namespace,
+                        block.header.round + 1,
+                    )
+                })
                 .expect("state commit must succeed");
+            let state_write_log = dedup_write_log(state_write_log);
             txn_dispatcher.finalize(new_state_root);
             cache.root.version = block.header.round + 1;
             cache.root.hash = new_state_root;
+            cache.push_pending(block.header.state_tree_root(), &state_write_log);
 
             // Generate I/O root. Since we already fetched the inputs we avoid the need
             // to fetch them again by generating the previous I/O tree (generated by the
             // transaction scheduler) from the inputs.
-            let mut txn_tree = TxnTree::new(
+            let txn_tree = TxnTree::new(
                 Box::new(NoopReadSyncer {}),
                 Root {
                     namespace: block.header.namespace,
                     version: block.header.round + 1,
+                    root_type: RootType::IO,
                     hash: Hash::empty_hash(),
                 },
             );
+            let mut txn_tree = match txn_tree {
+                Ok(txn_tree) => txn_tree,
+                Err(error) => {
+                    error!(self.logger, "Error while creating I/O tree"; "err" => %error);
+
+                    protocol
+                        .send_response(id, Body::Error(error.into()))
+                        .unwrap();
+                    return;
+                }
+            };
             let mut hashes = Vec::new();
             for (batch_order, input) in inputs.drain(..).enumerate() {
                 hashes.push(Hash::digest_bytes(&input));
@@ -357,9 +434,10 @@ impl Dispatcher {
                     .expect("add transaction must succeed");
             }
 
-            let (io_write_log, io_root) = txn_tree
-                .commit(Context::create_child(&ctx))
+            let (io_write_log, io_root) = info_span!("commit_io")
+                .in_scope(|| txn_tree.commit(Context::create_child(&ctx)))
                 .expect("io commit must succeed");
+            let io_write_log = dedup_write_log(io_write_log);
 
             let header = ComputeResultsHeader {
                 previous_hash: block.header.encoded_hash(),
@@ -374,6 +452,20 @@ impl Dispatcher {
                 "state_root" => ?header.state_root
             );
 
+            let state_metrics = cache.mkvs.metrics();
+            debug!(self.logger, "State tree metrics";
+                "cache_hits" => state_metrics.hits,
+                "cache_misses" => state_metrics.misses,
+                "spill_hits" => state_metrics.spill_hits,
+                "sync_round_trips" => state_metrics.sync_round_trips,
+                "nodes_fetched" => state_metrics.nodes_fetched,
+                "bytes_synced" => state_metrics.bytes_synced,
+                "nodes_spilled" => state_metrics.nodes_spilled,
+                "write_log_entries" => state_metrics.write_log_entries,
+                "write_log_bytes" => state_metrics.write_log_bytes
+            );
+            cache.mkvs.reset_metrics();
+
             let rak_sig = if self.rak.public_key().is_some() {
                 self.rak
                     .sign(&COMPUTE_RESULTS_HEADER_CONTEXT, &cbor::to_vec(&header))
@@ -382,16 +474,38 @@ impl Dispatcher {
                 Signature::default()
             };
 
-            let result = ComputedBatch {
-                header,
-                io_write_log,
-                state_write_log,
-                rak_sig,
+            let result = if protocol.compressed_write_logs() {
+                ComputedBatch {
+                    header,
+                    io_write_log: WriteLog::new(),
+                    state_write_log: WriteLog::new(),
+                    compressed_io_write_log: Some(CompressedWriteLog::compress(io_write_log)),
+                    compressed_state_write_log: Some(CompressedWriteLog::compress(state_write_log)),
+                    prune_hints,
+                    rak_sig,
+                }
+            } else {
+                ComputedBatch {
+                    header,
+                    io_write_log,
+                    state_write_log,
+                    compressed_io_write_log: None,
+                    compressed_state_write_log: None,
+                    prune_hints,
+                    rak_sig,
+                }
             };
 
-            // Send the result back.
+            // Send the result back, together with the state tree metrics so
+            // that the host can export them.
             protocol
-                .send_response(id, Body::RuntimeExecuteTxBatchResponse { batch: result })
+                .send_response(
+                    id,
+                    Body::RuntimeExecuteTxBatchResponse {
+                        batch: result,
+                        state_metrics,
+                    },
+                )
                 .unwrap();
         }
     }
@@ -401,36 +515,43 @@ impl Dispatcher {
         rpc_demux: &mut RpcDemux,
         rpc_dispatcher: &mut RpcDispatcher,
         protocol: &Arc<Protocol>,
-        ctx: Context,
+        mut ctx: Context,
         id: u64,
         request: Vec<u8>,
         state_root: Hash,
     ) {
         debug!(self.logger, "Received RPC call request"; "state_root" => ?state_root);
 
-        // Process frame.
+        // Process frame, unless the RPC buffers would go over their limit.
         let mut buffer = vec![];
-        let result = match rpc_demux.process_frame(request, &mut buffer) {
+        let result = memory::check_limit(Subsystem::RpcBuffers, request.len())
+            .map_err(anyhow::Error::from)
+            .and_then(|_| {
+                memory::scope(Subsystem::RpcBuffers, || {
+                    rpc_demux.process_frame(request, &mut buffer)
+                })
+            });
+        let result = match result {
             Ok(result) => result,
             Err(error) => {
                 error!(self.logger, "Error while processing frame"; "err" => %error);
 
                 protocol
-                    .send_response(
-                        id,
-                        Body::Error {
-                            module: "".to_owned(), // XXX: Error codes.
-                            code: 0,               // XXX: Error codes.
-                            message: format!("{}", error),
-                        },
-                    )
+                    .send_response(id, Body::Error(error.into()))
                     .unwrap();
                 return;
             }
         };
 
         let protocol_response;
-        if let Some((session_id, session_info, message, untrusted_plaintext)) = result {
+        if let Some((session_id, session_info, message, untrusted_plaintext, span_context)) = result
+        {
+            // Attribute the call to the caller's span unless the host
+            // propagated its own.
+            if tracing::get_span_context(&ctx).map_or(true, |sc| sc.is_empty()) {
+                tracing::add_span_context(&mut ctx, span_context);
+            }
+
             // Dispatch request.
             assert!(
                 buffer.is_empty(),
@@ -446,21 +567,21 @@ impl Dispatcher {
                             "untrusted_plaintext" => ?untrusted_plaintext,
                             "method" => ?req.method
                         );
-                        let err_reponse = Body::Error {
-                            module: "".to_owned(), // XXX: Error codes.
-                            code: 0,               // XXX: Error codes.
-                            message: "Request's method doesn't match untrusted_plaintext copy."
-                                .to_string(),
-                        };
+                        let err_reponse = Body::Error(DispatcherError::MethodMismatch.into());
                         protocol.send_response(id, err_reponse).unwrap();
                         return;
                     }
 
+                    let span = info_span!("rpc_call", method = %req.method);
+                    tracing::enter_span(&mut ctx, &span);
+                    let _guard = span.enter();
+
                     // Request, dispatch.
                     let ctx = ctx.freeze();
                     let read_syncer = HostReadSyncer::new(protocol.clone());
                     let mut mkvs = Tree::make()
                         .with_root(Root {
+                            root_type: RootType::State,
                             hash: state_root,
                             ..Default::default()
                         })
@@ -476,14 +597,19 @@ impl Dispatcher {
                         });
                     let response = RpcMessage::Response(response);
 
-                    let (write_log, new_state_root) = mkvs
-                        .commit(Context::create_child(&ctx), Default::default(), 0)
+                    let (write_log, new_state_root) = info_span!("commit_state")
+                        .in_scope(|| {
+                            mkvs.commit(Context::create_child(&ctx), Default::default(), 0)
+                        })
                         .expect("mkvs commit must succeed");
 
                     debug!(self.logger, "RPC call dispatch complete"; "new_state_root" => ?new_state_root);
 
                     let mut buffer = vec![];
-                    match rpc_demux.write_message(session_id, response, &mut buffer) {
+                    let result = memory::scope(Subsystem::RpcBuffers, || {
+                        rpc_demux.write_message(session_id, response, &mut buffer)
+                    });
+                    match result {
                         Ok(_) => {
                             // Transmit response.
                             protocol_response = Body::RuntimeRPCCallResponse {
@@ -494,11 +620,7 @@ impl Dispatcher {
                         }
                         Err(error) => {
                             error!(self.logger, "Error while writing response"; "err" => %error);
-                            protocol_response = Body::Error {
-                                module: "".to_owned(), // XXX: Error codes.
-                                code: 0,               // XXX: Error codes.
-                                message: format!("{}", error),
-                            };
+                            protocol_response = Body::Error(error.into());
                         }
                     }
                 }
@@ -516,21 +638,13 @@ impl Dispatcher {
                         }
                         Err(error) => {
                             error!(self.logger, "Error while closing session"; "err" => %error);
-                            protocol_response = Body::Error {
-                                module: "".to_owned(), // XXX: Error codes.
-                                code: 0,               // XXX: Error codes.
-                                message: format!("{}", error),
-                            };
+                            protocol_response = Body::Error(error.into());
                         }
                     }
                 }
                 msg => {
                     warn!(self.logger, "Ignoring invalid RPC message type"; "msg" => ?msg);
-                    protocol_response = Body::Error {
-                        module: "".to_owned(), // XXX: Error codes.
-                        code: 0,               // XXX: Error codes.
-                        message: "invalid RPC message type".to_owned(),
-                    };
+                    protocol_response = Body::Error(DispatcherError::InvalidRpcMessageType.into());
                 }
             }
         } else {
@@ -549,7 +663,7 @@ impl Dispatcher {
         &self,
         rpc_dispatcher: &mut RpcDispatcher,
         protocol: &Arc<Protocol>,
-        ctx: Context,
+        mut ctx: Context,
         id: u64,
         request: Vec<u8>,
         state_root: Hash,
@@ -558,11 +672,16 @@ impl Dispatcher {
 
         let req: RpcRequest = cbor::from_slice(&request).unwrap();
 
+        let span = info_span!("local_rpc_call", method = %req.method);
+        tracing::enter_span(&mut ctx, &span);
+        let _guard = span.enter();
+
         // Request, dispatch.
         let ctx = ctx.freeze();
         let read_syncer = HostReadSyncer::new(protocol.clone());
         let mut mkvs = Tree::make()
             .with_root(Root {
+                root_type: RootType::State,
                 hash: state_root,
                 ..Default::default()
             })
@@ -588,27 +707,306 @@ impl Dispatcher {
     }
 }
 
+/// State changes of an executed batch which has not yet been finalized.
+struct PendingBatch {
+    /// State root the batch was executed on.
+    base: Root,
+    /// State root after executing the batch.
+    root: Root,
+    /// State write log of the batch.
+    write_log: WriteLog,
+}
+
 struct Cache {
     mkvs: Tree,
     root: Root,
+    pending: Vec<PendingBatch>,
 }
 
 impl Cache {
     fn new(protocol: &Arc<Protocol>, root: Root) -> Self {
         let read_syncer = HostReadSyncer::new(protocol.clone());
+        // Nodes evicted from the cache are spilled to the host's spill storage
+        // so they do not need to be fetched from remote storage again.
+        let spill_storage = Arc::new(ProtocolSpillStorage::new(
+            Context::background(),
+            protocol.clone(),
+        ));
         let mkvs = Tree::make()
             .with_capacity(100_000, 10_000_000)
+            .with_spill_storage(spill_storage)
             .with_root(root)
             .new(Box::new(read_syncer));
 
-        Self { mkvs, root }
+        Self::from_tree(mkvs, root)
     }
 
-    fn maybe_replace(&mut self, protocol: &Arc<Protocol>, root: Root) {
+    fn from_tree(mkvs: Tree, root: Root) -> Self {
+        Self {
+            mkvs,
+            root,
+            pending: Vec::new(),
+        }
+    }
+
+    fn maybe_replace(&mut self, root: Root) -> Result<()> {
+        // The cache must only hold state roots.
+        root.ensure_type(RootType::State)?;
         if self.root == root {
-            return;
+            return Ok(());
+        }
+
+        // Retain any cached nodes which are still valid under the new root.
+        self.mkvs.switch_root(root);
+        self.root = root;
+
+        Ok(())
+    }
+
+    /// Record the state changes of a batch executed on the given root, which
+    /// resulted in the current root.
+    fn push_pending(&mut self, base: Root, write_log: &WriteLog) {
+        // Re-executing on the same root supersedes the previous execution and
+        // anything based on it.
+        if let Some(pos) = self.pending.iter().position(|p| p.base == base) {
+            self.pending.truncate(pos);
+        }
+        // Only batches executed on top of each other are tracked.
+        if self.pending.last().map_or(false, |p| p.root != base) {
+            self.pending.clear();
+        }
+
+        self.pending.push(PendingBatch {
+            base,
+            root: self.root,
+            write_log: write_log.clone(),
+        });
+    }
+
+    /// Create an overlay over the current root, which includes the changes of
+    /// all batches executed on top of it which have not yet been finalized.
+    ///
+    /// The overlay must be passed to `restore` once it is no longer needed.
+    fn pending_overlay(&mut self) -> OverlayTree<Tree> {
+        // Batches executed before the current root have either been finalized
+        // or discarded.
+        match self.pending.iter().position(|p| p.base == self.root) {
+            Some(pos) => {
+                self.pending.drain(..pos);
+            }
+            None => self.pending.clear(),
+        }
+
+        let mkvs = mem::replace(
+            &mut self.mkvs,
+            Tree::make().new(Box::new(NoopReadSyncer {})),
+        );
+        let mut overlay = OverlayTree::new(mkvs);
+        for batch in &self.pending {
+            overlay.stage_write_log(&batch.write_log);
+        }
+        overlay
+    }
+
+    /// Discard the changes in an overlay created by `pending_overlay` and
+    /// restore the state tree.
+    fn restore(&mut self, overlay: OverlayTree<Tree>) {
+        self.mkvs = overlay.into_inner();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::mkvs::{LogEntry, MemoryTree, MKVS};
+
+    fn state_root(version: u64, hash: Hash) -> Root {
+        Root {
+            namespace: Default::default(),
+            version,
+            root_type: RootType::State,
+            hash,
+        }
+    }
+
+    fn pending_get(cache: &mut Cache, key: &[u8]) -> Option<Vec<u8>> {
+        let overlay = cache.pending_overlay();
+        let value = overlay.get(Context::background(), key).expect("get");
+        cache.restore(overlay);
+        value
+    }
+
+    #[test]
+    fn test_pending_overlay() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+        tree.insert(Context::background(), b"foo", b"bar")
+            .expect("insert");
+        let (_, hash) =
+            Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+        let root0 = state_root(0, hash);
+        let root1 = state_root(1, Hash::digest_bytes(b"root 1"));
+        let root2 = state_root(2, Hash::digest_bytes(b"root 2"));
+        let mut cache = Cache::from_tree(tree, root0);
+
+        // Execute two batches on top of each other.
+        cache.root = root1;
+        cache.push_pending(
+            root0,
+            &vec![LogEntry::new(b"foo", b"baz"), LogEntry::new(b"moo", b"boo")],
+        );
+        cache.root = root2;
+        cache.push_pending(
+            root1,
+            &vec![LogEntry {
+                key: b"moo".to_vec(),
+                value: None,
+            }],
+        );
+
+        // Checks against the original root should observe both batches.
+        cache.root = root0;
+        assert_eq!(pending_get(&mut cache, b"foo"), Some(b"baz".to_vec()));
+        assert_eq!(pending_get(&mut cache, b"moo"), None);
+        assert_eq!(cache.pending.len(), 2);
+
+        // The state tree should be restored without the pending changes.
+        assert_eq!(
+            cache.mkvs.get(Context::background(), b"foo").expect("get"),
+            Some(b"bar".to_vec())
+        );
+
+        // Once the first batch is finalized, only the second one remains pending.
+        cache.root = root1;
+        assert_eq!(pending_get(&mut cache, b"moo"), None);
+        assert_eq!(cache.pending.len(), 1);
+        assert_eq!(cache.pending[0].base, root1);
+    }
+
+    #[test]
+    fn test_push_pending() {
+        let tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+        let root0 = state_root(0, Hash::empty_hash());
+        let root1 = state_root(1, Hash::digest_bytes(b"root 1"));
+        let root1_alt = state_root(1, Hash::digest_bytes(b"root 1 alt"));
+        let root2 = state_root(2, Hash::digest_bytes(b"root 2"));
+        let other = state_root(5, Hash::digest_bytes(b"other"));
+        let mut cache = Cache::from_tree(tree, root0);
+
+        cache.root = root1;
+        cache.push_pending(root0, &vec![LogEntry::new(b"foo", b"bar")]);
+        cache.root = root2;
+        cache.push_pending(root1, &vec![LogEntry::new(b"foo", b"baz")]);
+        assert_eq!(cache.pending.len(), 2);
+
+        // Re-executing on the same root supersedes the previous execution and
+        // anything based on it.
+        cache.root = root1_alt;
+        cache.push_pending(root0, &vec![LogEntry::new(b"foo", b"qux")]);
+        assert_eq!(cache.pending.len(), 1);
+        assert_eq!(cache.pending[0].root, root1_alt);
+        assert_eq!(
+            cache.pending[0].write_log,
+            vec![LogEntry::new(b"foo", b"qux")]
+        );
+
+        // Executing on an unrelated root discards everything else.
+        cache.root = state_root(6, Hash::digest_bytes(b"other next"));
+        cache.push_pending(other, &vec![]);
+        assert_eq!(cache.pending.len(), 1);
+        assert_eq!(cache.pending[0].base, other);
+
+        // Checking against a root without pending batches discards them.
+        cache.root = root2;
+        assert_eq!(pending_get(&mut cache, b"foo"), None);
+        assert!(cache.pending.is_empty());
+    }
+
+    #[test]
+    fn test_cache_check_after_execute() {
+        let keys: Vec<Vec<u8>> = (0..100)
+            .map(|i| format!("key {}", i).into_bytes())
+            .collect();
+        let mut tree = MemoryTree::new();
+        for key in &keys {
+            MKVS::insert(&mut tree, Context::background(), key, b"value").expect("insert");
         }
+        let (_, hash) =
+            MKVS::commit(&mut tree, Context::background(), Default::default(), 1).expect("commit");
+        let max_depth = tree
+            .stats(Context::background(), 0)
+            .expect("stats")
+            .max_depth;
+        let root = state_root(1, hash);
+        let store = tree.store().clone();
+
+        let read_all = |cache: &Cache| {
+            for key in &keys[1..] {
+                assert_eq!(
+                    cache.mkvs.get(Context::background(), key).expect("get"),
+                    Some(b"value".to_vec())
+                );
+            }
+        };
 
-        *self = Self::new(protocol, root);
+        // Execute round 1, which updates a single key.
+        let mut cache = Cache::from_tree(
+            Tree::make()
+                .with_root(root)
+                .new(Box::new(store.read_syncer())),
+            root,
+        );
+        cache.maybe_replace(root).expect("maybe_replace");
+        read_all(&cache);
+        cache
+            .mkvs
+            .insert(Context::background(), &keys[0], b"updated")
+            .expect("insert");
+        let (_, next_hash) = cache
+            .mkvs
+            .commit(Context::background(), Default::default(), 2)
+            .expect("commit");
+        cache.root.version = 2;
+        cache.root.hash = next_hash;
+        let next_root = state_root(2, next_hash);
+
+        // Storage receives the same state.
+        let mut writer = store.open(root);
+        MKVS::insert(&mut writer, Context::background(), &keys[0], b"updated").expect("insert");
+        let (_, hash) = MKVS::commit(&mut writer, Context::background(), Default::default(), 2)
+            .expect("commit");
+        assert_eq!(hash, next_hash);
+
+        // Checking against round 1 should only need to fetch the nodes on the paths
+        // to the first key read and to the updated key, the rest of the cache is
+        // retained.
+        cache.maybe_replace(root).expect("maybe_replace");
+        cache.mkvs.reset_metrics();
+        read_all(&cache);
+        assert_eq!(
+            cache
+                .mkvs
+                .get(Context::background(), &keys[0])
+                .expect("get"),
+            Some(b"value".to_vec())
+        );
+        let metrics = cache.mkvs.metrics();
+        assert!(
+            metrics.nodes_fetched <= 2 * (max_depth as u64 + 1),
+            "check should not refetch the cache ({} nodes fetched)",
+            metrics.nodes_fetched
+        );
+
+        // Executing round 2 should not fetch anything.
+        cache.maybe_replace(next_root).expect("maybe_replace");
+        cache.mkvs.reset_metrics();
+        read_all(&cache);
+        assert_eq!(
+            cache
+                .mkvs
+                .get(Context::background(), &keys[0])
+                .expect("get"),
+            Some(b"updated".to_vec())
+        );
+        assert_eq!(cache.mkvs.metrics().sync_round_trips, 0);
     }
 }
@@ -0,0 +1,355 @@
+//! Logger forwarding `log` records to the worker host.
+//!
+//! Records are buffered and sent to the worker host in batches through the
+//! runtime host protocol, where they end up in the node's structured log.
+//! This makes the `log` macros usable inside enclaves. Until the logger is
+//! attached to the protocol by `start_runtime`, or if forwarding fails,
+//! records are written to stderr as JSON instead.
+//!
+//! # Examples
+//!
+//! ```rust,ignore
+//! let mut config = LoggerConfig::default();
+//! config.targets.insert("my_runtime::methods".to_owned(), LogLevelFilter::Debug);
+//! host_logger::init(config).unwrap();
+//!
+//! oasis_core_runtime::start_runtime(Some(Box::new(reg)), version);
+//! ```
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    mem,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use anyhow::Result;
+use io_context::Context;
+use lazy_static::lazy_static;
+use log::{self, Log, LogLevel, LogLevelFilter, LogMetadata};
+
+use crate::{
+    protocol::Protocol,
+    types::{Body, LogRecord},
+};
+
+/// Default number of buffered records after which they are flushed.
+pub const DEFAULT_BUFFER_SIZE: usize = 64;
+
+lazy_static! {
+    /// The installed host logger.
+    static ref HOST_LOGGER: RwLock<Option<Arc<HostLogger>>> = RwLock::new(None);
+}
+
+/// Host logger configuration.
+#[derive(Clone, Debug)]
+pub struct LoggerConfig {
+    /// Level filter of targets without a more specific filter.
+    pub level: LogLevelFilter,
+    /// Per-target level filters. The filter of the longest matching target
+    /// prefix applies, where prefixes only match whole module path segments.
+    pub targets: BTreeMap<String, LogLevelFilter>,
+    /// Number of buffered records after which they are flushed. Errors are
+    /// always flushed immediately.
+    pub buffer_size: usize,
+}
+
+impl Default for LoggerConfig {
+    fn default() -> Self {
+        Self {
+            level: LogLevelFilter::Info,
+            targets: BTreeMap::new(),
+            buffer_size: DEFAULT_BUFFER_SIZE,
+        }
+    }
+}
+
+impl LoggerConfig {
+    /// Level filter applying to the given target.
+    pub fn level_for(&self, target: &str) -> LogLevelFilter {
+        self.targets
+            .iter()
+            .filter(|(prefix, _)| matches_target(prefix, target))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.level)
+    }
+
+    /// Most verbose level filter of all targets.
+    fn max_level(&self) -> LogLevelFilter {
+        self.targets
+            .values()
+            .cloned()
+            .fold(self.level, ::std::cmp::max)
+    }
+}
+
+/// A logger buffering records and forwarding them to the worker host.
+pub struct HostLogger {
+    config: LoggerConfig,
+    buffer: Mutex<Vec<LogRecord>>,
+    protocol: RwLock<Option<Arc<Protocol>>>,
+}
+
+impl HostLogger {
+    /// Create a new host logger with the given configuration.
+    pub fn new(config: LoggerConfig) -> Self {
+        Self {
+            buffer: Mutex::new(Vec::with_capacity(config.buffer_size)),
+            config,
+            protocol: RwLock::new(None),
+        }
+    }
+
+    /// Forward records through the given protocol from now on, flushing any
+    /// records buffered so far.
+    pub fn attach(&self, protocol: Arc<Protocol>) {
+        *self.protocol.write().unwrap() = Some(protocol);
+        self.flush();
+    }
+
+    /// Forward all buffered records, writing them to stderr if they cannot
+    /// be forwarded.
+    pub fn flush(&self) {
+        let records = mem::replace(&mut *self.buffer.lock().unwrap(), vec![]);
+        if records.is_empty() {
+            return;
+        }
+
+        let protocol = self.protocol.read().unwrap().clone();
+        match protocol {
+            Some(protocol) => {
+                if let Err(error) = protocol.send_notification(
+                    Context::background(),
+                    Body::HostLogRequest {
+                        records: records.clone(),
+                    },
+                ) {
+                    // Keep the records and avoid recursing into the logger.
+                    let mut fields = BTreeMap::new();
+                    fields.insert("err".to_owned(), format!("{}", error));
+                    let mut records = records;
+                    records.push(LogRecord {
+                        level: "error".to_owned(),
+                        target: module_path!().to_owned(),
+                        message: format!("failed to forward {} log records", records.len()),
+                        fields,
+                    });
+                    write_stderr(&records);
+                }
+            }
+            None => write_stderr(&records),
+        }
+    }
+
+    /// Forward all buffered records without blocking, writing them to stderr
+    /// if they cannot be forwarded right away. Records are left buffered if
+    /// the buffer is in use.
+    fn try_flush(&self) {
+        let records = match self.buffer.try_lock() {
+            Ok(mut buffer) => mem::replace(&mut *buffer, vec![]),
+            Err(_) => return,
+        };
+        if records.is_empty() {
+            return;
+        }
+
+        let protocol = match self.protocol.try_read() {
+            Ok(protocol) => protocol.clone(),
+            Err(_) => None,
+        };
+        let forwarded = match protocol {
+            Some(protocol) => protocol
+                .try_send_notification(
+                    Context::background(),
+                    Body::HostLogRequest {
+                        records: records.clone(),
+                    },
+                )
+                .is_ok(),
+            None => false,
+        };
+        if !forwarded {
+            write_stderr(&records);
+        }
+    }
+
+    fn push(&self, record: LogRecord, flush: bool) {
+        let full = {
+            let mut buffer = self.buffer.lock().unwrap();
+            buffer.push(record);
+            buffer.len() >= self.config.buffer_size
+        };
+
+        if full || flush {
+            self.flush();
+        }
+    }
+}
+
+impl Log for HostLogger {
+    fn enabled(&self, metadata: &LogMetadata) -> bool {
+        metadata.level() <= self.config.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &log::LogRecord) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let location = record.location();
+        let mut fields = BTreeMap::new();
+        fields.insert("module".to_owned(), location.module_path().to_owned());
+        fields.insert("file".to_owned(), location.file().to_owned());
+        fields.insert("line".to_owned(), location.line().to_string());
+
+        self.push(
+            LogRecord {
+                level: level_name(record.level()).to_owned(),
+                target: record.target().to_owned(),
+                message: format!("{}", record.args()),
+                fields,
+            },
+            record.level() == LogLevel::Error,
+        );
+    }
+}
+
+/// The `log` logger delegating to the installed host logger.
+struct GlobalLogger(Arc<HostLogger>);
+
+impl Log for GlobalLogger {
+    fn enabled(&self, metadata: &LogMetadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::LogRecord) {
+        self.0.log(record)
+    }
+}
+
+/// Install a host logger with the given configuration as the `log` logger.
+///
+/// This must be called before `start_runtime`, which attaches the installed
+/// logger to the worker host protocol.
+pub fn init(config: LoggerConfig) -> Result<Arc<HostLogger>> {
+    let logger = Arc::new(HostLogger::new(config));
+    let global_logger = logger.clone();
+    log::set_logger(move |max_level| {
+        max_level.set(global_logger.config.max_level());
+        Box::new(GlobalLogger(global_logger))
+    })?;
+
+    *HOST_LOGGER.write().unwrap() = Some(logger.clone());
+    Ok(logger)
+}
+
+/// Attach the installed host logger, if any, to the given protocol.
+pub(crate) fn attach(protocol: Arc<Protocol>) {
+    let logger = HOST_LOGGER.read().unwrap().clone();
+    if let Some(logger) = logger {
+        logger.attach(protocol);
+    }
+}
+
+/// Forward all records buffered by the installed host logger, if any.
+pub fn flush() {
+    let logger = HOST_LOGGER.read().unwrap().clone();
+    if let Some(logger) = logger {
+        logger.flush();
+    }
+}
+
+/// Forward all records buffered by the installed host logger, if any, without
+/// blocking. This is safe to call from panic hooks.
+pub(crate) fn try_flush() {
+    let logger = match HOST_LOGGER.try_read() {
+        Ok(logger) => logger.clone(),
+        Err(_) => None,
+    };
+    if let Some(logger) = logger {
+        logger.try_flush();
+    }
+}
+
+fn matches_target(prefix: &str, target: &str) -> bool {
+    target.starts_with(prefix)
+        && (target.len() == prefix.len() || target[prefix.len()..].starts_with("::"))
+}
+
+fn level_name(level: LogLevel) -> &'static str {
+    match level {
+        LogLevel::Error => "error",
+        LogLevel::Warn => "warn",
+        LogLevel::Info => "info",
+        LogLevel::Debug => "debug",
+        LogLevel::Trace => "trace",
+    }
+}
+
+fn write_stderr(records: &[LogRecord]) {
+    let stderr = io::stderr();
+    let mut stderr = stderr.lock();
+    for record in records {
+        if serde_json::to_writer(&mut stderr, record).is_ok() {
+            let _ = stderr.write_all(b"\n");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(message: &str) -> LogRecord {
+        LogRecord {
+            level: "info".to_owned(),
+            target: "test".to_owned(),
+            message: message.to_owned(),
+            fields: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_level_for() {
+        let mut config = LoggerConfig::default();
+        config
+            .targets
+            .insert("runtime::rpc".to_owned(), LogLevelFilter::Debug);
+        config
+            .targets
+            .insert("runtime::rpc::session".to_owned(), LogLevelFilter::Off);
+
+        assert_eq!(config.level_for("runtime"), LogLevelFilter::Info);
+        assert_eq!(config.level_for("runtime::rpc"), LogLevelFilter::Debug);
+        assert_eq!(
+            config.level_for("runtime::rpc::dispatcher"),
+            LogLevelFilter::Debug
+        );
+        assert_eq!(
+            config.level_for("runtime::rpc::session"),
+            LogLevelFilter::Off
+        );
+        // Prefixes only match whole path segments.
+        assert_eq!(config.level_for("runtime::rpcs"), LogLevelFilter::Info);
+        assert_eq!(config.max_level(), LogLevelFilter::Debug);
+    }
+
+    #[test]
+    fn test_buffering() {
+        let logger = HostLogger::new(LoggerConfig {
+            buffer_size: 2,
+            ..Default::default()
+        });
+
+        logger.push(record("first"), false);
+        assert_eq!(*logger.buffer.lock().unwrap(), vec![record("first")]);
+
+        // Records are flushed once the buffer is full.
+        logger.push(record("second"), false);
+        assert!(logger.buffer.lock().unwrap().is_empty());
+
+        // Or when requested.
+        logger.push(record("third"), true);
+        assert!(logger.buffer.lock().unwrap().is_empty());
+    }
+}
@@ -5,6 +5,7 @@ use crate::{
         version::Version,
     },
     dispatcher::{Dispatcher, Initializer},
+    host_logger, panic_handler,
     protocol::{Protocol, Stream},
     rak::RAK,
 };
@@ -22,6 +23,23 @@ pub fn start_runtime(initializer: Box<dyn Initializer>, version: Version) {
     let logger = get_logger("runtime");
     info!(logger, "Runtime is starting");
 
+    // Report panics to the worker host.
+    panic_handler::install();
+
+    // Initialize span export.
+    #[cfg(feature = "jaeger")]
+    {
+        if let Ok(agent) = env::var("OASIS_JAEGER_AGENT") {
+            let result = agent
+                .parse()
+                .map_err(anyhow::Error::from)
+                .and_then(|agent| crate::tracing::init_jaeger("oasis-runtime", agent));
+            if let Err(error) = result {
+                error!(logger, "Failed to initialize span export"; "err" => %error);
+            }
+        }
+    }
+
     // Initialize runtime attestation key.
     let rak = Arc::new(RAK::new());
 
@@ -56,6 +74,8 @@ pub fn start_runtime(initializer: Box<dyn Initializer>, version: Version) {
         dispatcher.clone(),
         version,
     ));
+    host_logger::attach(protocol.clone());
+    panic_handler::attach(protocol.clone());
 
     protocol.start();
 
@@ -13,6 +13,13 @@
 #![feature(test)]
 #![feature(box_into_pin)]
 #![feature(arbitrary_self_types)]
+#![feature(backtrace)]
+#![feature(thread_local)]
+
+#[cfg(all(target_env = "sgx", not(feature = "sgx")))]
+compile_error!("the sgx feature is required when building for SGX");
+#[cfg(all(target_env = "sgx", feature = "jaeger"))]
+compile_error!("the jaeger feature is not supported when building for SGX");
 
 #[macro_use]
 extern crate slog;
@@ -27,13 +34,15 @@ extern crate slog_json;
 extern crate slog_scope;
 extern crate slog_stdlog;
 #[macro_use]
-extern crate failure;
+extern crate anyhow;
 extern crate base64;
 extern crate bincode;
 extern crate chrono;
 #[macro_use]
 extern crate intrusive_collections;
 extern crate io_context;
+#[macro_use]
+extern crate oasis_core_common;
 extern crate pem_iterator;
 extern crate percent_encoding;
 extern crate rand;
@@ -53,8 +62,11 @@ use sgx_isa::{AttributesFlags, Report};
 pub mod common;
 pub mod dispatcher;
 pub mod executor;
+pub mod host_logger;
 pub mod init;
 pub mod macros;
+pub mod memory;
+mod panic_handler;
 pub mod protocol;
 pub mod rak;
 pub mod rpc;
@@ -63,11 +75,20 @@ pub mod tracing;
 pub mod transaction;
 pub mod types;
 
+/// Dependencies used by the exported macros.
+#[doc(hidden)]
+pub mod __private {
+    pub use anyhow;
+}
+
 use crate::common::version::{Version, PROTOCOL_VERSION};
 
 #[cfg(target_env = "sgx")]
 use self::common::sgx::avr::{EnclaveIdentity, MrSigner};
 
+// Re-exports.
+pub use oasis_core_common::{impl_bytes, impl_secret_bytes};
+
 lazy_static! {
     pub static ref BUILD_INFO: BuildInfo = {
         // Non-SGX builds are insecure by definition.
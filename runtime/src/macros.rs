@@ -71,7 +71,7 @@ macro_rules! runtime_api {
 /// # Examples
 ///
 /// ```rust,ignore
-/// fn my_call(args: &bool, ctx: &mut TxnContext) -> Fallible<()> {
+/// fn my_call(args: &bool, ctx: &mut TxnContext) -> Result<()> {
 ///     let rctx = runtime_context!(ctx, MyContext);
 ///
 ///     // ...
@@ -91,7 +91,7 @@ macro_rules! runtime_context {
 /// # Examples
 ///
 /// ```rust,ignore
-/// fn end_batch(&self, ctx: TxnContext) -> Fallible<()> {
+/// fn end_batch(&self, ctx: TxnContext) -> Result<()> {
 ///     let rctx = runtime_context_move!(ctx, MyContext);
 ///
 ///     // ...
@@ -0,0 +1,336 @@
+//! Enclave memory accounting.
+//!
+//! The `AccountingAllocator` tracks live heap allocations by the subsystem
+//! which made them. Code attributes its allocations to a subsystem by running
+//! them in a `scope`, and checks the subsystem's soft limit via `check_limit`
+//! before taking on more work. Going over a limit thus results in an
+//! `OutOfMemory` error instead of the enclave aborting once the heap is
+//! exhausted.
+//!
+//! With the `memory-accounting` feature, SGX builds install the
+//! `AccountingAllocator` as their global allocator. Otherwise allocations are
+//! not tracked and limits are never reached.
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    collections::BTreeMap,
+    ptr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::types::ErrorCode;
+
+/// Module name of memory errors.
+const MODULE_NAME: &'static str = "runtime/memory";
+
+/// Number of subsystems.
+const SUBSYSTEM_COUNT: usize = 4;
+/// Limit value meaning that there is no limit.
+const NO_LIMIT: usize = usize::max_value();
+
+/// A subsystem allocations are attributed to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subsystem {
+    /// Allocations which are not attributed to any particular subsystem.
+    Other = 0,
+    /// MKVS node cache.
+    MkvsCache = 1,
+    /// EnclaveRPC session state and frame buffers.
+    RpcBuffers = 2,
+    /// Key manager client key cache.
+    KeyCache = 3,
+}
+
+impl Subsystem {
+    /// All subsystems.
+    pub const ALL: [Subsystem; SUBSYSTEM_COUNT] = [
+        Subsystem::Other,
+        Subsystem::MkvsCache,
+        Subsystem::RpcBuffers,
+        Subsystem::KeyCache,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Memory error.
+#[derive(Debug, Error)]
+pub enum MemoryError {
+    #[error("out of memory in {subsystem:?} (used: {used} limit: {limit})")]
+    OutOfMemory {
+        subsystem: Subsystem,
+        used: usize,
+        limit: usize,
+    },
+}
+
+impl ErrorCode for MemoryError {
+    fn module_name(&self) -> &str {
+        MODULE_NAME
+    }
+
+    fn code(&self) -> u32 {
+        match self {
+            MemoryError::OutOfMemory { .. } => 1,
+        }
+    }
+}
+
+/// Live bytes by subsystem.
+static LIVE_BYTES: [AtomicUsize; SUBSYSTEM_COUNT] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+/// Live allocations by subsystem.
+static LIVE_ALLOCATIONS: [AtomicUsize; SUBSYSTEM_COUNT] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+/// Soft limits on live bytes by subsystem.
+static LIMITS: [AtomicUsize; SUBSYSTEM_COUNT] = [
+    AtomicUsize::new(NO_LIMIT),
+    AtomicUsize::new(NO_LIMIT),
+    AtomicUsize::new(NO_LIMIT),
+    AtomicUsize::new(NO_LIMIT),
+];
+
+// A lazily initialized thread local could allocate, so this must be a
+// plain #[thread_local] to be usable from within the allocator.
+#[thread_local]
+static CURRENT: Cell<Subsystem> = Cell::new(Subsystem::Other);
+
+#[cfg(all(target_env = "sgx", feature = "memory-accounting"))]
+#[global_allocator]
+static ALLOCATOR: AccountingAllocator = AccountingAllocator;
+
+/// A global allocator which accounts allocations to the subsystem of the
+/// current `scope`.
+///
+/// The subsystem is stored in front of each allocation, so memory is always
+/// accounted to the subsystem which allocated it, no matter where it is
+/// freed.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// #[global_allocator]
+/// static ALLOCATOR: AccountingAllocator = AccountingAllocator;
+/// ```
+pub struct AccountingAllocator;
+
+impl AccountingAllocator {
+    /// Offset of an allocation with the given layout from the start of its
+    /// block.
+    ///
+    /// The subsystem is stored in the byte right before the allocation, so
+    /// the offset keeps the allocation aligned.
+    fn offset(layout: &Layout) -> usize {
+        layout.align()
+    }
+
+    /// Layout of the block of an allocation with the given layout.
+    fn block_layout(layout: &Layout) -> Option<Layout> {
+        let size = layout.size().checked_add(Self::offset(layout))?;
+        Layout::from_size_align(size, layout.align()).ok()
+    }
+}
+
+unsafe impl GlobalAlloc for AccountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let block_layout = match Self::block_layout(&layout) {
+            Some(block_layout) => block_layout,
+            None => return ptr::null_mut(),
+        };
+        let block = System.alloc(block_layout);
+        if block.is_null() {
+            return block;
+        }
+
+        let offset = Self::offset(&layout);
+        let subsystem = CURRENT.get();
+        *block.add(offset - 1) = subsystem as u8;
+        LIVE_BYTES[subsystem.index()].fetch_add(layout.size(), Ordering::Relaxed);
+        LIVE_ALLOCATIONS[subsystem.index()].fetch_add(1, Ordering::Relaxed);
+
+        block.add(offset)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let offset = Self::offset(&layout);
+        let block = ptr.sub(offset);
+        let index = *block.add(offset - 1) as usize;
+        LIVE_BYTES[index].fetch_sub(layout.size(), Ordering::Relaxed);
+        LIVE_ALLOCATIONS[index].fetch_sub(1, Ordering::Relaxed);
+
+        // The block layout was valid when allocating.
+        let block_layout =
+            Layout::from_size_align_unchecked(layout.size() + offset, layout.align());
+        System.dealloc(block, block_layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let offset = Self::offset(&layout);
+        let new_block_size = match new_size.checked_add(offset) {
+            Some(new_block_size) => new_block_size,
+            None => return ptr::null_mut(),
+        };
+        let block = ptr.sub(offset);
+        let block_layout =
+            Layout::from_size_align_unchecked(layout.size() + offset, layout.align());
+        let new_block = System.realloc(block, block_layout, new_block_size);
+        if new_block.is_null() {
+            return new_block;
+        }
+
+        // The allocation stays with the subsystem which made it.
+        let index = *new_block.add(offset - 1) as usize;
+        LIVE_BYTES[index].fetch_sub(layout.size(), Ordering::Relaxed);
+        LIVE_BYTES[index].fetch_add(new_size, Ordering::Relaxed);
+
+        new_block.add(offset)
+    }
+}
+
+/// Run the given function with the allocations made by the current thread
+/// accounted to the given subsystem.
+pub fn scope<F, R>(subsystem: Subsystem, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    struct Guard(Subsystem);
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            CURRENT.set(self.0);
+        }
+    }
+
+    let _guard = Guard(CURRENT.replace(subsystem));
+    f()
+}
+
+/// Set the soft limit on the live bytes of the given subsystem.
+pub fn set_limit(subsystem: Subsystem, limit: Option<usize>) {
+    LIMITS[subsystem.index()].store(limit.unwrap_or(NO_LIMIT), Ordering::SeqCst);
+}
+
+/// Soft limit on the live bytes of the given subsystem, if any.
+pub fn limit(subsystem: Subsystem) -> Option<usize> {
+    match LIMITS[subsystem.index()].load(Ordering::SeqCst) {
+        NO_LIMIT => None,
+        limit => Some(limit),
+    }
+}
+
+/// Check that the given subsystem can allocate the given number of bytes
+/// without going over its soft limit.
+pub fn check_limit(subsystem: Subsystem, size: usize) -> Result<(), MemoryError> {
+    let limit = LIMITS[subsystem.index()].load(Ordering::SeqCst);
+    let used = LIVE_BYTES[subsystem.index()].load(Ordering::Relaxed);
+    if used.saturating_add(size) > limit {
+        return Err(MemoryError::OutOfMemory {
+            subsystem,
+            used,
+            limit,
+        });
+    }
+
+    Ok(())
+}
+
+/// Memory statistics of a subsystem.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubsystemStats {
+    /// Number of live bytes.
+    pub live_bytes: u64,
+    /// Number of live allocations.
+    pub live_allocations: u64,
+    /// Soft limit on the number of live bytes, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+}
+
+/// Memory statistics, as reported in the runtime health response.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryStats {
+    /// Whether allocations are accounted.
+    pub accounting: bool,
+    /// Statistics by subsystem.
+    pub subsystems: BTreeMap<Subsystem, SubsystemStats>,
+}
+
+/// Current memory statistics.
+pub fn stats() -> MemoryStats {
+    MemoryStats {
+        accounting: cfg!(all(target_env = "sgx", feature = "memory-accounting")),
+        subsystems: Subsystem::ALL
+            .iter()
+            .map(|&subsystem| {
+                let index = subsystem.index();
+                let stats = SubsystemStats {
+                    live_bytes: LIVE_BYTES[index].load(Ordering::Relaxed) as u64,
+                    live_allocations: LIVE_ALLOCATIONS[index].load(Ordering::Relaxed) as u64,
+                    limit: limit(subsystem).map(|limit| limit as u64),
+                };
+                (subsystem, stats)
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_memory_accounting() {
+        // The allocator is not installed in tests, so only allocations made
+        // through it directly are accounted. Other tests do not use the key
+        // cache subsystem.
+        let allocator = AccountingAllocator;
+        let live_bytes = || stats().subsystems[&Subsystem::KeyCache].live_bytes;
+        let base = live_bytes();
+
+        let layout = Layout::from_size_align(100, 16).unwrap();
+        let ptr = scope(Subsystem::KeyCache, || unsafe { allocator.alloc(layout) });
+        assert!(!ptr.is_null());
+        assert_eq!(ptr as usize % 16, 0);
+        assert_eq!(CURRENT.get(), Subsystem::Other);
+        assert_eq!(live_bytes(), base + 100);
+
+        // Reallocations and frees are accounted to the allocating subsystem.
+        let ptr = scope(Subsystem::MkvsCache, || unsafe {
+            allocator.realloc(ptr, layout, 200)
+        });
+        assert!(!ptr.is_null());
+        assert_eq!(live_bytes(), base + 200);
+
+        set_limit(Subsystem::KeyCache, Some(base as usize + 250));
+        assert!(check_limit(Subsystem::KeyCache, 50).is_ok());
+        match check_limit(Subsystem::KeyCache, 51) {
+            Err(MemoryError::OutOfMemory { subsystem, .. }) => {
+                assert_eq!(subsystem, Subsystem::KeyCache)
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(
+            stats().subsystems[&Subsystem::KeyCache].limit,
+            Some(base + 250)
+        );
+        set_limit(Subsystem::KeyCache, None);
+        assert!(check_limit(Subsystem::KeyCache, usize::max_value()).is_ok());
+
+        unsafe { allocator.dealloc(ptr, Layout::from_size_align(200, 16).unwrap()) };
+        assert_eq!(live_bytes(), base);
+    }
+}
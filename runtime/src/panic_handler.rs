@@ -0,0 +1,181 @@
+//! Panic handler reporting panics to the worker host.
+//!
+//! Once installed by `start_runtime`, the handler sends a report with the
+//! panic message, its location and a best-effort backtrace to the worker
+//! host, after running the previously installed panic hook. As runtimes are
+//! built to abort on panics, this is their final protocol message, so a dying
+//! runtime leaves diagnostics in the node's log instead of exiting silently.
+//!
+//! The handler may run while the panicking thread holds any lock, so it never
+//! waits for a lock. Until the handler is attached to the protocol, or if the
+//! protocol's stream is in use, the report is written to stderr as JSON
+//! instead. Once the stream has been acquired, writing the report may block
+//! until the host reads from the stream.
+//!
+//! Panics on different threads are reported independently. A panic raised
+//! while the same thread is reporting another panic is only written to
+//! stderr.
+use std::{
+    any::Any,
+    backtrace::{Backtrace, BacktraceStatus},
+    cell::Cell,
+    io::{self, Write},
+    panic::{self, PanicInfo},
+    sync::{Arc, RwLock},
+    thread,
+};
+
+use io_context::Context;
+use lazy_static::lazy_static;
+
+use crate::{
+    host_logger,
+    protocol::Protocol,
+    types::{Body, PanicReport},
+};
+
+thread_local! {
+    /// Whether the current thread is reporting a panic.
+    static REPORTING: Cell<bool> = Cell::new(false);
+}
+
+lazy_static! {
+    /// Protocol panics are reported through.
+    static ref PROTOCOL: RwLock<Option<Arc<Protocol>>> = RwLock::new(None);
+}
+
+/// Install the panic handler, chaining the current panic hook.
+pub(crate) fn install() {
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        previous_hook(info);
+
+        let report = build_report(info);
+        // Panics raised while reporting another panic on the same thread
+        // (or during thread teardown) are only written to stderr.
+        match REPORTING.try_with(|reporting| reporting.replace(true)) {
+            Ok(false) => {
+                handle_panic(report);
+                let _ = REPORTING.try_with(|reporting| reporting.set(false));
+            }
+            _ => write_stderr(&report),
+        }
+    }));
+}
+
+/// Report panics through the given protocol from now on.
+pub(crate) fn attach(protocol: Arc<Protocol>) {
+    *PROTOCOL.write().unwrap_or_else(|err| err.into_inner()) = Some(protocol);
+}
+
+fn handle_panic(report: PanicReport) {
+    // Make sure the records logged before the panic are not lost.
+    host_logger::try_flush();
+
+    let protocol = match PROTOCOL.try_read() {
+        Ok(protocol) => protocol.clone(),
+        Err(_) => None,
+    };
+    let reported = match protocol {
+        Some(protocol) => protocol
+            .try_send_notification(
+                Context::background(),
+                Body::HostPanicRequest {
+                    report: report.clone(),
+                },
+            )
+            .is_ok(),
+        None => false,
+    };
+    if !reported {
+        write_stderr(&report);
+    }
+}
+
+fn build_report(info: &PanicInfo) -> PanicReport {
+    let (file, line, column) = match info.location() {
+        Some(location) => (
+            location.file().to_owned(),
+            location.line(),
+            location.column(),
+        ),
+        None => (String::new(), 0, 0),
+    };
+
+    // Symbols may not be available (e.g., in enclaves), in which case the
+    // backtrace only consists of addresses.
+    let backtrace = Backtrace::force_capture();
+    let backtrace = match backtrace.status() {
+        BacktraceStatus::Captured => format!("{}", backtrace),
+        _ => String::new(),
+    };
+
+    PanicReport {
+        message: panic_message(info.payload()).to_owned(),
+        thread: thread::current().name().unwrap_or_default().to_owned(),
+        file,
+        line,
+        column,
+        backtrace,
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg
+    } else {
+        "Box<Any>"
+    }
+}
+
+fn write_stderr(report: &PanicReport) {
+    let stderr = io::stderr();
+    let mut stderr = stderr.lock();
+    if serde_json::to_writer(&mut stderr, report).is_ok() {
+        let _ = stderr.write_all(b"\n");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{mpsc, Mutex};
+
+    use super::*;
+
+    #[test]
+    fn test_build_report() {
+        // Panic hooks are global, so only capture the report of the panic
+        // in the spawned thread and leave others to the previous hook.
+        const THREAD_NAME: &str = "panic-handler-test";
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let previous_hook = Arc::new(panic::take_hook());
+        let hook = previous_hook.clone();
+        panic::set_hook(Box::new(move |info| {
+            if thread::current().name() == Some(THREAD_NAME) {
+                let _ = tx.lock().unwrap().send(build_report(info));
+            } else {
+                hook(info);
+            }
+        }));
+
+        let line = line!() + 3;
+        let result = thread::Builder::new()
+            .name(THREAD_NAME.to_owned())
+            .spawn(|| panic!("runtime failed: {}", 42))
+            .unwrap()
+            .join();
+        let _ = panic::take_hook();
+        panic::set_hook(Box::new(move |info| previous_hook(info)));
+
+        assert!(result.is_err());
+        let report = rx.recv().unwrap();
+        assert_eq!(report.message, "runtime failed: 42");
+        assert_eq!(report.thread, THREAD_NAME);
+        assert_eq!(report.file, file!());
+        assert_eq!(report.line, line);
+        assert!(report.column > 0);
+    }
+}
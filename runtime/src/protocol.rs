@@ -3,24 +3,31 @@ use std::{
     collections::HashMap,
     io::{BufReader, BufWriter, Read, Write},
     sync::{
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc, Mutex,
     },
 };
 
+use anyhow::Result;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use crossbeam::channel;
-use failure::Fallible;
 use io_context::Context;
 use slog::Logger;
+use thiserror::Error;
 
 use crate::{
-    common::{cbor, logger::get_logger, runtime::RuntimeId, version::Version},
+    common::{
+        cbor,
+        logger::get_logger,
+        runtime::RuntimeId,
+        version::{self, Version},
+    },
     dispatcher::Dispatcher,
+    memory,
     rak::RAK,
-    storage::KeyValue,
+    storage::{KeyValue, SpillStorage},
     tracing,
-    types::{Body, Message, MessageType},
+    types::{Body, ErrorCode, Message, MessageType, SpillStorageEntry},
     BUILD_INFO,
 };
 
@@ -32,19 +39,63 @@ pub type Stream = ::std::net::TcpStream;
 /// Maximum message size.
 const MAX_MESSAGE_SIZE: usize = 104_857_600; // 100MB
 
-#[derive(Debug, Fail)]
+/// Module name of runtime host protocol errors.
+const MODULE_NAME: &'static str = "runtime/protocol";
+
+#[derive(Debug, Error)]
 pub enum ProtocolError {
-    #[fail(display = "message too large")]
+    #[error("message too large")]
     MessageTooLarge,
-    #[fail(display = "method not supported")]
+    #[error("method not supported")]
     MethodNotSupported,
-    #[fail(display = "invalid response")]
+    #[error("invalid response")]
     InvalidResponse,
-    #[fail(display = "attestation required")]
+    #[error("attestation required")]
     #[allow(unused)]
     AttestationRequired,
-    #[fail(display = "runtime id not set")]
+    #[error("runtime id not set")]
     RuntimeIDNotSet,
+    #[error("stream busy")]
+    StreamBusy,
+}
+
+impl ErrorCode for ProtocolError {
+    fn module_name(&self) -> &str {
+        MODULE_NAME
+    }
+
+    fn code(&self) -> u32 {
+        match self {
+            ProtocolError::MessageTooLarge => 1,
+            ProtocolError::MethodNotSupported => 2,
+            ProtocolError::InvalidResponse => 3,
+            ProtocolError::AttestationRequired => 4,
+            ProtocolError::RuntimeIDNotSet => 5,
+            ProtocolError::StreamBusy => 6,
+        }
+    }
+}
+
+/// Decode a length-prefixed runtime host protocol message.
+pub fn decode_message<R: Read>(mut reader: R) -> Result<Message> {
+    let length = reader.read_u32::<BigEndian>()? as usize;
+    if length > MAX_MESSAGE_SIZE {
+        return Err(ProtocolError::MessageTooLarge.into());
+    }
+
+    // TODO: Avoid allocations.
+    let mut buffer = vec![0; length];
+    reader.read_exact(&mut buffer)?;
+
+    cbor::from_slice_with_limits(
+        &buffer,
+        &cbor::DecodeLimits {
+            max_input_size: MAX_MESSAGE_SIZE,
+            max_collection_length: MAX_MESSAGE_SIZE as u64,
+            max_allocation: 2 * MAX_MESSAGE_SIZE,
+            ..Default::default()
+        },
+    )
 }
 
 /// Runtime part of the runtime host protocol.
@@ -62,12 +113,15 @@ pub struct Protocol {
     stream: Stream,
     /// Outgoing request identifier generator.
     last_request_id: AtomicUsize,
-    /// Pending outgoing requests.
-    pending_out_requests: Mutex<HashMap<u64, channel::Sender<Body>>>,
+    /// Pending outgoing requests. Responses to requests without a sender
+    /// are discarded.
+    pending_out_requests: Mutex<HashMap<u64, Option<channel::Sender<Body>>>>,
     /// Runtime identifier.
     runtime_id: Mutex<Option<RuntimeId>>,
     /// Runtime version.
     runtime_version: Version,
+    /// Whether the host accepts compressed write logs.
+    compressed_write_logs: AtomicBool,
 }
 
 impl Protocol {
@@ -90,6 +144,7 @@ impl Protocol {
             pending_out_requests: Mutex::new(HashMap::new()),
             runtime_id: Mutex::new(None),
             runtime_version: runtime_version,
+            compressed_write_logs: AtomicBool::new(false),
         }
     }
 
@@ -105,6 +160,11 @@ impl Protocol {
             .expect("runtime_id should be set")
     }
 
+    /// Whether the host accepts compressed write logs in computed batches.
+    pub fn compressed_write_logs(&self) -> bool {
+        self.compressed_write_logs.load(Ordering::SeqCst)
+    }
+
     /// Start the protocol handler loop.
     pub fn start(self: &Arc<Protocol>) {
         info!(self.logger, "Starting protocol handler");
@@ -124,34 +184,75 @@ impl Protocol {
     }
 
     /// Make a new request to the worker host and wait for the response.
-    pub fn make_request(&self, ctx: Context, body: Body) -> Fallible<Body> {
-        let id = self.last_request_id.fetch_add(1, Ordering::SeqCst) as u64;
-        let span_context = tracing::get_span_context(&ctx).unwrap_or(&vec![]).clone();
-        let message = Message {
-            id,
-            body,
-            span_context,
-            message_type: MessageType::Request,
-        };
-
+    pub fn make_request(&self, ctx: Context, body: Body) -> Result<Body> {
         // Create a response channel and register an outstanding pending request.
         let (tx, rx) = channel::bounded(1);
+        self.send_request(ctx, body, Some(tx))?;
+
+        // Wait for the response.
+        match rx.recv()? {
+            Body::Error(error) => Err(error.into()),
+            body => Ok(body),
+        }
+    }
+
+    /// Make a new request to the worker host without waiting for the
+    /// response, which is discarded.
+    pub fn send_notification(&self, ctx: Context, body: Body) -> Result<()> {
+        self.send_request(ctx, body, None)
+    }
+
+    /// Make a new request to the worker host without waiting for the
+    /// response, failing instead of blocking if the stream is in use.
+    ///
+    /// This is meant for contexts where blocking could deadlock, e.g., panic
+    /// hooks, which may run while the current thread is writing to the stream.
+    /// Once the stream has been acquired, the write itself may still block
+    /// until the host reads from the stream.
+    pub(crate) fn try_send_notification(&self, ctx: Context, body: Body) -> Result<()> {
+        let _guard = self
+            .outgoing_mutex
+            .try_lock()
+            .map_err(|_| ProtocolError::StreamBusy)?;
+        let message = self.new_request(&ctx, body);
+        self.pending_out_requests
+            .try_lock()
+            .map_err(|_| ProtocolError::StreamBusy)?
+            .insert(message.id, None);
+
+        self.write_message(message)
+    }
+
+    fn send_request(
+        &self,
+        ctx: Context,
+        body: Body,
+        response_sender: Option<channel::Sender<Body>>,
+    ) -> Result<()> {
+        let message = self.new_request(&ctx, body);
+
         {
             let mut pending_requests = self.pending_out_requests.lock().unwrap();
-            pending_requests.insert(id, tx);
+            pending_requests.insert(message.id, response_sender);
         }
 
-        // Write message to stream and wait for the response.
-        self.encode_message(message)?;
+        // Write message to stream.
+        self.encode_message(message)
+    }
 
-        match rx.recv()? {
-            Body::Error { message, .. } => Err(format_err!("{}", message)),
-            body => Ok(body),
+    fn new_request(&self, ctx: &Context, body: Body) -> Message {
+        let id = self.last_request_id.fetch_add(1, Ordering::SeqCst) as u64;
+        let span_context = tracing::get_span_context(ctx).unwrap_or(&vec![]).clone();
+        Message {
+            id,
+            body,
+            span_context,
+            message_type: MessageType::Request,
         }
     }
 
     /// Send an async response to a previous request back to the worker host.
-    pub fn send_response(&self, id: u64, body: Body) -> Fallible<()> {
+    pub fn send_response(&self, id: u64, body: Body) -> Result<()> {
         self.encode_message(Message {
             id,
             body,
@@ -160,21 +261,13 @@ impl Protocol {
         })
     }
 
-    fn decode_message<R: Read>(&self, mut reader: R) -> Fallible<Message> {
-        let length = reader.read_u32::<BigEndian>()? as usize;
-        if length > MAX_MESSAGE_SIZE {
-            return Err(ProtocolError::MessageTooLarge.into());
-        }
-
-        // TODO: Avoid allocations.
-        let mut buffer = vec![0; length];
-        reader.read_exact(&mut buffer)?;
-
-        Ok(cbor::from_slice(&buffer)?)
+    fn encode_message(&self, message: Message) -> Result<()> {
+        let _guard = self.outgoing_mutex.lock().unwrap();
+        self.write_message(message)
     }
 
-    fn encode_message(&self, message: Message) -> Fallible<()> {
-        let _guard = self.outgoing_mutex.lock().unwrap();
+    /// Write a message to the stream. The caller must hold the outgoing mutex.
+    fn write_message(&self, message: Message) -> Result<()> {
         let mut writer = BufWriter::new(&self.stream);
 
         let buffer = cbor::to_vec(&message);
@@ -188,8 +281,8 @@ impl Protocol {
         Ok(())
     }
 
-    fn handle_message<R: Read>(self: &Arc<Protocol>, reader: R) -> Fallible<()> {
-        let message = self.decode_message(reader)?;
+    fn handle_message<R: Read>(self: &Arc<Protocol>, reader: R) -> Result<()> {
+        let message = decode_message(reader)?;
 
         match message.message_type {
             MessageType::Request => {
@@ -205,11 +298,7 @@ impl Protocol {
                         // is no need to do anything more.
                         return Ok(());
                     }
-                    Err(error) => Body::Error {
-                        module: "".to_owned(), // XXX: Error codes.
-                        code: 0,               // XXX: Error codes.
-                        message: format!("{}", error),
-                    },
+                    Err(error) => Body::Error(error.into()),
                 };
 
                 // Send response back.
@@ -228,7 +317,10 @@ impl Protocol {
                 };
 
                 match response_sender {
-                    Some(response_sender) => {
+                    Some(None) => {
+                        // Nobody is waiting for the response.
+                    }
+                    Some(Some(response_sender)) => {
                         if let Err(error) = response_sender.try_send(message.body) {
                             warn!(self.logger, "Unable to deliver response to local handler"; "err" => %error);
                         }
@@ -249,9 +341,31 @@ impl Protocol {
         ctx: Context,
         id: u64,
         request: Body,
-    ) -> Fallible<Option<Body>> {
+    ) -> Result<Option<Body>> {
         match request {
-            Body::RuntimeInfoRequest { runtime_id } => {
+            Body::RuntimeInfoRequest {
+                runtime_id,
+                protocol_version,
+                memory_limits,
+                compressed_write_logs,
+            } => {
+                // Hosts predating version negotiation do not send their version.
+                if protocol_version != 0 {
+                    version::negotiate(BUILD_INFO.protocol_version, protocol_version.into())?;
+                }
+
+                // Apply the memory limits configured by the host.
+                for (subsystem, limit) in memory_limits {
+                    info!(self.logger, "Setting memory limit";
+                        "subsystem" => ?subsystem,
+                        "limit" => limit,
+                    );
+                    memory::set_limit(subsystem, Some(limit as usize));
+                }
+
+                self.compressed_write_logs
+                    .store(compressed_write_logs, Ordering::SeqCst);
+
                 // Store the passed Runtime ID.
                 *self.runtime_id.lock().unwrap() = Some(runtime_id);
 
@@ -263,6 +377,9 @@ impl Protocol {
                 }))
             }
             Body::RuntimePingRequest {} => Ok(Some(Body::Empty {})),
+            Body::RuntimeHealthRequest {} => Ok(Some(Body::RuntimeHealthResponse {
+                memory: memory::stats(),
+            })),
             Body::RuntimeShutdownRequest {} => {
                 info!(self.logger, "Received worker shutdown request");
                 Err(ProtocolError::MethodNotSupported.into())
@@ -331,7 +448,7 @@ impl Protocol {
         }
     }
 
-    fn can_handle_runtime_requests(&self) -> Fallible<()> {
+    fn can_handle_runtime_requests(&self) -> Result<()> {
         if self.runtime_id.lock().unwrap().is_none() {
             return Err(ProtocolError::RuntimeIDNotSet.into());
         }
@@ -369,7 +486,7 @@ impl ProtocolUntrustedLocalStorage {
 }
 
 impl KeyValue for ProtocolUntrustedLocalStorage {
-    fn get(&self, key: Vec<u8>) -> Fallible<Vec<u8>> {
+    fn get(&self, key: Vec<u8>) -> Result<Vec<u8>> {
         let ctx = Context::create_child(&self.ctx);
 
         match self
@@ -382,7 +499,7 @@ impl KeyValue for ProtocolUntrustedLocalStorage {
         }
     }
 
-    fn insert(&self, key: Vec<u8>, value: Vec<u8>) -> Fallible<()> {
+    fn insert(&self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
         let ctx = Context::create_child(&self.ctx);
 
         match self
@@ -395,3 +512,108 @@ impl KeyValue for ProtocolUntrustedLocalStorage {
         }
     }
 }
+
+/// Spill storage for MKVS nodes evicted from the cache, held by the worker
+/// host separately from the runtime's local storage.
+///
+/// The same care as with `ProtocolUntrustedLocalStorage` MUST be taken as
+/// the worker host can return arbitrary data.
+pub struct ProtocolSpillStorage {
+    ctx: Arc<Context>,
+    protocol: Arc<Protocol>,
+}
+
+impl ProtocolSpillStorage {
+    pub fn new(ctx: Context, protocol: Arc<Protocol>) -> Self {
+        Self {
+            ctx: ctx.freeze(),
+            protocol,
+        }
+    }
+}
+
+impl SpillStorage for ProtocolSpillStorage {
+    fn get(&self, key: Vec<u8>) -> Result<Vec<u8>> {
+        let ctx = Context::create_child(&self.ctx);
+
+        match self
+            .protocol
+            .make_request(ctx, Body::HostSpillStorageGetRequest { key })
+        {
+            Ok(Body::HostSpillStorageGetResponse { value }) => Ok(value),
+            Ok(_) => Err(ProtocolError::InvalidResponse.into()),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn insert_batch(&self, entries: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+        let ctx = Context::create_child(&self.ctx);
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| SpillStorageEntry { key, value })
+            .collect();
+
+        match self
+            .protocol
+            .make_request(ctx, Body::HostSpillStorageInsertRequest { entries })
+        {
+            Ok(Body::HostSpillStorageInsertResponse {}) => Ok(()),
+            Ok(_) => Err(ProtocolError::InvalidResponse.into()),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn clear(&self) -> Result<()> {
+        let ctx = Context::create_child(&self.ctx);
+
+        match self
+            .protocol
+            .make_request(ctx, Body::HostSpillStorageClearRequest {})
+        {
+            Ok(Body::HostSpillStorageClearResponse {}) => Ok(()),
+            Ok(_) => Err(ProtocolError::InvalidResponse.into()),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{RpcDemux, RpcDispatcher, TxnDispatcher};
+
+    #[test]
+    fn test_try_send_notification() {
+        let (stream, host) = Stream::pair().unwrap();
+        let rak = Arc::new(RAK::new());
+        let initializer = |_: &Arc<Protocol>,
+                           _: &Arc<RAK>,
+                           _: &mut RpcDemux,
+                           _: &mut RpcDispatcher|
+         -> Option<Box<dyn TxnDispatcher>> { None };
+        let dispatcher = Dispatcher::new(Box::new(initializer), rak.clone());
+        let protocol = Protocol::new(stream, rak, dispatcher, Version::default());
+
+        // Notifications fail instead of blocking while the stream is in use.
+        {
+            let _guard = protocol.outgoing_mutex.lock().unwrap();
+            assert!(protocol
+                .try_send_notification(Context::background(), Body::RuntimePingRequest {})
+                .is_err());
+        }
+
+        protocol
+            .try_send_notification(Context::background(), Body::RuntimePingRequest {})
+            .unwrap();
+        let message = decode_message(&host).unwrap();
+        match (message.message_type, message.body) {
+            (MessageType::Request, Body::RuntimePingRequest {}) => {}
+            (_, body) => panic!("unexpected message: {:?}", body),
+        }
+        assert!(protocol
+            .pending_out_requests
+            .lock()
+            .unwrap()
+            .contains_key(&message.id));
+    }
+}
@@ -1,15 +1,18 @@
 //! Runtime attestation key handling.
 use std::sync::{Arc, RwLock};
 
-use failure::Fallible;
-use sgx_isa::Targetinfo;
+use anyhow::Result;
+use thiserror::Error;
 
 #[cfg_attr(not(target_env = "sgx"), allow(unused))]
 use crate::common::crypto::hash::Hash;
-use crate::common::{
-    crypto::signature::{PrivateKey, PublicKey, Signature, Signer},
-    sgx::avr,
-    time::insecure_posix_time,
+use crate::{
+    common::{
+        crypto::signature::{PrivateKey, PublicKey, Signature, Signer},
+        sgx::avr,
+        time::insecure_posix_time,
+    },
+    types::ErrorCode,
 };
 
 #[cfg(target_env = "sgx")]
@@ -17,44 +20,78 @@ use base64;
 #[cfg(target_env = "sgx")]
 use rand::{rngs::OsRng, Rng};
 #[cfg(target_env = "sgx")]
-use sgx_isa::Report;
+use sgx_isa::{Report, Targetinfo};
 
 /// Context used for computing the RAK digest.
 #[cfg_attr(not(target_env = "sgx"), allow(unused))]
 const RAK_HASH_CONTEXT: &'static [u8] = b"oasis-core/node: TEE RAK binding";
 
+/// Module name of RAK-related errors.
+const MODULE_NAME: &'static str = "runtime/rak";
+
 /// RAK-related error.
-#[derive(Debug, Fail)]
-enum RAKError {
-    #[fail(display = "RAK is not configured")]
+#[derive(Debug, Error)]
+pub(crate) enum RAKError {
+    #[error("RAK is not configured")]
     NotConfigured,
-    #[fail(display = "RAK binding mismatch")]
+    #[error("RAK binding mismatch")]
     BindingMismatch,
-    #[fail(display = "malformed report data")]
+    #[error("malformed report data")]
     MalformedReportData,
 }
 
+impl ErrorCode for RAKError {
+    fn module_name(&self) -> &str {
+        MODULE_NAME
+    }
+
+    fn code(&self) -> u32 {
+        match self {
+            RAKError::NotConfigured => 1,
+            RAKError::BindingMismatch => 2,
+            RAKError::MalformedReportData => 3,
+        }
+    }
+}
+
 /// AVR-related errors.
 #[cfg(target_env = "sgx")]
-#[derive(Debug, Fail)]
-enum AVRError {
-    #[fail(display = "malformed target_info")]
+#[derive(Debug, Error)]
+pub(crate) enum AVRError {
+    #[error("malformed target_info")]
     MalformedTargetInfo,
-    #[fail(display = "MRENCLAVE mismatch")]
+    #[error("MRENCLAVE mismatch")]
     MrEnclaveMismatch,
-    #[fail(display = "MRSIGNER mismatch")]
+    #[error("MRSIGNER mismatch")]
     MrSignerMismatch,
-    #[fail(display = "AVR nonce mismatch")]
+    #[error("AVR nonce mismatch")]
     NonceMismatch,
 }
 
+#[cfg(target_env = "sgx")]
+impl ErrorCode for AVRError {
+    fn module_name(&self) -> &str {
+        MODULE_NAME
+    }
+
+    fn code(&self) -> u32 {
+        // Codes are shared with RAKError, which uses the same module name.
+        match self {
+            AVRError::MalformedTargetInfo => 4,
+            AVRError::MrEnclaveMismatch => 5,
+            AVRError::MrSignerMismatch => 6,
+            AVRError::NonceMismatch => 7,
+        }
+    }
+}
+
 struct Inner {
     private_key: Option<PrivateKey>,
     avr: Option<Arc<avr::AVR>>,
     avr_timestamp: Option<i64>,
     #[allow(unused)]
     enclave_identity: Option<avr::EnclaveIdentity>,
-    #[allow(unused)]
+    #[cfg(target_env = "sgx")]
     target_info: Option<Targetinfo>,
     #[allow(unused)]
     nonce: Option<String>,
@@ -80,6 +117,7 @@ impl RAK {
                 avr: None,
                 avr_timestamp: None,
                 enclave_identity: avr::EnclaveIdentity::current(),
+                #[cfg(target_env = "sgx")]
                 target_info: None,
                 nonce: None,
             }),
@@ -122,7 +160,7 @@ impl RAK {
 
     /// Initialize the RAK.
     #[cfg(target_env = "sgx")]
-    pub(crate) fn init_rak(&self, target_info: Vec<u8>) -> Fallible<()> {
+    pub(crate) fn init_rak(&self, target_info: Vec<u8>) -> Result<()> {
         let mut inner = self.inner.write().unwrap();
 
         // Set the Quoting Enclave target_info first, as unlike key generation
@@ -172,7 +210,7 @@ impl RAK {
 
     /// Configure the attestation verification report for RAK.
     #[cfg(target_env = "sgx")]
-    pub(crate) fn set_avr(&self, avr: avr::AVR) -> Fallible<()> {
+    pub(crate) fn set_avr(&self, avr: avr::AVR) -> Result<()> {
         let rak_pub = self.public_key().expect("RAK must be configured");
 
         let mut inner = self.inner.write().unwrap();
@@ -270,7 +308,7 @@ impl RAK {
     }
 
     /// Verify a provided RAK binding.
-    pub fn verify_binding(avr: &avr::AuthenticatedAVR, rak: &PublicKey) -> Fallible<()> {
+    pub fn verify_binding(avr: &avr::AuthenticatedAVR, rak: &PublicKey) -> Result<()> {
         if avr.report_data.len() < 32 {
             return Err(RAKError::MalformedReportData.into());
         }
@@ -284,7 +322,7 @@ impl RAK {
 
 impl Signer for RAK {
     /// Generate a RAK signature with the private key over the context and message.
-    fn sign(&self, context: &[u8], message: &[u8]) -> Fallible<Signature> {
+    fn sign(&self, context: &[u8], message: &[u8]) -> Result<Signature> {
         let inner = self.inner.read().unwrap();
         match inner.private_key {
             Some(ref key) => Ok(key.sign(context, message)?),
@@ -1,7 +1,8 @@
 //! Session demultiplexer.
 use std::{collections::HashMap, io::Write, sync::Arc, time::SystemTime};
 
-use failure::Fallible;
+use anyhow::Result;
+use thiserror::Error;
 
 use super::{
     session::{Builder, Session, SessionInfo},
@@ -10,6 +11,7 @@ use super::{
 use crate::{
     common::{cbor, time::insecure_posix_system_time},
     rak::RAK,
+    types::ErrorCode,
 };
 
 /// Maximum concurrent EnclaveRPC sessions.
@@ -20,17 +22,38 @@ const DEFAULT_STALE_SESSION_TIMEOUT_SECS: u64 = 60;
 /// Stale session check will be performed on any new incoming connection with at minimum
 /// STALE_SESSIONS_CHECK_TIMEOUT_SECS seconds between checks.
 const STALE_SESSIONS_CHECK_TIMEOUT_SECS: u64 = 10;
+/// Module name of demux errors.
+const MODULE_NAME: &'static str = "runtime/rpc/demux";
 
 /// Demux error.
-#[derive(Debug, Fail)]
-enum DemuxError {
-    #[fail(display = "session not found for id {}", session)]
+#[derive(Debug, Error)]
+pub(crate) enum DemuxError {
+    #[error("session not found for id {session}")]
     SessionNotFound { session: SessionID },
-    #[fail(display = "max concurrent sessions reached")]
+    #[error("max concurrent sessions reached")]
     MaxConcurrentSessions,
 }
 
-pub type SessionMessage = (SessionID, Option<Arc<SessionInfo>>, Message, String);
+impl ErrorCode for DemuxError {
+    fn module_name(&self) -> &str {
+        MODULE_NAME
+    }
+
+    fn code(&self) -> u32 {
+        match self {
+            DemuxError::SessionNotFound { .. } => 1,
+            DemuxError::MaxConcurrentSessions => 2,
+        }
+    }
+}
+
+pub type SessionMessage = (
+    SessionID,
+    Option<Arc<SessionInfo>>,
+    Message,
+    String,
+    Vec<u8>,
+);
 
 /// Session demultiplexer.
 pub struct Demux {
@@ -90,10 +113,11 @@ impl Demux {
         &mut self,
         data: Vec<u8>,
         writer: W,
-    ) -> Fallible<Option<SessionMessage>> {
-        let frame: Frame = cbor::from_slice(&data)?;
+    ) -> Result<Option<SessionMessage>> {
+        let frame: Frame = cbor::from_slice_canonical(&data)?;
         let id = frame.session.clone();
         let untrusted_plaintext = frame.untrusted_plaintext.clone();
+        let span_context = frame.span_context.clone();
 
         if let Some(enriched_session) = self.sessions.get_mut(&id) {
             match enriched_session
@@ -106,6 +130,7 @@ impl Demux {
                             enriched_session.session.session_info(),
                             msg,
                             untrusted_plaintext.clone(),
+                            span_context.clone(),
                         )
                     })
                 }) {
@@ -138,7 +163,15 @@ impl Demux {
             if self.sessions.len() < self.max_concurrent_sessions {
                 let mut session = Builder::new().local_rak(self.rak.clone()).build_responder();
                 let result = match session.process_data(frame.payload, writer).map(|m| {
-                    m.map(|msg| (id, session.session_info(), msg, untrusted_plaintext.clone()))
+                    m.map(|msg| {
+                        (
+                            id,
+                            session.session_info(),
+                            msg,
+                            untrusted_plaintext.clone(),
+                            span_context.clone(),
+                        )
+                    })
                 }) {
                     Ok(result) => result,
                     // In case there is an error, drop the session.
@@ -165,7 +198,7 @@ impl Demux {
         id: SessionID,
         msg: Message,
         mut writer: W,
-    ) -> Fallible<()> {
+    ) -> Result<()> {
         match self.sessions.get_mut(&id) {
             Some(enriched_session) => {
                 // Responses don't need framing as they are linked at the
@@ -178,7 +211,7 @@ impl Demux {
     }
 
     /// Close the session and generate a response.
-    pub fn close<W: Write>(&mut self, id: SessionID, mut writer: W) -> Fallible<()> {
+    pub fn close<W: Write>(&mut self, id: SessionID, mut writer: W) -> Result<()> {
         match self.sessions.remove(&id) {
             Some(mut enriched_session) => {
                 // Responses don't need framing as they are linked at the
@@ -1,22 +1,38 @@
 //! RPC dispatcher.
 use std::collections::HashMap;
 
-use failure::Fallible;
+use anyhow::Result;
 use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
 
 use super::{
     context::Context,
     types::{Body, Request, Response},
 };
-use crate::common::cbor;
+use crate::{common::cbor, types::ErrorCode};
+
+/// Name of the module for dispatch errors.
+const MODULE_NAME: &'static str = "runtime/rpc/dispatcher";
 
 /// Dispatch error.
-#[derive(Debug, Fail)]
-enum DispatchError {
-    #[fail(display = "method not found: {}", method)]
+#[derive(Debug, Error)]
+pub(crate) enum DispatchError {
+    #[error("method not found: {method}")]
     MethodNotFound { method: String },
 }
 
+impl ErrorCode for DispatchError {
+    fn module_name(&self) -> &str {
+        MODULE_NAME
+    }
+
+    fn code(&self) -> u32 {
+        match self {
+            DispatchError::MethodNotFound { .. } => 1,
+        }
+    }
+}
+
 /// Custom context initializer.
 pub trait ContextInitializer {
     /// Called to initialize the context.
@@ -42,16 +58,16 @@ pub struct MethodDescriptor {
 /// Handler for a RPC method.
 pub trait MethodHandler<Rq, Rsp> {
     /// Invoke the method implementation and return a response.
-    fn handle(&self, request: &Rq, ctx: &mut Context) -> Fallible<Rsp>;
+    fn handle(&self, request: &Rq, ctx: &mut Context) -> Result<Rsp>;
 }
 
 impl<Rq, Rsp, F> MethodHandler<Rq, Rsp> for F
 where
     Rq: 'static,
     Rsp: 'static,
-    F: Fn(&Rq, &mut Context) -> Fallible<Rsp> + 'static,
+    F: Fn(&Rq, &mut Context) -> Result<Rsp> + 'static,
 {
-    fn handle(&self, request: &Rq, ctx: &mut Context) -> Fallible<Rsp> {
+    fn handle(&self, request: &Rq, ctx: &mut Context) -> Result<Rsp> {
         (*self)(&request, ctx)
     }
 }
@@ -62,7 +78,7 @@ pub trait MethodHandlerDispatch {
     fn get_descriptor(&self) -> &MethodDescriptor;
 
     /// Dispatch request.
-    fn dispatch(&self, request: Request, ctx: &mut Context) -> Fallible<Response>;
+    fn dispatch(&self, request: Request, ctx: &mut Context) -> Result<Response>;
 }
 
 struct MethodHandlerDispatchImpl<Rq, Rsp> {
@@ -81,7 +97,7 @@ where
         &self.descriptor
     }
 
-    fn dispatch(&self, request: Request, ctx: &mut Context) -> Fallible<Response> {
+    fn dispatch(&self, request: Request, ctx: &mut Context) -> Result<Response> {
         let request = cbor::from_value(request.args)?;
         let response = self.handler.handle(&request, ctx)?;
 
@@ -119,7 +135,7 @@ impl Method {
     }
 
     /// Dispatch a request.
-    pub fn dispatch(&self, request: Request, ctx: &mut Context) -> Fallible<Response> {
+    pub fn dispatch(&self, request: Request, ctx: &mut Context) -> Result<Response> {
         self.dispatcher.dispatch(request, ctx)
     }
 }
@@ -169,7 +185,7 @@ impl Dispatcher {
         match self.dispatch_fallible(request, &mut ctx, false) {
             Ok(response) => response,
             Err(error) => Response {
-                body: Body::Error(format!("{}", error)),
+                body: Body::Error(error.into()),
             },
         }
     }
@@ -179,7 +195,7 @@ impl Dispatcher {
         request: Request,
         ctx: &mut Context,
         is_local: bool,
-    ) -> Fallible<Response> {
+    ) -> Result<Response> {
         let vtbl = match is_local {
             false => &self.methods,
             true => &self.local_methods,
@@ -207,7 +223,7 @@ impl Dispatcher {
         match self.dispatch_fallible(request, &mut ctx, true) {
             Ok(response) => response,
             Err(error) => Response {
-                body: Body::Error(format!("{}", error)),
+                body: Body::Error(error.into()),
             },
         }
     }
@@ -25,7 +25,7 @@ macro_rules! register_runtime_rpc_methods {
                     },
                     |args: &$arguments_type,
                      ctx: &mut $crate::rpc::context::Context|
-                        -> ::failure::Fallible<$output_type> {
+                        -> $crate::__private::anyhow::Result<$output_type> {
                         $method_name(args, ctx)
                     },
                 ),
@@ -7,5 +7,8 @@ pub mod macros;
 pub mod session;
 pub mod types;
 
+#[cfg(test)]
+mod session_simulation;
+
 // Re-exports.
 pub use self::context::Context;
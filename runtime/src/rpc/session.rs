@@ -1,36 +1,61 @@
 //! Secure channel session.
 use std::{collections::HashSet, io::Write, mem, sync::Arc};
 
-use failure::Fallible;
+use anyhow::Result;
 use serde_derive::{Deserialize, Serialize};
 use snow;
+use thiserror::Error;
+use zeroize::Zeroize;
 
 use super::types::Message;
 use crate::{
     common::{
         cbor,
-        crypto::signature::{PublicKey, Signature, Signer},
+        crypto::{
+            context::RAK_SESSION_BINDING_CONTEXT,
+            signature::{PublicKey, Signature, Signer},
+        },
         sgx::avr,
+        version::{self, Version, ENCLAVE_RPC_PROTOCOL_VERSION},
     },
     rak::RAK,
+    types::ErrorCode,
 };
 
 /// Noise protocol pattern.
 const NOISE_PATTERN: &'static str = "Noise_XX_25519_ChaChaPoly_SHA256";
-/// RAK signature session binding context.
-const RAK_SESSION_BINDING_CONTEXT: [u8; 8] = *b"EkRakRpc";
+/// Module name of session-related errors.
+const MODULE_NAME: &'static str = "runtime/rpc/session";
 
 /// Session-related error.
-#[derive(Debug, Fail)]
-enum SessionError {
-    #[fail(display = "invalid input")]
+#[derive(Debug, Error)]
+pub(crate) enum SessionError {
+    #[error("invalid input")]
     InvalidInput,
-    #[fail(display = "invalid state")]
+    #[error("invalid state")]
     InvalidState,
-    #[fail(display = "session closed")]
+    #[error("session closed")]
     Closed,
-    #[fail(display = "mismatched enclave identity")]
+    #[error("mismatched enclave identity")]
     MismatchedEnclaveIdentity,
+    #[error("missing or malformed protocol version")]
+    InvalidVersion,
+}
+
+impl ErrorCode for SessionError {
+    fn module_name(&self) -> &str {
+        MODULE_NAME
+    }
+
+    fn code(&self) -> u32 {
+        match self {
+            SessionError::InvalidInput => 1,
+            SessionError::InvalidState => 2,
+            SessionError::Closed => 3,
+            SessionError::MismatchedEnclaveIdentity => 4,
+            SessionError::InvalidVersion => 5,
+        }
+    }
 }
 
 /// Information about a session.
@@ -52,6 +77,7 @@ pub struct Session {
     rak: Option<Arc<RAK>>,
     remote_enclaves: Option<HashSet<avr::EnclaveIdentity>>,
     info: Option<Arc<SessionInfo>>,
+    version: Option<Version>,
     state: State,
     buf: Vec<u8>,
 }
@@ -68,6 +94,7 @@ impl Session {
             rak,
             remote_enclaves,
             info: None,
+            version: None,
             state: State::Handshake1(handshake_state),
             buf: vec![0u8; 65535],
         }
@@ -82,7 +109,7 @@ impl Session {
         &mut self,
         data: Vec<u8>,
         mut writer: W,
-    ) -> Fallible<Option<Message>> {
+    ) -> Result<Option<Message>> {
         // Replace the state with a closed state. In case processing fails for whatever
         // reason, this will cause the session to be torn down.
         match mem::replace(&mut self.state, State::Closed) {
@@ -94,11 +121,16 @@ impl Session {
                     }
 
                     // -> e
-                    let len = state.write_message(&[], &mut self.buf)?;
+                    let len = state.write_message(
+                        &cbor::to_vec(&ENCLAVE_RPC_PROTOCOL_VERSION),
+                        &mut self.buf,
+                    )?;
                     writer.write_all(&self.buf[..len])?;
+                    self.version = Some(ENCLAVE_RPC_PROTOCOL_VERSION);
                 } else {
                     // <- e
-                    state.read_message(&data, &mut self.buf)?;
+                    let len = state.read_message(&data, &mut self.buf)?;
+                    self.version = Some(Self::negotiate_version(&self.buf[..len])?);
 
                     // -> e, ee, s, es
                     let len = state.write_message(&self.get_rak_binding(), &mut self.buf)?;
@@ -134,7 +166,7 @@ impl Session {
             State::Transport(mut state) => {
                 // TODO: Restore session in case of errors.
                 let len = state.read_message(&data, &mut self.buf)?;
-                let msg = cbor::from_slice(&self.buf[..len])?;
+                let msg = cbor::from_slice_with_limits(&self.buf[..len], &Default::default())?;
 
                 self.state = State::Transport(state);
                 return Ok(Some(msg));
@@ -151,7 +183,7 @@ impl Session {
     ///
     /// The `writer` will be used for protocol message output which should
     /// be transmitted to the remote session counterpart.
-    pub fn write_message<W: Write>(&mut self, msg: Message, mut writer: W) -> Fallible<()> {
+    pub fn write_message<W: Write>(&mut self, msg: Message, mut writer: W) -> Result<()> {
         if let State::Transport(ref mut state) = self.state {
             let msg = cbor::to_vec(&msg);
             let len = state.write_message(&msg, &mut self.buf)?;
@@ -171,6 +203,17 @@ impl Session {
         self.state = State::Closed;
     }
 
+    fn negotiate_version(payload: &[u8]) -> Result<Version> {
+        // Initiators must offer the version they use.
+        let remote_version: Version =
+            cbor::from_slice(payload).map_err(|_| SessionError::InvalidVersion)?;
+
+        Ok(version::negotiate(
+            ENCLAVE_RPC_PROTOCOL_VERSION,
+            remote_version,
+        )?)
+    }
+
     fn get_rak_binding(&self) -> Vec<u8> {
         match self.rak {
             Some(ref rak) => {
@@ -198,7 +241,7 @@ impl Session {
         &self,
         rak_binding: &[u8],
         remote_static: &[u8],
-    ) -> Fallible<Option<Arc<SessionInfo>>> {
+    ) -> Result<Option<Arc<SessionInfo>>> {
         if rak_binding.is_empty() {
             // If enclave identity verification is required and no RAK binding
             // has been provided, we must abort the session.
@@ -239,6 +282,16 @@ impl Session {
         self.info.clone()
     }
 
+    /// Negotiated enclave RPC protocol version, if known.
+    ///
+    /// Initiators always use their own version, while responders only know
+    /// the version once the initiator's first handshake message has been
+    /// processed. Responders reject initiators which do not offer a
+    /// compatible version.
+    pub fn version(&self) -> Option<Version> {
+        self.version
+    }
+
     /// Return true if session handshake has completed and the session
     /// is in transport mode.
     pub fn is_connected(&self) -> bool {
@@ -318,21 +371,149 @@ impl Builder {
 
     /// Build initiator session.
     pub fn build_initiator(self) -> Session {
-        let (builder, keypair, rak, enclaves) = self.build();
+        let (builder, mut keypair, rak, enclaves) = self.build();
         let session = builder
             .local_private_key(&keypair.private)
             .build_initiator()
             .unwrap();
+        keypair.private.zeroize();
         Session::new(session, keypair.public, rak, enclaves)
     }
 
     /// Build responder session.
     pub fn build_responder(self) -> Session {
-        let (builder, keypair, rak, enclaves) = self.build();
+        let (builder, mut keypair, rak, enclaves) = self.build();
         let session = builder
             .local_private_key(&keypair.private)
             .build_responder()
             .unwrap();
+        keypair.private.zeroize();
         Session::new(session, keypair.public, rak, enclaves)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use self::test::{black_box, Bencher};
+    use super::*;
+    use crate::{
+        common::cbor::Value,
+        rpc::types::{Body, Request, Response},
+    };
+
+    /// Perform a handshake between a new initiator and responder.
+    fn handshake() -> (Session, Session) {
+        let mut initiator = Builder::new().build_initiator();
+        let mut responder = Builder::new().build_responder();
+
+        let mut request = vec![];
+        initiator.process_data(vec![], &mut request).unwrap();
+        let mut response = vec![];
+        responder.process_data(request, &mut response).unwrap();
+        let mut request = vec![];
+        initiator.process_data(response, &mut request).unwrap();
+        responder.process_data(request, vec![]).unwrap();
+
+        (initiator, responder)
+    }
+
+    /// Send a request from the initiator and a response back.
+    fn round_trip(initiator: &mut Session, responder: &mut Session, payload: &[u8]) -> Message {
+        let request = Message::Request(Request {
+            method: "test".to_owned(),
+            args: Value::Bytes(payload.to_vec()),
+        });
+        let mut data = vec![];
+        initiator.write_message(request, &mut data).unwrap();
+        let request = match responder.process_data(data, vec![]).unwrap() {
+            Some(Message::Request(request)) => request,
+            msg => panic!("unexpected message: {:?}", msg),
+        };
+
+        let response = Message::Response(Response {
+            body: Body::Success(request.args),
+        });
+        let mut data = vec![];
+        responder.write_message(response, &mut data).unwrap();
+        initiator.process_data(data, vec![]).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let (mut initiator, mut responder) = handshake();
+        assert!(initiator.is_connected());
+        assert!(responder.is_connected());
+        assert_eq!(initiator.version(), Some(ENCLAVE_RPC_PROTOCOL_VERSION));
+        assert_eq!(responder.version(), Some(ENCLAVE_RPC_PROTOCOL_VERSION));
+
+        match round_trip(&mut initiator, &mut responder, b"hello") {
+            Message::Response(Response {
+                body: Body::Success(Value::Bytes(payload)),
+            }) => assert_eq!(payload, b"hello"),
+            msg => panic!("unexpected message: {:?}", msg),
+        }
+    }
+
+    /// Create the first handshake message of a new initiator, carrying the
+    /// given payload instead of the initiator's version.
+    fn first_message(payload: &[u8]) -> Vec<u8> {
+        let mut initiator = Builder::new().build_initiator();
+        let mut state = match mem::replace(&mut initiator.state, State::Closed) {
+            State::Handshake1(state) => state,
+            _ => panic!("initiator should be in the first handshake state"),
+        };
+        let mut data = vec![0u8; 65535];
+        let len = state.write_message(payload, &mut data).unwrap();
+        data.truncate(len);
+        data
+    }
+
+    #[test]
+    fn test_session_version() {
+        for payload in vec![vec![], b"garbage".to_vec()] {
+            let mut responder = Builder::new().build_responder();
+            let error = responder
+                .process_data(first_message(&payload), vec![])
+                .unwrap_err();
+            match error.downcast_ref::<SessionError>() {
+                Some(SessionError::InvalidVersion) => {}
+                _ => panic!("unexpected error: {}", error),
+            }
+            assert!(!responder.is_connected());
+        }
+
+        let mut responder = Builder::new().build_responder();
+        let error = responder
+            .process_data(first_message(&cbor::to_vec(&Version::new(1, 0, 0))), vec![])
+            .unwrap_err();
+        match error.downcast_ref::<version::VersionError>() {
+            Some(version::VersionError::Incompatible { remote, .. }) => {
+                assert_eq!(*remote, Version::new(1, 0, 0))
+            }
+            _ => panic!("unexpected error: {}", error),
+        }
+
+        // Compatible versions are negotiated down to the lower one.
+        let older = Version::new(0, 1, 0);
+        let mut responder = Builder::new().build_responder();
+        responder
+            .process_data(first_message(&cbor::to_vec(&older)), vec![])
+            .unwrap();
+        assert_eq!(responder.version(), Some(older));
+    }
+
+    #[bench]
+    fn bench_handshake(b: &mut Bencher) {
+        b.iter(|| black_box(handshake()));
+    }
+
+    #[bench]
+    fn bench_frame_round_trip_4096(b: &mut Bencher) {
+        let (mut initiator, mut responder) = handshake();
+        let payload = [0u8; 4096];
+
+        b.iter(|| black_box(round_trip(&mut initiator, &mut responder, &payload)));
+    }
+}
@@ -0,0 +1,407 @@
+//! Deterministic simulation of the session state machine.
+//!
+//! An initiator and responder sessions exchange messages over a simulated
+//! network which drops, duplicates, reorders, truncates and corrupts them,
+//! while the initiator may send requests or close the session at any point.
+//! The initiator is reset on errors as done by the RPC client and responder
+//! sessions are demultiplexed by the initiator's generation (playing the
+//! role of the session identifier) as done by `Demux`.
+//!
+//! Every schedule is derived from a seed, so a failing run can be reproduced
+//! from the seed and trace reported in the panic message.
+use std::{
+    any::Any,
+    collections::HashMap,
+    panic::{self, AssertUnwindSafe},
+};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use super::{
+    session::{Builder, Session},
+    types::{Body, Message, Request, Response},
+};
+use crate::common::cbor::Value;
+
+/// Number of simulated runs.
+const RUNS: u64 = 200;
+/// Number of steps of each run.
+const STEPS: usize = 64;
+/// Probability of a message being faulty in faulty runs.
+const FAULT_RATE: f64 = 0.2;
+/// Maximum number of deliveries when settling the network.
+const MAX_SETTLE_DELIVERIES: usize = 256;
+
+/// A fault injected by the network when delivering a message.
+#[derive(Clone, Copy, Debug)]
+enum Fault {
+    Drop,
+    Duplicate,
+    Reorder,
+    Truncate,
+    Corrupt,
+}
+
+/// Direction of a message.
+#[derive(Clone, Copy, Debug)]
+enum Direction {
+    ToResponder,
+    ToInitiator,
+}
+
+/// A simulation step.
+#[derive(Clone, Copy, Debug)]
+enum Action {
+    /// Start a handshake from the initiator.
+    Initiate,
+    /// Deliver the next message in the given direction.
+    Deliver(Direction, Option<Fault>),
+    /// Send a request from the initiator.
+    Request,
+    /// Close the session from the initiator.
+    Close,
+}
+
+/// A message in flight, tagged with the initiator generation.
+type Packet = (u64, Vec<u8>);
+
+struct Simulation {
+    rng: ChaCha20Rng,
+    /// Probability of a delivered message being faulty.
+    fault_rate: f64,
+    /// Generation of the initiator, incremented on each reset.
+    generation: u64,
+    initiator: Session,
+    /// Whether the current initiator has started a handshake.
+    initiated: bool,
+    /// Responder sessions by initiator generation.
+    responders: HashMap<u64, Session>,
+    to_responder: Vec<Packet>,
+    to_initiator: Vec<Packet>,
+    /// Number of completed request round trips.
+    round_trips: usize,
+    /// Number of sessions which failed.
+    failures: usize,
+    /// Steps taken so far.
+    trace: Vec<Action>,
+}
+
+impl Simulation {
+    fn new(seed: u64) -> Self {
+        Self {
+            rng: ChaCha20Rng::seed_from_u64(seed),
+            // Every fourth run uses a reliable network.
+            fault_rate: if seed % 4 == 0 { 0.0 } else { FAULT_RATE },
+            generation: 0,
+            initiator: Builder::new().build_initiator(),
+            initiated: false,
+            responders: HashMap::new(),
+            to_responder: vec![],
+            to_initiator: vec![],
+            round_trips: 0,
+            failures: 0,
+            trace: vec![],
+        }
+    }
+
+    /// Take random steps, then let the network settle and check that the
+    /// session can always be used or re-established.
+    fn run(&mut self) {
+        for _ in 0..STEPS {
+            let action = self.next_action();
+            self.step(action);
+        }
+
+        let reliable = self.fault_rate == 0.0;
+        self.settle();
+
+        if reliable {
+            // Without faults, sessions must always complete.
+            assert!(self.call(), "call over a reliable network must succeed");
+        } else if !self.call() {
+            // Otherwise a reset session must be able to recover.
+            self.reset();
+            assert!(self.call(), "call on a reset session must succeed");
+        }
+    }
+
+    fn next_action(&mut self) -> Action {
+        match self.rng.gen_range(0, 10) {
+            0 => Action::Initiate,
+            1..=3 => Action::Deliver(Direction::ToResponder, self.next_fault()),
+            4..=6 => Action::Deliver(Direction::ToInitiator, self.next_fault()),
+            7 | 8 => Action::Request,
+            _ => Action::Close,
+        }
+    }
+
+    fn next_fault(&mut self) -> Option<Fault> {
+        if !self.rng.gen_bool(self.fault_rate) {
+            return None;
+        }
+
+        Some(match self.rng.gen_range(0, 5) {
+            0 => Fault::Drop,
+            1 => Fault::Duplicate,
+            2 => Fault::Reorder,
+            3 => Fault::Truncate,
+            _ => Fault::Corrupt,
+        })
+    }
+
+    fn step(&mut self, action: Action) {
+        self.trace.push(action);
+
+        match action {
+            Action::Initiate => self.initiate(),
+            Action::Deliver(direction, fault) => self.deliver(direction, fault),
+            Action::Request => self.request(),
+            Action::Close => self.close(),
+        }
+    }
+
+    fn initiate(&mut self) {
+        let mut buffer = vec![];
+        if !self.initiated {
+            // This is what the RPC client relies on when connecting.
+            self.initiator
+                .process_data(vec![], &mut buffer)
+                .expect("initiation must always succeed");
+            self.initiated = true;
+            self.to_responder.push((self.generation, buffer));
+            return;
+        }
+
+        // Initiating again must fail (or be a no-op) without a panic.
+        match self.initiator.process_data(vec![], &mut buffer) {
+            Ok(_) => assert!(buffer.is_empty(), "repeated initiation must not send"),
+            Err(_) => self.initiator_failed(),
+        }
+    }
+
+    fn deliver(&mut self, direction: Direction, fault: Option<Fault>) {
+        let queue = match direction {
+            Direction::ToResponder => &mut self.to_responder,
+            Direction::ToInitiator => &mut self.to_initiator,
+        };
+        if queue.is_empty() {
+            return;
+        }
+
+        let index = match fault {
+            Some(Fault::Reorder) => self.rng.gen_range(0, queue.len()),
+            _ => 0,
+        };
+        let (generation, mut data) = queue.remove(index);
+        match fault {
+            Some(Fault::Drop) => return,
+            Some(Fault::Duplicate) => queue.insert(0, (generation, data.clone())),
+            Some(Fault::Truncate) if !data.is_empty() => {
+                data.truncate(self.rng.gen_range(0, data.len()));
+            }
+            Some(Fault::Corrupt) if !data.is_empty() => {
+                let index = self.rng.gen_range(0, data.len());
+                data[index] ^= 1u8 << self.rng.gen_range(0u32, 8);
+            }
+            _ => {}
+        }
+
+        match direction {
+            Direction::ToResponder => self.deliver_to_responder(generation, data),
+            Direction::ToInitiator => self.deliver_to_initiator(generation, data),
+        }
+    }
+
+    fn deliver_to_responder(&mut self, generation: u64, data: Vec<u8>) {
+        let responder = self
+            .responders
+            .entry(generation)
+            .or_insert_with(|| Builder::new().build_responder());
+
+        let mut buffer = vec![];
+        let keep = match responder.process_data(data, &mut buffer) {
+            Ok(None) => true,
+            Ok(Some(Message::Request(request))) => {
+                let response = Message::Response(Response {
+                    body: Body::Success(request.args),
+                });
+                responder
+                    .write_message(response, &mut buffer)
+                    .expect("connected session must accept messages");
+                true
+            }
+            Ok(Some(Message::Close)) => {
+                responder
+                    .write_message(Message::Close, &mut buffer)
+                    .expect("connected session must accept messages");
+                false
+            }
+            Ok(Some(_)) => false,
+            Err(_) => {
+                assert_failed_cleanly(responder);
+                self.failures += 1;
+                false
+            }
+        };
+
+        if !keep {
+            self.responders.remove(&generation);
+        }
+        if !buffer.is_empty() {
+            self.to_initiator.push((generation, buffer));
+        }
+    }
+
+    fn deliver_to_initiator(&mut self, generation: u64, data: Vec<u8>) {
+        if generation != self.generation {
+            // Responses to a previous session are never seen by the client.
+            return;
+        }
+
+        let mut buffer = vec![];
+        match self.initiator.process_data(data, &mut buffer) {
+            Ok(None) => {
+                if !buffer.is_empty() {
+                    self.to_responder.push((generation, buffer));
+                }
+            }
+            Ok(Some(Message::Response(Response {
+                body: Body::Success(_),
+            }))) => self.round_trips += 1,
+            Ok(Some(Message::Close)) => {
+                self.initiator.close();
+                self.reset();
+            }
+            Ok(Some(msg)) => panic!("unexpected message: {:?}", msg),
+            Err(_) => self.initiator_failed(),
+        }
+    }
+
+    fn request(&mut self) {
+        let request = Message::Request(Request {
+            method: "echo".to_owned(),
+            args: Value::Bytes(self.trace.len().to_le_bytes().to_vec()),
+        });
+
+        let mut buffer = vec![];
+        if self.initiator.is_connected() {
+            self.initiator
+                .write_message(request, &mut buffer)
+                .expect("connected session must accept messages");
+            self.to_responder.push((self.generation, buffer));
+        } else {
+            assert!(self.initiator.write_message(request, &mut buffer).is_err());
+            assert!(buffer.is_empty());
+        }
+    }
+
+    fn close(&mut self) {
+        if !self.initiator.is_connected() {
+            return;
+        }
+
+        let mut buffer = vec![];
+        self.initiator
+            .write_message(Message::Close, &mut buffer)
+            .expect("connected session must accept messages");
+        self.to_responder.push((self.generation, buffer));
+    }
+
+    fn initiator_failed(&mut self) {
+        assert_failed_cleanly(&mut self.initiator);
+        self.failures += 1;
+        self.reset();
+    }
+
+    /// Replace the initiator with a new one, as the RPC client does.
+    fn reset(&mut self) {
+        self.generation += 1;
+        self.initiator = Builder::new().build_initiator();
+        self.initiated = false;
+    }
+
+    /// Deliver all messages in flight without faults.
+    fn settle(&mut self) {
+        self.fault_rate = 0.0;
+        for _ in 0..MAX_SETTLE_DELIVERIES {
+            if !self.to_responder.is_empty() {
+                self.step(Action::Deliver(Direction::ToResponder, None));
+            } else if !self.to_initiator.is_empty() {
+                self.step(Action::Deliver(Direction::ToInitiator, None));
+            } else {
+                return;
+            }
+        }
+        panic!("network did not settle");
+    }
+
+    /// Perform a call as the RPC client does, connecting first if needed,
+    /// and return whether a response was received.
+    fn call(&mut self) -> bool {
+        if !self.initiator.is_connected() {
+            if self.initiated {
+                // A stalled handshake is abandoned by the client.
+                self.reset();
+            }
+            self.step(Action::Initiate);
+            self.settle();
+        }
+
+        let round_trips = self.round_trips;
+        self.step(Action::Request);
+        self.settle();
+        self.round_trips > round_trips
+    }
+}
+
+/// Check that a session which returned an error can no longer be used.
+fn assert_failed_cleanly(session: &mut Session) {
+    assert!(
+        !session.is_connected(),
+        "failed session must not be connected"
+    );
+    assert!(
+        session.process_data(vec![], vec![]).is_err(),
+        "failed session must not process data"
+    );
+    assert!(
+        session.write_message(Message::Close, vec![]).is_err(),
+        "failed session must not write messages"
+    );
+}
+
+fn panic_message(error: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = error.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = error.downcast_ref::<String>() {
+        msg
+    } else {
+        "unknown panic"
+    }
+}
+
+#[test]
+fn test_session_simulation() {
+    let mut round_trips = 0;
+    let mut failures = 0;
+
+    for seed in 0..RUNS {
+        let mut sim = Simulation::new(seed);
+        if let Err(error) = panic::catch_unwind(AssertUnwindSafe(|| sim.run())) {
+            panic!(
+                "simulation failed (seed: {} trace: {:?}): {}",
+                seed,
+                sim.trace,
+                panic_message(&*error)
+            );
+        }
+
+        round_trips += sim.round_trips;
+        failures += sim.failures;
+    }
+
+    // Make sure both the happy path and failures were exercised.
+    assert!(round_trips > 0);
+    assert!(failures > 0);
+}
@@ -4,6 +4,9 @@ use serde_derive::{Deserialize, Serialize};
 
 use crate::common::cbor::Value;
 
+// Re-exports.
+pub use crate::types::{Error, ErrorCode};
+
 impl_bytes!(
     SessionID,
     32,
@@ -35,6 +38,9 @@ pub struct Frame {
     pub untrusted_plaintext: String,
     #[serde(with = "serde_bytes")]
     pub payload: Vec<u8>,
+    /// Tracing span context of the caller, if any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "serde_bytes")]
+    pub span_context: Vec<u8>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -43,15 +49,11 @@ pub struct Request {
     pub args: Value,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Error {
-    pub message: String,
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Body {
     Success(Value),
-    Error(String),
+    /// Failed call, carrying the module name and code of the error.
+    Error(Error),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -1,7 +1,8 @@
 use std::{any::Any, ptr::NonNull, sync::Arc};
 
-use failure::Fallible;
+use anyhow::Result;
 use io_context::Context;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::storage::mkvs::{cache::lru_cache::CacheItemBox, sync::*, tree::*};
 
@@ -14,6 +15,31 @@ pub struct CacheStats {
     pub leaf_value_size: usize,
 }
 
+/// Counters tracking cache efficiency and read syncer usage.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CacheMetrics {
+    /// Number of node dereferences served from the cache.
+    pub hits: u64,
+    /// Number of node dereferences served from the spill storage.
+    pub spill_hits: u64,
+    /// Number of node dereferences which required a remote sync.
+    pub misses: u64,
+    /// Number of round trips made to the read syncer.
+    pub sync_round_trips: u64,
+    /// Number of nodes fetched from the read syncer.
+    pub nodes_fetched: u64,
+    /// Total size, in bytes, of proofs received from the read syncer.
+    pub bytes_synced: u64,
+    /// Number of nodes written to the spill storage.
+    pub nodes_spilled: u64,
+    /// Number of commits.
+    pub commits: u64,
+    /// Total number of write log entries produced by commits.
+    pub write_log_entries: u64,
+    /// Total size, in bytes, of keys and values in write logs produced by commits.
+    pub write_log_bytes: u64,
+}
+
 /// Used to fetch proofs from a remote tree via the ReadSyncer interface.
 pub trait ReadSyncFetcher {
     /// Fetch proof.
@@ -23,12 +49,20 @@ pub trait ReadSyncFetcher {
         root: Root,
         ptr: NodePtrRef,
         rs: &mut Box<dyn ReadSync>,
-    ) -> Fallible<Proof>;
+    ) -> Result<Proof>;
+
+    /// Return the path and bit depth of the node the proof is fetched for.
+    ///
+    /// These are needed to verify prefix-compressed proofs for subtrees which
+    /// are not rooted at the tree root.
+    fn position(&self) -> (Key, Depth) {
+        (Key::new(), 0)
+    }
 }
 
 impl<F> ReadSyncFetcher for F
 where
-    F: Fn(Context, Root, NodePtrRef, &mut Box<dyn ReadSync>) -> Fallible<Proof>,
+    F: Fn(Context, Root, NodePtrRef, &mut Box<dyn ReadSync>) -> Result<Proof>,
 {
     fn fetch(
         &self,
@@ -36,7 +70,7 @@ where
         root: Root,
         ptr: NodePtrRef,
         rs: &mut Box<dyn ReadSync>,
-    ) -> Fallible<Proof> {
+    ) -> Result<Proof> {
         (*self)(ctx, root, ptr, rs)
     }
 }
@@ -48,6 +82,8 @@ pub trait Cache {
 
     /// Return statistics about the contents of the cache.
     fn stats(&self) -> CacheStats;
+    /// Return cache and read syncer usage metrics.
+    fn metrics(&self) -> CacheMetrics;
 
     /// Get a pointer to the current uncommitted root node.
     fn get_pending_root(&self) -> NodePtrRef;
@@ -84,14 +120,14 @@ pub trait Cache {
         ctx: &Arc<Context>,
         ptr: NodePtrRef,
         fetcher: F,
-    ) -> Fallible<Option<NodeRef>>;
+    ) -> Result<Option<NodeRef>>;
     /// Perform a remote sync with the configured remote syncer.
     fn remote_sync<F: ReadSyncFetcher>(
         &mut self,
         ctx: &Arc<Context>,
         ptr: NodePtrRef,
         fetcher: F,
-    ) -> Fallible<()>;
+    ) -> Result<()>;
 
     /// Mark that a tree node was just used.
     fn use_node(&mut self, ptr: NodePtrRef) -> bool;
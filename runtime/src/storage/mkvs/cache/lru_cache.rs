@@ -1,19 +1,60 @@
-use std::{any::Any, cell::RefCell, pin::Pin, ptr::NonNull, rc::Rc, sync::Arc};
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    pin::Pin,
+    ptr::NonNull,
+    rc::Rc,
+    sync::Arc,
+};
 
-use failure::Fallible;
+use anyhow::Result;
 use intrusive_collections::{IntrusivePointer, LinkedList, LinkedListLink};
 use io_context::Context;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
 
-use crate::storage::mkvs::{cache::*, sync::*, tree::*};
+use crate::{
+    common::{
+        crypto::hash::Hash,
+        envelope::{self, Versioned},
+    },
+    memory::{self, MemoryError, Subsystem},
+    storage::{
+        mkvs::{cache::*, marshal::Marshal, sync::*, tree::*, WriteLog},
+        SpillStorage,
+    },
+};
 
-#[derive(Debug, Fail)]
-#[fail(display = "mkvs: tried to remove locked node")]
+/// A node in the spill storage.
+#[derive(Serialize, Deserialize)]
+struct SpilledNode {
+    /// Marshalled node.
+    #[serde(with = "serde_bytes")]
+    node: Vec<u8>,
+}
+
+impl Versioned for SpilledNode {
+    const TYPE_TAG: &'static str = "mkvs/spilled-node";
+    const VERSION: u16 = 1;
+}
+
+/// Nodes to be written to the spill storage in a single batch.
+#[derive(Default)]
+struct SpillBatch {
+    ptrs: Vec<NodePtrRef>,
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+#[derive(Debug, Error)]
+#[error("mkvs: tried to remove locked node")]
 struct RemoveLockedError;
 
 #[derive(Clone, Default)]
 pub struct CacheItemBox<Item: CacheItem + Default> {
     item: Rc<RefCell<Item>>,
     link: LinkedListLink,
+    protected: Cell<bool>,
 }
 
 unsafe impl<T: CacheItem + Default> IntrusivePointer<CacheItemBox<T>>
@@ -35,13 +76,20 @@ intrusive_adapter!(
         where Item: CacheItem + Default
 );
 
+/// Fraction (in percent) of the total capacity reserved for the protected
+/// segment when using the segmented eviction policy.
+const PROTECTED_SEGMENT_PERCENT: usize = 80;
+
 struct LRUList<V>
 where
     V: CacheItem + Default,
 {
     pub list: LinkedList<CacheItemAdapter<V>>,
+    pub protected: LinkedList<CacheItemAdapter<V>>,
     pub size: usize,
+    pub protected_size: usize,
     pub capacity: usize,
+    pub policy: EvictionPolicy,
     pub mark: CacheExtra<V>,
 }
 
@@ -49,15 +97,22 @@ impl<V> LRUList<V>
 where
     V: CacheItem + Default,
 {
-    pub fn new(capacity: usize) -> LRUList<V> {
+    pub fn new(capacity: usize, policy: EvictionPolicy) -> LRUList<V> {
         LRUList {
             list: LinkedList::new(CacheItemAdapter::new()),
+            protected: LinkedList::new(CacheItemAdapter::new()),
             size: 0,
+            protected_size: 0,
             capacity: capacity,
+            policy: policy,
             mark: None,
         }
     }
 
+    fn protected_capacity(&self) -> usize {
+        self.capacity * PROTECTED_SEGMENT_PERCENT / 100
+    }
+
     fn mark(&mut self) {
         self.mark = self.list.front().get().map(|front| {
             front
@@ -75,6 +130,7 @@ where
             let mut item_box = Box::pin(CacheItemBox {
                 item: val.clone(),
                 link: LinkedListLink::new(),
+                protected: Cell::new(false),
             });
             val_ref.set_cache_extra(NonNull::new(&mut *item_box));
             if let Some(non_null_pos) = &self.mark {
@@ -94,32 +150,87 @@ where
         match val_ref.get_cache_extra() {
             None => false,
             Some(non_null) => {
-                let mut item_cursor = unsafe { self.list.cursor_mut_from_ptr(non_null.as_ptr()) };
-                let removed_box = item_cursor.remove().unwrap();
-                self.list.push_front(removed_box);
+                let protected = unsafe { non_null.as_ref() }.protected.get();
+                match self.policy {
+                    EvictionPolicy::LRU => {
+                        let mut item_cursor =
+                            unsafe { self.list.cursor_mut_from_ptr(non_null.as_ptr()) };
+                        let removed_box = item_cursor.remove().unwrap();
+                        self.list.push_front(removed_box);
+                    }
+                    EvictionPolicy::Segmented if protected => {
+                        let mut item_cursor =
+                            unsafe { self.protected.cursor_mut_from_ptr(non_null.as_ptr()) };
+                        let removed_box = item_cursor.remove().unwrap();
+                        self.protected.push_front(removed_box);
+                    }
+                    EvictionPolicy::Segmented => {
+                        // Promote the item from the probationary segment into the
+                        // protected segment.
+                        self.clear_mark_if(non_null);
+                        let mut item_cursor =
+                            unsafe { self.list.cursor_mut_from_ptr(non_null.as_ptr()) };
+                        let removed_box = item_cursor.remove().unwrap();
+                        removed_box.protected.set(true);
+                        self.protected_size += val_ref.get_cached_size();
+                        self.protected.push_front(removed_box);
+                        self.demote_protected();
+                    }
+                }
                 true
             }
         }
     }
 
+    fn demote_protected(&mut self) {
+        let protected_capacity = self.protected_capacity();
+        if protected_capacity == 0 {
+            return;
+        }
+
+        // Demote least recently used protected items back into the probationary
+        // segment, giving them another chance before eviction.
+        while self.protected_size > protected_capacity {
+            let item_box = match self.protected.pop_back() {
+                Some(item_box) => item_box,
+                None => break,
+            };
+            item_box.protected.set(false);
+            self.protected_size -= item_box.item.borrow().get_cached_size();
+            self.list.push_front(item_box);
+        }
+    }
+
+    fn clear_mark_if(&mut self, non_null: NonNull<CacheItemBox<V>>) {
+        if let Some(non_null_mark) = self.mark {
+            if non_null.as_ptr() == non_null_mark.as_ptr() {
+                self.mark = None;
+            }
+        }
+    }
+
     fn remove(&mut self, val: Rc<RefCell<V>>) -> bool {
         let extra = val.borrow().get_cache_extra();
         match extra {
             None => false,
             Some(non_null) => {
-                if let Some(non_null_mark) = self.mark {
-                    if non_null.as_ptr() == non_null_mark.as_ptr() {
-                        self.mark = None;
-                    }
-                }
+                self.clear_mark_if(non_null);
 
-                let mut item_cursor = unsafe { self.list.cursor_mut_from_ptr(non_null.as_ptr()) };
+                let protected = unsafe { non_null.as_ref() }.protected.get();
+                let mut item_cursor = if protected {
+                    unsafe { self.protected.cursor_mut_from_ptr(non_null.as_ptr()) }
+                } else {
+                    unsafe { self.list.cursor_mut_from_ptr(non_null.as_ptr()) }
+                };
                 match item_cursor.remove() {
                     None => false,
                     Some(item_box) => {
                         let mut val = item_box.item.borrow_mut();
                         val.set_cache_extra(None);
                         self.size -= val.get_cached_size();
+                        if protected {
+                            self.protected_size -= val.get_cached_size();
+                        }
                         true
                     }
                 }
@@ -131,27 +242,70 @@ where
         &mut self,
         val: Rc<RefCell<V>>,
         locked_val: Option<&Rc<RefCell<V>>>,
+    ) -> Result<Vec<Rc<RefCell<V>>>, RemoveLockedError> {
+        let target_size = val.borrow().get_cached_size();
+        self.evict_for_size(target_size, locked_val)
+    }
+
+    fn evict_for_size(
+        &mut self,
+        target_size: usize,
+        locked_val: Option<&Rc<RefCell<V>>>,
     ) -> Result<Vec<Rc<RefCell<V>>>, RemoveLockedError> {
         let mut evicted: Vec<Rc<RefCell<V>>> = Vec::new();
         if self.capacity > 0 {
-            let target_size = val.borrow().get_cached_size();
-            while !self.list.is_empty() && self.size + target_size > self.capacity {
-                let back = (*self.list.back().get().unwrap()).item.clone();
-                if let Some(locked_val) = locked_val {
-                    if back.as_ptr() == locked_val.as_ptr() {
-                        return Err(RemoveLockedError);
-                    }
-                }
-                if self.remove(back.clone()) {
-                    evicted.push(back);
+            while self.size + target_size > self.capacity {
+                match self.evict_one(locked_val)? {
+                    Some(val) => evicted.push(val),
+                    None => break,
                 }
             }
         }
         Ok(evicted)
     }
+
+    fn evict_one(
+        &mut self,
+        locked_val: Option<&Rc<RefCell<V>>>,
+    ) -> Result<Option<Rc<RefCell<V>>>, RemoveLockedError> {
+        // Always evict from the probationary segment first.
+        let back = match self.list.back().get() {
+            Some(back) => back.item.clone(),
+            None => match self.protected.back().get() {
+                Some(back) => back.item.clone(),
+                None => return Ok(None),
+            },
+        };
+        if let Some(locked_val) = locked_val {
+            if back.as_ptr() == locked_val.as_ptr() {
+                return Err(RemoveLockedError);
+            }
+        }
+        if !self.remove(back.clone()) {
+            return Ok(None);
+        }
+        Ok(Some(back))
+    }
 }
 
-/// Cache implementation with a simple LRU eviction strategy.
+/// Eviction policy used by the in-memory tree cache.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Evict the least recently used items first.
+    LRU,
+    /// Segmented LRU. Newly cached items enter a probationary segment and are
+    /// only promoted into the protected segment when they are used again. This
+    /// prevents one-off scans from flushing frequently used nodes.
+    Segmented,
+}
+
+impl Default for EvictionPolicy {
+    fn default() -> Self {
+        EvictionPolicy::LRU
+    }
+}
+
+/// Cache implementation with a configurable LRU-based eviction strategy.
 pub struct LRUCache {
     read_syncer: Box<dyn ReadSync>,
 
@@ -160,6 +314,14 @@ pub struct LRUCache {
 
     lru_leaf: LRUList<NodePointer>,
     lru_internal: LRUList<NodePointer>,
+
+    /// Cached nodes from previous roots which may be reused under the current root.
+    retained: HashMap<Hash, NodePtrRef>,
+
+    /// Untrusted storage that evicted nodes are spilled to.
+    spill_storage: Option<Arc<dyn SpillStorage>>,
+
+    metrics: CacheMetrics,
 }
 
 impl LRUCache {
@@ -169,10 +331,12 @@ impl LRUCache {
     ///   cache before eviction.
     /// * `value_capacity` is the total size, in bytes, of values held
     ///   by the cache before eviction.
+    /// * `policy` is the eviction policy used by the cache.
     /// * `read_syncer` is the read syncer used as backing for the cache.
     pub fn new(
         node_capacity: usize,
         value_capacity: usize,
+        policy: EvictionPolicy,
         read_syncer: Box<dyn ReadSync>,
     ) -> Box<LRUCache> {
         Box::new(LRUCache {
@@ -184,11 +348,274 @@ impl LRUCache {
             })),
             sync_root: Root::default(),
 
-            lru_leaf: LRUList::new(value_capacity),
-            lru_internal: LRUList::new(node_capacity),
+            lru_leaf: LRUList::new(value_capacity, policy),
+            lru_internal: LRUList::new(node_capacity, policy),
+
+            retained: HashMap::new(),
+
+            spill_storage: None,
+
+            metrics: CacheMetrics::default(),
         })
     }
 
+    /// Configure untrusted storage that evicted nodes are spilled to.
+    ///
+    /// Nodes which are not available in the cache are first looked up in the
+    /// spill storage and only fetched from the read syncer if not found there.
+    /// As nodes are content-addressed, nodes loaded from the spill storage are
+    /// verified against the hash of the pointer referencing them.
+    ///
+    /// Entries left in the storage, e.g., by a previous instance, are removed.
+    /// Entries are also removed when switching to an unrelated root.
+    pub fn set_spill_storage(&mut self, storage: Arc<dyn SpillStorage>) {
+        let _ = storage.clear();
+        self.spill_storage = Some(storage);
+    }
+
+    /// Change the capacity of the cache, evicting any items that no longer fit.
+    ///
+    /// A capacity of 0 means that the relevant cache has unlimited capacity.
+    pub fn set_capacity(&mut self, node_capacity: usize, value_capacity: usize) {
+        self.lru_internal.capacity = node_capacity;
+        self.lru_leaf.capacity = value_capacity;
+
+        let evicted = self
+            .lru_internal
+            .evict_for_size(0, None)
+            .expect("no locked pointer passed, cannot fail");
+        self.remove_evicted(evicted, None)
+            .expect("no locked pointer passed, cannot fail");
+        self.lru_internal.demote_protected();
+
+        let evicted = self
+            .lru_leaf
+            .evict_for_size(0, None)
+            .expect("no locked pointer passed, cannot fail");
+        self.remove_evicted(evicted, None)
+            .expect("no locked pointer passed, cannot fail");
+        self.lru_leaf.demote_protected();
+    }
+
+    /// Record a commit which produced the given write log.
+    pub fn record_commit(&mut self, write_log: &WriteLog) {
+        self.metrics.commits += 1;
+        self.metrics.write_log_entries += write_log.len() as u64;
+        self.metrics.write_log_bytes += write_log
+            .iter()
+            .map(|entry| entry.key.len() + entry.value.as_ref().map_or(0, |v| v.len()))
+            .sum::<usize>() as u64;
+    }
+
+    /// Reset all cache metrics.
+    pub fn reset_metrics(&mut self) {
+        self.metrics = CacheMetrics::default();
+    }
+
+    /// Return the eviction policy used by the cache.
+    pub fn eviction_policy(&self) -> EvictionPolicy {
+        self.lru_internal.policy
+    }
+
+    /// Switch the cache to a new root, discarding any uncommitted changes.
+    ///
+    /// As nodes are content-addressed, any cached node is still valid under
+    /// the new root when it is referenced by the same hash. In case the new
+    /// root is in the same namespace and of the same type (regardless of its
+    /// version, e.g., when alternating between checking transactions against
+    /// the last finalized root and executing on top of it), cached nodes are
+    /// retained and reused when they are reached under the new root instead of
+    /// being fetched from the read syncer. Nodes retained by earlier switches
+    /// remain available until they are evicted. Otherwise the cache and the
+    /// spill storage are cleared.
+    pub fn switch_root(&mut self, root: Root) {
+        let old_root = self.pending_root.clone();
+
+        if root.namespace == self.sync_root.namespace && root.root_type == self.sync_root.root_type
+        {
+            // Forget retained nodes which have since been evicted.
+            self.retained.retain(|_, ptr| ptr.borrow().node.is_some());
+            self.retain_subtree(&old_root);
+        } else {
+            let retained: Vec<NodePtrRef> = self.retained.drain().map(|(_, ptr)| ptr).collect();
+            for ptr in retained {
+                self.remove_node(ptr);
+            }
+            self.remove_node(old_root);
+
+            if let Some(ref storage) = self.spill_storage {
+                let _ = storage.clear();
+            }
+        }
+
+        self.pending_root = if root.hash.is_empty() {
+            NodePointer::null_ptr()
+        } else {
+            NodePointer::hash_ptr(root.hash)
+        };
+        self.sync_root = root;
+    }
+
+    fn retain_subtree(&mut self, ptr: &NodePtrRef) {
+        let ptr_ref = ptr.borrow();
+        let node_ref = match ptr_ref.node {
+            Some(ref node_ref) => node_ref.clone(),
+            None => return,
+        };
+        if ptr_ref.clean && !ptr_ref.is_null() {
+            self.retained.insert(ptr_ref.hash, ptr.clone());
+        }
+
+        if let NodeBox::Internal(ref n) = *node_ref.borrow() {
+            // NOTE: Leaf nodes are retained together with their internal node.
+            self.retain_subtree(&n.left);
+            self.retain_subtree(&n.right);
+        };
+    }
+
+    /// Try to resolve a hash-only pointer using a retained node.
+    fn claim_retained(&mut self, ptr: &NodePtrRef) -> bool {
+        let hash = ptr.borrow().hash;
+        let retained = match self.retained.remove(&hash) {
+            Some(retained) => retained,
+            None => return false,
+        };
+        let kind = {
+            let retained = retained.borrow();
+            if !retained.clean || retained.hash != hash {
+                return false;
+            }
+            classify_noderef!(? retained.node)
+        };
+
+        // Move the node over to the new pointer so that each node is only
+        // referenced by a single pointer held by the cache.
+        match kind {
+            NodeKind::Internal => self.lru_internal.remove(retained.clone()),
+            NodeKind::Leaf => self.lru_leaf.remove(retained.clone()),
+            NodeKind::None => return false,
+        };
+        ptr.borrow_mut().node = retained.borrow_mut().node.take();
+        if self.try_commit_node(ptr.clone(), Some(ptr)).is_err() {
+            ptr.borrow_mut().node = None;
+            return false;
+        }
+
+        true
+    }
+
+    /// Remove evicted nodes from the cache, first writing them to the spill
+    /// storage in a single batch.
+    fn remove_evicted(
+        &mut self,
+        evicted: Vec<NodePtrRef>,
+        locked_ptr: Option<&NodePtrRef>,
+    ) -> Result<(), RemoveLockedError> {
+        if let Some(storage) = self.spill_storage.clone() {
+            let mut batch = SpillBatch::default();
+            for node in &evicted {
+                self.spill_subtree(node, &mut batch);
+            }
+            if !batch.entries.is_empty() && storage.insert_batch(batch.entries).is_ok() {
+                for ptr in batch.ptrs {
+                    ptr.borrow_mut().spilled = true;
+                    self.metrics.nodes_spilled += 1;
+                }
+            }
+        }
+
+        for node in evicted {
+            self.try_remove_node(node, locked_ptr)?;
+        }
+
+        Ok(())
+    }
+
+    /// Add all clean nodes in the given subtree to the spill batch.
+    ///
+    /// Nodes which are already in the spill storage are not written again.
+    fn spill_subtree(&self, ptr: &NodePtrRef, batch: &mut SpillBatch) {
+        let (node_ref, spilled) = {
+            let ptr = ptr.borrow();
+            match ptr.node {
+                Some(ref node_ref) if ptr.clean && !ptr.is_null() => {
+                    (node_ref.clone(), ptr.spilled)
+                }
+                _ => return,
+            }
+        };
+        let hash = ptr.borrow().hash;
+
+        let data = if spilled {
+            None
+        } else {
+            let node = node_ref.borrow();
+            match *node {
+                NodeBox::Internal(ref n) => {
+                    // Internal nodes embed their leaf node, which may have
+                    // already been evicted.
+                    let leaf_ptr = n.leaf_node.borrow();
+                    if !leaf_ptr.is_null() && leaf_ptr.node.is_none() {
+                        None
+                    } else {
+                        node.marshal_binary().ok()
+                    }
+                }
+                NodeBox::Leaf(_) => node.marshal_binary().ok(),
+            }
+        };
+        if let Some(data) = data {
+            batch.ptrs.push(ptr.clone());
+            batch.entries.push((
+                hash.as_ref().to_vec(),
+                envelope::to_vec(&SpilledNode { node: data }),
+            ));
+        }
+
+        if let NodeBox::Internal(ref n) = *node_ref.borrow() {
+            self.spill_subtree(&n.left, batch);
+            self.spill_subtree(&n.right, batch);
+        }
+    }
+
+    /// Try to resolve a hash-only pointer using the spill storage.
+    fn load_spilled(&mut self, ptr: &NodePtrRef) -> bool {
+        let loaded = self.try_load_spilled(ptr);
+        // Nodes which could not be loaded must be spilled again once evicted.
+        ptr.borrow_mut().spilled = loaded;
+        loaded
+    }
+
+    fn try_load_spilled(&mut self, ptr: &NodePtrRef) -> bool {
+        let hash = ptr.borrow().hash;
+        let data = match self.spill_storage {
+            Some(ref storage) => match storage.get(hash.as_ref().to_vec()) {
+                Ok(ref data) if data.is_empty() => return false,
+                Ok(data) => data,
+                Err(_) => return false,
+            },
+            None => return false,
+        };
+        let data = match envelope::from_slice::<SpilledNode>(&data) {
+            Ok(spilled) => spilled.node,
+            Err(_) => return false,
+        };
+
+        // The spill storage is untrusted, so verify the node before using it.
+        let mut node = NodeBox::default();
+        if node.unmarshal_binary(&data).is_err() || node.get_hash() != hash {
+            return false;
+        }
+
+        ptr.borrow_mut().node = Some(Rc::new(RefCell::new(node)));
+        if self.commit_merged_node(ptr.clone(), ptr).is_err() {
+            ptr.borrow_mut().node = None;
+            return false;
+        }
+
+        true
+    }
+
     fn new_internal_node_ptr(&mut self, node: Option<NodeRef>) -> NodePtrRef {
         Rc::new(RefCell::new(NodePointer {
             node: node,
@@ -223,18 +650,14 @@ impl LRUCache {
                 let evicted = self
                     .lru_internal
                     .evict_for_val(ptr.clone(), locked_ptr.clone())?;
-                for node in evicted {
-                    self.try_remove_node(node.clone(), locked_ptr.clone())?;
-                }
+                self.remove_evicted(evicted, locked_ptr.clone())?;
                 self.lru_internal.add(ptr.clone());
             }
             NodeKind::Leaf => {
                 let evicted = self
                     .lru_leaf
                     .evict_for_val(ptr.clone(), locked_ptr.clone())?;
-                for node in evicted {
-                    self.try_remove_node(node.clone(), locked_ptr.clone())?;
-                }
+                self.remove_evicted(evicted, locked_ptr.clone())?;
                 self.lru_leaf.add(ptr.clone());
             }
             NodeKind::None => return Ok(()),
@@ -315,6 +738,37 @@ impl LRUCache {
         Ok(())
     }
 
+    /// Evict nodes until the given number of bytes can be allocated without
+    /// going over the memory limit of the cache.
+    fn evict_for_memory(
+        &mut self,
+        size: usize,
+        locked_ptr: &NodePtrRef,
+    ) -> Result<(), MemoryError> {
+        loop {
+            let err = match memory::check_limit(Subsystem::MkvsCache, size) {
+                Ok(()) => return Ok(()),
+                Err(err) => err,
+            };
+
+            // Evict values first as they take up most of the memory.
+            let evicted = match self.lru_leaf.evict_one(Some(locked_ptr)) {
+                Ok(Some(node)) => node,
+                _ => match self.lru_internal.evict_one(Some(locked_ptr)) {
+                    Ok(Some(node)) => node,
+                    // Nothing left to evict.
+                    _ => return Err(err),
+                },
+            };
+            if self
+                .remove_evicted(vec![evicted], Some(locked_ptr))
+                .is_err()
+            {
+                return Err(err);
+            }
+        }
+    }
+
     fn commit_merged_node(
         &mut self,
         ptr: NodePtrRef,
@@ -358,6 +812,10 @@ impl Cache for LRUCache {
         }
     }
 
+    fn metrics(&self) -> CacheMetrics {
+        self.metrics.clone()
+    }
+
     fn get_pending_root(&self) -> NodePtrRef {
         self.pending_root.clone()
     }
@@ -416,7 +874,7 @@ impl Cache for LRUCache {
         ctx: &Arc<Context>,
         ptr: NodePtrRef,
         fetcher: F,
-    ) -> Fallible<Option<NodeRef>> {
+    ) -> Result<Option<NodeRef>> {
         let ptr_ref = ptr;
         let ptr = ptr_ref.borrow();
 
@@ -437,6 +895,7 @@ impl Cache for LRUCache {
                 drop(ptr);
                 self.remove_node(ptr_ref.clone());
             } else {
+                self.metrics.hits += 1;
                 return Ok(Some(node.clone()));
             }
         } else {
@@ -444,14 +903,27 @@ impl Cache for LRUCache {
                 return Ok(None);
             }
             drop(ptr);
+
+            // Node may still be available from a previous root.
+            if self.claim_retained(&ptr_ref) {
+                self.metrics.hits += 1;
+                return Ok(ptr_ref.borrow().node.clone());
+            }
+
+            // Node may have been spilled to the spill storage.
+            if self.load_spilled(&ptr_ref) {
+                self.metrics.spill_hits += 1;
+                return Ok(ptr_ref.borrow().node.clone());
+            }
         }
 
         // Node not available locally, fetch from read syncer.
+        self.metrics.misses += 1;
         self.remote_sync(ctx, ptr_ref.clone(), fetcher)?;
 
         let ptr = ptr_ref.borrow();
         if ptr.node.is_none() {
-            return Err(format_err!(
+            return Err(anyhow!(
                 "mkvs: received result did not contain node (or cache too small)"
             ));
         }
@@ -463,50 +935,84 @@ impl Cache for LRUCache {
         ctx: &Arc<Context>,
         ptr: NodePtrRef,
         fetcher: F,
-    ) -> Fallible<()> {
+    ) -> Result<()> {
         let proof = fetcher.fetch(
             Context::create_child(&ctx),
             self.sync_root,
             ptr.clone(),
             &mut self.read_syncer,
         )?;
+        let proof_size = proof
+            .entries
+            .iter()
+            .map(|entry| entry.as_ref().map_or(0, |e| e.len()))
+            .sum::<usize>();
+        self.metrics.sync_round_trips += 1;
+        self.metrics.bytes_synced += proof_size as u64;
+        self.metrics.nodes_fetched += proof
+            .entries
+            .iter()
+            .filter(|entry| {
+                entry.as_ref().map_or(false, |e| match e.first() {
+                    Some(&PROOF_ENTRY_FULL) | Some(&PROOF_ENTRY_COMPRESSED) => true,
+                    _ => false,
+                })
+            })
+            .count() as u64;
 
         // The proof can be for one of two hashes: i) it is either for ptr.Hash in case
         // all the nodes are only contained in the subtree below ptr, or ii) it is for
         // the c.syncRoot.Hash in case it contains nodes outside the subtree.
         let ptr_hash = ptr.borrow().hash;
-        let (dst_ptr, expected_root) = if proof.untrusted_root == ptr_hash {
-            (ptr.clone(), ptr_hash)
+        let (dst_ptr, expected_root, (path, bit_depth)) = if proof.untrusted_root == ptr_hash {
+            (ptr.clone(), ptr_hash, fetcher.position())
         } else if proof.untrusted_root == self.sync_root.hash {
-            (self.pending_root.clone(), self.sync_root.hash)
+            (
+                self.pending_root.clone(),
+                self.sync_root.hash,
+                (Key::new(), 0),
+            )
         } else {
-            return Err(format_err!(
-                "mkvs: got proof for unexpected root ({:?})",
-                proof.untrusted_root
-            ));
+            return Err(IntegrityError::UnexpectedRoot {
+                expected: ptr_hash,
+                got: proof.untrusted_root,
+            }
+            .into());
         };
 
-        // Verify proof.
-        let pv = ProofVerifier;
-        let subtree = pv.verify_proof(Context::create_child(&ctx), expected_root, &proof)?;
-
-        // Merge resulting nodes.
-        let mut merged_nodes: Vec<NodePtrRef> = Vec::new();
-        merge_verified_subtree(dst_ptr, subtree, &mut merged_nodes)?;
-        let mut remove = false;
-        for node_ref in merged_nodes {
-            if remove {
-                // Do not keep subtrees that we failed to commit in memory.
-                node_ref.borrow_mut().node = None;
-            }
+        // Make room for the fetched nodes, but refuse to grow the cache over its
+        // memory limit.
+        self.evict_for_memory(proof_size, &ptr)?;
+
+        memory::scope(Subsystem::MkvsCache, || -> Result<()> {
+            // Verify proof.
+            let pv = ProofVerifier;
+            let subtree = pv.verify_proof_at(
+                Context::create_child(&ctx),
+                expected_root,
+                &proof,
+                &path,
+                bit_depth,
+            )?;
 
-            if let Err(RemoveLockedError) = self.commit_merged_node(node_ref, &ptr) {
-                // Cache is too small, ignore.
-                remove = true;
+            // Merge resulting nodes.
+            let mut merged_nodes: Vec<NodePtrRef> = Vec::new();
+            merge_verified_subtree(dst_ptr, subtree, &mut merged_nodes)?;
+            let mut remove = false;
+            for node_ref in merged_nodes {
+                if remove {
+                    // Do not keep subtrees that we failed to commit in memory.
+                    node_ref.borrow_mut().node = None;
+                }
+
+                if let Err(RemoveLockedError) = self.commit_merged_node(node_ref, &ptr) {
+                    // Cache is too small, ignore.
+                    remove = true;
+                }
             }
-        }
 
-        Ok(())
+            Ok(())
+        })
     }
 
     fn use_node(&mut self, ptr: NodePtrRef) -> bool {
@@ -543,3 +1049,90 @@ impl Cache for LRUCache {
         self.lru_leaf.mark();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn new_items(count: usize) -> Vec<NodePtrRef> {
+        (0..count)
+            .map(|_| Rc::new(RefCell::new(NodePointer::default())))
+            .collect()
+    }
+
+    fn is_protected(item: &NodePtrRef) -> bool {
+        let extra = item
+            .borrow()
+            .get_cache_extra()
+            .expect("item must be cached");
+        unsafe { extra.as_ref() }.protected.get()
+    }
+
+    fn indices(items: &[NodePtrRef], evicted: &[NodePtrRef]) -> Vec<usize> {
+        evicted
+            .iter()
+            .map(|val| items.iter().position(|item| Rc::ptr_eq(item, val)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_segmented_lru() {
+        // A capacity of 5 leaves room for 4 items in the protected segment.
+        let mut lru: LRUList<NodePointer> = LRUList::new(5, EvictionPolicy::Segmented);
+        let items = new_items(9);
+        for item in &items[..5] {
+            lru.add(item.clone());
+        }
+        assert!(items[..5].iter().all(|item| !is_protected(item)));
+
+        // Items used again are promoted into the protected segment.
+        lru.use_val(items[0].clone());
+        lru.use_val(items[1].clone());
+        assert!(is_protected(&items[0]));
+        assert!(is_protected(&items[1]));
+        assert_eq!(lru.protected_size, 2);
+
+        // Probationary items are evicted first, even if they are more recent.
+        let evicted = lru.evict_for_size(1, None).unwrap();
+        assert_eq!(indices(&items, &evicted), vec![2]);
+        lru.add(items[5].clone());
+        let evicted = lru.evict_for_size(3, None).unwrap();
+        assert_eq!(indices(&items, &evicted), vec![3, 4, 5]);
+        assert_eq!(lru.size, 2);
+
+        // Promoting more items than fit demotes the least recently used
+        // protected item back into the probationary segment.
+        for item in &items[6..9] {
+            lru.add(item.clone());
+        }
+        lru.use_val(items[0].clone());
+        for item in &items[6..9] {
+            lru.use_val(item.clone());
+        }
+        assert_eq!(lru.protected_size, 4);
+        assert!(!is_protected(&items[1]));
+        assert!(is_protected(&items[0]));
+
+        // The demoted item is the next to be evicted, followed by the least
+        // recently used protected items.
+        let evicted = lru.evict_for_size(3, None).unwrap();
+        assert_eq!(indices(&items, &evicted), vec![1, 0, 6]);
+        assert_eq!(lru.size, 2);
+        assert_eq!(lru.protected_size, 2);
+    }
+
+    #[test]
+    fn test_lru() {
+        let mut lru: LRUList<NodePointer> = LRUList::new(3, EvictionPolicy::LRU);
+        let items = new_items(3);
+        for item in &items {
+            lru.add(item.clone());
+        }
+
+        // Items are never promoted and are evicted in least recently used order.
+        lru.use_val(items[0].clone());
+        assert!(!is_protected(&items[0]));
+        let evicted = lru.evict_for_size(2, None).unwrap();
+        assert_eq!(indices(&items, &evicted), vec![1, 2]);
+    }
+}
@@ -0,0 +1,466 @@
+//! MKVS checkpoint creation and restoration.
+use std::{collections::HashSet, sync::Arc};
+
+use anyhow::Result;
+use io_context::Context;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    common::{
+        crypto::hash::Hash,
+        envelope::{self, Versioned},
+    },
+    storage::mkvs::{cache::*, sync::*, tree::*},
+};
+
+/// Checkpoint-related error.
+#[derive(Debug, Error)]
+pub enum CheckpointError {
+    #[error("checkpoint: chunk not found")]
+    ChunkNotFound,
+    #[error("checkpoint: chunk already restored")]
+    ChunkAlreadyRestored,
+    #[error("checkpoint: corrupted chunk: digest incorrect (expected: {expected:?} got: {got:?})")]
+    ChunkCorrupted { expected: Hash, got: Hash },
+    #[error("checkpoint: chunk proof verification failed: {reason}")]
+    ChunkProofVerificationFailed { reason: String },
+    #[error("checkpoint: tree has uncommitted changes")]
+    DirtyTree,
+    #[error("checkpoint: restore not yet complete")]
+    RestoreIncomplete,
+}
+
+/// Chunk metadata.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChunkMetadata {
+    pub root: Root,
+    pub index: u64,
+    pub digest: Hash,
+}
+
+/// Checkpoint metadata.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub root: Root,
+    pub chunks: Vec<Hash>,
+}
+
+impl Versioned for Metadata {
+    const TYPE_TAG: &'static str = "mkvs/checkpoint";
+    const VERSION: u16 = 1;
+}
+
+impl Metadata {
+    /// Encode the checkpoint metadata.
+    pub fn to_vec(&self) -> Vec<u8> {
+        envelope::to_vec(self)
+    }
+
+    /// Decode checkpoint metadata.
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        envelope::from_slice(data)
+    }
+
+    /// Return the encoded cryptographic hash of the checkpoint metadata.
+    pub fn encoded_hash(&self) -> Hash {
+        Hash::digest_bytes(&self.to_vec())
+    }
+
+    /// Return the chunk metadata for the corresponding chunk.
+    pub fn get_chunk_metadata(&self, index: u64) -> Result<ChunkMetadata> {
+        let digest = self
+            .chunks
+            .get(index as usize)
+            .ok_or(CheckpointError::ChunkNotFound)?;
+
+        Ok(ChunkMetadata {
+            root: self.root,
+            index,
+            digest: *digest,
+        })
+    }
+}
+
+/// A checkpoint chunk.
+#[derive(Serialize, Deserialize)]
+struct Chunk {
+    /// Proof entries of the chunk's subtree, rooted at the checkpoint root.
+    entries: Vec<Option<RawProofEntry>>,
+}
+
+impl Versioned for Chunk {
+    const TYPE_TAG: &'static str = "mkvs/checkpoint-chunk";
+    const VERSION: u16 = 1;
+}
+
+/// Create a checkpoint of the given tree.
+///
+/// The tree must not have any uncommitted changes. Chunks are generated such
+/// that each chunk's proof is at most (approximately) `chunk_size` bytes in
+/// size. Returns the checkpoint metadata together with the encoded chunks.
+pub fn create_checkpoint(
+    ctx: Context,
+    tree: &Tree,
+    chunk_size: u64,
+) -> Result<(Metadata, Vec<Vec<u8>>)> {
+    let pending_root = tree.cache.borrow().get_pending_root();
+    let root = tree.cache.borrow().get_sync_root();
+    if !pending_root.borrow().clean || pending_root.borrow().hash != root.hash {
+        return Err(CheckpointError::DirtyTree.into());
+    }
+
+    let mut creator = ChunkCreator {
+        ctx: ctx.freeze(),
+        tree,
+        root,
+        chunk_size,
+        ancestors: vec![],
+        builder: ProofBuilder::new(root.hash),
+        pending: false,
+        chunks: vec![],
+    };
+    creator.visit(pending_root)?;
+    if creator.pending || creator.chunks.is_empty() {
+        creator.flush();
+    }
+
+    let metadata = Metadata {
+        root,
+        chunks: creator
+            .chunks
+            .iter()
+            .map(|chunk| Hash::digest_bytes(chunk))
+            .collect(),
+    };
+
+    Ok((metadata, creator.chunks))
+}
+
+struct ChunkCreator<'tree> {
+    ctx: Arc<Context>,
+    tree: &'tree Tree,
+    root: Root,
+    chunk_size: u64,
+    ancestors: Vec<NodeRef>,
+    builder: ProofBuilder,
+    pending: bool,
+    chunks: Vec<Vec<u8>>,
+}
+
+impl<'tree> ChunkCreator<'tree> {
+    fn visit(&mut self, ptr: NodePtrRef) -> Result<()> {
+        let node_ref = self.tree.cache.borrow_mut().deref_node_ptr(
+            &self.ctx,
+            ptr,
+            |ctx: Context, root: Root, ptr: NodePtrRef, rs: &mut Box<dyn ReadSync>| {
+                let rsp = rs.sync_iterate(
+                    ctx,
+                    IterateRequest {
+                        tree: TreeID {
+                            root,
+                            position: ptr.borrow().hash,
+                        },
+                        key: Key::new(),
+                        prefetch: 0,
+                        compressed: false,
+                    },
+                )?;
+                Ok(rsp.proof)
+            },
+        )?;
+        let node_ref = match node_ref {
+            Some(node_ref) => node_ref,
+            None => return Ok(()),
+        };
+
+        self.builder.include(&node_ref.borrow());
+        self.pending = true;
+
+        let children = match *node_ref.borrow() {
+            NodeBox::Internal(ref n) => Some((n.left.clone(), n.right.clone())),
+            NodeBox::Leaf(..) => None,
+        };
+        match children {
+            Some((left, right)) => {
+                self.ancestors.push(node_ref);
+                self.maybe_flush();
+                self.visit(left)?;
+                self.visit(right)?;
+                self.ancestors.pop();
+            }
+            None => self.maybe_flush(),
+        }
+
+        Ok(())
+    }
+
+    fn maybe_flush(&mut self) {
+        if self.builder.size() >= self.chunk_size {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        let proof = self.builder.build();
+        self.chunks.push(envelope::to_vec(&Chunk {
+            entries: proof.entries,
+        }));
+
+        // Start a new chunk which includes the path from the root to the
+        // current position so that the chunk can be verified on its own.
+        self.builder = ProofBuilder::new(self.root.hash);
+        for node_ref in &self.ancestors {
+            self.builder.include(&node_ref.borrow());
+        }
+        self.pending = false;
+    }
+}
+
+/// A checkpoint restorer.
+///
+/// The restorer reconstructs an in-memory tree from checkpoint chunks, verifying
+/// each chunk against the checkpoint metadata and the checkpoint root.
+pub struct Restorer {
+    checkpoint: Metadata,
+    tree: Tree,
+    restored: HashSet<u64>,
+}
+
+impl Restorer {
+    /// Start restoring the given checkpoint.
+    pub fn new(checkpoint: Metadata) -> Result<Self> {
+        let tree = Tree::make()
+            .with_capacity(0, 0)
+            .with_root(checkpoint.root)
+            .new(Box::new(NoopReadSyncer {}));
+
+        Ok(Self {
+            checkpoint,
+            tree,
+            restored: HashSet::new(),
+        })
+    }
+
+    /// Return the checkpoint that is being restored.
+    pub fn checkpoint(&self) -> &Metadata {
+        &self.checkpoint
+    }
+
+    /// Return true when all chunks have been restored.
+    pub fn is_done(&self) -> bool {
+        self.restored.len() == self.checkpoint.chunks.len()
+    }
+
+    /// Restore the given chunk.
+    ///
+    /// Returns true when the checkpoint has been fully restored.
+    pub fn restore_chunk(&mut self, ctx: Context, index: u64, chunk: &[u8]) -> Result<bool> {
+        let metadata = self.checkpoint.get_chunk_metadata(index)?;
+        if self.restored.contains(&index) {
+            return Err(CheckpointError::ChunkAlreadyRestored.into());
+        }
+
+        // Verify overall chunk integrity.
+        let digest = Hash::digest_bytes(chunk);
+        if digest != metadata.digest {
+            return Err(CheckpointError::ChunkCorrupted {
+                expected: metadata.digest,
+                got: digest,
+            }
+            .into());
+        }
+
+        // Reconstruct and verify the proof.
+        let chunk: Chunk = envelope::from_slice(chunk)?;
+        let proof = Proof {
+            untrusted_root: metadata.root.hash,
+            entries: chunk.entries,
+        };
+        let pv = ProofVerifier;
+        let subtree = pv
+            .verify_proof(ctx, metadata.root.hash, &proof)
+            .map_err(|err| CheckpointError::ChunkProofVerificationFailed {
+                reason: err.to_string(),
+            })?;
+
+        // Merge the verified nodes into the tree.
+        let mut cache = self.tree.cache.borrow_mut();
+        let mut merged_nodes: Vec<NodePtrRef> = Vec::new();
+        merge_verified_subtree(cache.get_pending_root(), subtree, &mut merged_nodes)?;
+        for node_ref in merged_nodes {
+            commit_subtree(&mut **cache, node_ref);
+        }
+        drop(cache);
+
+        self.restored.insert(index);
+
+        Ok(self.is_done())
+    }
+
+    /// Finish the restore and return the restored tree.
+    pub fn finish(self) -> Result<Tree> {
+        if !self.is_done() {
+            return Err(CheckpointError::RestoreIncomplete.into());
+        }
+        Ok(self.tree)
+    }
+}
+
+fn commit_subtree<C: Cache>(cache: &mut C, ptr: NodePtrRef) {
+    if ptr.borrow().node.is_none() {
+        return;
+    }
+    cache.commit_node(ptr.clone());
+
+    let node_ref = ptr.borrow().get_node();
+    if let NodeBox::Internal(ref n) = *node_ref.borrow() {
+        commit_subtree(cache, n.left.clone());
+        commit_subtree(cache, n.right.clone());
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use io_context::Context;
+
+    use super::*;
+    use crate::common::cbor;
+
+    fn populated_tree() -> (Tree, Vec<(Vec<u8>, Vec<u8>)>) {
+        let mut tree = Tree::make()
+            .with_capacity(0, 0)
+            .new(Box::new(NoopReadSyncer {}));
+        let mut items = vec![];
+        for i in 0..100 {
+            let key = format!("key {}", i).into_bytes();
+            let value = format!("value {}", i).into_bytes();
+            tree.insert(Context::background(), &key, &value)
+                .expect("insert");
+            items.push((key, value));
+        }
+        Tree::commit(&mut tree, Context::background(), Default::default(), 1).expect("commit");
+
+        (tree, items)
+    }
+
+    #[test]
+    fn test_checkpoint_restore() {
+        let (tree, items) = populated_tree();
+
+        let (metadata, chunks) =
+            create_checkpoint(Context::background(), &tree, 1024).expect("create checkpoint");
+        assert!(chunks.len() > 1, "checkpoint should have multiple chunks");
+        assert_eq!(metadata.chunks.len(), chunks.len());
+        assert_eq!(metadata.root.version, 1);
+
+        let mut restorer = Restorer::new(metadata).expect("restorer");
+        for (index, chunk) in chunks.iter().enumerate() {
+            let done = restorer
+                .restore_chunk(Context::background(), index as u64, chunk)
+                .expect("restore chunk");
+            assert_eq!(done, index == chunks.len() - 1);
+        }
+
+        let restored = restorer.finish().expect("finish");
+        for (key, value) in items {
+            let restored_value = restored
+                .get(Context::background(), &key)
+                .expect("get")
+                .expect("key should exist");
+            assert_eq!(restored_value, value);
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_restore_corrupted() {
+        let (tree, _) = populated_tree();
+
+        let (metadata, chunks) =
+            create_checkpoint(Context::background(), &tree, 1024).expect("create checkpoint");
+        let mut restorer = Restorer::new(metadata.clone()).expect("restorer");
+
+        // Corrupted chunk.
+        let mut corrupted = chunks[0].clone();
+        corrupted[10] ^= 0xff;
+        let err = restorer
+            .restore_chunk(Context::background(), 0, &corrupted)
+            .expect_err("corrupted chunk should fail");
+        match err.downcast_ref::<CheckpointError>() {
+            Some(CheckpointError::ChunkCorrupted { .. }) => {}
+            other => panic!("expected corrupted chunk error, got {:?}", other),
+        }
+
+        // Missing chunk.
+        assert!(restorer
+            .restore_chunk(Context::background(), chunks.len() as u64, &chunks[0])
+            .is_err());
+
+        // Valid chunk, restored twice.
+        restorer
+            .restore_chunk(Context::background(), 0, &chunks[0])
+            .expect("restore chunk");
+        let err = restorer
+            .restore_chunk(Context::background(), 0, &chunks[0])
+            .expect_err("restoring a chunk twice should fail");
+        match err.downcast_ref::<CheckpointError>() {
+            Some(CheckpointError::ChunkAlreadyRestored) => {}
+            other => panic!("expected chunk already restored error, got {:?}", other),
+        }
+
+        // Incomplete restore.
+        assert!(restorer.finish().is_err());
+
+        // Chunks with a valid digest must still verify against the root.
+        let mut metadata = metadata;
+        metadata.root.hash = Hash::digest_bytes(b"bogus root");
+        let mut restorer = Restorer::new(metadata).expect("restorer");
+        let err = restorer
+            .restore_chunk(Context::background(), 0, &chunks[0])
+            .expect_err("chunk for a different root should fail");
+        match err.downcast_ref::<CheckpointError>() {
+            Some(CheckpointError::ChunkProofVerificationFailed { .. }) => {}
+            other => panic!("expected proof verification error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_version_mismatch() {
+        let (tree, _) = populated_tree();
+
+        let (mut metadata, chunks) =
+            create_checkpoint(Context::background(), &tree, 1024).expect("create checkpoint");
+        assert_eq!(
+            Metadata::from_slice(&metadata.to_vec()).expect("decode metadata"),
+            metadata
+        );
+
+        // Metadata from a newer version.
+        let newer = cbor::to_vec(&envelope::Envelope {
+            type_tag: Metadata::TYPE_TAG.to_owned(),
+            version: Metadata::VERSION + 1,
+            body: cbor::to_vec(&metadata),
+        });
+        let err = Metadata::from_slice(&newer).expect_err("newer metadata should fail");
+        match err.downcast_ref::<envelope::EnvelopeError>() {
+            Some(envelope::EnvelopeError::VersionTooNew { .. }) => {}
+            other => panic!("expected version too new error, got {:?}", other),
+        }
+
+        // Chunk from a newer version.
+        let envelope: envelope::Envelope = cbor::from_slice(&chunks[0]).expect("decode envelope");
+        let newer = cbor::to_vec(&envelope::Envelope {
+            version: Chunk::VERSION + 1,
+            ..envelope
+        });
+        metadata.chunks[0] = Hash::digest_bytes(&newer);
+        let mut restorer = Restorer::new(metadata).expect("restorer");
+        let err = restorer
+            .restore_chunk(Context::background(), 0, &newer)
+            .expect_err("newer chunk should fail");
+        match err.downcast_ref::<envelope::EnvelopeError>() {
+            Some(envelope::EnvelopeError::VersionTooNew { .. }) => {}
+            other => panic!("expected version too new error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,507 @@
+//! Large value chunking.
+use std::{convert::TryInto, sync::Arc};
+
+use anyhow::Result;
+use io_context::Context;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    common::{cbor, crypto::hash::Hash, roothash::Namespace},
+    storage::mkvs::{Prefix, WriteLog, MKVS},
+};
+
+/// Default size of a single value chunk.
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+/// Default maximum size of a value.
+pub const DEFAULT_MAX_VALUE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Key prefix under which chunks are stored.
+///
+/// Keys starting with this prefix must not be used directly.
+pub const CHUNK_KEY_PREFIX: &'static [u8] = b"\xffmkvs/chunk/";
+
+/// Value is stored inline.
+const VALUE_INLINE: u8 = 0x00;
+/// Value is stored in chunks and the rest of the value is a manifest.
+const VALUE_CHUNKED: u8 = 0x01;
+/// Size of the chunk reference counter.
+const CHUNK_REFS_SIZE: usize = 8;
+
+/// Value chunking error.
+#[derive(Debug, Error)]
+pub enum ChunkError {
+    #[error("mkvs: value too large (size: {size} max: {max})")]
+    ValueTooLarge { size: usize, max: usize },
+    #[error("mkvs: malformed chunked value")]
+    Malformed,
+    #[error("mkvs: missing chunk {hash:?}")]
+    MissingChunk { hash: Hash },
+    #[error("mkvs: chunk hash mismatch (expected: {expected:?} got: {got:?})")]
+    ChunkHashMismatch { expected: Hash, got: Hash },
+    #[error("mkvs: chunk size must be non-zero")]
+    ZeroChunkSize,
+}
+
+/// Description of a value that has been split into chunks.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Manifest {
+    /// Total size of the value.
+    size: u64,
+    /// Hashes of the chunks in order.
+    chunks: Vec<Hash>,
+}
+
+/// A key-value tree wrapper that transparently splits large values into
+/// content-addressed chunks.
+///
+/// Values larger than the chunk size are split into chunks that are stored
+/// under their hash and the value itself is replaced by a manifest listing
+/// the chunk hashes. When reading, chunks are reassembled and verified
+/// against the manifest. Identical chunks are only stored once and are
+/// reference counted.
+///
+/// All values stored through the wrapper carry a one-byte header, so the
+/// underlying tree should only be accessed through the wrapper.
+pub struct ChunkedTree<T: MKVS> {
+    inner: T,
+    chunk_size: usize,
+    max_value_size: usize,
+}
+
+impl<T: MKVS> ChunkedTree<T> {
+    /// Create a new chunking wrapper with default parameters.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            max_value_size: DEFAULT_MAX_VALUE_SIZE,
+        }
+    }
+
+    /// Set the size of a single chunk.
+    ///
+    /// Inserting values fails if the chunk size is zero.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Set the maximum size of a value.
+    pub fn with_max_value_size(mut self, max_value_size: usize) -> Self {
+        self.max_value_size = max_value_size;
+        self
+    }
+
+    /// Return a reference to the inner tree.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Return the inner tree.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn decode_value(&self, ctx: &Arc<Context>, raw: &[u8]) -> Result<Vec<u8>> {
+        match raw.first() {
+            Some(&VALUE_INLINE) => Ok(raw[1..].to_vec()),
+            Some(&VALUE_CHUNKED) => {
+                let manifest = decode_manifest(&raw[1..])?;
+                let size: usize = manifest
+                    .size
+                    .try_into()
+                    .map_err(|_| ChunkError::Malformed)?;
+                if size > self.max_value_size {
+                    return Err(ChunkError::ValueTooLarge {
+                        size,
+                        max: self.max_value_size,
+                    }
+                    .into());
+                }
+
+                let mut value = Vec::with_capacity(size);
+                for hash in manifest.chunks {
+                    let chunk = self
+                        .inner
+                        .get(Context::create_child(ctx), &chunk_key(&hash))?
+                        .ok_or_else(|| ChunkError::MissingChunk { hash: hash.clone() })?;
+                    if chunk.len() < CHUNK_REFS_SIZE {
+                        return Err(ChunkError::Malformed.into());
+                    }
+                    let data = &chunk[CHUNK_REFS_SIZE..];
+
+                    let got = Hash::digest_bytes(data);
+                    if got != hash {
+                        return Err(ChunkError::ChunkHashMismatch {
+                            expected: hash,
+                            got,
+                        }
+                        .into());
+                    }
+                    value.extend_from_slice(data);
+                }
+                if value.len() != size {
+                    return Err(ChunkError::Malformed.into());
+                }
+
+                Ok(value)
+            }
+            _ => Err(ChunkError::Malformed.into()),
+        }
+    }
+
+    fn acquire_chunk(&mut self, ctx: &Arc<Context>, hash: &Hash, data: &[u8]) -> Result<()> {
+        let key = chunk_key(hash);
+        let refs = match self.inner.get(Context::create_child(ctx), &key)? {
+            Some(chunk) => chunk_refs(&chunk),
+            None => 0,
+        };
+
+        let mut chunk = Vec::with_capacity(CHUNK_REFS_SIZE + data.len());
+        chunk.extend_from_slice(&(refs + 1).to_be_bytes());
+        chunk.extend_from_slice(data);
+        self.inner
+            .insert(Context::create_child(ctx), &key, &chunk)?;
+
+        Ok(())
+    }
+
+    fn release_chunk(&mut self, ctx: &Arc<Context>, hash: &Hash) -> Result<()> {
+        let key = chunk_key(hash);
+        let mut chunk = match self.inner.get(Context::create_child(ctx), &key)? {
+            Some(chunk) => chunk,
+            None => return Ok(()),
+        };
+
+        let refs = chunk_refs(&chunk);
+        if refs <= 1 {
+            self.inner.remove(Context::create_child(ctx), &key)?;
+        } else {
+            chunk[..CHUNK_REFS_SIZE].copy_from_slice(&(refs - 1).to_be_bytes());
+            self.inner
+                .insert(Context::create_child(ctx), &key, &chunk)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn decode_manifest(data: &[u8]) -> Result<Manifest> {
+    Ok(cbor::from_slice(data).map_err(|_| ChunkError::Malformed)?)
+}
+
+fn chunk_key(hash: &Hash) -> Vec<u8> {
+    let mut key = CHUNK_KEY_PREFIX.to_vec();
+    key.extend_from_slice(hash.as_ref());
+    key
+}
+
+fn chunk_refs(chunk: &[u8]) -> u64 {
+    if chunk.len() < CHUNK_REFS_SIZE {
+        return 0;
+    }
+    let mut refs = [0u8; CHUNK_REFS_SIZE];
+    refs.copy_from_slice(&chunk[..CHUNK_REFS_SIZE]);
+    u64::from_be_bytes(refs)
+}
+
+impl<T: MKVS> MKVS for ChunkedTree<T> {
+    fn get(&self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let ctx = ctx.freeze();
+        match self.inner.get(Context::create_child(&ctx), key)? {
+            Some(raw) => Ok(Some(self.decode_value(&ctx, &raw)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        if self.chunk_size == 0 {
+            return Err(ChunkError::ZeroChunkSize.into());
+        }
+        if value.len() > self.max_value_size {
+            return Err(ChunkError::ValueTooLarge {
+                size: value.len(),
+                max: self.max_value_size,
+            }
+            .into());
+        }
+
+        let ctx = ctx.freeze();
+        let previous = self.remove(Context::create_child(&ctx), key)?;
+
+        let raw = if value.len() <= self.chunk_size {
+            let mut raw = Vec::with_capacity(1 + value.len());
+            raw.push(VALUE_INLINE);
+            raw.extend_from_slice(value);
+            raw
+        } else {
+            let mut manifest = Manifest {
+                size: value.len() as u64,
+                chunks: Vec::new(),
+            };
+            for chunk in value.chunks(self.chunk_size) {
+                let hash = Hash::digest_bytes(chunk);
+                self.acquire_chunk(&ctx, &hash, chunk)?;
+                manifest.chunks.push(hash);
+            }
+
+            let mut raw = vec![VALUE_CHUNKED];
+            raw.extend_from_slice(&cbor::to_vec(&manifest));
+            raw
+        };
+        self.inner.insert(Context::create_child(&ctx), key, &raw)?;
+
+        Ok(previous)
+    }
+
+    fn remove(&mut self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let ctx = ctx.freeze();
+        let raw = match self.inner.get(Context::create_child(&ctx), key)? {
+            Some(raw) => raw,
+            None => return Ok(None),
+        };
+        // Decode the value before removing it, so that a malformed value is
+        // left untouched.
+        let value = self.decode_value(&ctx, &raw)?;
+        self.inner.remove(Context::create_child(&ctx), key)?;
+
+        if raw[0] == VALUE_CHUNKED {
+            // The manifest has already been validated when decoding the value.
+            let manifest = decode_manifest(&raw[1..])?;
+            for hash in manifest.chunks {
+                self.release_chunk(&ctx, &hash)?;
+            }
+        }
+
+        Ok(Some(value))
+    }
+
+    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16) -> Result<()> {
+        self.inner.prefetch_prefixes(ctx, prefixes, limit)
+    }
+
+    fn commit(
+        &mut self,
+        ctx: Context,
+        namespace: Namespace,
+        version: u64,
+    ) -> Result<(WriteLog, Hash)> {
+        self.inner.commit(ctx, namespace, version)
+    }
+
+    fn rollback(&mut self) {
+        self.inner.rollback()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use io_context::Context;
+
+    use super::*;
+    use crate::storage::mkvs::{sync::NoopReadSyncer, OverlayTree, Tree};
+
+    #[test]
+    fn test_chunked_values() {
+        let tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+        let mut tree = ChunkedTree::new(tree)
+            .with_chunk_size(4)
+            .with_max_value_size(64);
+
+        // Small values are stored inline.
+        assert_eq!(
+            tree.insert(Context::background(), b"small", b"abc")
+                .expect("insert"),
+            None
+        );
+        assert_eq!(
+            tree.get(Context::background(), b"small").expect("get"),
+            Some(b"abc".to_vec())
+        );
+
+        // Large values are chunked and identical chunks are shared.
+        let large = b"aaaabbbbaaaabbbbcc".to_vec();
+        assert_eq!(
+            tree.insert(Context::background(), b"large", &large)
+                .expect("insert"),
+            None
+        );
+        assert_eq!(
+            tree.get(Context::background(), b"large").expect("get"),
+            Some(large.clone())
+        );
+        assert_eq!(
+            MKVS::get(
+                tree.inner(),
+                Context::background(),
+                &chunk_key(&Hash::digest_bytes(b"aaaa"))
+            )
+            .expect("get")
+            .map(|chunk| chunk_refs(&chunk)),
+            Some(2)
+        );
+
+        let other = b"aaaadddd".to_vec();
+        tree.insert(Context::background(), b"other", &other)
+            .expect("insert");
+
+        // Overwriting and removing should release chunks.
+        assert_eq!(
+            tree.insert(Context::background(), b"large", b"small")
+                .expect("insert"),
+            Some(large.clone())
+        );
+        assert_eq!(
+            MKVS::get(
+                tree.inner(),
+                Context::background(),
+                &chunk_key(&Hash::digest_bytes(b"bbbb"))
+            )
+            .expect("get"),
+            None
+        );
+        assert_eq!(
+            tree.remove(Context::background(), b"other")
+                .expect("remove"),
+            Some(other)
+        );
+        assert_eq!(
+            MKVS::get(
+                tree.inner(),
+                Context::background(),
+                &chunk_key(&Hash::digest_bytes(b"aaaa"))
+            )
+            .expect("get"),
+            None
+        );
+
+        // Values above the maximum size should be rejected.
+        assert_chunk_error(
+            tree.insert(Context::background(), b"huge", &[0u8; 65]),
+            |err| match err {
+                ChunkError::ValueTooLarge { size: 65, max: 64 } => true,
+                _ => false,
+            },
+        );
+
+        // A zero chunk size should be rejected.
+        let tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+        let mut tree = ChunkedTree::new(tree).with_chunk_size(0);
+        assert_chunk_error(
+            tree.insert(Context::background(), b"key", b"value"),
+            |err| match err {
+                ChunkError::ZeroChunkSize => true,
+                _ => false,
+            },
+        );
+    }
+
+    #[test]
+    fn test_chunked_values_corruption() {
+        let tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+        let mut tree = ChunkedTree::new(OverlayTree::new(tree)).with_chunk_size(4);
+        tree.insert(Context::background(), b"large", b"aaaabbbb")
+            .expect("insert");
+
+        // Tamper with a chunk in the underlying tree.
+        let mut inner = tree.into_inner();
+        let key = chunk_key(&Hash::digest_bytes(b"bbbb"));
+        let mut chunk = inner
+            .get(Context::background(), &key)
+            .expect("get")
+            .unwrap();
+        chunk[CHUNK_REFS_SIZE] = b'x';
+        inner
+            .insert(Context::background(), &key, &chunk)
+            .expect("insert");
+        let tree = ChunkedTree::new(inner).with_chunk_size(4);
+
+        assert_chunk_error(tree.get(Context::background(), b"large"), |err| match err {
+            ChunkError::ChunkHashMismatch { .. } => true,
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn test_chunked_values_malformed() {
+        let manifest = |size, chunks| {
+            let mut raw = vec![VALUE_CHUNKED];
+            raw.extend_from_slice(&cbor::to_vec(&Manifest { size, chunks }));
+            raw
+        };
+
+        let tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+        let mut inner = OverlayTree::new(tree);
+        for (key, raw) in vec![
+            (&b"empty"[..], vec![]),
+            (&b"header"[..], vec![0x02]),
+            (&b"manifest"[..], vec![VALUE_CHUNKED, 0xff]),
+            (&b"size"[..], manifest(5, vec![])),
+            (&b"oversized"[..], manifest(1 << 40, vec![])),
+            (
+                &b"missing"[..],
+                manifest(4, vec![Hash::digest_bytes(b"zzzz")]),
+            ),
+        ] {
+            inner
+                .insert(Context::background(), key, &raw)
+                .expect("insert");
+        }
+        let mut tree = ChunkedTree::new(inner).with_chunk_size(4);
+
+        // Malformed values should be reported through the MKVS interface.
+        for key in vec![&b"empty"[..], b"header", b"manifest", b"size"] {
+            assert_chunk_error(tree.get(Context::background(), key), |err| match err {
+                ChunkError::Malformed => true,
+                _ => false,
+            });
+            assert_chunk_error(tree.remove(Context::background(), key), |err| match err {
+                ChunkError::Malformed => true,
+                _ => false,
+            });
+            assert_chunk_error(
+                tree.insert(Context::background(), key, b"value"),
+                |err| match err {
+                    ChunkError::Malformed => true,
+                    _ => false,
+                },
+            );
+        }
+        assert_chunk_error(
+            tree.get(Context::background(), b"oversized"),
+            |err| match err {
+                ChunkError::ValueTooLarge { .. } => true,
+                _ => false,
+            },
+        );
+        assert_chunk_error(
+            tree.get(Context::background(), b"missing"),
+            |err| match err {
+                ChunkError::MissingChunk { .. } => true,
+                _ => false,
+            },
+        );
+
+        // Malformed values should be left untouched.
+        assert_eq!(
+            tree.inner()
+                .get(Context::background(), b"header")
+                .expect("get"),
+            Some(vec![0x02])
+        );
+    }
+
+    fn assert_chunk_error<T: std::fmt::Debug>(
+        result: Result<T>,
+        matches: impl Fn(&ChunkError) -> bool,
+    ) {
+        match result {
+            Err(err) => match err.downcast_ref::<ChunkError>() {
+                Some(chunk_err) if matches(chunk_err) => {}
+                _ => panic!("unexpected error: {:?}", err),
+            },
+            Ok(value) => panic!("expected an error, got: {:?}", value),
+        }
+    }
+}
@@ -0,0 +1,482 @@
+//! In-memory MKVS backend.
+use std::{
+    any::Any,
+    cmp::{min, Ordering},
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use io_context::Context;
+use thiserror::Error;
+
+use crate::{
+    common::{crypto::hash::Hash, roothash::Namespace},
+    storage::mkvs::{cache::*, marshal::Marshal, sync::*, tree::*, Prefix, WriteLog, MKVS},
+};
+
+/// In-memory backend error.
+#[derive(Debug, Error)]
+pub enum MemoryError {
+    #[error("mkvs: node not found in memory store ({hash:?})")]
+    NodeNotFound { hash: Hash },
+}
+
+type NodeDB = HashMap<Hash, Vec<u8>>;
+
+/// An in-memory node store.
+///
+/// The store keeps all committed nodes and serves them to trees through a
+/// read syncer that generates proofs, so that trees backed by the store go
+/// through the same sync and verification code paths as trees backed by a
+/// remote storage node.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    nodes: Arc<Mutex<NodeDB>>,
+}
+
+impl MemoryStore {
+    /// Create a new empty in-memory store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the number of nodes in the store.
+    pub fn len(&self) -> usize {
+        self.nodes.lock().unwrap().len()
+    }
+
+    /// Return true if the store does not contain any nodes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return a read syncer that serves nodes from this store.
+    pub fn read_syncer(&self) -> MemoryReadSyncer {
+        MemoryReadSyncer {
+            nodes: self.nodes.clone(),
+        }
+    }
+
+    /// Open a tree at the given root, backed by this store.
+    pub fn open(&self, root: Root) -> MemoryTree {
+        MemoryTree {
+            store: self.clone(),
+            tree: Tree::make()
+                .with_root(root)
+                .new(Box::new(self.read_syncer())),
+        }
+    }
+
+    fn insert_subtree(&self, ptr: &NodePtrRef) -> Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        Self::_insert_subtree(&mut nodes, ptr)
+    }
+
+    fn _insert_subtree(nodes: &mut NodeDB, ptr: &NodePtrRef) -> Result<()> {
+        let ptr = ptr.borrow();
+        if ptr.is_null() || nodes.contains_key(&ptr.hash) {
+            return Ok(());
+        }
+        let node_ref = match ptr.node {
+            Some(ref node_ref) => node_ref.clone(),
+            // Nodes that are not loaded have been obtained from the store.
+            None => return Ok(()),
+        };
+
+        let node = node_ref.borrow();
+        nodes.insert(ptr.hash, node.marshal_binary()?);
+        if let NodeBox::Internal(ref n) = *node {
+            // NOTE: The leaf node is always stored with the internal node.
+            Self::_insert_subtree(nodes, &n.left)?;
+            Self::_insert_subtree(nodes, &n.right)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A read syncer that serves proofs from an in-memory store.
+pub struct MemoryReadSyncer {
+    nodes: Arc<Mutex<NodeDB>>,
+}
+
+impl MemoryReadSyncer {
+    fn resolve(nodes: &NodeDB, hash: &Hash) -> Result<Option<NodeBox>> {
+        if hash.is_empty() {
+            return Ok(None);
+        }
+        let raw = nodes
+            .get(hash)
+            .ok_or_else(|| MemoryError::NodeNotFound { hash: hash.clone() })?;
+
+        let mut node = NodeBox::default();
+        node.unmarshal_binary(raw)?;
+        Ok(Some(node))
+    }
+}
+
+/// Walks subtrees in key order and includes nodes which may contain
+/// matching keys into a proof.
+///
+/// Subtrees are filtered based on the path leading to them so that a verifier
+/// can tell which of the omitted subtrees could not contain matching keys.
+/// For the same reason, all visited leaf nodes are included.
+struct SubtreeWalker<'a> {
+    nodes: &'a NodeDB,
+    builder: ProofBuilder,
+    subtree_filter: &'a dyn Fn(&Key, Depth) -> bool,
+    leaf_filter: &'a dyn Fn(&Key) -> bool,
+    remaining: usize,
+}
+
+impl<'a> SubtreeWalker<'a> {
+    fn walk(mut self, root: &Hash) -> Result<Proof> {
+        self.visit(root, 0, Key::new())?;
+        Ok(self.builder.build())
+    }
+
+    fn visit(&mut self, hash: &Hash, bit_depth: Depth, path: Key) -> Result<()> {
+        if self.remaining == 0 || !(self.subtree_filter)(&path, bit_depth) {
+            return Ok(());
+        }
+        let node = match MemoryReadSyncer::resolve(self.nodes, hash)? {
+            Some(node) => node,
+            None => return Ok(()),
+        };
+        self.builder.include(&node);
+
+        match node {
+            NodeBox::Internal(ref n) => {
+                let bit_length = bit_depth + n.label_bit_length;
+                let new_path = path.merge(bit_depth, &n.label, n.label_bit_length);
+
+                let leaf_node = n.leaf_node.borrow();
+                if !leaf_node.is_null()
+                    && (self.leaf_filter)(&noderef_as!(leaf_node.get_node(), Leaf).key)
+                {
+                    self.remaining -= 1;
+                }
+
+                self.visit(
+                    &n.left.borrow().hash,
+                    bit_length,
+                    new_path.append_bit(bit_length, false),
+                )?;
+                self.visit(
+                    &n.right.borrow().hash,
+                    bit_length,
+                    new_path.append_bit(bit_length, true),
+                )?;
+            }
+            NodeBox::Leaf(ref n) => {
+                if (self.leaf_filter)(&n.key) {
+                    self.remaining -= 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn new_proof_builder(root: Hash, compressed: bool) -> ProofBuilder {
+    if compressed {
+        ProofBuilder::new_compressed(root)
+    } else {
+        ProofBuilder::new(root)
+    }
+}
+
+impl ReadSync for MemoryReadSyncer {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn sync_get(&mut self, _ctx: Context, request: GetRequest) -> Result<ProofResponse> {
+        let nodes = self.nodes.lock().unwrap();
+        let key = &request.key;
+        let mut builder = new_proof_builder(request.tree.root.hash, request.compressed);
+        let mut hash = request.tree.root.hash;
+        let mut bit_depth: Depth = 0;
+
+        while let Some(node) = Self::resolve(&nodes, &hash)? {
+            builder.include(&node);
+
+            let n = match node {
+                NodeBox::Internal(ref n) => n,
+                NodeBox::Leaf(..) => break,
+            };
+            let bit_length = bit_depth + n.label_bit_length;
+            if key.bit_length() <= bit_length {
+                // Key either ends here (and the leaf node is included with the
+                // internal node) or is too short to be stored.
+                break;
+            }
+
+            let (next, sibling) = if key.get_bit(bit_length) {
+                (&n.right, &n.left)
+            } else {
+                (&n.left, &n.right)
+            };
+            if request.include_siblings {
+                if let Some(sibling) = Self::resolve(&nodes, &sibling.borrow().hash)? {
+                    builder.include(&sibling);
+                }
+            }
+
+            hash = next.borrow().hash;
+            bit_depth = bit_length;
+        }
+
+        Ok(ProofResponse {
+            proof: builder.build(),
+        })
+    }
+
+    fn sync_get_prefixes(
+        &mut self,
+        _ctx: Context,
+        request: GetPrefixesRequest,
+    ) -> Result<ProofResponse> {
+        let nodes = self.nodes.lock().unwrap();
+        let prefixes: Vec<Key> = request.prefixes.into_iter().map(|p| p.into()).collect();
+        let walker = SubtreeWalker {
+            nodes: &nodes,
+            builder: new_proof_builder(request.tree.root.hash, request.compressed),
+            subtree_filter: &|path: &Key, bit_length: Depth| {
+                prefixes.iter().any(|prefix| {
+                    let bits = min(bit_length, prefix.bit_length());
+                    cmp_bits(path, prefix, bits) == Ordering::Equal
+                })
+            },
+            leaf_filter: &|key: &Key| prefixes.iter().any(|prefix| key.starts_with(prefix)),
+            remaining: match request.limit {
+                0 => usize::max_value(),
+                limit => limit as usize,
+            },
+        };
+
+        Ok(ProofResponse {
+            proof: walker.walk(&request.tree.root.hash)?,
+        })
+    }
+
+    fn sync_iterate(&mut self, _ctx: Context, request: IterateRequest) -> Result<ProofResponse> {
+        let nodes = self.nodes.lock().unwrap();
+        let key = request.key;
+        let walker = SubtreeWalker {
+            nodes: &nodes,
+            builder: new_proof_builder(request.tree.root.hash, request.compressed),
+            subtree_filter: &|path: &Key, bit_length: Depth| {
+                let bits = min(bit_length, key.bit_length());
+                cmp_bits(path, &key, bits) != Ordering::Less
+            },
+            leaf_filter: &|leaf_key: &Key| *leaf_key >= key,
+            // Always include at least the item the iterator is seeking to.
+            remaining: 1 + request.prefetch as usize,
+        };
+
+        Ok(ProofResponse {
+            proof: walker.walk(&request.tree.root.hash)?,
+        })
+    }
+}
+
+/// A tree backed by an in-memory store.
+///
+/// The tree exposes the same API as a tree backed by a storage node, but
+/// committed nodes are kept in an in-memory store. This makes it possible to
+/// run code that requires a MKVS without a host or a storage node.
+pub struct MemoryTree {
+    store: MemoryStore,
+    tree: Tree,
+}
+
+impl MemoryTree {
+    /// Create a new empty tree backed by a new in-memory store.
+    pub fn new() -> Self {
+        let store = MemoryStore::new();
+        let tree = Tree::make().new(Box::new(store.read_syncer()));
+
+        Self { store, tree }
+    }
+
+    /// Return the in-memory store backing this tree.
+    pub fn store(&self) -> &MemoryStore {
+        &self.store
+    }
+
+    /// Commit tree updates to the in-memory store and return the write
+    /// log and new merkle root.
+    pub fn commit(
+        &mut self,
+        ctx: Context,
+        namespace: Namespace,
+        version: u64,
+    ) -> Result<(WriteLog, Hash)> {
+        let result = Tree::commit(&mut self.tree, ctx, namespace, version)?;
+        let pending_root = self.tree.cache.borrow().get_pending_root();
+        self.store.insert_subtree(&pending_root)?;
+
+        Ok(result)
+    }
+}
+
+impl Deref for MemoryTree {
+    type Target = Tree;
+
+    fn deref(&self) -> &Tree {
+        &self.tree
+    }
+}
+
+impl DerefMut for MemoryTree {
+    fn deref_mut(&mut self) -> &mut Tree {
+        &mut self.tree
+    }
+}
+
+impl MKVS for MemoryTree {
+    fn get(&self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        MKVS::get(&self.tree, ctx, key)
+    }
+
+    fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        MKVS::insert(&mut self.tree, ctx, key, value)
+    }
+
+    fn remove(&mut self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        MKVS::remove(&mut self.tree, ctx, key)
+    }
+
+    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16) -> Result<()> {
+        MKVS::prefetch_prefixes(&self.tree, ctx, prefixes, limit)
+    }
+
+    fn commit(
+        &mut self,
+        ctx: Context,
+        namespace: Namespace,
+        version: u64,
+    ) -> Result<(WriteLog, Hash)> {
+        MemoryTree::commit(self, ctx, namespace, version)
+    }
+
+    fn rollback(&mut self) {
+        MKVS::rollback(&mut self.tree)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use io_context::Context;
+
+    use super::*;
+    use crate::storage::mkvs::sync::NoopReadSyncer;
+
+    fn root(hash: Hash, version: u64) -> Root {
+        Root {
+            hash,
+            version,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_memory_tree() {
+        let items: Vec<(Vec<u8>, Vec<u8>)> = (0..100)
+            .map(|i| {
+                (
+                    format!("key {}", i).into_bytes(),
+                    format!("value {}", i).into_bytes(),
+                )
+            })
+            .collect();
+
+        let mut tree = MemoryTree::new();
+        let mut reference = Tree::make().new(Box::new(NoopReadSyncer {}));
+        for (key, value) in &items {
+            MKVS::insert(&mut tree, Context::background(), key, value).expect("insert");
+            MKVS::insert(&mut reference, Context::background(), key, value).expect("insert");
+        }
+        let (write_log, hash) =
+            MKVS::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+        let (expected_write_log, expected_hash) =
+            Tree::commit(&mut reference, Context::background(), Default::default(), 0)
+                .expect("commit");
+        assert_eq!(write_log, expected_write_log);
+        assert_eq!(hash, expected_hash);
+        assert!(!tree.store().is_empty());
+
+        // Reopen the committed root with an empty cache, forcing all reads
+        // to go through proofs generated by the store.
+        let store = tree.store().clone();
+        let remote = store.open(root(hash, 0));
+        for (key, value) in &items {
+            assert_eq!(
+                MKVS::get(&remote, Context::background(), key).expect("get"),
+                Some(value.clone())
+            );
+        }
+        assert_eq!(
+            MKVS::get(&remote, Context::background(), b"missing").expect("get"),
+            None
+        );
+
+        // Iteration.
+        let remote = store.open(root(hash, 0));
+        let mut it = remote.iter(Context::background());
+        it.seek(b"key 5");
+        let mut expected: Vec<Vec<u8>> = items
+            .iter()
+            .map(|(key, _)| key.clone())
+            .filter(|key| key.as_slice() >= &b"key 5"[..])
+            .collect();
+        expected.sort();
+        let keys: Vec<Vec<u8>> = it.map(|(key, _)| key).collect();
+        assert_eq!(keys, expected);
+
+        // Prefetching.
+        let remote = store.open(root(hash, 0));
+        MKVS::prefetch_prefixes(
+            &remote,
+            Context::background(),
+            &vec![b"key 1".to_vec().into()],
+            1000,
+        )
+        .expect("prefetch_prefixes");
+        let stats = remote.cache_stats();
+        assert!(stats.internal_node_count > 0);
+
+        // Updates on top of a reopened root.
+        let mut remote = store.open(root(hash, 0));
+        MKVS::remove(&mut remote, Context::background(), b"key 1").expect("remove");
+        MKVS::insert(&mut remote, Context::background(), b"key 2", b"updated").expect("insert");
+        let (_, new_hash) = MKVS::commit(&mut remote, Context::background(), Default::default(), 1)
+            .expect("commit");
+        MKVS::remove(&mut reference, Context::background(), b"key 1").expect("remove");
+        MKVS::insert(&mut reference, Context::background(), b"key 2", b"updated").expect("insert");
+        let (_, expected_hash) =
+            Tree::commit(&mut reference, Context::background(), Default::default(), 1)
+                .expect("commit");
+        assert_eq!(new_hash, expected_hash);
+
+        // Old and new roots should both remain available.
+        let old = store.open(root(hash, 0));
+        assert_eq!(
+            MKVS::get(&old, Context::background(), b"key 1").expect("get"),
+            Some(b"value 1".to_vec())
+        );
+        let new = store.open(root(new_hash, 1));
+        assert_eq!(
+            MKVS::get(&new, Context::background(), b"key 1").expect("get"),
+            None
+        );
+        assert_eq!(
+            MKVS::get(&new, Context::background(), b"key 2").expect("get"),
+            Some(b"updated".to_vec())
+        );
+    }
+}
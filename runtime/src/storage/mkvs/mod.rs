@@ -1,8 +1,8 @@
 //! Merklized key-value store.
 use std::ops::{Deref, DerefMut};
 
+use anyhow::Result;
 use base64;
-use failure::Fallible;
 use io_context::Context;
 use serde::{self, ser::SerializeSeq, Serializer};
 use serde_bytes::Bytes;
@@ -13,14 +13,26 @@ use crate::common::{crypto::hash::Hash, roothash::Namespace};
 #[macro_use]
 mod tree;
 mod cache;
+pub mod checkpoint;
+mod chunked;
 #[cfg(test)]
 mod interop;
 pub mod marshal;
+mod memory;
+mod overlay;
 pub mod sync;
 #[cfg(test)]
 mod tests;
+mod write_log;
 
+pub use cache::{CacheMetrics, CacheStats, EvictionPolicy};
+pub use chunked::{
+    ChunkError, ChunkedTree, CHUNK_KEY_PREFIX, DEFAULT_CHUNK_SIZE, DEFAULT_MAX_VALUE_SIZE,
+};
+pub use memory::{MemoryError, MemoryReadSyncer, MemoryStore, MemoryTree};
+pub use overlay::OverlayTree;
 pub use tree::{Depth, Key, NodeBox, Root, Tree};
+pub use write_log::{dedup_write_log, CompressedLogEntry, CompressedWriteLog, WriteLogError};
 
 /// The type of entry in the log.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -76,7 +88,8 @@ impl serde::Serialize for LogEntry {
 
 /// The write log.
 ///
-/// The keys in the write log must be unique.
+/// The keys in the write log must be unique. Write logs produced by a commit
+/// are sorted by key so that their encoding is deterministic.
 pub type WriteLog = Vec<LogEntry>;
 
 /// A key prefix.
@@ -115,10 +128,27 @@ impl From<Vec<u8>> for Prefix {
     }
 }
 
+/// A pruning hint for keys under a given prefix.
+///
+/// Runtimes emit pruning hints for ephemeral data. Storage nodes retain
+/// historical versions for at least the given number of rounds and prune them
+/// afterwards according to their pruning configuration. The latest version is
+/// never pruned.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PruneHint {
+    /// Prefix of the prunable keys.
+    pub prefix: Prefix,
+    /// Number of rounds for which historical versions must be retained.
+    pub retain_rounds: u64,
+}
+
 /// Merklized key-value store.
+///
+/// All operations may need to fetch nodes from remote storage and can thus fail, e.g., when the
+/// fetched nodes are invalid or do not fit into the memory limits of the tree cache.
 pub trait MKVS: Send + Sync {
     /// Fetch entry with given key.
-    fn get(&self, ctx: Context, key: &[u8]) -> Option<Vec<u8>>;
+    fn get(&self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>>;
 
     /// Update entry with given key.
     ///
@@ -128,14 +158,14 @@ pub trait MKVS: Send + Sync {
     /// returned.
     ///
     /// [`None`]: std::option::Option
-    fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Option<Vec<u8>>;
+    fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>>;
 
     /// Remove entry with given key, returning the value at the key if the key was previously
     /// in the database.
-    fn remove(&mut self, ctx: Context, key: &[u8]) -> Option<Vec<u8>>;
+    fn remove(&mut self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>>;
 
     /// Populate the in-memory tree with nodes for keys starting with given prefixes.
-    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16);
+    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16) -> Result<()>;
 
     /// Commit all database changes to the underlying store.
     fn commit(
@@ -143,12 +173,43 @@ pub trait MKVS: Send + Sync {
         ctx: Context,
         namespace: Namespace,
         version: u64,
-    ) -> Fallible<(WriteLog, Hash)>;
+    ) -> Result<(WriteLog, Hash)>;
 
     /// Rollback any pending changes.
     fn rollback(&mut self);
 }
 
+impl<'a, T: ?Sized + MKVS> MKVS for &'a mut T {
+    fn get(&self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        MKVS::get(&**self, ctx, key)
+    }
+
+    fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        MKVS::insert(&mut **self, ctx, key, value)
+    }
+
+    fn remove(&mut self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        MKVS::remove(&mut **self, ctx, key)
+    }
+
+    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16) -> Result<()> {
+        MKVS::prefetch_prefixes(&**self, ctx, prefixes, limit)
+    }
+
+    fn commit(
+        &mut self,
+        ctx: Context,
+        namespace: Namespace,
+        version: u64,
+    ) -> Result<(WriteLog, Hash)> {
+        MKVS::commit(&mut **self, ctx, namespace, version)
+    }
+
+    fn rollback(&mut self) {
+        MKVS::rollback(&mut **self)
+    }
+}
+
 #[cfg(test)]
 mod _tests {
     use super::*;
@@ -167,4 +228,93 @@ mod _tests {
 
         assert_eq!(write_log, deserialized);
     }
+
+    #[test]
+    fn test_write_log_deterministic_encoding() {
+        let entries = vec![
+            (b"foo".to_vec(), b"bar".to_vec()),
+            (b"moo".to_vec(), b"boo".to_vec()),
+            (b"a key".to_vec(), b"a value".to_vec()),
+        ];
+
+        // Insertion order must not affect the write log or its encoding.
+        let mut encodings = Vec::new();
+        for order in &[[0, 1, 2], [2, 0, 1], [1, 2, 0]] {
+            let mut tree = Tree::make().new(Box::new(sync::NoopReadSyncer {}));
+            for &i in order.iter() {
+                let (ref key, ref value) = entries[i];
+                tree.insert(Context::background(), key, value)
+                    .expect("insert");
+            }
+            let (write_log, _) =
+                Tree::commit(&mut tree, Context::background(), Default::default(), 0)
+                    .expect("commit");
+            encodings.push(cbor::to_vec(&write_log));
+        }
+        assert_eq!(encodings[0], encodings[1]);
+        assert_eq!(encodings[0], encodings[2]);
+
+        // Round trip must be byte-for-byte identical.
+        assert!(cbor::is_canonical(&encodings[0]));
+        let decoded: WriteLog = cbor::from_slice_canonical(&encodings[0]).expect("decode");
+        assert_eq!(cbor::to_vec(&decoded), encodings[0]);
+    }
+
+    #[test]
+    fn test_write_log_dedup() {
+        let write_log = vec![
+            LogEntry::new(b"foo", b"bar"),
+            LogEntry::new(b"moo", b"boo"),
+            LogEntry::new(b"foo", b"baz"),
+            LogEntry {
+                key: b"moo".to_vec(),
+                value: None,
+            },
+        ];
+
+        let deduped = dedup_write_log(write_log);
+        assert_eq!(
+            deduped,
+            vec![
+                LogEntry::new(b"foo", b"baz"),
+                LogEntry {
+                    key: b"moo".to_vec(),
+                    value: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_log_compression() {
+        let write_log = vec![
+            LogEntry::new(b"key 1", b"same value"),
+            LogEntry::new(b"key 2", b"same value"),
+            LogEntry::new(b"key 3", b"other value"),
+            LogEntry {
+                key: b"another key".to_vec(),
+                value: None,
+            },
+        ];
+
+        let compressed = CompressedWriteLog::compress(write_log.clone());
+        assert_eq!(compressed.values.len(), 2, "values should be deduplicated");
+        assert_eq!(compressed.entries[2].shared, 4, "keys should share prefix");
+
+        let raw = cbor::to_vec(&compressed);
+        let compressed: CompressedWriteLog = cbor::from_slice(&raw).unwrap();
+        let decompressed = compressed.decompress().expect("decompress");
+        assert_eq!(decompressed, dedup_write_log(write_log));
+
+        // Malformed compressed write logs should be rejected.
+        let malformed = CompressedWriteLog {
+            values: vec![],
+            entries: vec![CompressedLogEntry {
+                shared: 0,
+                suffix: b"foo".to_vec(),
+                value: Some(0),
+            }],
+        };
+        assert!(malformed.decompress().is_err());
+    }
 }
@@ -0,0 +1,260 @@
+//! MKVS overlay.
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use io_context::Context;
+
+use crate::{
+    common::{crypto::hash::Hash, roothash::Namespace},
+    storage::mkvs::{Prefix, WriteLog, MKVS},
+};
+
+/// A key-value tree overlay that holds all updates in memory and only
+/// applies them to the inner tree if requested.
+///
+/// This can be used to create snapshots that can be cheaply discarded, e.g.,
+/// for speculative execution or for checking transactions against pending
+/// state. Overlays can be stacked as the overlay itself implements MKVS.
+///
+/// While updates (inserts, removes) are stored in the overlay, reads are not
+/// cached in the overlay as the inner tree has its own cache.
+pub struct OverlayTree<T: MKVS> {
+    inner: T,
+    dirty: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl<T: MKVS> OverlayTree<T> {
+    /// Create a new overlay over the given inner tree.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            dirty: BTreeMap::new(),
+        }
+    }
+
+    /// Return a reference to the inner tree.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Return true if the overlay holds any pending updates.
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty.is_empty()
+    }
+
+    /// Stage all updates from the given write log in the overlay.
+    ///
+    /// The updates take precedence over any previously staged updates for the
+    /// same keys.
+    pub fn stage_write_log(&mut self, write_log: &WriteLog) {
+        for entry in write_log {
+            self.dirty.insert(entry.key.clone(), entry.value.clone());
+        }
+    }
+
+    /// Apply all pending updates to the inner tree.
+    ///
+    /// Note that this does not commit the inner tree. On failure, the
+    /// updates which were not yet applied are kept in the overlay.
+    pub fn apply(&mut self, ctx: Context) -> Result<()> {
+        let ctx = ctx.freeze();
+        while let Some(key) = self.dirty.keys().next().cloned() {
+            match self.dirty[&key] {
+                Some(ref value) => {
+                    self.inner
+                        .insert(Context::create_child(&ctx), &key, value)?;
+                }
+                None => {
+                    self.inner.remove(Context::create_child(&ctx), &key)?;
+                }
+            }
+            self.dirty.remove(&key);
+        }
+        Ok(())
+    }
+
+    /// Discard all pending updates.
+    pub fn discard(&mut self) {
+        self.dirty.clear();
+    }
+
+    /// Discard all pending updates and return the inner tree.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: MKVS> MKVS for OverlayTree<T> {
+    fn get(&self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        // For dirty values, check the overlay.
+        if let Some(value) = self.dirty.get(key) {
+            return Ok(value.clone());
+        }
+
+        // Otherwise fetch from inner tree.
+        self.inner.get(ctx, key)
+    }
+
+    fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        let previous = self.get(ctx, key)?;
+        self.dirty.insert(key.to_vec(), Some(value.to_vec()));
+        Ok(previous)
+    }
+
+    fn remove(&mut self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let previous = self.get(ctx, key)?;
+
+        // Do not treat a value as dirty if it was not dirty before and did not
+        // exist in the inner tree.
+        if previous.is_some() || self.dirty.contains_key(key) {
+            self.dirty.insert(key.to_vec(), None);
+        }
+        Ok(previous)
+    }
+
+    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16) -> Result<()> {
+        self.inner.prefetch_prefixes(ctx, prefixes, limit)
+    }
+
+    fn commit(
+        &mut self,
+        ctx: Context,
+        namespace: Namespace,
+        version: u64,
+    ) -> Result<(WriteLog, Hash)> {
+        let ctx = ctx.freeze();
+        self.apply(Context::create_child(&ctx))?;
+        self.inner
+            .commit(Context::create_child(&ctx), namespace, version)
+    }
+
+    fn rollback(&mut self) {
+        // Only discard the overlay, the inner tree is left untouched.
+        self.discard();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use io_context::Context;
+
+    use super::*;
+    use crate::storage::mkvs::{sync::NoopReadSyncer, LogEntry, Tree};
+
+    #[test]
+    fn test_overlay() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+        MKVS::insert(&mut tree, Context::background(), b"foo", b"bar").expect("insert");
+        MKVS::insert(&mut tree, Context::background(), b"moo", b"boo").expect("insert");
+        let (_, root_hash) =
+            MKVS::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+
+        {
+            let mut overlay = OverlayTree::new(&mut tree);
+
+            // Updates should be visible through the overlay.
+            assert_eq!(
+                overlay
+                    .insert(Context::background(), b"foo", b"baz")
+                    .expect("insert"),
+                Some(b"bar".to_vec())
+            );
+            assert_eq!(
+                overlay
+                    .remove(Context::background(), b"moo")
+                    .expect("remove"),
+                Some(b"boo".to_vec())
+            );
+            assert_eq!(
+                overlay
+                    .remove(Context::background(), b"missing")
+                    .expect("remove"),
+                None
+            );
+            overlay
+                .insert(Context::background(), b"new", b"value")
+                .expect("insert");
+            assert_eq!(
+                overlay.get(Context::background(), b"foo").expect("get"),
+                Some(b"baz".to_vec())
+            );
+            assert_eq!(
+                overlay.get(Context::background(), b"moo").expect("get"),
+                None
+            );
+
+            // But not in the inner tree.
+            assert_eq!(
+                MKVS::get(overlay.inner(), Context::background(), b"foo").expect("get"),
+                Some(b"bar".to_vec())
+            );
+
+            // Discarding the overlay should leave the inner tree unchanged.
+            overlay.discard();
+            assert!(!overlay.is_dirty());
+            assert_eq!(
+                overlay.get(Context::background(), b"foo").expect("get"),
+                Some(b"bar".to_vec())
+            );
+        }
+        let (write_log, hash) =
+            MKVS::commit(&mut tree, Context::background(), Default::default(), 1).expect("commit");
+        assert!(write_log.is_empty());
+        assert_eq!(hash, root_hash);
+
+        // Committing the overlay should apply the updates.
+        let mut overlay = OverlayTree::new(&mut tree);
+        overlay
+            .insert(Context::background(), b"foo", b"baz")
+            .expect("insert");
+        overlay
+            .remove(Context::background(), b"moo")
+            .expect("remove");
+        let (write_log, hash) = overlay
+            .commit(Context::background(), Default::default(), 2)
+            .expect("commit");
+        assert_eq!(write_log.len(), 2);
+        assert_ne!(hash, root_hash);
+        assert_eq!(
+            tree.get(Context::background(), b"foo").expect("get"),
+            Some(b"baz".to_vec())
+        );
+        assert_eq!(tree.get(Context::background(), b"moo").expect("get"), None);
+    }
+
+    #[test]
+    fn test_overlay_stage_write_log() {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+        MKVS::insert(&mut tree, Context::background(), b"foo", b"bar").expect("insert");
+        MKVS::insert(&mut tree, Context::background(), b"moo", b"boo").expect("insert");
+        MKVS::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+
+        let mut overlay = OverlayTree::new(&mut tree);
+        overlay.stage_write_log(&vec![
+            LogEntry::new(b"foo", b"baz"),
+            LogEntry {
+                key: b"moo".to_vec(),
+                value: None,
+            },
+        ]);
+        overlay.stage_write_log(&vec![LogEntry::new(b"foo", b"qux")]);
+
+        // Later write logs should take precedence.
+        assert_eq!(
+            overlay.get(Context::background(), b"foo").expect("get"),
+            Some(b"qux".to_vec())
+        );
+        assert_eq!(
+            overlay.get(Context::background(), b"moo").expect("get"),
+            None
+        );
+        assert!(overlay.is_dirty());
+
+        // The inner tree should be left untouched.
+        overlay.discard();
+        assert_eq!(
+            overlay.get(Context::background(), b"moo").expect("get"),
+            Some(b"boo".to_vec())
+        );
+    }
+}
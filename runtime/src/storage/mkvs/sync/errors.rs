@@ -1,7 +1,31 @@
-use failure::Fail;
+use thiserror::Error;
 
-#[derive(Debug, Fail)]
+use crate::common::crypto::hash::Hash;
+
+#[derive(Debug, Error)]
 pub enum SyncerError {
-    #[fail(display = "mkvs: method not supported")]
+    #[error("mkvs: method not supported")]
     Unsupported,
 }
+
+/// Error returned when data obtained from an untrusted read syncer fails
+/// to verify against the trusted root.
+#[derive(Debug, Error)]
+pub enum IntegrityError {
+    #[error("verifier: got proof for unexpected root (expected: {expected:?} got {got:?})")]
+    UnexpectedRoot { expected: Hash, got: Hash },
+    #[error("verifier: empty proof")]
+    EmptyProof,
+    #[error("verifier: malformed proof")]
+    MalformedProof,
+    #[error("verifier: malformed hash entry")]
+    MalformedHashEntry,
+    #[error("verifier: unexpected entry in proof ({entry_type:?})")]
+    UnexpectedEntry { entry_type: u8 },
+    #[error("verifier: bad root (expected: {expected:?} got {got:?})")]
+    BadRoot { expected: Hash, got: Hash },
+    #[error("merger: hash mismatch during merge (expected: {expected:?} got: {got:?})")]
+    MergeHashMismatch { expected: Hash, got: Hash },
+    #[error("verifier: proof does not cover the requested key")]
+    IncompleteProof,
+}
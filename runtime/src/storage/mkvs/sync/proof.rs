@@ -1,20 +1,26 @@
-use std::ops::{Deref, DerefMut};
+use std::{
+    cmp::{min, Ordering},
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+};
 
+use anyhow::Result;
 use arbitrary::Arbitrary;
-use failure::{format_err, Fallible};
 use io_context::Context;
 use serde_bytes;
 use serde_derive::{Deserialize, Serialize};
 
 use crate::{
     common::crypto::hash::Hash,
-    storage::mkvs::{marshal::Marshal, tree::*},
+    storage::mkvs::{marshal::Marshal, sync::IntegrityError, tree::*},
 };
 
 /// Proof entry type for full nodes.
-const PROOF_ENTRY_FULL: u8 = 0x01;
+pub(crate) const PROOF_ENTRY_FULL: u8 = 0x01;
 /// Proof entry type for subtree hashes.
-const PROOF_ENTRY_HASH: u8 = 0x02;
+pub(crate) const PROOF_ENTRY_HASH: u8 = 0x02;
+/// Proof entry type for full nodes using prefix compression.
+pub(crate) const PROOF_ENTRY_COMPRESSED: u8 = 0x03;
 
 /// A raw proof entry.
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, Arbitrary)]
@@ -63,66 +69,423 @@ pub struct Proof {
     pub entries: Vec<Option<RawProofEntry>>,
 }
 
+struct ProofNode {
+    serialized: Vec<u8>,
+    children: Vec<Hash>,
+    label_bit_length: Depth,
+    /// Copy of the leaf node when building a compressed proof.
+    leaf: Option<NodeBox>,
+}
+
+/// A Merkle proof builder.
+pub struct ProofBuilder {
+    root: Hash,
+    included: HashMap<Hash, ProofNode>,
+    size: u64,
+    compressed: bool,
+}
+
+impl ProofBuilder {
+    /// Create a new Merkle proof builder for the given root.
+    pub fn new(root: Hash) -> Self {
+        Self {
+            root,
+            included: HashMap::new(),
+            size: 0,
+            compressed: false,
+        }
+    }
+
+    /// Create a new Merkle proof builder for the given root which encodes
+    /// nodes using prefix compression.
+    ///
+    /// Compressed proofs are smaller when keys share long prefixes, but can
+    /// only be verified by verifiers that support compressed entries. The
+    /// root must be the root of the tree as node paths are reconstructed
+    /// starting from it.
+    pub fn new_compressed(root: Hash) -> Self {
+        Self {
+            compressed: true,
+            ..Self::new(root)
+        }
+    }
+
+    /// Add a node to the set of included nodes.
+    ///
+    /// The node must be clean.
+    pub fn include(&mut self, node: &NodeBox) {
+        if !node.is_clean() {
+            panic!("proof: attempted to add a dirty node");
+        }
+
+        // If node is already included, skip it.
+        let hash = node.get_hash();
+        if self.included.contains_key(&hash) {
+            return;
+        }
+
+        // Node is available, serialize it. Compressed leaf nodes are serialized
+        // again when building the proof as their encoding depends on the depth.
+        let serialized = match node {
+            NodeBox::Internal(..) if self.compressed => node.marshal_binary_compressed(0),
+            _ => node.marshal_binary(),
+        }
+        .expect("marshalling a clean node must succeed");
+        let leaf = match node {
+            NodeBox::Leaf(ref n) if self.compressed => Some(NodeBox::Leaf(n.copy())),
+            _ => None,
+        };
+
+        // For internal nodes, also add any children.
+        let (children, label_bit_length) = match node {
+            // NOTE: The leaf node is always included with the internal node.
+            NodeBox::Internal(ref n) => (
+                vec![n.left.borrow().hash, n.right.borrow().hash],
+                n.label_bit_length,
+            ),
+            NodeBox::Leaf(..) => (vec![], 0),
+        };
+
+        self.size += 1 + serialized.len() as u64;
+        self.included.insert(
+            hash,
+            ProofNode {
+                serialized,
+                children,
+                label_bit_length,
+                leaf,
+            },
+        );
+    }
+
+    /// Return true if the root node has already been included.
+    pub fn has_root(&self) -> bool {
+        self.included.contains_key(&self.root)
+    }
+
+    /// Return the root hash for this proof.
+    pub fn get_root(&self) -> Hash {
+        self.root
+    }
+
+    /// Return the current size of this proof.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Build the proof.
+    pub fn build(&self) -> Proof {
+        let mut proof = Proof {
+            untrusted_root: self.root,
+            entries: vec![],
+        };
+        self._build(&mut proof, &self.root, 0);
+        proof
+    }
+
+    fn _build(&self, proof: &mut Proof, hash: &Hash, bit_depth: Depth) {
+        if hash.is_empty() {
+            // Append nil for empty nodes.
+            proof.entries.push(None);
+            return;
+        }
+
+        match self.included.get(hash) {
+            None => {
+                // Node is not included in this proof, just add hash of subtree.
+                let mut entry = vec![PROOF_ENTRY_HASH];
+                entry.extend_from_slice(hash.as_ref());
+                proof.entries.push(Some(entry.into()));
+            }
+            Some(node) => {
+                // Pre-order traversal, add visited node.
+                let mut entry = if self.compressed {
+                    vec![PROOF_ENTRY_COMPRESSED]
+                } else {
+                    vec![PROOF_ENTRY_FULL]
+                };
+                match node.leaf {
+                    Some(ref leaf) => entry.extend_from_slice(
+                        &leaf
+                            .marshal_binary_compressed(bit_depth)
+                            .expect("marshalling a clean node must succeed"),
+                    ),
+                    None => entry.extend_from_slice(&node.serialized),
+                }
+                proof.entries.push(Some(entry.into()));
+
+                // And then add any children.
+                for child in &node.children {
+                    self._build(proof, child, bit_depth + node.label_bit_length);
+                }
+            }
+        }
+    }
+}
+
 /// A proof verifier enables verifying proofs returned by the ReadSyncer API.
 pub struct ProofVerifier;
 
 impl ProofVerifier {
     /// Verify a proof and generate an in-memory subtree representing the
     /// nodes which are included in the proof.
-    pub fn verify_proof(&self, _ctx: Context, root: Hash, proof: &Proof) -> Fallible<NodePtrRef> {
+    pub fn verify_proof(&self, ctx: Context, root: Hash, proof: &Proof) -> Result<NodePtrRef> {
+        self.verify_proof_at(ctx, root, proof, &Key::new(), 0)
+    }
+
+    /// Verify a proof for a subtree whose root node is located at the given
+    /// path and bit depth and generate an in-memory subtree representing the
+    /// nodes which are included in the proof.
+    ///
+    /// The position is only used to decode prefix-compressed nodes. A wrong
+    /// position results in wrong leaf keys and thus fails verification.
+    pub fn verify_proof_at(
+        &self,
+        _ctx: Context,
+        root: Hash,
+        proof: &Proof,
+        path: &Key,
+        bit_depth: Depth,
+    ) -> Result<NodePtrRef> {
         // Sanity check that the proof is for the correct root (as otherwise it
         // makes no sense to verify the proof).
         if proof.untrusted_root != root {
-            return Err(format_err!(
-                "verifier: got proof for unexpected root (expected: {:?} got {:?})",
-                root,
-                proof.untrusted_root,
-            ));
+            return Err(IntegrityError::UnexpectedRoot {
+                expected: root,
+                got: proof.untrusted_root,
+            }
+            .into());
         }
         if proof.entries.is_empty() {
-            return Err(format_err!("verifier: empty proof"));
+            return Err(IntegrityError::EmptyProof.into());
         }
 
-        let (_, root_node) = self._verify_proof(proof, 0)?;
+        let (_, root_node) = self._verify_proof(proof, 0, bit_depth, path)?;
         let root_hash = root_node.borrow().hash;
         if root_hash != root {
-            return Err(format_err!(
-                "verifier: bad root (expected: {:?} got {:?})",
-                root,
-                root_hash,
-            ));
+            return Err(IntegrityError::BadRoot {
+                expected: root,
+                got: root_hash,
+            }
+            .into());
         }
 
         Ok(root_node)
     }
 
-    fn _verify_proof(&self, proof: &Proof, idx: usize) -> Fallible<(usize, NodePtrRef)> {
+    /// Verify a proof for a single key and return the value of the key.
+    ///
+    /// A `None` value means that the proof shows that the key does not exist
+    /// under the given root. If the proof does not contain enough nodes to
+    /// determine the presence of the key, verification fails.
+    pub fn verify_proof_for_key(
+        &self,
+        ctx: Context,
+        root: Hash,
+        key: &[u8],
+        proof: &Proof,
+    ) -> Result<Option<Vec<u8>>> {
+        let root_ptr = self.verify_proof(ctx, root, proof)?;
+        let key = key.to_vec();
+
+        let mut ptr = root_ptr;
+        let mut bit_depth: Depth = 0;
+        loop {
+            let next = {
+                let p = ptr.borrow();
+                if p.is_null() {
+                    return Ok(None);
+                }
+                if !p.has_node() {
+                    return Err(IntegrityError::IncompleteProof.into());
+                }
+
+                let node_ref = p.get_node();
+                let node = node_ref.borrow();
+                match *node {
+                    NodeBox::Internal(ref n) => {
+                        let bit_length = bit_depth + n.label_bit_length;
+                        if key.bit_length() == bit_length {
+                            // Lookup key ends here, look into the leaf node.
+                            n.leaf_node.clone()
+                        } else if key.bit_length() < bit_length {
+                            // Lookup key is too short for the current label.
+                            return Ok(None);
+                        } else {
+                            bit_depth = bit_length;
+                            if key.get_bit(bit_length) {
+                                n.right.clone()
+                            } else {
+                                n.left.clone()
+                            }
+                        }
+                    }
+                    NodeBox::Leaf(ref n) => {
+                        if n.key == key {
+                            return Ok(Some(n.value.clone()));
+                        }
+                        return Ok(None);
+                    }
+                }
+            };
+            ptr = next;
+        }
+    }
+
+    /// Verify a proof for an iteration starting at the given key and return
+    /// the items covered by the proof.
+    ///
+    /// The returned items are exactly the first `1 + prefetch` keys which are
+    /// equal to or larger than the given key, in key order (or fewer if there
+    /// are no more keys in the tree). If the proof omits any nodes needed to
+    /// establish this, verification fails.
+    pub fn verify_proof_for_iterate(
+        &self,
+        ctx: Context,
+        root: Hash,
+        key: &[u8],
+        prefetch: u16,
+        proof: &Proof,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let root_ptr = self.verify_proof(ctx, root, proof)?;
+        let mut items = Vec::new();
+        self._verify_iterate(
+            &root_ptr,
+            0,
+            Key::new(),
+            &key.to_vec(),
+            1 + prefetch as usize,
+            &mut items,
+        )?;
+        Ok(items)
+    }
+
+    fn _verify_iterate(
+        &self,
+        ptr: &NodePtrRef,
+        bit_depth: Depth,
+        path: Key,
+        key: &Key,
+        limit: usize,
+        items: &mut Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> Result<()> {
+        if items.len() >= limit {
+            return Ok(());
+        }
+        // Subtrees whose path is smaller than the key can only contain smaller keys.
+        let bits = min(bit_depth, key.bit_length());
+        if cmp_bits(&path, key, bits) == Ordering::Less {
+            return Ok(());
+        }
+
+        let ptr = ptr.borrow();
+        if ptr.is_null() {
+            return Ok(());
+        }
+        if !ptr.has_node() {
+            return Err(IntegrityError::IncompleteProof.into());
+        }
+
+        let node_ref = ptr.get_node();
+        let node = node_ref.borrow();
+        match *node {
+            NodeBox::Internal(ref n) => {
+                let bit_length = bit_depth + n.label_bit_length;
+                let new_path = path.merge(bit_depth, &n.label, n.label_bit_length);
+
+                let leaf_node = n.leaf_node.borrow();
+                if !leaf_node.is_null() {
+                    if !leaf_node.has_node() {
+                        return Err(IntegrityError::IncompleteProof.into());
+                    }
+                    if let NodeBox::Leaf(ref leaf) = *leaf_node.get_node().borrow() {
+                        if leaf.key >= *key {
+                            items.push((leaf.key.clone(), leaf.value.clone()));
+                        }
+                    }
+                }
+
+                self._verify_iterate(
+                    &n.left,
+                    bit_length,
+                    new_path.append_bit(bit_length, false),
+                    key,
+                    limit,
+                    items,
+                )?;
+                self._verify_iterate(
+                    &n.right,
+                    bit_length,
+                    new_path.append_bit(bit_length, true),
+                    key,
+                    limit,
+                    items,
+                )?;
+            }
+            NodeBox::Leaf(ref n) => {
+                if n.key >= *key {
+                    items.push((n.key.clone(), n.value.clone()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn _verify_proof(
+        &self,
+        proof: &Proof,
+        idx: usize,
+        bit_depth: Depth,
+        path: &Key,
+    ) -> Result<(usize, NodePtrRef)> {
         if idx >= proof.entries.len() {
-            return Err(format_err!("verifier: malformed proof"));
+            return Err(IntegrityError::MalformedProof.into());
         }
         let entry = match &proof.entries[idx] {
             Some(entry) => entry.as_ref(),
             None => return Ok((idx + 1, NodePointer::null_ptr())),
         };
         if entry.is_empty() {
-            return Err(format_err!("verifier: malformed proof"));
+            return Err(IntegrityError::MalformedProof.into());
         }
 
         match entry[0] {
-            PROOF_ENTRY_FULL => {
+            PROOF_ENTRY_FULL | PROOF_ENTRY_COMPRESSED => {
                 // Full node.
                 let mut node = NodeBox::default();
-                node.unmarshal_binary(&entry[1..])?;
+                let result = if entry[0] == PROOF_ENTRY_COMPRESSED {
+                    node.unmarshal_binary_compressed(&entry[1..], path, bit_depth)
+                } else {
+                    node.unmarshal_binary(&entry[1..])
+                };
+                result.map_err(|_| IntegrityError::MalformedProof)?;
 
                 // For internal nodes, also decode children.
                 let mut pos = idx + 1;
                 if let NodeBox::Internal(ref mut nd) = node {
+                    let bit_length = bit_depth
+                        .checked_add(nd.label_bit_length)
+                        .filter(|bit_length| *bit_length < Depth::max_value())
+                        .ok_or(IntegrityError::MalformedProof)?;
+                    let new_path = path.merge(bit_depth, &nd.label, nd.label_bit_length);
+
                     // Left.
-                    let result = self._verify_proof(&proof, pos)?;
+                    let result = self._verify_proof(
+                        &proof,
+                        pos,
+                        bit_length,
+                        &new_path.append_bit(bit_length, false),
+                    )?;
                     pos = result.0;
                     nd.left = result.1;
                     // Right.
-                    let result = self._verify_proof(&proof, pos)?;
+                    let result = self._verify_proof(
+                        &proof,
+                        pos,
+                        bit_length,
+                        &new_path.append_bit(bit_length, true),
+                    )?;
                     pos = result.0;
                     nd.right = result.1;
 
@@ -136,15 +499,12 @@ impl ProofVerifier {
                 // Hash of a node.
                 let entry = &entry[1..];
                 if entry.len() != Hash::len() {
-                    return Err(format_err!("verifier: malformed hash entry"));
+                    return Err(IntegrityError::MalformedHashEntry.into());
                 }
 
                 Ok((idx + 1, NodePointer::hash_ptr(entry.into())))
             }
-            entry_type => Err(format_err!(
-                "verifier: unexpected entry in proof ({:?})",
-                entry_type
-            )),
+            entry_type => Err(IntegrityError::UnexpectedEntry { entry_type }.into()),
         }
     }
 }
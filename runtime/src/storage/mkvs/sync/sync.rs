@@ -1,6 +1,6 @@
 use std::any::Any;
 
-use failure::Fallible;
+use anyhow::Result;
 use io_context::Context;
 use serde_bytes;
 use serde_derive::{Deserialize, Serialize};
@@ -30,6 +30,9 @@ pub struct GetRequest {
     pub key: Vec<u8>,
     #[serde(default)]
     pub include_siblings: bool,
+    /// Whether the proof should use prefix compression.
+    #[serde(default)]
+    pub compressed: bool,
 }
 
 /// Request for the SyncGetPrefixes operation.
@@ -38,6 +41,9 @@ pub struct GetPrefixesRequest {
     pub tree: TreeID,
     pub prefixes: Vec<Prefix>,
     pub limit: u16,
+    /// Whether the proof should use prefix compression.
+    #[serde(default)]
+    pub compressed: bool,
 }
 
 /// Request for the SyncIterate operation.
@@ -47,6 +53,9 @@ pub struct IterateRequest {
     #[serde(with = "serde_bytes")]
     pub key: Vec<u8>,
     pub prefetch: u16,
+    /// Whether the proof should use prefix compression.
+    #[serde(default)]
+    pub compressed: bool,
 }
 
 /// Response for requests that produce proofs.
@@ -62,16 +71,16 @@ pub trait ReadSync {
     fn as_any(&self) -> &dyn Any;
 
     /// Fetch a single key and returns the corresponding proof.
-    fn sync_get(&mut self, ctx: Context, request: GetRequest) -> Fallible<ProofResponse>;
+    fn sync_get(&mut self, ctx: Context, request: GetRequest) -> Result<ProofResponse>;
 
     /// Fetch all keys under the given prefixes and returns the corresponding proofs.
     fn sync_get_prefixes(
         &mut self,
         ctx: Context,
         request: GetPrefixesRequest,
-    ) -> Fallible<ProofResponse>;
+    ) -> Result<ProofResponse>;
 
     /// Seek to a given key and then fetch the specified number of following items
     /// based on key iteration order.
-    fn sync_iterate(&mut self, ctx: Context, request: IterateRequest) -> Fallible<ProofResponse>;
+    fn sync_iterate(&mut self, ctx: Context, request: IterateRequest) -> Result<ProofResponse>;
 }
@@ -1,10 +1,17 @@
+use std::any::Any;
+
+use anyhow::Result;
 use io_context::Context;
 
-use crate::storage::mkvs::{
-    interop::{Driver, ProtocolServer},
-    sync::*,
-    tree::*,
-    LogEntry,
+use crate::{
+    common::crypto::hash::Hash,
+    storage::mkvs::{
+        interop::{Driver, ProtocolServer},
+        marshal::Marshal,
+        sync::*,
+        tree::*,
+        LogEntry,
+    },
 };
 
 #[test]
@@ -54,3 +61,85 @@ fn test_nil_pointers() {
         .insert(Context::background(), b"insert", b"key")
         .expect("insert");
 }
+
+/// A read syncer which returns proofs for bogus state.
+struct MaliciousReadSyncer {
+    untrusted_root: Option<Hash>,
+}
+
+impl MaliciousReadSyncer {
+    fn bogus_proof(&self, root: Hash) -> ProofResponse {
+        let leaf = NodeBox::Leaf(LeafNode {
+            key: b"foo".to_vec(),
+            value: b"bogus".to_vec(),
+            ..Default::default()
+        });
+        let mut entry = vec![PROOF_ENTRY_FULL];
+        entry.extend(leaf.marshal_binary().expect("marshal"));
+
+        ProofResponse {
+            proof: Proof {
+                untrusted_root: self.untrusted_root.unwrap_or(root),
+                entries: vec![Some(entry.into())],
+            },
+        }
+    }
+}
+
+impl ReadSync for MaliciousReadSyncer {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn sync_get(&mut self, _ctx: Context, request: GetRequest) -> Result<ProofResponse> {
+        Ok(self.bogus_proof(request.tree.root.hash))
+    }
+
+    fn sync_get_prefixes(
+        &mut self,
+        _ctx: Context,
+        request: GetPrefixesRequest,
+    ) -> Result<ProofResponse> {
+        Ok(self.bogus_proof(request.tree.root.hash))
+    }
+
+    fn sync_iterate(&mut self, _ctx: Context, request: IterateRequest) -> Result<ProofResponse> {
+        Ok(self.bogus_proof(request.tree.root.hash))
+    }
+}
+
+#[test]
+fn test_malicious_read_syncer() {
+    let root = Root {
+        hash: Hash::digest_bytes(b"trusted root"),
+        ..Default::default()
+    };
+
+    // Proof claiming to be for the trusted root, but containing bogus nodes.
+    let tree = Tree::make()
+        .with_root(root)
+        .new(Box::new(MaliciousReadSyncer {
+            untrusted_root: None,
+        }));
+    let err = tree
+        .get(Context::background(), b"foo")
+        .expect_err("get should fail with bogus state");
+    match err.downcast_ref::<IntegrityError>() {
+        Some(IntegrityError::BadRoot { .. }) => {}
+        other => panic!("expected bad root error, got {:?}", other),
+    }
+
+    // Proof for an unrelated root.
+    let tree = Tree::make()
+        .with_root(root)
+        .new(Box::new(MaliciousReadSyncer {
+            untrusted_root: Some(Hash::digest_bytes(b"bogus root")),
+        }));
+    let err = tree
+        .get(Context::background(), b"foo")
+        .expect_err("get should fail with proof for unexpected root");
+    match err.downcast_ref::<IntegrityError>() {
+        Some(IntegrityError::UnexpectedRoot { .. }) => {}
+        other => panic!("expected unexpected root error, got {:?}", other),
+    }
+}
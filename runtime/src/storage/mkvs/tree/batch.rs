@@ -0,0 +1,313 @@
+use std::{convert::TryInto, rc::Rc, sync::Arc};
+
+use anyhow::Result;
+use io_context::Context;
+
+use crate::storage::mkvs::{
+    cache::*, dedup_write_log, sync::*, tree::*, LogEntry, Prefix, WriteLog,
+};
+
+use super::lookup::FetcherSyncGet;
+
+impl Tree {
+    /// Apply a batch of inserts and removes to the tree.
+    ///
+    /// The batch is deduplicated (only the last update for each key is kept)
+    /// and sorted before being applied in a single pass over the tree. Each
+    /// affected node is visited once and the sorted batch is partitioned
+    /// between its children, instead of walking from the root for every key.
+    /// All nodes required by the batch are fetched from the read syncer in a
+    /// single round trip.
+    pub fn apply_write_log(&mut self, ctx: Context, write_log: WriteLog) -> Result<()> {
+        let ctx = ctx.freeze();
+        // If a key has already been removed locally, don't try to remove it again.
+        let write_log: WriteLog = dedup_write_log(write_log)
+            .into_iter()
+            .filter(
+                |entry| match (&entry.value, self.pending_write_log.get(&entry.key)) {
+                    (None, Some(PendingLogEntry { value: None, .. })) => false,
+                    _ => true,
+                },
+            )
+            .collect();
+        if write_log.is_empty() {
+            return Ok(());
+        }
+
+        // Prefetch all the affected paths in one go. This is only possible when the
+        // pending root is clean as merging into dirty subtrees is not supported.
+        let pending_root = self.cache.borrow().get_pending_root();
+        let can_prefetch = {
+            let root = pending_root.borrow();
+            root.clean && !root.is_null()
+        };
+        if can_prefetch {
+            let prefixes: Vec<Prefix> = write_log
+                .iter()
+                .map(|entry| Prefix::from(entry.key.clone()))
+                .collect();
+            let limit = prefixes.len().try_into().unwrap_or(u16::max_value());
+            if let Err(err) = self.prefetch_prefixes(Context::create_child(&ctx), &prefixes, limit)
+            {
+                match err.downcast_ref::<SyncerError>() {
+                    // Local-only trees cannot prefetch, nodes are already available.
+                    Some(SyncerError::Unsupported) => {}
+                    _ => return Err(err),
+                }
+            }
+        }
+
+        // Remember where the paths from root to target nodes end (will end).
+        self.cache.borrow_mut().mark_position();
+
+        let pending_root = self.cache.borrow().get_pending_root();
+        let mut existed = vec![false; write_log.len()];
+        let new_root = self._apply_write_log(&ctx, pending_root, 0, &write_log, &mut existed, 0)?;
+        for (entry, existed) in write_log.into_iter().zip(existed) {
+            match self.pending_write_log.get_mut(&entry.key) {
+                None => {
+                    self.pending_write_log.insert(
+                        entry.key.clone(),
+                        PendingLogEntry {
+                            key: entry.key,
+                            value: entry.value,
+                            existed,
+                        },
+                    );
+                }
+                Some(ref mut pending) => {
+                    pending.value = entry.value;
+                }
+            };
+        }
+        self.cache.borrow_mut().set_pending_root(new_root);
+
+        Ok(())
+    }
+
+    /// Apply a sorted and deduplicated batch of entries to the subtree rooted
+    /// at the given pointer, recording whether each key existed before.
+    fn _apply_write_log(
+        &mut self,
+        ctx: &Arc<Context>,
+        ptr: NodePtrRef,
+        bit_depth: Depth,
+        entries: &[LogEntry],
+        existed: &mut [bool],
+        depth: Depth,
+    ) -> Result<NodePtrRef> {
+        match entries.len() {
+            0 => return Ok(ptr),
+            1 => {
+                let entry = &entries[0];
+                let new_ptr = match entry.value {
+                    Some(ref value) => {
+                        let (new_ptr, old_val) =
+                            self._insert(ctx, ptr, bit_depth, &entry.key, value.clone(), depth)?;
+                        existed[0] = old_val.is_some();
+                        new_ptr
+                    }
+                    None => {
+                        let (new_ptr, changed, _) =
+                            self._remove(ctx, ptr, bit_depth, &entry.key, depth)?;
+                        existed[0] = changed;
+                        new_ptr
+                    }
+                };
+                return Ok(new_ptr);
+            }
+            _ => {}
+        }
+
+        let ptr = self._split_for_batch(ctx, ptr, bit_depth, entries)?;
+        let node_ref = ptr.borrow().get_node();
+        let (bit_length, leaf_node, left, right) = match *node_ref.borrow() {
+            NodeBox::Internal(ref n) => (
+                bit_depth + n.label_bit_length,
+                n.leaf_node.clone(),
+                n.left.clone(),
+                n.right.clone(),
+            ),
+            _ => unreachable!("batch split must produce an internal node"),
+        };
+
+        // Entries are sorted, so the entry ending at this node (if any) comes first,
+        // followed by the entries continuing to the left and then to the right.
+        let leaf_end = if entries[0].key.bit_length() == bit_length {
+            1
+        } else {
+            0
+        };
+        let left_end = leaf_end
+            + entries[leaf_end..]
+                .iter()
+                .take_while(|entry| !entry.key.get_bit(bit_length))
+                .count();
+        let (leaf_existed, existed) = existed.split_at_mut(leaf_end);
+        let (left_existed, right_existed) = existed.split_at_mut(left_end - leaf_end);
+
+        let new_leaf_node = self._apply_write_log(
+            ctx,
+            leaf_node.clone(),
+            bit_length,
+            &entries[..leaf_end],
+            leaf_existed,
+            depth,
+        )?;
+        let new_left = self._apply_write_log(
+            ctx,
+            left.clone(),
+            bit_length,
+            &entries[leaf_end..left_end],
+            left_existed,
+            depth + 1,
+        )?;
+        let new_right = self._apply_write_log(
+            ctx,
+            right.clone(),
+            bit_length,
+            &entries[left_end..],
+            right_existed,
+            depth + 1,
+        )?;
+
+        let changed = !Rc::ptr_eq(&leaf_node, &new_leaf_node)
+            || !Rc::ptr_eq(&left, &new_left)
+            || !Rc::ptr_eq(&right, &new_right)
+            || !new_leaf_node.borrow().clean
+            || !new_left.borrow().clean
+            || !new_right.borrow().clean;
+        if let NodeBox::Internal(ref mut n) = *node_ref.borrow_mut() {
+            n.leaf_node = new_leaf_node;
+            n.left = new_left;
+            n.right = new_right;
+        }
+
+        // If at most one child including LeafNode remains, collapse it.
+        if entries.iter().any(|entry| entry.value.is_none()) {
+            if let Some(new_ptr) = self._collapse(
+                ctx,
+                ptr.clone(),
+                node_ref.clone(),
+                bit_length,
+                &entries[0].key,
+            )? {
+                return Ok(new_ptr);
+            }
+        }
+
+        if changed {
+            noderef_as_mut!(node_ref, Internal).clean = false;
+            ptr.borrow_mut().clean = false;
+            // No longer eligible for eviction as it is dirty.
+            self.cache
+                .borrow_mut()
+                .rollback_node(ptr.clone(), NodeKind::Internal);
+        }
+
+        Ok(ptr)
+    }
+
+    /// Return a pointer to an internal node at the given position whose label
+    /// is shared by all (at least two) entries, splitting the existing node
+    /// if needed.
+    fn _split_for_batch(
+        &mut self,
+        ctx: &Arc<Context>,
+        ptr: NodePtrRef,
+        bit_depth: Depth,
+        entries: &[LogEntry],
+    ) -> Result<NodePtrRef> {
+        let first = &entries[0].key;
+        let last = &entries[entries.len() - 1].key;
+        let node_ref = self.cache.borrow_mut().deref_node_ptr(
+            ctx,
+            ptr.clone(),
+            FetcherSyncGet::new(first, bit_depth, false),
+        )?;
+
+        // As entries are sorted, the prefix shared by all of them is the prefix
+        // shared by the first and the last one.
+        let (_, first_remainder) = first.split(bit_depth, first.bit_length());
+        let (_, last_remainder) = last.split(bit_depth, last.bit_length());
+        let cp_len = first_remainder.common_prefix_len(
+            first.bit_length() - bit_depth,
+            &last_remainder,
+            last.bit_length() - bit_depth,
+        );
+
+        match classify_noderef!(?node_ref) {
+            NodeKind::None => {
+                let (label, _) = first_remainder.split(cp_len, first.bit_length() - bit_depth);
+                Ok(self.cache.borrow_mut().new_internal_node(
+                    &label,
+                    cp_len,
+                    NodePointer::null_ptr(),
+                    NodePointer::null_ptr(),
+                    NodePointer::null_ptr(),
+                ))
+            }
+            NodeKind::Internal => {
+                let node_ref = node_ref.unwrap();
+                let (label_prefix, cp_len, existing_right) = match *node_ref.borrow_mut() {
+                    NodeBox::Internal(ref mut n) => {
+                        let cp_len =
+                            n.label
+                                .common_prefix_len(n.label_bit_length, &first_remainder, cp_len);
+                        if cp_len == n.label_bit_length {
+                            // All entries continue through this node.
+                            return Ok(ptr);
+                        }
+
+                        // Entries mismatch the label at position cp_len. Split the edge.
+                        let label_split = n.label.split(cp_len, n.label_bit_length);
+                        n.label = label_split.1;
+                        n.label_bit_length = n.label_bit_length - cp_len;
+                        n.clean = false;
+                        (label_split.0, cp_len, n.label.get_bit(0))
+                    }
+                    _ => unreachable!("node kind is Internal"),
+                };
+                ptr.borrow_mut().clean = false;
+                // No longer eligible for eviction as it is dirty.
+                self.cache
+                    .borrow_mut()
+                    .rollback_node(ptr.clone(), NodeKind::Internal);
+
+                let (left, right) = if existing_right {
+                    (NodePointer::null_ptr(), ptr)
+                } else {
+                    (ptr, NodePointer::null_ptr())
+                };
+                Ok(self.cache.borrow_mut().new_internal_node(
+                    &label_prefix,
+                    cp_len,
+                    NodePointer::null_ptr(),
+                    left,
+                    right,
+                ))
+            }
+            NodeKind::Leaf => {
+                let node_ref = node_ref.unwrap();
+                let leaf_key = noderef_as!(node_ref, Leaf).key.clone();
+                let leaf_len = leaf_key.bit_length() - bit_depth;
+                let (_, leaf_remainder) = leaf_key.split(bit_depth, leaf_key.bit_length());
+                let cp_len = leaf_remainder.common_prefix_len(leaf_len, &first_remainder, cp_len);
+                let (label, _) = leaf_remainder.split(cp_len, leaf_len);
+
+                // Place the existing leaf below the new internal node.
+                let (leaf_node, left, right) = if cp_len == leaf_len {
+                    (ptr, NodePointer::null_ptr(), NodePointer::null_ptr())
+                } else if leaf_remainder.get_bit(cp_len) {
+                    (NodePointer::null_ptr(), NodePointer::null_ptr(), ptr)
+                } else {
+                    (NodePointer::null_ptr(), ptr, NodePointer::null_ptr())
+                };
+                Ok(self
+                    .cache
+                    .borrow_mut()
+                    .new_internal_node(&label, cp_len, leaf_node, left, right))
+            }
+        }
+    }
+}
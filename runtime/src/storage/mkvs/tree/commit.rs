@@ -1,6 +1,6 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, mem, sync::Arc};
 
-use failure::Fallible;
+use anyhow::Result;
 use io_context::Context;
 
 use crate::{
@@ -16,7 +16,7 @@ impl Tree {
         ctx: Context,
         namespace: Namespace,
         version: u64,
-    ) -> Fallible<(WriteLog, Hash)> {
+    ) -> Result<(WriteLog, Hash)> {
         let ctx = ctx.freeze();
         let mut update_list: UpdateList<LRUCache> = UpdateList::new();
         let pending_root = self.cache.borrow().get_pending_root();
@@ -24,22 +24,26 @@ impl Tree {
 
         update_list.commit(&mut self.cache.borrow_mut());
 
-        let mut log: WriteLog = Vec::new();
-        for (_, entry) in self.pending_write_log.iter() {
+        // Move entries out of the pending write log to avoid copying values.
+        let pending_write_log = mem::replace(&mut self.pending_write_log, BTreeMap::new());
+        let mut log: WriteLog = Vec::with_capacity(pending_write_log.len());
+        for (_, entry) in pending_write_log.into_iter() {
             // Skip all entries that do not exist after all the updates and
             // did not exist before.
             if entry.value.is_none() && !entry.existed {
                 continue;
             }
             log.push(LogEntry {
-                key: entry.key.clone(),
-                value: entry.value.clone(),
+                key: entry.key,
+                value: entry.value,
             });
         }
-        self.pending_write_log.clear();
+        self.cache.borrow_mut().record_commit(&log);
+        let root_type = self.cache.borrow().get_sync_root().root_type;
         self.cache.borrow_mut().set_sync_root(Root {
             namespace,
             version,
+            root_type,
             hash: new_hash,
         });
 
@@ -47,70 +51,70 @@ impl Tree {
     }
 }
 
+/// Compute the hashes of all dirty nodes under the given pointer.
+///
+/// Node hashes are not maintained while writes are applied as they depend on
+/// the version, which is only known at commit time. Instead each dirty node is
+/// hashed exactly once, bottom-up, and clean subtrees are skipped.
 pub fn _commit<C: Cache>(
     ctx: &Arc<Context>,
     ptr: NodePtrRef,
     update_list: &mut UpdateList<C>,
     version: Option<u64>,
-) -> Fallible<Hash> {
+) -> Result<Hash> {
     if ptr.borrow().clean {
         return Ok(ptr.borrow().hash);
     }
 
-    match classify_noderef!(? ptr.borrow().node) {
-        NodeKind::None => {
-            ptr.borrow_mut().hash = Hash::empty_hash();
-        }
-        NodeKind::Internal => {
-            let some_node_ref = ptr.borrow().get_node();
-            if some_node_ref.borrow().is_clean() {
-                ptr.borrow_mut().hash = some_node_ref.borrow().get_hash();
-            } else {
-                let int_leaf_node = noderef_as!(some_node_ref, Internal).leaf_node.clone();
-                let int_left = noderef_as!(some_node_ref, Internal).left.clone();
-                let int_right = noderef_as!(some_node_ref, Internal).right.clone();
-
-                _commit(ctx, int_leaf_node.clone(), update_list, version)?;
-                _commit(ctx, int_left.clone(), update_list, version)?;
-                _commit(ctx, int_right.clone(), update_list, version)?;
-
-                if let Some(version) = version {
-                    noderef_as_mut!(some_node_ref, Internal).version = version;
+    let node_ref = ptr.borrow().node.clone();
+    let hash = match node_ref {
+        None => Hash::empty_hash(),
+        Some(ref node_ref) if node_ref.borrow().is_clean() => node_ref.borrow().get_hash(),
+        Some(node_ref) => {
+            // Commit any children first so their hashes are available.
+            let children = match *node_ref.borrow() {
+                NodeBox::Internal(ref n) => {
+                    Some((n.leaf_node.clone(), n.left.clone(), n.right.clone()))
                 }
-                some_node_ref.borrow_mut().update_hash();
-                ptr.borrow_mut().hash = some_node_ref.borrow().get_hash();
-
-                let closure_node_ref = some_node_ref.clone();
-                update_list.push(Box::new(move |_| {
-                    noderef_as_mut!(closure_node_ref, Internal).clean = true
-                }));
+                NodeBox::Leaf(_) => None,
+            };
+            if let Some((leaf_node, left, right)) = children {
+                _commit(ctx, leaf_node, update_list, version)?;
+                _commit(ctx, left, update_list, version)?;
+                _commit(ctx, right, update_list, version)?;
             }
-        }
-        NodeKind::Leaf => {
-            let node_ref = ptr.borrow().get_node();
-            if node_ref.borrow().is_clean() {
-                ptr.borrow_mut().hash = node_ref.borrow().get_hash();
-            } else {
-                if let Some(version) = version {
-                    noderef_as_mut!(node_ref, Leaf).version = version;
-                }
-                node_ref.borrow_mut().update_hash();
-                ptr.borrow_mut().hash = node_ref.borrow().get_hash();
 
-                let closure_node_ref = node_ref.clone();
-                update_list.push(Box::new(move |_| {
-                    noderef_as_mut!(closure_node_ref, Leaf).clean = true
-                }));
+            let mut node = node_ref.borrow_mut();
+            if let Some(version) = version {
+                match *node {
+                    NodeBox::Internal(ref mut n) => n.version = version,
+                    NodeBox::Leaf(ref mut n) => n.version = version,
+                }
             }
+            node.update_hash();
+            node.get_hash()
         }
     };
+    {
+        let mut ptr = ptr.borrow_mut();
+        ptr.hash = hash;
+        // The node changed, so any spilled copy is stale.
+        ptr.spilled = false;
+    }
 
-    let closure_ptr = ptr.clone();
+    // A single update marks both the node and the pointer as clean.
+    let closure_ptr = ptr;
     update_list.push(Box::new(move |cache| {
+        if let Some(ref node_ref) = closure_ptr.borrow().node {
+            match *node_ref.borrow_mut() {
+                NodeBox::Internal(ref mut n) => n.clean = true,
+                NodeBox::Leaf(ref mut n) => n.clean = true,
+            }
+        }
         closure_ptr.borrow_mut().clean = true;
         // Make node eligible for eviction.
         cache.commit_node(closure_ptr.clone());
     }));
 
-    Ok(ptr.borrow().hash)
+    Ok(hash)
 }
@@ -1,9 +1,15 @@
-use failure::Fail;
+use thiserror::Error;
 
-#[derive(Debug, Fail)]
+use super::RootType;
+
+#[derive(Debug, Error)]
 pub enum TreeError {
-    #[fail(display = "mkvs: malformed node")]
+    #[error("mkvs: malformed node")]
     MalformedNode,
-    #[fail(display = "mkvs: malformed key")]
+    #[error("mkvs: malformed key")]
     MalformedKey,
+    #[error("mkvs: tree has uncommitted changes")]
+    Uncommitted,
+    #[error("mkvs: root type mismatch (expected: {expected:?} got: {got:?})")]
+    RootTypeMismatch { expected: RootType, got: RootType },
 }
@@ -1,19 +1,33 @@
 //! Tree iterator.
-use std::{collections::VecDeque, fmt, iter::Iterator, mem::replace, sync::Arc};
-
-use failure::{Error, Fallible};
+use std::{
+    cmp::{min, Ordering},
+    collections::VecDeque,
+    fmt,
+    iter::Iterator,
+    mem::replace,
+    sync::Arc,
+};
+
+use anyhow::{Error, Result};
 use io_context::Context;
 
 use crate::storage::mkvs::{cache::*, sync::*, tree::*};
 
 pub(super) struct FetcherSyncIterate<'a> {
     key: &'a Key,
+    path: &'a Key,
+    bit_depth: Depth,
     prefetch: usize,
 }
 
 impl<'a> FetcherSyncIterate<'a> {
-    pub(super) fn new(key: &'a Key, prefetch: usize) -> Self {
-        Self { key, prefetch }
+    pub(super) fn new(key: &'a Key, path: &'a Key, bit_depth: Depth, prefetch: usize) -> Self {
+        Self {
+            key,
+            path,
+            bit_depth,
+            prefetch,
+        }
     }
 }
 
@@ -24,7 +38,7 @@ impl<'a> ReadSyncFetcher for FetcherSyncIterate<'a> {
         root: Root,
         ptr: NodePtrRef,
         rs: &mut Box<dyn ReadSync>,
-    ) -> Fallible<Proof> {
+    ) -> Result<Proof> {
         let rsp = rs.sync_iterate(
             ctx,
             IterateRequest {
@@ -34,10 +48,15 @@ impl<'a> ReadSyncFetcher for FetcherSyncIterate<'a> {
                 },
                 key: self.key.clone(),
                 prefetch: self.prefetch as u16,
+                compressed: true,
             },
         )?;
         Ok(rsp.proof)
     }
+
+    fn position(&self) -> (Key, Depth) {
+        (self.path.clone(), self.bit_depth)
+    }
 }
 
 /// Visit state of a node.
@@ -191,11 +210,11 @@ impl<'tree> TreeIterator<'tree> {
         path: Key,
         mut key: Key,
         mut state: VisitState,
-    ) -> Fallible<()> {
+    ) -> Result<()> {
         let node_ref = self.tree.cache.borrow_mut().deref_node_ptr(
             &self.ctx,
             ptr.clone(),
-            FetcherSyncIterate::new(&key, self.prefetch),
+            FetcherSyncIterate::new(&key, &path, bit_depth, self.prefetch),
         )?;
 
         match classify_noderef!(?node_ref) {
@@ -346,6 +365,101 @@ impl Tree {
     pub fn iter(&self, ctx: Context) -> TreeIterator {
         TreeIterator::new(ctx, self)
     }
+
+    /// Generate a proof for iterating from the given key under the committed root.
+    ///
+    /// The proof covers the first `1 + prefetch` keys which are equal to or
+    /// larger than the given key and can be verified with
+    /// `ProofVerifier::verify_proof_for_iterate`.
+    pub fn get_iterate_proof(&self, ctx: Context, key: &[u8], prefetch: u16) -> Result<Proof> {
+        let ctx = ctx.freeze();
+        let boxed_key = key.to_vec();
+        let pending_root = self.cache.borrow().get_pending_root();
+        if !pending_root.borrow().clean {
+            return Err(TreeError::Uncommitted.into());
+        }
+
+        // Remember where the path from root to target node ends (will end).
+        self.cache.borrow_mut().mark_position();
+
+        let mut walker = IterateProofWalker {
+            tree: self,
+            ctx: &ctx,
+            key: &boxed_key,
+            remaining: 1 + prefetch as usize,
+            builder: ProofBuilder::new(pending_root.borrow().hash),
+        };
+        walker.visit(pending_root, 0, Key::new())?;
+        Ok(walker.builder.build())
+    }
+}
+
+/// Walks the tree in key order and includes all nodes visited while seeking
+/// to the given key and iterating over the following items into a proof.
+struct IterateProofWalker<'a> {
+    tree: &'a Tree,
+    ctx: &'a Arc<Context>,
+    key: &'a Key,
+    remaining: usize,
+    builder: ProofBuilder,
+}
+
+impl<'a> IterateProofWalker<'a> {
+    fn visit(&mut self, ptr: NodePtrRef, bit_depth: Depth, path: Key) -> Result<()> {
+        // Subtrees whose path is smaller than the key can only contain smaller keys.
+        let bits = min(bit_depth, self.key.bit_length());
+        if self.remaining == 0 || cmp_bits(&path, self.key, bits) == Ordering::Less {
+            return Ok(());
+        }
+
+        let node_ref = self.tree.cache.borrow_mut().deref_node_ptr(
+            self.ctx,
+            ptr,
+            FetcherSyncIterate::new(self.key, &path, bit_depth, self.remaining - 1),
+        )?;
+        let node_ref = match node_ref {
+            Some(node_ref) => node_ref,
+            None => return Ok(()),
+        };
+        self.builder.include(&node_ref.borrow());
+
+        let children = match *node_ref.borrow() {
+            NodeBox::Internal(ref n) => {
+                let bit_length = bit_depth + n.label_bit_length;
+                let new_path = path.merge(bit_depth, &n.label, n.label_bit_length);
+
+                let leaf_node = n.leaf_node.borrow();
+                if !leaf_node.is_null() && noderef_as!(leaf_node.get_node(), Leaf).key >= *self.key
+                {
+                    self.remaining -= 1;
+                }
+
+                vec![
+                    (
+                        n.left.clone(),
+                        bit_length,
+                        new_path.append_bit(bit_length, false),
+                    ),
+                    (
+                        n.right.clone(),
+                        bit_length,
+                        new_path.append_bit(bit_length, true),
+                    ),
+                ]
+            }
+            NodeBox::Leaf(ref n) => {
+                if n.key >= *self.key {
+                    self.remaining -= 1;
+                }
+                vec![]
+            }
+        };
+        for (ptr, bit_depth, path) in children {
+            self.visit(ptr, bit_depth, path)?;
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -354,7 +468,13 @@ mod test {
     use rustc_hex::FromHex;
 
     use super::{tree_test::generate_key_value_pairs_ex, *};
-    use crate::storage::mkvs::interop::{Driver, ProtocolServer};
+    use crate::{
+        common::crypto::hash::Hash,
+        storage::mkvs::{
+            interop::{Driver, ProtocolServer},
+            MemoryTree, MKVS,
+        },
+    };
 
     #[test]
     fn test_iterator() {
@@ -576,6 +696,100 @@ mod test {
         assert_eq!(2, stats.sync_iterate_count, "sync_iterate_count");
     }
 
+    #[test]
+    fn test_iterate_proof() {
+        let mut tree = MemoryTree::new();
+        let (keys, values) = generate_key_value_pairs_ex("T".to_owned(), 100);
+        let mut items: Vec<(Vec<u8>, Vec<u8>)> = keys.into_iter().zip(values.into_iter()).collect();
+        items.sort();
+        for (key, value) in &items {
+            MKVS::insert(&mut tree, Context::background(), &key, &value).expect("insert");
+        }
+
+        // Proofs can only be generated for committed roots.
+        assert!(tree
+            .get_iterate_proof(Context::background(), b"T", 0)
+            .is_err());
+
+        let (_, hash) =
+            MKVS::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+        let root = Root {
+            hash,
+            ..Default::default()
+        };
+        let mut read_syncer = tree.store().read_syncer();
+
+        let pv = ProofVerifier;
+        for seek in &[
+            b"".to_vec(),
+            b"T".to_vec(),
+            b"T5".to_vec(),
+            b"T50".to_vec(),
+            b"U".to_vec(),
+        ] {
+            let start = items
+                .iter()
+                .position(|(k, _)| k >= seek)
+                .unwrap_or(items.len());
+            for prefetch in &[0, 3, 1000] {
+                let end = min(items.len(), start + 1 + *prefetch as usize);
+                let expected = items[start..end].to_vec();
+
+                // Proof generated by the tree.
+                let proof = tree
+                    .get_iterate_proof(Context::background(), seek, *prefetch)
+                    .expect("get_iterate_proof");
+                let result = pv
+                    .verify_proof_for_iterate(Context::background(), hash, seek, *prefetch, &proof)
+                    .expect("verify_proof_for_iterate");
+                assert_eq!(result, expected, "tree proof should cover the successors");
+
+                // Proof returned by a read syncer.
+                let rsp = read_syncer
+                    .sync_iterate(
+                        Context::background(),
+                        IterateRequest {
+                            tree: TreeID {
+                                root,
+                                position: hash,
+                            },
+                            key: seek.clone(),
+                            prefetch: *prefetch,
+                            compressed: false,
+                        },
+                    )
+                    .expect("sync_iterate");
+                let result = pv
+                    .verify_proof_for_iterate(
+                        Context::background(),
+                        hash,
+                        seek,
+                        *prefetch,
+                        &rsp.proof,
+                    )
+                    .expect("verify_proof_for_iterate");
+                assert_eq!(result, expected, "synced proof should cover the successors");
+            }
+        }
+
+        // A proof which does not cover enough items should not verify.
+        let proof = tree
+            .get_iterate_proof(Context::background(), b"T", 3)
+            .expect("get_iterate_proof");
+        match pv.verify_proof_for_iterate(Context::background(), hash, b"T", 10, &proof) {
+            Err(err) => match err.downcast_ref::<IntegrityError>() {
+                Some(IntegrityError::IncompleteProof) => {}
+                _ => panic!("unexpected error: {:?}", err),
+            },
+            Ok(_) => panic!("proof should not cover additional items"),
+        }
+
+        // Proof should not verify against a different root.
+        assert!(pv
+            .verify_proof_for_iterate(Context::background(), Hash::empty_hash(), b"T", 3, &proof)
+            .is_err());
+    }
+
     fn test_iterator_with(
         items: &Vec<(Vec<u8>, Vec<u8>)>,
         mut it: TreeIterator,
@@ -1,19 +1,21 @@
 use std::sync::Arc;
 
-use failure::Fallible;
+use anyhow::Result;
 use io_context::Context;
 
 use crate::storage::mkvs::{cache::*, sync::*, tree::*};
 
 pub(super) struct FetcherSyncGet<'a> {
     key: &'a Key,
+    bit_depth: Depth,
     include_siblings: bool,
 }
 
 impl<'a> FetcherSyncGet<'a> {
-    pub(super) fn new(key: &'a Key, include_siblings: bool) -> Self {
+    pub(super) fn new(key: &'a Key, bit_depth: Depth, include_siblings: bool) -> Self {
         Self {
             key,
+            bit_depth,
             include_siblings,
         }
     }
@@ -26,7 +28,7 @@ impl<'a> ReadSyncFetcher for FetcherSyncGet<'a> {
         root: Root,
         ptr: NodePtrRef,
         rs: &mut Box<dyn ReadSync>,
-    ) -> Fallible<Proof> {
+    ) -> Result<Proof> {
         let rsp = rs.sync_get(
             ctx,
             GetRequest {
@@ -36,15 +38,21 @@ impl<'a> ReadSyncFetcher for FetcherSyncGet<'a> {
                 },
                 key: self.key.clone(),
                 include_siblings: self.include_siblings,
+                compressed: true,
             },
         )?;
         Ok(rsp.proof)
     }
+
+    fn position(&self) -> (Key, Depth) {
+        // The node is on the path of the key.
+        (self.key.clone(), self.bit_depth)
+    }
 }
 
 impl Tree {
     /// Get an existing key.
-    pub fn get(&self, ctx: Context, key: &[u8]) -> Fallible<Option<Vec<u8>>> {
+    pub fn get(&self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
         let ctx = ctx.freeze();
         let boxed_key = key.to_vec();
         let pending_root = self.cache.borrow().get_pending_root();
@@ -60,6 +68,26 @@ impl Tree {
         Ok(self._get(&ctx, pending_root, 0, &boxed_key, 0)?)
     }
 
+    /// Generate a proof for the given key under the committed root.
+    ///
+    /// The proof can be verified with `ProofVerifier::verify_proof_for_key` and
+    /// either proves the value of the key or that the key does not exist.
+    pub fn get_proof(&self, ctx: Context, key: &[u8]) -> Result<Proof> {
+        let ctx = ctx.freeze();
+        let boxed_key = key.to_vec();
+        let pending_root = self.cache.borrow().get_pending_root();
+        if !pending_root.borrow().clean {
+            return Err(TreeError::Uncommitted.into());
+        }
+
+        // Remember where the path from root to target node ends (will end).
+        self.cache.borrow_mut().mark_position();
+
+        let mut builder = ProofBuilder::new(pending_root.borrow().hash);
+        self._get_proof(&ctx, pending_root, 0, &boxed_key, &mut builder)?;
+        Ok(builder.build())
+    }
+
     fn _get(
         &self,
         ctx: &Arc<Context>,
@@ -67,11 +95,12 @@ impl Tree {
         bit_depth: Depth,
         key: &Key,
         depth: Depth,
-    ) -> Fallible<Option<Value>> {
-        let node_ref =
-            self.cache
-                .borrow_mut()
-                .deref_node_ptr(ctx, ptr, FetcherSyncGet::new(key, false))?;
+    ) -> Result<Option<Value>> {
+        let node_ref = self.cache.borrow_mut().deref_node_ptr(
+            ctx,
+            ptr,
+            FetcherSyncGet::new(key, bit_depth, false),
+        )?;
 
         match classify_noderef!(?node_ref) {
             NodeKind::None => {
@@ -131,4 +160,43 @@ impl Tree {
             }
         };
     }
+
+    fn _get_proof(
+        &self,
+        ctx: &Arc<Context>,
+        ptr: NodePtrRef,
+        bit_depth: Depth,
+        key: &Key,
+        builder: &mut ProofBuilder,
+    ) -> Result<()> {
+        let node_ref = self.cache.borrow_mut().deref_node_ptr(
+            ctx,
+            ptr,
+            FetcherSyncGet::new(key, bit_depth, false),
+        )?;
+        let node_ref = match node_ref {
+            Some(node_ref) => node_ref,
+            None => return Ok(()),
+        };
+        builder.include(&node_ref.borrow());
+
+        let next = match *node_ref.borrow() {
+            NodeBox::Internal(ref n) => {
+                let bit_length = bit_depth + n.label_bit_length;
+                if key.bit_length() <= bit_length {
+                    // The leaf node is always included with the internal node.
+                    None
+                } else if key.get_bit(bit_length) {
+                    Some((n.right.clone(), bit_length))
+                } else {
+                    Some((n.left.clone(), bit_length))
+                }
+            }
+            NodeBox::Leaf(..) => None,
+        };
+        match next {
+            Some((ptr, bit_depth)) => self._get_proof(ctx, ptr, bit_depth, key, builder),
+            None => Ok(()),
+        }
+    }
 }
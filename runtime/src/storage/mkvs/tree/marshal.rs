@@ -1,6 +1,6 @@
-use std::{cell::RefCell, mem::size_of, rc::Rc};
+use std::{cell::RefCell, cmp::min, mem::size_of, rc::Rc};
 
-use failure::Fallible;
+use anyhow::Result;
 
 use crate::{
     common::crypto::hash::Hash,
@@ -12,35 +12,75 @@ const VERSION_SIZE: usize = size_of::<u64>();
 /// Size of the encoded value length.
 const VALUE_LENGTH_SIZE: usize = size_of::<u32>();
 
+impl NodeBox {
+    /// Marshal the node using prefix compression relative to the path leading
+    /// to the node, which starts at the given bit depth.
+    ///
+    /// Keys of leaf nodes are encoded without the bytes they share with the
+    /// path and keys of leaf nodes embedded in internal nodes are omitted, as
+    /// they are equal to the path of the internal node. The result can only be
+    /// decoded using `unmarshal_binary_compressed` with the same path.
+    pub fn marshal_binary_compressed(&self, bit_depth: Depth) -> Result<Vec<u8>> {
+        match self {
+            NodeBox::Internal(ref n) => n._marshal_binary(true),
+            NodeBox::Leaf(ref n) => n._marshal_binary(min(bit_depth as usize / 8, n.key.len())),
+        }
+    }
+
+    /// Unmarshal a node encoded using `marshal_binary_compressed`, given the
+    /// path leading to the node and the bit depth at which it starts.
+    pub fn unmarshal_binary_compressed(
+        &mut self,
+        data: &[u8],
+        path: &Key,
+        bit_depth: Depth,
+    ) -> Result<usize> {
+        if path.len() < bit_depth.to_bytes() {
+            return Err(TreeError::MalformedNode.into());
+        }
+
+        self.reset_kind(data)?;
+        match self {
+            NodeBox::Internal(ref mut n) => n._unmarshal_binary(data, Some((path, bit_depth))),
+            NodeBox::Leaf(ref mut n) => n._unmarshal_binary(data, &path[..bit_depth as usize / 8]),
+        }
+    }
+
+    fn reset_kind(&mut self, data: &[u8]) -> Result<()> {
+        let mut kind = NodeKind::None;
+        kind.unmarshal_binary(data)?;
+        match kind {
+            NodeKind::Internal => {
+                *self = NodeBox::Internal(InternalNode {
+                    ..Default::default()
+                });
+            }
+            NodeKind::Leaf => {
+                *self = NodeBox::Leaf(LeafNode {
+                    ..Default::default()
+                });
+            }
+            _ => {
+                return Err(TreeError::MalformedNode.into());
+            }
+        };
+        Ok(())
+    }
+}
+
 impl Marshal for NodeBox {
-    fn marshal_binary(&self) -> Fallible<Vec<u8>> {
+    fn marshal_binary(&self) -> Result<Vec<u8>> {
         match self {
             NodeBox::Internal(ref n) => n.marshal_binary(),
             NodeBox::Leaf(ref n) => n.marshal_binary(),
         }
     }
 
-    fn unmarshal_binary(&mut self, data: &[u8]) -> Fallible<usize> {
+    fn unmarshal_binary(&mut self, data: &[u8]) -> Result<usize> {
         if data.len() < 1 {
             Err(TreeError::MalformedNode.into())
         } else {
-            let mut kind = NodeKind::None;
-            kind.unmarshal_binary(data)?;
-            match kind {
-                NodeKind::Internal => {
-                    *self = NodeBox::Internal(InternalNode {
-                        ..Default::default()
-                    });
-                }
-                NodeKind::Leaf => {
-                    *self = NodeBox::Leaf(LeafNode {
-                        ..Default::default()
-                    });
-                }
-                _ => {
-                    return Err(TreeError::MalformedNode.into());
-                }
-            };
+            self.reset_kind(data)?;
             match self {
                 NodeBox::Internal(ref mut n) => n.unmarshal_binary(data),
                 NodeBox::Leaf(ref mut n) => n.unmarshal_binary(data),
@@ -50,11 +90,11 @@ impl Marshal for NodeBox {
 }
 
 impl Marshal for NodeKind {
-    fn marshal_binary(&self) -> Fallible<Vec<u8>> {
+    fn marshal_binary(&self) -> Result<Vec<u8>> {
         Ok(vec![*self as u8])
     }
 
-    fn unmarshal_binary(&mut self, data: &[u8]) -> Fallible<usize> {
+    fn unmarshal_binary(&mut self, data: &[u8]) -> Result<usize> {
         if data.len() < 1 {
             Err(TreeError::MalformedNode.into())
         } else {
@@ -72,14 +112,21 @@ impl Marshal for NodeKind {
     }
 }
 
-impl Marshal for InternalNode {
-    fn marshal_binary(&self) -> Fallible<Vec<u8>> {
+impl InternalNode {
+    fn _marshal_binary(&self, compressed: bool) -> Result<Vec<u8>> {
         let leaf_node_binary: Vec<u8>;
         if self.leaf_node.borrow().is_null() {
             leaf_node_binary = vec![NodeKind::None as u8];
         } else {
-            leaf_node_binary =
-                noderef_as!(self.leaf_node.borrow().get_node(), Leaf).marshal_binary()?;
+            let node_ref = self.leaf_node.borrow().get_node();
+            let node = node_ref.borrow();
+            let leaf_node = match *node {
+                NodeBox::Leaf(ref n) => n,
+                _ => unreachable!(),
+            };
+            // The key of the leaf node is equal to the path of the internal node.
+            let skip = if compressed { leaf_node.key.len() } else { 0 };
+            leaf_node_binary = leaf_node._marshal_binary(skip)?;
         }
 
         let mut result: Vec<u8> =
@@ -95,7 +142,7 @@ impl Marshal for InternalNode {
         Ok(result)
     }
 
-    fn unmarshal_binary(&mut self, data: &[u8]) -> Fallible<usize> {
+    fn _unmarshal_binary(&mut self, data: &[u8], path: Option<(&Key, Depth)>) -> Result<usize> {
         let mut pos = 0;
         if data.len() < 1 + VERSION_SIZE + size_of::<Depth>() + 1
             || data[pos] != NodeKind::Internal as u8
@@ -127,7 +174,15 @@ impl Marshal for InternalNode {
             let mut leaf_node = LeafNode {
                 ..Default::default()
             };
-            pos += leaf_node.unmarshal_binary(&data[pos..])?;
+            match path {
+                Some((path, bit_depth)) => {
+                    let new_path = path.merge(bit_depth, &self.label, self.label_bit_length);
+                    pos += leaf_node._unmarshal_binary(&data[pos..], &new_path)?;
+                }
+                None => {
+                    pos += leaf_node.unmarshal_binary(&data[pos..])?;
+                }
+            }
             self.leaf_node = Rc::new(RefCell::new(NodePointer {
                 clean: true,
                 hash: leaf_node.get_hash(),
@@ -173,19 +228,30 @@ impl Marshal for InternalNode {
     }
 }
 
-impl Marshal for LeafNode {
-    fn marshal_binary(&self) -> Fallible<Vec<u8>> {
+impl Marshal for InternalNode {
+    fn marshal_binary(&self) -> Result<Vec<u8>> {
+        self._marshal_binary(false)
+    }
+
+    fn unmarshal_binary(&mut self, data: &[u8]) -> Result<usize> {
+        self._unmarshal_binary(data, None)
+    }
+}
+
+impl LeafNode {
+    fn _marshal_binary(&self, skip: usize) -> Result<Vec<u8>> {
         let mut result: Vec<u8> = Vec::with_capacity(1 + VERSION_SIZE + VALUE_LENGTH_SIZE);
         result.push(NodeKind::Leaf as u8);
         result.append(&mut self.version.marshal_binary()?);
-        result.append(&mut self.key.marshal_binary()?);
+        // Only encode the part of the key which is not shared with the path.
+        result.append(&mut self.key[skip..].to_vec().marshal_binary()?);
         result.append(&mut (self.value.len() as u32).marshal_binary()?);
         result.extend_from_slice(&self.value);
 
         Ok(result)
     }
 
-    fn unmarshal_binary(&mut self, data: &[u8]) -> Fallible<usize> {
+    fn _unmarshal_binary(&mut self, data: &[u8], prefix: &[u8]) -> Result<usize> {
         if data.len() < 1 + VERSION_SIZE + size_of::<Depth>() + VALUE_LENGTH_SIZE
             || data[0] != NodeKind::Leaf as u8
         {
@@ -199,7 +265,7 @@ impl Marshal for LeafNode {
             .unmarshal_binary(&data[pos..(pos + VERSION_SIZE)])?;
         pos += VERSION_SIZE;
 
-        self.key = Key::new();
+        self.key = prefix.to_vec();
         let key_len = self.key.unmarshal_binary(&data[pos..])?;
         pos += key_len;
         if pos + VALUE_LENGTH_SIZE > data.len() {
@@ -224,15 +290,25 @@ impl Marshal for LeafNode {
     }
 }
 
+impl Marshal for LeafNode {
+    fn marshal_binary(&self) -> Result<Vec<u8>> {
+        self._marshal_binary(0)
+    }
+
+    fn unmarshal_binary(&mut self, data: &[u8]) -> Result<usize> {
+        self._unmarshal_binary(data, &[])
+    }
+}
+
 impl Marshal for Key {
-    fn marshal_binary(&self) -> Fallible<Vec<u8>> {
+    fn marshal_binary(&self) -> Result<Vec<u8>> {
         let mut result: Vec<u8> = Vec::new();
         result.append(&mut (self.len() as Depth).marshal_binary()?);
         result.extend_from_slice(self);
         Ok(result)
     }
 
-    fn unmarshal_binary(&mut self, data: &[u8]) -> Fallible<usize> {
+    fn unmarshal_binary(&mut self, data: &[u8]) -> Result<usize> {
         if data.len() < size_of::<Depth>() {
             return Err(TreeError::MalformedKey.into());
         }
@@ -1,4 +1,4 @@
-use failure::Fallible;
+use anyhow::Result;
 use io_context::Context;
 
 use crate::{
@@ -9,30 +9,28 @@ use crate::{
 unsafe impl Send for Tree {}
 unsafe impl Sync for Tree {}
 
-// TODO: We should likely change the MKVS interface to propagate errors instead of unwrapping.
-
 impl MKVS for Tree {
-    fn get(&self, ctx: Context, key: &[u8]) -> Option<Vec<u8>> {
+    fn get(&self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
         let _lock = self.lock.lock().unwrap();
-        self.get(ctx, key).unwrap()
+        Tree::get(self, ctx, key)
     }
 
-    fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Option<Vec<u8>> {
+    fn insert(&mut self, ctx: Context, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
         let lock = self.lock.clone();
         let _guard = lock.lock().unwrap();
-        self.insert(ctx, key, value).unwrap()
+        Tree::insert(self, ctx, key, value)
     }
 
-    fn remove(&mut self, ctx: Context, key: &[u8]) -> Option<Vec<u8>> {
+    fn remove(&mut self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
         let lock = self.lock.clone();
         let _guard = lock.lock().unwrap();
-        self.remove(ctx, key).unwrap()
+        Tree::remove(self, ctx, key)
     }
 
-    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16) {
+    fn prefetch_prefixes(&self, ctx: Context, prefixes: &Vec<Prefix>, limit: u16) -> Result<()> {
         let lock = self.lock.clone();
         let _guard = lock.lock().unwrap();
-        self.prefetch_prefixes(ctx, prefixes, limit).unwrap()
+        Tree::prefetch_prefixes(self, ctx, prefixes, limit)
     }
 
     fn commit(
@@ -40,7 +38,7 @@ impl MKVS for Tree {
         ctx: Context,
         namespace: Namespace,
         version: u64,
-    ) -> Fallible<(WriteLog, Hash)> {
+    ) -> Result<(WriteLog, Hash)> {
         let lock = self.lock.clone();
         let _guard = lock.lock().unwrap();
         Tree::commit(self, ctx, namespace, version)
@@ -1,6 +1,7 @@
 #[macro_use]
 mod macros;
 
+mod batch;
 mod commit;
 mod errors;
 mod insert;
@@ -11,14 +12,17 @@ mod mkvs;
 mod node;
 mod prefetch;
 mod remove;
+mod stats;
 mod tree;
 
+pub use batch::*;
 pub use commit::*;
 pub use errors::*;
 pub use insert::*;
 pub use iterator::*;
 pub use node::*;
 pub use remove::*;
+pub use stats::*;
 pub use tree::*;
 
 #[cfg(test)]
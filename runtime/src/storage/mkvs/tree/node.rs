@@ -1,10 +1,12 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, cmp::Ordering, rc::Rc};
 
+use anyhow::Result;
 use serde_derive::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
 
 use crate::{
     common::{crypto::hash::Hash, roothash::Namespace},
-    storage::mkvs::{cache::*, marshal::*},
+    storage::mkvs::{cache::*, tree::TreeError},
 };
 
 /// Common interface for node-like objects in the tree.
@@ -19,6 +21,31 @@ pub trait Node {
     fn extract(&self) -> NodeRef;
 }
 
+/// Storage root type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum RootType {
+    /// Untyped root.
+    Invalid = 0,
+    /// Root of a runtime state tree.
+    State = 1,
+    /// Root of a transaction I/O tree.
+    IO = 2,
+}
+
+impl RootType {
+    /// Check whether the root type is unset.
+    pub fn is_invalid(&self) -> bool {
+        *self == RootType::Invalid
+    }
+}
+
+impl Default for RootType {
+    fn default() -> Self {
+        RootType::Invalid
+    }
+}
+
 /// Storage root.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Root {
@@ -27,10 +54,29 @@ pub struct Root {
     pub namespace: Namespace,
     /// Monotonically increasing version number in which the root is stored.
     pub version: u64,
+    /// Type of the tree the root belongs to.
+    #[serde(default, skip_serializing_if = "RootType::is_invalid")]
+    pub root_type: RootType,
     /// Merkle root hash.
     pub hash: Hash,
 }
 
+impl Root {
+    /// Ensure that the root is of the given type.
+    ///
+    /// Untyped roots are accepted as not all sources of roots track types.
+    pub fn ensure_type(&self, root_type: RootType) -> Result<()> {
+        if !self.root_type.is_invalid() && self.root_type != root_type {
+            return Err(TreeError::RootTypeMismatch {
+                expected: root_type,
+                got: self.root_type,
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
 /// A box type that can contain either internal or leaf nodes.
 #[derive(Debug, Eq, PartialEq)]
 pub enum NodeBox {
@@ -95,6 +141,8 @@ pub struct NodePointer {
     pub clean: bool,
     pub hash: Hash,
     pub node: Option<NodeRef>,
+    /// Whether the node is known to be in the spill storage.
+    pub spilled: bool,
 
     pub cache_extra: CacheExtra<NodePointer>,
 }
@@ -243,8 +291,8 @@ impl Node for InternalNode {
 
         self.hash = Hash::digest_bytes_list(&[
             &[NodeKind::Internal as u8],
-            &self.version.marshal_binary().unwrap(),
-            &self.label_bit_length.marshal_binary().unwrap(),
+            &self.version.to_le_bytes(),
+            &self.label_bit_length.to_le_bytes(),
             self.label.as_ref(),
             leaf_node_hash.as_ref(),
             left_hash.as_ref(),
@@ -320,7 +368,7 @@ impl Node for LeafNode {
     fn update_hash(&mut self) {
         self.hash = Hash::digest_bytes_list(&[
             &[NodeKind::Leaf as u8],
-            &self.version.marshal_binary().unwrap(),
+            &self.version.to_le_bytes(),
             self.key.as_ref(),
             self.value.as_ref(),
         ]);
@@ -527,5 +575,16 @@ impl KeyTrait for Key {
     }
 }
 
+/// Compare the first `bits` bits of the given keys.
+pub(crate) fn cmp_bits(a: &Key, b: &Key, bits: Depth) -> Ordering {
+    for bit in 0..bits {
+        match a.get_bit(bit).cmp(&b.get_bit(bit)) {
+            Ordering::Equal => continue,
+            ordering => return ordering,
+        }
+    }
+    Ordering::Equal
+}
+
 // Value holds the leaf node value.
 pub type Value = Vec<u8>;
@@ -1,7 +1,7 @@
 use std::{cell::RefCell, rc::Rc, str::FromStr};
 
 use crate::{
-    common::crypto::hash::Hash,
+    common::{cbor, crypto::hash::Hash},
     storage::mkvs::{marshal::*, tree::*},
 };
 
@@ -95,6 +95,77 @@ fn test_serialization_internal() {
     assert_eq!(false, decoded_int_node.right.borrow().node.is_some());
 }
 
+#[test]
+fn test_serialization_compressed() {
+    let path: Key = b"a golden ".to_vec();
+    let bit_depth = 72 as Depth;
+
+    // Leaf nodes only encode the part of the key following the path.
+    let mut leaf_node = LeafNode {
+        key: b"a golden key".to_vec(),
+        value: b"value".to_vec(),
+        ..Default::default()
+    };
+    leaf_node.update_hash();
+    let leaf = NodeBox::Leaf(leaf_node.copy());
+
+    let marshaled = leaf.marshal_binary_compressed(bit_depth).expect("marshal");
+    assert!(marshaled.len() < leaf.marshal_binary().expect("marshal").len());
+
+    let mut decoded = NodeBox::default();
+    decoded
+        .unmarshal_binary_compressed(marshaled.as_slice(), &path, bit_depth)
+        .expect("unmarshal");
+    assert_eq!(leaf.get_hash(), decoded.get_hash());
+    match decoded {
+        NodeBox::Leaf(ref n) => assert_eq!(leaf_node.key, n.key),
+        _ => panic!("decoded node should be a leaf node"),
+    }
+
+    // Embedded leaf nodes omit the key as it is equal to the path.
+    let mut int_node = NodeBox::Internal(InternalNode {
+        label: b"key".to_vec(),
+        label_bit_length: 24,
+        leaf_node: Rc::new(RefCell::new(NodePointer {
+            clean: true,
+            hash: leaf_node.get_hash(),
+            node: Some(Rc::new(RefCell::new(NodeBox::Leaf(leaf_node)))),
+            ..Default::default()
+        })),
+        left: NodePointer::null_ptr(),
+        right: Rc::new(RefCell::new(NodePointer {
+            clean: true,
+            hash: Hash::digest_bytes(b"everyone move to the right"),
+            ..Default::default()
+        })),
+        ..Default::default()
+    });
+    int_node.update_hash();
+
+    let marshaled = int_node
+        .marshal_binary_compressed(bit_depth)
+        .expect("marshal");
+    assert!(marshaled.len() < int_node.marshal_binary().expect("marshal").len());
+
+    let mut decoded = NodeBox::default();
+    decoded
+        .unmarshal_binary_compressed(marshaled.as_slice(), &path, bit_depth)
+        .expect("unmarshal");
+    assert_eq!(int_node.get_hash(), decoded.get_hash());
+
+    // Decoding with a different path yields a different node.
+    let mut decoded = NodeBox::default();
+    decoded
+        .unmarshal_binary_compressed(marshaled.as_slice(), &b"a silver ".to_vec(), bit_depth)
+        .expect("unmarshal");
+    assert_ne!(int_node.get_hash(), decoded.get_hash());
+
+    // The path must cover the given depth.
+    assert!(NodeBox::default()
+        .unmarshal_binary_compressed(marshaled.as_slice(), &Key::new(), bit_depth)
+        .is_err());
+}
+
 #[test]
 fn test_hash_leaf() {
     let mut leaf_node = LeafNode {
@@ -260,3 +331,28 @@ fn test_key_common_prefix_len() {
     assert_eq!(12, key.common_prefix_len(13, &vec![0xab, 0xcd], 12));
     assert_eq!(12, key.common_prefix_len(12, &vec![0xab, 0xcd], 13));
 }
+
+#[test]
+fn test_root_type() {
+    let untyped = Root {
+        hash: Hash::empty_hash(),
+        ..Default::default()
+    };
+    assert!(untyped.ensure_type(RootType::State).is_ok());
+    assert!(untyped.ensure_type(RootType::IO).is_ok());
+
+    let state = Root {
+        root_type: RootType::State,
+        ..untyped
+    };
+    assert!(state.ensure_type(RootType::State).is_ok());
+    assert!(state.ensure_type(RootType::IO).is_err());
+
+    // Untyped roots should keep their encoding.
+    let encoded = cbor::to_vec(&untyped);
+    let decoded: Root = cbor::from_slice(&encoded).unwrap();
+    assert_eq!(decoded, untyped);
+    assert_ne!(cbor::to_vec(&state), encoded);
+    let decoded: Root = cbor::from_slice(&cbor::to_vec(&state)).unwrap();
+    assert_eq!(decoded, state);
+}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use failure::Fallible;
+use anyhow::Result;
 use io_context::Context;
 
 use crate::storage::mkvs::{cache::*, tree::*};
@@ -9,7 +9,7 @@ use super::lookup::FetcherSyncGet;
 
 impl Tree {
     /// Remove a key from the tree and return true if the tree was modified.
-    pub fn remove(&mut self, ctx: Context, key: &[u8]) -> Fallible<Option<Vec<u8>>> {
+    pub fn remove(&mut self, ctx: Context, key: &[u8]) -> Result<Option<Vec<u8>>> {
         let ctx = ctx.freeze();
         let boxed_key = key.to_vec();
         let pending_root = self.cache.borrow().get_pending_root();
@@ -43,18 +43,18 @@ impl Tree {
         Ok(old_val)
     }
 
-    fn _remove(
+    pub(super) fn _remove(
         &mut self,
         ctx: &Arc<Context>,
         ptr: NodePtrRef,
         bit_depth: Depth,
         key: &Key,
         depth: Depth,
-    ) -> Fallible<(NodePtrRef, bool, Option<Value>)> {
+    ) -> Result<(NodePtrRef, bool, Option<Value>)> {
         let node_ref = self.cache.borrow_mut().deref_node_ptr(
             ctx,
             ptr.clone(),
-            FetcherSyncGet::new(key, true),
+            FetcherSyncGet::new(key, bit_depth, true),
         )?;
 
         match classify_noderef!(?node_ref) {
@@ -65,131 +65,44 @@ impl Tree {
             NodeKind::Internal => {
                 // Remove from internal node and recursively collapse the path, if needed.
                 let node_ref = node_ref.unwrap();
-                let (changed, old_val): (bool, Option<Value>);
-                let (remaining_leaf, remaining_left, remaining_right): (
-                    Option<NodeRef>,
-                    Option<NodeRef>,
-                    Option<NodeRef>,
-                );
-                if let NodeBox::Internal(ref mut n) = *node_ref.borrow_mut() {
-                    // Remove from internal node and recursively collapse the branch, if
-                    // needed.
-                    let bit_length = bit_depth + n.label_bit_length;
-
-                    if key.bit_length() < bit_length {
-                        // Lookup key is too short for the current n.Label, so it doesn't exist.
-                        return Ok((ptr.clone(), false, None));
-                    }
-
-                    let (new_child, c, o) = if key.bit_length() == bit_length {
-                        self._remove(ctx, n.leaf_node.clone(), bit_depth, key, depth)?
-                    } else if key.get_bit(bit_length) {
-                        self._remove(ctx, n.right.clone(), bit_length, key, depth + 1)?
-                    } else {
-                        self._remove(ctx, n.left.clone(), bit_length, key, depth + 1)?
-                    };
+                let (bit_length, changed, old_val) =
+                    if let NodeBox::Internal(ref mut n) = *node_ref.borrow_mut() {
+                        // Remove from internal node and recursively collapse the branch, if
+                        // needed.
+                        let bit_length = bit_depth + n.label_bit_length;
+
+                        if key.bit_length() < bit_length {
+                            // Lookup key is too short for the current n.Label, so it doesn't exist.
+                            return Ok((ptr.clone(), false, None));
+                        }
 
-                    changed = c;
-                    old_val = o;
+                        let (new_child, c, o) = if key.bit_length() == bit_length {
+                            self._remove(ctx, n.leaf_node.clone(), bit_depth, key, depth)?
+                        } else if key.get_bit(bit_length) {
+                            self._remove(ctx, n.right.clone(), bit_length, key, depth + 1)?
+                        } else {
+                            self._remove(ctx, n.left.clone(), bit_length, key, depth + 1)?
+                        };
+
+                        if key.bit_length() == bit_length {
+                            n.leaf_node = new_child;
+                        } else if key.get_bit(bit_length) {
+                            n.right = new_child;
+                        } else {
+                            n.left = new_child;
+                        }
 
-                    if key.bit_length() == bit_length {
-                        n.leaf_node = new_child;
-                    } else if key.get_bit(bit_length) {
-                        n.right = new_child;
+                        (bit_length, c, o)
                     } else {
-                        n.left = new_child;
-                    }
-
-                    // Fetch and check the remaining children.
-                    // NOTE: The leaf node is always included with the internal node.
-                    remaining_leaf = n.leaf_node.borrow().node.clone();
-                    remaining_left = self.cache.borrow_mut().deref_node_ptr(
-                        ctx,
-                        n.left.clone(),
-                        FetcherSyncGet::new(key, true),
-                    )?;
-                    remaining_right = self.cache.borrow_mut().deref_node_ptr(
-                        ctx,
-                        n.right.clone(),
-                        FetcherSyncGet::new(key, true),
-                    )?;
-                } else {
-                    unreachable!("node kind is Internal");
-                }
+                        unreachable!("node kind is Internal");
+                    };
 
                 // If exactly one child including LeafNode remains, collapse it.
-                match remaining_leaf {
-                    Some(_) => match remaining_left {
-                        Some(_) => (),
-                        None => match remaining_right {
-                            None => {
-                                let nd_leaf = noderef_as!(node_ref, Internal).leaf_node.clone();
-                                noderef_as_mut!(node_ref, Internal).leaf_node =
-                                    NodePointer::null_ptr();
-                                self.cache.borrow_mut().remove_node(ptr.clone());
-                                return Ok((nd_leaf, true, old_val));
-                            }
-                            Some(_) => (),
-                        },
-                    },
-                    None => {
-                        let mut nd_child: Option<NodeRef> = None;
-                        let mut node_ptr: NodePtrRef = NodePointer::null_ptr();
-                        let mut both_children = true;
-                        match remaining_left {
-                            Some(_) => match remaining_right {
-                                None => {
-                                    node_ptr = noderef_as!(node_ref, Internal).left.clone();
-                                    noderef_as_mut!(node_ref, Internal).left =
-                                        NodePointer::null_ptr();
-                                    nd_child = remaining_left;
-                                    both_children = false;
-                                }
-                                Some(_) => (),
-                            },
-                            None => match remaining_right {
-                                None => (),
-                                Some(_) => {
-                                    node_ptr = noderef_as!(node_ref, Internal).right.clone();
-                                    noderef_as_mut!(node_ref, Internal).right =
-                                        NodePointer::null_ptr();
-                                    nd_child = remaining_right;
-                                    both_children = false;
-                                }
-                            },
-                        }
-
-                        if !both_children {
-                            // If child is an internal node, also fix the label.
-                            match nd_child {
-                                Some(_) => match classify_noderef!(?nd_child) {
-                                    NodeKind::Internal => {
-                                        if let NodeBox::Internal(ref mut inode) =
-                                            *nd_child.unwrap().borrow_mut()
-                                        {
-                                            inode.label =
-                                                noderef_as!(node_ref, Internal).label.merge(
-                                                    noderef_as!(node_ref, Internal)
-                                                        .label_bit_length,
-                                                    &inode.label,
-                                                    inode.label_bit_length,
-                                                );
-                                            inode.label_bit_length +=
-                                                noderef_as!(node_ref, Internal).label_bit_length;
-                                            inode.clean = false;
-                                            node_ptr.borrow_mut().clean = false;
-                                        }
-                                    }
-                                    _ => (),
-                                },
-                                _ => (),
-                            }
-
-                            self.cache.borrow_mut().remove_node(ptr.clone());
-                            return Ok((node_ptr, true, old_val));
-                        }
-                    }
-                };
+                if let Some(new_ptr) =
+                    self._collapse(ctx, ptr.clone(), node_ref.clone(), bit_length, key)?
+                {
+                    return Ok((new_ptr, true, old_val));
+                }
 
                 // Two or more children including leaf_node remain, just mark dirty bit.
                 if changed {
@@ -216,4 +129,107 @@ impl Tree {
             }
         };
     }
+
+    /// Collapse the given internal node if at most one child including the
+    /// leaf node remains, returning the pointer that should replace it.
+    pub(super) fn _collapse(
+        &mut self,
+        ctx: &Arc<Context>,
+        ptr: NodePtrRef,
+        node_ref: NodeRef,
+        bit_length: Depth,
+        key: &Key,
+    ) -> Result<Option<NodePtrRef>> {
+        // Fetch and check the remaining children.
+        // NOTE: The leaf node is always included with the internal node.
+        let (leaf_node, left, right) = match *node_ref.borrow() {
+            NodeBox::Internal(ref n) => (n.leaf_node.clone(), n.left.clone(), n.right.clone()),
+            _ => unreachable!("node kind is Internal"),
+        };
+        let remaining_leaf = leaf_node.borrow().node.clone();
+        let remaining_left = self.cache.borrow_mut().deref_node_ptr(
+            ctx,
+            left,
+            FetcherSyncGet::new(key, bit_length, true),
+        )?;
+        let remaining_right = self.cache.borrow_mut().deref_node_ptr(
+            ctx,
+            right,
+            FetcherSyncGet::new(key, bit_length, true),
+        )?;
+
+        match remaining_leaf {
+            Some(_) => match remaining_left {
+                Some(_) => (),
+                None => match remaining_right {
+                    None => {
+                        let nd_leaf = noderef_as!(node_ref, Internal).leaf_node.clone();
+                        noderef_as_mut!(node_ref, Internal).leaf_node = NodePointer::null_ptr();
+                        self.cache.borrow_mut().remove_node(ptr.clone());
+                        return Ok(Some(nd_leaf));
+                    }
+                    Some(_) => (),
+                },
+            },
+            None => {
+                let mut nd_child: Option<NodeRef> = None;
+                let mut node_ptr: NodePtrRef = NodePointer::null_ptr();
+                let mut both_children = true;
+                match remaining_left {
+                    Some(_) => match remaining_right {
+                        None => {
+                            node_ptr = noderef_as!(node_ref, Internal).left.clone();
+                            noderef_as_mut!(node_ref, Internal).left = NodePointer::null_ptr();
+                            nd_child = remaining_left;
+                            both_children = false;
+                        }
+                        Some(_) => (),
+                    },
+                    None => match remaining_right {
+                        None => {
+                            // Nothing remains.
+                            self.cache.borrow_mut().remove_node(ptr.clone());
+                            return Ok(Some(NodePointer::null_ptr()));
+                        }
+                        Some(_) => {
+                            node_ptr = noderef_as!(node_ref, Internal).right.clone();
+                            noderef_as_mut!(node_ref, Internal).right = NodePointer::null_ptr();
+                            nd_child = remaining_right;
+                            both_children = false;
+                        }
+                    },
+                }
+
+                if !both_children {
+                    // If child is an internal node, also fix the label.
+                    match nd_child {
+                        Some(_) => match classify_noderef!(?nd_child) {
+                            NodeKind::Internal => {
+                                if let NodeBox::Internal(ref mut inode) =
+                                    *nd_child.unwrap().borrow_mut()
+                                {
+                                    inode.label = noderef_as!(node_ref, Internal).label.merge(
+                                        noderef_as!(node_ref, Internal).label_bit_length,
+                                        &inode.label,
+                                        inode.label_bit_length,
+                                    );
+                                    inode.label_bit_length +=
+                                        noderef_as!(node_ref, Internal).label_bit_length;
+                                    inode.clean = false;
+                                    node_ptr.borrow_mut().clean = false;
+                                }
+                            }
+                            _ => (),
+                        },
+                        _ => (),
+                    }
+
+                    self.cache.borrow_mut().remove_node(ptr.clone());
+                    return Ok(Some(node_ptr));
+                }
+            }
+        }
+
+        Ok(None)
+    }
 }
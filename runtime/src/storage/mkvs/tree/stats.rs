@@ -0,0 +1,97 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use anyhow::Result;
+use io_context::Context;
+
+use crate::storage::mkvs::{cache::*, tree::*, Prefix};
+
+/// Statistics about the contents of a tree.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TreeStats {
+    /// Maximum depth (in internal nodes) of any leaf node.
+    pub max_depth: Depth,
+    /// Number of internal nodes.
+    pub internal_node_count: u64,
+    /// Number of leaf nodes.
+    pub leaf_node_count: u64,
+    /// Total size of all keys.
+    pub leaf_key_size: u64,
+    /// Total size of all values.
+    pub leaf_value_size: u64,
+    /// Number of nodes which have been modified since the last commit.
+    pub dirty_node_count: u64,
+    /// Number of leaf nodes at each depth.
+    pub leaf_depths: BTreeMap<Depth, u64>,
+}
+
+impl Tree {
+    /// Traverse the tree and return statistics about its contents.
+    ///
+    /// Any nodes not present in the in-memory cache are fetched from the read
+    /// syncer, so this can be expensive for large trees. A non-zero `max_depth`
+    /// limits the traversal to the given depth.
+    ///
+    /// This is intended for debugging and planning purposes only.
+    pub fn stats(&self, ctx: Context, max_depth: Depth) -> Result<TreeStats> {
+        let ctx = ctx.freeze();
+        let pending_root = self.cache.borrow().get_pending_root();
+        let mut stats = TreeStats::default();
+        self._stats(&ctx, pending_root, 0, max_depth, &mut stats)?;
+        Ok(stats)
+    }
+
+    fn _stats(
+        &self,
+        ctx: &Arc<Context>,
+        ptr: NodePtrRef,
+        depth: Depth,
+        max_depth: Depth,
+        stats: &mut TreeStats,
+    ) -> Result<()> {
+        if max_depth > 0 && depth > max_depth {
+            return Ok(());
+        }
+
+        let prefixes = vec![Prefix::default()];
+        let node_ref = self.cache.borrow_mut().deref_node_ptr(
+            ctx,
+            ptr,
+            FetcherSyncGetPrefixes::new(&prefixes, u16::max_value()),
+        )?;
+
+        match classify_noderef!(?node_ref) {
+            NodeKind::None => {}
+            NodeKind::Internal => {
+                let node_ref = node_ref.unwrap();
+                stats.internal_node_count += 1;
+                if !node_ref.borrow().is_clean() {
+                    stats.dirty_node_count += 1;
+                }
+
+                let leaf_node = noderef_as!(node_ref, Internal).leaf_node.clone();
+                let left = noderef_as!(node_ref, Internal).left.clone();
+                let right = noderef_as!(node_ref, Internal).right.clone();
+
+                self._stats(ctx, leaf_node, depth, max_depth, stats)?;
+                self._stats(ctx, left, depth + 1, max_depth, stats)?;
+                self._stats(ctx, right, depth + 1, max_depth, stats)?;
+            }
+            NodeKind::Leaf => {
+                let node_ref = node_ref.unwrap();
+                stats.leaf_node_count += 1;
+                if !node_ref.borrow().is_clean() {
+                    stats.dirty_node_count += 1;
+                }
+
+                stats.leaf_key_size += noderef_as!(node_ref, Leaf).key.len() as u64;
+                stats.leaf_value_size += noderef_as!(node_ref, Leaf).value.len() as u64;
+                *stats.leaf_depths.entry(depth).or_insert(0) += 1;
+                if depth > stats.max_depth {
+                    stats.max_depth = depth;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
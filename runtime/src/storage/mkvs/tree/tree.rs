@@ -6,7 +6,10 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use crate::storage::mkvs::{cache::*, sync::*, tree::*};
+use crate::storage::{
+    mkvs::{cache::*, sync::*, tree::*},
+    SpillStorage,
+};
 
 pub struct PendingLogEntry {
     pub key: Vec<u8>,
@@ -18,6 +21,8 @@ pub struct PendingLogEntry {
 pub struct Options {
     node_capacity: usize,
     value_capacity: usize,
+    eviction_policy: EvictionPolicy,
+    spill_storage: Option<Arc<dyn SpillStorage>>,
     root: Option<Root>,
 }
 
@@ -37,6 +42,25 @@ impl Options {
         self
     }
 
+    /// Set the eviction policy of the underlying in-memory cache.
+    ///
+    /// If left unspecified, the cache will use a simple LRU eviction policy.
+    pub fn with_eviction_policy(mut self, policy: EvictionPolicy) -> Self {
+        self.eviction_policy = policy;
+        self
+    }
+
+    /// Spill nodes evicted from the in-memory cache to the given (untrusted)
+    /// storage, e.g., the host's spill storage.
+    ///
+    /// This allows a much larger effective cache in environments with tight
+    /// memory limits. Nodes loaded back from the storage are verified. Any
+    /// entries already in the storage are discarded.
+    pub fn with_spill_storage(mut self, storage: Arc<dyn SpillStorage>) -> Self {
+        self.spill_storage = Some(storage);
+        self
+    }
+
     /// Set an existing root as the root for the new tree.
     pub fn with_root(mut self, root: Root) -> Self {
         self.root = Some(root);
@@ -63,12 +87,17 @@ impl Tree {
             cache: RefCell::new(LRUCache::new(
                 opts.node_capacity,
                 opts.value_capacity,
+                opts.eviction_policy,
                 read_syncer,
             )),
             pending_write_log: BTreeMap::new(),
             lock: Arc::new(Mutex::new(0)),
         };
 
+        if let Some(ref storage) = opts.spill_storage {
+            tree.cache.borrow_mut().set_spill_storage(storage.clone());
+        }
+
         if let Some(root) = opts.root {
             tree.cache
                 .borrow_mut()
@@ -88,9 +117,47 @@ impl Tree {
         Options {
             node_capacity: 50_000,
             value_capacity: 16 * 1024 * 1024,
+            eviction_policy: EvictionPolicy::default(),
+            spill_storage: None,
             root: None,
         }
     }
+
+    /// Change the capacity of the underlying in-memory cache.
+    ///
+    /// Any cached nodes which no longer fit into the cache are evicted. See
+    /// `Options::with_capacity` for the meaning of the arguments.
+    pub fn set_cache_capacity(&self, node_capacity: usize, value_capacity: usize) {
+        self.cache
+            .borrow_mut()
+            .set_capacity(node_capacity, value_capacity);
+    }
+
+    /// Return statistics about the contents of the underlying in-memory cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.borrow().stats()
+    }
+
+    /// Return cache and read syncer usage metrics accumulated since the tree
+    /// was created or since the metrics were last reset.
+    pub fn metrics(&self) -> CacheMetrics {
+        self.cache.borrow().metrics()
+    }
+
+    /// Reset cache and read syncer usage metrics.
+    pub fn reset_metrics(&self) {
+        self.cache.borrow_mut().reset_metrics();
+    }
+
+    /// Switch the tree to a new root, discarding any uncommitted changes.
+    ///
+    /// In case the new root is in the same namespace, cached nodes which are
+    /// still part of the new tree are reused instead of being fetched again
+    /// from the read syncer, regardless of the version of the new root.
+    pub fn switch_root(&mut self, root: Root) {
+        self.pending_write_log.clear();
+        self.cache.borrow_mut().switch_root(root);
+    }
 }
 
 impl fmt::Debug for Tree {
@@ -138,3 +138,54 @@ fn bench_insert_no_commit_batch_100(b: &mut Bencher) {
 fn bench_insert_no_commit_batch_1000(b: &mut Bencher) {
     bench_insert_batch(b, 1000, false)
 }
+
+fn bench_update_batch(b: &mut Bencher, num_values: usize, commit: bool) {
+    let (mut tree, keys) = gen_tree();
+    tree.commit(Context::background(), Default::default(), 0)
+        .expect("commit");
+
+    let mut round = 0;
+    b.iter(|| {
+        round += 1;
+        for i in 0..num_values {
+            let key = &keys[(round * num_values + i) % keys.len()];
+            let value = format!("value {} {}", round, i);
+            tree.insert(Context::background(), key.as_ref(), value.as_bytes())
+                .expect("insert");
+        }
+        if commit {
+            tree.commit(Context::background(), Default::default(), round as u64)
+                .expect("commit");
+        }
+    });
+}
+
+#[bench]
+fn bench_update_commit_batch_10(b: &mut Bencher) {
+    bench_update_batch(b, 10, true)
+}
+
+#[bench]
+fn bench_update_commit_batch_100(b: &mut Bencher) {
+    bench_update_batch(b, 100, true)
+}
+
+#[bench]
+fn bench_update_commit_batch_1000(b: &mut Bencher) {
+    bench_update_batch(b, 1000, true)
+}
+
+#[bench]
+fn bench_update_no_commit_batch_10(b: &mut Bencher) {
+    bench_update_batch(b, 10, false)
+}
+
+#[bench]
+fn bench_update_no_commit_batch_100(b: &mut Bencher) {
+    bench_update_batch(b, 100, false)
+}
+
+#[bench]
+fn bench_update_no_commit_batch_1000(b: &mut Bencher) {
+    bench_update_batch(b, 1000, false)
+}
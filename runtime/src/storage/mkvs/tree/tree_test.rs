@@ -1,16 +1,27 @@
+use anyhow::Result;
 use io_context::Context;
 use serde_json;
-use std::{collections::HashSet, fs::File, io::BufReader, iter::FromIterator, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::BufReader,
+    iter::FromIterator,
+    path::Path,
+    sync::{Arc, Mutex},
+};
 
 use crate::{
     common::crypto::hash::Hash,
-    storage::mkvs::{
-        cache::*,
-        interop::{Driver, ProtocolServer},
-        sync::*,
-        tests,
-        tree::*,
-        LogEntry, LogEntryKind, WriteLog,
+    storage::{
+        mkvs::{
+            cache::*,
+            interop::{Driver, ProtocolServer},
+            sync::*,
+            tests,
+            tree::*,
+            LogEntry, LogEntryKind, MemoryTree, WriteLog, MKVS,
+        },
+        SpillStorage,
     },
 };
 
@@ -803,6 +814,97 @@ fn test_syncer_prefetch_prefixes() {
     assert_eq!(0, stats.sync_iterate_count, "sync_iterate count");
 }
 
+#[test]
+fn test_syncer_compressed() {
+    let server = ProtocolServer::new();
+
+    let mut tree = Tree::make()
+        .with_capacity(0, 0)
+        .new(Box::new(NoopReadSyncer {}));
+
+    let (keys, values) =
+        generate_key_value_pairs_ex("runtime/state/some/deep/namespace/".to_string(), 100);
+    for i in 0..keys.len() {
+        tree.insert(
+            Context::background(),
+            keys[i].as_slice(),
+            values[i].as_slice(),
+        )
+        .expect("insert");
+    }
+
+    let (write_log, hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    server.apply(&write_log, hash, Default::default(), 0);
+    let root = Root {
+        hash,
+        ..Default::default()
+    };
+
+    // Proofs served by the remote tree should honor the compression flag.
+    let pv = ProofVerifier;
+    let mut read_syncer = server.read_sync();
+    let size = |proof: &Proof| -> usize {
+        proof
+            .entries
+            .iter()
+            .map(|entry| entry.as_ref().map_or(0, |e| e.len()))
+            .sum()
+    };
+    for i in 0..keys.len() {
+        let request = |compressed| GetRequest {
+            tree: TreeID {
+                root,
+                position: hash,
+            },
+            key: keys[i].clone(),
+            include_siblings: false,
+            compressed,
+        };
+        let full = read_syncer
+            .sync_get(Context::background(), request(false))
+            .expect("sync_get")
+            .proof;
+        let compressed = read_syncer
+            .sync_get(Context::background(), request(true))
+            .expect("sync_get")
+            .proof;
+        assert!(compressed.entries.iter().any(|entry| entry
+            .as_ref()
+            .map_or(false, |e| e[0] == PROOF_ENTRY_COMPRESSED)));
+        assert!(size(&compressed) < size(&full));
+
+        let value = pv
+            .verify_proof_for_key(Context::background(), hash, &keys[i], &compressed)
+            .expect("verify_proof_for_key");
+        assert_eq!(value, Some(values[i].clone()));
+    }
+
+    // Trees should be able to sync using compressed proofs, including proofs for
+    // subtrees which are not rooted at the tree root.
+    let remote_tree = Tree::make()
+        .with_capacity(0, 0)
+        .with_root(root)
+        .new(server.read_sync());
+    for i in 0..keys.len() {
+        let value = remote_tree
+            .get(Context::background(), keys[i].as_slice())
+            .expect("get")
+            .expect("get_some");
+        assert_eq!(values[i], value.as_slice());
+    }
+    assert!(remote_tree.cache.borrow().metrics().sync_round_trips > 1);
+
+    let remote_tree = Tree::make()
+        .with_capacity(0, 0)
+        .with_root(root)
+        .new(server.read_sync());
+    let mut it = remote_tree.iter(Context::background());
+    it.set_prefetch(10);
+    it.rewind();
+    assert_eq!(it.count(), keys.len());
+}
+
 #[test]
 fn test_value_eviction() {
     let mut tree = Tree::make()
@@ -874,6 +976,668 @@ fn test_node_eviction() {
     );
 }
 
+#[test]
+fn test_cache_resize() {
+    let mut tree = Tree::make()
+        .with_capacity(0, 0)
+        .new(Box::new(NoopReadSyncer {}));
+
+    let (keys, values) = generate_key_value_pairs();
+    for i in 0..keys.len() {
+        tree.insert(
+            Context::background(),
+            keys[i].as_slice(),
+            values[i].as_slice(),
+        )
+        .expect("insert");
+    }
+    Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+
+    assert_eq!(
+        999,
+        tree.cache_stats().internal_node_count,
+        "cache.internal_node_count"
+    );
+
+    // Shrinking the cache should evict nodes and values.
+    tree.set_cache_capacity(128, 512);
+    let stats = tree.cache_stats();
+    assert!(
+        stats.internal_node_count <= 128,
+        "cache.internal_node_count after resize"
+    );
+    assert!(
+        stats.leaf_value_size <= 512,
+        "cache.leaf_value_size after resize"
+    );
+}
+
+#[test]
+fn test_segmented_eviction() {
+    let mut tree = Tree::make()
+        .with_capacity(128, 0)
+        .with_eviction_policy(EvictionPolicy::Segmented)
+        .new(Box::new(NoopReadSyncer {}));
+
+    let (keys, values) = generate_key_value_pairs_ex("foo".to_string(), 150);
+    for i in 0..keys.len() {
+        tree.insert(
+            Context::background(),
+            keys[i].as_slice(),
+            values[i].as_slice(),
+        )
+        .expect("insert");
+    }
+    Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+
+    let (keys, values) = generate_key_value_pairs_ex("foo key 1".to_string(), 150);
+    for i in 0..keys.len() {
+        tree.insert(
+            Context::background(),
+            keys[i].as_slice(),
+            values[i].as_slice(),
+        )
+        .expect("insert");
+    }
+    Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+
+    // Only a subset of nodes should remain in cache.
+    assert_eq!(
+        128,
+        tree.cache_stats().internal_node_count,
+        "cache.internal_node_count"
+    );
+}
+
+#[test]
+fn test_metrics() {
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+    tree.insert(Context::background(), b"foo", b"bar")
+        .expect("insert");
+    tree.insert(Context::background(), b"moo", b"boo")
+        .expect("insert");
+    Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+
+    tree.get(Context::background(), b"foo").expect("get");
+
+    let metrics = tree.metrics();
+    assert_eq!(metrics.commits, 1, "metrics.commits");
+    assert_eq!(metrics.write_log_entries, 2, "metrics.write_log_entries");
+    assert_eq!(metrics.write_log_bytes, 12, "metrics.write_log_bytes");
+    assert!(metrics.hits > 0, "metrics.hits");
+    assert_eq!(metrics.misses, 0, "metrics.misses");
+    assert_eq!(metrics.sync_round_trips, 0, "metrics.sync_round_trips");
+
+    tree.reset_metrics();
+    assert_eq!(tree.metrics(), CacheMetrics::default());
+}
+
+#[test]
+fn test_apply_write_log() {
+    let (keys, values) = generate_key_value_pairs();
+
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+    for i in 0..keys.len() {
+        tree.insert(
+            Context::background(),
+            keys[i].as_slice(),
+            values[i].as_slice(),
+        )
+        .expect("insert");
+    }
+    tree.remove(Context::background(), keys[0].as_slice())
+        .expect("remove");
+    let (_, expected_hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+
+    // Apply the same updates as a single (unsorted, duplicated) batch.
+    let mut write_log: WriteLog = keys
+        .iter()
+        .zip(values.iter())
+        .rev()
+        .map(|(key, value)| LogEntry {
+            key: key.clone(),
+            value: Some(value.clone()),
+        })
+        .collect();
+    write_log.push(LogEntry {
+        key: keys[0].clone(),
+        value: None,
+    });
+
+    let mut batch_tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+    batch_tree
+        .apply_write_log(Context::background(), write_log)
+        .expect("apply_write_log");
+    let (_, hash) = Tree::commit(
+        &mut batch_tree,
+        Context::background(),
+        Default::default(),
+        0,
+    )
+    .expect("commit");
+    assert_eq!(hash, expected_hash);
+
+    // Batches should also apply on top of committed state.
+    batch_tree
+        .apply_write_log(
+            Context::background(),
+            vec![
+                LogEntry {
+                    key: keys[1].clone(),
+                    value: None,
+                },
+                LogEntry {
+                    key: keys[0].clone(),
+                    value: Some(values[0].clone()),
+                },
+            ],
+        )
+        .expect("apply_write_log");
+    assert_eq!(
+        batch_tree
+            .get(Context::background(), keys[0].as_slice())
+            .expect("get"),
+        Some(values[0].clone())
+    );
+    assert_eq!(
+        batch_tree
+            .get(Context::background(), keys[1].as_slice())
+            .expect("get"),
+        None
+    );
+}
+
+#[test]
+fn test_apply_write_log_mixed() {
+    let (keys, values) = generate_key_value_pairs();
+    let (long_keys, long_values) = generate_long_key_value_pairs();
+
+    let make_base = || {
+        let mut tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+        for i in 0..keys.len() {
+            tree.insert(
+                Context::background(),
+                keys[i].as_slice(),
+                values[i].as_slice(),
+            )
+            .expect("insert");
+        }
+        for i in (0..long_keys.len()).step_by(2) {
+            tree.insert(
+                Context::background(),
+                long_keys[i].as_slice(),
+                long_values[i].as_slice(),
+            )
+            .expect("insert");
+        }
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+        tree
+    };
+
+    // Mix removes (including of missing keys), updates and inserts of keys which are
+    // prefixes of each other.
+    let mut write_log: WriteLog = Vec::new();
+    for i in 0..keys.len() {
+        match i % 3 {
+            0 => write_log.push(LogEntry {
+                key: keys[i].clone(),
+                value: None,
+            }),
+            1 => write_log.push(LogEntry {
+                key: keys[i].clone(),
+                value: Some(format!("updated value {}", i).into_bytes()),
+            }),
+            _ => {}
+        }
+        write_log.push(LogEntry {
+            key: format!("missing {}", i).into_bytes(),
+            value: None,
+        });
+    }
+    for i in 0..long_keys.len() {
+        write_log.push(LogEntry {
+            key: long_keys[i].clone(),
+            value: if i % 2 == 0 {
+                None
+            } else {
+                Some(long_values[i].clone())
+            },
+        });
+    }
+
+    let mut tree = make_base();
+    for entry in &write_log {
+        match entry.value {
+            Some(ref value) => {
+                tree.insert(Context::background(), &entry.key, value)
+                    .expect("insert");
+            }
+            None => {
+                tree.remove(Context::background(), &entry.key)
+                    .expect("remove");
+            }
+        }
+    }
+    let (mut expected_write_log, expected_hash) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 1).expect("commit");
+    expected_write_log.sort_by(|a, b| a.key.cmp(&b.key));
+
+    let mut batch_tree = make_base();
+    batch_tree
+        .apply_write_log(Context::background(), write_log)
+        .expect("apply_write_log");
+    let (mut batch_write_log, hash) = Tree::commit(
+        &mut batch_tree,
+        Context::background(),
+        Default::default(),
+        1,
+    )
+    .expect("commit");
+    batch_write_log.sort_by(|a, b| a.key.cmp(&b.key));
+    assert_eq!(hash, expected_hash);
+    assert_eq!(batch_write_log, expected_write_log);
+
+    // Removing all remaining keys in a single batch should result in an empty tree.
+    let write_log: WriteLog = keys
+        .iter()
+        .chain(long_keys.iter())
+        .map(|key| LogEntry {
+            key: key.clone(),
+            value: None,
+        })
+        .collect();
+    batch_tree
+        .apply_write_log(Context::background(), write_log)
+        .expect("apply_write_log");
+    let (_, hash) = Tree::commit(
+        &mut batch_tree,
+        Context::background(),
+        Default::default(),
+        2,
+    )
+    .expect("commit");
+    assert_eq!(hash, Hash::empty_hash());
+}
+
+#[test]
+fn test_get_proof() {
+    let (keys, values) = generate_key_value_pairs_ex("".to_string(), 100);
+
+    let mut tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+    for i in 0..keys.len() {
+        tree.insert(
+            Context::background(),
+            keys[i].as_slice(),
+            values[i].as_slice(),
+        )
+        .expect("insert");
+    }
+
+    // Proofs can only be generated for committed roots.
+    assert!(tree.get_proof(Context::background(), &keys[0]).is_err());
+
+    let (_, root) =
+        Tree::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+
+    let pv = ProofVerifier;
+    for i in 0..keys.len() {
+        let proof = tree
+            .get_proof(Context::background(), &keys[i])
+            .expect("get_proof");
+        let value = pv
+            .verify_proof_for_key(Context::background(), root, &keys[i], &proof)
+            .expect("verify_proof_for_key");
+        assert_eq!(value, Some(values[i].clone()));
+
+        // Proof should not verify against a different root.
+        assert!(pv
+            .verify_proof_for_key(Context::background(), Hash::empty_hash(), &keys[i], &proof)
+            .is_err());
+    }
+
+    // Non-inclusion proofs.
+    for key in &[b"missing".to_vec(), b"key".to_vec(), b"key 1000".to_vec()] {
+        let proof = tree
+            .get_proof(Context::background(), key)
+            .expect("get_proof");
+        let value = pv
+            .verify_proof_for_key(Context::background(), root, key, &proof)
+            .expect("verify_proof_for_key");
+        assert_eq!(value, None);
+    }
+
+    // A proof for one key should not be usable for an unrelated key.
+    let proof = tree
+        .get_proof(Context::background(), &keys[0])
+        .expect("get_proof");
+    let result = pv.verify_proof_for_key(Context::background(), root, &keys[50], &proof);
+    match result {
+        Err(err) => match err.downcast_ref::<IntegrityError>() {
+            Some(IntegrityError::IncompleteProof) => {}
+            _ => panic!("unexpected error: {:?}", err),
+        },
+        Ok(_) => panic!("proof should not cover an unrelated key"),
+    }
+
+    // Proofs for an empty tree.
+    let tree = Tree::make().new(Box::new(NoopReadSyncer {}));
+    let proof = tree
+        .get_proof(Context::background(), b"foo")
+        .expect("get_proof");
+    let value = pv
+        .verify_proof_for_key(Context::background(), Hash::empty_hash(), b"foo", &proof)
+        .expect("verify_proof_for_key");
+    assert_eq!(value, None);
+}
+
+#[test]
+fn test_compressed_proof() {
+    let (keys, values) =
+        generate_key_value_pairs_ex("runtime/state/some/deep/namespace/".to_string(), 100);
+
+    let mut tree = MemoryTree::new();
+    for i in 0..keys.len() {
+        MKVS::insert(&mut tree, Context::background(), &keys[i], &values[i]).expect("insert");
+    }
+    let (_, hash) =
+        MKVS::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    let root = Root {
+        hash,
+        ..Default::default()
+    };
+
+    let pv = ProofVerifier;
+    let mut read_syncer = tree.store().read_syncer();
+    for i in 0..keys.len() {
+        let rsp = read_syncer
+            .sync_get(
+                Context::background(),
+                GetRequest {
+                    tree: TreeID {
+                        root,
+                        position: hash,
+                    },
+                    key: keys[i].clone(),
+                    include_siblings: false,
+                    compressed: true,
+                },
+            )
+            .expect("sync_get");
+        let value = pv
+            .verify_proof_for_key(Context::background(), hash, &keys[i], &rsp.proof)
+            .expect("verify_proof_for_key");
+        assert_eq!(value, Some(values[i].clone()));
+
+        // Compressed proofs should be smaller than uncompressed proofs for the same key.
+        let proof = tree
+            .get_proof(Context::background(), &keys[i])
+            .expect("get_proof");
+        assert_eq!(proof.entries.len(), rsp.proof.entries.len());
+        let size = |proof: &Proof| -> usize {
+            proof
+                .entries
+                .iter()
+                .map(|entry| entry.as_ref().map_or(0, |e| e.len()))
+                .sum()
+        };
+        assert!(size(&rsp.proof) < size(&proof));
+    }
+
+    // Trees backed by compressed proofs should work as usual.
+    let remote = Tree::make()
+        .with_capacity(0, 0)
+        .with_root(root)
+        .new(Box::new(tree.store().read_syncer()));
+    for i in 0..keys.len() {
+        assert_eq!(
+            remote.get(Context::background(), &keys[i]).expect("get"),
+            Some(values[i].clone())
+        );
+    }
+}
+
+#[test]
+fn test_switch_root() {
+    let (keys, values) = generate_key_value_pairs_ex("".to_string(), 100);
+
+    let mut tree = MemoryTree::new();
+    for i in 0..keys.len() {
+        MKVS::insert(&mut tree, Context::background(), &keys[i], &values[i]).expect("insert");
+    }
+    let (_, hash0) =
+        MKVS::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    let root0 = Root {
+        hash: hash0,
+        version: 0,
+        ..Default::default()
+    };
+
+    // Derive the next version which only updates a single key.
+    let store = tree.store().clone();
+    let mut writer = store.open(root0);
+    MKVS::insert(&mut writer, Context::background(), &keys[0], b"updated").expect("insert");
+    let (_, hash1) =
+        MKVS::commit(&mut writer, Context::background(), Default::default(), 1).expect("commit");
+    let root1 = Root {
+        hash: hash1,
+        version: 1,
+        ..Default::default()
+    };
+
+    let read_all = |tree: &Tree| {
+        for i in 1..keys.len() {
+            assert_eq!(
+                tree.get(Context::background(), &keys[i]).expect("get"),
+                Some(values[i].clone())
+            );
+        }
+    };
+
+    let mut tree = Tree::make()
+        .with_root(root0)
+        .new(Box::new(store.read_syncer()));
+    read_all(&tree);
+
+    // Switching to a later root should retain nodes which did not change.
+    tree.switch_root(root1);
+    tree.reset_metrics();
+    read_all(&tree);
+    assert_eq!(
+        tree.get(Context::background(), &keys[0]).expect("get"),
+        Some(b"updated".to_vec())
+    );
+    let retained_round_trips = tree.metrics().sync_round_trips;
+
+    let fresh = Tree::make()
+        .with_root(root1)
+        .new(Box::new(store.read_syncer()));
+    read_all(&fresh);
+    assert!(
+        retained_round_trips < fresh.metrics().sync_round_trips,
+        "retained cache should reduce round trips"
+    );
+
+    // Uncommitted changes should be discarded.
+    tree.insert(Context::background(), b"pending", b"value")
+        .expect("insert");
+    tree.switch_root(root1);
+    assert_eq!(
+        tree.get(Context::background(), b"pending").expect("get"),
+        None
+    );
+
+    // Switching to an unrelated root should clear the cache.
+    tree.switch_root(Root {
+        hash: hash1,
+        version: 1,
+        root_type: RootType::IO,
+        ..Default::default()
+    });
+    assert_eq!(tree.cache_stats().internal_node_count, 0);
+    read_all(&tree);
+}
+
+#[test]
+fn test_stats() {
+    let mut tree = MemoryTree::new();
+    let mut value_size = 0;
+    for i in 0..100 {
+        let key = format!("key {:03}", i).into_bytes();
+        let value = format!("value {}", i).into_bytes();
+        value_size += value.len() as u64;
+        MKVS::insert(&mut tree, Context::background(), &key, &value).expect("insert");
+    }
+
+    let stats = tree.stats(Context::background(), 0).expect("stats");
+    assert_eq!(stats.leaf_node_count, 100);
+    assert_eq!(stats.internal_node_count, 99);
+    assert_eq!(stats.dirty_node_count, 199);
+    assert_eq!(stats.leaf_key_size, 700);
+    assert_eq!(stats.leaf_value_size, value_size);
+    assert_eq!(stats.leaf_depths.values().sum::<u64>(), 100);
+    assert_eq!(
+        stats.leaf_depths.keys().max().cloned(),
+        Some(stats.max_depth)
+    );
+
+    let (_, hash) = tree
+        .commit(Context::background(), Default::default(), 0)
+        .expect("commit");
+    let committed = tree.stats(Context::background(), 0).expect("stats");
+    assert_eq!(committed.dirty_node_count, 0);
+    assert_eq!(
+        committed,
+        TreeStats {
+            dirty_node_count: 0,
+            ..stats
+        }
+    );
+
+    // Nodes should be fetched from the read syncer as needed.
+    let remote_tree = tree.store().open(Root {
+        hash,
+        ..Default::default()
+    });
+    assert_eq!(
+        remote_tree.stats(Context::background(), 0).expect("stats"),
+        committed
+    );
+
+    // Limiting the depth should skip deeper leaves.
+    let limited = tree.stats(Context::background(), 2).expect("stats");
+    assert!(limited.max_depth <= 2);
+    assert!(limited.leaf_node_count < committed.leaf_node_count);
+}
+
+#[derive(Default)]
+struct MemorySpillStorage {
+    entries: Mutex<HashMap<Vec<u8>, Vec<u8>>>,
+    batches: Mutex<u64>,
+}
+
+impl SpillStorage for MemorySpillStorage {
+    fn get(&self, key: Vec<u8>) -> Result<Vec<u8>> {
+        Ok(self
+            .entries
+            .lock()
+            .unwrap()
+            .get(&key)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn insert_batch(&self, entries: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+        *self.batches.lock().unwrap() += 1;
+        self.entries.lock().unwrap().extend(entries);
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.entries.lock().unwrap().clear();
+        Ok(())
+    }
+}
+
+#[test]
+fn test_spill_storage() {
+    let (keys, values) = generate_key_value_pairs_ex("".to_string(), 100);
+
+    let mut tree = MemoryTree::new();
+    for i in 0..keys.len() {
+        MKVS::insert(&mut tree, Context::background(), &keys[i], &values[i]).expect("insert");
+    }
+    let (_, hash) =
+        MKVS::commit(&mut tree, Context::background(), Default::default(), 0).expect("commit");
+    let root = Root {
+        hash,
+        ..Default::default()
+    };
+
+    let read_all = |tree: &Tree| {
+        for i in 0..keys.len() {
+            assert_eq!(
+                tree.get(Context::background(), &keys[i]).expect("get"),
+                Some(values[i].clone())
+            );
+        }
+    };
+
+    let spill = Arc::new(MemorySpillStorage::default());
+    let mut spilled = Tree::make()
+        .with_capacity(10, 0)
+        .with_spill_storage(spill.clone())
+        .with_root(root)
+        .new(Box::new(tree.store().read_syncer()));
+    read_all(&spilled);
+    let first = spilled.metrics();
+    assert!(first.nodes_spilled > 0);
+    read_all(&spilled);
+    let second = spilled.metrics();
+    assert!(second.spill_hits > first.spill_hits);
+    // Each node fetched from the read syncer is spilled at most once, nodes
+    // loaded from the spill storage are not spilled again.
+    assert!(second.nodes_spilled <= second.nodes_fetched);
+    // Nodes evicted together are spilled in a single batch.
+    assert!(*spill.batches.lock().unwrap() < second.nodes_spilled);
+
+    // Spilled nodes should save round trips to the read syncer.
+    let plain = Tree::make()
+        .with_capacity(10, 0)
+        .with_root(root)
+        .new(Box::new(tree.store().read_syncer()));
+    read_all(&plain);
+    read_all(&plain);
+    assert!(second.sync_round_trips < plain.metrics().sync_round_trips);
+
+    // Corrupted nodes in the spill storage must not be used. The node is at
+    // the end of the envelope, so the envelope itself remains well-formed.
+    for value in spill.entries.lock().unwrap().values_mut() {
+        let last = value.len() - 1;
+        value[last] ^= 0xff;
+    }
+    read_all(&spilled);
+
+    // Switching to an unrelated root removes all spilled entries.
+    assert!(!spill.entries.lock().unwrap().is_empty());
+    spilled.switch_root(Root {
+        root_type: RootType::IO,
+        ..root
+    });
+    assert!(spill.entries.lock().unwrap().is_empty());
+
+    // Attaching the storage to a new tree removes any leftover entries.
+    spill
+        .entries
+        .lock()
+        .unwrap()
+        .insert(b"leftover".to_vec(), b"value".to_vec());
+    let _ = Tree::make()
+        .with_spill_storage(spill.clone())
+        .new(Box::new(tree.store().read_syncer()));
+    assert!(spill.entries.lock().unwrap().is_empty());
+}
+
 /// Location of the test vectors directory (from Go).
 const TEST_VECTORS_DIR: &'static str = "../go/storage/mkvs/testdata";
 
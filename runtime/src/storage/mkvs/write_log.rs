@@ -0,0 +1,116 @@
+//! Write log helpers.
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use serde_bytes::{self, ByteBuf};
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::storage::mkvs::{LogEntry, WriteLog};
+
+/// Write log compression error.
+#[derive(Debug, Error)]
+pub enum WriteLogError {
+    #[error("mkvs: malformed compressed write log")]
+    Malformed,
+}
+
+/// Deduplicate the given write log.
+///
+/// When the same key is written multiple times, only the last write is kept.
+/// The resulting write log is sorted by key.
+pub fn dedup_write_log(write_log: WriteLog) -> WriteLog {
+    let mut entries = BTreeMap::new();
+    for entry in write_log {
+        entries.insert(entry.key.clone(), entry);
+    }
+    entries.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// An entry in the compressed write log.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressedLogEntry {
+    /// Length of the prefix shared with the previous entry's key.
+    pub shared: u32,
+    /// Remainder of the key after the shared prefix.
+    #[serde(with = "serde_bytes")]
+    pub suffix: Vec<u8>,
+    /// Index into the value table (none if the key was deleted).
+    pub value: Option<u32>,
+}
+
+/// A compact representation of a write log.
+///
+/// Entries are sorted by key and deduplicated, keys are prefix-compressed
+/// against the previous key and identical values are only stored once.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressedWriteLog {
+    /// Unique values referenced by the entries.
+    pub values: Vec<ByteBuf>,
+    /// Compressed write log entries.
+    pub entries: Vec<CompressedLogEntry>,
+}
+
+impl CompressedWriteLog {
+    /// Compress the given write log.
+    pub fn compress(write_log: WriteLog) -> Self {
+        let mut compressed = CompressedWriteLog::default();
+        let mut value_index: HashMap<Vec<u8>, u32> = HashMap::new();
+        let mut previous_key: &[u8] = &[];
+
+        let write_log = dedup_write_log(write_log);
+        for entry in &write_log {
+            let shared = previous_key
+                .iter()
+                .zip(entry.key.iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            let value = entry.value.as_ref().map(|value| {
+                let next_index = compressed.values.len() as u32;
+                *value_index.entry(value.clone()).or_insert_with(|| {
+                    compressed.values.push(ByteBuf::from(value.clone()));
+                    next_index
+                })
+            });
+
+            compressed.entries.push(CompressedLogEntry {
+                shared: shared as u32,
+                suffix: entry.key[shared..].to_vec(),
+                value,
+            });
+            previous_key = &entry.key;
+        }
+
+        compressed
+    }
+
+    /// Decompress into a regular write log.
+    pub fn decompress(self) -> Result<WriteLog> {
+        let mut write_log = WriteLog::with_capacity(self.entries.len());
+        let mut previous_key: Vec<u8> = Vec::new();
+
+        for entry in self.entries {
+            let shared = entry.shared as usize;
+            if shared > previous_key.len() {
+                return Err(WriteLogError::Malformed.into());
+            }
+
+            let mut key = previous_key[..shared].to_vec();
+            key.extend_from_slice(&entry.suffix);
+
+            let value = match entry.value {
+                Some(index) => match self.values.get(index as usize) {
+                    Some(value) => Some(value.to_vec()),
+                    None => return Err(WriteLogError::Malformed.into()),
+                },
+                None => None,
+            };
+
+            previous_key = key.clone();
+            write_log.push(LogEntry { key, value });
+        }
+
+        Ok(write_log)
+    }
+}
@@ -1,7 +1,7 @@
 //! Runtime storage interfaces and implementations.
 use std::sync::Arc;
 
-use failure::Fallible;
+use anyhow::Result;
 
 pub mod context;
 pub mod mkvs;
@@ -12,18 +12,34 @@ pub use self::{context::StorageContext, mkvs::MKVS};
 /// Trivial Key/Value storage.
 pub trait KeyValue: Send + Sync {
     /// Fetch the value for a specific key.
-    fn get(&self, key: Vec<u8>) -> Fallible<Vec<u8>>;
+    fn get(&self, key: Vec<u8>) -> Result<Vec<u8>>;
 
     /// Store a specific key/value into storage.
-    fn insert(&self, key: Vec<u8>, value: Vec<u8>) -> Fallible<()>;
+    fn insert(&self, key: Vec<u8>, value: Vec<u8>) -> Result<()>;
+}
+
+/// Untrusted storage for nodes evicted from the MKVS cache.
+///
+/// Spill storage is kept separate from the runtime's own key/value storage
+/// and only ever holds data which can be refetched, so the host is free to
+/// drop entries at any time.
+pub trait SpillStorage: Send + Sync {
+    /// Fetch the value for a specific key, or an empty value if not present.
+    fn get(&self, key: Vec<u8>) -> Result<Vec<u8>>;
+
+    /// Store a batch of key/value pairs into storage.
+    fn insert_batch(&self, entries: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()>;
+
+    /// Remove all entries from storage.
+    fn clear(&self) -> Result<()>;
 }
 
 impl<T: ?Sized + KeyValue> KeyValue for Arc<T> {
-    fn get(&self, key: Vec<u8>) -> Fallible<Vec<u8>> {
+    fn get(&self, key: Vec<u8>) -> Result<Vec<u8>> {
         KeyValue::get(&**self, key)
     }
 
-    fn insert(&self, key: Vec<u8>, value: Vec<u8>) -> Fallible<()> {
+    fn insert(&self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
         KeyValue::insert(&**self, key, value)
     }
 }
@@ -1,8 +1,91 @@
 //! Tracing helpers.
+//!
+//! Spans are created with the `tracing` crate. To make a single trace span
+//! the client, the node and the runtime, span contexts are propagated in
+//! protocol messages and EnclaveRPC frames in the Jaeger binary format,
+//! which is the format used by the node's tracer.
+use std::convert::TryInto;
+
+use anyhow::Result;
 use io_context::Context;
+use thiserror::Error;
 
 const TRACING_SPAN_CONTEXT_KEY: &'static str = "OASIS_TRACING_SPAN_CONTEXT";
 
+/// Name of the gRPC metadata header carrying the span context.
+pub const TRACE_HEADER: &'static str = "uber-trace-id";
+
+/// Size of an encoded span context without baggage.
+const SPAN_CONTEXT_SIZE: usize = 37;
+/// Flag marking the trace as sampled.
+const FLAG_SAMPLED: u8 = 1;
+
+/// Span context error.
+#[derive(Debug, Error)]
+pub enum SpanContextError {
+    #[error("malformed span context")]
+    Malformed,
+}
+
+/// A span context as propagated between processes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SpanContext {
+    /// Trace identifier.
+    pub trace_id: u128,
+    /// Span identifier.
+    pub span_id: u64,
+    /// Parent span identifier, zero for root spans.
+    pub parent_id: u64,
+    /// Trace flags.
+    pub flags: u8,
+}
+
+impl SpanContext {
+    /// Check whether the trace is sampled.
+    pub fn is_sampled(&self) -> bool {
+        self.flags & FLAG_SAMPLED != 0
+    }
+
+    /// Decode a span context in the Jaeger binary format.
+    ///
+    /// Any baggage items are ignored.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        if data.len() < SPAN_CONTEXT_SIZE {
+            return Err(SpanContextError::Malformed.into());
+        }
+
+        let u64_at =
+            |offset: usize| u64::from_be_bytes(data[offset..offset + 8].try_into().unwrap());
+        Ok(Self {
+            trace_id: (u128::from(u64_at(0)) << 64) | u128::from(u64_at(8)),
+            span_id: u64_at(16),
+            parent_id: u64_at(24),
+            flags: data[32],
+        })
+    }
+
+    /// Encode the span context in the Jaeger binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(SPAN_CONTEXT_SIZE);
+        data.extend_from_slice(&((self.trace_id >> 64) as u64).to_be_bytes());
+        data.extend_from_slice(&(self.trace_id as u64).to_be_bytes());
+        data.extend_from_slice(&self.span_id.to_be_bytes());
+        data.extend_from_slice(&self.parent_id.to_be_bytes());
+        data.push(self.flags);
+        // No baggage.
+        data.extend_from_slice(&0u32.to_be_bytes());
+        data
+    }
+
+    /// Encode the span context as the value of the `TRACE_HEADER` header.
+    pub fn to_header_value(&self) -> String {
+        format!(
+            "{:032x}:{:016x}:{:x}:{:x}",
+            self.trace_id, self.span_id, self.parent_id, self.flags
+        )
+    }
+}
+
 /// Add a tracing span context to the provided `Context`.
 pub fn add_span_context(ctx: &mut Context, span_context: Vec<u8>) {
     ctx.add_value(TRACING_SPAN_CONTEXT_KEY, span_context);
@@ -12,3 +95,153 @@ pub fn add_span_context(ctx: &mut Context, span_context: Vec<u8>) {
 pub fn get_span_context(ctx: &Context) -> Option<&Vec<u8>> {
     ctx.get_value(TRACING_SPAN_CONTEXT_KEY)
 }
+
+/// Make the span context in the provided `Context`, if any, the remote
+/// parent of the given span and replace it with the context under which
+/// the span is exported.
+///
+/// Requests made with the `Context` are then attributed to the span. If
+/// the span is not exported, the `Context` is left unchanged so that remote
+/// spans are still attributed to the caller's trace.
+pub fn enter_span(ctx: &mut Context, span: &::tracing::Span) -> Option<SpanContext> {
+    let parent = get_span_context(ctx).and_then(|data| SpanContext::from_bytes(data).ok());
+    if let Some(ref parent) = parent {
+        set_remote_parent(span, parent);
+    }
+
+    match exported_span_context(span) {
+        Some(mut span_context) => {
+            span_context.parent_id = parent.map_or(0, |parent| parent.span_id);
+            add_span_context(ctx, span_context.to_bytes());
+            Some(span_context)
+        }
+        None => parent,
+    }
+}
+
+/// Make the given span context the remote parent of the given span.
+///
+/// Without the `jaeger` feature, spans are not exported and this does
+/// nothing.
+pub fn set_remote_parent(span: &::tracing::Span, parent: &SpanContext) {
+    #[cfg(feature = "jaeger")]
+    {
+        use opentelemetry::api;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        span.set_parent(api::SpanContext::new(
+            api::TraceId::from_u128(parent.trace_id),
+            api::SpanId::from_u64(parent.span_id),
+            parent.flags,
+            true,
+        ));
+    }
+
+    #[cfg(not(feature = "jaeger"))]
+    let _ = (span, parent);
+}
+
+/// Return the context under which the given span is exported, without
+/// the parent span identifier.
+///
+/// Returns `None` if the span is not exported, e.g., without the `jaeger`
+/// feature or when the Jaeger subscriber is not installed.
+fn exported_span_context(span: &::tracing::Span) -> Option<SpanContext> {
+    #[cfg(feature = "jaeger")]
+    {
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let span_context = span.context();
+        if !span_context.is_valid() {
+            return None;
+        }
+        Some(SpanContext {
+            trace_id: span_context.trace_id().to_u128(),
+            span_id: span_context.span_id().to_u64(),
+            parent_id: 0,
+            flags: span_context.trace_flags(),
+        })
+    }
+
+    #[cfg(not(feature = "jaeger"))]
+    {
+        let _ = span;
+        None
+    }
+}
+
+/// Export all spans to the Jaeger agent at the given address.
+///
+/// This installs a global `tracing` subscriber and must be called at most
+/// once.
+#[cfg(feature = "jaeger")]
+pub fn init_jaeger(service_name: &str, agent_endpoint: std::net::SocketAddr) -> Result<()> {
+    use opentelemetry::{api::Provider, sdk};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let exporter = opentelemetry_jaeger::Exporter::builder()
+        .with_agent_endpoint(agent_endpoint)
+        .with_process(opentelemetry_jaeger::Process {
+            service_name: service_name.to_owned(),
+            tags: Vec::new(),
+        })
+        .init()?;
+    let provider = sdk::Provider::builder()
+        .with_simple_exporter(exporter)
+        .with_config(sdk::Config {
+            default_sampler: Box::new(sdk::Sampler::Always),
+            ..Default::default()
+        })
+        .build();
+    let layer =
+        tracing_opentelemetry::OpenTelemetryLayer::with_tracer(provider.get_tracer("oasis-core"));
+    ::tracing::subscriber::set_global_default(tracing_subscriber::Registry::default().with(layer))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_span_context() {
+        let span_context = SpanContext {
+            trace_id: 0x1234,
+            span_id: 0xab,
+            parent_id: 0xcd,
+            flags: FLAG_SAMPLED,
+        };
+        assert!(span_context.is_sampled());
+
+        let data = span_context.to_bytes();
+        assert_eq!(data.len(), SPAN_CONTEXT_SIZE);
+        assert_eq!(SpanContext::from_bytes(&data).unwrap(), span_context);
+        assert!(SpanContext::from_bytes(&data[..SPAN_CONTEXT_SIZE - 1]).is_err());
+
+        assert_eq!(
+            span_context.to_header_value(),
+            "00000000000000000000000000001234:00000000000000ab:cd:1"
+        );
+    }
+
+    #[test]
+    fn test_enter_span() {
+        // Without an exporter, the span is not exported and the caller's
+        // span context is propagated unchanged instead of a made up one.
+        let mut ctx = Context::background();
+        let span = ::tracing::info_span!("test");
+        assert_eq!(enter_span(&mut ctx, &span), None);
+        assert_eq!(get_span_context(&ctx), None);
+
+        let parent = SpanContext {
+            trace_id: 0x1234,
+            span_id: 0xab,
+            parent_id: 0,
+            flags: FLAG_SAMPLED,
+        };
+        add_span_context(&mut ctx, parent.to_bytes());
+        assert_eq!(enter_span(&mut ctx, &span), Some(parent));
+        assert_eq!(get_span_context(&ctx), Some(&parent.to_bytes()));
+    }
+}
@@ -1,10 +1,18 @@
 //! Runtime call context.
-use std::{any::Any, sync::Arc};
+use std::{any::Any, collections::BTreeMap, sync::Arc};
 
 use io_context::Context as IoContext;
 
-use super::tags::{Tag, Tags};
-use crate::common::roothash::{Header, Message};
+use anyhow::Result;
+
+use super::{
+    rng::{BeaconRng, RngError},
+    tags::{Tag, Tags},
+};
+use crate::{
+    common::roothash::{Header, Message},
+    storage::mkvs::{Prefix, PruneHint},
+};
 
 struct NoRuntimeContext;
 
@@ -21,11 +29,17 @@ pub struct Context<'a> {
     /// running the transaction.
     pub check_only: bool,
 
+    /// Random beacon at the consensus height of the block, if available.
+    beacon: Option<Vec<u8>>,
+
     /// List of emitted tags for each transaction.
     tags: Vec<Tags>,
 
     /// List of messages emitted.
     messages: Vec<Message>,
+
+    /// Declared prunable prefixes and their retention periods.
+    prune_hints: BTreeMap<Prefix, u64>,
 }
 
 impl<'a> Context<'a> {
@@ -36,8 +50,38 @@ impl<'a> Context<'a> {
             header,
             runtime: Box::new(NoRuntimeContext),
             check_only,
+            beacon: None,
             tags: Vec::new(),
             messages: Vec::new(),
+            prune_hints: BTreeMap::new(),
+        }
+    }
+
+    /// Set the random beacon used to derive deterministic randomness.
+    pub fn with_beacon(mut self, beacon: Vec<u8>) -> Self {
+        self.beacon = Some(beacon);
+        self
+    }
+
+    /// Deterministic RNG for the given domain, derived from the consensus
+    /// random beacon and the round being computed.
+    ///
+    /// All executors obtain the same random stream for the same domain in
+    /// a given round, so the RNG may be used for in-consensus randomness.
+    /// The stream is predictable by anyone once the beacon is public, see
+    /// `BeaconRng`.
+    /// Use a distinct domain for each purpose, since the same domain always
+    /// yields the same stream within a round. The beacon is only available
+    /// when executing batches, not when checking transactions.
+    pub fn rng(&self, domain: &[u8]) -> Result<BeaconRng> {
+        match self.beacon {
+            Some(ref beacon) => BeaconRng::new(
+                beacon,
+                &self.header.namespace,
+                self.header.round + 1,
+                domain,
+            ),
+            None => Err(RngError::BeaconNotAvailable.into()),
         }
     }
 
@@ -46,9 +90,19 @@ impl<'a> Context<'a> {
         self.tags.push(Tags::new());
     }
 
-    /// Close the context and return the emitted tags and sent roothash messages.
-    pub fn close(self) -> (Vec<Tags>, Vec<Message>) {
-        (self.tags, self.messages)
+    /// Close the context and return the emitted tags, sent roothash messages
+    /// and declared pruning hints.
+    pub fn close(self) -> (Vec<Tags>, Vec<Message>, Vec<PruneHint>) {
+        let prune_hints = self
+            .prune_hints
+            .into_iter()
+            .map(|(prefix, retain_rounds)| PruneHint {
+                prefix,
+                retain_rounds,
+            })
+            .collect();
+
+        (self.tags, self.messages, prune_hints)
     }
 
     /// Emit a runtime-specific indexable tag refering to the specific
@@ -82,4 +136,23 @@ impl<'a> Context<'a> {
     pub fn send_roothash_message(&mut self, message: Message) {
         self.messages.push(message);
     }
+
+    /// Declare keys under the given prefix as prunable after the given number
+    /// of rounds.
+    ///
+    /// The declaration is emitted as a pruning hint together with the batch's
+    /// state write log. If the same prefix is declared multiple times, the
+    /// longest retention period is used.
+    pub fn declare_prunable<P>(&mut self, prefix: P, retain_rounds: u64)
+    where
+        P: AsRef<[u8]>,
+    {
+        let entry = self
+            .prune_hints
+            .entry(prefix.as_ref().to_vec().into())
+            .or_insert(retain_rounds);
+        if retain_rounds > *entry {
+            *entry = retain_rounds;
+        }
+    }
 }
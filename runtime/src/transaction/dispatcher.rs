@@ -1,26 +1,30 @@
 //! Runtime transaction batch dispatcher.
 use std::collections::HashMap;
 
-use failure::{Fallible, ResultExt};
+use anyhow::{Context as _, Result};
 use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
 
 use super::{
     context::Context,
     tags::Tags,
     types::{TxnBatch, TxnCall, TxnCheckResult, TxnOutput},
 };
-use crate::common::{cbor, crypto::hash::Hash, roothash::Message as RoothashMessage};
+use crate::{
+    common::{cbor, crypto::hash::Hash, roothash::Message as RoothashMessage},
+    storage::mkvs::PruneHint,
+};
 
 /// Dispatch error.
-#[derive(Debug, Fail)]
+#[derive(Debug, Error)]
 enum DispatchError {
-    #[fail(display = "method not found: {}", method)]
+    #[error("method not found: {method}")]
     MethodNotFound { method: String },
 }
 
 /// Error indicating that performing a transaction check was successful.
-#[derive(Debug, Default, Fail)]
-#[fail(display = "transaction check successful")]
+#[derive(Debug, Default, Error)]
+#[error("transaction check successful")]
 pub struct CheckOnlySuccess(pub TxnCheckResult);
 
 /// Custom batch handler.
@@ -81,16 +85,16 @@ pub struct MethodDescriptor {
 /// Handler for a runtime method.
 pub trait MethodHandler<Call, Output> {
     /// Invoke the method implementation and return a response.
-    fn handle(&self, call: &Call, ctx: &mut Context) -> Fallible<Output>;
+    fn handle(&self, call: &Call, ctx: &mut Context) -> Result<Output>;
 }
 
 impl<Call, Output, F> MethodHandler<Call, Output> for F
 where
     Call: 'static,
     Output: 'static,
-    F: Fn(&Call, &mut Context) -> Fallible<Output> + 'static,
+    F: Fn(&Call, &mut Context) -> Result<Output> + 'static,
 {
-    fn handle(&self, call: &Call, ctx: &mut Context) -> Fallible<Output> {
+    fn handle(&self, call: &Call, ctx: &mut Context) -> Result<Output> {
         (*self)(&call, ctx)
     }
 }
@@ -101,7 +105,7 @@ pub trait MethodHandlerDispatch {
     fn get_descriptor(&self) -> &MethodDescriptor;
 
     /// Dispatches the given raw call.
-    fn dispatch(&self, call: TxnCall, ctx: &mut Context) -> Fallible<cbor::Value>;
+    fn dispatch(&self, call: TxnCall, ctx: &mut Context) -> Result<cbor::Value>;
 }
 
 struct MethodHandlerDispatchImpl<Call, Output> {
@@ -120,7 +124,7 @@ where
         &self.descriptor
     }
 
-    fn dispatch(&self, call: TxnCall, ctx: &mut Context) -> Fallible<cbor::Value> {
+    fn dispatch(&self, call: TxnCall, ctx: &mut Context) -> Result<cbor::Value> {
         let call = cbor::from_value(call.args).context("unable to parse call arguments")?;
         let response = self.handler.handle(&call, ctx)?;
 
@@ -156,7 +160,7 @@ impl Method {
     }
 
     /// Dispatch method call.
-    pub fn dispatch(&self, call: TxnCall, ctx: &mut Context) -> Fallible<cbor::Value> {
+    pub fn dispatch(&self, call: TxnCall, ctx: &mut Context) -> Result<cbor::Value> {
         self.dispatcher.dispatch(call, ctx)
     }
 }
@@ -171,9 +175,14 @@ pub trait Dispatcher {
         &self,
         batch: &TxnBatch,
         ctx: Context,
-    ) -> (TxnBatch, Vec<Tags>, Vec<RoothashMessage>);
+    ) -> (TxnBatch, Vec<Tags>, Vec<RoothashMessage>, Vec<PruneHint>);
     /// Invoke the finalizer (if any).
     fn finalize(&self, new_storage_root: Hash);
+    /// Whether transaction checks should observe state changes of batches
+    /// which have been executed but not yet finalized.
+    fn check_pending_state(&self) -> bool {
+        false
+    }
 }
 
 /// No-op dispatcher.
@@ -193,10 +202,10 @@ impl Dispatcher for NoopDispatcher {
         &self,
         _batch: &TxnBatch,
         ctx: Context,
-    ) -> (TxnBatch, Vec<Tags>, Vec<RoothashMessage>) {
+    ) -> (TxnBatch, Vec<Tags>, Vec<RoothashMessage>, Vec<PruneHint>) {
         let outputs = TxnBatch::new(Vec::new());
-        let (tags, roothash_messages) = ctx.close();
-        (outputs, tags, roothash_messages)
+        let (tags, roothash_messages, prune_hints) = ctx.close();
+        (outputs, tags, roothash_messages, prune_hints)
     }
 
     fn finalize(&self, _new_storage_root: Hash) {
@@ -218,6 +227,8 @@ pub struct MethodDispatcher {
     ctx_initializer: Option<Box<dyn ContextInitializer>>,
     /// Registered finalizer.
     finalizer: Option<Box<dyn Finalizer>>,
+    /// Whether checks observe pending state.
+    check_pending_state: bool,
 }
 
 impl MethodDispatcher {
@@ -228,6 +239,7 @@ impl MethodDispatcher {
             batch_handler: None,
             ctx_initializer: None,
             finalizer: None,
+            check_pending_state: false,
         }
     }
 
@@ -260,6 +272,16 @@ impl MethodDispatcher {
         self.finalizer = Some(Box::new(finalizer));
     }
 
+    /// Configure whether transaction checks observe pending state.
+    ///
+    /// When enabled, transaction checks see the state changes of batches which
+    /// have been executed by this runtime but not yet finalized, which makes
+    /// it possible to accurately check transactions (e.g., nonces or balances)
+    /// against state that already includes the current proposal.
+    pub fn set_check_pending_state(&mut self, enabled: bool) {
+        self.check_pending_state = enabled;
+    }
+
     /// Dispatches a raw runtime invocation request.
     fn dispatch(&self, call: &Vec<u8>, ctx: &mut Context) -> Vec<u8> {
         let rsp = match self.dispatch_fallible(call, ctx) {
@@ -273,9 +295,27 @@ impl MethodDispatcher {
         cbor::to_vec(&rsp)
     }
 
-    fn dispatch_fallible(&self, call: &Vec<u8>, ctx: &mut Context) -> Fallible<cbor::Value> {
-        let call: TxnCall = cbor::from_slice(call).context("unable to parse call")?;
+    /// Dispatches a single call outside of a batch.
+    ///
+    /// This can be used to evaluate read-only queries against a given state
+    /// (e.g., a historical state root) by entering the corresponding storage
+    /// context first. The context initializer is invoked, but batch handlers
+    /// are not and any emitted tags or messages are discarded.
+    pub fn dispatch_call(&self, call: TxnCall, mut ctx: Context) -> Result<cbor::Value> {
+        if let Some(ref ctx_init) = self.ctx_initializer {
+            ctx_init.init(&mut ctx);
+        }
+
+        ctx.start_transaction();
+        self.dispatch_method(call, &mut ctx)
+    }
+
+    fn dispatch_fallible(&self, call: &Vec<u8>, ctx: &mut Context) -> Result<cbor::Value> {
+        let call: TxnCall = cbor::from_slice_canonical(call).context("unable to parse call")?;
+        self.dispatch_method(call, ctx)
+    }
 
+    fn dispatch_method(&self, call: TxnCall, ctx: &mut Context) -> Result<cbor::Value> {
         match self.methods.get(&call.method) {
             Some(dispatcher) => dispatcher.dispatch(call, ctx),
             None => Err(DispatchError::MethodNotFound {
@@ -291,7 +331,7 @@ impl Dispatcher for MethodDispatcher {
         &self,
         batch: &TxnBatch,
         mut ctx: Context,
-    ) -> (TxnBatch, Vec<Tags>, Vec<RoothashMessage>) {
+    ) -> (TxnBatch, Vec<Tags>, Vec<RoothashMessage>, Vec<PruneHint>) {
         if let Some(ref ctx_init) = self.ctx_initializer {
             ctx_init.init(&mut ctx);
         }
@@ -317,8 +357,8 @@ impl Dispatcher for MethodDispatcher {
             handler.end_batch(&mut ctx);
         }
 
-        let (tags, roothash_messages) = ctx.close();
-        (outputs, tags, roothash_messages)
+        let (tags, roothash_messages, prune_hints) = ctx.close();
+        (outputs, tags, roothash_messages, prune_hints)
     }
 
     fn finalize(&self, new_storage_root: Hash) {
@@ -326,6 +366,10 @@ impl Dispatcher for MethodDispatcher {
             finalizer.finalize(new_storage_root);
         }
     }
+
+    fn check_pending_state(&self) -> bool {
+        self.check_pending_state
+    }
 }
 
 #[cfg(test)]
@@ -352,7 +396,7 @@ mod tests {
             MethodDescriptor {
                 name: "dummy".to_owned(),
             },
-            |call: &Complex, ctx: &mut Context| -> Fallible<Complex> {
+            |call: &Complex, ctx: &mut Context| -> Result<Complex> {
                 assert_eq!(ctx.header.timestamp, TEST_TIMESTAMP);
 
                 Ok(Complex {
@@ -404,4 +448,78 @@ mod tests {
             _ => panic!("txn call should return success"),
         }
     }
+
+    #[test]
+    fn test_dispatcher_call() {
+        let mut dispatcher = MethodDispatcher::new();
+        register_dummy_method(&mut dispatcher);
+
+        let header = Header {
+            timestamp: TEST_TIMESTAMP,
+            ..Default::default()
+        };
+        let call = TxnCall {
+            method: "dummy".to_owned(),
+            args: cbor::to_value(Complex {
+                text: "hello".to_owned(),
+                number: 21,
+            }),
+        };
+        let ctx = Context::new(IoContext::background().freeze(), &header, false);
+        let value: Complex =
+            cbor::from_value(dispatcher.dispatch_call(call, ctx).unwrap()).unwrap();
+        assert_eq!(
+            value,
+            Complex {
+                text: "hello".to_owned(),
+                number: 42
+            }
+        );
+
+        let call = TxnCall {
+            method: "missing".to_owned(),
+            args: cbor::Value::Null,
+        };
+        let ctx = Context::new(IoContext::background().freeze(), &header, false);
+        assert!(dispatcher.dispatch_call(call, ctx).is_err());
+    }
+
+    #[test]
+    fn test_dispatcher_prune_hints() {
+        let mut dispatcher = MethodDispatcher::new();
+        dispatcher.add_method(Method::new(
+            MethodDescriptor {
+                name: "ephemeral".to_owned(),
+            },
+            |call: &u64, ctx: &mut Context| -> Result<()> {
+                ctx.declare_prunable(b"ephemeral/", *call);
+                Ok(())
+            },
+        ));
+
+        let batch = TxnBatch::new(
+            [10u64, 20, 5]
+                .iter()
+                .map(|rounds| {
+                    cbor::to_vec(&TxnCall {
+                        method: "ephemeral".to_owned(),
+                        args: cbor::to_value(rounds),
+                    })
+                })
+                .collect(),
+        );
+
+        let header = Header::default();
+        let ctx = Context::new(IoContext::background().freeze(), &header, false);
+        let (_, _, _, prune_hints) = dispatcher.dispatch_batch(&batch, ctx);
+
+        // The longest retention period should be used.
+        assert_eq!(
+            prune_hints,
+            vec![PruneHint {
+                prefix: b"ephemeral/".to_vec().into(),
+                retain_rounds: 20,
+            }]
+        );
+    }
 }
@@ -25,7 +25,7 @@ macro_rules! register_runtime_txn_methods {
                     },
                     |args: &$arguments_type,
                      ctx: &mut $crate::transaction::context::Context|
-                        -> ::failure::Fallible<$output_type> {
+                        -> $crate::__private::anyhow::Result<$output_type> {
                         $method_name(args, ctx)
                     },
                 )
@@ -33,3 +33,48 @@ macro_rules! register_runtime_txn_methods {
         )*
     }
 }
+
+#[cfg(test)]
+mod test {
+    use io_context::Context as IoContext;
+
+    use crate::{
+        common::{cbor, roothash::Header},
+        transaction::{dispatcher::MethodDispatcher, types::TxnCall, Context},
+    };
+
+    fn double(args: &u64, _ctx: &mut Context) -> anyhow::Result<u64> {
+        Ok(args * 2)
+    }
+
+    fn greet(args: &String, _ctx: &mut Context) -> anyhow::Result<String> {
+        Ok(format!("hello {}", args))
+    }
+
+    #[test]
+    fn test_register_runtime_txn_methods() {
+        let mut dispatcher = MethodDispatcher::new();
+        register_runtime_txn_methods!(
+            dispatcher,
+            pub fn double(u64) -> u64;
+            pub fn greet(String) -> String;
+        );
+
+        let header = Header::default();
+        let call = TxnCall {
+            method: "double".to_owned(),
+            args: cbor::to_value(21u64),
+        };
+        let ctx = Context::new(IoContext::background().freeze(), &header, false);
+        let value: u64 = cbor::from_value(dispatcher.dispatch_call(call, ctx).unwrap()).unwrap();
+        assert_eq!(value, 42);
+
+        let call = TxnCall {
+            method: "greet".to_owned(),
+            args: cbor::to_value("world".to_owned()),
+        };
+        let ctx = Context::new(IoContext::background().freeze(), &header, false);
+        let value: String = cbor::from_value(dispatcher.dispatch_call(call, ctx).unwrap()).unwrap();
+        assert_eq!(value, "hello world");
+    }
+}
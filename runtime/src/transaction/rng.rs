@@ -0,0 +1,109 @@
+//! Deterministic randomness derived from the consensus random beacon.
+use anyhow::Result;
+use rand::{CryptoRng, Error, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use thiserror::Error;
+
+use crate::common::{crypto::hash::Hash, roothash::Namespace};
+
+/// Size of the consensus random beacon in bytes.
+pub const BEACON_SIZE: usize = 32;
+
+/// Domain separation context of the RNG seed derivation.
+const BEACON_RNG_CONTEXT: &'static str = "oasis-core/runtime: beacon rng";
+
+/// RNG error.
+#[derive(Debug, Error)]
+pub enum RngError {
+    #[error("random beacon not available")]
+    BeaconNotAvailable,
+    #[error("malformed random beacon")]
+    MalformedBeacon,
+}
+
+/// A deterministic RNG seeded from the consensus random beacon.
+///
+/// The seed is derived from the beacon, the runtime namespace, the round
+/// and a caller-provided domain, so the stream is the same for everyone
+/// computing the given round. The beacon is public by the time the round is
+/// computed, so anyone, including transaction senders, can predict the stream
+/// in advance. It must not be used where unpredictability matters, e.g., for
+/// secrets or lotteries.
+pub struct BeaconRng(ChaCha20Rng);
+
+impl BeaconRng {
+    /// Create a new RNG for the given domain.
+    pub fn new(beacon: &[u8], namespace: &Namespace, round: u64, domain: &[u8]) -> Result<Self> {
+        if beacon.len() != BEACON_SIZE {
+            return Err(RngError::MalformedBeacon.into());
+        }
+
+        let seed = Hash::digest_blake3_list(
+            BEACON_RNG_CONTEXT,
+            &[beacon, namespace.as_ref(), &round.to_le_bytes(), domain],
+        );
+
+        Ok(BeaconRng(ChaCha20Rng::from_seed(seed.0)))
+    }
+}
+
+impl RngCore for BeaconRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+impl CryptoRng for BeaconRng {}
+
+#[cfg(test)]
+mod test {
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn test_beacon_rng() {
+        let beacon = [1u8; BEACON_SIZE];
+        let namespace = Namespace::default();
+        let sample = |beacon: &[u8], round: u64, domain: &[u8]| -> [u64; 4] {
+            BeaconRng::new(beacon, &namespace, round, domain)
+                .unwrap()
+                .gen()
+        };
+
+        // The same inputs yield the same stream.
+        let reference = sample(&beacon, 1, b"lottery");
+        assert_eq!(sample(&beacon, 1, b"lottery"), reference);
+
+        // Any different input yields a different stream.
+        assert_ne!(sample(&[2u8; BEACON_SIZE], 1, b"lottery"), reference);
+        assert_ne!(sample(&beacon, 2, b"lottery"), reference);
+        assert_ne!(sample(&beacon, 1, b"ordering"), reference);
+        assert_ne!(
+            BeaconRng::new(
+                &beacon,
+                &Namespace::from(vec![1u8; 32].as_slice()),
+                1,
+                b"lottery"
+            )
+            .unwrap()
+            .gen::<[u64; 4]>(),
+            reference
+        );
+
+        // Beacons must be well formed.
+        assert!(BeaconRng::new(&[], &namespace, 1, b"lottery").is_err());
+    }
+}
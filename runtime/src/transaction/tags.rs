@@ -1,14 +1,18 @@
 //! Transaction tags.
+use serde_derive::{Deserialize, Serialize};
+
 use crate::common::crypto::hash::Hash;
 
 /// Tag is a key/value pair of arbitrary byte blobs with runtime-dependent
 /// semantics which can be indexed to allow easier lookup of blocks and
 /// transactions on runtime clients.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Tag {
     /// The tag key.
+    #[serde(with = "serde_bytes")]
     pub key: Vec<u8>,
     /// The tag value.
+    #[serde(with = "serde_bytes")]
     pub value: Vec<u8>,
     /// The hash of the transaction that emitted the tag.
     pub tx_hash: Hash,
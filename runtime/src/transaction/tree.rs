@@ -1,14 +1,18 @@
 //! Transaction I/O tree.
-use failure::{format_err, Fallible};
+use anyhow::{anyhow, Result};
 use io_context::Context;
 use serde::{self, ser::SerializeSeq, Serializer};
 use serde_bytes::{self, Bytes};
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
 
-use super::tags::Tags;
+use super::tags::{Tag, Tags};
 use crate::{
     common::{cbor, crypto::hash::Hash, key_format::KeyFormat},
-    storage::mkvs::{self, sync::ReadSync, Root, WriteLog},
+    storage::mkvs::{
+        self,
+        sync::{Proof, ProofVerifier, ReadSync},
+        Root, RootType, WriteLog,
+    },
 };
 
 // NOTE: This should be kept in sync with go/runtime/transaction/transaction.go.
@@ -147,6 +151,109 @@ impl serde::Serialize for OutputArtifacts {
     }
 }
 
+/// A receipt for a transaction included in an I/O tree.
+///
+/// The receipt contains proofs of inclusion of the transaction's input,
+/// output and emitted tags which can be verified against the I/O root of
+/// the block that included the transaction.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TxnReceipt {
+    /// Round in which the transaction was included.
+    pub round: u64,
+    /// Transaction order within the batch.
+    pub batch_order: u32,
+    /// Transaction input.
+    #[serde(with = "serde_bytes")]
+    pub input: Vec<u8>,
+    /// Transaction output.
+    #[serde(with = "serde_bytes")]
+    pub output: Vec<u8>,
+    /// Tags emitted by the transaction.
+    pub tags: Tags,
+    /// Proof of inclusion of the input artifacts.
+    pub input_proof: Proof,
+    /// Proof of inclusion of the output artifacts.
+    pub output_proof: Proof,
+    /// Proofs of inclusion of the emitted tags, in the same order as `tags`.
+    pub tag_proofs: Vec<Proof>,
+}
+
+impl TxnReceipt {
+    /// Hash of the receipt's transaction.
+    pub fn tx_hash(&self) -> Hash {
+        Hash::digest_bytes(&self.input)
+    }
+
+    /// Verify the receipt against the given I/O root.
+    ///
+    /// Note that this only proves that the included tags were emitted by
+    /// the transaction, not that there are no other tags.
+    pub fn verify(&self, ctx: Context, io_root: Root) -> Result<()> {
+        let ctx = ctx.freeze();
+        io_root.ensure_type(RootType::IO)?;
+        if io_root.version != self.round {
+            return Err(anyhow!("transaction: receipt round mismatch"));
+        }
+        if self.tag_proofs.len() != self.tags.len() {
+            return Err(anyhow!("transaction: receipt tag proofs missing"));
+        }
+
+        let tx_hash = self.tx_hash();
+        let verify = |key: Vec<u8>, expected: &[u8], proof: &Proof| -> Result<()> {
+            let value = ProofVerifier.verify_proof_for_key(
+                Context::create_child(&ctx),
+                io_root.hash,
+                &key,
+                proof,
+            )?;
+            match value {
+                Some(ref value) if &value[..] == expected => Ok(()),
+                _ => Err(anyhow!("transaction: receipt does not match proof")),
+            }
+        };
+
+        verify(
+            TxnKeyFormat {
+                tx_hash,
+                kind: ArtifactKind::Input,
+            }
+            .encode(),
+            &cbor::to_vec(&InputArtifacts {
+                input: self.input.clone(),
+                batch_order: self.batch_order,
+            }),
+            &self.input_proof,
+        )?;
+        verify(
+            TxnKeyFormat {
+                tx_hash,
+                kind: ArtifactKind::Output,
+            }
+            .encode(),
+            &cbor::to_vec(&OutputArtifacts {
+                output: self.output.clone(),
+            }),
+            &self.output_proof,
+        )?;
+        for (tag, proof) in self.tags.iter().zip(self.tag_proofs.iter()) {
+            if tag.tx_hash != tx_hash {
+                return Err(anyhow!("transaction: receipt tag hash mismatch"));
+            }
+            verify(
+                TagKeyFormat {
+                    key: tag.key.clone(),
+                    tx_hash,
+                }
+                .encode(),
+                &tag.value,
+                proof,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
 /// A Merkle tree containing transaction artifacts.
 pub struct Tree {
     io_root: Root,
@@ -155,17 +262,21 @@ pub struct Tree {
 
 impl Tree {
     /// Create a new transaction artifacts tree.
-    pub fn new(read_syncer: Box<dyn ReadSync>, io_root: Root) -> Self {
-        Self {
+    ///
+    /// Fails if the given root is typed and not an I/O root.
+    pub fn new(read_syncer: Box<dyn ReadSync>, io_root: Root) -> Result<Self> {
+        io_root.ensure_type(RootType::IO)?;
+
+        Ok(Self {
             io_root,
             tree: mkvs::Tree::make().with_root(io_root).new(read_syncer),
-        }
+        })
     }
 
     /// Add an input transaction artifact.
-    pub fn add_input(&mut self, ctx: Context, input: Vec<u8>, batch_order: u32) -> Fallible<()> {
+    pub fn add_input(&mut self, ctx: Context, input: Vec<u8>, batch_order: u32) -> Result<()> {
         if input.is_empty() {
-            return Err(format_err!("transaction: no input given"));
+            return Err(anyhow!("transaction: no input given"));
         }
 
         let tx_hash = Hash::digest_bytes(&input);
@@ -190,7 +301,7 @@ impl Tree {
         tx_hash: Hash,
         output: Vec<u8>,
         tags: Tags,
-    ) -> Fallible<()> {
+    ) -> Result<()> {
         let ctx = ctx.freeze();
 
         self.tree.insert(
@@ -219,9 +330,140 @@ impl Tree {
         Ok(())
     }
 
+    /// Retrieve the output of the given transaction, if it exists.
+    pub fn get_output(&self, ctx: Context, tx_hash: Hash) -> Result<Option<Vec<u8>>> {
+        let raw = self.tree.get(
+            ctx,
+            &TxnKeyFormat {
+                tx_hash,
+                kind: ArtifactKind::Output,
+            }
+            .encode(),
+        )?;
+
+        match raw {
+            Some(raw) => {
+                let artifacts: OutputArtifacts = cbor::from_slice(&raw)?;
+                Ok(Some(artifacts.output))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Retrieve the value of a tag emitted by the given transaction, if it
+    /// exists.
+    pub fn get_tag(&self, ctx: Context, key: &[u8], tx_hash: Hash) -> Result<Option<Vec<u8>>> {
+        self.tree.get(
+            ctx,
+            &TagKeyFormat {
+                key: key.to_vec(),
+                tx_hash,
+            }
+            .encode(),
+        )
+    }
+
+    /// Retrieve all tags emitted by transactions in the tree.
+    ///
+    /// Tags are ordered by key and then by the hash of the transaction that
+    /// emitted them.
+    pub fn get_tags(&self, ctx: Context) -> Result<Tags> {
+        let prefix = TagKeyFormat::default().encode_partial(0);
+        let mut it = self.tree.iter(ctx);
+        it.seek(&prefix);
+
+        let tags = it
+            .by_ref()
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .filter_map(|(key, value)| {
+                TagKeyFormat::decode(&key).map(|format| Tag {
+                    key: format.key,
+                    value,
+                    tx_hash: format.tx_hash,
+                })
+            })
+            .collect();
+        if let Some(error) = it.error() {
+            return Err(anyhow!("{}", error));
+        }
+
+        Ok(tags)
+    }
+
+    /// Generate a receipt for the given transaction, if it exists.
+    ///
+    /// The tree must not contain any uncommitted changes.
+    pub fn get_receipt(&self, ctx: Context, tx_hash: Hash) -> Result<Option<TxnReceipt>> {
+        let ctx = ctx.freeze();
+
+        let input_key = TxnKeyFormat {
+            tx_hash,
+            kind: ArtifactKind::Input,
+        }
+        .encode();
+        let input: InputArtifacts = match self.tree.get(Context::create_child(&ctx), &input_key)? {
+            Some(raw) => cbor::from_slice(&raw)?,
+            None => return Ok(None),
+        };
+        let output_key = TxnKeyFormat {
+            tx_hash,
+            kind: ArtifactKind::Output,
+        }
+        .encode();
+        let output = self
+            .get_output(Context::create_child(&ctx), tx_hash)?
+            .ok_or_else(|| anyhow!("transaction: output missing"))?;
+
+        // Tags are keyed by tag key first, so all of them need to be scanned.
+        let mut tags = Tags::new();
+        let mut tag_keys = vec![];
+        let mut it = self.tree.iter(Context::create_child(&ctx));
+        it.seek(&[TagKeyFormat::prefix()]);
+        for (key, value) in &mut it {
+            let decoded = match TagKeyFormat::decode(&key) {
+                Some(decoded) => decoded,
+                None => break,
+            };
+            if decoded.tx_hash != tx_hash {
+                continue;
+            }
+
+            tags.push(Tag {
+                key: decoded.key,
+                value,
+                tx_hash,
+            });
+            tag_keys.push(key);
+        }
+        if let Some(error) = it.error() {
+            return Err(anyhow!("transaction: failed to scan tags: {}", error));
+        }
+        drop(it);
+
+        let tag_proofs = tag_keys
+            .iter()
+            .map(|key| self.tree.get_proof(Context::create_child(&ctx), key))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Some(TxnReceipt {
+            round: self.io_root.version,
+            batch_order: input.batch_order,
+            input: input.input,
+            output,
+            tags,
+            input_proof: self
+                .tree
+                .get_proof(Context::create_child(&ctx), &input_key)?,
+            output_proof: self
+                .tree
+                .get_proof(Context::create_child(&ctx), &output_key)?,
+            tag_proofs,
+        }))
+    }
+
     /// Commit updates to the underlying Merkle tree and return the write
     /// log and root hash.
-    pub fn commit(&mut self, ctx: Context) -> Fallible<(WriteLog, Hash)> {
+    pub fn commit(&mut self, ctx: Context) -> Result<(WriteLog, Hash)> {
         self.tree
             .commit(ctx, self.io_root.namespace, self.io_root.version)
     }
@@ -243,7 +485,8 @@ mod test {
                 hash: Hash::empty_hash(),
                 ..Default::default()
             },
-        );
+        )
+        .unwrap();
 
         let input = b"this goes in".to_vec();
         let tx_hash = Hash::digest_bytes(&input);
@@ -279,5 +522,77 @@ mod test {
             format!("{:?}", root_hash),
             "c65f4e8bd5314c26f245337a859ad244f4b1544acf60ef334cf0d0eadb47363b",
         );
+
+        assert_eq!(
+            tree.get_output(Context::background(), tx_hash).unwrap(),
+            Some(b"and this comes out".to_vec())
+        );
+        assert_eq!(
+            tree.get_tag(Context::background(), b"tag1", tx_hash)
+                .unwrap(),
+            Some(b"value1".to_vec())
+        );
+        assert_eq!(
+            tree.get_tag(Context::background(), b"tagA", tx_hash)
+                .unwrap(),
+            None
+        );
+        let tags = tree.get_tags(Context::background()).unwrap();
+        assert_eq!(tags.len(), 41);
+        let tags: Vec<_> = tags
+            .into_iter()
+            .filter(|tag| tag.tx_hash == tx_hash)
+            .collect();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].key, b"tag1".to_vec());
+        assert_eq!(tags[0].value, b"value1".to_vec());
+
+        let receipt = tree
+            .get_receipt(Context::background(), tx_hash)
+            .unwrap()
+            .expect("transaction should exist");
+        assert_eq!(receipt.batch_order, 0);
+        assert_eq!(receipt.input, b"this goes in".to_vec());
+        assert_eq!(receipt.output, b"and this comes out".to_vec());
+        assert_eq!(receipt.tags.len(), 1);
+        assert_eq!(receipt.tags[0].key, b"tag1".to_vec());
+        assert_eq!(receipt.tags[0].value, b"value1".to_vec());
+
+        let io_root = Root {
+            hash: root_hash,
+            root_type: RootType::IO,
+            ..Default::default()
+        };
+        receipt
+            .verify(Context::background(), io_root)
+            .expect("receipt should verify");
+
+        let mut forged = receipt.clone();
+        forged.output = b"something else".to_vec();
+        assert!(forged.verify(Context::background(), io_root).is_err());
+        let mut forged = receipt.clone();
+        forged.tags[0].value = b"value2".to_vec();
+        assert!(forged.verify(Context::background(), io_root).is_err());
+
+        assert!(tree
+            .get_receipt(Context::background(), Hash::empty_hash())
+            .unwrap()
+            .is_none());
+
+        assert_eq!(
+            tree.get_output(Context::background(), Hash::empty_hash())
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_transaction_root_type() {
+        let state_root = Root {
+            hash: Hash::empty_hash(),
+            root_type: RootType::State,
+            ..Default::default()
+        };
+        assert!(Tree::new(Box::new(NoopReadSyncer {}), state_root).is_err());
     }
 }
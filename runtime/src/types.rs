@@ -1,4 +1,8 @@
 //! Types used by the worker-host protocol.
+use std::{any::TypeId, collections::BTreeMap, error::Error as StdError, sync::RwLock};
+
+use lazy_static::lazy_static;
+
 use serde::{self, Deserializer, Serializer};
 use serde_bytes;
 use serde_derive::{Deserialize, Serialize};
@@ -13,10 +17,141 @@ use crate::{
         runtime::RuntimeId,
         sgx::avr::AVR,
     },
-    storage::mkvs::{sync, WriteLog},
+    dispatcher::DispatcherError,
+    memory::{MemoryError, MemoryStats, Subsystem},
+    protocol::ProtocolError,
+    rak::RAKError,
+    rpc::{
+        demux::DemuxError, dispatcher::DispatchError as RpcDispatchError, session::SessionError,
+    },
+    storage::mkvs::{sync, CacheMetrics, CompressedWriteLog, PruneHint, WriteLog},
     transaction::types::TxnBatch,
 };
 
+/// Module name used for errors without a known module.
+pub const UNKNOWN_MODULE: &'static str = "unknown";
+/// Code used for errors without a known code.
+pub const CODE_UNKNOWN: u32 = 1;
+
+/// An error with a stable module name and code.
+///
+/// Such errors keep their identity when reported over the runtime host
+/// protocol, so they can be reconstructed by the worker host (see
+/// go/common/errors).
+pub trait ErrorCode: StdError {
+    /// Name of the module the error belongs to.
+    fn module_name(&self) -> &str;
+
+    /// Error code, unique within the module.
+    ///
+    /// Code zero is reserved to mean "no error".
+    fn code(&self) -> u32;
+}
+
+/// Runtime host protocol error.
+///
+/// # Note
+///
+/// This should be kept in sync with go/runtime/host/protocol/types.go.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+#[error("{message}")]
+pub struct Error {
+    #[serde(default)]
+    pub module: String,
+    #[serde(default)]
+    pub code: u32,
+    #[serde(default)]
+    pub message: String,
+}
+
+impl Error {
+    /// Create a new error.
+    pub fn new(module: &str, code: u32, message: &str) -> Self {
+        Self {
+            module: module.to_owned(),
+            code,
+            message: message.to_owned(),
+        }
+    }
+}
+
+impl<E: ErrorCode> From<E> for Error {
+    fn from(err: E) -> Self {
+        Self::new(err.module_name(), err.code(), &format!("{}", err))
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    /// Convert an error, preserving the module name and code of the first
+    /// error in its chain which has one.
+    fn from(err: anyhow::Error) -> Self {
+        let message = format!("{}", err);
+        match err.chain().find_map(error_code) {
+            Some((module, code)) => Self::new(module, code, &message),
+            None => Self::new(UNKNOWN_MODULE, CODE_UNKNOWN, &message),
+        }
+    }
+}
+
+/// Function returning the module name and code of an error of a registered
+/// type.
+type ErrorCodeFn = for<'a> fn(&'a (dyn StdError + 'static)) -> Option<(&'a str, u32)>;
+
+lazy_static! {
+    static ref ERROR_CODES: RwLock<Vec<(TypeId, ErrorCodeFn)>> = {
+        let mut codes = vec![
+            error_code_entry::<ProtocolError>(),
+            error_code_entry::<DispatcherError>(),
+            error_code_entry::<RAKError>(),
+            error_code_entry::<DemuxError>(),
+            error_code_entry::<SessionError>(),
+            error_code_entry::<RpcDispatchError>(),
+            error_code_entry::<MemoryError>(),
+        ];
+        #[cfg(target_env = "sgx")]
+        codes.push(error_code_entry::<crate::rak::AVRError>());
+
+        RwLock::new(codes)
+    };
+}
+
+fn error_code_entry<E: ErrorCode + 'static>() -> (TypeId, ErrorCodeFn) {
+    (TypeId::of::<E>(), downcast_error_code::<E>)
+}
+
+fn downcast_error_code<E: ErrorCode + 'static>(
+    err: &(dyn StdError + 'static),
+) -> Option<(&str, u32)> {
+    err.downcast_ref::<E>()
+        .map(|err| (err.module_name(), err.code()))
+}
+
+/// Register an error type, so that its module name and code are preserved
+/// when it is reported as the cause of an `anyhow::Error`, e.g., over the
+/// runtime host protocol or in EnclaveRPC responses.
+///
+/// Registering a type more than once has no effect.
+pub fn register_error_code<E: ErrorCode + 'static>() {
+    let mut codes = ERROR_CODES.write().unwrap();
+    if codes.iter().all(|(id, _)| *id != TypeId::of::<E>()) {
+        codes.push(error_code_entry::<E>());
+    }
+}
+
+/// Look up the module name and code of an error.
+fn error_code<'a>(err: &'a (dyn StdError + 'static)) -> Option<(&'a str, u32)> {
+    // Errors received from the worker host are passed through as-is.
+    if let Some(err) = err.downcast_ref::<Error>() {
+        return Some((err.module.as_str(), err.code));
+    }
+
+    ERROR_CODES
+        .read()
+        .unwrap()
+        .iter()
+        .find_map(|(_, error_code)| error_code(err))
+}
+
 /// Computed batch.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ComputedBatch {
@@ -26,6 +161,17 @@ pub struct ComputedBatch {
     pub io_write_log: WriteLog,
     /// Log of changes to the state tree.
     pub state_write_log: WriteLog,
+    /// Compressed log that generates the I/O tree, sent instead of
+    /// `io_write_log` if the host supports compressed write logs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compressed_io_write_log: Option<CompressedWriteLog>,
+    /// Compressed log of changes to the state tree, sent instead of
+    /// `state_write_log` if the host supports compressed write logs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compressed_state_write_log: Option<CompressedWriteLog>,
+    /// Pruning hints for ephemeral state.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prune_hints: Vec<PruneHint>,
     /// If this runtime uses a TEE, then this is the signature of the batch's
     /// BatchSigMessage with the node's RAK for this runtime.
     pub rak_sig: Signature,
@@ -52,18 +198,17 @@ pub enum Body {
     Empty {},
 
     // An error response.
-    Error {
-        #[serde(default)]
-        module: String,
-        #[serde(default)]
-        code: u32,
-        #[serde(default)]
-        message: String,
-    },
+    Error(Error),
 
     // Runtime interface.
     RuntimeInfoRequest {
         runtime_id: RuntimeId,
+        #[serde(default)]
+        protocol_version: u64,
+        #[serde(default)]
+        memory_limits: BTreeMap<Subsystem, u64>,
+        #[serde(default)]
+        compressed_write_logs: bool,
     },
     RuntimeInfoResponse {
         protocol_version: u64,
@@ -73,6 +218,10 @@ pub enum Body {
     RuntimeShutdownRequest {},
     RuntimeAbortRequest {},
     RuntimeAbortResponse {},
+    RuntimeHealthRequest {},
+    RuntimeHealthResponse {
+        memory: MemoryStats,
+    },
     RuntimeCapabilityTEERakInitRequest {
         #[serde(with = "serde_bytes")]
         target_info: Vec<u8>,
@@ -120,9 +269,14 @@ pub enum Body {
         io_root: Hash,
         inputs: TxnBatch,
         block: Block,
+        #[serde(default, with = "serde_bytes")]
+        beacon: Vec<u8>,
     },
     RuntimeExecuteTxBatchResponse {
         batch: ComputedBatch,
+        /// State tree counters accumulated since the previous response.
+        #[serde(default)]
+        state_metrics: CacheMetrics,
     },
 
     // Host interface.
@@ -167,6 +321,73 @@ pub enum Body {
         value: Vec<u8>,
     },
     HostLocalStorageSetResponse {},
+    HostSpillStorageGetRequest {
+        #[serde(with = "serde_bytes")]
+        key: Vec<u8>,
+    },
+    HostSpillStorageGetResponse {
+        #[serde(with = "serde_bytes")]
+        value: Vec<u8>,
+    },
+    HostSpillStorageInsertRequest {
+        entries: Vec<SpillStorageEntry>,
+    },
+    HostSpillStorageInsertResponse {},
+    HostSpillStorageClearRequest {},
+    HostSpillStorageClearResponse {},
+    HostLogRequest {
+        records: Vec<LogRecord>,
+    },
+    HostLogResponse {},
+    HostPanicRequest {
+        report: PanicReport,
+    },
+    HostPanicResponse {},
+}
+
+/// An entry of the host's spill storage.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpillStorageEntry {
+    #[serde(with = "serde_bytes")]
+    pub key: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    pub value: Vec<u8>,
+}
+
+/// A log record forwarded to the worker host.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogRecord {
+    /// Level of the record (one of error, warn, info, debug and trace).
+    pub level: String,
+    /// Target of the record, usually the module path.
+    pub target: String,
+    /// Log message.
+    pub message: String,
+    /// Additional structured fields.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+}
+
+/// A panic of the runtime reported to the worker host.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PanicReport {
+    /// Panic message.
+    pub message: String,
+    /// Name of the panicking thread, if any.
+    #[serde(default)]
+    pub thread: String,
+    /// Source file of the panic.
+    #[serde(default)]
+    pub file: String,
+    /// Source line of the panic.
+    #[serde(default)]
+    pub line: u32,
+    /// Source column of the panic.
+    #[serde(default)]
+    pub column: u32,
+    /// Best-effort backtrace, empty if none could be captured.
+    #[serde(default)]
+    pub backtrace: String,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -215,3 +436,102 @@ pub struct Message {
     #[serde(with = "serde_bytes")]
     pub span_context: Vec<u8>,
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use self::test::{black_box, Bencher};
+    use super::*;
+    use crate::{common::cbor, protocol::decode_message};
+
+    fn execute_tx_batch_request(batch_size: usize) -> Message {
+        Message {
+            id: 42,
+            message_type: MessageType::Request,
+            body: Body::RuntimeExecuteTxBatchRequest {
+                io_root: Hash::empty_hash(),
+                inputs: TxnBatch::new(vec![vec![0u8; 256]; batch_size]),
+                block: Block::default(),
+                beacon: vec![0u8; 32],
+            },
+            span_context: vec![],
+        }
+    }
+
+    #[test]
+    fn test_error_code() {
+        let error: Error = anyhow::Error::from(ProtocolError::MethodNotSupported).into();
+        assert_eq!(
+            error,
+            Error::new("runtime/protocol", 2, "method not supported")
+        );
+
+        // Context does not hide the code of the underlying error.
+        let error: Error = anyhow::Error::from(ProtocolError::MethodNotSupported)
+            .context("request failed")
+            .into();
+        assert_eq!(error, Error::new("runtime/protocol", 2, "request failed"));
+
+        // Errors received from the worker host are passed through.
+        let error: Error = anyhow::Error::from(Error::new("storage", 3, "root not found")).into();
+        assert_eq!(error, Error::new("storage", 3, "root not found"));
+
+        let error: Error = anyhow!("something went wrong").into();
+        assert_eq!(
+            error,
+            Error::new(UNKNOWN_MODULE, CODE_UNKNOWN, "something went wrong")
+        );
+
+        // Errors of other crates keep their code once registered.
+        #[derive(Debug, thiserror::Error)]
+        #[error("custom error")]
+        struct CustomError;
+
+        impl ErrorCode for CustomError {
+            fn module_name(&self) -> &str {
+                "custom"
+            }
+
+            fn code(&self) -> u32 {
+                7
+            }
+        }
+
+        let error: Error = anyhow::Error::from(CustomError).into();
+        assert_eq!(
+            error,
+            Error::new(UNKNOWN_MODULE, CODE_UNKNOWN, "custom error")
+        );
+        register_error_code::<CustomError>();
+        register_error_code::<CustomError>();
+        let error: Error = anyhow::Error::from(CustomError).into();
+        assert_eq!(error, Error::new("custom", 7, "custom error"));
+    }
+
+    #[test]
+    fn test_error_encoding() {
+        // NOTE: This must be kept compatible with go/runtime/host/protocol/types.go.
+        let data = include_bytes!("../fuzz/corpus/fuzz-protocol-message/error_response");
+        let message = decode_message(&data[..]).unwrap();
+        match message.body {
+            Body::Error(ref error) => assert_eq!(error, &Error::new("test", 1, "error")),
+            ref body => panic!("unexpected body: {:?}", body),
+        }
+        assert_eq!(cbor::to_vec(&message), &data[4..]);
+    }
+
+    #[bench]
+    fn bench_encode_execute_tx_batch_request_100(b: &mut Bencher) {
+        let message = execute_tx_batch_request(100);
+
+        b.iter(|| black_box(cbor::to_vec(&message)));
+    }
+
+    #[bench]
+    fn bench_decode_execute_tx_batch_request_100(b: &mut Bencher) {
+        let encoded = cbor::to_vec(&execute_tx_batch_request(100));
+
+        b.iter(|| black_box(cbor::from_slice::<Message>(&encoded).unwrap()));
+    }
+}
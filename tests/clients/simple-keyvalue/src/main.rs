@@ -112,7 +112,8 @@ fn main() {
     println!("Accessing read-only state snapshot...");
     let r = snapshot
         .get(Context::background(), kv.key.as_bytes())
-        .expect("read-only state get");
+        .expect("read-only state get")
+        .expect("key should exist");
     println!(
         "Got \"{}\" ({:?})",
         String::from_utf8(r.clone()).unwrap(),
@@ -1,22 +1,18 @@
 use std::sync::Arc;
 
-use failure::{format_err, Fallible};
+use anyhow::{anyhow, Result};
 use io_context::Context as IoContext;
 
-use oasis_core_keymanager_client::{ContractId, KeyManagerClient};
+use oasis_core_keymanager_client::{
+    encryption::{EncryptionContext, KeyMode},
+    ContractId, KeyManagerClient,
+};
 use oasis_core_runtime::{
-    common::{
-        crypto::{
-            hash::Hash,
-            mrae::deoxysii::{DeoxysII, KEY_SIZE, NONCE_SIZE, TAG_SIZE},
-        },
-        runtime::RuntimeId,
-        version::Version,
-    },
+    common::{crypto::hash::Hash, runtime::RuntimeId, version::Version},
     executor::Executor,
     rak::RAK,
     register_runtime_txn_methods, runtime_context,
-    storage::{StorageContext, MKVS},
+    storage::StorageContext,
     transaction::{dispatcher::CheckOnlySuccess, Context as TxnContext},
     version_from_cargo, Protocol, RpcDemux, RpcDispatcher, TxnDispatcher, TxnMethDispatcher,
 };
@@ -29,16 +25,16 @@ struct Context {
 }
 
 /// Return previously set runtime ID of this runtime.
-fn get_runtime_id(_args: &(), ctx: &mut TxnContext) -> Fallible<Option<String>> {
+fn get_runtime_id(_args: &(), ctx: &mut TxnContext) -> Result<Option<String>> {
     let rctx = runtime_context!(ctx, Context);
 
     Ok(Some(rctx.test_runtime_id.to_string()))
 }
 
 /// Insert a key/value pair.
-fn insert(args: &KeyValue, ctx: &mut TxnContext) -> Fallible<Option<String>> {
+fn insert(args: &KeyValue, ctx: &mut TxnContext) -> Result<Option<String>> {
     if args.value.as_bytes().len() > 128 {
-        return Err(format_err!("Value too big to be inserted."));
+        return Err(anyhow!("Value too big to be inserted."));
     }
     if ctx.check_only {
         return Err(CheckOnlySuccess::default().into());
@@ -52,12 +48,12 @@ fn insert(args: &KeyValue, ctx: &mut TxnContext) -> Fallible<Option<String>> {
             args.key.as_bytes(),
             args.value.as_bytes(),
         )
-    });
+    })?;
     Ok(existing.map(|v| String::from_utf8(v)).transpose()?)
 }
 
 /// Retrieve a key/value pair.
-fn get(args: &String, ctx: &mut TxnContext) -> Fallible<Option<String>> {
+fn get(args: &String, ctx: &mut TxnContext) -> Result<Option<String>> {
     if ctx.check_only {
         return Err(CheckOnlySuccess::default().into());
     }
@@ -66,12 +62,12 @@ fn get(args: &String, ctx: &mut TxnContext) -> Fallible<Option<String>> {
 
     let existing = StorageContext::with_current(|mkvs, _untrusted_local| {
         mkvs.get(IoContext::create_child(&ctx.io_ctx), args.as_bytes())
-    });
+    })?;
     Ok(existing.map(|v| String::from_utf8(v)).transpose()?)
 }
 
 /// Remove a key/value pair.
-fn remove(args: &String, ctx: &mut TxnContext) -> Fallible<Option<String>> {
+fn remove(args: &String, ctx: &mut TxnContext) -> Result<Option<String>> {
     if ctx.check_only {
         return Err(CheckOnlySuccess::default().into());
     }
@@ -80,12 +76,12 @@ fn remove(args: &String, ctx: &mut TxnContext) -> Fallible<Option<String>> {
 
     let existing = StorageContext::with_current(|mkvs, _untrusted_local| {
         mkvs.remove(IoContext::create_child(&ctx.io_ctx), args.as_bytes())
-    });
+    })?;
     Ok(existing.map(|v| String::from_utf8(v)).transpose()?)
 }
 
 /// Helper for doing encrypted MKVS operations.
-fn get_encryption_context(ctx: &mut TxnContext, key: &[u8]) -> Fallible<EncryptionContext> {
+fn get_encryption_context(ctx: &mut TxnContext, key: &[u8]) -> Result<EncryptionContext> {
     let rctx = runtime_context!(ctx, Context);
 
     // Derive contract ID based on key.
@@ -93,18 +89,14 @@ fn get_encryption_context(ctx: &mut TxnContext, key: &[u8]) -> Fallible<Encrypti
 
     // Fetch encryption keys.
     let io_ctx = IoContext::create_child(&ctx.io_ctx);
-    let result = rctx.km_client.get_or_create_keys(io_ctx, contract_id);
-    let key = Executor::with_current(|executor| executor.block_on(result))?;
+    let result =
+        EncryptionContext::for_contract(&*rctx.km_client, io_ctx, contract_id, KeyMode::Encrypted);
 
-    Ok(EncryptionContext::new(key.state_key.as_ref()))
+    Executor::with_current(|executor| executor.block_on(result))
 }
 
 /// (encrypted) Insert a key/value pair.
-fn enc_insert(args: &KeyValue, ctx: &mut TxnContext) -> Fallible<Option<String>> {
-    // NOTE: This is only for example purposes, the correct way would be
-    //       to also generate a (deterministic) nonce.
-    let nonce = [0u8; NONCE_SIZE];
-
+fn enc_insert(args: &KeyValue, ctx: &mut TxnContext) -> Result<Option<String>> {
     let enc_ctx = get_encryption_context(ctx, args.key.as_bytes())?;
     let existing = StorageContext::with_current(|mkvs, _untrusted_local| {
         enc_ctx.insert(
@@ -112,130 +104,29 @@ fn enc_insert(args: &KeyValue, ctx: &mut TxnContext) -> Fallible<Option<String>>
             IoContext::create_child(&ctx.io_ctx),
             args.key.as_bytes(),
             args.value.as_bytes(),
-            &nonce,
         )
-    });
+    })?;
     Ok(existing.map(|v| String::from_utf8(v)).transpose()?)
 }
 
 /// (encrypted) Retrieve a key/value pair.
-fn enc_get(args: &String, ctx: &mut TxnContext) -> Fallible<Option<String>> {
+fn enc_get(args: &String, ctx: &mut TxnContext) -> Result<Option<String>> {
     let enc_ctx = get_encryption_context(ctx, args.as_bytes())?;
     let existing = StorageContext::with_current(|mkvs, _untrusted_local| {
         enc_ctx.get(mkvs, IoContext::create_child(&ctx.io_ctx), args.as_bytes())
-    });
+    })?;
     Ok(existing.map(|v| String::from_utf8(v)).transpose()?)
 }
 
 /// (encrypted) Remove a key/value pair.
-fn enc_remove(args: &String, ctx: &mut TxnContext) -> Fallible<Option<String>> {
+fn enc_remove(args: &String, ctx: &mut TxnContext) -> Result<Option<String>> {
     let enc_ctx = get_encryption_context(ctx, args.as_bytes())?;
     let existing = StorageContext::with_current(|mkvs, _untrusted_local| {
         enc_ctx.remove(mkvs, IoContext::create_child(&ctx.io_ctx), args.as_bytes())
-    });
+    })?;
     Ok(existing.map(|v| String::from_utf8(v)).transpose()?)
 }
 
-/// A keyed storage encryption context, for use with a MKVS instance.
-struct EncryptionContext {
-    d2: DeoxysII,
-}
-
-impl EncryptionContext {
-    /// Initialize a new EncryptionContext with the given MRAE key.
-    pub fn new(key: &[u8]) -> Self {
-        if key.len() != KEY_SIZE {
-            panic!("mkvs: invalid encryption key size {}", key.len());
-        }
-        let mut raw_key = [0u8; KEY_SIZE];
-        raw_key.copy_from_slice(&key[..KEY_SIZE]);
-
-        let d2 = DeoxysII::new(&raw_key);
-        //raw_key.zeroize();
-
-        Self { d2 }
-    }
-
-    /// Get encrypted MKVS entry.
-    pub fn get(&self, mkvs: &dyn MKVS, ctx: IoContext, key: &[u8]) -> Option<Vec<u8>> {
-        let key = self.derive_encrypted_key(key);
-        let ciphertext = match mkvs.get(ctx, &key) {
-            Some(ciphertext) => ciphertext,
-            None => return None,
-        };
-
-        self.open(&ciphertext)
-    }
-
-    /// Insert encrypted MKVS entry.
-    pub fn insert(
-        &self,
-        mkvs: &mut dyn MKVS,
-        ctx: IoContext,
-        key: &[u8],
-        value: &[u8],
-        nonce: &[u8],
-    ) -> Option<Vec<u8>> {
-        let nonce = Self::derive_nonce(&nonce);
-        let mut ciphertext = self.d2.seal(&nonce, value.to_vec(), vec![]);
-        ciphertext.extend_from_slice(&nonce);
-
-        let key = self.derive_encrypted_key(key);
-        let ciphertext = match mkvs.insert(ctx, &key, &ciphertext) {
-            Some(ciphertext) => ciphertext,
-            None => return None,
-        };
-
-        self.open(&ciphertext)
-    }
-
-    /// Remove encrypted MKVS entry.
-    pub fn remove(&self, mkvs: &mut dyn MKVS, ctx: IoContext, key: &[u8]) -> Option<Vec<u8>> {
-        let key = self.derive_encrypted_key(key);
-        let ciphertext = match mkvs.remove(ctx, &key) {
-            Some(ciphertext) => ciphertext,
-            None => return None,
-        };
-
-        self.open(&ciphertext)
-    }
-
-    fn open(&self, ciphertext: &[u8]) -> Option<Vec<u8>> {
-        // ciphertext || tag || nonce.
-        if ciphertext.len() < TAG_SIZE + NONCE_SIZE {
-            return None;
-        }
-
-        let nonce_offset = ciphertext.len() - NONCE_SIZE;
-        let mut nonce = [0u8; NONCE_SIZE];
-        nonce.copy_from_slice(&ciphertext[nonce_offset..]);
-        let ciphertext = &ciphertext[..nonce_offset];
-
-        let plaintext = self.d2.open(&nonce, ciphertext.to_vec(), vec![]);
-        plaintext.ok()
-    }
-
-    fn derive_encrypted_key(&self, key: &[u8]) -> Vec<u8> {
-        // XXX: The plan is eventually to use a lighter weight transform
-        // for the key instead of a full fledged MRAE algorithm.  For now
-        // approximate it with a Deoxys-II call with an all 0 nonce.
-
-        let nonce = [0u8; NONCE_SIZE];
-        self.d2.seal(&nonce, key.to_vec(), vec![])
-    }
-
-    fn derive_nonce(nonce: &[u8]) -> [u8; NONCE_SIZE] {
-        // Just a copy for type safety.
-        let mut n = [0u8; NONCE_SIZE];
-        if nonce.len() != NONCE_SIZE {
-            panic!("invalid nonce size: {}", nonce.len());
-        }
-        n.copy_from_slice(nonce);
-
-        n
-    }
-}
-
 fn main() {
     // Initializer.
     let init = |protocol: &Arc<Protocol>,